@@ -146,7 +146,8 @@ pub extern "C" fn Java_com_jarxconcepts_abetterworld_Renderer_nativeInitRenderer
     let mut limits = wgpu::Limits::downlevel_defaults();
     let (device, queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
         label: Some("Android Device"),
-        required_features: wgpu::Features::empty(),
+        // stencil for draped vectors, where the adapter has it with float depth
+        required_features: adapter.features() & wgpu::Features::DEPTH32FLOAT_STENCIL8,
         required_limits: limits.using_resolution(adapter.limits()),
         memory_hints: wgpu::MemoryHints::Performance,
         trace: wgpu::Trace::Off,
@@ -230,7 +231,13 @@ pub extern "C" fn Java_com_jarxconcepts_abetterworld_Renderer_nativeInitRenderer
         event!(Level::ERROR, "surface.configure validation error: {err}");
     }
 
-    let abw = World::new(&device, &config, format, &get_debug_config());
+    let abw = World::new(
+        Some(&adapter),
+        &device,
+        &config,
+        format,
+        &get_debug_config(),
+    );
 
     state.gfx = Some(GfxState {
         _instance: instance,
@@ -263,6 +270,7 @@ pub extern "C" fn Java_com_jarxconcepts_abetterworld_Renderer_nativeResize(
     g.config.width = width as u32;
     g.config.height = height as u32;
     g.surface.configure(&g.device, &g.config);
+    g.abw.resize(&g.device, g.config.width, g.config.height);
 }
 
 #[no_mangle]
@@ -306,20 +314,15 @@ pub extern "C" fn Java_com_jarxconcepts_abetterworld_Renderer_nativeRender(
     {
         let mut rp = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("clear"),
-            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: &view,
-                resolve_target: None,
-                ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(wgpu::Color {
-                        r: 0.1,
-                        g: 0.2,
-                        b: 0.3,
-                        a: 1.0,
-                    }),
-                    store: wgpu::StoreOp::Store,
-                },
-                depth_slice: None,
-            })],
+            color_attachments: &[Some(g.abw.get_color_attachment(
+                &view,
+                wgpu::LoadOp::Clear(wgpu::Color {
+                    r: 0.1,
+                    g: 0.2,
+                    b: 0.3,
+                    a: 1.0,
+                }),
+            ))],
             depth_stencil_attachment: Some(g.abw.get_depth_attachment()),
 
             occlusion_query_set: None,
@@ -114,7 +114,8 @@ pub extern "C" fn abetterworld_ios_init(
     // Create the device and queue
     let (device, queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
         label: Some("iOS Metal Device"),
-        required_features: wgpu::Features::empty(),
+        // stencil for draped vectors, where the adapter has it with float depth
+        required_features: adapter.features() & wgpu::Features::DEPTH32FLOAT_STENCIL8,
         required_limits: wgpu::Limits::default(),
         memory_hints: wgpu::MemoryHints::default(),
         trace: wgpu::Trace::Off,
@@ -164,7 +165,13 @@ pub extern "C" fn abetterworld_ios_init(
             .unwrap()
             .to_string()
     };
-    let abw = World::new(&device, &config, texture_format, &debug_config);
+    let abw = World::new(
+        Some(&adapter),
+        &device,
+        &config,
+        texture_format,
+        &debug_config,
+    );
 
     state.inner = Some(StateInner {
         device,
@@ -293,20 +300,15 @@ pub extern "C" fn abetterworld_ios_render(ptr: *mut ABetterWorldiOS) {
     {
         let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("World Render Pass"),
-            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: &state.texture_view,
-                resolve_target: None,
-                ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(wgpu::Color {
-                        r: 0.1,
-                        g: 0.2,
-                        b: 0.3,
-                        a: 1.0,
-                    }),
-                    store: wgpu::StoreOp::Store,
-                },
-                depth_slice: None,
-            })],
+            color_attachments: &[Some(state.abw.get_color_attachment(
+                &state.texture_view,
+                wgpu::LoadOp::Clear(wgpu::Color {
+                    r: 0.1,
+                    g: 0.2,
+                    b: 0.3,
+                    a: 1.0,
+                }),
+            ))],
 
             depth_stencil_attachment: Some(state.abw.get_depth_attachment()),
 
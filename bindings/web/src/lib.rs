@@ -105,7 +105,8 @@ impl<'window> State<'window> {
 
         let (device, queue) = adapter
             .request_device(&wgpu::DeviceDescriptor {
-                required_features: wgpu::Features::empty(),
+                // stencil for draped vectors, where the adapter has it with float depth
+                required_features: adapter.features() & wgpu::Features::DEPTH32FLOAT_STENCIL8,
                 required_limits: wgpu::Limits::default(),
                 label: None,
                 memory_hints: Default::default(),
@@ -141,7 +142,13 @@ impl<'window> State<'window> {
 
         surface.configure(&device, &config);
 
-        let world = World::new(&device, &config, surface_format, &get_debug_config());
+        let world = World::new(
+            Some(&adapter),
+            &device,
+            &config,
+            surface_format,
+            &get_debug_config(),
+        );
 
         Self {
             surface,
@@ -202,20 +209,15 @@ impl<'window> State<'window> {
         {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.01,
-                            g: 0.01,
-                            b: 0.01,
-                            a: 1.0,
-                        }),
-                        store: wgpu::StoreOp::Store,
-                    },
-                    depth_slice: None,
-                })],
+                color_attachments: &[Some(self.world.get_color_attachment(
+                    &view,
+                    wgpu::LoadOp::Clear(wgpu::Color {
+                        r: 0.01,
+                        g: 0.01,
+                        b: 0.01,
+                        a: 1.0,
+                    }),
+                ))],
                 depth_stencil_attachment: Some(self.world.get_depth_attachment()),
 
                 timestamp_writes: None,
@@ -0,0 +1,196 @@
+// Lighting and the atmosphere model, shared by shader.wgsl and sky.wgsl,
+// which are each appended to this file. Constants mirror render/atmosphere.rs.
+
+struct Lighting {
+  // ECEF unit vector toward the sun
+  sun_direction: vec4<f32>,
+  sun_radiance: vec4<f32>,
+  ambient: vec4<f32>,
+  // ECEF camera position; positions here are relative to it
+  eye: vec4<f32>,
+  // Clip space back to eye relative positions
+  inverse_view_proj: mat4x4<f32>,
+  flags: u32,
+};
+
+// Lighting::flags bits
+const LIGHTING_AERIAL_PERSPECTIVE: u32 = 1u;
+
+@group(0) @binding(2) var<uniform> uLighting : Lighting;
+@group(0) @binding(3) var transmittance_lut: texture_2d<f32>;
+@group(0) @binding(4) var multi_scattering_lut: texture_2d<f32>;
+@group(0) @binding(5) var lut_sampler: sampler;
+
+const PI: f32 = 3.14159265;
+
+const BOTTOM_RADIUS: f32 = 6378137.0;
+const TOP_RADIUS: f32 = 6478137.0;
+// WGS84_A / WGS84_B: scaling z by this turns the ellipsoid into a sphere
+const POLAR_SCALE: f32 = 1.0033640898;
+
+const RAYLEIGH_SCATTERING: vec3<f32> = vec3<f32>(5.802e-6, 13.558e-6, 33.1e-6);
+const RAYLEIGH_SCALE_HEIGHT: f32 = 8000.0;
+const MIE_SCATTERING: f32 = 3.996e-6;
+const MIE_EXTINCTION: f32 = 4.40e-6;
+const MIE_SCALE_HEIGHT: f32 = 1200.0;
+const MIE_G: f32 = 0.8;
+const OZONE_ABSORPTION: vec3<f32> = vec3<f32>(0.650e-6, 1.881e-6, 0.085e-6);
+const OZONE_CENTER: f32 = 25000.0;
+const OZONE_HALF_WIDTH: f32 = 15000.0;
+const GROUND_ALBEDO: f32 = 0.3;
+
+// Sky and haze brightness against the tile shading, which has no exposure
+// of its own
+const ATMOSPHERE_EXPOSURE: f32 = 5.0;
+
+// ECEF to the spherical atmosphere's frame
+fn to_atmosphere(p: vec3<f32>) -> vec3<f32> {
+  return vec3<f32>(p.xy, p.z * POLAR_SCALE);
+}
+
+fn sun_in_atmosphere() -> vec3<f32> {
+  return normalize(to_atmosphere(uLighting.sun_direction.xyz));
+}
+
+struct Medium {
+  rayleigh: vec3<f32>,
+  mie: f32,
+  extinction: vec3<f32>,
+};
+
+fn medium(altitude: f32) -> Medium {
+  var m: Medium;
+  m.rayleigh = RAYLEIGH_SCATTERING * exp(-altitude / RAYLEIGH_SCALE_HEIGHT);
+  let mie_density = exp(-altitude / MIE_SCALE_HEIGHT);
+  m.mie = MIE_SCATTERING * mie_density;
+  let ozone = max(1.0 - abs(altitude - OZONE_CENTER) / OZONE_HALF_WIDTH, 0.0);
+  m.extinction = m.rayleigh + vec3<f32>(MIE_EXTINCTION * mie_density) + OZONE_ABSORPTION * ozone;
+  return m;
+}
+
+fn rayleigh_phase(c: f32) -> f32 {
+  return 3.0 / (16.0 * PI) * (1.0 + c * c);
+}
+
+// Cornette-Shanks
+fn mie_phase(c: f32) -> f32 {
+  let g2 = MIE_G * MIE_G;
+  let k = 3.0 / (8.0 * PI) * (1.0 - g2) / (2.0 + g2);
+  return k * (1.0 + c * c) / pow(1.0 + g2 - 2.0 * MIE_G * c, 1.5);
+}
+
+// Nearest and farthest distances along a ray to a sphere at the origin;
+// x > y when it misses
+fn ray_sphere(origin: vec3<f32>, dir: vec3<f32>, radius: f32) -> vec2<f32> {
+  let b = dot(origin, dir);
+  let c = dot(origin, origin) - radius * radius;
+  let disc = b * b - c;
+  if (disc < 0.0) {
+    return vec2<f32>(1.0, -1.0);
+  }
+  let s = sqrt(disc);
+  return vec2<f32>(-b - s, -b + s);
+}
+
+fn ray_hits_ground(r: f32, mu: f32) -> bool {
+  return mu < 0.0 && r * r * (mu * mu - 1.0) + BOTTOM_RADIUS * BOTTOM_RADIUS >= 0.0;
+}
+
+fn transmittance_uv(r: f32, mu: f32) -> vec2<f32> {
+  let h = sqrt(TOP_RADIUS * TOP_RADIUS - BOTTOM_RADIUS * BOTTOM_RADIUS);
+  let rho = sqrt(max(r * r - BOTTOM_RADIUS * BOTTOM_RADIUS, 0.0));
+  let disc = r * r * (mu * mu - 1.0) + TOP_RADIUS * TOP_RADIUS;
+  let d = max(-r * mu + sqrt(max(disc, 0.0)), 0.0);
+  let d_min = TOP_RADIUS - r;
+  let d_max = rho + h;
+  return vec2<f32>((d - d_min) / (d_max - d_min), rho / h);
+}
+
+// Transmittance from radius r along zenith cosine mu to space
+fn transmittance_to_top(r: f32, mu: f32) -> vec3<f32> {
+  let uv = transmittance_uv(clamp(r, BOTTOM_RADIUS, TOP_RADIUS), mu);
+  return textureSampleLevel(transmittance_lut, lut_sampler, uv, 0.0).rgb;
+}
+
+fn multi_scattering(r: f32, mu_s: f32) -> vec3<f32> {
+  let uv = vec2<f32>(
+    mu_s * 0.5 + 0.5,
+    clamp((r - BOTTOM_RADIUS) / (TOP_RADIUS - BOTTOM_RADIUS), 0.0, 1.0),
+  );
+  return textureSampleLevel(multi_scattering_lut, lut_sampler, uv, 0.0).rgb;
+}
+
+// Sunlight reaching a point, zero in the planet's shadow
+fn sun_transmittance(p: vec3<f32>, sun: vec3<f32>) -> vec3<f32> {
+  let r = length(p);
+  let mu_s = dot(p, sun) / r;
+  return select(transmittance_to_top(r, mu_s), vec3<f32>(0.0), ray_hits_ground(r, mu_s));
+}
+
+struct Scattering {
+  // Light scattered toward the origin, for a sun of unit illuminance
+  inscattered: vec3<f32>,
+  transmittance: vec3<f32>,
+};
+
+// Single scattering plus the multiple scattering LUT along origin + dir * t
+// for t in [t0, t1], in the atmosphere's frame.
+fn integrate_scattering(
+  origin: vec3<f32>,
+  dir: vec3<f32>,
+  t0: f32,
+  t1: f32,
+  steps: u32,
+) -> Scattering {
+  let sun = sun_in_atmosphere();
+  let c = dot(dir, sun);
+  let phase_r = rayleigh_phase(c);
+  let phase_m = mie_phase(c);
+  let dt = (t1 - t0) / f32(steps);
+
+  var out: Scattering;
+  out.inscattered = vec3<f32>(0.0);
+  out.transmittance = vec3<f32>(1.0);
+  for (var i = 0u; i < steps; i = i + 1u) {
+    let p = origin + dir * (t0 + (f32(i) + 0.5) * dt);
+    let r = length(p);
+    let m = medium(r - BOTTOM_RADIUS);
+    let step_transmittance = exp(-m.extinction * dt);
+    let scattering = m.rayleigh + vec3<f32>(m.mie);
+
+    let single = sun_transmittance(p, sun) * (m.rayleigh * phase_r + vec3<f32>(m.mie * phase_m));
+    let multiple = multi_scattering(r, dot(p, sun) / r) * scattering;
+    let s = single + multiple;
+
+    // Energy conserving integration over the step
+    let extinction = max(m.extinction, vec3<f32>(1e-20));
+    out.inscattered = out.inscattered + out.transmittance * (s - s * step_transmittance) / extinction;
+    out.transmittance = out.transmittance * step_transmittance;
+  }
+  return out;
+}
+
+// Sun illuminance the atmosphere is lit with
+fn atmosphere_illuminance() -> vec3<f32> {
+  return uLighting.sun_radiance.rgb * ATMOSPHERE_EXPOSURE;
+}
+
+// Fogs a tile fragment at eye relative `world` by the air between it and
+// the camera.
+fn aerial_perspective(color: vec3<f32>, world: vec3<f32>) -> vec3<f32> {
+  if ((uLighting.flags & LIGHTING_AERIAL_PERSPECTIVE) == 0u) {
+    return color;
+  }
+  let origin = to_atmosphere(uLighting.eye.xyz);
+  let surface = to_atmosphere(uLighting.eye.xyz + world);
+  let distance = length(surface - origin);
+  let dir = (surface - origin) / max(distance, 1e-3);
+  let top = ray_sphere(origin, dir, TOP_RADIUS);
+  let t0 = max(top.x, 0.0);
+  let t1 = min(top.y, distance);
+  if (top.x > top.y || t1 <= t0) {
+    return color;
+  }
+  let s = integrate_scattering(origin, dir, t0, t1, 8u);
+  return color * s.transmittance + s.inscattered * atmosphere_illuminance();
+}
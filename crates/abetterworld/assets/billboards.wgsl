@@ -0,0 +1,127 @@
+// Billboards: icons, points and text labels facing the screen, sized in
+// pixels. See render/billboards.rs.
+
+struct Camera {
+  viewProj: mat4x4<f32>,
+};
+
+// Mirrors BillboardInstance in render/billboards.rs
+struct Instance {
+  // relative to the eye
+  position: vec3<f32>,
+  scale: f32,
+  flags: u32,
+  _pad0: u32,
+  _pad1: u32,
+  _pad2: u32,
+};
+
+struct InstanceBuffer {
+  data: array<Instance>,
+};
+
+// Mirrors BillboardUniform in render/billboards.rs
+struct Billboards {
+  viewport: vec2<f32>,
+  _pad: vec2<f32>,
+};
+
+@group(0) @binding(0) var<uniform> uCamera : Camera;
+@group(0) @binding(1) var<storage, read> Instances : InstanceBuffer;
+@group(0) @binding(2) var<uniform> uBillboards : Billboards;
+@group(0) @binding(3) var tGlyphs : texture_2d<f32>;
+@group(0) @binding(4) var tIcons : texture_2d<f32>;
+@group(0) @binding(5) var sAtlas : sampler;
+
+// Match KIND_* and the flags in render/billboards.rs
+const KIND_GLYPH: u32 = 0u;
+const KIND_IMAGE: u32 = 1u;
+const KIND_POINT: u32 = 2u;
+const HIDDEN: u32 = 1u;
+const LABEL_HIDDEN: u32 = 2u;
+
+// Pulls billboards a little toward the eye, as a fraction of their
+// distance, so they aren't buried in the ground they stand on
+const DEPTH_NUDGE: f32 = 1e-3;
+
+struct VSIn {
+  // pixels from the anchor, y down, scaled by distance
+  @location(0) offset: vec2<f32>,
+  // pixels, not scaled
+  @location(1) shift: vec2<f32>,
+  // atlas texels, or radii from the middle of a point
+  @location(2) uv: vec2<f32>,
+  @location(3) color: vec4<f32>,
+  @location(4) outline_color: vec4<f32>,
+  // x pixels per unit of distance field for glyphs, the radius for points;
+  // y outline width in pixels
+  @location(5) style: vec2<f32>,
+  @location(6) kind: u32,
+  @location(7) billboard: u32,
+};
+
+struct VSOut {
+  @builtin(position) pos: vec4<f32>,
+  @location(0) uv: vec2<f32>,
+  @location(1) color: vec4<f32>,
+  @location(2) outline_color: vec4<f32>,
+  // style, scaled by distance
+  @location(3) style: vec2<f32>,
+  @location(4) @interpolate(flat) kind: u32,
+};
+
+fn srgb_to_linear(c: vec4<f32>) -> vec4<f32> {
+  let low = c.rgb / 12.92;
+  let high = pow((c.rgb + 0.055) / 1.055, vec3<f32>(2.4));
+  return vec4<f32>(select(high, low, c.rgb <= vec3<f32>(0.04045)), c.a);
+}
+
+@vertex
+fn vs_billboard(in: VSIn) -> VSOut {
+  var out: VSOut;
+  let inst = Instances.data[in.billboard];
+  var clip = uCamera.viewProj * vec4<f32>(inst.position, 1.0);
+  let hidden = (inst.flags & HIDDEN) != 0u
+    || (in.kind == KIND_GLYPH && (inst.flags & LABEL_HIDDEN) != 0u);
+  if (hidden || clip.w <= 0.0) {
+    // every corner the same, so nothing is drawn
+    out.pos = vec4<f32>(0.0, 0.0, -1.0, 1.0);
+    return out;
+  }
+
+  let pixels = in.offset * inst.scale + in.shift;
+  let ndc = vec2<f32>(pixels.x, -pixels.y) * 2.0 / uBillboards.viewport;
+  clip = vec4<f32>(clip.xy + ndc * clip.w, min(clip.z * (1.0 + DEPTH_NUDGE), clip.w), clip.w);
+
+  out.pos = clip;
+  out.uv = in.uv;
+  out.color = srgb_to_linear(in.color);
+  out.outline_color = srgb_to_linear(in.outline_color);
+  out.style = in.style * inst.scale;
+  out.kind = in.kind;
+  return out;
+}
+
+@fragment
+fn fs_billboard(in: VSOut) -> @location(0) vec4<f32> {
+  if (in.kind == KIND_IMAGE) {
+    let size = vec2<f32>(textureDimensions(tIcons));
+    return textureSampleLevel(tIcons, sAtlas, in.uv / size, 0.0) * in.color;
+  }
+
+  // pixels inside the fill's edge, negative outside it
+  var inside: f32;
+  if (in.kind == KIND_GLYPH) {
+    let size = vec2<f32>(textureDimensions(tGlyphs));
+    let field = textureSampleLevel(tGlyphs, sAtlas, in.uv / size, 0.0).r;
+    inside = (field * 255.0 - 128.0) / 127.0 * in.style.x;
+  } else {
+    inside = (1.0 - length(in.uv)) * in.style.x;
+  }
+
+  let outline = in.style.y;
+  let coverage = clamp(inside + outline + 0.5, 0.0, 1.0);
+  let fill = clamp(inside + 0.5, 0.0, 1.0);
+  let color = select(mix(in.outline_color, in.color, fill), in.color, outline <= 0.0);
+  return vec4<f32>(color.rgb, color.a * coverage);
+}
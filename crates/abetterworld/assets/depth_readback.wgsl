@@ -0,0 +1,18 @@
+// Copies the depth buffer into a color target so it can be read back, as
+// depth textures can't be copied to buffers everywhere. The target is R32Uint
+// holding the float's bits, since R32Float isn't renderable everywhere.
+// Depth is bound as an unfilterable float texture, which GLSL can load from.
+
+@group(0) @binding(0) var depth_texture: texture_2d<f32>;
+
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+  let ndc = vec2<f32>(f32((index << 1u) & 2u) * 2.0 - 1.0, f32(index & 2u) * 2.0 - 1.0);
+  return vec4<f32>(ndc, 0.0, 1.0);
+}
+
+@fragment
+fn fs_depth(@builtin(position) pos: vec4<f32>) -> @location(0) vec4<u32> {
+  let depth = textureLoad(depth_texture, vec2<i32>(pos.xy), 0).r;
+  return vec4<u32>(bitcast<u32>(depth), 0u, 0u, 0u);
+}
@@ -0,0 +1,26 @@
+// Renders one mip level from the level above it. The bilinear sample at the
+// center of each destination texel is a 2x2 box filter of the source, done
+// in linear light when the views are sRGB.
+
+struct VsOut {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@group(0) @binding(0) var src_texture: texture_2d<f32>;
+@group(0) @binding(1) var src_sampler: sampler;
+
+// Single triangle covering the viewport
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VsOut {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VsOut;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VsOut) -> @location(0) vec4<f32> {
+    return textureSample(src_texture, src_sampler, in.uv);
+}
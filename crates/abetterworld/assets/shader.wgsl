@@ -1,3 +1,5 @@
+// Tile shader, appended to atmosphere.wgsl for the lighting uniform.
+
 struct Camera {
   viewProj: mat4x4<f32>,
 };
@@ -15,33 +17,106 @@ struct InstanceBuffer {
 @group(0) @binding(0) var<uniform> uCamera : Camera;
 @group(0) @binding(1) var<storage, read> Instances : InstanceBuffer;
 
-@group(1) @binding(0) var my_texture: texture_2d<f32>;
-@group(1) @binding(1) var my_sampler: sampler;
+// Cascaded sun shadows; mirrors ShadowUniform in render/shadows.rs
+struct Shadows {
+  view_proj: array<mat4x4<f32>, 4>,
+  forward: vec4<f32>,
+  splits: vec4<f32>,
+  texel_meters: vec4<f32>,
+  cascade_count: u32,
+  texel_uv: f32,
+};
 
-struct VSIn {
-  @location(0) position : vec3<f32>,
-  @location(1) normal   : vec3<f32>,
-  @location(2) color    : vec4<f32>,
-  @location(3) tex0     : vec2<f32>,
-  @location(4) tex1     : vec2<f32>,
-  @builtin(instance_index) iid : u32,
+@group(0) @binding(6) var shadow_maps: texture_depth_2d_array;
+@group(0) @binding(7) var shadow_sampler: sampler_comparison;
+@group(0) @binding(8) var<uniform> uShadows : Shadows;
+
+struct TextureSlot {
+  // Rows of the UV transform (KHR_texture_transform), xyz used
+  row0: vec4<f32>,
+  row1: vec4<f32>,
+};
+
+// MaterialUniform::flags bits
+const MATERIAL_MASK: u32 = 1u;
+const MATERIAL_BLEND: u32 = 2u;
+const MATERIAL_DOUBLE_SIDED: u32 = 4u;
+const MATERIAL_UNLIT: u32 = 8u;
+const MATERIAL_NORMAL_MAP: u32 = 16u;
+
+// Texture slots, in MaterialUniform::uv_transforms order
+const SLOT_BASE_COLOR: u32 = 0u;
+const SLOT_METALLIC_ROUGHNESS: u32 = 1u;
+const SLOT_NORMAL: u32 = 2u;
+const SLOT_OCCLUSION: u32 = 3u;
+const SLOT_EMISSIVE: u32 = 4u;
+
+struct Material {
+  base_color_factor: vec4<f32>,
+  emissive_factor: vec4<f32>,
+  metallic_factor: f32,
+  roughness_factor: f32,
+  normal_scale: f32,
+  occlusion_strength: f32,
+  alpha_cutoff: f32,
+  flags: u32,
+  // Bit per slot, set when it samples TEXCOORD_1
+  tex_coords: u32,
+  _pad: u32,
+  uv: array<TextureSlot, 5>,
 };
 
+@group(1) @binding(0) var base_color_texture: texture_2d<f32>;
+@group(1) @binding(1) var material_sampler: sampler;
+@group(1) @binding(2) var<uniform> uMaterial : Material;
+@group(1) @binding(3) var metallic_roughness_texture: texture_2d<f32>;
+@group(1) @binding(4) var normal_texture: texture_2d<f32>;
+@group(1) @binding(5) var occlusion_texture: texture_2d<f32>;
+@group(1) @binding(6) var emissive_texture: texture_2d<f32>;
+
+fn texture_uv(tex0: vec2<f32>, tex1: vec2<f32>, slot: u32) -> vec2<f32> {
+  let t = uMaterial.uv[slot];
+  let uv = vec3<f32>(select(tex0, tex1, ((uMaterial.tex_coords >> slot) & 1u) == 1u), 1.0);
+  return vec2<f32>(dot(t.row0.xyz, uv), dot(t.row1.xyz, uv));
+}
+
 struct VSOut {
   @builtin(position) pos : vec4<f32>,
   @location(0) color : vec4<f32>,
   @location(1) normal: vec3<f32>,
   @location(2) tex0  : vec2<f32>,
   @location(3) tex1  : vec2<f32>,
+  // Eye relative, for view vectors and derivative based frames
+  @location(4) world : vec3<f32>,
+  // 1 when the normal came with the mesh, 0 to use flat normals
+  @location(5) @interpolate(flat) has_normal : f32,
 };
 
-@vertex
-fn vs_main(in: VSIn) -> VSOut {
+// Octahedral normal decode, matching octahedral_encode on the Rust side
+fn oct_decode(e: vec2<f32>) -> vec3<f32> {
+  var n = vec3<f32>(e.x, e.y, 1.0 - abs(e.x) - abs(e.y));
+  let t = max(-n.z, 0.0);
+  n.x = n.x + select(t, -t, n.x >= 0.0);
+  n.y = n.y + select(t, -t, n.y >= 0.0);
+  return normalize(n);
+}
+
+// Positions are Snorm16 around the mesh center; the instance transform
+// carries the dequantization along with the node transform. The position's
+// w says whether the mesh has normals.
+fn shade_vertex(
+  iid: u32,
+  position: vec4<f32>,
+  normal: vec2<f32>,
+  color: vec4<f32>,
+  tex0: vec2<f32>,
+  tex1: vec2<f32>,
+) -> VSOut {
   var out: VSOut;
 
-  let inst = Instances.data[in.iid];
+  let inst = Instances.data[iid];
 
-  let v = vec4<f32>(in.position, 1.0);
+  let v = vec4<f32>(position.xyz, 1.0);
   let world = vec3<f32>(
     dot(inst.r0, v),
     dot(inst.r1, v),
@@ -49,27 +124,264 @@ fn vs_main(in: VSIn) -> VSOut {
   );
 
   out.pos = uCamera.viewProj * vec4<f32>(world, 1.0);
+  out.world = world;
+  out.has_normal = position.w;
 
+  let local_normal = oct_decode(normal);
   let n = vec3<f32>(
-    dot(inst.r0.xyz, in.normal),
-    dot(inst.r1.xyz, in.normal),
-    dot(inst.r2.xyz, in.normal),
+    dot(inst.r0.xyz, local_normal),
+    dot(inst.r1.xyz, local_normal),
+    dot(inst.r2.xyz, local_normal),
   );
   out.normal = normalize(n);
 
-  out.color = in.color;
-  out.tex0  = in.tex0;
-  out.tex1  = in.tex1;
+  out.color = color;
+  out.tex0  = tex0;
+  out.tex1  = tex1;
   return out;
 }
 
+// One entry point per VertexLayout; attributes a layout lacks get the
+// values the decoders use for missing data.
+struct VSIn {
+  @location(0) position : vec4<f32>,
+  @location(1) normal   : vec2<f32>,
+  @location(3) tex0     : vec2<f32>,
+  @builtin(instance_index) iid : u32,
+};
+
+@vertex
+fn vs_main(in: VSIn) -> VSOut {
+  return shade_vertex(in.iid, in.position, in.normal, vec4<f32>(1.0), in.tex0, vec2<f32>(0.0));
+}
+
+struct VSInColor {
+  @location(0) position : vec4<f32>,
+  @location(1) normal   : vec2<f32>,
+  @location(2) color    : vec4<f32>,
+  @location(3) tex0     : vec2<f32>,
+  @builtin(instance_index) iid : u32,
+};
+
+@vertex
+fn vs_main_color(in: VSInColor) -> VSOut {
+  return shade_vertex(in.iid, in.position, in.normal, in.color, in.tex0, vec2<f32>(0.0));
+}
+
+struct VSInUv1 {
+  @location(0) position : vec4<f32>,
+  @location(1) normal   : vec2<f32>,
+  @location(3) tex0     : vec2<f32>,
+  @location(4) tex1     : vec2<f32>,
+  @builtin(instance_index) iid : u32,
+};
+
+@vertex
+fn vs_main_uv1(in: VSInUv1) -> VSOut {
+  return shade_vertex(in.iid, in.position, in.normal, vec4<f32>(1.0), in.tex0, in.tex1);
+}
+
+struct VSInColorUv1 {
+  @location(0) position : vec4<f32>,
+  @location(1) normal   : vec2<f32>,
+  @location(2) color    : vec4<f32>,
+  @location(3) tex0     : vec2<f32>,
+  @location(4) tex1     : vec2<f32>,
+  @builtin(instance_index) iid : u32,
+};
+
+@vertex
+fn vs_main_color_uv1(in: VSInColorUv1) -> VSOut {
+  return shade_vertex(in.iid, in.position, in.normal, in.color, in.tex0, in.tex1);
+}
+
+// Share of the ambient light left on the night side
+const NIGHT_AMBIENT: f32 = 0.25;
+
+// How much sunlight reaches the ground at an eye relative position: fades
+// out through twilight as the sun drops below the local horizon.
+fn daylight(world: vec3<f32>) -> f32 {
+  let up = normalize(uLighting.eye.xyz + world);
+  return smoothstep(-0.05, 0.05, dot(up, uLighting.sun_direction.xyz));
+}
+
+// Perturbs `n` by a tangent space normal, with the tangent frame taken from
+// position and UV derivatives, since tile meshes carry no tangents.
+fn apply_normal_map(
+  n: vec3<f32>,
+  pos_dx: vec3<f32>,
+  pos_dy: vec3<f32>,
+  uv_dx: vec2<f32>,
+  uv_dy: vec2<f32>,
+  sampled: vec3<f32>,
+) -> vec3<f32> {
+  let det = uv_dx.x * uv_dy.y - uv_dy.x * uv_dx.y;
+  let t_raw = (uv_dy.y * pos_dx - uv_dx.y * pos_dy) / det;
+  let t_ortho = t_raw - n * dot(n, t_raw);
+  if (abs(det) < 1e-12 || dot(t_ortho, t_ortho) < 1e-12) {
+    return n;
+  }
+  let t = normalize(t_ortho);
+  let b = cross(n, t);
+  let m = (sampled * 2.0 - 1.0) * vec3<f32>(uMaterial.normal_scale, uMaterial.normal_scale, 1.0);
+  return normalize(t * m.x + b * m.y + n * m.z);
+}
+
+// Fraction of sunlight reaching eye relative `world` past the shadow
+// casters, 3x3 PCF in the nearest cascade covering it. Lit beyond the last
+// cascade, fading out over its final tenth.
+fn sun_shadow(world: vec3<f32>, n: vec3<f32>) -> f32 {
+  let count = uShadows.cascade_count;
+  let depth = dot(world, uShadows.forward.xyz);
+  if (count == 0u || depth >= uShadows.splits[count - 1u]) {
+    return 1.0;
+  }
+  var cascade = 0u;
+  for (var i = 0u; i + 1u < count; i = i + 1u) {
+    if (depth >= uShadows.splits[i]) {
+      cascade = i + 1u;
+    }
+  }
+
+  // Offset along the normal by a texel or so against acne on slopes
+  let offset = n * uShadows.texel_meters[cascade] * 1.5;
+  let clip = uShadows.view_proj[cascade] * vec4<f32>(world + offset, 1.0);
+  let uv = clip.xy * vec2<f32>(0.5, -0.5) + 0.5;
+  if (any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) || clip.z < 0.0 || clip.z > 1.0) {
+    return 1.0;
+  }
+
+  var lit = 0.0;
+  for (var y = -1; y <= 1; y = y + 1) {
+    for (var x = -1; x <= 1; x = x + 1) {
+      let texel = vec2<f32>(f32(x), f32(y)) * uShadows.texel_uv;
+      lit = lit + textureSampleCompareLevel(
+        shadow_maps, shadow_sampler, uv + texel, i32(cascade), clip.z);
+    }
+  }
+  lit = lit / 9.0;
+
+  let last = uShadows.splits[count - 1u];
+  let fade = smoothstep(last * 0.9, last, depth);
+  return mix(lit, 1.0, fade);
+}
+
+// Cook-Torrance with GGX distribution, height-correlated Smith visibility
+// and Schlick Fresnel, for the sun plus ambient. `day` scales both;
+// `shadow` only the sun.
+fn shade_pbr(
+  base: vec3<f32>,
+  metallic: f32,
+  roughness: f32,
+  occlusion: f32,
+  n: vec3<f32>,
+  v: vec3<f32>,
+  day: f32,
+  shadow: f32,
+) -> vec3<f32> {
+  let l = uLighting.sun_direction.xyz;
+  let h = normalize(l + v);
+  let n_dot_l = clamp(dot(n, l), 0.0, 1.0);
+  let n_dot_v = clamp(abs(dot(n, v)), 1e-4, 1.0);
+  let n_dot_h = clamp(dot(n, h), 0.0, 1.0);
+  let v_dot_h = clamp(dot(v, h), 0.0, 1.0);
+
+  let f0 = mix(vec3<f32>(0.04), base, metallic);
+  let f = f0 + (1.0 - f0) * pow(1.0 - v_dot_h, 5.0);
+
+  let a = roughness * roughness;
+  let a2 = a * a;
+  let dd = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+  let d = a2 / (PI * dd * dd);
+  let vis_denom = n_dot_l * sqrt(n_dot_v * n_dot_v * (1.0 - a2) + a2)
+    + n_dot_v * sqrt(n_dot_l * n_dot_l * (1.0 - a2) + a2);
+  let vis = select(0.0, 0.5 / vis_denom, vis_denom > 0.0);
+
+  let diffuse_color = base * (1.0 - metallic);
+  let direct = ((1.0 - f) * diffuse_color / PI + f * d * vis)
+    * uLighting.sun_radiance.rgb * n_dot_l * day * shadow;
+  let ambient = (diffuse_color + f0) * uLighting.ambient.rgb * occlusion
+    * mix(NIGHT_AMBIENT, 1.0, day);
+  return direct + ambient;
+}
+
+fn shade_fragment(in: VSOut, front_facing: bool) -> vec4<f32> {
+  let flags = uMaterial.flags;
+
+  // Every sample and derivative up front, in uniform control flow
+  let normal_uv = texture_uv(in.tex0, in.tex1, SLOT_NORMAL);
+  let base_sample = textureSample(
+    base_color_texture, material_sampler, texture_uv(in.tex0, in.tex1, SLOT_BASE_COLOR));
+  let mr_sample = textureSample(
+    metallic_roughness_texture, material_sampler,
+    texture_uv(in.tex0, in.tex1, SLOT_METALLIC_ROUGHNESS));
+  let normal_sample = textureSample(normal_texture, material_sampler, normal_uv).xyz;
+  let occlusion_sample = textureSample(
+    occlusion_texture, material_sampler, texture_uv(in.tex0, in.tex1, SLOT_OCCLUSION)).r;
+  let emissive_sample = textureSample(
+    emissive_texture, material_sampler, texture_uv(in.tex0, in.tex1, SLOT_EMISSIVE)).rgb;
+  let pos_dx = dpdx(in.world);
+  let pos_dy = dpdy(in.world);
+  let uv_dx = dpdx(normal_uv);
+  let uv_dy = dpdy(normal_uv);
+
+  let base = base_sample * uMaterial.base_color_factor * in.color;
+  var alpha = base.a;
+  if ((flags & MATERIAL_MASK) != 0u) {
+    if (alpha < uMaterial.alpha_cutoff) {
+      discard;
+    }
+    alpha = 1.0;
+  } else if ((flags & MATERIAL_BLEND) == 0u) {
+    alpha = 1.0;
+  }
+
+  if ((flags & MATERIAL_UNLIT) != 0u) {
+    return vec4<f32>(aerial_perspective(base.rgb, in.world), alpha);
+  }
+
+  let v = normalize(-in.world);
+
+  // Meshes without normals get flat ones, facing the viewer
+  var flat_n = normalize(cross(pos_dx, pos_dy));
+  flat_n = select(flat_n, -flat_n, dot(flat_n, v) < 0.0);
+  let back_face = (flags & MATERIAL_DOUBLE_SIDED) != 0u && !front_facing;
+  let vertex_n = select(normalize(in.normal), -normalize(in.normal), back_face);
+  var n = select(flat_n, vertex_n, in.has_normal > 0.5);
+  // Geometric normal for the shadow offset, before any normal map
+  let shadow = sun_shadow(in.world, n);
+  if ((flags & MATERIAL_NORMAL_MAP) != 0u) {
+    n = apply_normal_map(n, pos_dx, pos_dy, uv_dx, uv_dy, normal_sample);
+  }
+
+  let metallic = clamp(uMaterial.metallic_factor * mr_sample.b, 0.0, 1.0);
+  let roughness = clamp(uMaterial.roughness_factor * mr_sample.g, 0.04, 1.0);
+  let occlusion = 1.0 + uMaterial.occlusion_strength * (occlusion_sample - 1.0);
+  let emissive = uMaterial.emissive_factor.rgb * emissive_sample;
+
+  let day = daylight(in.world);
+  let color = shade_pbr(base.rgb, metallic, roughness, occlusion, n, v, day, shadow) + emissive;
+  return vec4<f32>(aerial_perspective(color, in.world), alpha);
+}
+
 @fragment
-fn fs_main(
-  @location(0) color : vec4<f32>,
-  @location(1) normal: vec3<f32>,
-  @location(2) tex0  : vec2<f32>,
-  @location(3) tex1  : vec2<f32>,
-) -> @location(0) vec4<f32> {
-  let tex_color = textureSample(my_texture, my_sampler, tex0);
-  return tex_color * color;
-}
\ No newline at end of file
+fn fs_main(in: VSOut, @builtin(front_facing) front_facing: bool) -> @location(0) vec4<f32> {
+  return shade_fragment(in, front_facing);
+}
+
+// The globe keeps its own depth ordering but is squeezed towards the far
+// plane, so any tile in front of or intersecting it wins the depth test
+const GLOBE_DEPTH_SCALE: f32 = 1e-6;
+
+struct GlobeOut {
+  @location(0) color: vec4<f32>,
+  @builtin(frag_depth) depth: f32,
+};
+
+@fragment
+fn fs_globe(in: VSOut, @builtin(front_facing) front_facing: bool) -> GlobeOut {
+  var out: GlobeOut;
+  out.color = shade_fragment(in, front_facing);
+  out.depth = in.pos.z * GLOBE_DEPTH_SCALE;
+  return out;
+}
@@ -0,0 +1,55 @@
+// Sky pass, appended to atmosphere.wgsl. One triangle covering the screen
+// at the far plane; the depth test keeps it behind anything drawn.
+
+struct SkyOut {
+  @builtin(position) pos: vec4<f32>,
+  @location(0) ndc: vec2<f32>,
+};
+
+@vertex
+fn vs_sky(@builtin(vertex_index) index: u32) -> SkyOut {
+  let ndc = vec2<f32>(f32((index << 1u) & 2u) * 2.0 - 1.0, f32(index & 2u) * 2.0 - 1.0);
+  var out: SkyOut;
+  out.pos = vec4<f32>(ndc, 0.0, 1.0);
+  out.ndc = ndc;
+  return out;
+}
+
+// Apparent radius of the sun's disk, radians
+const SUN_ANGULAR_RADIUS: f32 = 0.00465;
+
+@fragment
+fn fs_sky(in: SkyOut) -> @location(0) vec4<f32> {
+  // Reverse Z: the near plane is at depth 1
+  let near = uLighting.inverse_view_proj * vec4<f32>(in.ndc, 1.0, 1.0);
+  let view_dir = normalize(near.xyz / near.w);
+
+  let origin = to_atmosphere(uLighting.eye.xyz);
+  let dir = normalize(to_atmosphere(view_dir));
+  let sun = sun_in_atmosphere();
+
+  let top = ray_sphere(origin, dir, TOP_RADIUS);
+  if (top.x > top.y || top.y < 0.0) {
+    return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+  }
+  let ground = ray_sphere(origin, dir, BOTTOM_RADIUS);
+  let hits_ground = ground.x <= ground.y && ground.x > 0.0;
+  let t0 = max(top.x, 0.0);
+  let t1 = select(top.y, ground.x, hits_ground);
+
+  let s = integrate_scattering(origin, dir, t0, t1, 32u);
+  var color = s.inscattered * atmosphere_illuminance();
+
+  if (hits_ground) {
+    // Bare ellipsoid where tiles haven't loaded, shaded like a tile
+    let p = origin + dir * t1;
+    let n = normalize(p);
+    let lit = sun_transmittance(p, sun) * max(dot(n, sun), 0.0) * GROUND_ALBEDO / PI;
+    color = color + s.transmittance * lit * uLighting.sun_radiance.rgb;
+  } else {
+    let disk = smoothstep(cos(SUN_ANGULAR_RADIUS * 1.2), cos(SUN_ANGULAR_RADIUS), dot(dir, sun));
+    color = color + s.transmittance * disk * uLighting.sun_radiance.rgb * 100.0;
+  }
+
+  return vec4<f32>(color, 1.0);
+}
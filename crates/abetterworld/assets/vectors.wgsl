@@ -0,0 +1,164 @@
+// Vector overlays: lines kept a fixed number of pixels wide, fills, and the
+// volumes that drape clamped shapes over the tiles. See render/vectors.rs.
+
+struct Camera {
+  viewProj: mat4x4<f32>,
+};
+
+struct Instance3x4 {
+  r0: vec4<f32>,
+  r1: vec4<f32>,
+  r2: vec4<f32>,
+};
+
+struct InstanceBuffer {
+  data: array<Instance3x4>,
+};
+
+// Mirrors VectorUniform in render/vectors.rs
+struct Vectors {
+  viewport: vec2<f32>,
+  // meters a pixel covers one meter in front of the camera
+  pixel_scale: f32,
+  _pad: f32,
+  // ECEF, only precise enough for directions
+  eye: vec4<f32>,
+};
+
+@group(0) @binding(0) var<uniform> uCamera : Camera;
+@group(0) @binding(1) var<storage, read> Instances : InstanceBuffer;
+@group(0) @binding(2) var<uniform> uVectors : Vectors;
+
+// Match DRAPE_MIN_HEIGHT and DRAPE_MAX_HEIGHT
+const DRAPE_MIN_HEIGHT: f32 = -500.0;
+const DRAPE_MAX_HEIGHT: f32 = 9000.0;
+
+// Twice the globe's GLOBE_DEPTH_SCALE, so ground shapes land on the globe
+// but still lose to every tile
+const GROUND_DEPTH_SCALE: f32 = 2e-6;
+
+struct VSIn {
+  @location(0) position: vec3<f32>,
+  @location(1) other: vec3<f32>,
+  // x left (1) or right (-1), y start (0) or end (1), z drape bottom (0) or
+  // top (1), w width in pixels
+  @location(2) extrude: vec4<f32>,
+  @location(3) color: vec4<f32>,
+  @builtin(instance_index) iid: u32,
+};
+
+struct VSOut {
+  @builtin(position) pos: vec4<f32>,
+  @location(0) color: vec4<f32>,
+  // relative to the eye
+  @location(1) world: vec3<f32>,
+};
+
+fn to_world(p: vec3<f32>, iid: u32) -> vec3<f32> {
+  let inst = Instances.data[iid];
+  let h = vec4<f32>(p, 1.0);
+  return vec3<f32>(dot(inst.r0, h), dot(inst.r1, h), dot(inst.r2, h));
+}
+
+fn srgb_to_linear(c: vec4<f32>) -> vec4<f32> {
+  let low = c.rgb / 12.92;
+  let high = pow((c.rgb + 0.055) / 1.055, vec3<f32>(2.4));
+  return vec4<f32>(select(high, low, c.rgb <= vec3<f32>(0.04045)), c.a);
+}
+
+// Moves p along the segment to q until it's in front of the near plane,
+// where z == w with the reverse-Z infinite projection
+fn clip_to_near(p: vec4<f32>, q: vec4<f32>) -> vec4<f32> {
+  let dp = p.w - p.z;
+  let dq = q.w - q.z;
+  if (dp >= 0.0 || dq <= 0.0) {
+    return p;
+  }
+  return mix(p, q, dp / (dp - dq));
+}
+
+@vertex
+fn vs_fill(in: VSIn) -> VSOut {
+  var out: VSOut;
+  out.world = to_world(in.position, in.iid);
+  out.pos = uCamera.viewProj * vec4<f32>(out.world, 1.0);
+  out.color = srgb_to_linear(in.color);
+  return out;
+}
+
+// One corner of a segment's quad, pushed out sideways and past its end in
+// screen space
+@vertex
+fn vs_line(in: VSIn) -> VSOut {
+  var out: VSOut;
+  out.world = to_world(in.position, in.iid);
+  let other = uCamera.viewProj * vec4<f32>(to_world(in.other, in.iid), 1.0);
+  let here = uCamera.viewProj * vec4<f32>(out.world, 1.0);
+  var p = clip_to_near(here, other);
+  let q = clip_to_near(other, here);
+
+  let half_viewport = uVectors.viewport * 0.5;
+  var toward = q.xy / q.w * half_viewport - p.xy / p.w * half_viewport;
+  if (dot(toward, toward) < 1e-12) {
+    toward = vec2<f32>(1.0, 0.0);
+  }
+  toward = normalize(toward);
+  // start to end, so both ends agree on which side is left
+  let along = select(toward, -toward, in.extrude.y > 0.5);
+  let left = vec2<f32>(-along.y, along.x);
+  let offset = (left * in.extrude.x - toward) * in.extrude.w * 0.5;
+  p = vec4<f32>(p.xy + offset / half_viewport * p.w, p.zw);
+
+  out.pos = p;
+  out.color = srgb_to_linear(in.color);
+  return out;
+}
+
+// One corner of the box around a clamped segment, reaching from below the
+// lowest ground to above the highest, as wide as the line is on screen
+@vertex
+fn vs_drape_line(in: VSIn) -> VSOut {
+  var out: VSOut;
+  let world = to_world(in.position, in.iid);
+  let up = normalize(uVectors.eye.xyz + world);
+  var toward = to_world(in.other, in.iid) - world;
+  toward = toward - up * dot(toward, up);
+  if (dot(toward, toward) < 1e-12) {
+    toward = cross(up, vec3<f32>(0.0, 0.0, 1.0));
+  }
+  toward = normalize(toward);
+  let along = select(toward, -toward, in.extrude.y > 0.5);
+  let left = normalize(cross(up, along));
+
+  let distance = max((uCamera.viewProj * vec4<f32>(world, 1.0)).w, 1.0);
+  let half_width = 0.5 * in.extrude.w * uVectors.pixel_scale * distance;
+  let height = mix(DRAPE_MIN_HEIGHT, DRAPE_MAX_HEIGHT, in.extrude.z);
+  out.world = world + (left * in.extrude.x - toward) * half_width + up * height;
+  out.pos = uCamera.viewProj * vec4<f32>(out.world, 1.0);
+  out.color = srgb_to_linear(in.color);
+  return out;
+}
+
+@fragment
+fn fs_vector(in: VSOut) -> @location(0) vec4<f32> {
+  return in.color;
+}
+
+struct GroundOut {
+  @location(0) color: vec4<f32>,
+  @builtin(frag_depth) depth: f32,
+};
+
+// Clamped shapes where there are no tiles. The squeezed depth can't hide
+// them behind the globe, so the horizon does, taking the Earth as a sphere
+// through the point.
+@fragment
+fn fs_ground(in: VSOut) -> GroundOut {
+  if (dot(in.world, uVectors.eye.xyz + in.world) > 0.0) {
+    discard;
+  }
+  var out: GroundOut;
+  out.color = in.color;
+  out.depth = in.pos.z * GROUND_DEPTH_SCALE;
+  return out;
+}
@@ -25,6 +25,11 @@ fn main() {
         return;
     }
 
+    if env::var_os("CARGO_FEATURE_RUST_DRACO").is_some() {
+        println!("cargo:warning=Skipping Draco C++ build, using the rust-draco decoder");
+        return;
+    }
+
     let draco_src = "./draco";
 
     // ===== CMake (Draco) =====
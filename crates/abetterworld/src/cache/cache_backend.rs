@@ -0,0 +1,165 @@
+use bytes::Bytes;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, RwLock,
+};
+use tracing::{event, Level};
+use xxhash_rust::xxh3::xxh3_64;
+
+use crate::{helpers::AbwError, world::CacheBackendConfig, Config};
+
+#[cfg(not(target_arch = "wasm32"))]
+pub type CacheFuture<'a, T> = futures::future::BoxFuture<'a, T>;
+
+// IndexedDB handles are JS objects, so wasm futures can't be Send
+#[cfg(target_arch = "wasm32")]
+pub type CacheFuture<'a, T> = futures::future::LocalBoxFuture<'a, T>;
+
+/// Called with the key of every entry a backend drops on its own (capacity, corruption, ...).
+/// Explicit `clear` calls are not reported.
+pub type EvictionHook = Arc<dyn Fn(&str) + Send + Sync>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheEntry {
+    pub content_type: String,
+    pub data: Bytes,
+    /// xxh3 of `data`, computed on insert. Backends persist it next to the payload so
+    /// damaged entries are caught on read instead of failing later in the decoder.
+    pub checksum: u64,
+}
+
+impl CacheEntry {
+    pub fn new(content_type: String, data: Bytes) -> Self {
+        let checksum = content_checksum(&data);
+        Self {
+            content_type,
+            data,
+            checksum,
+        }
+    }
+
+    pub fn is_intact(&self) -> bool {
+        content_checksum(&self.data) == self.checksum
+    }
+}
+
+pub fn content_checksum(data: &[u8]) -> u64 {
+    xxh3_64(data)
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CacheStats {
+    /// `None` when the backend can't cheaply count what it holds.
+    pub entries: Option<u64>,
+    pub bytes: Option<u64>,
+    pub hits: u64,
+    pub misses: u64,
+    pub inserts: u64,
+    pub evictions: u64,
+}
+
+/// Persistent tier behind `TilesetCache`. Keys are the full content URIs; backends are
+/// free to hash them. Implement this to plug an application specific store into `World`.
+pub trait CacheBackend: Send + Sync {
+    fn get<'a>(&'a self, key: &'a str) -> CacheFuture<'a, Result<Option<CacheEntry>, AbwError>>;
+
+    fn insert<'a>(
+        &'a self,
+        key: &'a str,
+        entry: CacheEntry,
+    ) -> CacheFuture<'a, Result<(), AbwError>>;
+
+    fn remove<'a>(&'a self, key: &'a str) -> CacheFuture<'a, Result<(), AbwError>>;
+
+    fn clear(&self) -> CacheFuture<'_, Result<(), AbwError>>;
+
+    fn stats(&self) -> CacheStats;
+
+    /// Backends that never evict on their own can ignore the hook.
+    fn set_eviction_hook(&self, _hook: Option<EvictionHook>) {}
+
+    /// Reports an entry the caller removed on the backend's behalf, such as one
+    /// `TilesetCache` found corrupt, to the hook and eviction count.
+    fn evicted(&self, _key: &str) {}
+}
+
+/// Hit/miss bookkeeping shared by the built-in backends.
+#[derive(Default)]
+pub struct CacheCounters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    inserts: AtomicU64,
+    evictions: AtomicU64,
+    hook: RwLock<Option<EvictionHook>>,
+}
+
+impl CacheCounters {
+    pub fn hit(&self) {
+        self.hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn miss(&self) {
+        self.misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn insert(&self) {
+        self.inserts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn evicted(&self, key: &str) {
+        self.evictions.fetch_add(1, Ordering::Relaxed);
+        let hook = self.hook.read().ok().and_then(|h| h.clone());
+        if let Some(hook) = hook {
+            hook(key);
+        }
+    }
+
+    pub fn set_hook(&self, hook: Option<EvictionHook>) {
+        if let Ok(mut guard) = self.hook.write() {
+            *guard = hook;
+        }
+    }
+
+    pub fn stats(&self, entries: Option<u64>, bytes: Option<u64>) -> CacheStats {
+        CacheStats {
+            entries,
+            bytes,
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            inserts: self.inserts.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Build the backend selected by `Config::cache_backend`. Persistent backends that
+/// don't exist on this platform fall back to the one that does.
+pub fn build_cache_backend(config: &Config) -> Arc<dyn CacheBackend> {
+    match &config.cache_backend {
+        CacheBackendConfig::Memory { max_bytes } => {
+            Arc::new(crate::cache::MemoryCacheBackend::new(*max_bytes))
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        CacheBackendConfig::Filesystem | CacheBackendConfig::IndexedDb => {
+            if config.cache_backend == CacheBackendConfig::IndexedDb {
+                event!(
+                    Level::WARN,
+                    "IndexedDB cache is only available on wasm, using the filesystem"
+                );
+            }
+            Arc::new(crate::cache::FsCacheBackend::new(&config.cache_dir))
+        }
+
+        #[cfg(target_arch = "wasm32")]
+        CacheBackendConfig::Filesystem | CacheBackendConfig::IndexedDb => {
+            if config.cache_backend == CacheBackendConfig::Filesystem {
+                event!(
+                    Level::WARN,
+                    "Filesystem cache is not available on wasm, using IndexedDB"
+                );
+            }
+            Arc::new(crate::cache::IndexedDbCacheBackend::new())
+        }
+    }
+}
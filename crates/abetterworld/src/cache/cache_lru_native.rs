@@ -1,4 +1,4 @@
-use crate::cache::cache_native::TilesetMemoryCache;
+use crate::cache::types::TilesetMemoryCache;
 use bytes::Bytes;
 
 pub struct NativeCache {
@@ -0,0 +1,174 @@
+use crate::cache::{
+    CacheBackend, CacheCounters, CacheEntry, CacheFuture, CacheStats, EvictionHook,
+};
+use crate::helpers::AbwError;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+struct MemoryEntry {
+    entry: CacheEntry,
+    tick: u64,
+}
+
+#[derive(Default)]
+struct MemoryState {
+    entries: HashMap<String, MemoryEntry>,
+    // Access order, oldest first: tick -> key
+    order: BTreeMap<u64, String>,
+    bytes: u64,
+    tick: u64,
+}
+
+impl MemoryState {
+    fn touch(&mut self, key: &str) -> Option<CacheEntry> {
+        self.tick += 1;
+        let tick = self.tick;
+        let item = self.entries.get_mut(key)?;
+        self.order.remove(&item.tick);
+        item.tick = tick;
+        self.order.insert(tick, key.to_owned());
+        Some(item.entry.clone())
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some(old) = self.entries.remove(key) {
+            self.order.remove(&old.tick);
+            self.bytes -= entry_size(key, &old.entry);
+        }
+    }
+
+    fn pop_oldest(&mut self) -> Option<String> {
+        let (_, key) = self.order.pop_first()?;
+        if let Some(old) = self.entries.remove(&key) {
+            self.bytes -= entry_size(&key, &old.entry);
+        }
+        Some(key)
+    }
+}
+
+fn entry_size(key: &str, entry: &CacheEntry) -> u64 {
+    (key.len() + entry.content_type.len() + entry.data.len()) as u64
+}
+
+/// Cache backend that never touches disk. Entries are dropped least recently used
+/// first once `max_bytes` is exceeded; a single entry larger than the budget is not kept.
+pub struct MemoryCacheBackend {
+    max_bytes: u64,
+    state: Mutex<MemoryState>,
+    counters: CacheCounters,
+}
+
+impl MemoryCacheBackend {
+    pub fn new(max_bytes: u64) -> Self {
+        Self {
+            max_bytes,
+            state: Mutex::new(MemoryState::default()),
+            counters: CacheCounters::default(),
+        }
+    }
+
+    fn read(&self, key: &str) -> Result<Option<CacheEntry>, AbwError> {
+        let mut state = self
+            .state
+            .lock()
+            .map_err(|_| AbwError::Io("memory cache lock poisoned".into()))?;
+        let entry = state.touch(key);
+        drop(state);
+
+        match entry {
+            Some(_) => self.counters.hit(),
+            None => self.counters.miss(),
+        }
+        Ok(entry)
+    }
+
+    fn write(&self, key: &str, entry: CacheEntry) -> Result<(), AbwError> {
+        let size = entry_size(key, &entry);
+        let mut evicted = Vec::new();
+        {
+            let mut state = self
+                .state
+                .lock()
+                .map_err(|_| AbwError::Io("memory cache lock poisoned".into()))?;
+            state.remove(key);
+
+            if size > self.max_bytes {
+                evicted.push(key.to_owned());
+            } else {
+                while state.bytes + size > self.max_bytes {
+                    match state.pop_oldest() {
+                        Some(old) => evicted.push(old),
+                        None => break,
+                    }
+                }
+
+                state.tick += 1;
+                let tick = state.tick;
+                state.order.insert(tick, key.to_owned());
+                state
+                    .entries
+                    .insert(key.to_owned(), MemoryEntry { entry, tick });
+                state.bytes += size;
+            }
+        }
+
+        self.counters.insert();
+        // Hooks run outside the lock so they may call back into the cache
+        for old in evicted {
+            self.counters.evicted(&old);
+        }
+        Ok(())
+    }
+}
+
+impl CacheBackend for MemoryCacheBackend {
+    fn get<'a>(&'a self, key: &'a str) -> CacheFuture<'a, Result<Option<CacheEntry>, AbwError>> {
+        Box::pin(async move { self.read(key) })
+    }
+
+    fn insert<'a>(
+        &'a self,
+        key: &'a str,
+        entry: CacheEntry,
+    ) -> CacheFuture<'a, Result<(), AbwError>> {
+        Box::pin(async move { self.write(key, entry) })
+    }
+
+    fn remove<'a>(&'a self, key: &'a str) -> CacheFuture<'a, Result<(), AbwError>> {
+        Box::pin(async move {
+            let mut state = self
+                .state
+                .lock()
+                .map_err(|_| AbwError::Io("memory cache lock poisoned".into()))?;
+            state.remove(key);
+            Ok(())
+        })
+    }
+
+    fn clear(&self) -> CacheFuture<'_, Result<(), AbwError>> {
+        Box::pin(async move {
+            let mut state = self
+                .state
+                .lock()
+                .map_err(|_| AbwError::Io("memory cache lock poisoned".into()))?;
+            *state = MemoryState::default();
+            Ok(())
+        })
+    }
+
+    fn stats(&self) -> CacheStats {
+        let (entries, bytes) = match self.state.lock() {
+            Ok(state) => (Some(state.entries.len() as u64), Some(state.bytes)),
+            Err(_) => (None, None),
+        };
+        self.counters.stats(entries, bytes)
+    }
+
+    fn set_eviction_hook(&self, hook: Option<EvictionHook>) {
+        self.counters.set_hook(hook);
+    }
+
+    fn evicted(&self, key: &str) {
+        self.counters.evicted(key);
+    }
+}
@@ -1,110 +1,148 @@
-use crate::cache::cache_lru_native::NativeCache;
+use crate::cache::{
+    CacheBackend, CacheCounters, CacheEntry, CacheFuture, CacheStats, EvictionHook,
+};
 use crate::helpers::{hash_uri, AbwError};
 use bytes::Bytes;
-use serde::{Deserialize, Serialize};
-use std::sync::{Arc, RwLock};
+use std::sync::RwLock;
 use std::{fs, path::Path};
-
-const LRU_CACHE_CAPACITY: u64 = 512;
-
-#[derive(Serialize, Deserialize)]
-struct DiskCacheEntry {
-    id: String,
-    content_type: String,
-    data: Vec<u8>,
+use tracing::{event, Level};
+
+// On-disk entry: magic "ABWC", version u32 LE, payload checksum u64 LE, content type
+// length u32 LE, content type (utf-8), then the payload up to the end of the file.
+const ENTRY_MAGIC: &[u8; 4] = b"ABWC";
+const ENTRY_VERSION: u32 = 2;
+const ENTRY_HEADER_LEN: usize = 20;
+
+fn encode_entry(entry: &CacheEntry) -> Vec<u8> {
+    let ct = entry.content_type.as_bytes();
+    let mut out = Vec::with_capacity(ENTRY_HEADER_LEN + ct.len() + entry.data.len());
+    out.extend_from_slice(ENTRY_MAGIC);
+    out.extend_from_slice(&ENTRY_VERSION.to_le_bytes());
+    out.extend_from_slice(&entry.checksum.to_le_bytes());
+    out.extend_from_slice(&(ct.len() as u32).to_le_bytes());
+    out.extend_from_slice(ct);
+    out.extend_from_slice(&entry.data);
+    out
 }
 
-pub trait TilesetMemoryCache: Send + Sync {
-    fn get(&self, key: u64) -> Option<(String, Bytes)>;
-    fn insert(&self, key: u64, value: (String, Bytes));
-    fn invalidate_all(&self);
+fn decode_entry(bytes: Vec<u8>) -> Result<CacheEntry, AbwError> {
+    if bytes.len() < ENTRY_HEADER_LEN || &bytes[0..4] != ENTRY_MAGIC {
+        return Err(AbwError::Io("Cache file has no entry header".into()));
+    }
+    let version = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
+    if version != ENTRY_VERSION {
+        return Err(AbwError::Io(format!(
+            "Unsupported cache entry version {version}"
+        )));
+    }
+    let checksum = u64::from_le_bytes(bytes[8..16].try_into().unwrap());
+    let ct_len = u32::from_le_bytes(bytes[16..20].try_into().unwrap()) as usize;
+    let data_start = ENTRY_HEADER_LEN + ct_len;
+    if data_start > bytes.len() {
+        return Err(AbwError::Io("Cache entry truncated".into()));
+    }
+    let content_type = String::from_utf8(bytes[ENTRY_HEADER_LEN..data_start].to_vec())
+        .map_err(|e| AbwError::Io(format!("Invalid cache content type: {e}")))?;
+
+    let mut data = Bytes::from(bytes);
+    Ok(CacheEntry {
+        content_type,
+        data: data.split_off(data_start),
+        checksum,
+    })
 }
 
-pub struct TilesetCache {
-    pub map: Arc<dyn TilesetMemoryCache>,
+/// Persistent cache backend storing one file per entry under `cache_dir`.
+pub struct FsCacheBackend {
     file_lock: RwLock<()>,
     base_dir: std::path::PathBuf,
+    counters: CacheCounters,
 }
 
-pub async fn init_wasm_indexdb_on_every_thread() -> Result<(), AbwError> {
-    // No-op on native
-    Ok(())
-}
-
-impl TilesetCache {
+impl FsCacheBackend {
     pub fn new(cache_dir: &str) -> Self {
         let base_dir = cache_dir.into();
         let _ = fs::create_dir_all(&base_dir);
         Self {
-            map: Arc::new(NativeCache::new(LRU_CACHE_CAPACITY)),
             file_lock: RwLock::new(()),
             base_dir,
+            counters: CacheCounters::default(),
         }
     }
 
     pub fn disk_path_for(&self, key: &str) -> std::path::PathBuf {
         let encoded = hash_uri(key);
-        self.base_dir.join(format!("{encoded}.json"))
+        self.base_dir.join(format!("{encoded}.bin"))
     }
 
-    pub async fn get(&self, key: &str) -> Result<Option<(String, Bytes)>, AbwError> {
-        let id = hash_uri(key);
-        if let Some((ct, data)) = self.map.get(id) {
-            return Ok(Some((ct, data)));
-        }
-
+    fn read(&self, key: &str) -> Result<Option<CacheEntry>, AbwError> {
         let filename = self.disk_path_for(key);
-        if Path::new(&filename).exists() {
-            let _guard = self
-                .file_lock
-                .read()
-                .map_err(|_| AbwError::Io("cache get lock poisoned".into()))?;
-
-            let bytes = fs::read(&filename)
-                .map_err(|e| AbwError::Io(format!("Failed to read cache file: {e}")))?;
-
-            let entry: DiskCacheEntry = serde_json::from_slice(&bytes)
-                .map_err(|e| AbwError::Io(format!("Failed to deserialize cache entry: {e}")))?;
+        if !Path::new(&filename).exists() {
+            self.counters.miss();
+            return Ok(None);
+        }
 
-            let data = Bytes::from(entry.data.clone());
-            self.map
-                .insert(id, (entry.content_type.clone(), data.clone()));
+        let guard = self
+            .file_lock
+            .read()
+            .map_err(|_| AbwError::Io("cache get lock poisoned".into()))?;
 
-            return Ok(Some((entry.content_type, data)));
+        let bytes = fs::read(&filename)
+            .map_err(|e| AbwError::Io(format!("Failed to read cache file: {e}")))?;
+        drop(guard);
+
+        let entry = decode_entry(bytes).and_then(|entry| {
+            if entry.is_intact() {
+                Ok(entry)
+            } else {
+                Err(AbwError::Io("Cache entry checksum mismatch".into()))
+            }
+        });
+
+        match entry {
+            Ok(entry) => {
+                self.counters.hit();
+                Ok(Some(entry))
+            }
+            Err(e) => {
+                // Damaged or written by an older layout: drop it so the next load refetches
+                event!(Level::WARN, "Evicting corrupt cache entry {}: {}", key, e);
+                self.delete(key)?;
+                self.counters.miss();
+                self.counters.evicted(key);
+                Ok(None)
+            }
         }
-
-        Ok(None)
     }
 
-    pub async fn insert(
-        &self,
-        key: String,
-        content_type: String,
-        bytes: Bytes,
-    ) -> Result<(), AbwError> {
-        let id = hash_uri(&key);
-        self.map.insert(id, (content_type.clone(), bytes.clone()));
-
-        let entry = DiskCacheEntry {
-            id: id.to_string(),
-            content_type,
-            data: bytes.to_vec(),
-        };
+    fn delete(&self, key: &str) -> Result<(), AbwError> {
+        let filename = self.disk_path_for(key);
+        let _guard = self
+            .file_lock
+            .write()
+            .map_err(|e| AbwError::Io(format!("Failed to acquire cache remove lock: {e}")))?;
+        match fs::remove_file(filename) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(AbwError::Io(format!("Failed to remove cache file: {e}")))
+            }
+            _ => Ok(()),
+        }
+    }
 
-        let filename = self.disk_path_for(&key);
-        let bytes = serde_json::to_vec(&entry).unwrap();
+    fn write(&self, key: &str, entry: &CacheEntry) -> Result<(), AbwError> {
+        let filename = self.disk_path_for(key);
+        let bytes = encode_entry(entry);
         let _guard = self
             .file_lock
             .write()
             .map_err(|e| AbwError::Io(format!("Failed to acquire cache insert lock: {e}")))?;
-        let _ = fs::write(filename, bytes);
-
+        fs::write(filename, bytes)
+            .map_err(|e| AbwError::Io(format!("Failed to write cache file: {e}")))?;
+        self.counters.insert();
         Ok(())
     }
 
-    pub fn clear(&self) -> Result<(), AbwError> {
-        self.map.invalidate_all();
-
+    fn remove_all(&self) -> Result<(), AbwError> {
         let _guard = self
             .file_lock
             .write()
@@ -116,4 +154,53 @@ impl TilesetCache {
 
         Ok(())
     }
+
+    fn disk_usage(&self) -> Option<(u64, u64)> {
+        let _guard = self.file_lock.read().ok()?;
+        let mut entries = 0;
+        let mut bytes = 0;
+        for dir_entry in fs::read_dir(&self.base_dir).ok()?.flatten() {
+            let path = dir_entry.path();
+            if path.extension().is_some_and(|ext| ext == "bin") {
+                entries += 1;
+                bytes += dir_entry.metadata().map(|m| m.len()).unwrap_or(0);
+            }
+        }
+        Some((entries, bytes))
+    }
+}
+
+impl CacheBackend for FsCacheBackend {
+    fn get<'a>(&'a self, key: &'a str) -> CacheFuture<'a, Result<Option<CacheEntry>, AbwError>> {
+        Box::pin(async move { self.read(key) })
+    }
+
+    fn insert<'a>(
+        &'a self,
+        key: &'a str,
+        entry: CacheEntry,
+    ) -> CacheFuture<'a, Result<(), AbwError>> {
+        Box::pin(async move { self.write(key, &entry) })
+    }
+
+    fn remove<'a>(&'a self, key: &'a str) -> CacheFuture<'a, Result<(), AbwError>> {
+        Box::pin(async move { self.delete(key) })
+    }
+
+    fn clear(&self) -> CacheFuture<'_, Result<(), AbwError>> {
+        Box::pin(async move { self.remove_all() })
+    }
+
+    fn stats(&self) -> CacheStats {
+        let usage = self.disk_usage();
+        self.counters.stats(usage.map(|u| u.0), usage.map(|u| u.1))
+    }
+
+    fn set_eviction_hook(&self, hook: Option<EvictionHook>) {
+        self.counters.set_hook(hook);
+    }
+
+    fn evicted(&self, key: &str) {
+        self.counters.evicted(key);
+    }
 }
@@ -1,9 +1,10 @@
-use crate::cache::types::TilesetMemoryCache;
-use crate::helpers::{hash_uri, IoContext};
-use crate::{cache::cache_lru_wasm::WasmCache, helpers::AbwError};
+use crate::cache::{
+    CacheBackend, CacheCounters, CacheEntry, CacheFuture, CacheStats, EvictionHook,
+};
+use crate::helpers::{hash_uri, AbwError, IoContext};
 use bytes::Bytes;
 use serde::{Deserialize, Serialize};
-use std::sync::{Arc, RwLock};
+use std::sync::Arc;
 use tracing::{event, Level};
 
 use {
@@ -17,21 +18,22 @@ struct DiskCacheEntry {
     id: String,
     content_type: String,
     data: Vec<u8>,
+    // u64 doesn't survive a round trip through a JS number, so it's kept as text.
+    // Entries written before checksums existed have none and are treated as corrupt.
+    #[serde(default)]
+    checksum: Option<String>,
 }
 
-pub struct TilesetCache {
-    pub map: Arc<dyn TilesetMemoryCache>,
-    file_lock: RwLock<()>,
+/// Persistent cache backend storing entries in the `abetterworld` IndexedDB store.
+/// The database handle is per thread, see `init_wasm_indexdb_on_every_thread`.
+#[derive(Default)]
+pub struct IndexedDbCacheBackend {
+    counters: CacheCounters,
 }
 
-impl TilesetCache {
-    pub fn new(_cache_dir: &str) -> Self {
-        let map: Arc<dyn TilesetMemoryCache> = Arc::new(WasmCache::new());
-
-        Self {
-            map,
-            file_lock: RwLock::new(()),
-        }
+impl IndexedDbCacheBackend {
+    pub fn new() -> Self {
+        Self::default()
     }
 
     async fn get_idb_data(
@@ -98,94 +100,167 @@ impl TilesetCache {
         Ok(id)
     }
 
-    pub async fn get(&self, key: &str) -> Result<Option<(String, Bytes)>, AbwError> {
-        let id = hash_uri(key);
-        if let Some((ct, data)) = self.map.get(id) {
-            return Ok(Some((ct, data)));
+    async fn delete_idb_data(database: &Database, id: JsValue) -> Result<(), AbwError> {
+        let transaction = database
+            .transaction(&["abetterworld"], TransactionMode::ReadWrite)
+            .io("Failed to create transaction")?;
+        let store = transaction
+            .object_store("abetterworld")
+            .io("Failed to get object store")?;
+
+        store
+            .delete(id)
+            .io("Failed to delete data from object store")?
+            .await
+            .io("Failed to delete data from object store")?;
+        transaction
+            .commit()
+            .io("Failed to commit transaction")?
+            .await
+            .io("Failed to commit transaction")?;
+
+        Ok(())
+    }
+
+    fn database() -> Option<Arc<Database>> {
+        let db = IDB_DB.with(|cell| cell.borrow().clone());
+        if db.is_none() {
+            event!(Level::WARN, "IndexedDB not initialized");
         }
+        db
+    }
 
-        let db_arc_result = IDB_DB.with(|cell| cell.borrow().clone());
-        let db_arc = match db_arc_result {
-            Some(db) => db,
-            None => {
-                event!(Level::WARN, "IndexedDB not initialized");
-                return Ok(None);
-            }
+    async fn read(&self, key: &str) -> Result<Option<CacheEntry>, AbwError> {
+        let Some(db_arc) = Self::database() else {
+            return Ok(None);
         };
 
-        let entry_result = Self::get_idb_data(&db_arc, JsValue::from_str(&id.to_string())).await;
-        match entry_result {
+        let id = hash_uri(key);
+        match Self::get_idb_data(&db_arc, JsValue::from_str(&id.to_string())).await {
             Ok(Some(entry)) => {
-                let data = Bytes::from(entry.data);
-                self.map
-                    .insert(id, (entry.content_type.clone(), data.clone()));
-                return Ok(Some((entry.content_type, data)));
+                let checksum = entry
+                    .checksum
+                    .as_deref()
+                    .and_then(|c| c.parse::<u64>().ok());
+                let entry = CacheEntry {
+                    content_type: entry.content_type,
+                    data: Bytes::from(entry.data),
+                    checksum: checksum.unwrap_or_default(),
+                };
+
+                if checksum.is_none() || !entry.is_intact() {
+                    event!(Level::WARN, "Evicting corrupt cache entry {}", key);
+                    Self::delete_idb_data(&db_arc, JsValue::from_str(&id.to_string())).await?;
+                    self.counters.miss();
+                    self.counters.evicted(key);
+                    return Ok(None);
+                }
+
+                self.counters.hit();
+                Ok(Some(entry))
             }
-            Ok(None) => return Ok(None),
-            Err(_) => return Err(AbwError::Io("Failed to get data from IndexedDB".to_owned())),
-        };
+            Ok(None) => {
+                self.counters.miss();
+                Ok(None)
+            }
+            Err(_) => Err(AbwError::Io("Failed to get data from IndexedDB".to_owned())),
+        }
     }
 
-    pub async fn insert(
-        &self,
-        key: String,
-        content_type: String,
-        bytes: Bytes,
-    ) -> Result<(), AbwError> {
-        let id = hash_uri(&key);
-        self.map.insert(id, (content_type.clone(), bytes.clone()));
-
-        let entry = DiskCacheEntry {
-            id: id.to_string(),
-            content_type,
-            data: bytes.to_vec(),
+    async fn write(&self, key: &str, entry: CacheEntry) -> Result<(), AbwError> {
+        let Some(db_arc) = Self::database() else {
+            return Ok(());
         };
 
-        let db_arc_result = IDB_DB.with(|cell| cell.borrow().clone());
-        let db_arc = match db_arc_result {
-            Some(db) => db,
-            None => {
-                event!(Level::WARN, "IndexedDB not initialized");
-                return Ok(());
-            }
+        let entry = DiskCacheEntry {
+            id: hash_uri(key).to_string(),
+            content_type: entry.content_type,
+            data: entry.data.to_vec(),
+            checksum: Some(entry.checksum.to_string()),
         };
 
-        let insert_result = Self::insert_idb_data(&db_arc, entry).await;
-        if let Err(err) = insert_result {
+        if let Err(err) = Self::insert_idb_data(&db_arc, entry).await {
             event!(
                 Level::ERROR,
                 "IndexedDB insert failed for {:?}: {:?}",
                 key,
                 err
             );
+        } else {
+            self.counters.insert();
         }
 
         Ok(())
     }
 
-    pub async fn clear(&self) -> Result<(), AbwError> {
-        self.map.invalidate_all();
-        let db_arc_result = IDB_DB.with(|cell| cell.borrow().clone());
-        let db_arc = match db_arc_result {
-            Some(db) => db,
-            None => {
-                event!(Level::WARN, "IndexedDB not initialized");
-                return Ok(());
-            }
+    async fn remove_all(&self) -> Result<(), AbwError> {
+        let Some(db_arc) = Self::database() else {
+            return Ok(());
         };
 
         let transaction = db_arc
             .transaction(&["abetterworld"], TransactionMode::ReadWrite)
-            .unwrap();
-        let store = transaction.object_store("abetterworld").unwrap();
+            .io("Failed to create transaction")?;
+        let store = transaction
+            .object_store("abetterworld")
+            .io("Failed to get object store")?;
 
-        store.clear().unwrap().await.unwrap();
-        transaction.commit().unwrap().await.unwrap();
+        store
+            .clear()
+            .io("Failed to clear object store")?
+            .await
+            .io("Failed to clear object store")?;
+        transaction
+            .commit()
+            .io("Failed to commit transaction")?
+            .await
+            .io("Failed to commit transaction")?;
 
         Ok(())
     }
 }
 
+impl CacheBackend for IndexedDbCacheBackend {
+    fn get<'a>(&'a self, key: &'a str) -> CacheFuture<'a, Result<Option<CacheEntry>, AbwError>> {
+        Box::pin(self.read(key))
+    }
+
+    fn insert<'a>(
+        &'a self,
+        key: &'a str,
+        entry: CacheEntry,
+    ) -> CacheFuture<'a, Result<(), AbwError>> {
+        Box::pin(self.write(key, entry))
+    }
+
+    fn remove<'a>(&'a self, key: &'a str) -> CacheFuture<'a, Result<(), AbwError>> {
+        Box::pin(async move {
+            let Some(db_arc) = Self::database() else {
+                return Ok(());
+            };
+            let id = JsValue::from_str(&hash_uri(key).to_string());
+            Self::delete_idb_data(&db_arc, id).await
+        })
+    }
+
+    fn clear(&self) -> CacheFuture<'_, Result<(), AbwError>> {
+        Box::pin(self.remove_all())
+    }
+
+    fn stats(&self) -> CacheStats {
+        // Counting an object store is itself async, so only the counters are reported.
+        self.counters.stats(None, None)
+    }
+
+    fn set_eviction_hook(&self, hook: Option<EvictionHook>) {
+        self.counters.set_hook(hook);
+    }
+
+    fn evicted(&self, key: &str) {
+        self.counters.evicted(key);
+    }
+}
+
 thread_local! {
     static IDB_DB: RefCell<Option<Arc<Database>>> = RefCell::new(None);
 }
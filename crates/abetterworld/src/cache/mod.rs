@@ -1,7 +1,14 @@
-mod cache_shared;
+mod cache_backend;
+mod cache_memory;
+mod tileset_cache;
 mod types;
 
-pub use cache_shared::{destroy_tileset_cache, get_tileset_cache, init_tileset_cache};
+pub use cache_backend::{
+    build_cache_backend, CacheBackend, CacheCounters, CacheEntry, CacheFuture, CacheStats,
+    EvictionHook,
+};
+pub use cache_memory::MemoryCacheBackend;
+pub use tileset_cache::TilesetCache;
 
 #[cfg(target_arch = "wasm32")]
 mod cache_wasm;
@@ -10,7 +17,7 @@ mod cache_wasm;
 mod cache_lru_wasm;
 
 #[cfg(target_arch = "wasm32")]
-pub use cache_wasm::{init_wasm_indexdb_on_every_thread, TilesetCache};
+pub use cache_wasm::{init_wasm_indexdb_on_every_thread, IndexedDbCacheBackend};
 
 #[cfg(not(target_arch = "wasm32"))]
 mod cache_native;
@@ -19,4 +26,4 @@ mod cache_native;
 mod cache_lru_native;
 
 #[cfg(not(target_arch = "wasm32"))]
-pub use cache_native::{init_wasm_indexdb_on_every_thread, TilesetCache};
+pub use cache_native::FsCacheBackend;
@@ -0,0 +1,114 @@
+use crate::cache::types::TilesetMemoryCache;
+use crate::cache::{CacheBackend, CacheEntry, CacheStats};
+use crate::helpers::{hash_uri, AbwError};
+use bytes::Bytes;
+use std::sync::Arc;
+use tracing::{event, Level};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::cache::cache_lru_native::NativeCache;
+
+#[cfg(target_arch = "wasm32")]
+use crate::cache::cache_lru_wasm::WasmCache;
+
+#[cfg(not(target_arch = "wasm32"))]
+const LRU_CACHE_CAPACITY: u64 = 512;
+
+const DECODED_TILE_CONTENT_TYPE: &str = "application/x-abw-decoded";
+
+fn decoded_key(key: &str) -> String {
+    format!("decoded:{key}")
+}
+
+/// Raw downloads and decoded tiles, kept in a small in-memory tier in front of a
+/// pluggable [`CacheBackend`].
+pub struct TilesetCache {
+    pub map: Arc<dyn TilesetMemoryCache>,
+    backend: Arc<dyn CacheBackend>,
+}
+
+impl TilesetCache {
+    pub fn new(backend: Arc<dyn CacheBackend>) -> Self {
+        #[cfg(not(target_arch = "wasm32"))]
+        let map: Arc<dyn TilesetMemoryCache> = Arc::new(NativeCache::new(LRU_CACHE_CAPACITY));
+
+        #[cfg(target_arch = "wasm32")]
+        let map: Arc<dyn TilesetMemoryCache> = Arc::new(WasmCache::new());
+
+        Self { map, backend }
+    }
+
+    pub fn backend(&self) -> &Arc<dyn CacheBackend> {
+        &self.backend
+    }
+
+    /// Backend read that re-checks the payload, so custom backends that don't verify
+    /// checksums themselves still never hand back damaged data. Dropped entries are
+    /// reported like the backend's own evictions.
+    async fn get_verified(&self, key: &str) -> Result<Option<CacheEntry>, AbwError> {
+        match self.backend.get(key).await? {
+            Some(entry) if !entry.is_intact() => {
+                event!(Level::WARN, "Evicting corrupt cache entry {}", key);
+                self.backend.remove(key).await?;
+                self.backend.evicted(key);
+                Ok(None)
+            }
+            entry => Ok(entry),
+        }
+    }
+
+    pub async fn get(&self, key: &str) -> Result<Option<(String, Bytes)>, AbwError> {
+        let id = hash_uri(key);
+        if let Some((ct, data)) = self.map.get(id) {
+            return Ok(Some((ct, data)));
+        }
+
+        match self.get_verified(key).await? {
+            Some(entry) => {
+                self.map
+                    .insert(id, (entry.content_type.clone(), entry.data.clone()));
+                Ok(Some((entry.content_type, entry.data)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub async fn insert(
+        &self,
+        key: String,
+        content_type: String,
+        bytes: Bytes,
+    ) -> Result<(), AbwError> {
+        let id = hash_uri(&key);
+        self.map.insert(id, (content_type.clone(), bytes.clone()));
+
+        self.backend
+            .insert(&key, CacheEntry::new(content_type, bytes))
+            .await
+    }
+
+    /// Second cache tier: post-decode tile payloads. Not kept in the memory LRU, the
+    /// renderer already holds the GPU copy of anything that was recently decoded.
+    pub async fn get_decoded(&self, key: &str) -> Result<Option<Bytes>, AbwError> {
+        let entry = self.get_verified(&decoded_key(key)).await?;
+        Ok(entry.map(|e| e.data))
+    }
+
+    pub async fn insert_decoded(&self, key: &str, bytes: Bytes) -> Result<(), AbwError> {
+        self.backend
+            .insert(
+                &decoded_key(key),
+                CacheEntry::new(DECODED_TILE_CONTENT_TYPE.to_string(), bytes),
+            )
+            .await
+    }
+
+    pub async fn clear(&self) -> Result<(), AbwError> {
+        self.map.invalidate_all();
+        self.backend.clear().await
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.backend.stats()
+    }
+}
@@ -0,0 +1,321 @@
+//! glTF accessor reader. Handles every component type, `normalized` integers,
+//! byte strides, matrix column padding and sparse substitution, so integer
+//! attributes written under `KHR_mesh_quantization` come out as plain floats.
+
+use serde_json::Value;
+
+use crate::helpers::AbwError;
+
+type Result<T> = std::result::Result<T, AbwError>;
+
+fn err<T>(msg: &str) -> Result<T> {
+    Err(AbwError::TileLoading(format!("accessor: {msg}")))
+}
+
+/// Most elements an accessor without a bufferView may have. Nothing in the
+/// file bounds those, and they are all zeros but for sparse substitutions.
+const MAX_ZEROED_ELEMENTS: usize = 1 << 24;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ComponentType {
+    I8,
+    U8,
+    I16,
+    U16,
+    U32,
+    F32,
+}
+
+impl ComponentType {
+    pub(crate) fn from_gl(code: u64) -> Result<Self> {
+        Ok(match code {
+            5120 => ComponentType::I8,
+            5121 => ComponentType::U8,
+            5122 => ComponentType::I16,
+            5123 => ComponentType::U16,
+            5125 => ComponentType::U32,
+            5126 => ComponentType::F32,
+            _ => return err(&format!("unknown component type {code}")),
+        })
+    }
+
+    pub(crate) fn size(self) -> usize {
+        match self {
+            ComponentType::I8 | ComponentType::U8 => 1,
+            ComponentType::I16 | ComponentType::U16 => 2,
+            ComponentType::U32 | ComponentType::F32 => 4,
+        }
+    }
+
+    /// Reads one component as a float, applying the glTF normalization rules
+    /// when `normalized` is set.
+    fn read_f32(self, b: &[u8], normalized: bool) -> f32 {
+        match (self, normalized) {
+            (ComponentType::I8, false) => b[0] as i8 as f32,
+            (ComponentType::I8, true) => (b[0] as i8 as f32 / 127.0).max(-1.0),
+            (ComponentType::U8, false) => b[0] as f32,
+            (ComponentType::U8, true) => b[0] as f32 / 255.0,
+            (ComponentType::I16, false) => i16::from_le_bytes([b[0], b[1]]) as f32,
+            (ComponentType::I16, true) => {
+                (i16::from_le_bytes([b[0], b[1]]) as f32 / 32767.0).max(-1.0)
+            }
+            (ComponentType::U16, false) => u16::from_le_bytes([b[0], b[1]]) as f32,
+            (ComponentType::U16, true) => u16::from_le_bytes([b[0], b[1]]) as f32 / 65535.0,
+            (ComponentType::U32, _) => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32,
+            (ComponentType::F32, _) => f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+        }
+    }
+
+    fn read_u32(self, b: &[u8]) -> Result<u32> {
+        match self {
+            ComponentType::U8 => Ok(b[0] as u32),
+            ComponentType::U16 => Ok(u16::from_le_bytes([b[0], b[1]]) as u32),
+            ComponentType::U32 => Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+            _ => err(&format!("{self:?} is not an index type")),
+        }
+    }
+}
+
+/// Shape of one accessor element. Matrix columns start on 4-byte
+/// boundaries, which pads byte and short matrices.
+#[derive(Clone, Copy, Debug)]
+struct Layout {
+    component: ComponentType,
+    columns: usize,
+    rows: usize,
+}
+
+impl Layout {
+    fn new(component: ComponentType, ty: &str) -> Result<Self> {
+        let (columns, rows) = match ty {
+            "SCALAR" => (1, 1),
+            "VEC2" => (1, 2),
+            "VEC3" => (1, 3),
+            "VEC4" => (1, 4),
+            "MAT2" => (2, 2),
+            "MAT3" => (3, 3),
+            "MAT4" => (4, 4),
+            _ => return err(&format!("unknown accessor type {ty}")),
+        };
+        Ok(Layout {
+            component,
+            columns,
+            rows,
+        })
+    }
+
+    fn components(&self) -> usize {
+        self.columns * self.rows
+    }
+
+    fn column_stride(&self) -> usize {
+        let len = self.rows * self.component.size();
+        if self.columns > 1 {
+            len.next_multiple_of(4)
+        } else {
+            len
+        }
+    }
+
+    fn element_size(&self) -> usize {
+        self.columns * self.column_stride()
+    }
+
+    /// Byte offsets of each component within an element, column-major.
+    fn offsets(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.columns).flat_map(move |c| {
+            (0..self.rows).map(move |r| c * self.column_stride() + r * self.component.size())
+        })
+    }
+}
+
+/// A bufferView window over the binary chunk.
+struct View<'a> {
+    data: &'a [u8],
+    stride: Option<usize>,
+}
+
+fn buffer_view<'a>(json: &Value, bin: &'a [u8], index: u64) -> Result<View<'a>> {
+    let Some(view) = json.get("bufferViews").and_then(|v| v.get(index as usize)) else {
+        return err(&format!("missing bufferView {index}"));
+    };
+    let offset = view.get("byteOffset").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+    let Some(length) = view.get("byteLength").and_then(|v| v.as_u64()) else {
+        return err(&format!("bufferView {index} has no byteLength"));
+    };
+    let Some(data) = offset
+        .checked_add(length as usize)
+        .and_then(|end| bin.get(offset..end))
+    else {
+        return err(&format!("bufferView {index} is out of bounds"));
+    };
+    let stride = view
+        .get("byteStride")
+        .and_then(|v| v.as_u64())
+        .map(|s| s as usize);
+    Ok(View { data, stride })
+}
+
+/// Returns the `count` element slices starting at `offset` in `view`.
+fn elements<'a>(
+    view: &View<'a>,
+    offset: usize,
+    count: usize,
+    element_size: usize,
+) -> Result<impl Iterator<Item = &'a [u8]>> {
+    let stride = view.stride.unwrap_or(element_size);
+    if stride < element_size {
+        return err("byteStride is smaller than the element");
+    }
+    if count > 0 {
+        let end = (count - 1)
+            .checked_mul(stride)
+            .zip(offset.checked_add(element_size))
+            .and_then(|(last, first_end)| last.checked_add(first_end));
+        if end.is_none_or(|end| end > view.data.len()) {
+            return err("accessor runs past its bufferView");
+        }
+    }
+    let data = view.data;
+    Ok((0..count).map(move |i| &data[offset + i * stride..][..element_size]))
+}
+
+/// Reads an accessor into a flat list of per-component values, applying any
+/// sparse substitution. Accessors without a bufferView start out zeroed.
+fn read_accessor<T: Copy + Default>(
+    json: &Value,
+    bin: &[u8],
+    accessor_idx: u64,
+    read: impl Fn(ComponentType, &[u8]) -> Result<T>,
+) -> Result<(Vec<T>, usize)> {
+    let Some(accessor) = json
+        .get("accessors")
+        .and_then(|v| v.get(accessor_idx as usize))
+    else {
+        return err(&format!("missing accessor {accessor_idx}"));
+    };
+    let (Some(component_type), Some(count), Some(ty)) = (
+        accessor.get("componentType").and_then(|v| v.as_u64()),
+        accessor.get("count").and_then(|v| v.as_u64()),
+        accessor.get("type").and_then(|v| v.as_str()),
+    ) else {
+        return err(&format!("accessor {accessor_idx} is incomplete"));
+    };
+    let count = count as usize;
+    let layout = Layout::new(ComponentType::from_gl(component_type)?, ty)?;
+    let n = layout.components();
+
+    let decode_element = |element: &[u8], out: &mut [T]| -> Result<()> {
+        for (slot, offset) in out.iter_mut().zip(layout.offsets()) {
+            *slot = read(layout.component, &element[offset..])?;
+        }
+        Ok(())
+    };
+
+    // The view has to hold `count` elements before anything is allocated
+    let source = match accessor.get("bufferView").and_then(|v| v.as_u64()) {
+        Some(view_idx) => {
+            let view = buffer_view(json, bin, view_idx)?;
+            let offset = accessor
+                .get("byteOffset")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(0) as usize;
+            Some(elements(&view, offset, count, layout.element_size())?)
+        }
+        None if count > MAX_ZEROED_ELEMENTS => {
+            return err(&format!("accessor {accessor_idx} has too many elements"))
+        }
+        None => None,
+    };
+    let Some(len) = count.checked_mul(n) else {
+        return err(&format!("accessor {accessor_idx} has too many elements"));
+    };
+    let mut values = vec![T::default(); len];
+    if let Some(source) = source {
+        for (element, out) in source.zip(values.chunks_exact_mut(n)) {
+            decode_element(element, out)?;
+        }
+    }
+
+    if let Some(sparse) = accessor.get("sparse") {
+        let sparse_count = sparse.get("count").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+        let (Some(indices), Some(sparse_values)) = (sparse.get("indices"), sparse.get("values"))
+        else {
+            return err("sparse accessor is missing indices or values");
+        };
+
+        let window = |section: &Value| -> Result<(View, usize)> {
+            let Some(view_idx) = section.get("bufferView").and_then(|v| v.as_u64()) else {
+                return err("sparse section has no bufferView");
+            };
+            // Sparse data is always tightly packed
+            let mut view = buffer_view(json, bin, view_idx)?;
+            view.stride = None;
+            let offset = section
+                .get("byteOffset")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(0) as usize;
+            Ok((view, offset))
+        };
+
+        let index_type = match indices.get("componentType").and_then(|v| v.as_u64()) {
+            Some(code) => ComponentType::from_gl(code)?,
+            None => return err("sparse indices have no componentType"),
+        };
+        let (index_view, index_offset) = window(indices)?;
+        let (value_view, value_offset) = window(sparse_values)?;
+
+        let targets = elements(&index_view, index_offset, sparse_count, index_type.size())?;
+        let replacements = elements(
+            &value_view,
+            value_offset,
+            sparse_count,
+            layout.element_size(),
+        )?;
+        for (target, element) in targets.zip(replacements) {
+            let target = index_type.read_u32(target)? as usize;
+            if target >= count {
+                return err("sparse index is out of range");
+            }
+            decode_element(element, &mut values[target * n..(target + 1) * n])?;
+        }
+    }
+
+    Ok((values, n))
+}
+
+/// Reads any accessor as floats, normalizing integer components when the
+/// accessor says so. Returns the values and the components per element.
+pub(crate) fn read_accessor_f32(
+    json: &Value,
+    bin: &[u8],
+    accessor_idx: u64,
+) -> Result<(Vec<f32>, usize)> {
+    let normalized = json
+        .get("accessors")
+        .and_then(|v| v.get(accessor_idx as usize))
+        .and_then(|a| a.get("normalized"))
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    read_accessor(json, bin, accessor_idx, |component, bytes| {
+        if normalized && matches!(component, ComponentType::U32 | ComponentType::F32) {
+            return err(&format!("{component:?} components can't be normalized"));
+        }
+        Ok(component.read_f32(bytes, normalized))
+    })
+}
+
+/// Reads an unsigned integer SCALAR accessor, as used for indices.
+pub(crate) fn read_accessor_indices(
+    json: &Value,
+    bin: &[u8],
+    accessor_idx: u64,
+) -> Result<Vec<u32>> {
+    let (values, n) = read_accessor(json, bin, accessor_idx, |component, bytes| {
+        component.read_u32(bytes)
+    })?;
+    if n != 1 {
+        return err("index accessor is not SCALAR");
+    }
+    Ok(values)
+}
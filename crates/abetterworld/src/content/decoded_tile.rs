@@ -0,0 +1,407 @@
+use crate::{
+    content::types::{
+        AlphaMode, Material, Node, Texture, TextureEncoding, TextureRef, TextureTransform,
+        TileState,
+    },
+    content::PackedMesh,
+    helpers::AbwError,
+    render::VertexLayout,
+};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use cgmath::Matrix4;
+use std::io::{Cursor, Read};
+
+// Binary layout of a decoded tile (all integers little endian):
+//
+//   header    : magic "ABWD", version u32
+//   nodes     : count u32, then per node 16 x f64 (column major), mesh index count u32, indices u32[]
+//   meshes    : count u32, then per mesh material i64 (-1 = none), vertex layout bits u32,
+//               center f32 x3, extent f32, vertex count u32, index count u32,
+//               vertex bytes (count x layout stride, as uploaded), u32[]
+//   primitives: count u32, then per glTF mesh start u32, end u32 into the meshes above
+//   textures  : count u32, then per texture width u32, height u32, encoding u32, level count u32,
+//               then per level byte len u32, bytes (largest level first)
+//   materials : count u32, then per material base color factor f32 x4, metallic f32,
+//               roughness f32, normal scale f32, occlusion strength f32, emissive factor f32 x3,
+//               alpha mode u32, alpha cutoff f32, flags u32 (1 double sided, 2 unlit), then the
+//               five texture slots in `Material::textures` order, each a texture i64 (-1 = none)
+//               and when present its texCoord u32, offset f32 x2, rotation f32, scale f32 x2
+//
+// Bump DECODED_TILE_VERSION whenever any of the above (or `VertexLayout`, or what the importer
+// stores in it) changes so stale entries are treated as cache misses instead of being
+// misread.
+pub const DECODED_TILE_MAGIC: &[u8; 4] = b"ABWD";
+pub const DECODED_TILE_VERSION: u32 = 7;
+
+fn invalid(msg: &str) -> AbwError {
+    AbwError::Io(format!("Invalid decoded tile: {msg}"))
+}
+
+fn write_opt_index(out: &mut Vec<u8>, idx: Option<usize>) -> std::io::Result<()> {
+    out.write_i64::<LittleEndian>(idx.map(|i| i as i64).unwrap_or(-1))
+}
+
+fn read_opt_index(cursor: &mut Cursor<&[u8]>) -> std::io::Result<Option<usize>> {
+    let v = cursor.read_i64::<LittleEndian>()?;
+    Ok(if v < 0 { None } else { Some(v as usize) })
+}
+
+fn write_texture_ref(out: &mut Vec<u8>, texture: Option<TextureRef>) -> std::io::Result<()> {
+    write_opt_index(out, texture.map(|t| t.index))?;
+    if let Some(t) = texture {
+        out.write_u32::<LittleEndian>(t.tex_coord)?;
+        let [ox, oy] = t.transform.offset;
+        let [sx, sy] = t.transform.scale;
+        for v in [ox, oy, t.transform.rotation, sx, sy] {
+            out.write_f32::<LittleEndian>(v)?;
+        }
+    }
+    Ok(())
+}
+
+fn read_texture_ref(cursor: &mut Cursor<&[u8]>) -> std::io::Result<Option<TextureRef>> {
+    let Some(index) = read_opt_index(cursor)? else {
+        return Ok(None);
+    };
+    let tex_coord = cursor.read_u32::<LittleEndian>()?;
+    let mut v = [0.0f32; 5];
+    cursor.read_f32_into::<LittleEndian>(&mut v)?;
+    Ok(Some(TextureRef {
+        index,
+        tex_coord,
+        transform: TextureTransform {
+            offset: [v[0], v[1]],
+            rotation: v[2],
+            scale: [v[3], v[4]],
+        },
+    }))
+}
+
+fn alpha_mode_tag(mode: AlphaMode) -> u32 {
+    match mode {
+        AlphaMode::Opaque => 0,
+        AlphaMode::Mask => 1,
+        AlphaMode::Blend => 2,
+    }
+}
+
+fn alpha_mode_from_tag(tag: u32) -> Result<AlphaMode, AbwError> {
+    Ok(match tag {
+        0 => AlphaMode::Opaque,
+        1 => AlphaMode::Mask,
+        2 => AlphaMode::Blend,
+        _ => return Err(invalid("unknown alpha mode")),
+    })
+}
+
+fn write_material(out: &mut Vec<u8>, material: &Material) -> std::io::Result<()> {
+    let factors = material.base_color_factor.into_iter().chain([
+        material.metallic_factor,
+        material.roughness_factor,
+        material.normal_scale,
+        material.occlusion_strength,
+    ]);
+    for v in factors.chain(material.emissive_factor) {
+        out.write_f32::<LittleEndian>(v)?;
+    }
+    out.write_u32::<LittleEndian>(alpha_mode_tag(material.alpha_mode))?;
+    out.write_f32::<LittleEndian>(material.alpha_cutoff)?;
+    out.write_u32::<LittleEndian>(material.double_sided as u32 | (material.unlit as u32) << 1)?;
+    for texture in material.textures() {
+        write_texture_ref(out, texture)?;
+    }
+    Ok(())
+}
+
+fn read_material(cursor: &mut Cursor<&[u8]>) -> Result<Material, AbwError> {
+    let trunc = |_| invalid("truncated data");
+    let mut v = [0.0f32; 11];
+    cursor
+        .read_f32_into::<LittleEndian>(&mut v)
+        .map_err(trunc)?;
+    let alpha_mode = alpha_mode_from_tag(cursor.read_u32::<LittleEndian>().map_err(trunc)?)?;
+    let alpha_cutoff = cursor.read_f32::<LittleEndian>().map_err(trunc)?;
+    let flags = cursor.read_u32::<LittleEndian>().map_err(trunc)?;
+    // in `Material::textures` order
+    let base_color_texture = read_texture_ref(cursor).map_err(trunc)?;
+    let metallic_roughness_texture = read_texture_ref(cursor).map_err(trunc)?;
+    let normal_texture = read_texture_ref(cursor).map_err(trunc)?;
+    let occlusion_texture = read_texture_ref(cursor).map_err(trunc)?;
+    let emissive_texture = read_texture_ref(cursor).map_err(trunc)?;
+    Ok(Material {
+        base_color_factor: [v[0], v[1], v[2], v[3]],
+        base_color_texture,
+        metallic_factor: v[4],
+        roughness_factor: v[5],
+        metallic_roughness_texture,
+        normal_texture,
+        normal_scale: v[6],
+        occlusion_texture,
+        occlusion_strength: v[7],
+        emissive_factor: [v[8], v[9], v[10]],
+        emissive_texture,
+        alpha_mode,
+        alpha_cutoff,
+        double_sided: flags & 1 != 0,
+        unlit: flags & 2 != 0,
+    })
+}
+
+fn encoding_tag(encoding: TextureEncoding) -> u32 {
+    match encoding {
+        TextureEncoding::Rgba8 => 0,
+        TextureEncoding::Bc7 => 1,
+        TextureEncoding::Etc2Rgb8 => 2,
+        TextureEncoding::Etc2Rgba8 => 3,
+        TextureEncoding::Astc4x4 => 4,
+    }
+}
+
+fn encoding_from_tag(tag: u32) -> Result<TextureEncoding, AbwError> {
+    Ok(match tag {
+        0 => TextureEncoding::Rgba8,
+        1 => TextureEncoding::Bc7,
+        2 => TextureEncoding::Etc2Rgb8,
+        3 => TextureEncoding::Etc2Rgba8,
+        4 => TextureEncoding::Astc4x4,
+        _ => return Err(invalid("unknown texture encoding")),
+    })
+}
+
+fn write_len(out: &mut Vec<u8>, len: usize) -> Result<(), AbwError> {
+    let len = u32::try_from(len).map_err(|_| invalid("section too large"))?;
+    out.write_u32::<LittleEndian>(len)
+        .map_err(|e| AbwError::Io(e.to_string()))
+}
+
+/// Reads a u32 length and makes sure at least `len * min_elem_size` bytes remain,
+/// so corrupt input can't trigger huge allocations.
+fn read_len(cursor: &mut Cursor<&[u8]>, min_elem_size: usize) -> Result<usize, AbwError> {
+    let len = cursor
+        .read_u32::<LittleEndian>()
+        .map_err(|_| invalid("truncated length"))? as usize;
+    let remaining = cursor.get_ref().len() - cursor.position() as usize;
+    if len.saturating_mul(min_elem_size) > remaining {
+        return Err(invalid("length exceeds payload"));
+    }
+    Ok(len)
+}
+
+fn read_bytes(cursor: &mut Cursor<&[u8]>, len: usize) -> Result<Vec<u8>, AbwError> {
+    let mut buf = vec![0u8; len];
+    cursor
+        .read_exact(&mut buf)
+        .map_err(|_| invalid("truncated data"))?;
+    Ok(buf)
+}
+
+/// Serialize a `TileState::Decoded` payload into the versioned cache layout.
+pub fn encode_decoded_tile(state: &TileState) -> Result<Vec<u8>, AbwError> {
+    let TileState::Decoded {
+        nodes,
+        meshes,
+        mesh_primitives,
+        textures,
+        materials,
+    } = state
+    else {
+        return Err(AbwError::TileLoading("Tile is not in Decoded state".into()));
+    };
+
+    let payload: usize = meshes
+        .iter()
+        .map(|m| m.vertices.len() + std::mem::size_of_val(m.indices.as_slice()))
+        .sum::<usize>()
+        + textures
+            .iter()
+            .flat_map(|t| t.levels.iter().map(Vec::len))
+            .sum::<usize>();
+    let mut out = Vec::with_capacity(payload + 1024);
+
+    let io = |e: std::io::Error| AbwError::Io(e.to_string());
+
+    out.extend_from_slice(DECODED_TILE_MAGIC);
+    out.write_u32::<LittleEndian>(DECODED_TILE_VERSION)
+        .map_err(io)?;
+
+    write_len(&mut out, nodes.len())?;
+    for node in nodes {
+        let cols: &[[f64; 4]; 4] = node.transform.as_ref();
+        for v in cols.iter().flatten() {
+            out.write_f64::<LittleEndian>(*v).map_err(io)?;
+        }
+        write_len(&mut out, node.mesh_indices.len())?;
+        for idx in &node.mesh_indices {
+            out.write_u32::<LittleEndian>(*idx as u32).map_err(io)?;
+        }
+    }
+
+    write_len(&mut out, meshes.len())?;
+    for mesh in meshes {
+        write_opt_index(&mut out, mesh.material_index).map_err(io)?;
+        out.write_u32::<LittleEndian>(mesh.layout.bits())
+            .map_err(io)?;
+        for v in mesh.center.into_iter().chain([mesh.extent]) {
+            out.write_f32::<LittleEndian>(v).map_err(io)?;
+        }
+        write_len(&mut out, mesh.vertex_count())?;
+        write_len(&mut out, mesh.indices.len())?;
+        out.extend_from_slice(&mesh.vertices);
+        for idx in &mesh.indices {
+            out.write_u32::<LittleEndian>(*idx).map_err(io)?;
+        }
+    }
+
+    write_len(&mut out, mesh_primitives.len())?;
+    for range in mesh_primitives {
+        write_len(&mut out, range.start)?;
+        write_len(&mut out, range.end)?;
+    }
+
+    write_len(&mut out, textures.len())?;
+    for texture in textures {
+        out.write_u32::<LittleEndian>(texture.width).map_err(io)?;
+        out.write_u32::<LittleEndian>(texture.height).map_err(io)?;
+        out.write_u32::<LittleEndian>(encoding_tag(texture.encoding))
+            .map_err(io)?;
+        write_len(&mut out, texture.levels.len())?;
+        for level in &texture.levels {
+            write_len(&mut out, level.len())?;
+            out.extend_from_slice(level);
+        }
+    }
+
+    write_len(&mut out, materials.len())?;
+    for material in materials {
+        write_material(&mut out, material).map_err(io)?;
+    }
+
+    Ok(out)
+}
+
+/// Parse a payload written by [`encode_decoded_tile`]. Version mismatches and
+/// malformed data are reported as errors so callers can fall back to a full decode.
+pub fn decode_decoded_tile(bytes: &[u8]) -> Result<TileState, AbwError> {
+    let mut cursor = Cursor::new(bytes);
+    let trunc = |_| invalid("truncated data");
+
+    let mut magic = [0u8; 4];
+    cursor.read_exact(&mut magic).map_err(trunc)?;
+    if &magic != DECODED_TILE_MAGIC {
+        return Err(invalid("bad magic"));
+    }
+    let version = cursor.read_u32::<LittleEndian>().map_err(trunc)?;
+    if version != DECODED_TILE_VERSION {
+        return Err(invalid(&format!(
+            "version {version}, expected {DECODED_TILE_VERSION}"
+        )));
+    }
+
+    let node_count = read_len(&mut cursor, 16 * 8 + 4)?;
+    let mut nodes = Vec::with_capacity(node_count);
+    for _ in 0..node_count {
+        let mut m = [0.0f64; 16];
+        for v in m.iter_mut() {
+            *v = cursor.read_f64::<LittleEndian>().map_err(trunc)?;
+        }
+        let index_count = read_len(&mut cursor, 4)?;
+        let mut mesh_indices = Vec::with_capacity(index_count);
+        for _ in 0..index_count {
+            mesh_indices.push(cursor.read_u32::<LittleEndian>().map_err(trunc)? as usize);
+        }
+        nodes.push(Node {
+            transform: Matrix4::new(
+                m[0], m[1], m[2], m[3], m[4], m[5], m[6], m[7], m[8], m[9], m[10], m[11], m[12],
+                m[13], m[14], m[15],
+            ),
+            mesh_indices,
+        });
+    }
+
+    let mesh_count = read_len(&mut cursor, 8 + 4 + 16 + 4 + 4)?;
+    let mut meshes = Vec::with_capacity(mesh_count);
+    for _ in 0..mesh_count {
+        let material_index = read_opt_index(&mut cursor).map_err(trunc)?;
+        let layout = VertexLayout::from_bits(cursor.read_u32::<LittleEndian>().map_err(trunc)?)
+            .ok_or_else(|| invalid("unknown vertex layout"))?;
+        let mut quantization = [0.0f32; 4];
+        cursor
+            .read_f32_into::<LittleEndian>(&mut quantization)
+            .map_err(trunc)?;
+        let vertex_count = read_len(&mut cursor, layout.stride())?;
+        let index_count = read_len(&mut cursor, 4)?;
+
+        let vertices = read_bytes(&mut cursor, vertex_count * layout.stride())?;
+
+        let mut indices = Vec::with_capacity(index_count);
+        for _ in 0..index_count {
+            indices.push(cursor.read_u32::<LittleEndian>().map_err(trunc)?);
+        }
+
+        let [cx, cy, cz, extent] = quantization;
+        meshes.push(PackedMesh {
+            layout,
+            vertices,
+            indices,
+            material_index,
+            center: [cx, cy, cz],
+            extent,
+        });
+    }
+
+    let range_count = read_len(&mut cursor, 8)?;
+    let mut mesh_primitives = Vec::with_capacity(range_count);
+    for _ in 0..range_count {
+        let start = cursor.read_u32::<LittleEndian>().map_err(trunc)? as usize;
+        let end = cursor.read_u32::<LittleEndian>().map_err(trunc)? as usize;
+        if start > end || end > mesh_count {
+            return Err(invalid("primitive range out of bounds"));
+        }
+        mesh_primitives.push(start..end);
+    }
+
+    let texture_count = read_len(&mut cursor, 16)?;
+    let mut textures = Vec::with_capacity(texture_count);
+    for _ in 0..texture_count {
+        let width = cursor.read_u32::<LittleEndian>().map_err(trunc)?;
+        let height = cursor.read_u32::<LittleEndian>().map_err(trunc)?;
+        let encoding = encoding_from_tag(cursor.read_u32::<LittleEndian>().map_err(trunc)?)?;
+        let level_count = read_len(&mut cursor, 4)?;
+        if level_count == 0 || level_count > 32 {
+            return Err(invalid("bad texture level count"));
+        }
+        let mut levels = Vec::with_capacity(level_count);
+        for level in 0..level_count {
+            let len = read_len(&mut cursor, 1)?;
+            if len != encoding.level_len(width, height, level) {
+                return Err(invalid("texture size mismatch"));
+            }
+            levels.push(read_bytes(&mut cursor, len)?);
+        }
+        textures.push(Texture {
+            width,
+            height,
+            encoding,
+            levels,
+        });
+    }
+
+    // 56 bytes of factors and flags plus five texture slots of at least 8
+    let material_count = read_len(&mut cursor, 96)?;
+    let mut materials = Vec::with_capacity(material_count);
+    for _ in 0..material_count {
+        materials.push(read_material(&mut cursor)?);
+    }
+
+    if cursor.position() as usize != bytes.len() {
+        return Err(invalid("trailing bytes"));
+    }
+
+    Ok(TileState::Decoded {
+        nodes,
+        meshes,
+        mesh_primitives,
+        textures,
+        materials,
+    })
+}
@@ -2,17 +2,17 @@ use bytes::Bytes;
 use tracing::{event, Level};
 
 use crate::{
-    cache::get_tileset_cache,
-    content::Client,
+    cache::TilesetCache,
+    content::{validate_download, Client, DIGEST_HEADERS},
     helpers::{AbwError, TileLoadingContext},
 };
 
 pub async fn download_content(
     client: &Client,
+    cache: &TilesetCache,
     content_url: &str,
 ) -> Result<(String, Bytes), AbwError> {
     // Try cache first
-    let cache = get_tileset_cache();
     if let Some((content_type, bytes)) = cache.get(content_url).await? {
         return Ok((content_type, bytes));
     }
@@ -53,23 +53,35 @@ pub async fn download_content(
         .and_then(|v| v.to_str().ok())
         .and_then(|s| s.parse::<usize>().ok());
 
+    // reqwest drops content-length when it transparently decompresses a body, and any
+    // digest the server sent covers the compressed bytes. Only check digests when we
+    // know we're looking at the body exactly as it was sent.
+    let digests: Vec<String> = DIGEST_HEADERS
+        .iter()
+        .filter(|_| expected_len.is_some())
+        .filter_map(|name| response.headers().get(*name))
+        .filter_map(|v| v.to_str().ok())
+        .map(|s| s.to_string())
+        .collect();
+
     let bytes = response.bytes().await.tile_loading(&format!(
         "Failed to access byte content from {}",
         content_url
     ))?;
 
-    if let Some(expected) = expected_len {
-        if bytes.len() < expected {
-            event!(
-                Level::ERROR,
-                "Truncated content: expected {} bytes, got {}",
-                expected,
-                bytes.len()
-            );
-        }
+    // Never cache something that would fail to load on every later hit
+    let digest_refs: Vec<&str> = digests.iter().map(String::as_str).collect();
+    if let Err(e) = validate_download(
+        content_url,
+        &content_type,
+        expected_len,
+        &digest_refs,
+        &bytes,
+    ) {
+        event!(Level::ERROR, "Rejected download: {}", e);
+        return Err(e);
     }
 
-    let cache = get_tileset_cache();
     cache
         .insert(content_url.to_string(), content_type.clone(), bytes.clone())
         .await?;
@@ -0,0 +1,365 @@
+//! The WGS84 ellipsoid drawn beneath the tiles. It is split along the web
+//! mercator quadtree so that each globe tile drapes exactly one imagery
+//! tile per layer, and refines by the screen size of that imagery's texels.
+
+use crate::{
+    content::{
+        composite_imagery, generate_mips, needs_refinement, pack_mesh, BoundingVolume,
+        ContentOptions, Imagery, ImageryRequest, Material, Node, Quantization, RefineMode,
+        TextureRef, TextureTransform, TileInfo, TileKey, TileLayer, TileState, IMAGERY_TILE_SIZE,
+    },
+    decode::{OwnedDecodedMesh, Vertex},
+    dynamics::CameraRefinementData,
+    helpers::{geodetic_to_ecef_z_up, hash_uri, is_bounding_volume_visible},
+    GlobeConfig, MipmapMode,
+};
+use cgmath::{EuclideanSpace, Matrix4, MetricSpace, Point3, Vector3};
+use std::f64::consts::PI;
+
+/// Quads along each edge of a globe tile.
+pub const GLOBE_GRID: usize = 16;
+/// Screen pixels a texel may cover before its tile is refined.
+const MAX_TEXEL_PIXELS: f64 = 1.5;
+const EQUATORIAL_RADIUS: f64 = 6_378_137.0;
+const EQUATORIAL_CIRCUMFERENCE: f64 = 2.0 * PI * EQUATORIAL_RADIUS;
+
+/// A tile of the web mercator quadtree: 2^z by 2^z tiles, rows from the
+/// north.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GlobeTileId {
+    pub z: u8,
+    pub x: u32,
+    pub y: u32,
+}
+
+impl GlobeTileId {
+    pub const ROOT: GlobeTileId = GlobeTileId { z: 0, x: 0, y: 0 };
+
+    pub fn new(z: u8, x: u32, y: u32) -> Self {
+        Self { z, x, y }
+    }
+
+    fn tiles_per_side(&self) -> u32 {
+        1 << self.z
+    }
+
+    pub fn uri(&self) -> String {
+        format!("globe/{}/{}/{}", self.z, self.x, self.y)
+    }
+
+    pub fn key(&self) -> TileKey {
+        hash_uri(&self.uri())
+    }
+
+    pub fn parent(&self) -> Option<Self> {
+        (self.z > 0).then(|| Self::new(self.z - 1, self.x / 2, self.y / 2))
+    }
+
+    /// North west, north east, south west, south east.
+    pub fn children(&self) -> [Self; 4] {
+        let (z, x, y) = (self.z + 1, self.x * 2, self.y * 2);
+        [
+            Self::new(z, x, y),
+            Self::new(z, x + 1, y),
+            Self::new(z, x, y + 1),
+            Self::new(z, x + 1, y + 1),
+        ]
+    }
+
+    /// This tile's ancestor at level `z`, or itself if it is no deeper.
+    pub fn ancestor(&self, z: u8) -> Self {
+        if z >= self.z {
+            return *self;
+        }
+        let shift = self.z - z;
+        Self::new(z, self.x >> shift, self.y >> shift)
+    }
+
+    /// Where this tile sits inside `ancestor`: the offset of its north west
+    /// corner and its size, as fractions of the ancestor's edge.
+    pub fn within(&self, ancestor: Self) -> [f64; 3] {
+        let scale = (1u64 << (self.z - ancestor.z.min(self.z))) as f64;
+        let ax = ancestor.x as f64 * scale;
+        let ay = ancestor.y as f64 * scale;
+        [
+            (self.x as f64 - ax) / scale,
+            (self.y as f64 - ay) / scale,
+            1.0 / scale,
+        ]
+    }
+
+    /// Longitude in degrees at `u` across the tile, west to east.
+    pub fn lon(&self, u: f64) -> f64 {
+        (self.x as f64 + u) / self.tiles_per_side() as f64 * 360.0 - 180.0
+    }
+
+    /// Latitude in degrees at `v` down the tile, north to south.
+    pub fn lat(&self, v: f64) -> f64 {
+        let n = PI * (1.0 - 2.0 * (self.y as f64 + v) / self.tiles_per_side() as f64);
+        n.sinh().atan().to_degrees()
+    }
+
+    /// The tile's width along its middle latitude, in meters.
+    pub fn width_meters(&self) -> f64 {
+        EQUATORIAL_CIRCUMFERENCE / self.tiles_per_side() as f64 * self.lat(0.5).to_radians().cos()
+    }
+
+    /// Ground size of one imagery texel, the tile's geometric error.
+    pub fn texel_meters(&self) -> f64 {
+        self.width_meters() / IMAGERY_TILE_SIZE as f64
+    }
+
+    /// How far the skirts hang: past the gap to a neighbor one level
+    /// coarser, whose straight edges cut under the curve, and past the
+    /// position quantization of tiles this size.
+    fn skirt_meters(&self) -> f64 {
+        let coarse_edge =
+            2.0 * EQUATORIAL_CIRCUMFERENCE / (self.tiles_per_side() as f64 * GLOBE_GRID as f64);
+        coarse_edge * coarse_edge / (8.0 * EQUATORIAL_RADIUS) * 2.0
+            + EQUATORIAL_CIRCUMFERENCE / self.tiles_per_side() as f64 * 1e-3
+    }
+
+    /// Rows of the mesh as (latitude, texture v). Tiles on the mercator's
+    /// edge get one more row out to the pole, stretching their edge texels.
+    fn rows(&self) -> Vec<(f64, f64)> {
+        let mut rows = Vec::with_capacity(GLOBE_GRID + 3);
+        if self.y == 0 {
+            rows.push((90.0, 0.0));
+        }
+        rows.extend((0..=GLOBE_GRID).map(|j| {
+            let v = j as f64 / GLOBE_GRID as f64;
+            (self.lat(v), v)
+        }));
+        if self.y + 1 == self.tiles_per_side() {
+            rows.push((-90.0, 1.0));
+        }
+        rows
+    }
+
+    /// An axis aligned box around the tile's surface and skirts.
+    pub fn bounding_volume(&self) -> BoundingVolume {
+        const SAMPLES: usize = 8;
+        let mut min = Vector3::new(f64::MAX, f64::MAX, f64::MAX);
+        let mut max = -min;
+        let mut lats: Vec<f64> = (0..=SAMPLES)
+            .map(|j| self.lat(j as f64 / SAMPLES as f64))
+            .collect();
+        lats.extend(self.rows().first().map(|r| r.0));
+        lats.extend(self.rows().last().map(|r| r.0));
+        for lat in lats {
+            for i in 0..=SAMPLES {
+                let p = geodetic_to_ecef_z_up(lat, self.lon(i as f64 / SAMPLES as f64), 0.0);
+                for axis in 0..3 {
+                    min[axis] = min[axis].min(p[axis]);
+                    max[axis] = max[axis].max(p[axis]);
+                }
+            }
+        }
+        // the surface bulges out between samples, and the skirts hang below
+        let span = EQUATORIAL_CIRCUMFERENCE / (self.tiles_per_side() * SAMPLES as u32) as f64;
+        let pad = span * span / (8.0 * EQUATORIAL_RADIUS) + self.skirt_meters();
+        let center = (min + max) * 0.5;
+        let half = (max - min) * 0.5 + Vector3::new(pad, pad, pad);
+        BoundingVolume::from_box([
+            center.x, center.y, center.z, half.x, 0.0, 0.0, 0.0, half.y, 0.0, 0.0, 0.0, half.z,
+        ])
+    }
+}
+
+/// A globe tile on its way through the workers, with the imagery for it
+/// to fetch and then composite.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlobeTile {
+    pub id: GlobeTileId,
+    pub imagery: Vec<ImageryRequest>,
+}
+
+/// Surface normal of the ellipsoid at a geodetic position.
+fn ellipsoid_normal(lat: f64, lon: f64) -> [f32; 3] {
+    let (lat, lon) = (lat.to_radians(), lon.to_radians());
+    [
+        (lat.cos() * lon.cos()) as f32,
+        (lat.cos() * lon.sin()) as f32,
+        lat.sin() as f32,
+    ]
+}
+
+/// The tile's mesh, positioned relative to the returned center: a grid on
+/// the ellipsoid with a skirt hanging from its edges to hide cracks
+/// against neighbors of other levels. Faces wind counter-clockwise seen
+/// from above.
+pub fn build_globe_mesh(tile: GlobeTileId) -> (Point3<f64>, OwnedDecodedMesh) {
+    let rows = tile.rows();
+    let columns = GLOBE_GRID + 1;
+    let center = tile.bounding_volume().center();
+    let skirt = tile.skirt_meters();
+
+    let vertex = |lat: f64, u: f64, v: f64, depth: f64| {
+        let lon = tile.lon(u);
+        let normal = ellipsoid_normal(lat, lon);
+        let p = geodetic_to_ecef_z_up(lat, lon, -depth) - center.to_vec();
+        Vertex {
+            position: [p.x as f32, p.y as f32, p.z as f32],
+            normal,
+            color: [1.0; 4],
+            texcoord0: [u as f32, v as f32],
+            texcoord1: [0.0; 2],
+        }
+    };
+
+    let mut vertices = Vec::with_capacity(rows.len() * columns * 2);
+    for &(lat, v) in &rows {
+        for i in 0..columns {
+            vertices.push(vertex(lat, i as f64 / GLOBE_GRID as f64, v, 0.0));
+        }
+    }
+
+    let mut indices = Vec::with_capacity(rows.len() * columns * 6 + columns * 24);
+    for r in 0..rows.len() - 1 {
+        for i in 0..GLOBE_GRID {
+            let nw = (r * columns + i) as u32;
+            let ne = nw + 1;
+            let sw = nw + columns as u32;
+            let se = sw + 1;
+            indices.extend([nw, sw, se, nw, se, ne]);
+        }
+    }
+
+    // the edge loop, counter-clockwise seen from above: east along the
+    // south edge, north up the east edge, then back west and south
+    let last = rows.len() - 1;
+    let mut edge: Vec<usize> = (0..columns).map(|i| last * columns + i).collect();
+    edge.extend((0..last).rev().map(|r| r * columns + GLOBE_GRID));
+    edge.extend((0..GLOBE_GRID).rev());
+    edge.extend((1..=last).map(|r| r * columns));
+    let first_skirt = vertices.len() as u32;
+    for &at in &edge {
+        let Vertex {
+            texcoord0: [u, v], ..
+        } = vertices[at];
+        let lat = rows[at / columns].0;
+        vertices.push(vertex(lat, u as f64, v as f64, skirt));
+    }
+    for k in 0..edge.len() - 1 {
+        let (p, q) = (edge[k] as u32, edge[k + 1] as u32);
+        let (p_low, q_low) = (first_skirt + k as u32, first_skirt + k as u32 + 1);
+        indices.extend([p, p_low, q_low, p, q_low, q]);
+    }
+
+    let mut mesh = OwnedDecodedMesh::from_vertices_and_indices(vertices, indices);
+    mesh.material_index = Some(0);
+    (center, mesh)
+}
+
+/// Turns a globe tile with its imagery fetched into renderable content.
+pub fn decode_globe_tile(tile: &GlobeTile, options: ContentOptions) -> TileState {
+    let (center, mesh) = build_globe_mesh(tile.id);
+    let mut texture = composite_imagery(tile.id, &tile.imagery);
+    if options.mipmaps == MipmapMode::Cpu {
+        generate_mips(&mut texture);
+    }
+
+    // positions are relative to the tile center, like an RTC tile's
+    let quantization = Quantization::for_tile(std::slice::from_ref(&mesh), true);
+    TileState::Decoded {
+        nodes: vec![Node {
+            transform: Matrix4::from_translation(center.to_vec()),
+            mesh_indices: vec![0],
+        }],
+        meshes: vec![pack_mesh(&mesh, quantization)],
+        // one mesh with one primitive
+        mesh_primitives: std::iter::once(0..1).collect(),
+        textures: vec![texture],
+        materials: vec![Material {
+            base_color_texture: Some(TextureRef {
+                index: 0,
+                tex_coord: 0,
+                transform: TextureTransform::default(),
+            }),
+            metallic_factor: 0.0,
+            ..Default::default()
+        }],
+    }
+}
+
+/// A globe tile the pager wants loaded, with the info the renderer refines by.
+pub struct GlobeSelection {
+    pub tile: GlobeTileId,
+    pub info: TileInfo,
+    /// Squared distance from the camera.
+    pub priority: f64,
+}
+
+/// The globe as the pager sees it.
+pub struct Globe {
+    pub imagery: Imagery,
+    pub max_level: u8,
+}
+
+impl Globe {
+    pub fn new(config: &GlobeConfig) -> Option<Self> {
+        config.enabled.then(|| Self {
+            imagery: Imagery::open(&config.imagery),
+            max_level: config.max_level,
+        })
+    }
+
+    /// The tile state the workers start from for `tile`.
+    pub fn to_load(&self, tile: GlobeTileId) -> TileState {
+        TileState::Globe(GlobeTile {
+            id: tile,
+            imagery: self.imagery.requests(tile),
+        })
+    }
+
+    /// Walks the quadtree from the root, refining visible tiles until their
+    /// texels are small enough on screen. Children of refined tiles are
+    /// selected whether visible or not, as the renderer only swaps to them
+    /// once all four are in. Visible tiles come first, nearest first.
+    pub fn select(&self, camera: &CameraRefinementData) -> Vec<GlobeSelection> {
+        let mut in_view = Vec::new();
+        let mut out_of_view = Vec::new();
+        let mut stack = vec![GlobeTileId::ROOT];
+        while let Some(tile) = stack.pop() {
+            let volume = tile.bounding_volume();
+            let visible = is_bounding_volume_visible(&camera.planes, &volume.to_aabb());
+            let refine = visible
+                && tile.z < self.max_level
+                && needs_refinement(
+                    camera,
+                    &volume,
+                    tile.texel_meters(),
+                    camera.screen_height,
+                    MAX_TEXEL_PIXELS,
+                );
+
+            let children = refine.then(|| tile.children());
+            if let Some(children) = children {
+                stack.extend(children);
+            }
+            let selection = GlobeSelection {
+                tile,
+                priority: camera.position.distance2(volume.center()),
+                info: TileInfo {
+                    children: children
+                        .map(|children| children.iter().map(GlobeTileId::key).collect()),
+                    parent: tile.parent().map(|parent| parent.key()),
+                    volume,
+                    refine: RefineMode::Replace,
+                    geometric_error: tile.texel_meters(),
+                    layer: TileLayer::Globe,
+                },
+            };
+            if visible {
+                in_view.push(selection);
+            } else {
+                out_of_view.push(selection);
+            }
+        }
+
+        in_view.sort_unstable_by(|a, b| a.priority.total_cmp(&b.priority));
+        out_of_view.sort_unstable_by(|a, b| a.priority.total_cmp(&b.priority));
+        in_view.extend(out_of_view);
+        in_view
+    }
+}
@@ -0,0 +1,327 @@
+//! Raster imagery for the globe: opening the configured layers, picking
+//! the source tile each globe tile drapes, and compositing them.
+
+use crate::{
+    cache::TilesetCache,
+    content::{download_content, pmtiles::PmTiles, Client, GlobeTileId, Texture, TextureEncoding},
+    helpers::AbwError,
+    ImageryLayer, ImagerySource,
+};
+use bytes::Bytes;
+use image::{imageops, RgbaImage};
+use std::{fs::File, io::BufReader, sync::Arc};
+use tracing::{event, Level};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::content::mbtiles::MbTiles;
+
+/// Edge length of a composited globe tile texture.
+pub const IMAGERY_TILE_SIZE: u32 = 256;
+
+/// sRGB color of the globe where no imagery covers it.
+pub const GLOBE_BASE_COLOR: [u8; 4] = [31, 48, 74, 255];
+
+/// A local tile archive, read by the download stage.
+pub enum LocalArchive {
+    #[cfg(not(target_arch = "wasm32"))]
+    MbTiles(MbTiles),
+    PmTiles(PmTiles),
+}
+
+impl LocalArchive {
+    fn open(source: &ImagerySource) -> Result<Option<Self>, AbwError> {
+        let open = |path: &str| {
+            File::open(path)
+                .map(BufReader::new)
+                .map_err(|e| AbwError::Io(format!("opening {path}: {e}")))
+        };
+        Ok(match source {
+            #[cfg(not(target_arch = "wasm32"))]
+            ImagerySource::MbTiles { path } => Some(LocalArchive::MbTiles(MbTiles::open(path)?)),
+            #[cfg(target_arch = "wasm32")]
+            ImagerySource::MbTiles { .. } => {
+                return Err(AbwError::InvalidInput(
+                    "MBTiles imagery needs a native build".into(),
+                ))
+            }
+            ImagerySource::PmTiles { path } => {
+                Some(LocalArchive::PmTiles(PmTiles::new(Box::new(open(path)?))?))
+            }
+            ImagerySource::Xyz { .. } | ImagerySource::Wmts { .. } => None,
+        })
+    }
+
+    fn max_zoom(&self) -> Option<u8> {
+        match self {
+            #[cfg(not(target_arch = "wasm32"))]
+            LocalArchive::MbTiles(archive) => archive.max_zoom,
+            LocalArchive::PmTiles(archive) => Some(archive.header.max_zoom),
+        }
+    }
+
+    pub fn tile(&self, tile: GlobeTileId) -> Result<Option<Vec<u8>>, AbwError> {
+        match self {
+            #[cfg(not(target_arch = "wasm32"))]
+            LocalArchive::MbTiles(archive) => archive.tile(tile),
+            LocalArchive::PmTiles(archive) => archive.tile(tile),
+        }
+    }
+}
+
+/// Where one imagery tile is fetched from.
+#[derive(Clone)]
+pub enum ImageryLocation {
+    Url(String),
+    Local(Arc<LocalArchive>, GlobeTileId),
+}
+
+impl PartialEq for ImageryLocation {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (ImageryLocation::Url(a), ImageryLocation::Url(b)) => a == b,
+            (ImageryLocation::Local(a, ta), ImageryLocation::Local(b, tb)) => {
+                Arc::ptr_eq(a, b) && ta == tb
+            }
+            _ => false,
+        }
+    }
+}
+
+impl std::fmt::Debug for ImageryLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImageryLocation::Url(url) => write!(f, "Url({url})"),
+            ImageryLocation::Local(_, tile) => write!(f, "Local({tile:?})"),
+        }
+    }
+}
+
+/// One layer's contribution to a globe tile.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageryRequest {
+    pub location: ImageryLocation,
+    /// The imagery tile fetched: the globe tile itself, or past the layer's
+    /// deepest level the ancestor it is cropped from.
+    pub source_tile: GlobeTileId,
+    pub opacity: f32,
+    /// Filled in by the download stage; None if the layer has nothing here.
+    pub bytes: Option<Bytes>,
+}
+
+impl ImageryRequest {
+    /// Fetches the encoded image, leaving `bytes` empty when the source has
+    /// no tile or the download fails.
+    pub async fn fetch(&mut self, client: &Client, cache: &TilesetCache) {
+        let result = match &self.location {
+            ImageryLocation::Url(url) => download_content(client, cache, url)
+                .await
+                .map(|(_, bytes)| Some(bytes)),
+            ImageryLocation::Local(archive, tile) => read_local(archive.clone(), *tile)
+                .await
+                .map(|tile| tile.map(Bytes::from)),
+        };
+        self.bytes = result.unwrap_or_else(|e| {
+            event!(Level::WARN, "No imagery from {:?}: {}", self.location, e);
+            None
+        });
+    }
+}
+
+/// Archive reads are blocking file I/O, so they run on tokio's blocking
+/// threads rather than holding up the downloads sharing the runtime.
+#[cfg(not(target_arch = "wasm32"))]
+async fn read_local(
+    archive: Arc<LocalArchive>,
+    tile: GlobeTileId,
+) -> Result<Option<Vec<u8>>, AbwError> {
+    tokio::task::spawn_blocking(move || archive.tile(tile))
+        .await
+        .map_err(|e| AbwError::Io(format!("reading imagery archive: {e}")))?
+}
+
+#[cfg(target_arch = "wasm32")]
+async fn read_local(
+    archive: Arc<LocalArchive>,
+    tile: GlobeTileId,
+) -> Result<Option<Vec<u8>>, AbwError> {
+    archive.tile(tile)
+}
+
+enum LayerTiles {
+    Xyz { url: String },
+    Wmts { url: String, query: String },
+    Local(Arc<LocalArchive>),
+}
+
+struct OpenLayer {
+    tiles: LayerTiles,
+    max_zoom: u8,
+    opacity: f32,
+}
+
+/// The configured imagery layers, opened once by the pager.
+pub struct Imagery {
+    layers: Vec<OpenLayer>,
+}
+
+impl Imagery {
+    /// Layers that fail to open are logged and left out.
+    pub fn open(layers: &[ImageryLayer]) -> Self {
+        let layers = layers
+            .iter()
+            .filter_map(|layer| {
+                let opacity = layer.opacity.clamp(0.0, 1.0);
+                let (tiles, max_zoom) = match &layer.source {
+                    ImagerySource::Xyz { url, max_zoom } => {
+                        (LayerTiles::Xyz { url: url.clone() }, *max_zoom)
+                    }
+                    ImagerySource::Wmts {
+                        url,
+                        layer,
+                        style,
+                        tile_matrix_set,
+                        format,
+                        max_zoom,
+                    } => {
+                        let query = format!(
+                            "SERVICE=WMTS&REQUEST=GetTile&VERSION=1.0.0&LAYER={}&STYLE={}\
+                             &TILEMATRIXSET={}&FORMAT={}",
+                            urlencoding::encode(layer),
+                            urlencoding::encode(style),
+                            urlencoding::encode(tile_matrix_set),
+                            urlencoding::encode(format),
+                        );
+                        let url = url.clone();
+                        (LayerTiles::Wmts { url, query }, *max_zoom)
+                    }
+                    local => match LocalArchive::open(local) {
+                        Ok(Some(archive)) => {
+                            let max_zoom = archive.max_zoom().unwrap_or(u8::MAX);
+                            (LayerTiles::Local(Arc::new(archive)), max_zoom)
+                        }
+                        Ok(None) => return None,
+                        Err(e) => {
+                            event!(Level::ERROR, "Skipping imagery layer {:?}: {}", local, e);
+                            return None;
+                        }
+                    },
+                };
+                Some(OpenLayer {
+                    tiles,
+                    max_zoom,
+                    opacity,
+                })
+            })
+            .collect();
+        Self { layers }
+    }
+
+    /// What to fetch for `tile` from each layer, bottom first.
+    pub fn requests(&self, tile: GlobeTileId) -> Vec<ImageryRequest> {
+        self.layers
+            .iter()
+            .map(|layer| {
+                let source_tile = tile.ancestor(layer.max_zoom);
+                let GlobeTileId { z, x, y } = source_tile;
+                let location = match &layer.tiles {
+                    LayerTiles::Xyz { url } => ImageryLocation::Url(
+                        url.replace("{z}", &z.to_string())
+                            .replace("{x}", &x.to_string())
+                            .replace("{y}", &y.to_string()),
+                    ),
+                    LayerTiles::Wmts { url, query } => {
+                        let separator = if url.contains('?') { '&' } else { '?' };
+                        ImageryLocation::Url(format!(
+                            "{url}{separator}{query}&TILEMATRIX={z}&TILEROW={y}&TILECOL={x}"
+                        ))
+                    }
+                    LayerTiles::Local(archive) => {
+                        ImageryLocation::Local(archive.clone(), source_tile)
+                    }
+                };
+                ImageryRequest {
+                    location,
+                    source_tile,
+                    opacity: layer.opacity,
+                    bytes: None,
+                }
+            })
+            .collect()
+    }
+}
+
+fn base_texture(size: u32) -> RgbaImage {
+    RgbaImage::from_pixel(size, size, image::Rgba(GLOBE_BASE_COLOR))
+}
+
+/// Blends `src` over `dst` with `opacity` scaling its alpha.
+pub fn blend_over(dst: &mut RgbaImage, src: &RgbaImage, opacity: f32) {
+    for (d, s) in dst.pixels_mut().zip(src.pixels()) {
+        let a = s.0[3] as f32 / 255.0 * opacity;
+        for c in 0..3 {
+            d.0[c] = (d.0[c] as f32 * (1.0 - a) + s.0[c] as f32 * a).round() as u8;
+        }
+    }
+}
+
+/// The part of `image` under `tile`, when `image` is the imagery for its
+/// ancestor `source`, scaled to the texture size.
+pub fn crop_to_tile(image: &RgbaImage, tile: GlobeTileId, source: GlobeTileId) -> RgbaImage {
+    let [u, v, size] = tile.within(source);
+    let (w, h) = image.dimensions();
+    let x = ((u * w as f64) as u32).min(w - 1);
+    let y = ((v * h as f64) as u32).min(h - 1);
+    let cw = ((size * w as f64).round() as u32).clamp(1, w - x);
+    let ch = ((size * h as f64).round() as u32).clamp(1, h - y);
+    let cropped = imageops::crop_imm(image, x, y, cw, ch).to_image();
+    if cropped.dimensions() == (IMAGERY_TILE_SIZE, IMAGERY_TILE_SIZE) {
+        cropped
+    } else {
+        imageops::resize(
+            &cropped,
+            IMAGERY_TILE_SIZE,
+            IMAGERY_TILE_SIZE,
+            imageops::FilterType::Triangle,
+        )
+    }
+}
+
+/// The globe tile's texture: the fetched layers blended over the base
+/// color in order. A single texel of the base color when nothing covers it.
+pub fn composite_imagery(tile: GlobeTileId, requests: &[ImageryRequest]) -> Texture {
+    let mut canvas: Option<RgbaImage> = None;
+    for request in requests {
+        let Some(bytes) = &request.bytes else {
+            continue;
+        };
+        let image = match image::load_from_memory(bytes) {
+            Ok(image) => image.to_rgba8(),
+            Err(e) => {
+                event!(
+                    Level::DEBUG,
+                    "Undecodable imagery {:?}: {}",
+                    request.location,
+                    e
+                );
+                continue;
+            }
+        };
+        if image.width() == 0 || image.height() == 0 {
+            continue;
+        }
+        let image = crop_to_tile(&image, tile, request.source_tile);
+        blend_over(
+            canvas.get_or_insert_with(|| base_texture(IMAGERY_TILE_SIZE)),
+            &image,
+            request.opacity,
+        );
+    }
+
+    let canvas = canvas.unwrap_or_else(|| base_texture(1));
+    Texture {
+        width: canvas.width(),
+        height: canvas.height(),
+        encoding: TextureEncoding::Rgba8,
+        levels: vec![canvas.into_raw()],
+    }
+}
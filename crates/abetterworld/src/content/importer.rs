@@ -1,17 +1,24 @@
 use crate::{
-    content::types::{Material, Node, Texture},
+    content::accessor::{read_accessor_f32, read_accessor_indices},
+    content::mipmaps::mip_level_count,
+    content::types::{
+        AlphaMode, Material, Node, SupportedTextureEncodings, Texture, TextureEncoding,
+        TextureRef, TextureTransform,
+    },
     decode::{self, DracoClient, OwnedDecodedMesh, Vertex},
-    render::TextureResource,
+    render::{MaterialResource, MaterialUniform, RenderState, TextureResource, TextureUploader},
 };
 use byteorder::{LittleEndian, ReadBytesExt};
-use cgmath::{Deg, Matrix4, One, Quaternion, Vector3, Vector4};
+use cgmath::{Deg, Matrix4, Quaternion, Vector3, Vector4};
 use image::GenericImageView;
 use serde_json::Value;
 use std::{
     io::{Cursor, Read},
+    ops::Range,
     sync::Arc,
 };
 use tracing::{event, Level};
+use wgpu::util::DeviceExt;
 
 pub fn parse_glb(glb: &[u8]) -> Result<(Value, Vec<u8>), Box<std::io::Error>> {
     let total_len = glb.len();
@@ -117,15 +124,28 @@ pub fn parse_glb(glb: &[u8]) -> Result<(Value, Vec<u8>), Box<std::io::Error>> {
     Ok((json, bin_buf))
 }
 
+/// Decodes every primitive of every glTF mesh into one flat list. Alongside
+/// it returns, per glTF mesh, the range of that list holding its primitives;
+/// primitives that fail to decode are left out of the range.
 pub async fn build_meshes(
     decode_client: Arc<DracoClient>,
     json: &Value,
     bin: &[u8],
-) -> Result<Vec<OwnedDecodedMesh>, std::io::Error> {
+) -> Result<(Vec<OwnedDecodedMesh>, Vec<Range<usize>>), std::io::Error> {
     let mut results = Vec::new();
+    let mut mesh_primitives = Vec::new();
+
+    // Expand EXT_meshopt_compression views so accessors read plain data
+    let decompressed = decode::meshopt::decompress_buffer_views(json, bin)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))?;
+    let (json, bin) = match &decompressed {
+        Some((json, bin)) => (json, bin.as_slice()),
+        None => (json, bin),
+    };
 
     if let Some(meshes) = json.get("meshes").and_then(|v| v.as_array()) {
         for mesh in meshes {
+            let first = results.len();
             if let Some(primitives) = mesh.get("primitives").and_then(|v| v.as_array()) {
                 for primitive in primitives {
                     let draco = primitive
@@ -150,10 +170,11 @@ pub async fn build_meshes(
                     }
                 }
             }
+            mesh_primitives.push(first..results.len());
         }
     }
 
-    Ok(results)
+    Ok((results, mesh_primitives))
 }
 
 async fn decode_draco_primitive(
@@ -193,130 +214,24 @@ async fn decode_draco_primitive(
     Ok(Some(mesh_data))
 }
 
-/// Read a float slice from an accessor (supports f32 component type 5126).
-fn read_accessor_f32(json: &Value, bin: &[u8], accessor_idx: u64) -> Option<Vec<f32>> {
-    let accessor = json.get("accessors")?.get(accessor_idx as usize)?;
-    let buffer_view_idx = accessor.get("bufferView")?.as_u64()?;
-    let buffer_view = json.get("bufferViews")?.get(buffer_view_idx as usize)?;
-    let component_type = accessor.get("componentType")?.as_u64()?;
-    let count = accessor.get("count")?.as_u64()? as usize;
-    let acc_type = accessor.get("type")?.as_str()?;
-
-    let components_per_element = match acc_type {
-        "SCALAR" => 1,
-        "VEC2" => 2,
-        "VEC3" => 3,
-        "VEC4" => 4,
-        _ => return None,
-    };
-
-    let bv_offset = buffer_view
-        .get("byteOffset")
-        .and_then(|v| v.as_u64())
-        .unwrap_or(0) as usize;
-    let acc_offset = accessor
-        .get("byteOffset")
-        .and_then(|v| v.as_u64())
-        .unwrap_or(0) as usize;
-    let stride = buffer_view
-        .get("byteStride")
-        .and_then(|v| v.as_u64())
-        .map(|s| s as usize);
-
-    let total_floats = count * components_per_element;
-
-    match component_type {
-        5126 => {
-            // FLOAT
-            let element_byte_size = components_per_element * 4;
-            let byte_stride = stride.unwrap_or(element_byte_size);
-            let mut result = Vec::with_capacity(total_floats);
-            for i in 0..count {
-                let base = bv_offset + acc_offset + i * byte_stride;
-                for c in 0..components_per_element {
-                    let off = base + c * 4;
-                    if off + 4 > bin.len() {
-                        return None;
-                    }
-                    let val = f32::from_le_bytes([
-                        bin[off],
-                        bin[off + 1],
-                        bin[off + 2],
-                        bin[off + 3],
-                    ]);
-                    result.push(val);
-                }
-            }
-            Some(result)
-        }
-        _ => None,
-    }
-}
-
-/// Read indices from an accessor (supports u16=5123 and u32=5125).
-fn read_accessor_indices(json: &Value, bin: &[u8], accessor_idx: u64) -> Option<Vec<u32>> {
-    let accessor = json.get("accessors")?.get(accessor_idx as usize)?;
-    let buffer_view_idx = accessor.get("bufferView")?.as_u64()?;
-    let buffer_view = json.get("bufferViews")?.get(buffer_view_idx as usize)?;
-    let component_type = accessor.get("componentType")?.as_u64()?;
-    let count = accessor.get("count")?.as_u64()? as usize;
-
-    let bv_offset = buffer_view
-        .get("byteOffset")
-        .and_then(|v| v.as_u64())
-        .unwrap_or(0) as usize;
-    let acc_offset = accessor
-        .get("byteOffset")
-        .and_then(|v| v.as_u64())
-        .unwrap_or(0) as usize;
-    let stride = buffer_view
-        .get("byteStride")
-        .and_then(|v| v.as_u64())
-        .map(|s| s as usize);
-
-    match component_type {
-        5123 => {
-            // UNSIGNED_SHORT
-            let byte_stride = stride.unwrap_or(2);
-            let mut result = Vec::with_capacity(count);
-            for i in 0..count {
-                let off = bv_offset + acc_offset + i * byte_stride;
-                if off + 2 > bin.len() {
-                    return None;
-                }
-                let val = u16::from_le_bytes([bin[off], bin[off + 1]]);
-                result.push(val as u32);
-            }
-            Some(result)
-        }
-        5125 => {
-            // UNSIGNED_INT
-            let byte_stride = stride.unwrap_or(4);
-            let mut result = Vec::with_capacity(count);
-            for i in 0..count {
-                let off = bv_offset + acc_offset + i * byte_stride;
-                if off + 4 > bin.len() {
-                    return None;
-                }
-                let val = u32::from_le_bytes([bin[off], bin[off + 1], bin[off + 2], bin[off + 3]]);
-                result.push(val);
-            }
-            Some(result)
+/// Reads an optional vertex attribute, dropping it (with a warning) when it
+/// can't be read or has an unexpected number of components.
+fn read_attribute(
+    json: &Value,
+    bin: &[u8],
+    idx: u64,
+    widths: &[usize],
+) -> Option<(Vec<f32>, usize)> {
+    match read_accessor_f32(json, bin, idx) {
+        Ok((values, n)) if widths.contains(&n) => Some((values, n)),
+        Ok((_, n)) => {
+            event!(Level::WARN, "ignoring attribute with {n} components");
+            None
         }
-        5121 => {
-            // UNSIGNED_BYTE
-            let byte_stride = stride.unwrap_or(1);
-            let mut result = Vec::with_capacity(count);
-            for i in 0..count {
-                let off = bv_offset + acc_offset + i * byte_stride;
-                if off >= bin.len() {
-                    return None;
-                }
-                result.push(bin[off] as u32);
-            }
-            Some(result)
+        Err(e) => {
+            event!(Level::WARN, "ignoring attribute: {e}");
+            None
         }
-        _ => None,
     }
 }
 
@@ -336,8 +251,12 @@ fn decode_accessor_primitive(
         None => return Ok(None),
     };
     let positions = match read_accessor_f32(json, bin, pos_idx) {
-        Some(p) => p,
-        None => return Ok(None),
+        Ok((p, 3)) => p,
+        Ok(_) => return Ok(None),
+        Err(e) => {
+            event!(Level::WARN, "skipping primitive: {e}");
+            return Ok(None);
+        }
     };
     let vertex_count = positions.len() / 3;
 
@@ -345,25 +264,44 @@ fn decode_accessor_primitive(
     let normals = attributes
         .get("NORMAL")
         .and_then(|v| v.as_u64())
-        .and_then(|idx| read_accessor_f32(json, bin, idx));
+        .and_then(|idx| read_attribute(json, bin, idx, &[3]));
 
     // TEXCOORD_0 (optional)
     let texcoords0 = attributes
         .get("TEXCOORD_0")
         .and_then(|v| v.as_u64())
-        .and_then(|idx| read_accessor_f32(json, bin, idx));
+        .and_then(|idx| read_attribute(json, bin, idx, &[2]));
 
     // TEXCOORD_1 (optional)
     let texcoords1 = attributes
         .get("TEXCOORD_1")
         .and_then(|v| v.as_u64())
-        .and_then(|idx| read_accessor_f32(json, bin, idx));
+        .and_then(|idx| read_attribute(json, bin, idx, &[2]));
 
     // COLOR_0 (optional)
     let colors = attributes
         .get("COLOR_0")
         .and_then(|v| v.as_u64())
-        .and_then(|idx| read_accessor_f32(json, bin, idx));
+        .and_then(|idx| read_attribute(json, bin, idx, &[3, 4]));
+
+    // Every attribute has to describe the same vertices
+    for (name, attribute) in [
+        ("NORMAL", &normals),
+        ("TEXCOORD_0", &texcoords0),
+        ("TEXCOORD_1", &texcoords1),
+        ("COLOR_0", &colors),
+    ] {
+        if let Some((values, n)) = attribute {
+            if values.len() != vertex_count * n {
+                let count = values.len() / n;
+                event!(
+                    Level::WARN,
+                    "skipping primitive: {name} has {count} vertices, POSITION {vertex_count}"
+                );
+                return Ok(None);
+            }
+        }
+    }
 
     // Build vertices
     let mut vertices = Vec::with_capacity(vertex_count);
@@ -375,12 +313,12 @@ fn decode_accessor_primitive(
         ];
         let normal = normals
             .as_ref()
-            .map(|n| [n[i * 3], n[i * 3 + 1], n[i * 3 + 2]])
+            .map(|(n, _)| [n[i * 3], n[i * 3 + 1], n[i * 3 + 2]])
             .unwrap_or([0.0, 0.0, 1.0]);
         let color = colors
             .as_ref()
-            .map(|c| {
-                let components = c.len() / vertex_count;
+            .map(|(c, components)| {
+                let components = *components;
                 if components >= 4 {
                     [c[i * components], c[i * components + 1], c[i * components + 2], c[i * components + 3]]
                 } else if components == 3 {
@@ -392,11 +330,11 @@ fn decode_accessor_primitive(
             .unwrap_or([1.0, 1.0, 1.0, 1.0]);
         let texcoord0 = texcoords0
             .as_ref()
-            .map(|t| [t[i * 2], t[i * 2 + 1]])
+            .map(|(t, _)| [t[i * 2], t[i * 2 + 1]])
             .unwrap_or([0.0, 0.0]);
         let texcoord1 = texcoords1
             .as_ref()
-            .map(|t| [t[i * 2], t[i * 2 + 1]])
+            .map(|(t, _)| [t[i * 2], t[i * 2 + 1]])
             .unwrap_or([0.0, 0.0]);
 
         vertices.push(Vertex {
@@ -411,145 +349,365 @@ fn decode_accessor_primitive(
     // Indices (optional — generate sequential if missing)
     let indices = if let Some(idx_accessor) = primitive.get("indices").and_then(|v| v.as_u64()) {
         match read_accessor_indices(json, bin, idx_accessor) {
-            Some(idx) => idx,
-            None => return Ok(None),
+            Ok(idx) => idx,
+            Err(e) => {
+                event!(Level::WARN, "skipping primitive: {e}");
+                return Ok(None);
+            }
         }
     } else {
         (0..vertex_count as u32).collect()
     };
 
-    let mut mesh = OwnedDecodedMesh::from_vertices_and_indices(vertices, indices);
+    let mesh = OwnedDecodedMesh::from_vertices_and_indices(vertices, indices);
     Ok(Some(mesh))
 }
 
-pub fn parse_textures_from_gltf(json: &Value, bin: &[u8]) -> Result<Vec<Texture>, std::io::Error> {
+/// Decodes one texture per glTF `textures` entry, so material texture indices
+/// can be used directly. `KHR_texture_basisu` sources are preferred; when one
+/// can't be read the plain `source` image is used instead.
+pub fn parse_textures_from_gltf(
+    json: &Value,
+    bin: &[u8],
+    supported: SupportedTextureEncodings,
+) -> Result<Vec<Texture>, std::io::Error> {
     let mut textures = Vec::new();
 
-    if let Some(images) = json.get("images").and_then(|v| v.as_array()) {
-        for image in images {
-            let buffer_view_idx = image.get("bufferView").and_then(|v| v.as_u64());
-            let mime_type = image
-                .get("mimeType")
-                .and_then(|v| v.as_str())
-                .unwrap_or("image/png");
-            if let Some(buffer_view_idx) = buffer_view_idx {
-                if let Some(buffer_view) = json
-                    .get("bufferViews")
-                    .and_then(|v| v.get(buffer_view_idx as usize))
-                {
-                    let offset = buffer_view
-                        .get("byteOffset")
-                        .and_then(|v| v.as_u64())
-                        .unwrap_or(0);
-                    let length = buffer_view
-                        .get("byteLength")
-                        .and_then(|v| v.as_u64())
-                        .unwrap_or(0);
-                    let start = offset as usize;
-                    let end = (offset + length) as usize;
-
-                    if end <= bin.len() && start < end {
-                        let img_bytes = &bin[start..end];
-
-                        let dyn_img = match mime_type {
-                            "image/png" | "image/jpeg" => image::load_from_memory(img_bytes)
-                                .map_err(|e| {
-                                    std::io::Error::new(std::io::ErrorKind::InvalidData, e)
-                                })?,
-                            _ => {
-                                return Err(std::io::Error::new(
-                                    std::io::ErrorKind::InvalidData,
-                                    "Unsupported image type",
-                                ))
-                            }
-                        };
-
-                        let rgba = dyn_img.to_rgba8();
-                        let (width, height) = dyn_img.dimensions();
-
-                        textures.push(Texture {
-                            width,
-                            height,
-                            rgba: rgba.into_raw(),
-                        });
+    if let Some(textures_json) = json.get("textures").and_then(|v| v.as_array()) {
+        for texture_json in textures_json {
+            let basisu = texture_json
+                .get("extensions")
+                .and_then(|v| v.get("KHR_texture_basisu"))
+                .and_then(|v| v.get("source"))
+                .and_then(|v| v.as_u64());
+            let source = texture_json.get("source").and_then(|v| v.as_u64());
+
+            let mut decoded = None;
+            for image_idx in [basisu, source].into_iter().flatten() {
+                match decode_image(json, bin, image_idx as usize, supported) {
+                    Ok(texture) => {
+                        decoded = Some(texture);
+                        break;
                     }
+                    Err(e) => event!(Level::WARN, "Failed to decode image {}: {}", image_idx, e),
                 }
             }
+
+            // Keep indices aligned with the glTF so the geometry still draws
+            textures.push(decoded.unwrap_or_else(Texture::white));
         }
     }
 
     Ok(textures)
 }
 
+fn decode_image(
+    json: &Value,
+    bin: &[u8],
+    image_idx: usize,
+    supported: SupportedTextureEncodings,
+) -> Result<Texture, std::io::Error> {
+    let invalid = |msg: String| std::io::Error::new(std::io::ErrorKind::InvalidData, msg);
+
+    let image = json
+        .get("images")
+        .and_then(|v| v.get(image_idx))
+        .ok_or_else(|| invalid(format!("Missing image {}", image_idx)))?;
+    let buffer_view = image
+        .get("bufferView")
+        .and_then(|v| v.as_u64())
+        .and_then(|idx| json.get("bufferViews").and_then(|v| v.get(idx as usize)))
+        .ok_or_else(|| invalid("Image has no bufferView".into()))?;
+
+    let offset = buffer_view
+        .get("byteOffset")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(0) as usize;
+    let length = buffer_view
+        .get("byteLength")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(0) as usize;
+    let img_bytes = offset
+        .checked_add(length)
+        .and_then(|end| bin.get(offset..end))
+        .filter(|b| !b.is_empty())
+        .ok_or_else(|| invalid("Image bufferView out of range".into()))?;
+
+    let mime_type = image
+        .get("mimeType")
+        .and_then(|v| v.as_str())
+        .unwrap_or("image/png");
+
+    match mime_type {
+        "image/ktx2" => {
+            decode::basisu::transcode_ktx2(img_bytes, supported).map_err(|e| invalid(e.to_string()))
+        }
+        "image/png" | "image/jpeg" => {
+            let dyn_img = image::load_from_memory(img_bytes)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+            let (width, height) = dyn_img.dimensions();
+            Ok(Texture {
+                width,
+                height,
+                encoding: TextureEncoding::Rgba8,
+                levels: vec![dyn_img.to_rgba8().into_raw()],
+            })
+        }
+        _ => Err(invalid(format!("Unsupported image type {}", mime_type))),
+    }
+}
+
+/// Flags which of a tile's `texture_count` textures only the materials' data
+/// slots read, so they upload without sRGB decoding. A texture also sampled
+/// as color stays sRGB.
+pub fn linear_textures(materials: &[Material], texture_count: usize) -> Vec<bool> {
+    let mut linear = vec![false; texture_count];
+    for index in materials.iter().flat_map(Material::linear_textures) {
+        if let Some(l) = linear.get_mut(index) {
+            *l = true;
+        }
+    }
+    for material in materials {
+        for t in [material.base_color_texture, material.emissive_texture]
+            .into_iter()
+            .flatten()
+        {
+            if let Some(l) = linear.get_mut(t.index) {
+                *l = false;
+            }
+        }
+    }
+    linear
+}
+
+/// Uploads decoded textures, all sharing the uploader's sampler. Textures
+/// flagged in `linear` drop the surface format's sRGB decoding. Single-level
+/// RGBA8 textures get their mip chain rendered here when the uploader has a
+/// GPU mip generator.
 pub fn upload_textures_to_gpu(
     device: &wgpu::Device,
     queue: &wgpu::Queue,
     texture_data: &[Texture],
-    texture_bind_group_layout: &wgpu::BindGroupLayout,
-    surface_format: wgpu::TextureFormat,
+    linear: &[bool],
+    uploader: &TextureUploader,
 ) -> Vec<TextureResource> {
-    texture_data
+    let mut encoder = None;
+
+    let resources = texture_data
         .iter()
-        .map(|td| {
+        .enumerate()
+        .map(|(i, td)| {
+            let format = match td.encoding.wgpu_format(uploader.surface_format) {
+                format if linear.get(i) == Some(&true) => format.remove_srgb_suffix(),
+                format => format,
+            };
+            let (block_dim, block_bytes) = td.encoding.block();
             let texture_size = wgpu::Extent3d {
                 width: td.width,
                 height: td.height,
                 depth_or_array_layers: 1,
             };
+            let full_chain = mip_level_count(td.width, td.height);
+            let generator = uploader
+                .mip_generator(format)
+                .filter(|_| td.levels.len() == 1 && full_chain > 1);
+            let mut usage = wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST;
+            if generator.is_some() {
+                usage |= wgpu::TextureUsages::RENDER_ATTACHMENT;
+            }
             let texture = device.create_texture(&wgpu::TextureDescriptor {
                 label: Some("GLTF Texture"),
                 size: texture_size,
-                mip_level_count: 1,
+                mip_level_count: match generator {
+                    Some(_) => full_chain,
+                    None => td.levels.len() as u32,
+                },
                 sample_count: 1,
                 dimension: wgpu::TextureDimension::D2,
-                format: surface_format,
-                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                format,
+                usage,
                 view_formats: &[],
             });
-            queue.write_texture(
-                wgpu::TexelCopyTextureInfo {
-                    texture: &texture,
-                    mip_level: 0,
-                    origin: wgpu::Origin3d::ZERO,
-                    aspect: wgpu::TextureAspect::All,
-                },
-                &td.rgba,
-                wgpu::TexelCopyBufferLayout {
-                    offset: 0,
-                    bytes_per_row: Some(4 * td.width),
-                    rows_per_image: Some(td.height),
-                },
-                texture_size,
-            );
-            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-            let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-                label: Some("GLTF Texture Sampler"),
-                ..Default::default()
-            });
-
-            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-                layout: texture_bind_group_layout,
-                entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: wgpu::BindingResource::TextureView(&view),
+            for (level, data) in td.levels.iter().enumerate() {
+                // Block formats copy whole blocks, even for mips smaller than one
+                let mip_size =
+                    texture_size.mip_level_size(level as u32, wgpu::TextureDimension::D2);
+                let blocks_x = mip_size.width.div_ceil(block_dim);
+                let blocks_y = mip_size.height.div_ceil(block_dim);
+                queue.write_texture(
+                    wgpu::TexelCopyTextureInfo {
+                        texture: &texture,
+                        mip_level: level as u32,
+                        origin: wgpu::Origin3d::ZERO,
+                        aspect: wgpu::TextureAspect::All,
                     },
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: wgpu::BindingResource::Sampler(&sampler),
+                    data,
+                    wgpu::TexelCopyBufferLayout {
+                        offset: 0,
+                        bytes_per_row: Some(blocks_x * block_bytes),
+                        rows_per_image: Some(blocks_y),
                     },
-                ],
-                label: Some("Texture Bind Group"),
-            });
+                    mip_size.physical_size(format),
+                );
+            }
+            if let Some(generator) = generator {
+                let encoder = encoder.get_or_insert_with(|| {
+                    device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                        label: Some("Mipmap Encoder"),
+                    })
+                });
+                generator.generate(device, encoder, &texture, full_chain);
+            }
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
 
             TextureResource {
                 texture,
                 view,
-                sampler,
-                bind_group,
+                sampler: uploader.sampler.clone(),
             }
         })
-        .collect()
+        .collect();
+
+    // Level 0 writes land before the submitted passes read them
+    if let Some(encoder) = encoder {
+        queue.submit(Some(encoder.finish()));
+    }
+    resources
+}
+
+/// Creates one bind group per material, binding its five texture slots and
+/// a uniform block with its factors and UV transforms, plus a default for
+/// meshes without a material. Missing textures sample a white texel, or a
+/// flat one for normal maps.
+pub fn build_material_resources(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    materials: &[Material],
+    textures: &[TextureResource],
+    material_bind_group_layout: &wgpu::BindGroupLayout,
+    uploader: &TextureUploader,
+) -> (Vec<MaterialResource>, MaterialResource) {
+    let mut fallbacks: Option<Vec<TextureResource>> = None;
+
+    let mut build = |material: &Material| {
+        // white, then a flat normal
+        let fallback = fallbacks.get_or_insert_with(|| {
+            upload_textures_to_gpu(
+                device,
+                queue,
+                &[Texture::white(), Texture::flat_normal()],
+                &[false, true],
+                uploader,
+            )
+        });
+        let views: Vec<&wgpu::TextureView> = material
+            .textures()
+            .iter()
+            .enumerate()
+            .map(|(slot, t)| match t.and_then(|t| textures.get(t.index)) {
+                Some(texture) => &texture.view,
+                // slot 2 is the normal map
+                None if slot == 2 => &fallback[1].view,
+                None => &fallback[0].view,
+            })
+            .collect();
+
+        let uniform = MaterialUniform::new(material);
+        let uniform = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Material Uniform"),
+            contents: bytemuck::bytes_of(&uniform),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let mut entries = vec![
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&fallback[0].sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: uniform.as_entire_binding(),
+            },
+        ];
+        // base color at binding 0, the other slots from binding 3 on
+        for (slot, view) in views.into_iter().enumerate() {
+            entries.push(wgpu::BindGroupEntry {
+                binding: if slot == 0 { 0 } else { slot as u32 + 2 },
+                resource: wgpu::BindingResource::TextureView(view),
+            });
+        }
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: material_bind_group_layout,
+            entries: &entries,
+            label: Some("Material Bind Group"),
+        });
+
+        MaterialResource {
+            uniform,
+            bind_group,
+            state: RenderState::new(material),
+        }
+    };
+
+    let resources = materials.iter().map(&mut build).collect();
+    let default = build(&Material::default());
+    (resources, default)
+}
+
+/// Reads a glTF textureInfo, applying any `KHR_texture_transform` (whose
+/// `texCoord` overrides the textureInfo's own).
+fn parse_texture_ref(info: &Value) -> Option<TextureRef> {
+    let index = info.get("index")?.as_u64()? as usize;
+    let mut tex_coord = info.get("texCoord").and_then(|v| v.as_u64()).unwrap_or(0);
+    let mut transform = TextureTransform::default();
+
+    if let Some(ext) = info
+        .get("extensions")
+        .and_then(|v| v.get("KHR_texture_transform"))
+    {
+        let pair = |key: &str| {
+            let values = ext.get(key)?.as_array()?;
+            match values.as_slice() {
+                [x, y] => Some([x.as_f64()? as f32, y.as_f64()? as f32]),
+                _ => None,
+            }
+        };
+        if let Some(offset) = pair("offset") {
+            transform.offset = offset;
+        }
+        if let Some(scale) = pair("scale") {
+            transform.scale = scale;
+        }
+        if let Some(rotation) = ext.get("rotation").and_then(|v| v.as_f64()) {
+            transform.rotation = rotation as f32;
+        }
+        if let Some(t) = ext.get("texCoord").and_then(|v| v.as_u64()) {
+            tex_coord = t;
+        }
+    }
+
+    if tex_coord > 1 {
+        event!(
+            Level::WARN,
+            "TEXCOORD_{} isn't loaded, sampling TEXCOORD_0 instead",
+            tex_coord
+        );
+        tex_coord = 0;
+    }
+
+    Some(TextureRef {
+        index,
+        tex_coord: tex_coord as u32,
+        transform,
+    })
+}
+
+/// Reads a glTF number, falling back to the spec default.
+fn f32_or(value: Option<&Value>, default: f32) -> f32 {
+    value.and_then(|v| v.as_f64()).map_or(default, |v| v as f32)
+}
+
+fn f32_array_or<const N: usize>(value: Option<&Value>, default: [f32; N]) -> [f32; N] {
+    f64_array::<N>(value).map_or(default, |v| v.map(|c| c as f32))
 }
 
 pub fn build_materials(json: &Value) -> Result<Vec<Material>, std::io::Error> {
@@ -557,22 +715,50 @@ pub fn build_materials(json: &Value) -> Result<Vec<Material>, std::io::Error> {
 
     if let Some(materials_json) = json.get("materials").and_then(|v| v.as_array()) {
         for mat in materials_json {
-            // Default to None if not found
-            let mut base_color_texture_index = None;
-
-            // Look for baseColorTexture index
-            if let Some(pbr) = mat.get("pbrMetallicRoughness") {
-                if let Some(tex) = pbr.get("baseColorTexture") {
-                    if let Some(idx) = tex.get("index").and_then(|v| v.as_u64()) {
-                        base_color_texture_index = Some(idx as usize);
-                    }
+            let defaults = Material::default();
+            let pbr = mat.get("pbrMetallicRoughness");
+            let pbr_field = |key: &str| pbr.and_then(|pbr| pbr.get(key));
+            let texture = |info: Option<&Value>| info.and_then(parse_texture_ref);
+
+            let alpha_mode = match mat.get("alphaMode").and_then(|v| v.as_str()) {
+                Some("MASK") => AlphaMode::Mask,
+                Some("BLEND") => AlphaMode::Blend,
+                Some("OPAQUE") | None => AlphaMode::Opaque,
+                Some(other) => {
+                    event!(Level::WARN, "Unknown alphaMode {}, using OPAQUE", other);
+                    AlphaMode::Opaque
                 }
-            }
-
-            // Add more parsing as needed for other attributes...
+            };
 
+            let normal = mat.get("normalTexture");
+            let occlusion = mat.get("occlusionTexture");
             materials.push(Material {
-                base_color_texture_index,
+                base_color_factor: f32_array_or(
+                    pbr_field("baseColorFactor"),
+                    defaults.base_color_factor,
+                ),
+                base_color_texture: texture(pbr_field("baseColorTexture")),
+                metallic_factor: f32_or(pbr_field("metallicFactor"), defaults.metallic_factor),
+                roughness_factor: f32_or(pbr_field("roughnessFactor"), defaults.roughness_factor),
+                metallic_roughness_texture: texture(pbr_field("metallicRoughnessTexture")),
+                normal_texture: texture(normal),
+                normal_scale: f32_or(normal.and_then(|n| n.get("scale")), defaults.normal_scale),
+                occlusion_texture: texture(occlusion),
+                occlusion_strength: f32_or(
+                    occlusion.and_then(|o| o.get("strength")),
+                    defaults.occlusion_strength,
+                ),
+                emissive_factor: f32_array_or(mat.get("emissiveFactor"), defaults.emissive_factor),
+                emissive_texture: texture(mat.get("emissiveTexture")),
+                alpha_mode,
+                alpha_cutoff: f32_or(mat.get("alphaCutoff"), defaults.alpha_cutoff),
+                double_sided: mat
+                    .get("doubleSided")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(defaults.double_sided),
+                unlit: mat
+                    .get("extensions")
+                    .is_some_and(|e| e.get("KHR_materials_unlit").is_some()),
             });
         }
     }
@@ -580,87 +766,182 @@ pub fn build_materials(json: &Value) -> Result<Vec<Material>, std::io::Error> {
     Ok(materials)
 }
 
-pub fn build_nodes(json: &Value) -> Result<Vec<Node>, std::io::Error> {
+/// Unwraps a legacy Batched 3D Model, returning its `RTC_CENTER` (if any)
+/// and the embedded GLB. Anything without the `b3dm` magic is passed through
+/// untouched.
+pub fn unwrap_b3dm(bytes: &[u8]) -> Result<(Option<Vector3<f64>>, &[u8]), std::io::Error> {
+    if !bytes.starts_with(b"b3dm") {
+        return Ok((None, bytes));
+    }
+
+    let invalid = |msg: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, msg);
+    if bytes.len() < 28 {
+        return Err(invalid("b3dm header is truncated"));
+    }
+    let word = |i: usize| u32::from_le_bytes(bytes[i * 4..i * 4 + 4].try_into().unwrap()) as usize;
+    let (feature_json_len, feature_bin_len) = (word(3), word(4));
+    let (batch_json_len, batch_bin_len) = (word(5), word(6));
+
+    // lengths near usize::MAX on 32 bit targets would wrap past the check
+    let past_end = || invalid("b3dm tables run past the end of the file");
+    let feature_json_end = 28usize.checked_add(feature_json_len).ok_or_else(past_end)?;
+    let glb_start = [feature_bin_len, batch_json_len, batch_bin_len]
+        .into_iter()
+        .try_fold(feature_json_end, usize::checked_add)
+        .ok_or_else(past_end)?;
+    if glb_start > bytes.len() {
+        return Err(past_end());
+    }
+
+    let mut rtc_center = None;
+    if feature_json_len > 0 {
+        let feature_table: Value = serde_json::from_slice(&bytes[28..feature_json_end])
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        rtc_center = match feature_table.get("RTC_CENTER") {
+            Some(Value::Array(_)) => f64_array::<3>(feature_table.get("RTC_CENTER")),
+            // A binary reference points at three f32s in the feature table body
+            Some(reference) => reference
+                .get("byteOffset")
+                .and_then(|v| v.as_u64())
+                .and_then(|offset| {
+                    let body = &bytes[feature_json_end..feature_json_end + feature_bin_len];
+                    let start = offset as usize;
+                    let xyz = body.get(start..start.checked_add(12)?)?;
+                    Some(std::array::from_fn(|i| {
+                        f32::from_le_bytes(xyz[i * 4..i * 4 + 4].try_into().unwrap()) as f64
+                    }))
+                }),
+            None => None,
+        }
+        .map(Vector3::from);
+    }
+
+    Ok((rtc_center, &bytes[glb_start..]))
+}
+
+/// Reads a JSON array of exactly `N` numbers.
+fn f64_array<const N: usize>(value: Option<&Value>) -> Option<[f64; N]> {
+    let values = value?.as_array()?;
+    if values.len() != N {
+        return None;
+    }
+    let mut out = [0.0; N];
+    for (o, v) in out.iter_mut().zip(values) {
+        *o = v.as_f64()?;
+    }
+    Some(out)
+}
+
+/// A node's local transform: `matrix` when present, otherwise T * R * S.
+fn local_transform(node_json: &Value) -> Matrix4<f64> {
+    if let Some(m) = f64_array::<16>(node_json.get("matrix")) {
+        return Matrix4::from_cols(
+            Vector4::new(m[0], m[1], m[2], m[3]),
+            Vector4::new(m[4], m[5], m[6], m[7]),
+            Vector4::new(m[8], m[9], m[10], m[11]),
+            Vector4::new(m[12], m[13], m[14], m[15]),
+        );
+    }
+
+    let [tx, ty, tz] = f64_array(node_json.get("translation")).unwrap_or([0.0; 3]);
+    let [rx, ry, rz, rw] = f64_array(node_json.get("rotation")).unwrap_or([0.0, 0.0, 0.0, 1.0]);
+    let [sx, sy, sz] = f64_array(node_json.get("scale")).unwrap_or([1.0; 3]);
+
+    Matrix4::from_translation(Vector3::new(tx, ty, tz))
+        * Matrix4::from(Quaternion::new(rw, rx, ry, rz))
+        * Matrix4::from_nonuniform_scale(sx, sy, sz)
+}
+
+/// The tile's RTC offset, `CESIUM_RTC` plus any b3dm `RTC_CENTER`, or None
+/// when its positions are absolute.
+pub fn rtc_offset(json: &Value, rtc_center: Option<Vector3<f64>>) -> Option<Vector3<f64>> {
+    let cesium_rtc = f64_array::<3>(
+        json.get("extensions")
+            .and_then(|e| e.get("CESIUM_RTC"))
+            .and_then(|r| r.get("center")),
+    )
+    .map(Vector3::from);
+    match (rtc_center, cesium_rtc) {
+        (Some(a), Some(b)) => Some(a + b),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Walks the glTF scene from its roots and emits one `Node` per mesh-bearing
+/// node with its world transform. The RTC offset (`CESIUM_RTC` plus any b3dm
+/// `RTC_CENTER`) and the y-up to z-up rotation are applied once, at the root.
+pub fn build_nodes(
+    json: &Value,
+    rtc_center: Option<Vector3<f64>>,
+) -> Result<Vec<Node>, std::io::Error> {
+    let empty = Vec::new();
+    let nodes_json = json
+        .get("nodes")
+        .and_then(|v| v.as_array())
+        .unwrap_or(&empty);
+
+    let children = |node_json: &Value| -> Vec<usize> {
+        node_json
+            .get("children")
+            .and_then(|v| v.as_array())
+            .map(|c| {
+                c.iter()
+                    .filter_map(|i| i.as_u64())
+                    .map(|i| i as usize)
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+
+    // The default scene's roots; without scenes, every node nobody parents
+    let scene = json.get("scene").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+    let roots: Vec<usize> = match json
+        .get("scenes")
+        .and_then(|v| v.get(scene))
+        .and_then(|s| s.get("nodes"))
+        .and_then(|v| v.as_array())
+    {
+        Some(roots) => roots
+            .iter()
+            .filter_map(|i| i.as_u64())
+            .map(|i| i as usize)
+            .collect(),
+        None => {
+            let mut parented = vec![false; nodes_json.len()];
+            for child in nodes_json.iter().flat_map(children) {
+                if let Some(p) = parented.get_mut(child) {
+                    *p = true;
+                }
+            }
+            (0..nodes_json.len()).filter(|&i| !parented[i]).collect()
+        }
+    };
+
+    let offset = rtc_offset(json, rtc_center).unwrap_or(Vector3::new(0.0, 0.0, 0.0));
+    let root_transform = Matrix4::from_translation(offset) * Matrix4::from_angle_x(Deg(90.0));
+
     let mut nodes = Vec::new();
-    let y_up_to_z_up = Matrix4::from_angle_x(Deg(90.0));
-    let rotate_around_z = Matrix4::from_angle_y(Deg(180.0));
-
-    if let Some(nodes_json) = json.get("nodes").and_then(|v| v.as_array()) {
-        for node_json in nodes_json {
-            // Check for a 4x4 "matrix" first (16 floats, column major)
-            let matrix =
-                if let Some(matrix_json) = node_json.get("matrix").and_then(|v| v.as_array()) {
-                    if matrix_json.len() == 16 {
-                        let mut m = [0.0f64; 16];
-                        for (i, v) in matrix_json.iter().enumerate() {
-                            m[i] = v.as_f64().unwrap();
-                        }
-                        Matrix4::from_cols(
-                            Vector4::new(m[0], m[1], m[2], m[3]),
-                            Vector4::new(m[4], m[5], m[6], m[7]),
-                            Vector4::new(m[8], m[9], m[10], m[11]),
-                            Vector4::new(m[12], m[13], m[14], m[15]),
-                        )
-                    } else {
-                        Matrix4::one()
-                    }
-                } else {
-                    // Build from translation, rotation, scale (TRS)
-                    let translation = node_json
-                        .get("translation")
-                        .and_then(|t| t.as_array())
-                        .map(|arr| {
-                            [
-                                arr[0].as_f64().unwrap(),
-                                arr[1].as_f64().unwrap(),
-                                arr[2].as_f64().unwrap(),
-                            ]
-                        })
-                        .unwrap_or([0.0, 0.0, 0.0]);
-                    let rotation = node_json
-                        .get("rotation")
-                        .and_then(|r| r.as_array())
-                        .map(|arr| {
-                            [
-                                arr[0].as_f64().unwrap(),
-                                arr[1].as_f64().unwrap(),
-                                arr[2].as_f64().unwrap(),
-                                arr[3].as_f64().unwrap(),
-                            ]
-                        })
-                        .unwrap_or([0.0, 0.0, 0.0, 1.0]);
-                    let scale = node_json
-                        .get("scale")
-                        .and_then(|s| s.as_array())
-                        .map(|arr| {
-                            [
-                                arr[0].as_f64().unwrap(),
-                                arr[1].as_f64().unwrap(),
-                                arr[2].as_f64().unwrap(),
-                            ]
-                        })
-                        .unwrap_or([1.0, 1.0, 1.0]);
-
-                    let t = Matrix4::from_translation(Vector3::new(
-                        translation[0],
-                        translation[1],
-                        translation[2],
-                    ));
-                    let r = Matrix4::from(Quaternion::new(
-                        rotation[3],
-                        rotation[0],
-                        rotation[1],
-                        rotation[2],
-                    ));
-                    let s = Matrix4::from_nonuniform_scale(scale[0], scale[1], scale[2]);
-                    t * r * s
-                };
-
-            // Support both "mesh" (single) and "meshes" (array) for flexibility:
-            let mesh_indices = if let Some(meshes_json) = node_json.get("meshes") {
+    let mut visited = vec![false; nodes_json.len()];
+    let mut stack: Vec<(usize, Matrix4<f64>)> =
+        roots.iter().rev().map(|&i| (i, root_transform)).collect();
+
+    while let Some((index, parent)) = stack.pop() {
+        // Nodes form disjoint trees; a repeat means a cycle or shared child
+        let Some(node_json) = nodes_json.get(index) else {
+            event!(Level::WARN, "scene references missing node {index}");
+            continue;
+        };
+        if std::mem::replace(&mut visited[index], true) {
+            event!(Level::WARN, "node {index} is reachable more than once");
+            continue;
+        }
+
+        let transform = parent * local_transform(node_json);
+
+        // Support both "mesh" (single) and "meshes" (array) for flexibility:
+        let mesh_indices: Vec<usize> =
+            if let Some(meshes_json) = node_json.get("meshes").and_then(|v| v.as_array()) {
                 meshes_json
-                    .as_array()
-                    .unwrap_or(&vec![])
                     .iter()
                     .filter_map(|m| m.as_u64().map(|idx| idx as usize))
                     .collect()
@@ -670,12 +951,19 @@ pub fn build_nodes(json: &Value) -> Result<Vec<Node>, std::io::Error> {
                 vec![]
             };
 
-            let matrix = y_up_to_z_up * matrix;
+        if !mesh_indices.is_empty() {
             nodes.push(Node {
-                transform: matrix,
+                transform,
                 mesh_indices,
             });
         }
+
+        stack.extend(
+            children(node_json)
+                .into_iter()
+                .rev()
+                .map(|c| (c, transform)),
+        );
     }
 
     Ok(nodes)
@@ -0,0 +1,79 @@
+//! Read only MBTiles: raster tiles in a SQLite file, looked up through the
+//! `tiles` table, or the view over `map` and `images` that deduplicated
+//! archives define under that name. Rows are stored TMS style, counted from
+//! the south.
+
+use crate::{content::GlobeTileId, helpers::AbwError};
+use rusqlite::{Connection, OpenFlags, OptionalExtension};
+use std::{path::Path, sync::Mutex};
+
+const TILE_QUERY: &str =
+    "SELECT tile_data FROM tiles WHERE zoom_level = ?1 AND tile_column = ?2 AND tile_row = ?3";
+const HAS_METADATA: &str = "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'metadata'";
+const MAX_ZOOM_QUERY: &str = "SELECT CAST(value AS INTEGER) FROM metadata WHERE name = 'maxzoom'";
+
+fn err(msg: impl Into<String>) -> AbwError {
+    AbwError::TileLoading(format!("mbtiles: {}", msg.into()))
+}
+
+pub struct MbTiles {
+    db: Mutex<Connection>,
+    /// From the `maxzoom` metadata row when there is one.
+    pub max_zoom: Option<u8>,
+}
+
+impl MbTiles {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, AbwError> {
+        let flags = OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+        let db =
+            Connection::open_with_flags(path, flags).map_err(|e| err(format!("opening: {e}")))?;
+
+        // check b-tree cells as pages are read, so damage is an error
+        // rather than whatever lies past the page
+        db.pragma_update(None, "cell_size_check", true)
+            .map_err(|e| err(format!("opening: {e}")))?;
+
+        // fails on a damaged schema or one without tiles now, not per tile
+        db.prepare_cached(TILE_QUERY)
+            .map_err(|e| err(format!("reading schema: {e}")))?;
+
+        // metadata is optional, as is its maxzoom row
+        let read_max_zoom = || {
+            if db
+                .query_row(HAS_METADATA, [], |_| Ok(()))
+                .optional()?
+                .is_none()
+            {
+                return Ok(None);
+            }
+            db.query_row(MAX_ZOOM_QUERY, [], |row| row.get::<_, Option<i64>>(0))
+                .optional()
+                .map(Option::flatten)
+        };
+        let max_zoom = read_max_zoom()
+            .map_err(|e: rusqlite::Error| err(format!("reading metadata: {e}")))?
+            .and_then(|z| u8::try_from(z).ok());
+
+        Ok(Self {
+            db: Mutex::new(db),
+            max_zoom,
+        })
+    }
+
+    /// The encoded image for `tile`, rows counted from the north.
+    pub fn tile(&self, tile: GlobeTileId) -> Result<Option<Vec<u8>>, AbwError> {
+        let tms_row = (1i64 << tile.z) - 1 - tile.y as i64;
+        let db = self.db.lock().unwrap();
+        let data = db
+            .prepare_cached(TILE_QUERY)
+            .and_then(|mut query| {
+                query
+                    .query_row((tile.z, tile.x, tms_row), |row| {
+                        row.get::<_, Option<Vec<u8>>>(0)
+                    })
+                    .optional()
+            })
+            .map_err(|e| err(format!("reading {tile:?}: {e}")))?;
+        Ok(data.flatten())
+    }
+}
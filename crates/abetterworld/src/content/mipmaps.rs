@@ -0,0 +1,86 @@
+//! CPU mip chain generation for RGBA8 textures, run on the decode workers.
+//! Color is averaged in linear light so distant tiles don't darken; alpha is
+//! averaged as stored.
+
+use std::sync::OnceLock;
+
+use crate::content::{Texture, TextureEncoding};
+
+/// Number of levels in a full chain down to 1x1.
+pub fn mip_level_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+fn srgb_to_linear() -> &'static [f32; 256] {
+    static TABLE: OnceLock<[f32; 256]> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        std::array::from_fn(|i| {
+            let c = i as f32 / 255.0;
+            if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        })
+    })
+}
+
+fn linear_to_srgb(c: f32) -> u8 {
+    let c = c.clamp(0.0, 1.0);
+    let s = if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    };
+    (s * 255.0 + 0.5) as u8
+}
+
+/// Halves `src` (`width` x `height` RGBA8) with a 2x2 box filter. An odd
+/// trailing row or column folds into the last output texel's footprint by
+/// clamping, and an axis already at 1 texel stays at 1.
+pub fn downsample_rgba8(src: &[u8], width: u32, height: u32) -> Vec<u8> {
+    let lut = srgb_to_linear();
+    let (w, h) = (width as usize, height as usize);
+    let (out_w, out_h) = ((w / 2).max(1), (h / 2).max(1));
+    let mut out = Vec::with_capacity(out_w * out_h * 4);
+
+    for y in 0..out_h {
+        let rows = [(2 * y).min(h - 1), (2 * y + 1).min(h - 1)];
+        for x in 0..out_w {
+            let cols = [(2 * x).min(w - 1), (2 * x + 1).min(w - 1)];
+            let mut sum = [0.0f32; 4];
+            for row in rows {
+                for col in cols {
+                    let texel = &src[(row * w + col) * 4..][..4];
+                    for (c, s) in sum[..3].iter_mut().enumerate() {
+                        *s += lut[texel[c] as usize];
+                    }
+                    sum[3] += texel[3] as f32;
+                }
+            }
+            out.extend(sum[..3].iter().map(|&s| linear_to_srgb(s / 4.0)));
+            out.push((sum[3] / 4.0 + 0.5) as u8);
+        }
+    }
+    out
+}
+
+/// Fills in the full mip chain for a single-level RGBA8 texture. Textures
+/// that already have mips, and block compressed ones, are left alone.
+pub fn generate_mips(texture: &mut Texture) {
+    if texture.encoding != TextureEncoding::Rgba8 || texture.levels.len() != 1 {
+        return;
+    }
+    let expected = texture.width as usize * texture.height as usize * 4;
+    if texture.levels[0].len() != expected {
+        return;
+    }
+
+    let (mut width, mut height) = (texture.width, texture.height);
+    for _ in 1..mip_level_count(width, height) {
+        let next = downsample_rgba8(texture.levels.last().unwrap(), width, height);
+        texture.levels.push(next);
+        width = (width / 2).max(1);
+        height = (height / 2).max(1);
+    }
+}
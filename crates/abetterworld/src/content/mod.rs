@@ -1,16 +1,45 @@
+pub(crate) mod accessor;
+
+pub mod decoded_tile;
+pub use decoded_tile::*;
+
 pub mod download;
 pub use download::*;
 
 pub mod download_client;
 pub use download_client::*;
 
+pub mod globe;
+pub use globe::*;
+
+pub mod imagery;
+pub use imagery::*;
+
 pub mod importer;
 pub use importer::*;
 
+#[cfg(not(target_arch = "wasm32"))]
+pub mod mbtiles;
+
+pub mod mipmaps;
+pub use mipmaps::*;
+
+pub mod normals;
+pub use normals::*;
+
+pub mod packing;
+pub use packing::*;
+
+pub mod pmtiles;
+
+pub mod terrain;
+pub use terrain::*;
+
 pub mod tile_manager;
 pub use tile_manager::*;
 
 pub mod tiles;
+pub use tiles::ContentOptions;
 
 pub mod tiles_priority;
 
@@ -20,8 +49,11 @@ pub use tilesets::*;
 pub mod types;
 pub use types::*;
 
+pub mod validation;
+pub use validation::*;
+
 pub mod volumes;
 pub use volumes::*;
 
 pub mod pager;
-pub use pager::start_pager;
+pub use pager::{start_pager, PagerHandle, WorkerStats};
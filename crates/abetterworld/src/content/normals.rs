@@ -0,0 +1,158 @@
+//! Smooth normal generation for meshes that arrive without normals. Each
+//! face contributes its normal weighted by area and by the corner angle at
+//! the vertex. Faces meeting at more than the crease angle keep separate
+//! normals, splitting the vertex where they share one. Contributions are
+//! gathered by position, so UV seams don't show up as shading seams.
+
+use std::collections::HashMap;
+
+use crate::decode::{OwnedDecodedMesh, Vertex};
+
+/// Normal the decoders fill in when a mesh has none.
+const MISSING_NORMAL: [f32; 3] = [0.0, 0.0, 1.0];
+
+/// True when the mesh carries real normals, rather than only the decoders'
+/// placeholder (or zeros).
+pub fn has_normals(mesh: &OwnedDecodedMesh) -> bool {
+    mesh.as_vertex_slice()
+        .iter()
+        .any(|v| v.normal != MISSING_NORMAL && v.normal != [0.0; 3])
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn normalize(v: [f32; 3]) -> Option<[f32; 3]> {
+    let len = dot(v, v).sqrt();
+    (len > 0.0 && len.is_finite()).then(|| v.map(|c| c / len))
+}
+
+/// Position key for grouping coincident vertices; -0.0 and 0.0 match.
+fn position_key(p: [f32; 3]) -> [u32; 3] {
+    p.map(|c| if c == 0.0 { 0 } else { c.to_bits() })
+}
+
+struct Face {
+    /// Unit face normal, None for degenerate triangles.
+    normal: Option<[f32; 3]>,
+    /// Twice the area.
+    area: f32,
+    /// Interior angle at each corner.
+    angles: [f32; 3],
+}
+
+fn face(p: [[f32; 3]; 3]) -> Face {
+    let n = cross(sub(p[1], p[0]), sub(p[2], p[0]));
+    let angle = |at: usize| {
+        let a = sub(p[(at + 1) % 3], p[at]);
+        let b = sub(p[(at + 2) % 3], p[at]);
+        let s = dot(cross(a, b), cross(a, b)).sqrt();
+        s.atan2(dot(a, b))
+    };
+    Face {
+        normal: normalize(n),
+        area: dot(n, n).sqrt(),
+        angles: [angle(0), angle(1), angle(2)],
+    }
+}
+
+/// Returns a copy of `mesh` with generated normals. Faces whose normals
+/// differ by more than `crease_angle` degrees don't smooth into each other.
+/// Indices are read as a triangle list; vertices are duplicated where a
+/// crease runs through them.
+pub fn generate_normals(mesh: &OwnedDecodedMesh, crease_angle: f32) -> OwnedDecodedMesh {
+    let source = mesh.as_vertex_slice();
+    let triangles: Vec<[usize; 3]> = mesh
+        .as_index_slice()
+        .chunks_exact(3)
+        .map(|t| [t[0] as usize, t[1] as usize, t[2] as usize])
+        .filter(|t| t.iter().all(|&i| i < source.len()))
+        .collect();
+
+    let faces: Vec<Face> = triangles
+        .iter()
+        .map(|t| face(t.map(|i| source[i].position)))
+        .collect();
+
+    // Every (triangle, corner) touching each position
+    let mut corners: HashMap<[u32; 3], Vec<(usize, usize)>> = HashMap::new();
+    for (ti, t) in triangles.iter().enumerate() {
+        for (corner, &vi) in t.iter().enumerate() {
+            corners
+                .entry(position_key(source[vi].position))
+                .or_default()
+                .push((ti, corner));
+        }
+    }
+
+    let cos_crease = crease_angle.to_radians().cos();
+    let mut vertices: Vec<Vertex> = source.to_vec();
+    let mut assigned = vec![false; source.len()];
+    // Copies made of each vertex for corners on the other side of a crease
+    let mut splits: HashMap<usize, Vec<usize>> = HashMap::new();
+    let mut indices = Vec::with_capacity(triangles.len() * 3);
+
+    for (ti, t) in triangles.iter().enumerate() {
+        for &vi in t {
+            let normal = match faces[ti].normal {
+                Some(own) => {
+                    let mut sum = [0.0f32; 3];
+                    for &(other, corner) in &corners[&position_key(source[vi].position)] {
+                        let f = &faces[other];
+                        let Some(n) = f.normal.filter(|&n| dot(n, own) >= cos_crease) else {
+                            continue;
+                        };
+                        let weight = f.area * f.angles[corner];
+                        for (s, c) in sum.iter_mut().zip(n) {
+                            *s += c * weight;
+                        }
+                    }
+                    normalize(sum).unwrap_or(own)
+                }
+                None => MISSING_NORMAL,
+            };
+
+            let index = if !assigned[vi] {
+                assigned[vi] = true;
+                vertices[vi].normal = normal;
+                vi
+            } else if dot(vertices[vi].normal, normal) >= 0.9999 {
+                vi
+            } else {
+                let copies = splits.entry(vi).or_default();
+                match copies
+                    .iter()
+                    .find(|&&c| dot(vertices[c].normal, normal) >= 0.9999)
+                {
+                    Some(&c) => c,
+                    None => {
+                        vertices.push(Vertex {
+                            normal,
+                            ..source[vi]
+                        });
+                        copies.push(vertices.len() - 1);
+                        vertices.len() - 1
+                    }
+                }
+            };
+            indices.push(index as u32);
+        }
+    }
+
+    let mut out = OwnedDecodedMesh::from_vertices_and_indices(vertices, indices);
+    out.material_index = mesh.material_index;
+    out
+}
@@ -0,0 +1,156 @@
+//! Packs decoded meshes into the compact per-mesh vertex layouts the tile
+//! pipelines read. See `VertexLayout` for the attribute formats.
+//!
+//! Positions are quantized relative to the tile center, with one scale for
+//! all of a tile's meshes. See `Quantization`.
+
+use cgmath::{Matrix4, Vector3};
+use half::f16;
+
+use crate::{
+    content::has_normals,
+    decode::{OwnedDecodedMesh, Vertex},
+    render::VertexLayout,
+};
+
+/// The frame a tile's positions are quantized in: Snorm16 values scaled by
+/// `extent` around `center`, shared by all of the tile's meshes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quantization {
+    pub center: [f32; 3],
+    pub extent: f32,
+}
+
+impl Quantization {
+    /// Around the tile center, far enough out to hold every mesh. Positions
+    /// relative to an RTC center have the tile center at their origin.
+    /// Absolute ones use the middle of their combined bounds instead.
+    pub fn for_tile(meshes: &[OwnedDecodedMesh], rtc: bool) -> Self {
+        let mut min = [f32::INFINITY; 3];
+        let mut max = [f32::NEG_INFINITY; 3];
+        for v in meshes.iter().flat_map(|mesh| mesh.as_vertex_slice()) {
+            for ((lo, hi), p) in min.iter_mut().zip(&mut max).zip(v.position) {
+                *lo = lo.min(p);
+                *hi = hi.max(p);
+            }
+        }
+        if min[0] > max[0] {
+            return Self {
+                center: [0.0; 3],
+                extent: 1.0,
+            };
+        }
+
+        let center: [f32; 3] = if rtc {
+            [0.0; 3]
+        } else {
+            std::array::from_fn(|axis| (min[axis] + max[axis]) * 0.5)
+        };
+        let extent = (0..3)
+            .map(|axis| (max[axis] - center[axis]).max(center[axis] - min[axis]))
+            .fold(0.0f32, f32::max);
+        Self {
+            center,
+            extent: if extent > 0.0 { extent } else { 1.0 },
+        }
+    }
+}
+
+/// A decoded mesh in its GPU vertex layout.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackedMesh {
+    pub layout: VertexLayout,
+    /// `layout.stride()` bytes per vertex.
+    pub vertices: Vec<u8>,
+    pub indices: Vec<u32>,
+    pub material_index: Option<usize>,
+    /// Positions decode to `center + q * extent`, with q the Snorm16 value.
+    pub center: [f32; 3],
+    pub extent: f32,
+}
+
+impl PackedMesh {
+    pub fn vertex_count(&self) -> usize {
+        self.vertices.len() / self.layout.stride()
+    }
+
+    /// The position dequantization as a transform from Snorm space to mesh
+    /// space. The scale is uniform, so normals only need renormalizing.
+    pub fn dequantize(&self) -> Matrix4<f64> {
+        let [x, y, z] = self.center.map(f64::from);
+        Matrix4::from_translation(Vector3::new(x, y, z)) * Matrix4::from_scale(self.extent as f64)
+    }
+}
+
+fn snorm16(v: f32) -> i16 {
+    (v.clamp(-1.0, 1.0) * 32767.0).round() as i16
+}
+
+/// Octahedral encoding of a unit vector into two Snorm16 values. Zero
+/// vectors encode as +Z.
+pub fn octahedral_encode(n: [f32; 3]) -> [i16; 2] {
+    let l1 = n[0].abs() + n[1].abs() + n[2].abs();
+    if l1 == 0.0 || !l1.is_finite() {
+        return [0, 0];
+    }
+    let (x, y) = (n[0] / l1, n[1] / l1);
+    let sign = |v: f32| if v >= 0.0 { 1.0 } else { -1.0 };
+    let (x, y) = if n[2] < 0.0 {
+        ((1.0 - y.abs()) * sign(x), (1.0 - x.abs()) * sign(y))
+    } else {
+        (x, y)
+    };
+    [snorm16(x), snorm16(y)]
+}
+
+fn push_half2(out: &mut Vec<u8>, v: [f32; 2]) {
+    for c in v {
+        out.extend(f16::from_f32(c).to_bits().to_le_bytes());
+    }
+}
+
+/// Picks the smallest layout holding everything the vertices carry. Colors
+/// that are all opaque white and all-zero second UVs are what the decoders
+/// fill in for missing attributes, so they are dropped.
+pub fn choose_layout(vertices: &[Vertex]) -> VertexLayout {
+    VertexLayout {
+        color: vertices.iter().any(|v| v.color != [1.0; 4]),
+        texcoord1: vertices.iter().any(|v| v.texcoord1 != [0.0; 2]),
+    }
+}
+
+/// Quantizes and packs a decoded mesh, positions in its tile's frame.
+pub fn pack_mesh(mesh: &OwnedDecodedMesh, quantization: Quantization) -> PackedMesh {
+    let vertices = mesh.as_vertex_slice();
+    let layout = choose_layout(vertices);
+    let Quantization { center, extent } = quantization;
+
+    // Position w flags whether the normals are worth lighting with
+    let normal_flag = if has_normals(mesh) { i16::MAX } else { 0 };
+    let mut out = Vec::with_capacity(vertices.len() * layout.stride());
+    for v in vertices {
+        for (p, c) in v.position.iter().zip(center) {
+            out.extend(snorm16((p - c) / extent).to_le_bytes());
+        }
+        out.extend(normal_flag.to_le_bytes());
+        for q in octahedral_encode(v.normal) {
+            out.extend(q.to_le_bytes());
+        }
+        if layout.color {
+            out.extend(v.color.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8));
+        }
+        push_half2(&mut out, v.texcoord0);
+        if layout.texcoord1 {
+            push_half2(&mut out, v.texcoord1);
+        }
+    }
+
+    PackedMesh {
+        layout,
+        vertices: out,
+        indices: mesh.as_index_slice().to_vec(),
+        material_index: mesh.material_index,
+        center,
+        extent,
+    }
+}
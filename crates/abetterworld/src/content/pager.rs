@@ -1,38 +1,163 @@
+#[cfg(target_arch = "wasm32")]
 use crate::cache::init_wasm_indexdb_on_every_thread;
+use crate::cache::TilesetCache;
 use crate::content::tiles_priority::{priortize, Pri};
 use crate::content::{
-    go, Gen, ParsingState, TileContent, TileKey, TileManager, TileMessage, TileSourceContent,
-    TileSourceContentState,
+    go, Gen, Globe, ParsingState, TileContent, TileKey, TileManager, TileMessage,
+    TileSourceContent, TileSourceContentState,
 };
 use crate::dynamics::CameraRefinementData;
 use crate::helpers::{sleep_ms, yield_now, PlatformAwait};
 use crate::{
-    content::{tiles::wait_and_load_content, Client, TilePipelineMessage},
+    content::{
+        tiles::{
+            decode_content_worker, download_content_stage, ContentOptions, DecodeJob,
+            DownloadContext,
+        },
+        Client, TilePipelineMessage,
+    },
     dynamics::Camera,
     helpers::{
-        channel::{channel, Sender},
+        channel::{channel, Receiver, Sender},
         enter_runtime, AbwError,
     },
-    set_thread_name, spawn_detached_thread, Source,
+    set_thread_name, spawn_detached_thread, GlobeConfig, Source, WorkersConfig,
+};
+use std::sync::{
+    atomic::{AtomicBool, AtomicIsize, AtomicU64, Ordering},
+    Arc,
 };
-use std::sync::Arc;
 use tracing::{event, Level};
 
+/// Load on the tile worker pools, as returned by `World::worker_stats`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WorkerStats {
+    /// Downloads allowed in flight at once.
+    pub download_workers: usize,
+    pub decode_workers: usize,
+    /// Tiles requested by the pager that the download stage hasn't picked up yet.
+    pub download_queue: usize,
+    /// Downloaded tiles waiting for a decode worker.
+    pub decode_queue: usize,
+}
+
+/// Shared between the pager, its workers and the handle, so the render thread
+/// can tell when the current view has finished loading.
+#[derive(Debug, Default)]
+pub struct PagerStatus {
+    shutdown: AtomicBool,
+    /// Camera generation the pager last found nothing more to request for.
+    settled_generation: AtomicU64,
+    /// Tiles requested but not yet handed to the renderer or dropped. Dips
+    /// below zero when a worker finishes before the pager has counted them.
+    in_flight: AtomicIsize,
+}
+
+impl PagerStatus {
+    pub fn is_shutdown(&self) -> bool {
+        self.shutdown.load(Ordering::Relaxed)
+    }
+
+    pub fn requested(&self, count: usize) {
+        self.in_flight.fetch_add(count as isize, Ordering::SeqCst);
+    }
+
+    /// A requested tile reached the renderer, or failed on the way.
+    pub fn finished(&self) {
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    pub fn settle(&self, camera_generation: u64) {
+        self.settled_generation
+            .fetch_max(camera_generation, Ordering::SeqCst);
+    }
+
+    pub fn in_flight(&self) -> isize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
+    /// Everything for `camera_generation` has been requested and has left
+    /// the workers. It may still be waiting in the renderer's channel.
+    pub fn is_idle(&self, camera_generation: u64) -> bool {
+        self.settled_generation.load(Ordering::SeqCst) >= camera_generation && self.in_flight() == 0
+    }
+}
+
+/// Keeps the pager alive. Dropping it stops the pager thread, which closes the work
+/// queue so the workers exit too and release their handle on the cache.
+pub struct PagerHandle {
+    status: Arc<PagerStatus>,
+    workers: WorkersConfig,
+    // only read for their depth
+    download_queue: Receiver<TilePipelineMessage>,
+    decode_queue: Receiver<DecodeJob>,
+}
+
+impl PagerHandle {
+    pub fn stats(&self) -> WorkerStats {
+        WorkerStats {
+            download_workers: self.workers.download,
+            decode_workers: self.workers.decode,
+            download_queue: self.download_queue.len(),
+            decode_queue: self.decode_queue.len(),
+        }
+    }
+
+    pub fn status(&self) -> &PagerStatus {
+        &self.status
+    }
+}
+
+impl Drop for PagerHandle {
+    fn drop(&mut self) {
+        self.status.shutdown.store(true, Ordering::Relaxed);
+    }
+}
+
+/// What the pager loads: the source's tiles, and the globe beneath them
+/// when it is enabled.
+pub struct PagerSources {
+    pub source: Source,
+    pub globe: Option<Globe>,
+}
+
+/// Starts the pager and the two stages behind it. The download stage takes
+/// tile requests from the pager and runs up to `workers.download` of them
+/// at once on the async runtime, feeding a bounded queue that the decode
+/// threads drain, so slow downloads and slow decodes don't hold each other
+/// up.
 pub fn start_pager(
     source: Source,
+    globe: &GlobeConfig,
     camera_src: Arc<Camera>,
     render_tx: Sender<TilePipelineMessage>,
-) -> Result<(), AbwError> {
-    const LOADER_THREADS: usize = 12;
-    // unbounded: pager -> prioritizer
-    let (mut loader_tx, loader_rx) = channel::<TilePipelineMessage>(LOADER_THREADS);
-    let client = build_client(LOADER_THREADS)?;
+    cache: Arc<TilesetCache>,
+    options: ContentOptions,
+    workers: WorkersConfig,
+) -> Result<PagerHandle, AbwError> {
+    let workers = WorkersConfig {
+        download: workers.download.max(1),
+        decode: workers.decode.max(1),
+        decode_queue: workers.decode_queue.max(1),
+    };
+    // pager -> download stage
+    let (mut loader_tx, loader_rx) = channel::<TilePipelineMessage>(workers.download);
+    // download stage -> decode workers
+    let (decode_tx, decode_rx) = channel::<DecodeJob>(workers.decode_queue);
+    let client = build_client(workers.download)?;
+    let status = Arc::new(PagerStatus::default());
+    // local imagery archives open here, before the pager thread starts
+    let sources = PagerSources {
+        source,
+        globe: Globe::new(globe),
+    };
 
     // ---------- 1. Pager (discovers tiles) ----------
     {
         let client_clone = client.clone();
         let pager_cam = Arc::clone(&camera_src);
-        let source_clone = source.clone();
+        let cache_clone = cache.clone();
+        let status_clone = status.clone();
         let mut render_time = render_tx.clone();
         spawn_detached_thread!({
             set_thread_name!("Pager");
@@ -44,11 +169,13 @@ pub fn start_pager(
             }
 
             let fut = parser_thread(
-                &source_clone,
+                &sources,
                 pager_cam,
                 &mut loader_tx,
                 &mut render_time,
                 client_clone,
+                cache_clone,
+                &status_clone,
             );
 
             // wasm only
@@ -61,33 +188,76 @@ pub fn start_pager(
         });
     }
 
-    // ---------- 2. Workers ----------
+    // ---------- 2. Download stage ----------
     {
-        for _ in 0..LOADER_THREADS {
-            let client_clone = client.clone();
-            let mut render_time = render_tx.clone();
-            let mut rx = loader_rx.clone();
+        let context = DownloadContext {
+            client: client.clone(),
+            cache: cache.clone(),
+            decode_tx: decode_tx.clone(),
+            render_time: render_tx.clone(),
+            options,
+            status: status.clone(),
+        };
+        let mut rx = loader_rx.clone();
 
-            spawn_detached_thread!({
-                set_thread_name!("Download/Decode Worker");
+        spawn_detached_thread!({
+            set_thread_name!("Download");
 
-                let _enter = enter_runtime();
+            let _enter = enter_runtime();
 
-                let fut = wait_and_load_content(&client_clone, &mut rx, &mut render_time);
+            let fut = download_content_stage(context, &mut rx, workers.download);
 
-                // wasm only
-                #[cfg(target_arch = "wasm32")]
-                fut.await.expect("Failed to load content in worker thread");
+            // wasm only
+            #[cfg(target_arch = "wasm32")]
+            fut.await.expect("Failed to run the download stage");
 
-                // native only
-                #[cfg(not(target_arch = "wasm32"))]
-                fut.platform_await()
-                    .expect("Failed to load content in worker thread");
-            });
-        }
+            // native only
+            #[cfg(not(target_arch = "wasm32"))]
+            fut.platform_await()
+                .expect("Failed to run the download stage");
+        });
     }
+    // the decode queue closes once the download stage and its downloads are done
+    drop(decode_tx);
 
-    Ok(())
+    // ---------- 3. Decode workers ----------
+    for _ in 0..workers.decode {
+        let cache_clone = cache.clone();
+        let mut render_time = render_tx.clone();
+        let mut rx = decode_rx.clone();
+        let status_clone = status.clone();
+
+        spawn_detached_thread!({
+            set_thread_name!("Decode Worker");
+
+            let _enter = enter_runtime();
+
+            let fut = decode_content_worker(
+                &cache_clone,
+                &mut rx,
+                &mut render_time,
+                options,
+                &status_clone,
+            );
+
+            // wasm only
+            #[cfg(target_arch = "wasm32")]
+            fut.await
+                .expect("Failed to decode content in worker thread");
+
+            // native only
+            #[cfg(not(target_arch = "wasm32"))]
+            fut.platform_await()
+                .expect("Failed to decode content in worker thread");
+        });
+    }
+
+    Ok(PagerHandle {
+        status,
+        workers,
+        download_queue: loader_rx,
+        decode_queue: decode_rx,
+    })
 }
 
 pub fn send_load_tile(
@@ -97,7 +267,10 @@ pub fn send_load_tile(
 ) -> Result<(), AbwError> {
     let tile = TileContent {
         uri: tile_src.tile_content.uri.clone(),
-        state: crate::content::types::TileState::ToLoad,
+        state: match &tile_src.tile.terrain {
+            Some(node) => crate::content::types::TileState::Terrain(node.id),
+            None => crate::content::types::TileState::ToLoad,
+        },
     };
     pager_tx.try_send(TilePipelineMessage::Load((
         TileMessage {
@@ -136,9 +309,60 @@ pub fn send_update_tile(
     Err(AbwError::TileLoading("No tile info to update".into()))
 }
 
+/// Requests the globe tiles the camera needs, like `parser_iteration`
+/// does for the source's.
+pub fn globe_iteration(
+    globe: &Globe,
+    camera_data: &CameraRefinementData,
+    pipeline_state: &TileManager,
+    decoder_tx: &mut Sender<TilePipelineMessage>,
+    renderer_tx: &mut Sender<TilePipelineMessage>,
+    gen: Gen,
+) -> ParsingState {
+    let selection = globe.select(camera_data);
+
+    let mut parsing_state = ParsingState::Stable;
+    for selected in selection.iter() {
+        let key = selected.tile.key();
+        if !pipeline_state.is_tile_loaded(key) {
+            let load = TilePipelineMessage::Load((
+                TileMessage { key, gen },
+                TileContent {
+                    uri: selected.tile.uri(),
+                    state: globe.to_load(selected.tile),
+                },
+            ));
+            if decoder_tx.try_send(load).is_err() {
+                // the channel is full, we will try again next time
+                parsing_state = ParsingState::Instable;
+                break;
+            }
+
+            pipeline_state.mark_tile_loaded(key);
+        }
+    }
+
+    for selected in selection {
+        let key = selected.tile.key();
+        if !pipeline_state.compare_tile_info(key, &selected.info) {
+            let update =
+                TilePipelineMessage::Update((TileMessage { key, gen }, selected.info.clone()));
+            if renderer_tx.try_send(update).is_err() {
+                parsing_state = ParsingState::Instable;
+                break;
+            }
+
+            pipeline_state.add_or_update_tile_info(key, selected.info);
+        }
+    }
+
+    parsing_state
+}
+
 pub fn parser_iteration(
     source: &Source,
     client: &Client,
+    cache: &Arc<TilesetCache>,
     camera_data: &CameraRefinementData,
     root: &mut Option<TileSourceContent>,
     pipeline_state: &TileManager,
@@ -146,7 +370,7 @@ pub fn parser_iteration(
     renderer_tx: &mut Sender<TilePipelineMessage>,
     gen: Gen,
 ) -> Result<ParsingState, AbwError> {
-    let mut parsing_state = go(source, client, camera_data, root)?;
+    let mut parsing_state = go(source, client, cache, camera_data, root)?;
 
     if let Some(tile) = root {
         if let Some(TileSourceContentState::LoadedTileSet { permanent, .. }) = &tile.loaded {
@@ -214,11 +438,13 @@ pub fn parser_iteration(
 }
 
 pub async fn parser_thread(
-    source: &Source,
+    sources: &PagerSources,
     cam: Arc<Camera>,
     decoder_tx: &mut Sender<TilePipelineMessage>,
     renderer_tx: &mut Sender<TilePipelineMessage>,
     client: Client,
+    cache: Arc<TilesetCache>,
+    status: &PagerStatus,
 ) -> Result<(), AbwError> {
     let mut root = None;
     let pipeline_state = TileManager::new();
@@ -226,15 +452,17 @@ pub async fn parser_thread(
     let mut last_cam_gen = 0;
     let mut parsing_gen = 1;
     let mut parsing_state = ParsingState::Instable;
-    loop {
+    while !status.is_shutdown() {
         let new_cam_gen = cam.generation();
         if new_cam_gen != last_cam_gen || parsing_state == ParsingState::Instable {
             let span = tracing::debug_span!("parser_iteration",).entered();
 
             let camera_data = cam.refinement_data();
+            let requested_before = pipeline_state.loaded_count();
             parsing_state = parser_iteration(
-                source,
+                &sources.source,
                 &client,
+                &cache,
                 &camera_data,
                 &mut root,
                 &pipeline_state,
@@ -242,6 +470,26 @@ pub async fn parser_thread(
                 renderer_tx,
                 parsing_gen,
             )?;
+            if let Some(globe) = &sources.globe {
+                let globe_state = globe_iteration(
+                    globe,
+                    &camera_data,
+                    &pipeline_state,
+                    decoder_tx,
+                    renderer_tx,
+                    parsing_gen,
+                );
+                if globe_state == ParsingState::Instable {
+                    parsing_state = ParsingState::Instable;
+                }
+            }
+
+            // counted before settling, so a settled generation never
+            // has uncounted tiles behind it
+            status.requested(pipeline_state.loaded_count() - requested_before);
+            if parsing_state == ParsingState::Stable {
+                status.settle(new_cam_gen);
+            }
 
             last_cam_gen = new_cam_gen;
             parsing_gen += 1;
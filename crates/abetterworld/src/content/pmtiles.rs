@@ -0,0 +1,281 @@
+//! Read only PMTiles v3 archives: a fixed header, then varint encoded
+//! directories mapping Hilbert curve tile ids to byte ranges.
+
+use crate::{content::GlobeTileId, helpers::AbwError};
+use std::{
+    io::{Read, Seek, SeekFrom},
+    sync::Mutex,
+};
+
+pub const HEADER_LEN: usize = 127;
+const MAGIC: &[u8; 7] = b"PMTiles";
+/// Leaf directories nest at most this deep below the root.
+const MAX_DEPTH: usize = 4;
+
+fn err(msg: impl Into<String>) -> AbwError {
+    AbwError::TileLoading(format!("pmtiles: {}", msg.into()))
+}
+
+pub trait ReadSeek: Read + Seek + Send {}
+impl<T: Read + Seek + Send> ReadSeek for T {}
+
+/// The spec's compression codes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Unknown,
+    None,
+    Gzip,
+    Brotli,
+    Zstd,
+}
+
+impl Compression {
+    fn from_code(code: u8) -> Self {
+        match code {
+            1 => Compression::None,
+            2 => Compression::Gzip,
+            3 => Compression::Brotli,
+            4 => Compression::Zstd,
+            _ => Compression::Unknown,
+        }
+    }
+
+    pub fn decompress(self, data: Vec<u8>) -> Result<Vec<u8>, AbwError> {
+        let mut out = Vec::new();
+        match self {
+            Compression::None | Compression::Unknown => return Ok(data),
+            Compression::Gzip => flate2::read::GzDecoder::new(&data[..])
+                .read_to_end(&mut out)
+                .map_err(|e| err(format!("gzip: {e}")))?,
+            Compression::Zstd => ruzstd::decoding::StreamingDecoder::new(&data[..])
+                .map_err(|e| err(format!("zstd: {e}")))?
+                .read_to_end(&mut out)
+                .map_err(|e| err(format!("zstd: {e}")))?,
+            Compression::Brotli => return Err(err("brotli compression isn't supported")),
+        };
+        Ok(out)
+    }
+}
+
+/// The parts of the header needed to find tiles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    pub root_offset: u64,
+    pub root_length: u64,
+    pub leaf_offset: u64,
+    pub tile_data_offset: u64,
+    pub internal_compression: Compression,
+    pub tile_compression: Compression,
+    pub min_zoom: u8,
+    pub max_zoom: u8,
+}
+
+impl Header {
+    pub fn parse(bytes: &[u8]) -> Result<Self, AbwError> {
+        if bytes.len() < HEADER_LEN || &bytes[..7] != MAGIC {
+            return Err(err("not a PMTiles archive"));
+        }
+        if bytes[7] != 3 {
+            return Err(err(format!("version {} isn't supported", bytes[7])));
+        }
+        let u64_at = |at: usize| u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap());
+        Ok(Self {
+            root_offset: u64_at(8),
+            root_length: u64_at(16),
+            leaf_offset: u64_at(40),
+            tile_data_offset: u64_at(56),
+            internal_compression: Compression::from_code(bytes[97]),
+            tile_compression: Compression::from_code(bytes[98]),
+            min_zoom: bytes[100],
+            max_zoom: bytes[101],
+        })
+    }
+}
+
+/// A run of `run_length` consecutive tile ids sharing one byte range, or
+/// with a run length of 0, a leaf directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Entry {
+    pub tile_id: u64,
+    pub offset: u64,
+    pub length: u32,
+    pub run_length: u32,
+}
+
+/// Little-endian base 128 varint. Returns the value and the bytes it took.
+pub fn read_varint(data: &[u8]) -> Option<(u64, usize)> {
+    let mut value = 0u64;
+    for (i, &byte) in data.iter().take(10).enumerate() {
+        value |= ((byte & 0x7f) as u64) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+/// Decodes an uncompressed directory: the entry count, then each column of
+/// delta coded ids, run lengths, lengths and offsets in turn.
+pub fn parse_directory(data: &[u8]) -> Result<Vec<Entry>, AbwError> {
+    let mut pos = 0;
+    let mut next = || -> Result<u64, AbwError> {
+        let (value, len) =
+            read_varint(&data[pos.min(data.len())..]).ok_or_else(|| err("truncated directory"))?;
+        pos += len;
+        Ok(value)
+    };
+    let count = next()? as usize;
+    if count > data.len() {
+        return Err(err("directory entry count overruns it"));
+    }
+    let mut entries = vec![
+        Entry {
+            tile_id: 0,
+            offset: 0,
+            length: 0,
+            run_length: 0,
+        };
+        count
+    ];
+    let mut last_id = 0u64;
+    for entry in entries.iter_mut() {
+        last_id = last_id
+            .checked_add(next()?)
+            .ok_or_else(|| err("directory tile ids overflow"))?;
+        entry.tile_id = last_id;
+    }
+    for entry in entries.iter_mut() {
+        entry.run_length = next()? as u32;
+    }
+    for entry in entries.iter_mut() {
+        entry.length = next()? as u32;
+    }
+    for i in 0..count {
+        // 0 continues on from the previous entry, anything else is offset + 1
+        let value = next()?;
+        entries[i].offset = if value == 0 && i > 0 {
+            entries[i - 1]
+                .offset
+                .checked_add(entries[i - 1].length as u64)
+                .ok_or_else(|| err("directory offsets overflow"))?
+        } else {
+            value.saturating_sub(1)
+        };
+    }
+    Ok(entries)
+}
+
+/// Position of (x, y) along the Hilbert curve filling a 2^z grid.
+pub fn hilbert_index(z: u8, mut x: u32, mut y: u32) -> u64 {
+    let n = 1u64 << z;
+    let mut d = 0u64;
+    let mut s = n / 2;
+    while s > 0 {
+        let rx = (x as u64 & s) > 0;
+        let ry = (y as u64 & s) > 0;
+        d += s * s * ((3 * rx as u64) ^ ry as u64);
+        if !ry {
+            if rx {
+                x = (n - 1) as u32 - x;
+                y = (n - 1) as u32 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    d
+}
+
+/// The archive wide tile id: every tile of the levels above, then the
+/// tile's place on its level's Hilbert curve.
+pub fn tile_id(tile: GlobeTileId) -> u64 {
+    let above = ((1u64 << (2 * tile.z as u64)) - 1) / 3;
+    above + hilbert_index(tile.z, tile.x, tile.y)
+}
+
+/// The entry covering `id`, if any.
+pub fn find_entry(entries: &[Entry], id: u64) -> Option<Entry> {
+    let at = entries
+        .partition_point(|e| e.tile_id <= id)
+        .checked_sub(1)?;
+    let entry = entries[at];
+    (entry.run_length == 0 || id - entry.tile_id < entry.run_length as u64).then_some(entry)
+}
+
+pub struct PmTiles {
+    file: Mutex<Box<dyn ReadSeek>>,
+    /// The archive's size in bytes, which bounds every read.
+    len: u64,
+    pub header: Header,
+    root: Vec<Entry>,
+}
+
+impl PmTiles {
+    pub fn new(mut file: Box<dyn ReadSeek>) -> Result<Self, AbwError> {
+        let mut bytes = [0u8; HEADER_LEN];
+        file.read_exact(&mut bytes)
+            .map_err(|e| err(format!("reading header: {e}")))?;
+        let header = Header::parse(&bytes)?;
+        let len = file
+            .seek(SeekFrom::End(0))
+            .map_err(|e| err(format!("sizing archive: {e}")))?;
+        let reader = Self {
+            file: Mutex::new(file),
+            len,
+            header,
+            root: Vec::new(),
+        };
+        let root = reader.directory(header.root_offset, header.root_length)?;
+        Ok(Self { root, ..reader })
+    }
+
+    fn read(&self, offset: u64, length: u64) -> Result<Vec<u8>, AbwError> {
+        if offset.checked_add(length).is_none_or(|end| end > self.len) {
+            return Err(err(format!(
+                "{length} bytes at {offset} run past the {} byte archive",
+                self.len
+            )));
+        }
+        let mut data = vec![0u8; length as usize];
+        let mut file = self.file.lock().unwrap();
+        file.seek(SeekFrom::Start(offset))
+            .and_then(|_| file.read_exact(&mut data))
+            .map_err(|e| err(format!("reading {length} bytes at {offset}: {e}")))?;
+        Ok(data)
+    }
+
+    fn directory(&self, offset: u64, length: u64) -> Result<Vec<Entry>, AbwError> {
+        let data = self
+            .header
+            .internal_compression
+            .decompress(self.read(offset, length)?)?;
+        parse_directory(&data)
+    }
+
+    /// The encoded image for `tile`, rows counted from the north.
+    pub fn tile(&self, tile: GlobeTileId) -> Result<Option<Vec<u8>>, AbwError> {
+        let id = tile_id(tile);
+        let mut entries = std::borrow::Cow::Borrowed(&self.root);
+        for _ in 0..=MAX_DEPTH {
+            let Some(entry) = find_entry(&entries, id) else {
+                return Ok(None);
+            };
+            if entry.run_length > 0 {
+                let offset = self
+                    .header
+                    .tile_data_offset
+                    .checked_add(entry.offset)
+                    .ok_or_else(|| err("tile offset overflows"))?;
+                let data = self.read(offset, entry.length as u64)?;
+                return self.header.tile_compression.decompress(data).map(Some);
+            }
+            let offset = self
+                .header
+                .leaf_offset
+                .checked_add(entry.offset)
+                .ok_or_else(|| err("leaf directory offset overflows"))?;
+            entries = std::borrow::Cow::Owned(self.directory(offset, entry.length as u64)?);
+        }
+        Err(err("leaf directories nest too deep"))
+    }
+}
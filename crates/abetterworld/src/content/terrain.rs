@@ -0,0 +1,689 @@
+//! Cesium quantized-mesh-1.0 terrain. A `layer.json` describes the tileset
+//! and which tiles exist on the geographic grid; each `.terrain` tile is a
+//! quantized height mesh, optionally with oct-encoded vertex normals and a
+//! water mask. Terrain tiles are laid out as `TileSource`s, so the pager
+//! refines and prioritizes them like any other source's tiles.
+
+use crate::{
+    content::{
+        generate_mips, generate_normals, has_normals, pack_mesh, BoundingVolume, ContentOptions,
+        Material, Node, Quantization, Texture, TextureEncoding, TextureRef, TextureTransform,
+        TileSource, TileSourceContent, TileState,
+    },
+    decode::{OwnedDecodedMesh, Vertex},
+    dynamics::WGS84_A,
+    helpers::{geodetic_to_ecef_z_up, AbwError},
+    MipmapMode, NormalsConfig,
+};
+use cgmath::{EuclideanSpace, Matrix4, Point3, Vector3};
+use serde::Deserialize;
+use std::{f64::consts::PI, io::Read, sync::Arc};
+
+/// Largest quantized coordinate; u and v span the tile and heights span
+/// the header's min to max height.
+const QUANTIZED_MAX: f64 = 32767.0;
+const HEADER_LEN: usize = 88;
+
+const EXTENSION_OCT_NORMALS: u8 = 1;
+const EXTENSION_WATER_MASK: u8 = 2;
+
+/// Edge length of a water mask that isn't all land or all water.
+pub const WATER_MASK_SIZE: u32 = 256;
+/// sRGB colors terrain is drawn with, land to water as the mask goes.
+pub const TERRAIN_LAND_COLOR: [u8; 4] = [150, 140, 120, 255];
+pub const TERRAIN_WATER_COLOR: [u8; 4] = [36, 64, 104, 255];
+
+/// Heights assumed for a tile's bounds before it is loaded.
+const TERRAIN_MIN_HEIGHT: f64 = -500.0;
+const TERRAIN_MAX_HEIGHT: f64 = 9000.0;
+
+/// Cesium's estimate for a level zero tile of a 65 sample heightmap, halved
+/// each level.
+const LEVEL_ZERO_GEOMETRIC_ERROR: f64 = WGS84_A * 2.0 * PI * 0.25 / (65.0 * 2.0);
+/// Skirts hang this many times the level's geometric error.
+const SKIRT_ERROR_SCALE: f64 = 5.0;
+
+fn err(msg: impl Into<String>) -> AbwError {
+    AbwError::TileLoading(format!("quantized-mesh: {}", msg.into()))
+}
+
+/// A tile of the geographic grid: 2^(z+1) by 2^z tiles of equal angular
+/// size, rows counted from the south as in TMS.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TerrainTileId {
+    pub z: u8,
+    pub x: u32,
+    pub y: u32,
+}
+
+impl TerrainTileId {
+    pub fn new(z: u8, x: u32, y: u32) -> Self {
+        Self { z, x, y }
+    }
+
+    /// The western and eastern hemispheres.
+    pub fn level_zero() -> [Self; 2] {
+        [Self::new(0, 0, 0), Self::new(0, 1, 0)]
+    }
+
+    pub fn rows(&self) -> u32 {
+        1 << self.z
+    }
+
+    /// South west, south east, north west, north east.
+    pub fn children(&self) -> [Self; 4] {
+        let (z, x, y) = (self.z + 1, self.x * 2, self.y * 2);
+        [
+            Self::new(z, x, y),
+            Self::new(z, x + 1, y),
+            Self::new(z, x, y + 1),
+            Self::new(z, x + 1, y + 1),
+        ]
+    }
+
+    /// West, south, east and north edges in degrees.
+    pub fn rectangle(&self) -> [f64; 4] {
+        let size = 180.0 / self.rows() as f64;
+        let west = self.x as f64 * size - 180.0;
+        let south = self.y as f64 * size - 90.0;
+        [west, south, west + size, south + size]
+    }
+
+    pub fn geometric_error(&self) -> f64 {
+        LEVEL_ZERO_GEOMETRIC_ERROR / self.rows() as f64
+    }
+
+    pub fn skirt_height(&self) -> f64 {
+        self.geometric_error() * SKIRT_ERROR_SCALE
+    }
+
+    /// An axis aligned box around the tile between the two heights, and
+    /// its skirts.
+    pub fn bounding_volume(&self, min_height: f64, max_height: f64) -> BoundingVolume {
+        const SAMPLES: usize = 8;
+        let [west, south, east, north] = self.rectangle();
+        let mut min = Vector3::new(f64::MAX, f64::MAX, f64::MAX);
+        let mut max = -min;
+        for j in 0..=SAMPLES {
+            let lat = south + (north - south) * j as f64 / SAMPLES as f64;
+            for i in 0..=SAMPLES {
+                let lon = west + (east - west) * i as f64 / SAMPLES as f64;
+                for h in [min_height - self.skirt_height(), max_height] {
+                    let p = geodetic_to_ecef_z_up(lat, lon, h);
+                    for axis in 0..3 {
+                        min[axis] = min[axis].min(p[axis]);
+                        max[axis] = max[axis].max(p[axis]);
+                    }
+                }
+            }
+        }
+        // the surface bulges out between samples
+        let span = (east - west).to_radians() * WGS84_A / SAMPLES as f64;
+        let pad = span * span / (8.0 * WGS84_A);
+        let center = (min + max) * 0.5;
+        let half = (max - min) * 0.5 + Vector3::new(pad, pad, pad);
+        BoundingVolume::from_box([
+            center.x, center.y, center.z, half.x, 0.0, 0.0, 0.0, half.y, 0.0, 0.0, 0.0, half.z,
+        ])
+    }
+}
+
+/// A rectangle of available tiles on one level, inclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TileRange {
+    pub start_x: u32,
+    pub start_y: u32,
+    pub end_x: u32,
+    pub end_y: u32,
+}
+
+impl TileRange {
+    fn contains(&self, x: u32, y: u32) -> bool {
+        (self.start_x..=self.end_x).contains(&x) && (self.start_y..=self.end_y).contains(&y)
+    }
+}
+
+fn default_scheme() -> String {
+    "tms".to_string()
+}
+
+fn default_projection() -> String {
+    "EPSG:4326".to_string()
+}
+
+/// The parts of `layer.json` used to find tiles.
+#[derive(Debug, Clone, Deserialize)]
+pub struct LayerJson {
+    pub format: String,
+    pub tiles: Vec<String>,
+    #[serde(default)]
+    pub version: String,
+    #[serde(default = "default_scheme")]
+    pub scheme: String,
+    #[serde(default = "default_projection")]
+    pub projection: String,
+    /// Per level, the tiles that exist. Empty when every tile does.
+    #[serde(default)]
+    pub available: Vec<Vec<TileRange>>,
+    #[serde(default)]
+    pub extensions: Vec<String>,
+    pub maxzoom: Option<u8>,
+}
+
+/// A terrain tileset, ready to address tiles in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TerrainLayer {
+    template: String,
+    version: String,
+    /// Rows counted from the north in urls and availability.
+    slippy: bool,
+    available: Vec<Vec<TileRange>>,
+    max_zoom: Option<u8>,
+    /// Extensions both the layer and the decoder support, to ask for.
+    extensions: Vec<String>,
+}
+
+impl TerrainLayer {
+    pub fn new(layer: LayerJson) -> Result<Self, AbwError> {
+        if !layer.format.starts_with("quantized-mesh-1.") {
+            return Err(err(format!("unsupported format {}", layer.format)));
+        }
+        if layer.projection != "EPSG:4326" {
+            return Err(err(format!("unsupported projection {}", layer.projection)));
+        }
+        let template = layer
+            .tiles
+            .into_iter()
+            .next()
+            .ok_or_else(|| err("layer.json has no tile urls"))?;
+        let extensions = layer
+            .extensions
+            .into_iter()
+            .filter(|e| e == "octvertexnormals" || e == "watermask")
+            .collect();
+        Ok(Self {
+            template,
+            version: layer.version,
+            slippy: layer.scheme == "slippyMap",
+            available: layer.available,
+            max_zoom: layer.maxzoom,
+            extensions,
+        })
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self, AbwError> {
+        let layer: LayerJson =
+            serde_json::from_slice(bytes).map_err(|e| err(format!("layer.json: {e}")))?;
+        Self::new(layer)
+    }
+
+    fn scheme_row(&self, tile: TerrainTileId) -> u32 {
+        if self.slippy {
+            tile.rows() - 1 - tile.y
+        } else {
+            tile.y
+        }
+    }
+
+    pub fn is_available(&self, tile: TerrainTileId) -> bool {
+        if self.max_zoom.is_some_and(|max| tile.z > max) {
+            return false;
+        }
+        if self.available.is_empty() {
+            return true;
+        }
+        let y = self.scheme_row(tile);
+        self.available
+            .get(tile.z as usize)
+            .is_some_and(|ranges| ranges.iter().any(|r| r.contains(tile.x, y)))
+    }
+
+    /// The tile's url, relative to `layer.json` unless the template is
+    /// absolute, asking for the extensions the layer has.
+    pub fn tile_uri(&self, tile: TerrainTileId) -> String {
+        let uri = self
+            .template
+            .replace("{z}", &tile.z.to_string())
+            .replace("{x}", &tile.x.to_string())
+            .replace("{y}", &self.scheme_row(tile).to_string())
+            .replace("{version}", &self.version);
+        if self.extensions.is_empty() {
+            return uri;
+        }
+        let separator = if uri.contains('?') { '&' } else { '?' };
+        format!("{uri}{separator}extensions={}", self.extensions.join("-"))
+    }
+
+    pub fn tile_source(self: &Arc<Self>, tile: TerrainTileId) -> TileSource {
+        TileSource {
+            bounding_volume: tile.bounding_volume(TERRAIN_MIN_HEIGHT, TERRAIN_MAX_HEIGHT),
+            geometric_error: tile.geometric_error(),
+            refine: Some("REPLACE".to_string()),
+            content: Some(TileSourceContent {
+                uri: self.tile_uri(tile),
+                ..Default::default()
+            }),
+            children: None,
+            needs_refinement_flag: None,
+            terrain: Some(TerrainNode {
+                id: tile,
+                layer: Arc::clone(self),
+            }),
+        }
+    }
+
+    /// A content-less root over the level zero tiles that exist.
+    pub fn root(self) -> TileSource {
+        let layer = Arc::new(self);
+        let children = TerrainTileId::level_zero()
+            .into_iter()
+            .filter(|tile| layer.is_available(*tile))
+            .map(|tile| layer.tile_source(tile))
+            .collect();
+        TileSource {
+            bounding_volume: BoundingVolume::from_box([
+                0.0, 0.0, 0.0, WGS84_A, 0.0, 0.0, 0.0, WGS84_A, 0.0, 0.0, 0.0, WGS84_A,
+            ]),
+            // always refined into the level zero tiles
+            geometric_error: f64::INFINITY,
+            refine: Some("REPLACE".to_string()),
+            content: None,
+            children: Some(children),
+            needs_refinement_flag: None,
+            terrain: None,
+        }
+    }
+}
+
+/// Where a terrain `TileSource` sits, so its children can be laid out once
+/// it is refined.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TerrainNode {
+    pub id: TerrainTileId,
+    pub layer: Arc<TerrainLayer>,
+}
+
+impl TerrainNode {
+    /// All four children when they all exist, otherwise none, so refining
+    /// never leaves a hole.
+    pub fn children(&self) -> Vec<TileSource> {
+        let children = self.id.children();
+        if !children.iter().all(|c| self.layer.is_available(*c)) {
+            return Vec::new();
+        }
+        children
+            .iter()
+            .map(|c| self.layer.tile_source(*c))
+            .collect()
+    }
+}
+
+/// Coverage of water over a tile.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WaterMask {
+    /// 0 for all land, 255 for all water.
+    Uniform(u8),
+    /// 256 by 256 coverage values, rows from the south the way Cesium
+    /// samples them.
+    Grid(Vec<u8>),
+}
+
+/// A decoded `.terrain` tile.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuantizedMesh {
+    /// Earth centered, what the vertices are placed relative to.
+    pub center: Point3<f64>,
+    pub min_height: f32,
+    pub max_height: f32,
+    pub bounding_sphere: (Point3<f64>, f64),
+    pub horizon_occlusion_point: Point3<f64>,
+    /// Quantized 0..=32767: u west to east, v south to north, and height
+    /// from `min_height` to `max_height`.
+    pub u: Vec<u16>,
+    pub v: Vec<u16>,
+    pub height: Vec<u16>,
+    /// Counter-clockwise triangles.
+    pub indices: Vec<u32>,
+    pub west_indices: Vec<u32>,
+    pub south_indices: Vec<u32>,
+    pub east_indices: Vec<u32>,
+    pub north_indices: Vec<u32>,
+    pub normals: Option<Vec<[f32; 3]>>,
+    pub water_mask: Option<WaterMask>,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], AbwError> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.data.len())
+            .ok_or_else(|| err(format!("truncated at byte {}", self.pos)))?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, AbwError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, AbwError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, AbwError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn f32(&mut self) -> Result<f32, AbwError> {
+        Ok(f32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn f64(&mut self) -> Result<f64, AbwError> {
+        Ok(f64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn point(&mut self) -> Result<Point3<f64>, AbwError> {
+        Ok(Point3::new(self.f64()?, self.f64()?, self.f64()?))
+    }
+
+    fn u16s(&mut self, count: usize) -> Result<Vec<u16>, AbwError> {
+        (0..count).map(|_| self.u16()).collect()
+    }
+
+    /// 16 bit indices, or 32 bit ones past 65536 vertices.
+    fn indices(&mut self, count: usize, wide: bool) -> Result<Vec<u32>, AbwError> {
+        if wide {
+            (0..count).map(|_| self.u32()).collect()
+        } else {
+            (0..count).map(|_| self.u16().map(u32::from)).collect()
+        }
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+}
+
+/// Undoes the zig-zag delta coding of the u, v and height arrays.
+pub fn decode_zigzag_deltas(values: &mut [u16]) {
+    let mut value = 0i32;
+    for v in values.iter_mut() {
+        let delta = (*v >> 1) as i32 ^ -((*v & 1) as i32);
+        value += delta;
+        *v = value as u16;
+    }
+}
+
+/// Undoes high water mark coding: each index is the highest seen so far
+/// less the code, and a code of 0 introduces the next new vertex.
+pub fn decode_high_water_mark(indices: &mut [u32]) {
+    let mut highest = 0u32;
+    for index in indices.iter_mut() {
+        let code = *index;
+        *index = highest.wrapping_sub(code);
+        if code == 0 {
+            highest += 1;
+        }
+    }
+}
+
+/// Oct-encoded unit vector, two bytes mapped to -1..=1.
+pub fn oct_decode(x: u8, y: u8) -> [f32; 3] {
+    let unorm = |b: u8| b as f32 / 255.0 * 2.0 - 1.0;
+    let (x, y) = (unorm(x), unorm(y));
+    let z = 1.0 - x.abs() - y.abs();
+    let (x, y) = if z < 0.0 {
+        let sign = |v: f32| if v >= 0.0 { 1.0 } else { -1.0 };
+        ((1.0 - y.abs()) * sign(x), (1.0 - x.abs()) * sign(y))
+    } else {
+        (x, y)
+    };
+    let length = (x * x + y * y + z * z).sqrt();
+    [x / length, y / length, z / length]
+}
+
+impl QuantizedMesh {
+    /// Parses a tile, gunzipping it first if the server didn't.
+    pub fn parse(bytes: &[u8]) -> Result<Self, AbwError> {
+        if bytes.starts_with(&[0x1f, 0x8b]) {
+            let mut out = Vec::new();
+            flate2::read::GzDecoder::new(bytes)
+                .read_to_end(&mut out)
+                .map_err(|e| err(format!("gzip: {e}")))?;
+            return Self::parse_uncompressed(&out);
+        }
+        Self::parse_uncompressed(bytes)
+    }
+
+    fn parse_uncompressed(bytes: &[u8]) -> Result<Self, AbwError> {
+        if bytes.len() < HEADER_LEN {
+            return Err(err("shorter than its header"));
+        }
+        let mut r = Reader {
+            data: bytes,
+            pos: 0,
+        };
+        let center = r.point()?;
+        let min_height = r.f32()?;
+        let max_height = r.f32()?;
+        let bounding_sphere = (r.point()?, r.f64()?);
+        let horizon_occlusion_point = r.point()?;
+
+        let vertex_count = r.u32()? as usize;
+        if vertex_count * 6 > r.remaining() {
+            return Err(err(format!("{vertex_count} vertices overrun the tile")));
+        }
+        let mut u = r.u16s(vertex_count)?;
+        let mut v = r.u16s(vertex_count)?;
+        let mut height = r.u16s(vertex_count)?;
+        decode_zigzag_deltas(&mut u);
+        decode_zigzag_deltas(&mut v);
+        decode_zigzag_deltas(&mut height);
+
+        // 32 bit indices start 4 byte aligned
+        let wide = vertex_count > 65536;
+        if wide {
+            r.take((4 - r.pos % 4) % 4)?;
+        }
+        let triangle_count = r.u32()? as usize;
+        let mut indices = r.indices(triangle_count * 3, wide)?;
+        decode_high_water_mark(&mut indices);
+        if indices.iter().any(|&i| i as usize >= vertex_count) {
+            return Err(err("triangle index out of range"));
+        }
+
+        let edge = |r: &mut Reader| -> Result<Vec<u32>, AbwError> {
+            let count = r.u32()? as usize;
+            let indices = r.indices(count, wide)?;
+            if indices.iter().any(|&i| i as usize >= vertex_count) {
+                return Err(err("edge index out of range"));
+            }
+            Ok(indices)
+        };
+        let west_indices = edge(&mut r)?;
+        let south_indices = edge(&mut r)?;
+        let east_indices = edge(&mut r)?;
+        let north_indices = edge(&mut r)?;
+
+        let mut normals = None;
+        let mut water_mask = None;
+        while r.remaining() >= 5 {
+            let id = r.u8()?;
+            let len = r.u32()? as usize;
+            let data = r.take(len)?;
+            match id {
+                EXTENSION_OCT_NORMALS if len == vertex_count * 2 => {
+                    normals = Some(data.chunks(2).map(|n| oct_decode(n[0], n[1])).collect());
+                }
+                EXTENSION_WATER_MASK if len == 1 => {
+                    water_mask = Some(WaterMask::Uniform(data[0]));
+                }
+                EXTENSION_WATER_MASK if len == (WATER_MASK_SIZE * WATER_MASK_SIZE) as usize => {
+                    water_mask = Some(WaterMask::Grid(data.to_vec()));
+                }
+                EXTENSION_OCT_NORMALS | EXTENSION_WATER_MASK => {
+                    return Err(err(format!("extension {id} has the wrong length {len}")));
+                }
+                // metadata and anything newer
+                _ => {}
+            }
+        }
+
+        Ok(Self {
+            center,
+            min_height,
+            max_height,
+            bounding_sphere,
+            horizon_occlusion_point,
+            u,
+            v,
+            height,
+            indices,
+            west_indices,
+            south_indices,
+            east_indices,
+            north_indices,
+            normals,
+            water_mask,
+        })
+    }
+
+    /// The edge vertices in a loop counter-clockwise seen from above:
+    /// east along the south edge, north up the east, west along the north
+    /// and back south down the west.
+    fn edge_loop(&self) -> Vec<u32> {
+        let sorted = |edge: &[u32], key: &dyn Fn(u32) -> i32| {
+            let mut edge = edge.to_vec();
+            edge.sort_by_key(|&i| key(i));
+            edge
+        };
+        let (u, v) = (&self.u, &self.v);
+        let mut edge = sorted(&self.south_indices, &|i| u[i as usize] as i32);
+        edge.extend(sorted(&self.east_indices, &|i| v[i as usize] as i32));
+        edge.extend(sorted(&self.north_indices, &|i| -(u[i as usize] as i32)));
+        edge.extend(sorted(&self.west_indices, &|i| -(v[i as usize] as i32)));
+        edge.dedup();
+        if edge.len() > 1 && edge.first() != edge.last() {
+            edge.push(edge[0]);
+        }
+        edge
+    }
+}
+
+/// The tile's mesh relative to its center, with skirts hanging from the
+/// edges to hide cracks against neighbors of other levels. Without the
+/// normals extension the vertices have no normals.
+pub fn build_terrain_mesh(tile: TerrainTileId, mesh: &QuantizedMesh) -> OwnedDecodedMesh {
+    let [west, south, east, north] = tile.rectangle();
+    let (min_height, max_height) = (mesh.min_height as f64, mesh.max_height as f64);
+    let vertex = |i: usize, drop: f64| {
+        let u = mesh.u[i] as f64 / QUANTIZED_MAX;
+        let v = mesh.v[i] as f64 / QUANTIZED_MAX;
+        let h = min_height + (max_height - min_height) * mesh.height[i] as f64 / QUANTIZED_MAX;
+        let lon = west + (east - west) * u;
+        let lat = south + (north - south) * v;
+        let p = geodetic_to_ecef_z_up(lat, lon, h - drop) - mesh.center.to_vec();
+        Vertex {
+            position: [p.x as f32, p.y as f32, p.z as f32],
+            normal: mesh.normals.as_ref().map_or([0.0; 3], |n| n[i]),
+            color: [1.0; 4],
+            texcoord0: [u as f32, v as f32],
+            texcoord1: [0.0; 2],
+        }
+    };
+
+    let mut vertices: Vec<Vertex> = (0..mesh.u.len()).map(|i| vertex(i, 0.0)).collect();
+    let mut indices = mesh.indices.clone();
+
+    let edge = mesh.edge_loop();
+    let first_skirt = vertices.len() as u32;
+    vertices.extend(
+        edge.iter()
+            .map(|&i| vertex(i as usize, tile.skirt_height())),
+    );
+    for k in 0..edge.len().saturating_sub(1) {
+        let (p, q) = (edge[k], edge[k + 1]);
+        let (p_low, q_low) = (first_skirt + k as u32, first_skirt + k as u32 + 1);
+        indices.extend([p, p_low, q_low, p, q_low, q]);
+    }
+
+    OwnedDecodedMesh::from_vertices_and_indices(vertices, indices)
+}
+
+fn mix(land: [u8; 4], water: [u8; 4], coverage: u8) -> [u8; 4] {
+    let t = coverage as f32 / 255.0;
+    std::array::from_fn(|c| (land[c] as f32 + (water[c] as f32 - land[c] as f32) * t).round() as u8)
+}
+
+/// The tile's base color: land and water by the mask, or plain land.
+pub fn water_mask_texture(mask: Option<&WaterMask>) -> Texture {
+    let (size, texels) = match mask {
+        None => (1, vec![0]),
+        Some(WaterMask::Uniform(coverage)) => (1, vec![*coverage]),
+        Some(WaterMask::Grid(coverage)) => (WATER_MASK_SIZE, coverage.clone()),
+    };
+    Texture {
+        width: size,
+        height: size,
+        encoding: TextureEncoding::Rgba8,
+        levels: vec![texels
+            .into_iter()
+            .flat_map(|c| mix(TERRAIN_LAND_COLOR, TERRAIN_WATER_COLOR, c))
+            .collect()],
+    }
+}
+
+/// Turns a downloaded `.terrain` tile into renderable content.
+pub fn decode_terrain_tile(
+    tile: TerrainTileId,
+    bytes: &[u8],
+    options: ContentOptions,
+) -> Result<TileState, AbwError> {
+    let quantized = QuantizedMesh::parse(bytes)?;
+    let mut mesh = build_terrain_mesh(tile, &quantized);
+    if let NormalsConfig {
+        generate: true,
+        crease_angle,
+    } = options.normals
+    {
+        if !has_normals(&mesh) {
+            mesh = generate_normals(&mesh, crease_angle);
+        }
+    }
+    mesh.material_index = Some(0);
+
+    let mut texture = water_mask_texture(quantized.water_mask.as_ref());
+    if options.mipmaps == MipmapMode::Cpu {
+        generate_mips(&mut texture);
+    }
+
+    // positions are relative to the tile center, like an RTC tile's
+    let quantization = Quantization::for_tile(std::slice::from_ref(&mesh), true);
+    Ok(TileState::Decoded {
+        nodes: vec![Node {
+            transform: Matrix4::from_translation(quantized.center.to_vec()),
+            mesh_indices: vec![0],
+        }],
+        meshes: vec![pack_mesh(&mesh, quantization)],
+        // one mesh with one primitive
+        mesh_primitives: std::iter::once(0..1).collect(),
+        textures: vec![texture],
+        materials: vec![Material {
+            base_color_texture: Some(TextureRef {
+                index: 0,
+                tex_coord: 0,
+                transform: TextureTransform::default(),
+            }),
+            metallic_factor: 0.0,
+            ..Default::default()
+        }],
+    })
+}
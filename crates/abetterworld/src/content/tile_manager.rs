@@ -35,6 +35,11 @@ impl TileManager {
         true
     }
 
+    /// Tiles marked loaded so far; with nothing unloaded, the number requested.
+    pub fn loaded_count(&self) -> usize {
+        self.tile_content_loaded.read().unwrap().len()
+    }
+
     pub fn mark_tile_unloaded(&self, key: TileKey) {
         let mut content_map = self.tile_content_loaded.write().unwrap();
         if let Some(pos) = content_map.iter().position(|x| *x == key) {
@@ -1,24 +1,33 @@
 // ─── Crate: content ────────────────────────────────────────────────────────────
 use crate::content::{
-    build_materials, build_meshes, build_nodes, download_content, parse_glb,
-    parse_textures_from_gltf, upload_textures_to_gpu, Client, TileContent, TileMessage,
-    TilePipelineMessage,
+    build_material_resources, build_materials, build_meshes, build_nodes, decode_decoded_tile,
+    decode_globe_tile, decode_terrain_tile, download_content, encode_decoded_tile, generate_mips,
+    generate_normals, has_normals, linear_textures, pack_mesh, parse_glb, parse_textures_from_gltf,
+    rtc_offset, unwrap_b3dm, upload_textures_to_gpu, Client, Quantization,
+    SupportedTextureEncodings, TileContent, TileMessage, TilePipelineMessage,
 };
 
+use crate::cache::TilesetCache;
+
 // ─── Crate: content::types ─────────────────────────────────────────────────────
+use crate::content::pager::PagerStatus;
 use crate::content::types::TileState;
 
 use crate::decode::DracoClient;
 use crate::helpers::channel::{Receiver, Sender};
 // ─── Crate: helpers ────────────────────────────────────────────────────────────
-use crate::helpers::{AbwError, TileLoadingContext};
-use crate::render::{Mesh, RenderableState};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::helpers::PlatformAwait;
+use crate::helpers::{spawn_detached, AbwError, TileLoadingContext};
+use crate::render::{Mesh, RenderableState, TextureUploader};
+use crate::{MipmapMode, NormalsConfig};
 
-use tracing::{event, Level};
+use tracing::{event, Instrument, Level};
 
 // ─── External ──────────────────────────────────────────────────────────────────
 use bytes::Bytes;
 use std::sync::Arc;
+use tokio::sync::Semaphore;
 use wgpu::util::DeviceExt;
 
 fn download_content_for_tile_shared(
@@ -26,17 +35,22 @@ fn download_content_for_tile_shared(
     content_type: String,
     bytes: Bytes,
 ) -> Result<Vec<u8>, AbwError> {
-    if content_type != "model/gltf-binary" {
+    // terrain servers disagree on its content type, the decoder checks it;
+    // b3dm usually comes as application/octet-stream, so meshes go by magic
+    let terrain = matches!(load.state, TileState::Terrain(_));
+    if !terrain && !bytes.starts_with(b"glTF") && !bytes.starts_with(b"b3dm") {
         event!(
             Level::ERROR,
-            "Unsupported content type: URI: {}, Content-Type: {}, Bytes: {:?}",
+            "Unsupported content type: URI: {}, Content-Type: {}, {} bytes",
             load.uri,
             content_type,
-            bytes
+            bytes.len()
         );
         return Err(AbwError::TileLoading(format!(
-            "Unsupported content type: URI: {}, Content-Type: {}, Bytes: {:?}",
-            load.uri, content_type, bytes
+            "Unsupported content type: URI: {}, Content-Type: {}, {} bytes",
+            load.uri,
+            content_type,
+            bytes.len()
         )));
     }
 
@@ -45,9 +59,10 @@ fn download_content_for_tile_shared(
 
 async fn download_content_for_tile(
     client: &Client,
+    cache: &TilesetCache,
     load: &TileContent,
 ) -> Result<Vec<u8>, AbwError> {
-    let (content_type, bytes) = download_content(&client, &load.uri).await?;
+    let (content_type, bytes) = download_content(client, cache, &load.uri).await?;
     download_content_for_tile_shared(load, content_type, bytes)
 }
 
@@ -55,9 +70,19 @@ async fn process_content_bytes(
     decoder: Arc<DracoClient>,
     load: &mut TileContent,
     bytes: Vec<u8>,
+    options: ContentOptions,
 ) -> Result<(), AbwError> {
+    if let TileState::Terrain(id) = load.state {
+        load.state = decode_terrain_tile(id, &bytes, options)
+            .tile_loading(&format!("Failed to parse terrain: URI: {}", load.uri,))?;
+        return Ok(());
+    }
+
+    let (rtc_center, glb) =
+        unwrap_b3dm(&bytes).tile_loading(&format!("Failed to parse b3dm: URI: {}", load.uri,))?;
+
     let (gltf_json, gltf_bin) =
-        parse_glb(&bytes).tile_loading(&format!("Failed to parse GLB: URI: {}", load.uri,))?;
+        parse_glb(glb).tile_loading(&format!("Failed to parse GLB: URI: {}", load.uri,))?;
 
     // write out the gltf_json for debugging
     /*     #[cfg(debug_assertions)]
@@ -70,22 +95,41 @@ async fn process_content_bytes(
             .unwrap();
     } */
 
-    let meshes = build_meshes(decoder, &gltf_json, &gltf_bin)
+    let (meshes, mesh_primitives) = build_meshes(decoder, &gltf_json, &gltf_bin)
         .await
         .tile_loading(&format!("Failed to parse GLB meshes: URI: {}", load.uri,))?;
+    let quantization =
+        Quantization::for_tile(&meshes, rtc_offset(&gltf_json, rtc_center).is_some());
+    let meshes = meshes
+        .iter()
+        .map(|mesh| match options.normals {
+            NormalsConfig {
+                generate: true,
+                crease_angle,
+            } if !has_normals(mesh) => {
+                pack_mesh(&generate_normals(mesh, crease_angle), quantization)
+            }
+            _ => pack_mesh(mesh, quantization),
+        })
+        .collect();
 
-    let textures = parse_textures_from_gltf(&gltf_json, &gltf_bin)
-        .tile_loading(&format!("Failed to parse GLB textures: URI: {}", load.uri,))?;
+    let mut textures =
+        parse_textures_from_gltf(&gltf_json, &gltf_bin, options.texture_encodings)
+            .tile_loading(&format!("Failed to parse GLB textures: URI: {}", load.uri,))?;
+    if options.mipmaps == MipmapMode::Cpu {
+        textures.iter_mut().for_each(generate_mips);
+    }
 
     let materials = build_materials(&gltf_json)
         .tile_loading(&format!("Failed to parse GLB materials: URI: {}", load.uri,))?;
 
-    let nodes = build_nodes(&gltf_json)
+    let nodes = build_nodes(&gltf_json, rtc_center)
         .tile_loading(&format!("Failed to parse GLB nodes: URI: {}", load.uri,))?;
 
     load.state = TileState::Decoded {
         nodes,
         meshes,
+        mesh_primitives,
         textures,
         materials,
     };
@@ -93,80 +137,295 @@ async fn process_content_bytes(
     Ok(())
 }
 
-pub async fn load_content(
-    client: &Client,
-    header: TileMessage,
-    mut tile: TileContent,
-    render_time: &mut Sender<TilePipelineMessage>,
-    decoder: Arc<DracoClient>,
-) -> Result<(), AbwError> {
-    let _span = tracing::debug_span!("load_content").entered();
+/// The decoded cache key for `uri`. Generated normals are baked into the
+/// entry, so tiles decoded with generation on, at each crease angle, are
+/// kept apart from those decoded without.
+fn decoded_cache_key(uri: &str, normals: NormalsConfig) -> String {
+    if normals.generate {
+        format!("{uri}#normals:{}", normals.crease_angle)
+    } else {
+        uri.to_string()
+    }
+}
+
+async fn load_decoded_from_cache(
+    cache: &TilesetCache,
+    uri: &str,
+    options: ContentOptions,
+) -> Option<TileState> {
+    let texture_encodings = options.texture_encodings;
+    match cache
+        .get_decoded(&decoded_cache_key(uri, options.normals))
+        .await
+    {
+        Ok(Some(bytes)) => match decode_decoded_tile(&bytes) {
+            // transcoded for a device with other formats, redo it for this one
+            Ok(TileState::Decoded { textures, .. })
+                if !textures
+                    .iter()
+                    .all(|t| texture_encodings.supports(t.encoding)) =>
+            {
+                None
+            }
+            Ok(state) => Some(state),
+            Err(e) => {
+                // stale version or damaged entry, fall back to a full decode and overwrite it
+                event!(
+                    Level::WARN,
+                    "Ignoring decoded cache entry for {}: {}",
+                    uri,
+                    e
+                );
+                None
+            }
+        },
+        Ok(None) => None,
+        Err(e) => {
+            event!(Level::WARN, "Decoded cache read failed for {}: {}", uri, e);
+            None
+        }
+    }
+}
 
-    if tile.state == TileState::ToLoad {
-        if let Err(e) = content_load(&client, &mut tile, decoder).await {
-            event!(Level::ERROR, "load failed: {e}");
-            return Err(e);
+async fn store_decoded_in_cache(cache: &TilesetCache, tile: &TileContent, options: ContentOptions) {
+    let encoded = match encode_decoded_tile(&tile.state) {
+        Ok(encoded) => encoded,
+        Err(e) => {
+            event!(
+                Level::WARN,
+                "Failed to encode decoded tile {}: {}",
+                tile.uri,
+                e
+            );
+            return;
         }
+    };
 
-        // that's a bit hacky, but we want to avoid cloning the tile
-        let _ = render_time
-            .send(TilePipelineMessage::Load((header, tile)))
-            .await;
+    let key = decoded_cache_key(&tile.uri, options.normals);
+    if let Err(e) = cache.insert_decoded(&key, Bytes::from(encoded)).await {
+        event!(
+            Level::WARN,
+            "Decoded cache write failed for {}: {}",
+            tile.uri,
+            e
+        );
     }
-    Ok(())
 }
 
-pub async fn wait_and_load_content(
-    client: &Client,
+/// Per-worker settings for turning downloaded tiles into renderable content.
+#[derive(Clone, Copy, Debug)]
+pub struct ContentOptions {
+    /// Read and write decoded tiles through the cache.
+    pub decoded_cache: bool,
+    /// Block formats the device can sample, for KTX2 transcoding.
+    pub texture_encodings: SupportedTextureEncodings,
+    /// With `MipmapMode::Cpu`, RGBA8 textures get their mip chains here.
+    pub mipmaps: MipmapMode,
+    /// Normal generation for meshes without them.
+    pub normals: NormalsConfig,
+}
+
+/// A downloaded tile waiting in the queue between the download and decode pools.
+#[derive(Clone)]
+pub struct DecodeJob {
+    pub header: TileMessage,
+    pub tile: TileContent,
+    pub bytes: Vec<u8>,
+}
+
+/// Fills `tile` from the decoded cache. A hit skips GLB parsing, Draco and
+/// image decoding entirely.
+async fn load_cached_content(
+    cache: &TilesetCache,
+    tile: &mut TileContent,
+    options: ContentOptions,
+) -> bool {
+    let Some(mut state) = load_decoded_from_cache(cache, &tile.uri, options).await else {
+        return false;
+    };
+
+    // entries written with mipmaps off or on the GPU only have the base level
+    if let (TileState::Decoded { textures, .. }, MipmapMode::Cpu) = (&mut state, options.mipmaps) {
+        textures.iter_mut().for_each(generate_mips);
+    }
+    tile.state = state;
+    true
+}
+
+/// What every download needs, cloned into the task that runs it.
+#[derive(Clone)]
+pub struct DownloadContext {
+    pub client: Client,
+    pub cache: Arc<TilesetCache>,
+    pub decode_tx: Sender<DecodeJob>,
+    pub render_time: Sender<TilePipelineMessage>,
+    pub options: ContentOptions,
+    pub status: Arc<PagerStatus>,
+}
+
+/// Download stage. Takes tile requests from the pager and runs up to
+/// `concurrency` downloads at once as tasks on the async runtime, each
+/// handing its bytes to the decode pool. Unload/update messages go straight
+/// to the renderer. Returns once the pager closes its queue.
+pub async fn download_content_stage(
+    context: DownloadContext,
     rx: &mut Receiver<TilePipelineMessage>,
-    render_time: &mut Sender<TilePipelineMessage>,
+    concurrency: usize,
 ) -> Result<(), AbwError> {
-    let decoder = Arc::new(DracoClient::new());
-    while let Ok(tile) = rx.recv().await {
-        match tile {
-            TilePipelineMessage::Unload(id) => {
-                let _ = render_time.send(TilePipelineMessage::Unload(id)).await;
-                continue;
+    let permits = Arc::new(Semaphore::new(concurrency.max(1)));
+    while let Ok(message) = rx.recv().await {
+        let (header, tile) = match message {
+            TilePipelineMessage::Load((header, tile))
+                if matches!(
+                    tile.state,
+                    TileState::ToLoad | TileState::Terrain(_) | TileState::Globe(_)
+                ) =>
+            {
+                (header, tile)
             }
-            TilePipelineMessage::Load((h, t)) => {
-                load_content(client, h, t, render_time, decoder.clone()).await?;
+            TilePipelineMessage::Load(_) => {
+                context.status.finished();
+                continue;
             }
-            TilePipelineMessage::Update(message) => {
-                let _ = render_time.send(TilePipelineMessage::Update(message)).await;
+            other => {
+                let _ = context.render_time.send(other).await;
                 continue;
             }
-        }
+        };
+
+        let Ok(permit) = permits.clone().acquire_owned().await else {
+            break;
+        };
+        let context = context.clone();
+        let download = async move {
+            download_tile(&context, header, tile).await;
+            drop(permit);
+        };
+        spawn_detached(download.instrument(tracing::debug_span!("download_content")));
     }
     Ok(())
 }
 
-pub async fn content_load(
-    client: &Client,
-    tile: &mut TileContent,
-    decoder: Arc<DracoClient>,
-) -> Result<(), AbwError> {
-    if tile.state != TileState::ToLoad {
-        return Err(AbwError::TileLoading(format!(
-            "Tile is not in ToLoad state: {}",
-            tile.uri
-        )));
+/// Fetches one tile and queues it for decoding, waiting while the queue is
+/// full. Decoded cache hits go straight to the renderer.
+async fn download_tile(context: &DownloadContext, header: TileMessage, mut tile: TileContent) {
+    let DownloadContext {
+        client,
+        cache,
+        options,
+        status,
+        ..
+    } = context;
+
+    // globe tiles fetch all their imagery layers at once, and are built on
+    // the decode pool
+    let bytes = if let TileState::Globe(globe) = &mut tile.state {
+        let fetches = globe
+            .imagery
+            .iter_mut()
+            .map(|request| request.fetch(client, cache));
+        futures::future::join_all(fetches).await;
+        Vec::new()
+    } else if options.decoded_cache && load_cached_content(cache, &mut tile, *options).await {
+        let message = TilePipelineMessage::Load((header, tile));
+        hand_off(&context.render_time, message).await;
+        status.finished();
+        return;
+    } else {
+        match download_content_for_tile(client, cache, &tile).await {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                event!(Level::ERROR, "download failed: {e}");
+                status.finished();
+                return;
+            }
+        }
+    };
+
+    let job = DecodeJob {
+        header,
+        tile,
+        bytes,
+    };
+    if !hand_off(&context.decode_tx, job).await {
+        // the decode pool is gone, so nothing else will finish it
+        status.finished();
     }
+}
+
+/// Sends on one of the pipeline's bounded channels. The native channels
+/// block while full, which must not hold up the runtime's other downloads.
+#[cfg(not(target_arch = "wasm32"))]
+async fn hand_off<T>(tx: &Sender<T>, item: T) -> bool {
+    tokio::task::block_in_place(|| tx.send(item).platform_await()).is_ok()
+}
+
+#[cfg(target_arch = "wasm32")]
+async fn hand_off<T>(tx: &Sender<T>, item: T) -> bool {
+    tx.send(item).await.is_ok()
+}
+
+/// Decode pool worker. Turns downloaded bytes into decoded tiles for the
+/// renderer, writing them through the decoded cache when it is on.
+pub async fn decode_content_worker(
+    cache: &TilesetCache,
+    rx: &mut Receiver<DecodeJob>,
+    render_time: &mut Sender<TilePipelineMessage>,
+    options: ContentOptions,
+    status: &PagerStatus,
+) -> Result<(), AbwError> {
+    let decoder = Arc::new(DracoClient::new());
+    while let Ok(DecodeJob {
+        header,
+        mut tile,
+        bytes,
+    }) = rx.recv().await
+    {
+        // a span entered across the awaits below would stay entered on
+        // this thread while other tasks ran, so it follows the job instead
+        let decode = async {
+            if let TileState::Globe(globe) = &tile.state {
+                tile.state = decode_globe_tile(globe, options);
+                let _ = render_time
+                    .send(TilePipelineMessage::Load((header, tile)))
+                    .await;
+                return;
+            }
+
+            if let Err(e) = process_content_bytes(decoder.clone(), &mut tile, bytes, options).await
+            {
+                event!(Level::ERROR, "decode failed: {e}");
+                return;
+            }
+
+            if options.decoded_cache {
+                store_decoded_in_cache(cache, &tile, options).await;
+            }
 
-    let data = download_content_for_tile(client, &tile).await?;
-    process_content_bytes(decoder, tile, data).await
+            let _ = render_time
+                .send(TilePipelineMessage::Load((header, tile)))
+                .await;
+        };
+        decode
+            .instrument(tracing::debug_span!("decode_content"))
+            .await;
+        status.finished();
+    }
+    Ok(())
 }
 
 pub fn content_render_setup(
     device: &wgpu::Device,
     queue: &wgpu::Queue,
-    texture_bind_group_layout: &wgpu::BindGroupLayout,
+    material_bind_group_layout: &wgpu::BindGroupLayout,
+    uploader: &TextureUploader,
     tile: TileContent, // you own this
-    surface_format: wgpu::TextureFormat,
 ) -> Result<RenderableState, AbwError> {
     // Consume tile.state and move its contents out
     let TileState::Decoded {
         nodes,
         meshes,
+        mesh_primitives,
         textures,
         materials,
     } = tile.state
@@ -175,12 +434,15 @@ pub fn content_render_setup(
     };
 
     // You still have the moved-out values by ownership now
-    let textures = upload_textures_to_gpu(
+    let linear = linear_textures(&materials, textures.len());
+    let textures = upload_textures_to_gpu(device, queue, &textures, &linear, uploader);
+    let (materials, default_material) = build_material_resources(
         device,
         queue,
+        &materials,
         &textures,
-        texture_bind_group_layout,
-        surface_format,
+        material_bind_group_layout,
+        uploader,
     );
 
     let return_meshes = meshes
@@ -188,21 +450,22 @@ pub fn content_render_setup(
         .map(|mesh| {
             let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: Some("Vertex Buffer"),
-                contents: bytemuck::cast_slice(mesh.as_vertex_slice()),
+                contents: &mesh.vertices,
                 usage: wgpu::BufferUsages::VERTEX,
             });
             let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: Some("Index Buffer"),
-                contents: bytemuck::cast_slice(mesh.as_index_slice()),
+                contents: bytemuck::cast_slice(&mesh.indices),
                 usage: wgpu::BufferUsages::INDEX,
             });
-            let num_indices = mesh.as_index_slice().len() as u32;
 
             Mesh {
                 vertex_buffer,
                 index_buffer,
-                num_indices,
+                num_indices: mesh.indices.len() as u32,
                 material_index: mesh.material_index,
+                layout: mesh.layout,
+                dequantize: mesh.dequantize(),
             }
         })
         .collect();
@@ -210,7 +473,9 @@ pub fn content_render_setup(
     Ok(RenderableState {
         nodes, // moved, not cloned
         meshes: return_meshes,
-        textures: textures.into_iter().map(|t| t.into()).collect(),
-        materials, // moved, not cloned
+        mesh_primitives,
+        textures,
+        materials,
+        default_material,
     })
 }
@@ -1,6 +1,6 @@
 use crate::{
     content::{
-        ChildrenKeys, RefineMode, TileInfo, TileManager, TileSource, TileSourceContent,
+        ChildrenKeys, RefineMode, TileInfo, TileLayer, TileManager, TileSource, TileSourceContent,
         TileSourceContentState,
     },
     dynamics::CameraRefinementData,
@@ -123,6 +123,7 @@ pub fn gather_priority_tiles<'a>(
                 volume: tile.bounding_volume.clone(),
                 refine: RefineMode::Replace, //tile.refine,
                 geometric_error: tile.geometric_error,
+                layer: TileLayer::Content,
             });
         }
 
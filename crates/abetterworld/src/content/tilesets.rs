@@ -1,4 +1,7 @@
-use crate::content::{download_content, BoundingVolume, Client, TileKey};
+use crate::cache::TilesetCache;
+use crate::content::{
+    download_content, BoundingVolume, Client, TerrainLayer, TerrainNode, TileKey,
+};
 use crate::dynamics::CameraRefinementData;
 use crate::helpers::{hash_uri, spawn_detached, AbwError, TileLoadingContext};
 use crate::Source;
@@ -65,6 +68,10 @@ pub struct TileSource {
 
     #[serde(skip, default)]
     pub needs_refinement_flag: Option<bool>,
+
+    /// Set on quantized-mesh tiles, whose children are laid out on refinement.
+    #[serde(skip, default)]
+    pub terrain: Option<TerrainNode>,
 }
 
 // This is not optimal (make a custom implementation that doesn't allocate extra strings)
@@ -97,7 +104,18 @@ fn is_nested_tileset(uri: &str) -> bool {
 }
 
 fn is_visual(uri: &str) -> bool {
-    is_nested_ext(uri, ".glb")
+    is_nested_ext(uri, ".glb") || is_nested_ext(uri, ".terrain")
+}
+
+/// A tileset's root, or for quantized-mesh terrain a root laid over its
+/// `layer.json`.
+fn parse_tileset(uri: &str, bytes: &[u8]) -> Result<Option<TileSource>, AbwError> {
+    if is_nested_ext(uri, "layer.json") {
+        return Ok(Some(TerrainLayer::parse(bytes)?.root()));
+    }
+    serde_json::from_slice::<TileSourceRoot>(bytes)
+        .map(|ts| ts.root)
+        .tile_loading("Failed to parse tileset JSON")
 }
 
 fn extract_session(url: &str) -> Option<&str> {
@@ -140,7 +158,7 @@ fn compute_sse(
 }
 
 /// Drop-in `needs_refinement` using the 12-number box.
-fn needs_refinement(
+pub fn needs_refinement(
     camera: &CameraRefinementData,
     bv: &BoundingVolume, // <- your Google 12-number box
     geometric_error: f64,
@@ -169,6 +187,7 @@ fn needs_refinement(
 
 fn load_tile(
     client: &Client,
+    cache: &Arc<TilesetCache>,
     key: &String,
     tile: &mut TileSourceContent,
 ) -> Result<ParsingState, AbwError> {
@@ -184,25 +203,26 @@ fn load_tile(
         });
 
         let client = client.clone();
+        let cache = cache.clone();
         let uri = tile.uri.clone();
 
         spawn_detached({
             let tile_dst = tile_dst.clone();
             async move {
-                match download_content(&client, &uri).await {
+                match download_content(&client, &cache, &uri).await {
                     Ok((content_type, bytes)) => {
                         if content_type.starts_with("application/json") {
-                            match serde_json::from_slice::<TileSourceRoot>(&bytes) {
-                                Ok(ts) => {
+                            match parse_tileset(&uri, &bytes) {
+                                Ok(root) => {
                                     event!(Level::INFO, "Loaded tileset: {}", uri);
 
                                     *tile_dst.write().unwrap() = TileSourceRootShared {
-                                        root: ts.root,
+                                        root,
                                         done: true,
                                     }; // <- store it
                                 }
                                 Err(e) => {
-                                    event!(Level::ERROR, "Failed to parse tileset JSON: {}", e);
+                                    event!(Level::ERROR, "{}", e);
                                 }
                             }
                         } else {
@@ -251,6 +271,7 @@ fn build_child_tile_content(parent: &Option<&TileSourceContent>, tile: &mut Tile
 fn process_tile_content(
     source: &Source,
     client: &Client,
+    cache: &Arc<TilesetCache>,
     camera: &CameraRefinementData,
     tileset: &Option<&TileSourceContent>,
     tile_content: &mut TileSourceContent,
@@ -260,8 +281,10 @@ fn process_tile_content(
 
         return load_tile(
             client,
+            cache,
             match source {
                 Source::Google { key, .. } => key,
+                Source::QuantizedMesh { url } => url,
                 _ => return Err(AbwError::TileLoading("Unsupported source type".into())),
             },
             tile_content,
@@ -291,7 +314,7 @@ fn process_tile_content(
             // We should already have a permanent root, process it immediately
 
             if let Some(root) = permanent.as_mut().and_then(|p| p.root.as_mut()) {
-                process_tile(source, client, camera, &Some(tile_content), root)?;
+                process_tile(source, client, cache, camera, &Some(tile_content), root)?;
             }
             (loaded, ParsingState::Stable)
         }
@@ -335,12 +358,13 @@ pub fn force_refinement(tile: &mut TileSource, flag: Option<bool>, skip_parent:
 pub fn process_tile(
     source: &Source,
     client: &Client,
+    cache: &Arc<TilesetCache>,
     camera: &CameraRefinementData,
     tileset: &Option<&TileSourceContent>,
     tile: &mut TileSource,
 ) -> Result<ParsingState, AbwError> {
     let mut parsing_state = match &mut tile.content {
-        Some(content) => process_tile_content(source, client, camera, tileset, content)?,
+        Some(content) => process_tile_content(source, client, cache, camera, tileset, content)?,
         None => ParsingState::Stable,
     };
 
@@ -355,9 +379,13 @@ pub fn process_tile(
     tile.needs_refinement_flag = Some(needs_refinement);
 
     if needs_refinement {
+        if tile.children.is_none() {
+            tile.children = tile.terrain.as_ref().map(TerrainNode::children);
+        }
         if let Some(children) = tile.children.as_mut() {
             for child in children.iter_mut() {
-                let child_parsing_state = process_tile(source, client, camera, tileset, child)?;
+                let child_parsing_state =
+                    process_tile(source, client, cache, camera, tileset, child)?;
 
                 if parsing_state == ParsingState::Stable {
                     parsing_state = child_parsing_state;
@@ -374,6 +402,7 @@ pub fn process_tile(
 pub fn go(
     source: &Source,
     client: &Client,
+    cache: &Arc<TilesetCache>,
     camera: &CameraRefinementData,
     root: &mut Option<TileSourceContent>,
 ) -> Result<ParsingState, AbwError> {
@@ -388,14 +417,28 @@ pub fn go(
                     key: hash_uri(url),
                 });
             }
+            Source::QuantizedMesh { url } => {
+                let uri = if url.ends_with("layer.json") {
+                    url.clone()
+                } else {
+                    format!("{}/layer.json", url.trim_end_matches('/'))
+                };
+                *root = Some(TileSourceContent {
+                    key: hash_uri(&uri),
+                    uri,
+                    access_key: None,
+                    session: None,
+                    loaded: None,
+                });
+            }
             _ => {
                 return Err(AbwError::TileLoading("Unsupported source type".into()));
             }
         }
     }
 
-    let mut tile = root.as_mut().unwrap();
+    let tile = root.as_mut().unwrap();
     build_child_tile_content(&None, tile);
-    let parsing_state = process_tile_content(source, client, camera, &None, &mut tile)?;
+    let parsing_state = process_tile_content(source, client, cache, camera, &None, tile)?;
     Ok(parsing_state)
 }
@@ -1,7 +1,8 @@
-use crate::{content::BoundingVolume, decode::OwnedDecodedMesh};
+use crate::content::{BoundingVolume, GlobeTile, PackedMesh, TerrainTileId};
 
 use cgmath::Matrix4;
 use smallvec::SmallVec;
+use std::ops::Range;
 
 pub const MAX_RENDERABLE_TILES: u64 = 1024;
 pub const MAX_RENDERABLE_NODES: u64 = 1024;
@@ -35,9 +36,15 @@ pub struct TileContent {
 #[derive(Debug, Clone, PartialEq)]
 pub enum TileState {
     ToLoad,
+    /// A quantized-mesh tile to download, at this place on the geographic grid.
+    Terrain(TerrainTileId),
+    /// A globe tile, built once its imagery has been fetched.
+    Globe(GlobeTile),
     Decoded {
         nodes: Vec<Node>,
-        meshes: Vec<OwnedDecodedMesh>,
+        meshes: Vec<PackedMesh>,
+        /// Range of `meshes` holding each glTF mesh's primitives.
+        mesh_primitives: Vec<Range<usize>>,
         textures: Vec<Texture>,
         materials: Vec<Material>,
     },
@@ -49,6 +56,16 @@ pub enum RefineMode {
     Replace,
 }
 
+/// Which part of the scene a tile belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TileLayer {
+    /// The source's 3D content.
+    #[default]
+    Content,
+    /// The imagery draped ellipsoid drawn beneath it.
+    Globe,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct TileInfo {
     pub children: Option<ChildrenKeys>,
@@ -56,14 +73,117 @@ pub struct TileInfo {
     pub volume: BoundingVolume,
     pub refine: RefineMode,
     pub geometric_error: f64,
+    pub layer: TileLayer,
+}
+
+/// How the bytes of a texture level are laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureEncoding {
+    Rgba8,
+    Bc7,
+    Etc2Rgb8,
+    Etc2Rgba8,
+    Astc4x4,
+}
+
+impl TextureEncoding {
+    /// Texel block edge length and bytes per block.
+    pub fn block(self) -> (u32, u32) {
+        match self {
+            TextureEncoding::Rgba8 => (1, 4),
+            TextureEncoding::Etc2Rgb8 => (4, 8),
+            TextureEncoding::Bc7 | TextureEncoding::Etc2Rgba8 | TextureEncoding::Astc4x4 => (4, 16),
+        }
+    }
+
+    /// Byte length of mip `level` for a texture of the given base size.
+    pub fn level_len(self, width: u32, height: u32, level: usize) -> usize {
+        let (dim, bytes) = self.block();
+        let blocks = |v: u32| (v >> level).max(1).div_ceil(dim) as usize;
+        blocks(width) * blocks(height) * bytes as usize
+    }
+
+    /// The wgpu format for a compressed encoding; `Rgba8` uploads with the
+    /// caller's surface format.
+    pub fn wgpu_format(self, rgba8: wgpu::TextureFormat) -> wgpu::TextureFormat {
+        let srgb = rgba8.is_srgb();
+        match self {
+            TextureEncoding::Rgba8 => rgba8,
+            TextureEncoding::Bc7 if srgb => wgpu::TextureFormat::Bc7RgbaUnormSrgb,
+            TextureEncoding::Bc7 => wgpu::TextureFormat::Bc7RgbaUnorm,
+            TextureEncoding::Etc2Rgb8 if srgb => wgpu::TextureFormat::Etc2Rgb8UnormSrgb,
+            TextureEncoding::Etc2Rgb8 => wgpu::TextureFormat::Etc2Rgb8Unorm,
+            TextureEncoding::Etc2Rgba8 if srgb => wgpu::TextureFormat::Etc2Rgba8UnormSrgb,
+            TextureEncoding::Etc2Rgba8 => wgpu::TextureFormat::Etc2Rgba8Unorm,
+            TextureEncoding::Astc4x4 => wgpu::TextureFormat::Astc {
+                block: wgpu::AstcBlock::B4x4,
+                channel: if srgb {
+                    wgpu::AstcChannel::UnormSrgb
+                } else {
+                    wgpu::AstcChannel::Unorm
+                },
+            },
+        }
+    }
+}
+
+/// Compressed texture families the device was created with. Transcoding
+/// picks from these; anything else falls back to RGBA8.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SupportedTextureEncodings {
+    pub bc7: bool,
+    pub etc2: bool,
+    pub astc: bool,
+}
+
+impl SupportedTextureEncodings {
+    pub fn from_features(features: wgpu::Features) -> Self {
+        Self {
+            bc7: features.contains(wgpu::Features::TEXTURE_COMPRESSION_BC),
+            etc2: features.contains(wgpu::Features::TEXTURE_COMPRESSION_ETC2),
+            astc: features.contains(wgpu::Features::TEXTURE_COMPRESSION_ASTC),
+        }
+    }
+
+    pub fn supports(&self, encoding: TextureEncoding) -> bool {
+        match encoding {
+            TextureEncoding::Rgba8 => true,
+            TextureEncoding::Bc7 => self.bc7,
+            TextureEncoding::Etc2Rgb8 | TextureEncoding::Etc2Rgba8 => self.etc2,
+            TextureEncoding::Astc4x4 => self.astc,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Texture {
     pub width: u32,
     pub height: u32,
-    pub rgba: Vec<u8>,
-    // add more metadata as needed (mime, index, etc)
+    pub encoding: TextureEncoding,
+    /// Mip levels, largest first; rows of texels or 4x4 blocks, tightly packed.
+    pub levels: Vec<Vec<u8>>,
+}
+
+impl Texture {
+    /// A single opaque white texel, for textures that can't be decoded and
+    /// materials without one.
+    pub fn white() -> Self {
+        Self {
+            width: 1,
+            height: 1,
+            encoding: TextureEncoding::Rgba8,
+            levels: vec![vec![255; 4]],
+        }
+    }
+
+    /// A single texel of an unperturbed tangent space normal, for materials
+    /// without a normal map.
+    pub fn flat_normal() -> Self {
+        Self {
+            levels: vec![vec![128, 128, 255, 255]],
+            ..Self::white()
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -72,8 +192,141 @@ pub struct Node {
     pub mesh_indices: Vec<usize>,
 }
 
+impl Node {
+    /// Indices of the decoded primitives this node draws, given the per glTF
+    /// mesh ranges from `build_meshes`. Unknown mesh indices draw nothing.
+    pub fn primitives<'a>(
+        &'a self,
+        mesh_primitives: &'a [Range<usize>],
+    ) -> impl Iterator<Item = usize> + 'a {
+        self.mesh_indices
+            .iter()
+            .filter_map(|&mesh| mesh_primitives.get(mesh))
+            .flat_map(|range| range.clone())
+    }
+}
+
+/// `KHR_texture_transform` parameters. The default is the identity.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureTransform {
+    pub offset: [f32; 2],
+    /// Radians, counter-clockwise in UV space.
+    pub rotation: f32,
+    pub scale: [f32; 2],
+}
+
+impl Default for TextureTransform {
+    fn default() -> Self {
+        Self {
+            offset: [0.0, 0.0],
+            rotation: 0.0,
+            scale: [1.0, 1.0],
+        }
+    }
+}
+
+impl TextureTransform {
+    /// Rows of the affine UV matrix translation * rotation * scale, applied
+    /// to (u, v, 1).
+    pub fn matrix(&self) -> [[f32; 3]; 2] {
+        let (sin, cos) = self.rotation.sin_cos();
+        let [sx, sy] = self.scale;
+        let [ox, oy] = self.offset;
+        [[cos * sx, sin * sy, ox], [-sin * sx, cos * sy, oy]]
+    }
+}
+
+/// A material's use of one of the tile's textures.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureRef {
+    /// Index into the glTF `textures` array.
+    pub index: usize,
+    /// Which `TEXCOORD_n` set to sample with.
+    pub tex_coord: u32,
+    pub transform: TextureTransform,
+}
+
+/// glTF `alphaMode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AlphaMode {
+    /// Alpha is ignored.
+    #[default]
+    Opaque,
+    /// Fragments below `alpha_cutoff` are discarded, the rest are opaque.
+    Mask,
+    /// Blended over what is behind, without writing depth.
+    Blend,
+}
+
+/// A glTF metallic-roughness material. Defaults are the spec's.
 #[derive(Debug, Clone, PartialEq)]
 pub struct Material {
-    pub base_color_texture_index: Option<usize>,
-    // Add more as needed (base_color_factor, metallic_roughness_texture, etc.)
+    /// Linear RGBA, multiplied with the texture and vertex color.
+    pub base_color_factor: [f32; 4],
+    pub base_color_texture: Option<TextureRef>,
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    /// Roughness in green, metalness in blue.
+    pub metallic_roughness_texture: Option<TextureRef>,
+    pub normal_texture: Option<TextureRef>,
+    pub normal_scale: f32,
+    /// Ambient occlusion in red.
+    pub occlusion_texture: Option<TextureRef>,
+    pub occlusion_strength: f32,
+    pub emissive_factor: [f32; 3],
+    pub emissive_texture: Option<TextureRef>,
+    pub alpha_mode: AlphaMode,
+    pub alpha_cutoff: f32,
+    pub double_sided: bool,
+    /// `KHR_materials_unlit`: base color only, no lighting.
+    pub unlit: bool,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            base_color_factor: [1.0; 4],
+            base_color_texture: None,
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            normal_scale: 1.0,
+            occlusion_texture: None,
+            occlusion_strength: 1.0,
+            emissive_factor: [0.0; 3],
+            emissive_texture: None,
+            alpha_mode: AlphaMode::Opaque,
+            alpha_cutoff: 0.5,
+            double_sided: false,
+            unlit: false,
+        }
+    }
+}
+
+impl Material {
+    /// Texture slots in binding order: base color, metallic-roughness,
+    /// normal, occlusion, emissive.
+    pub fn textures(&self) -> [Option<TextureRef>; 5] {
+        [
+            self.base_color_texture,
+            self.metallic_roughness_texture,
+            self.normal_texture,
+            self.occlusion_texture,
+            self.emissive_texture,
+        ]
+    }
+
+    /// Texture indices this material reads as data rather than color, which
+    /// must not go through sRGB decoding.
+    pub fn linear_textures(&self) -> impl Iterator<Item = usize> {
+        [
+            self.metallic_roughness_texture,
+            self.normal_texture,
+            self.occlusion_texture,
+        ]
+        .into_iter()
+        .flatten()
+        .map(|t| t.index)
+    }
 }
@@ -0,0 +1,109 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use sha2::{Digest, Sha256, Sha512};
+use url::Url;
+
+use crate::helpers::AbwError;
+
+const GLB_MAGIC: &[u8; 4] = b"glTF";
+const GLB_HEADER_LEN: usize = 12;
+
+/// Response headers that may carry a digest of the body, newest spec first.
+pub const DIGEST_HEADERS: [&str; 3] = ["content-digest", "repr-digest", "digest"];
+
+fn url_path(url: &str) -> String {
+    Url::parse(url)
+        .map(|u| u.path().to_ascii_lowercase())
+        .unwrap_or_else(|_| url.to_ascii_lowercase())
+}
+
+fn is_glb(url: &str, content_type: &str, bytes: &[u8]) -> bool {
+    content_type == "model/gltf-binary"
+        || url_path(url).ends_with(".glb")
+        || bytes.starts_with(GLB_MAGIC)
+}
+
+fn is_json(url: &str, content_type: &str) -> bool {
+    content_type.starts_with("application/json") || url_path(url).ends_with(".json")
+}
+
+fn validate_glb(url: &str, bytes: &[u8]) -> Result<(), AbwError> {
+    if bytes.len() < GLB_HEADER_LEN || !bytes.starts_with(GLB_MAGIC) {
+        return Err(AbwError::TileLoading(format!(
+            "Missing GLB header in content from {url}"
+        )));
+    }
+
+    let declared = u32::from_le_bytes(bytes[8..12].try_into().unwrap()) as usize;
+    if declared != bytes.len() {
+        return Err(AbwError::TileLoading(format!(
+            "GLB from {url} declares {declared} bytes, got {}",
+            bytes.len()
+        )));
+    }
+
+    Ok(())
+}
+
+/// Checks one digest header value against `bytes`. Accepts both the RFC 9530
+/// (`sha-256=:base64:`) and RFC 3230 (`SHA-256=base64`) forms. Returns `Ok(false)` if
+/// none of the listed algorithms is supported, so the caller can tell "verified" from
+/// "nothing to verify".
+pub fn verify_digest(value: &str, bytes: &[u8]) -> Result<bool, AbwError> {
+    let mut verified = false;
+
+    for item in value.split(',') {
+        let Some((alg, encoded)) = item.trim().split_once('=') else {
+            continue;
+        };
+
+        let actual = match alg.trim().to_ascii_lowercase().as_str() {
+            "sha-256" => Sha256::digest(bytes).to_vec(),
+            "sha-512" => Sha512::digest(bytes).to_vec(),
+            _ => continue,
+        };
+
+        let expected = STANDARD
+            .decode(encoded.trim().trim_matches(':'))
+            .map_err(|e| AbwError::Network(format!("Malformed {alg} digest: {e}")))?;
+
+        if expected != actual {
+            return Err(AbwError::Network(format!("{alg} digest mismatch")));
+        }
+        verified = true;
+    }
+
+    Ok(verified)
+}
+
+/// Rejects a download before it reaches the cache: short or long bodies, digest
+/// mismatches, GLBs without a valid header and tilesets that aren't JSON.
+pub fn validate_download(
+    url: &str,
+    content_type: &str,
+    expected_len: Option<usize>,
+    digests: &[&str],
+    bytes: &[u8],
+) -> Result<(), AbwError> {
+    if let Some(expected) = expected_len {
+        if bytes.len() != expected {
+            return Err(AbwError::Network(format!(
+                "Content from {url} has {} bytes, expected {expected}",
+                bytes.len()
+            )));
+        }
+    }
+
+    for digest in digests {
+        verify_digest(digest, bytes)
+            .map_err(|e| AbwError::Network(format!("Content from {url}: {e}")))?;
+    }
+
+    if is_glb(url, content_type, bytes) {
+        validate_glb(url, bytes)?;
+    } else if is_json(url, content_type) {
+        serde_json::from_slice::<serde::de::IgnoredAny>(bytes)
+            .map_err(|e| AbwError::TileLoading(format!("Malformed JSON from {url}: {e}")))?;
+    }
+
+    Ok(())
+}
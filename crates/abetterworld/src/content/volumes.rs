@@ -43,6 +43,11 @@ impl BoundingVolume {
         }
     }
 
+    /// A box in the 3D Tiles layout: center, then three half axes.
+    pub fn from_box(bounding_box: [f64; 12]) -> Self {
+        BoundingVolume { bounding_box }
+    }
+
     pub fn center(&self) -> Point3<f64> {
         let b = &self.bounding_box;
         Point3::new(b[0], b[1], b[2])
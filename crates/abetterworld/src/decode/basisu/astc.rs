@@ -0,0 +1,75 @@
+//! Single-partition ASTC 4x4 block encoder. Both layouts keep endpoints at
+//! full 8-bit precision so the integer sequence is plain binary: opaque
+//! blocks use LDR RGB direct with 3-bit weights, translucent ones LDR RGBA
+//! direct with 2-bit weights.
+
+/// 4x4 weight grid, single plane, weight range 0..7.
+const BLOCK_MODE_RGB: u128 = 0b000_0101_0011;
+/// 4x4 weight grid, single plane, weight range 0..3.
+const BLOCK_MODE_RGBA: u128 = 0b000_0100_0010;
+const CEM_LDR_RGB_DIRECT: u128 = 8;
+const CEM_LDR_RGBA_DIRECT: u128 = 12;
+
+const WEIGHTS_Q8: [i32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const WEIGHTS_Q4: [i32; 4] = [0, 21, 43, 64];
+
+/// Encodes 16 RGBA texels (row-major) as one ASTC 4x4 block.
+pub(crate) fn encode_astc(texels: &[[u8; 4]; 16]) -> [u8; 16] {
+    let opaque = texels.iter().all(|t| t[3] == 255);
+    let channels = if opaque { 3 } else { 4 };
+
+    let (mut lo, mut hi) = ([255u8; 4], [0u8; 4]);
+    for t in texels {
+        for c in 0..channels {
+            lo[c] = lo[c].min(t[c]);
+            hi[c] = hi[c].max(t[c]);
+        }
+    }
+    if opaque {
+        (lo[3], hi[3]) = (255, 255);
+    }
+    // The first endpoint must have the smaller RGB sum, otherwise the
+    // decoder applies blue contraction; the bounding box already ensures it.
+    let weights: &[i32] = if opaque { &WEIGHTS_Q8 } else { &WEIGHTS_Q4 };
+
+    let indices: Vec<u8> = texels
+        .iter()
+        .map(|t| {
+            (0..weights.len())
+                .min_by_key(|&i| {
+                    let w = weights[i];
+                    (0..channels)
+                        .map(|c| {
+                            let v = ((64 - w) * lo[c] as i32 + w * hi[c] as i32 + 32) >> 6;
+                            (v - t[c] as i32).pow(2)
+                        })
+                        .sum::<i32>()
+                })
+                .unwrap() as u8
+        })
+        .collect();
+
+    let (mode, cem, weight_bits) = if opaque {
+        (BLOCK_MODE_RGB, CEM_LDR_RGB_DIRECT, 3)
+    } else {
+        (BLOCK_MODE_RGBA, CEM_LDR_RGBA_DIRECT, 2)
+    };
+
+    // Block mode, one partition, endpoint mode, then (r0 r1 g0 g1 b0 b1 [a0 a1]).
+    let mut bits = mode | cem << 13;
+    let mut pos = 17;
+    for c in 0..channels {
+        bits |= (lo[c] as u128) << pos;
+        bits |= (hi[c] as u128) << (pos + 8);
+        pos += 16;
+    }
+
+    // Weights grow downward from the top bit, each value bit-reversed.
+    for (i, &index) in indices.iter().enumerate() {
+        for b in 0..weight_bits {
+            let bit = (index >> b) & 1;
+            bits |= (bit as u128) << (127 - (i * weight_bits + b));
+        }
+    }
+    bits.to_le_bytes()
+}
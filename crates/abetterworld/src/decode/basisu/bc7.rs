@@ -0,0 +1,127 @@
+//! Single-subset BC7 (mode 6) block encoder.
+
+const WEIGHTS: [i32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+/// Encodes 16 RGBA texels (row-major) as a mode 6 block: 7-bit endpoints
+/// with a p-bit each and 4-bit indices, fitted along the principal axis.
+pub(crate) fn encode_bc7(texels: &[[u8; 4]; 16]) -> [u8; 16] {
+    let (lo, hi) = principal_extent(texels);
+
+    let mut best: Option<(u32, [[u8; 4]; 2], [u8; 16])> = None;
+    for p0 in 0..2u8 {
+        for p1 in 0..2u8 {
+            let e0 = quantize(lo, p0);
+            let e1 = quantize(hi, p1);
+            let palette = palette(&e0, &e1);
+            let mut indices = [0u8; 16];
+            let mut error = 0;
+            for (i, texel) in texels.iter().enumerate() {
+                let (index, e) = nearest(&palette, texel);
+                indices[i] = index;
+                error += e;
+            }
+            if best.as_ref().is_none_or(|b| error < b.0) {
+                best = Some((error, [e0, e1], indices));
+            }
+        }
+    }
+    let (_, [mut e0, mut e1], mut indices) = best.unwrap();
+
+    // The anchor texel's index drops its top bit, so it must be < 8.
+    if indices[0] >= 8 {
+        std::mem::swap(&mut e0, &mut e1);
+        for index in &mut indices {
+            *index = 15 - *index;
+        }
+    }
+    pack(&e0, &e1, &indices)
+}
+
+/// Endpoints of the texels' projection on their principal axis.
+fn principal_extent(texels: &[[u8; 4]; 16]) -> ([f32; 4], [f32; 4]) {
+    let mut mean = [0f32; 4];
+    for t in texels {
+        for c in 0..4 {
+            mean[c] += t[c] as f32 / 16.0;
+        }
+    }
+
+    let mut cov = [[0f32; 4]; 4];
+    for t in texels {
+        let d: Vec<f32> = (0..4).map(|c| t[c] as f32 - mean[c]).collect();
+        for i in 0..4 {
+            for j in 0..4 {
+                cov[i][j] += d[i] * d[j];
+            }
+        }
+    }
+
+    // A few power iterations are plenty for a 4x4 covariance.
+    let mut axis = [1f32, 1.0, 1.0, 1.0];
+    for _ in 0..8 {
+        let mut next = [0f32; 4];
+        for (i, row) in cov.iter().enumerate() {
+            next[i] = row.iter().zip(&axis).map(|(c, a)| c * a).sum();
+        }
+        let len = next.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if len < 1e-6 {
+            break;
+        }
+        axis = next.map(|v| v / len);
+    }
+
+    let (mut min, mut max) = (f32::MAX, f32::MIN);
+    for t in texels {
+        let proj: f32 = (0..4).map(|c| (t[c] as f32 - mean[c]) * axis[c]).sum();
+        min = min.min(proj);
+        max = max.max(proj);
+    }
+    let at = |s: f32| std::array::from_fn(|c| (mean[c] + axis[c] * s).clamp(0.0, 255.0));
+    (at(min), at(max))
+}
+
+/// Rounds to the nearest 8-bit value whose low bit is `p`.
+fn quantize(color: [f32; 4], p: u8) -> [u8; 4] {
+    color.map(|v| {
+        let q = ((v - p as f32) / 2.0).round().clamp(0.0, 127.0) as u8;
+        (q << 1) | p
+    })
+}
+
+fn palette(e0: &[u8; 4], e1: &[u8; 4]) -> [[i32; 4]; 16] {
+    std::array::from_fn(|i| {
+        let w = WEIGHTS[i];
+        std::array::from_fn(|c| ((64 - w) * e0[c] as i32 + w * e1[c] as i32 + 32) >> 6)
+    })
+}
+
+fn nearest(palette: &[[i32; 4]; 16], texel: &[u8; 4]) -> (u8, u32) {
+    palette
+        .iter()
+        .enumerate()
+        .map(|(i, p)| {
+            let e: i32 = (0..4).map(|c| (p[c] - texel[c] as i32).pow(2)).sum();
+            (i as u8, e as u32)
+        })
+        .min_by_key(|&(_, e)| e)
+        .unwrap()
+}
+
+fn pack(e0: &[u8; 4], e1: &[u8; 4], indices: &[u8; 16]) -> [u8; 16] {
+    let mut bits = 1u128 << 6;
+    let mut pos = 7;
+    let mut put = |value: u128, count: u32| {
+        bits |= value << pos;
+        pos += count;
+    };
+    for c in 0..4 {
+        put((e0[c] >> 1) as u128, 7);
+        put((e1[c] >> 1) as u128, 7);
+    }
+    put((e0[0] & 1) as u128, 1);
+    put((e1[0] & 1) as u128, 1);
+    for (i, &index) in indices.iter().enumerate() {
+        put(index as u128, if i == 0 { 3 } else { 4 });
+    }
+    bits.to_le_bytes()
+}
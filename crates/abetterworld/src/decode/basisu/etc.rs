@@ -0,0 +1,129 @@
+//! ETC1 block decoding and ETC2 EAC alpha encoding.
+
+use super::etc1s::INTENSITY;
+
+/// EAC modifier tables, indexed by the block's table index and a 3-bit texel
+/// index.
+const EAC_MODIFIERS: [[i32; 8]; 16] = [
+    [-3, -6, -9, -15, 2, 5, 8, 14],
+    [-3, -7, -10, -13, 2, 6, 9, 12],
+    [-2, -5, -8, -13, 1, 4, 7, 12],
+    [-2, -4, -6, -13, 1, 3, 5, 12],
+    [-3, -6, -8, -12, 2, 5, 7, 11],
+    [-3, -7, -9, -11, 2, 6, 8, 10],
+    [-4, -7, -8, -11, 3, 6, 7, 10],
+    [-3, -5, -8, -11, 2, 4, 7, 10],
+    [-2, -6, -8, -10, 1, 5, 7, 9],
+    [-2, -5, -8, -10, 1, 4, 7, 9],
+    [-2, -4, -8, -10, 1, 3, 7, 9],
+    [-2, -5, -7, -10, 1, 4, 6, 9],
+    [-3, -4, -7, -10, 2, 3, 6, 9],
+    [-1, -2, -3, -10, 0, 1, 2, 9],
+    [-4, -6, -8, -9, 3, 5, 7, 8],
+    [-3, -5, -7, -9, 2, 4, 6, 8],
+];
+
+/// ETC1 texel index (+small, +large, -small, -large) to ascending order.
+const ETC1_TO_SELECTOR: [usize; 4] = [2, 3, 1, 0];
+
+/// Decodes an ETC1 block into 16 RGBA texels in row-major order.
+pub(crate) fn decode_etc1(block: &[u8; 8]) -> [[u8; 4]; 16] {
+    let diff = block[3] & 2 != 0;
+    let flip = block[3] & 1 != 0;
+
+    let mut base = [[0i32; 3]; 2];
+    for c in 0..3 {
+        if diff {
+            let b5 = (block[c] >> 3) as i32;
+            let delta = (((block[c] & 7) as i8) << 5 >> 5) as i32;
+            let d5 = (b5 + delta) & 31;
+            base[0][c] = (b5 << 3) | (b5 >> 2);
+            base[1][c] = (d5 << 3) | (d5 >> 2);
+        } else {
+            let hi = (block[c] >> 4) as i32;
+            let lo = (block[c] & 15) as i32;
+            base[0][c] = hi * 17;
+            base[1][c] = lo * 17;
+        }
+    }
+    let tables = [(block[3] >> 5) as usize, ((block[3] >> 2) & 7) as usize];
+    let bits = u32::from_be_bytes([block[4], block[5], block[6], block[7]]);
+
+    let mut texels = [[0u8; 4]; 16];
+    for y in 0..4 {
+        for x in 0..4 {
+            let sub = if flip {
+                (y >= 2) as usize
+            } else {
+                (x >= 2) as usize
+            };
+            let bit = x * 4 + y;
+            let index = (((bits >> (16 + bit)) & 1) << 1 | ((bits >> bit) & 1)) as usize;
+            let modifier = INTENSITY[tables[sub]][ETC1_TO_SELECTOR[index]];
+            let texel = &mut texels[y * 4 + x];
+            for c in 0..3 {
+                texel[c] = (base[sub][c] + modifier).clamp(0, 255) as u8;
+            }
+            texel[3] = 255;
+        }
+    }
+    texels
+}
+
+/// Encodes 16 alpha values (row-major) as an EAC block by searching every
+/// table with a base and multiplier fitted to the value range.
+pub(crate) fn encode_eac_alpha(alpha: &[u8; 16]) -> [u8; 8] {
+    let min = *alpha.iter().min().unwrap() as i32;
+    let max = *alpha.iter().max().unwrap() as i32;
+
+    let mut best = (u32::MAX, 0u64);
+    if min == max {
+        // Table 13 has a zero modifier, so a flat block is exact.
+        best.1 = pack_eac(min, 1, 13, &[4; 16]);
+        return best.1.to_be_bytes();
+    }
+
+    for (table_index, table) in EAC_MODIFIERS.iter().enumerate() {
+        let span = (table[7] - table[3]) as f32;
+        let ideal = (max - min) as f32 / span;
+        for multiplier in [ideal.floor(), ideal.ceil()] {
+            let multiplier = (multiplier as i32).clamp(1, 15);
+            let center = min - table[3] * multiplier;
+            for base in [center - 1, center, center + 1] {
+                let base = base.clamp(0, 255);
+                let mut indices = [0u8; 16];
+                let mut error = 0u32;
+                for (i, &a) in alpha.iter().enumerate() {
+                    let (index, e) = table
+                        .iter()
+                        .enumerate()
+                        .map(|(k, m)| {
+                            let v = (base + m * multiplier).clamp(0, 255);
+                            (k as u8, (v - a as i32).unsigned_abs().pow(2))
+                        })
+                        .min_by_key(|&(_, e)| e)
+                        .unwrap();
+                    indices[i] = index;
+                    error += e;
+                }
+                if error < best.0 {
+                    best = (error, pack_eac(base, multiplier, table_index, &indices));
+                }
+            }
+        }
+    }
+    best.1.to_be_bytes()
+}
+
+/// Texel indices are stored column-major, three bits each, after the base,
+/// multiplier and table index.
+fn pack_eac(base: i32, multiplier: i32, table: usize, indices: &[u8; 16]) -> u64 {
+    let mut bits = (base as u64) << 56 | (multiplier as u64) << 52 | (table as u64) << 48;
+    for x in 0..4 {
+        for y in 0..4 {
+            let slot = x * 4 + y;
+            bits |= (indices[y * 4 + x] as u64) << (45 - slot * 3);
+        }
+    }
+    bits
+}
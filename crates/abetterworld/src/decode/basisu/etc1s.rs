@@ -0,0 +1,496 @@
+//! BasisLZ (ETC1S) slice decoder. The global codebooks hold endpoint and
+//! selector palettes; each slice is a stream of palette indices with
+//! spatial prediction, which resolves to one ETC1 block per 4x4 texels.
+
+use super::{err, Result};
+
+const MAX_CODE_SIZE: usize = 16;
+const TOTAL_CODE_LENGTH_CODES: usize = 21;
+const SMALL_ZERO_RUN_CODE: u32 = 17;
+const BIG_ZERO_RUN_CODE: u32 = 18;
+const SMALL_REPEAT_CODE: u32 = 19;
+/// Code length codes are stored in this order so trailing unused ones can be
+/// dropped.
+const SORTED_CODE_LENGTH_CODES: [usize; TOTAL_CODE_LENGTH_CODES] = [
+    17, 18, 19, 20, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15, 16,
+];
+
+const COLOR5_PAL0_PREV_HI: u8 = 9;
+const COLOR5_PAL1_PREV_HI: u8 = 21;
+
+const ENDPOINT_PRED_REPEAT_LAST_SYMBOL: u32 = 256;
+const ENDPOINT_PRED_MIN_REPEAT_COUNT: u32 = 3;
+const ENDPOINT_PRED_COUNT_VLC_BITS: u32 = 4;
+
+const SELECTOR_HISTORY_BUF_RLE_COUNT_THRESH: u32 = 3;
+const SELECTOR_HISTORY_BUF_RLE_COUNT_TOTAL: u32 = 64;
+
+/// ETC1 intensity modifiers in ascending order, indexed by a selector value.
+pub(crate) const INTENSITY: [[i32; 4]; 8] = [
+    [-8, -2, 2, 8],
+    [-17, -5, 5, 17],
+    [-29, -9, 9, 29],
+    [-42, -13, 13, 42],
+    [-60, -18, 18, 60],
+    [-80, -24, 24, 80],
+    [-106, -33, 33, 106],
+    [-183, -47, 47, 183],
+];
+
+/// ETC1 stores modifiers as (+small, +large, -small, -large).
+const SELECTOR_TO_ETC1: [u8; 4] = [3, 2, 0, 1];
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn bit(&mut self) -> Result<u32> {
+        let Some(byte) = self.data.get(self.pos >> 3) else {
+            return err("etc1s: read past end of stream");
+        };
+        let bit = (byte >> (self.pos & 7)) & 1;
+        self.pos += 1;
+        Ok(bit as u32)
+    }
+
+    /// Reads `n` bits, least significant first.
+    fn bits(&mut self, n: u32) -> Result<u32> {
+        let mut v = 0;
+        for i in 0..n {
+            v |= self.bit()? << i;
+        }
+        Ok(v)
+    }
+
+    /// Variable length integer in `chunk_bits` groups, each followed by a
+    /// continuation bit.
+    fn vlc(&mut self, chunk_bits: u32) -> Result<u32> {
+        let chunk_size = 1 << chunk_bits;
+        let mut v = 0u32;
+        let mut shift = 0;
+        loop {
+            let s = self.bits(chunk_bits + 1)?;
+            v |= (s & (chunk_size - 1)) << shift;
+            shift += chunk_bits;
+            if s & chunk_size == 0 {
+                return Ok(v);
+            }
+            if shift >= 32 {
+                return err("etc1s: vlc overflow");
+            }
+        }
+    }
+
+    fn huffman(&mut self, table: &Huffman) -> Result<u32> {
+        // Canonical codes arrive most significant bit first.
+        let (mut code, mut first, mut index) = (0u32, 0u32, 0u32);
+        for len in 1..=MAX_CODE_SIZE {
+            code |= self.bit()?;
+            let count = table.counts[len];
+            if code.wrapping_sub(first) < count {
+                return Ok(table.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        err("etc1s: invalid huffman code")
+    }
+
+    fn huffman_table(&mut self) -> Result<Huffman> {
+        let total_syms = self.bits(14)? as usize;
+        if total_syms == 0 {
+            return Ok(Huffman::default());
+        }
+
+        let num_code_length_codes = self.bits(5)? as usize;
+        if !(1..=TOTAL_CODE_LENGTH_CODES).contains(&num_code_length_codes) {
+            return err("etc1s: bad code length table");
+        }
+        let mut code_length_sizes = [0u8; TOTAL_CODE_LENGTH_CODES];
+        for &code in &SORTED_CODE_LENGTH_CODES[..num_code_length_codes] {
+            code_length_sizes[code] = self.bits(3)? as u8;
+        }
+        let code_lengths = Huffman::new(&code_length_sizes)?;
+
+        let mut sizes = vec![0u8; total_syms];
+        let mut cur = 0;
+        while cur < total_syms {
+            let c = self.huffman(&code_lengths)?;
+            let (run, value) = match c {
+                0..=16 => (1, c as u8),
+                SMALL_ZERO_RUN_CODE => (self.bits(3)? as usize + 3, 0),
+                BIG_ZERO_RUN_CODE => (self.bits(7)? as usize + 11, 0),
+                _ => {
+                    if cur == 0 {
+                        return err("etc1s: repeat without previous code size");
+                    }
+                    let run = if c == SMALL_REPEAT_CODE {
+                        self.bits(2)? as usize + 3
+                    } else {
+                        self.bits(6)? as usize + 7
+                    };
+                    (run, sizes[cur - 1])
+                }
+            };
+            if cur + run > total_syms {
+                return err("etc1s: code size run overflows table");
+            }
+            sizes[cur..cur + run].fill(value);
+            cur += run;
+        }
+        Huffman::new(&sizes)
+    }
+}
+
+/// Canonical Huffman table: code counts per length and symbols sorted by
+/// (length, value).
+#[derive(Default)]
+struct Huffman {
+    counts: [u32; MAX_CODE_SIZE + 1],
+    symbols: Vec<u32>,
+}
+
+impl Huffman {
+    fn new(sizes: &[u8]) -> Result<Self> {
+        let mut table = Huffman::default();
+        for &size in sizes {
+            if size as usize > MAX_CODE_SIZE {
+                return err("etc1s: code size too large");
+            }
+            table.counts[size as usize] += 1;
+        }
+        table.counts[0] = 0;
+        for len in 1..=MAX_CODE_SIZE as u8 {
+            for (sym, _) in sizes.iter().enumerate().filter(|(_, &s)| s == len) {
+                table.symbols.push(sym as u32);
+            }
+        }
+        if table.symbols.is_empty() {
+            return err("etc1s: empty huffman table");
+        }
+        Ok(table)
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct Endpoint {
+    color5: [u8; 3],
+    inten: u8,
+}
+
+/// Four rows of four 2-bit selectors, pixel x at bits `x * 2`.
+#[derive(Clone, Copy, Default)]
+struct Selector([u8; 4]);
+
+/// Codebooks and tables from the KTX2 supercompression global data.
+pub(crate) struct Codebook {
+    endpoints: Vec<Endpoint>,
+    selectors: Vec<Selector>,
+    endpoint_pred: Huffman,
+    delta_endpoint: Huffman,
+    selector: Huffman,
+    selector_history_rle: Huffman,
+    selector_history_size: usize,
+}
+
+impl Codebook {
+    pub(crate) fn new(
+        num_endpoints: usize,
+        endpoints: &[u8],
+        num_selectors: usize,
+        selectors: &[u8],
+        tables: &[u8],
+    ) -> Result<Self> {
+        if num_endpoints == 0 || num_selectors == 0 {
+            return err("etc1s: empty codebook");
+        }
+
+        let mut reader = BitReader::new(tables);
+        let endpoint_pred = reader.huffman_table()?;
+        let delta_endpoint = reader.huffman_table()?;
+        let selector = reader.huffman_table()?;
+        let selector_history_rle = reader.huffman_table()?;
+        let selector_history_size = reader.bits(13)? as usize;
+
+        Ok(Self {
+            endpoints: decode_endpoints(num_endpoints, endpoints)?,
+            selectors: decode_selectors(num_selectors, selectors)?,
+            endpoint_pred,
+            delta_endpoint,
+            selector,
+            selector_history_rle,
+            selector_history_size,
+        })
+    }
+
+    /// Decodes one slice into `blocks_x * blocks_y` ETC1 blocks.
+    pub(crate) fn decode_slice(
+        &self,
+        data: &[u8],
+        blocks_x: usize,
+        blocks_y: usize,
+    ) -> Result<Vec<[u8; 8]>> {
+        let num_endpoints = self.endpoints.len();
+        let num_selectors = self.selectors.len();
+        let history_rle_symbol = (num_selectors + self.selector_history_size) as u32;
+        let total_blocks = blocks_x * blocks_y;
+
+        let mut reader = BitReader::new(data);
+        let mut history = MoveToFront::new(self.selector_history_size);
+        let mut selector_rle_count = 0u32;
+
+        // Per column: prediction bits for the odd row and the endpoint index
+        // of the block above, double buffered by row parity.
+        let mut pred_bits = [vec![0u8; blocks_x], vec![0u8; blocks_x]];
+        let mut above = [vec![0usize; blocks_x], vec![0usize; blocks_x]];
+
+        let mut cur_pred_bits = 0u32;
+        let mut prev_pred_sym = 0u32;
+        let mut pred_repeat = 0u32;
+        let mut prev_endpoint = 0usize;
+
+        // Runs let a few bits cover many blocks, so the slice length only
+        // bounds the first allocation; the caller caps the dimensions.
+        let mut blocks = Vec::with_capacity(total_blocks.min(data.len() * 8));
+        for block_y in 0..blocks_y {
+            let row = block_y & 1;
+            for block_x in 0..blocks_x {
+                // Prediction symbols cover 2x2 blocks, two bits per block.
+                if block_x & 1 == 0 {
+                    if row == 0 {
+                        if pred_repeat > 0 {
+                            pred_repeat -= 1;
+                            cur_pred_bits = prev_pred_sym;
+                        } else {
+                            cur_pred_bits = reader.huffman(&self.endpoint_pred)?;
+                            if cur_pred_bits == ENDPOINT_PRED_REPEAT_LAST_SYMBOL {
+                                pred_repeat = reader.vlc(ENDPOINT_PRED_COUNT_VLC_BITS)?
+                                    + ENDPOINT_PRED_MIN_REPEAT_COUNT
+                                    - 1;
+                                cur_pred_bits = prev_pred_sym;
+                            } else {
+                                prev_pred_sym = cur_pred_bits;
+                            }
+                        }
+                        pred_bits[row ^ 1][block_x] = (cur_pred_bits >> 4) as u8;
+                    } else {
+                        cur_pred_bits = pred_bits[row][block_x] as u32;
+                    }
+                }
+
+                let pred = cur_pred_bits & 3;
+                cur_pred_bits >>= 2;
+                let endpoint = match pred {
+                    0 if block_x > 0 => prev_endpoint,
+                    1 if block_y > 0 => above[row ^ 1][block_x],
+                    2 if block_x > 0 && block_y > 0 => above[row ^ 1][block_x - 1],
+                    3 => {
+                        let delta = reader.huffman(&self.delta_endpoint)? as usize;
+                        let index = delta + prev_endpoint;
+                        if index >= num_endpoints {
+                            index - num_endpoints
+                        } else {
+                            index
+                        }
+                    }
+                    _ => return err("etc1s: invalid endpoint prediction"),
+                };
+                if endpoint >= num_endpoints {
+                    return err("etc1s: endpoint index out of range");
+                }
+                above[row][block_x] = endpoint;
+                prev_endpoint = endpoint;
+
+                let symbol = if selector_rle_count > 0 {
+                    selector_rle_count -= 1;
+                    num_selectors as u32
+                } else {
+                    let mut symbol = reader.huffman(&self.selector)?;
+                    if symbol == history_rle_symbol {
+                        let run = reader.huffman(&self.selector_history_rle)?;
+                        selector_rle_count = if run == SELECTOR_HISTORY_BUF_RLE_COUNT_TOTAL - 1 {
+                            reader.vlc(7)? + SELECTOR_HISTORY_BUF_RLE_COUNT_THRESH
+                        } else {
+                            run + SELECTOR_HISTORY_BUF_RLE_COUNT_THRESH
+                        };
+                        if selector_rle_count as usize > total_blocks {
+                            return err("etc1s: selector run too long");
+                        }
+                        symbol = num_selectors as u32;
+                        selector_rle_count -= 1;
+                    }
+                    symbol
+                };
+
+                let selector = if (symbol as usize) >= num_selectors {
+                    let index = symbol as usize - num_selectors;
+                    if index >= history.len() {
+                        return err("etc1s: selector history index out of range");
+                    }
+                    let selector = history.get(index);
+                    if index != 0 {
+                        history.touch(index);
+                    }
+                    selector
+                } else {
+                    if self.selector_history_size > 0 {
+                        history.add(symbol as usize);
+                    }
+                    symbol as usize
+                };
+
+                blocks.push(etc1_block(
+                    &self.endpoints[endpoint],
+                    &self.selectors[selector],
+                ));
+            }
+        }
+        Ok(blocks)
+    }
+}
+
+/// Palette entries are delta coded against the previous entry, with the
+/// delta model picked by the previous value's magnitude.
+fn decode_endpoints(count: usize, data: &[u8]) -> Result<Vec<Endpoint>> {
+    let mut reader = BitReader::new(data);
+    let color_models = [
+        reader.huffman_table()?,
+        reader.huffman_table()?,
+        reader.huffman_table()?,
+    ];
+    let inten_model = reader.huffman_table()?;
+    let grayscale = reader.bits(1)? != 0;
+
+    let mut prev_color = [16u8; 3];
+    let mut prev_inten = 0u32;
+    let mut endpoints = Vec::with_capacity(count);
+    for _ in 0..count {
+        let inten = (reader.huffman(&inten_model)? + prev_inten) & 7;
+        prev_inten = inten;
+
+        let channels = if grayscale { 1 } else { 3 };
+        let mut color5 = [0u8; 3];
+        for c in 0..channels {
+            let model = match prev_color[c] {
+                p if p <= COLOR5_PAL0_PREV_HI => &color_models[0],
+                p if p <= COLOR5_PAL1_PREV_HI => &color_models[1],
+                _ => &color_models[2],
+            };
+            let delta = reader.huffman(model)?;
+            let v = ((prev_color[c] as u32 + delta) & 31) as u8;
+            color5[c] = v;
+            prev_color[c] = v;
+        }
+        if grayscale {
+            color5 = [color5[0]; 3];
+        }
+        endpoints.push(Endpoint {
+            color5,
+            inten: inten as u8,
+        });
+    }
+    Ok(endpoints)
+}
+
+fn decode_selectors(count: usize, data: &[u8]) -> Result<Vec<Selector>> {
+    let mut reader = BitReader::new(data);
+    if reader.bits(1)? != 0 {
+        return err("etc1s: global selector codebooks are not supported");
+    }
+    if reader.bits(1)? != 0 {
+        return err("etc1s: hybrid selector codebooks are not supported");
+    }
+
+    let mut selectors = Vec::with_capacity(count);
+    if reader.bits(1)? != 0 {
+        for _ in 0..count {
+            let mut rows = [0u8; 4];
+            for row in &mut rows {
+                *row = reader.bits(8)? as u8;
+            }
+            selectors.push(Selector(rows));
+        }
+        return Ok(selectors);
+    }
+
+    // Each row is XOR coded against the same row of the previous entry.
+    let delta_model = reader.huffman_table()?;
+    let mut prev = [0u8; 4];
+    for i in 0..count {
+        for row in &mut prev {
+            *row = if i == 0 {
+                reader.bits(8)? as u8
+            } else {
+                reader.huffman(&delta_model)? as u8 ^ *row
+            };
+        }
+        selectors.push(Selector(prev));
+    }
+    Ok(selectors)
+}
+
+/// Recently used selector indices; hits drift toward the front.
+struct MoveToFront {
+    values: Vec<usize>,
+    rover: usize,
+}
+
+impl MoveToFront {
+    fn new(size: usize) -> Self {
+        Self {
+            values: vec![0; size],
+            rover: 0,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    fn get(&self, index: usize) -> usize {
+        self.values[index]
+    }
+
+    fn add(&mut self, value: usize) {
+        self.values[self.rover] = value;
+        self.rover += 1;
+        if self.rover == self.values.len() {
+            self.rover = self.values.len() / 2;
+        }
+    }
+
+    fn touch(&mut self, index: usize) {
+        self.values.swap(index / 2, index);
+    }
+}
+
+/// Packs an ETC1S endpoint and selector as a differential ETC1 block with a
+/// zero delta, so both sub-blocks share one color and table.
+fn etc1_block(endpoint: &Endpoint, selector: &Selector) -> [u8; 8] {
+    let mut block = [0u8; 8];
+    for (byte, c5) in block.iter_mut().zip(endpoint.color5) {
+        *byte = c5 << 3;
+    }
+    block[3] = (endpoint.inten << 5) | (endpoint.inten << 2) | 0b10;
+
+    let (mut msb, mut lsb) = (0u16, 0u16);
+    for y in 0..4 {
+        for x in 0..4 {
+            let sel = (selector.0[y] >> (x * 2)) & 3;
+            let etc = SELECTOR_TO_ETC1[sel as usize];
+            let bit = x * 4 + y;
+            msb |= ((etc >> 1) as u16) << bit;
+            lsb |= ((etc & 1) as u16) << bit;
+        }
+    }
+    block[4..6].copy_from_slice(&msb.to_be_bytes());
+    block[6..8].copy_from_slice(&lsb.to_be_bytes());
+    block
+}
@@ -0,0 +1,422 @@
+//! KTX2 reader for `KHR_texture_basisu` images. BasisLZ (ETC1S) and UASTC
+//! payloads are transcoded to the best block format the device supports,
+//! falling back to RGBA8; plain RGBA8 payloads, optionally Zstd
+//! supercompressed, pass through.
+
+mod astc;
+mod bc7;
+mod etc;
+mod etc1s;
+mod uastc;
+
+use byteorder::{ByteOrder, LittleEndian};
+use std::io::Read;
+
+use crate::content::{SupportedTextureEncodings, Texture, TextureEncoding};
+use crate::helpers::AbwError;
+
+type Result<T> = std::result::Result<T, AbwError>;
+
+fn err<T>(msg: &str) -> Result<T> {
+    Err(AbwError::TileLoading(format!("ktx2: {msg}")))
+}
+
+const IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+const HEADER_SIZE: usize = 80;
+const LEVEL_INDEX_ENTRY_SIZE: usize = 24;
+
+const VK_FORMAT_UNDEFINED: u32 = 0;
+const VK_FORMAT_R8G8B8A8_UNORM: u32 = 37;
+const VK_FORMAT_R8G8B8A8_SRGB: u32 = 43;
+
+const SUPERCOMPRESSION_NONE: u32 = 0;
+const SUPERCOMPRESSION_BASIS_LZ: u32 = 1;
+const SUPERCOMPRESSION_ZSTD: u32 = 2;
+
+const DF_MODEL_ETC1S: u8 = 163;
+const DF_MODEL_UASTC: u8 = 166;
+
+const ETC1S_GLOBAL_HEADER_SIZE: usize = 20;
+const ETC1S_IMAGE_DESC_SIZE: usize = 20;
+const ETC1S_P_FRAME: u32 = 2;
+
+const UASTC_BLOCK_SIZE: usize = 16;
+
+/// Largest width or height accepted, the highest `max_texture_dimension_2d`
+/// adapters report.
+const MAX_DIMENSION: u32 = 16384;
+/// Zstd can't expand its input by more than this: a 3 byte block header and
+/// one RLE byte stand for at most 128 KiB.
+const ZSTD_MAX_RATIO: u64 = 128 * 1024 / 4;
+
+struct Header {
+    vk_format: u32,
+    width: u32,
+    height: u32,
+    supercompression: u32,
+    levels: Vec<Level>,
+    color_model: u8,
+    samples: usize,
+    global_data: (usize, usize),
+}
+
+struct Level {
+    offset: usize,
+    length: usize,
+    uncompressed_length: usize,
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32> {
+    match offset.checked_add(4).and_then(|end| data.get(offset..end)) {
+        Some(bytes) => Ok(LittleEndian::read_u32(bytes)),
+        None => err("truncated file"),
+    }
+}
+
+fn read_usize(data: &[u8], offset: usize) -> Result<usize> {
+    match offset.checked_add(8).and_then(|end| data.get(offset..end)) {
+        Some(bytes) => usize::try_from(LittleEndian::read_u64(bytes)).or(err("offset too large")),
+        None => err("truncated file"),
+    }
+}
+
+fn range(data: &[u8], offset: usize, length: usize) -> Result<&[u8]> {
+    match offset
+        .checked_add(length)
+        .and_then(|end| data.get(offset..end))
+    {
+        Some(slice) => Ok(slice),
+        None => err("section outside the file"),
+    }
+}
+
+/// Whether `data` starts with the KTX2 identifier.
+pub(crate) fn is_ktx2(data: &[u8]) -> bool {
+    data.starts_with(&IDENTIFIER)
+}
+
+fn parse_header(data: &[u8]) -> Result<Header> {
+    if !is_ktx2(data) || data.len() < HEADER_SIZE {
+        return err("not a KTX2 file");
+    }
+    let field = |i: usize| read_u32(data, 12 + i * 4);
+    let vk_format = field(0)?;
+    let (width, height, depth) = (field(2)?, field(3)?, field(4)?);
+    let (layers, faces, level_count) = (field(5)?, field(6)?, field(7)?);
+    let supercompression = field(8)?;
+    if width == 0 || height == 0 || depth > 1 || layers > 1 || faces != 1 {
+        return err("only single 2D images are supported");
+    }
+    if width > MAX_DIMENSION || height > MAX_DIMENSION {
+        return err("image too large");
+    }
+
+    let dfd_offset = read_u32(data, 48)? as usize;
+    let global_data = (read_usize(data, 64)?, read_usize(data, 72)?);
+
+    let level_count = level_count.max(1) as usize;
+    if level_count > 32 {
+        return err("too many levels");
+    }
+    let mut levels = Vec::with_capacity(level_count);
+    for i in 0..level_count {
+        let entry = HEADER_SIZE + i * LEVEL_INDEX_ENTRY_SIZE;
+        levels.push(Level {
+            offset: read_usize(data, entry)?,
+            length: read_usize(data, entry + 8)?,
+            uncompressed_length: read_usize(data, entry + 16)?,
+        });
+    }
+
+    // Basic data format descriptor: total size, then the block whose third
+    // word starts with the color model and whose size gives the sample count.
+    let dfd_word = |i: usize| match dfd_offset.checked_add(i * 4) {
+        Some(offset) => read_u32(data, offset),
+        None => err("truncated file"),
+    };
+    let block_size = (dfd_word(2)? >> 16) as usize;
+    let color_model = dfd_word(3)? as u8;
+    let samples = block_size.saturating_sub(24) / 16;
+
+    Ok(Header {
+        vk_format,
+        width,
+        height,
+        supercompression,
+        levels,
+        color_model,
+        samples,
+        global_data,
+    })
+}
+
+/// Reads a KTX2 image and returns it with every mip level, encoded in the
+/// best format `supported` allows.
+pub(crate) fn transcode_ktx2(data: &[u8], supported: SupportedTextureEncodings) -> Result<Texture> {
+    let header = parse_header(data)?;
+    match (
+        header.vk_format,
+        header.supercompression,
+        header.color_model,
+    ) {
+        (VK_FORMAT_UNDEFINED, SUPERCOMPRESSION_BASIS_LZ, DF_MODEL_ETC1S) => {
+            transcode_etc1s(data, &header, supported)
+        }
+        (VK_FORMAT_UNDEFINED, SUPERCOMPRESSION_NONE | SUPERCOMPRESSION_ZSTD, DF_MODEL_UASTC) => {
+            transcode_uastc(data, &header, supported)
+        }
+        (VK_FORMAT_R8G8B8A8_UNORM | VK_FORMAT_R8G8B8A8_SRGB, _, _) => read_rgba8(data, &header),
+        (format, scheme, _) => err(&format!(
+            "unsupported format {format} with supercompression {scheme}"
+        )),
+    }
+}
+
+fn read_rgba8(data: &[u8], header: &Header) -> Result<Texture> {
+    let mut levels = Vec::with_capacity(header.levels.len());
+    for (i, level) in header.levels.iter().enumerate() {
+        let (w, h) = level_size(header, i);
+        levels.push(level_bytes(data, header, level, w as u64 * h as u64 * 4)?);
+    }
+    Ok(Texture {
+        width: header.width,
+        height: header.height,
+        encoding: TextureEncoding::Rgba8,
+        levels,
+    })
+}
+
+/// A level's bytes, with any Zstd supercompression undone, which must come
+/// to the `expected` size its dimensions imply.
+fn level_bytes(data: &[u8], header: &Header, level: &Level, expected: u64) -> Result<Vec<u8>> {
+    let bytes = range(data, level.offset, level.length)?;
+    let bytes = match header.supercompression {
+        SUPERCOMPRESSION_NONE => bytes.to_vec(),
+        SUPERCOMPRESSION_ZSTD => {
+            let uncompressed = level.uncompressed_length as u64;
+            if uncompressed > expected || uncompressed > bytes.len() as u64 * ZSTD_MAX_RATIO {
+                return err("zstd level is larger than its dimensions");
+            }
+            zstd(bytes, expected)?
+        }
+        _ => return err("unsupported supercompression"),
+    };
+    if bytes.len() as u64 != expected {
+        return err("level size does not match its dimensions");
+    }
+    Ok(bytes)
+}
+
+/// Inflates `data`, reading at most one byte past `expected` so an oversized
+/// stream is caught without inflating all of it.
+fn zstd(data: &[u8], expected: u64) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(expected as usize);
+    let decoder = ruzstd::decoding::StreamingDecoder::new(data)
+        .map_err(|e| AbwError::TileLoading(format!("ktx2: zstd: {e}")))?;
+    decoder
+        .take(expected + 1)
+        .read_to_end(&mut out)
+        .map_err(|e| AbwError::TileLoading(format!("ktx2: zstd: {e}")))?;
+    Ok(out)
+}
+
+fn level_size(header: &Header, level: usize) -> (u32, u32) {
+    (
+        (header.width >> level).max(1),
+        (header.height >> level).max(1),
+    )
+}
+
+/// ETC1S maps losslessly onto ETC2, so it wins when available; otherwise
+/// texels are decoded and re-encoded. Block formats need a base level made of
+/// whole blocks.
+fn pick_etc1s_target(
+    header: &Header,
+    supported: SupportedTextureEncodings,
+    alpha: bool,
+) -> TextureEncoding {
+    let whole_blocks = header.width.is_multiple_of(4) && header.height.is_multiple_of(4);
+    let etc2 = if alpha {
+        TextureEncoding::Etc2Rgba8
+    } else {
+        TextureEncoding::Etc2Rgb8
+    };
+    [etc2, TextureEncoding::Bc7, TextureEncoding::Astc4x4]
+        .into_iter()
+        .find(|&e| whole_blocks && supported.supports(e))
+        .unwrap_or(TextureEncoding::Rgba8)
+}
+
+fn transcode_etc1s(
+    data: &[u8],
+    header: &Header,
+    supported: SupportedTextureEncodings,
+) -> Result<Texture> {
+    let global = range(data, header.global_data.0, header.global_data.1)?;
+    if global.len() < ETC1S_GLOBAL_HEADER_SIZE {
+        return err("truncated BasisLZ global data");
+    }
+    let num_endpoints = LittleEndian::read_u16(&global[0..]) as usize;
+    let num_selectors = LittleEndian::read_u16(&global[2..]) as usize;
+    let endpoints_len = LittleEndian::read_u32(&global[4..]) as usize;
+    let selectors_len = LittleEndian::read_u32(&global[8..]) as usize;
+    let tables_len = LittleEndian::read_u32(&global[12..]) as usize;
+
+    let descs_offset = ETC1S_GLOBAL_HEADER_SIZE;
+    let descs_len = header.levels.len() * ETC1S_IMAGE_DESC_SIZE;
+    let descs = range(global, descs_offset, descs_len)?;
+    // each section ends inside `global`, so the next one's offset can't overflow
+    let endpoints_offset = descs_offset + descs.len();
+    let endpoints = range(global, endpoints_offset, endpoints_len)?;
+    let selectors_offset = endpoints_offset + endpoints.len();
+    let selectors = range(global, selectors_offset, selectors_len)?;
+    let tables = range(global, selectors_offset + selectors.len(), tables_len)?;
+    let codebook =
+        etc1s::Codebook::new(num_endpoints, endpoints, num_selectors, selectors, tables)?;
+
+    let alpha = header.samples >= 2;
+    let target = pick_etc1s_target(header, supported, alpha);
+
+    let mut levels = Vec::with_capacity(header.levels.len());
+    for (i, level) in header.levels.iter().enumerate() {
+        let desc = &descs[i * ETC1S_IMAGE_DESC_SIZE..];
+        let word = |k: usize| LittleEndian::read_u32(&desc[k * 4..]) as usize;
+        if word(0) as u32 & ETC1S_P_FRAME != 0 {
+            return err("BasisLZ video frames are not supported");
+        }
+        let level_data = range(data, level.offset, level.length)?;
+
+        let (w, h) = level_size(header, i);
+        let (blocks_x, blocks_y) = (w.div_ceil(4) as usize, h.div_ceil(4) as usize);
+        let rgb =
+            codebook.decode_slice(range(level_data, word(1), word(2))?, blocks_x, blocks_y)?;
+        let alpha = if alpha {
+            Some(codebook.decode_slice(range(level_data, word(3), word(4))?, blocks_x, blocks_y)?)
+        } else {
+            None
+        };
+
+        levels.push(encode_level(target, &rgb, alpha.as_deref(), w, h, blocks_x));
+    }
+
+    Ok(Texture {
+        width: header.width,
+        height: header.height,
+        encoding: target,
+        levels,
+    })
+}
+
+fn encode_level(
+    target: TextureEncoding,
+    rgb: &[[u8; 8]],
+    alpha: Option<&[[u8; 8]]>,
+    width: u32,
+    height: u32,
+    blocks_x: usize,
+) -> Vec<u8> {
+    // ETC1S alpha slices carry the value in every channel; green is used.
+    let texels = |i: usize| {
+        let mut texels = etc::decode_etc1(&rgb[i]);
+        if let Some(alpha) = alpha {
+            for (t, a) in texels.iter_mut().zip(etc::decode_etc1(&alpha[i])) {
+                t[3] = a[1];
+            }
+        }
+        texels
+    };
+
+    match target {
+        TextureEncoding::Etc2Rgb8 => rgb.concat(),
+        TextureEncoding::Etc2Rgba8 => {
+            let mut out = Vec::with_capacity(rgb.len() * 16);
+            for (i, block) in rgb.iter().enumerate() {
+                let alpha: [u8; 16] = texels(i).map(|t| t[3]);
+                out.extend_from_slice(&etc::encode_eac_alpha(&alpha));
+                out.extend_from_slice(block);
+            }
+            out
+        }
+        _ => encode_texels(target, rgb.len(), texels, width, height, blocks_x),
+    }
+}
+
+/// Encodes `blocks` decoded 4x4 blocks for any target but ETC2, which only
+/// ETC1S reaches without re-encoding.
+fn encode_texels(
+    target: TextureEncoding,
+    blocks: usize,
+    texels: impl Fn(usize) -> [[u8; 4]; 16],
+    width: u32,
+    height: u32,
+    blocks_x: usize,
+) -> Vec<u8> {
+    let (_, block_bytes) = target.block();
+    let mut out = Vec::with_capacity(blocks * block_bytes as usize);
+    match target {
+        TextureEncoding::Bc7 => (0..blocks).for_each(|i| out.extend(bc7::encode_bc7(&texels(i)))),
+        TextureEncoding::Astc4x4 => {
+            (0..blocks).for_each(|i| out.extend(astc::encode_astc(&texels(i))))
+        }
+        TextureEncoding::Rgba8 => {
+            let (w, h) = (width as usize, height as usize);
+            out.resize(w * h * 4, 0);
+            for i in 0..blocks {
+                let (bx, by) = (i % blocks_x * 4, i / blocks_x * 4);
+                for (k, texel) in texels(i).iter().enumerate() {
+                    let (x, y) = (bx + k % 4, by + k / 4);
+                    if x < w && y < h {
+                        let o = (y * w + x) * 4;
+                        out[o..o + 4].copy_from_slice(texel);
+                    }
+                }
+            }
+        }
+        TextureEncoding::Etc2Rgb8 | TextureEncoding::Etc2Rgba8 => {
+            unreachable!("only ETC1S is transcoded to ETC2")
+        }
+    }
+    out
+}
+
+/// UASTC has no cheap way into ETC2 here, so its texels are re-encoded as
+/// ASTC, which its blocks are modelled on, or BC7. Block formats need a base
+/// level made of whole blocks.
+fn pick_uastc_target(header: &Header, supported: SupportedTextureEncodings) -> TextureEncoding {
+    let whole_blocks = header.width.is_multiple_of(4) && header.height.is_multiple_of(4);
+    [TextureEncoding::Astc4x4, TextureEncoding::Bc7]
+        .into_iter()
+        .find(|&e| whole_blocks && supported.supports(e))
+        .unwrap_or(TextureEncoding::Rgba8)
+}
+
+fn transcode_uastc(
+    data: &[u8],
+    header: &Header,
+    supported: SupportedTextureEncodings,
+) -> Result<Texture> {
+    let target = pick_uastc_target(header, supported);
+
+    let mut levels = Vec::with_capacity(header.levels.len());
+    for (i, level) in header.levels.iter().enumerate() {
+        let (w, h) = level_size(header, i);
+        let (blocks_x, blocks_y) = (w.div_ceil(4) as usize, h.div_ceil(4) as usize);
+        let expected = (blocks_x * blocks_y * UASTC_BLOCK_SIZE) as u64;
+        let bytes = level_bytes(data, header, level, expected)?;
+        let blocks = bytes
+            .chunks_exact(UASTC_BLOCK_SIZE)
+            .map(|block| uastc::decode_block(block.try_into().unwrap()))
+            .collect::<Result<Vec<_>>>()?;
+        let texels = |i: usize| blocks[i];
+        levels.push(encode_texels(target, blocks.len(), texels, w, h, blocks_x));
+    }
+
+    Ok(Texture {
+        width: header.width,
+        height: header.height,
+        encoding: target,
+        levels,
+    })
+}
@@ -0,0 +1,371 @@
+//! UASTC block decoder. Blocks are unpacked to texels the way an ASTC
+//! decoder would, endpoints through the same unquantization and partitions
+//! through the same hash, so any target encoder sees exact texels.
+
+use super::{err, Result};
+
+const MODE_SOLID: usize = 8;
+
+/// Prefix code and length of each mode, read from the block's low bits.
+const MODE_CODES: [(u8, u32); 19] = [
+    (0x01, 4),
+    (0x35, 6),
+    (0x1d, 5),
+    (0x03, 5),
+    (0x13, 5),
+    (0x0b, 5),
+    (0x1b, 5),
+    (0x07, 5),
+    (0x17, 5),
+    (0x0f, 5),
+    (0x02, 3),
+    (0x00, 2),
+    (0x06, 3),
+    (0x1f, 5),
+    (0x0d, 5),
+    (0x05, 7),
+    (0x15, 6),
+    (0x25, 6),
+    (0x09, 4),
+];
+
+struct Mode {
+    /// Bits per weight; the first weight of each subset and plane has one less.
+    weight_bits: u32,
+    /// ASTC integer sequence range of the endpoints.
+    endpoint_range: usize,
+    subsets: usize,
+    planes: usize,
+    /// 2 for luminance and alpha, 3 for RGB, 4 for RGBA.
+    channels: usize,
+    /// BC1, ETC1 and ETC2 transcoding hints, skipped over.
+    hint_bits: u32,
+}
+
+const fn mode(
+    weight_bits: u32,
+    endpoint_range: usize,
+    subsets: usize,
+    planes: usize,
+    channels: usize,
+    hint_bits: u32,
+) -> Mode {
+    Mode {
+        weight_bits,
+        endpoint_range,
+        subsets,
+        planes,
+        channels,
+        hint_bits,
+    }
+}
+
+const MODES: [Mode; 19] = [
+    mode(4, 19, 1, 1, 3, 15),
+    mode(2, 20, 1, 1, 3, 15),
+    mode(3, 8, 2, 1, 3, 15),
+    mode(2, 7, 3, 1, 3, 15),
+    mode(2, 12, 2, 1, 3, 15),
+    mode(3, 20, 1, 1, 3, 15),
+    mode(2, 18, 1, 2, 3, 15),
+    mode(2, 12, 2, 1, 3, 15),
+    // solid color, decoded on its own
+    mode(0, 0, 0, 0, 4, 0),
+    mode(2, 8, 2, 1, 4, 23),
+    mode(4, 13, 1, 1, 4, 17),
+    mode(2, 13, 1, 2, 4, 17),
+    mode(3, 19, 1, 1, 4, 17),
+    mode(1, 20, 1, 2, 4, 23),
+    mode(2, 20, 1, 1, 4, 23),
+    mode(4, 20, 1, 1, 2, 23),
+    mode(2, 20, 2, 1, 2, 23),
+    mode(2, 20, 1, 2, 2, 23),
+    mode(5, 11, 1, 1, 3, 15),
+];
+
+/// ASTC partition seeds of the patterns UASTC shares with BC7: two subsets,
+/// three subsets, and for mode 7 two subsets standing in for BC7's three.
+const PATTERNS_2: [u16; 30] = [
+    28, 20, 16, 29, 91, 9, 107, 72, 149, 204, 50, 114, 496, 17, 78, 39, 252, 828, 43, 156, 116,
+    210, 476, 273, 684, 359, 246, 195, 694, 524,
+];
+const PATTERNS_3: [u16; 11] = [260, 74, 32, 156, 183, 15, 745, 0, 335, 902, 254];
+const PATTERNS_MODE_7: [u16; 19] = [
+    36, 48, 61, 137, 161, 183, 226, 281, 302, 307, 479, 495, 593, 594, 605, 799, 812, 988, 993,
+];
+
+/// ASTC integer sequence ranges: bits, and whether a trit or quint rides on
+/// top of them.
+const RANGES: [(u32, u32); 21] = [
+    (1, 1),
+    (0, 3),
+    (2, 1),
+    (0, 5),
+    (1, 3),
+    (3, 1),
+    (1, 5),
+    (2, 3),
+    (4, 1),
+    (2, 5),
+    (3, 3),
+    (5, 1),
+    (3, 5),
+    (4, 3),
+    (6, 1),
+    (4, 5),
+    (5, 3),
+    (7, 1),
+    (5, 5),
+    (6, 3),
+    (8, 1),
+];
+
+/// LSB-first reader over a 128-bit block.
+struct BlockBits {
+    bits: u128,
+    pos: u32,
+}
+
+impl BlockBits {
+    fn read(&mut self, count: u32) -> u32 {
+        let value =
+            self.bits.checked_shr(self.pos).unwrap_or(0) as u32 & ((1u64 << count) - 1) as u32;
+        self.pos += count;
+        value
+    }
+}
+
+/// Decodes one UASTC block to 16 RGBA texels, row-major.
+pub(crate) fn decode_block(block: &[u8; 16]) -> Result<[[u8; 4]; 16]> {
+    let bits = u128::from_le_bytes(*block);
+    let Some(index) = MODE_CODES
+        .iter()
+        .position(|&(code, len)| bits as u32 & ((1 << len) - 1) == code as u32)
+    else {
+        return err("reserved UASTC mode");
+    };
+    let mut reader = BlockBits {
+        bits,
+        pos: MODE_CODES[index].1,
+    };
+
+    if index == MODE_SOLID {
+        let color = [0; 4].map(|_: u8| reader.read(8) as u8);
+        return Ok([color; 16]);
+    }
+    let mode = &MODES[index];
+    reader.pos += mode.hint_bits;
+
+    let seed = match index {
+        2 | 4 | 9 | 16 => PATTERNS_2.get(reader.read(5) as usize),
+        3 => PATTERNS_3.get(reader.read(4) as usize),
+        7 => PATTERNS_MODE_7.get(reader.read(5) as usize),
+        _ => Some(&0),
+    };
+    let Some(&seed) = seed else {
+        return err("invalid UASTC partition pattern");
+    };
+    let subset: [usize; 16] = if mode.subsets > 1 {
+        std::array::from_fn(|i| partition(seed as u32, mode.subsets as u32, i))
+    } else {
+        [0; 16]
+    };
+
+    // the channel on the second plane; luminance and alpha always split alpha
+    let ccs = match (mode.planes, index) {
+        (1, _) => None,
+        (_, 17) => Some(3),
+        _ => Some(reader.read(2) as usize),
+    };
+
+    let values = read_endpoints(
+        &mut reader,
+        mode.endpoint_range,
+        mode.channels * 2 * mode.subsets,
+    );
+    let endpoints: Vec<[[u8; 4]; 2]> = values
+        .chunks_exact(mode.channels * 2)
+        .map(|v| color_endpoints(v, mode.channels))
+        .collect();
+
+    // Weights interleave per texel when there are two planes. The first
+    // texel of each subset, on every plane, drops its top weight bit.
+    let mut weights = [[0u32; 2]; 16];
+    for i in 0..16 {
+        let anchor = subset[..i].iter().all(|&s| s != subset[i]);
+        for plane in weights[i].iter_mut().take(mode.planes) {
+            let bits = mode.weight_bits - anchor as u32;
+            *plane = unquantize_weight(reader.read(bits), mode.weight_bits);
+        }
+    }
+
+    Ok(std::array::from_fn(|i| {
+        let [e0, e1] = endpoints[subset[i]];
+        std::array::from_fn(|c| {
+            let w = weights[i][(ccs == Some(c)) as usize];
+            interpolate(e0[c], e1[c], w)
+        })
+    }))
+}
+
+/// `count` endpoint values in `range`, unquantized to 8 bits. UASTC packs
+/// the trits or quints of a sequence first, five trits to 8 bits or three
+/// quints to 7 as plain base 3 or 5 numbers, then every value's low bits.
+fn read_endpoints(reader: &mut BlockBits, range: usize, count: usize) -> Vec<u8> {
+    let (bits, levels) = RANGES[range];
+    let (group, group_bits, tail_bits): (usize, u32, &[u32]) = match levels {
+        3 => (5, 8, &[0, 2, 4, 5, 7]),
+        5 => (3, 7, &[0, 3, 5]),
+        _ => (1, 0, &[]),
+    };
+    let groups = if levels > 1 { count.div_ceil(group) } else { 0 };
+    let packed: Vec<u32> = (0..groups)
+        .map(|g| {
+            let remaining = count - g * group;
+            reader.read(if remaining < group {
+                tail_bits[remaining]
+            } else {
+                group_bits
+            })
+        })
+        .collect();
+
+    (0..count)
+        .map(|i| {
+            let low = reader.read(bits);
+            let high = if levels > 1 {
+                packed[i / group] / levels.pow((i % group) as u32) % levels
+            } else {
+                0
+            };
+            unquantize_endpoint(high, low, bits, levels)
+        })
+        .collect()
+}
+
+/// ASTC color endpoint unquantization of a value whose trit or quint is
+/// `high` over `bits` plain bits `low`.
+fn unquantize_endpoint(high: u32, low: u32, bits: u32, levels: u32) -> u8 {
+    if levels == 1 {
+        // replicate the bits out to 8
+        let mut value = low << (8 - bits);
+        let mut filled = bits;
+        while filled < 8 {
+            value |= value >> filled;
+            filled *= 2;
+        }
+        return value as u8;
+    }
+
+    let a = if low & 1 != 0 { 0x1ff } else { 0 };
+    let m = low >> 1;
+    let (b, c) = match (levels, bits) {
+        (3, 1) => (0, 204),
+        (3, 2) => (m << 8 | m << 4 | m << 2 | m << 1, 93),
+        (3, 3) => (m << 7 | m << 2 | m, 44),
+        (3, 4) => (m << 6 | m, 22),
+        (3, 5) => (m << 5 | m >> 2, 11),
+        (3, 6) => (m << 4 | m >> 4, 5),
+        (5, 1) => (0, 113),
+        (5, 2) => (m << 8 | m << 3 | m << 2, 54),
+        (5, 3) => (m << 7 | m << 1 | m >> 1, 26),
+        (5, 4) => (m << 6 | m >> 1, 13),
+        (5, 5) => (m << 5 | m >> 3, 6),
+        _ => unreachable!("no UASTC mode uses this range"),
+    };
+    let t = (high * c + b) ^ a;
+    ((a & 0x80) | (t >> 2)) as u8
+}
+
+/// Bits of a weight replicated out to 0..=64.
+fn unquantize_weight(weight: u32, bits: u32) -> u32 {
+    let mut value = weight << (6 - bits);
+    let mut filled = bits;
+    while filled < 6 {
+        value |= value >> filled;
+        filled *= 2;
+    }
+    value + (value > 32) as u32
+}
+
+/// Endpoint pair from a subset's values in ASTC order (r0 r1 g0 g1 ...),
+/// with ASTC's blue contraction when the second endpoint is darker.
+fn color_endpoints(v: &[u8], channels: usize) -> [[u8; 4]; 2] {
+    let alpha = |i: usize| if channels == 4 { v[i] } else { 255 };
+    match channels {
+        2 => [[v[0], v[0], v[0], v[2]], [v[1], v[1], v[1], v[3]]],
+        _ => {
+            let sum = |e: usize| v[e] as u32 + v[e + 2] as u32 + v[e + 4] as u32;
+            if sum(1) >= sum(0) {
+                [[v[0], v[2], v[4], alpha(6)], [v[1], v[3], v[5], alpha(7)]]
+            } else {
+                let contract = |r: u8, g: u8, b: u8, a: u8| {
+                    [
+                        ((r as u32 + b as u32) >> 1) as u8,
+                        ((g as u32 + b as u32) >> 1) as u8,
+                        b,
+                        a,
+                    ]
+                };
+                [
+                    contract(v[1], v[3], v[5], alpha(7)),
+                    contract(v[0], v[2], v[4], alpha(6)),
+                ]
+            }
+        }
+    }
+}
+
+fn interpolate(e0: u8, e1: u8, weight: u32) -> u8 {
+    let (e0, e1) = (e0 as u32 * 257, e1 as u32 * 257);
+    (((e0 * (64 - weight) + e1 * weight + 32) >> 6) >> 8) as u8
+}
+
+/// ASTC's partition hash for a 4x4 block.
+fn partition(seed: u32, partitions: u32, texel: usize) -> usize {
+    // small blocks sample the hash at doubled coordinates
+    let (x, y) = ((texel % 4) as u32 * 2, (texel / 4) as u32 * 2);
+    let seed = seed + (partitions - 1) * 1024;
+
+    let mut rnum = seed;
+    rnum ^= rnum >> 15;
+    rnum = rnum.wrapping_mul(0xeede_0891);
+    rnum ^= rnum >> 5;
+    rnum = rnum.wrapping_add(rnum << 16);
+    rnum ^= rnum >> 7;
+    rnum ^= rnum >> 3;
+    rnum ^= rnum << 6;
+    rnum ^= rnum >> 17;
+
+    let nibble = |shift: u32| {
+        let v = (rnum >> shift) & 0xf;
+        v * v
+    };
+    let (sh1, sh2) = if seed & 1 != 0 {
+        (
+            if seed & 2 != 0 { 4 } else { 5 },
+            if partitions == 3 { 6 } else { 5 },
+        )
+    } else {
+        (
+            if partitions == 3 { 6 } else { 5 },
+            if seed & 2 != 0 { 4 } else { 5 },
+        )
+    };
+
+    let a = ((nibble(0) >> sh1) * x + (nibble(4) >> sh2) * y + (rnum >> 14)) & 0x3f;
+    let b = ((nibble(8) >> sh1) * x + (nibble(12) >> sh2) * y + (rnum >> 10)) & 0x3f;
+    let c = if partitions == 3 {
+        ((nibble(16) >> sh1) * x + (nibble(20) >> sh2) * y + (rnum >> 6)) & 0x3f
+    } else {
+        0
+    };
+
+    if a >= b && a >= c {
+        0
+    } else if b >= c {
+        1
+    } else {
+        2
+    }
+}
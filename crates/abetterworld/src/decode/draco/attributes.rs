@@ -0,0 +1,825 @@
+use super::{
+    buffer::Buffer,
+    connectivity::{EdgebreakerData, EncodingData, MeshConnectivity},
+    corner_table::{Connectivity, TableRef, INVALID},
+    err,
+    prediction::{
+        MeshData, OctahedronToolBox, Positions, Scheme, Transform, PREDICTION_NONE,
+        TRANSFORM_NORMAL_OCTAHEDRON, TRANSFORM_NORMAL_OCTAHEDRON_CANONICALIZED, TRANSFORM_WRAP,
+    },
+    rans::decode_symbols,
+    Result,
+};
+
+/// `GeometryAttribute::Type` values.
+pub(super) const POSITION: u8 = 0;
+pub(super) const NORMAL: u8 = 1;
+pub(super) const COLOR: u8 = 2;
+pub(super) const TEX_COORD: u8 = 3;
+const NAMED_ATTRIBUTES_COUNT: u8 = 5;
+
+/// `DataType` values.
+const DT_INT8: u8 = 1;
+const DT_UINT8: u8 = 2;
+const DT_INT16: u8 = 3;
+const DT_UINT16: u8 = 4;
+const DT_INT32: u8 = 5;
+const DT_UINT32: u8 = 6;
+const DT_INT64: u8 = 7;
+const DT_UINT64: u8 = 8;
+const DT_FLOAT32: u8 = 9;
+const DT_BOOL: u8 = 11;
+
+const MESH_VERTEX_ATTRIBUTE: u8 = 0;
+const MESH_TRAVERSAL_DEPTH_FIRST: u8 = 0;
+const NUM_TRAVERSAL_METHODS: u8 = 2;
+
+/// A decoded point attribute. Values are kept as `f32`, converted the way
+/// `GeometryAttribute::ConvertValue<float>` does.
+pub(super) struct Attribute {
+    pub att_type: u8,
+    data_type: u8,
+    pub num_components: usize,
+    normalized: bool,
+    /// Point to value mapping; empty means identity.
+    point_to_value: Vec<u32>,
+    values: Vec<f32>,
+    /// Integer values before the portable transform was reverted.
+    portable: Option<Vec<i32>>,
+}
+
+impl Attribute {
+    fn mapped_index(&self, point: u32) -> u32 {
+        if self.point_to_value.is_empty() {
+            point
+        } else {
+            self.point_to_value
+                .get(point as usize)
+                .copied()
+                .unwrap_or(INVALID)
+        }
+    }
+
+    /// The components stored for `point`, if the point maps to a value.
+    pub fn value(&self, point: u32) -> Option<&[f32]> {
+        let start = (self.mapped_index(point) as usize).checked_mul(self.num_components)?;
+        self.values.get(start..start + self.num_components)
+    }
+
+    pub(super) fn portable_position(&self, point: u32) -> Result<[i64; 3]> {
+        let index = self.mapped_index(point) as usize;
+        match self
+            .portable
+            .as_ref()
+            .and_then(|p| p.get(index * 3..index * 3 + 3))
+        {
+            Some(v) => Ok([v[0] as i64, v[1] as i64, v[2] as i64]),
+            None => err("position value out of range"),
+        }
+    }
+}
+
+/// How the values of one attribute group are ordered.
+enum Sequencer {
+    Linear,
+    Traversal {
+        att_data_id: i32,
+        vertex_attribute: bool,
+        max_prediction_degree: bool,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum SequentialDecoder {
+    Generic,
+    Integer,
+    Quantization,
+    Normals,
+}
+
+/// Per-attribute state between decoding the portable values and reverting
+/// the portable transform.
+enum PortableTransform {
+    None,
+    Quantization { min: Vec<f32>, range: f32, bits: u8 },
+    Normals { bits: u8 },
+}
+
+struct AttributesDecoder {
+    sequencer: Sequencer,
+    attribute_ids: Vec<usize>,
+    decoders: Vec<SequentialDecoder>,
+}
+
+/// Decodes all attribute groups that follow the connectivity
+/// (`PointCloudDecoder::DecodePointAttributes`).
+pub(super) fn decode_attributes(
+    buffer: &mut Buffer,
+    connectivity: &mut MeshConnectivity,
+) -> Result<Vec<Attribute>> {
+    let num_decoders = buffer.u8()? as usize;
+    let mut decoders = Vec::with_capacity(num_decoders);
+    let mut pos_data_bound = false;
+    for decoder_id in 0..num_decoders {
+        let sequencer = match connectivity.edgebreaker.as_mut() {
+            None => Sequencer::Linear,
+            Some(eb) => {
+                let att_data_id = buffer.i8()? as i32;
+                let decoder_type = buffer.u8()?;
+                if att_data_id >= 0 {
+                    let Some(data) = eb.attribute_data.get_mut(att_data_id as usize) else {
+                        return err("attribute data id out of range");
+                    };
+                    if data.decoder_id >= 0 {
+                        return err("attribute data bound twice");
+                    }
+                    data.decoder_id = decoder_id as i32;
+                } else {
+                    if pos_data_bound {
+                        return err("position data bound twice");
+                    }
+                    pos_data_bound = true;
+                }
+                let traversal = buffer.u8()?;
+                if traversal >= NUM_TRAVERSAL_METHODS {
+                    return err("unknown traversal method");
+                }
+                let vertex_attribute = decoder_type == MESH_VERTEX_ATTRIBUTE;
+                if vertex_attribute {
+                    if att_data_id >= 0 {
+                        eb.attribute_data[att_data_id as usize].is_connectivity_used = false;
+                    }
+                } else if traversal != MESH_TRAVERSAL_DEPTH_FIRST || att_data_id < 0 {
+                    return err("invalid corner attribute decoder");
+                }
+                Sequencer::Traversal {
+                    att_data_id,
+                    vertex_attribute,
+                    max_prediction_degree: traversal != MESH_TRAVERSAL_DEPTH_FIRST,
+                }
+            }
+        };
+        decoders.push(AttributesDecoder {
+            sequencer,
+            attribute_ids: Vec::new(),
+            decoders: Vec::new(),
+        });
+    }
+
+    let mut attributes = Vec::new();
+    for decoder in &mut decoders {
+        let num_attributes = buffer.varint_u32()? as usize;
+        if num_attributes == 0 || num_attributes > 5 * buffer.remaining() {
+            return err("invalid attribute count");
+        }
+        for _ in 0..num_attributes {
+            let att_type = buffer.u8()?;
+            let data_type = buffer.u8()?;
+            let num_components = buffer.u8()? as usize;
+            let normalized = buffer.u8()? > 0;
+            let _unique_id = buffer.varint_u32()?;
+            if att_type >= NAMED_ATTRIBUTES_COUNT
+                || data_type == 0
+                || data_type > DT_BOOL
+                || num_components == 0
+            {
+                return err("invalid attribute header");
+            }
+            decoder.attribute_ids.push(attributes.len());
+            attributes.push(Attribute {
+                att_type,
+                data_type,
+                num_components,
+                normalized,
+                point_to_value: Vec::new(),
+                values: Vec::new(),
+                portable: None,
+            });
+        }
+        for &id in &decoder.attribute_ids {
+            let att = &attributes[id];
+            let kind = match buffer.u8()? {
+                0 => SequentialDecoder::Generic,
+                1 => SequentialDecoder::Integer,
+                2 if att.data_type == DT_FLOAT32 => SequentialDecoder::Quantization,
+                3 if att.data_type == DT_FLOAT32 && att.num_components == 3 => {
+                    SequentialDecoder::Normals
+                }
+                _ => return err("unsupported sequential attribute decoder"),
+            };
+            decoder.decoders.push(kind);
+        }
+    }
+
+    for decoder in &decoders {
+        decode_group(buffer, connectivity, decoder, &mut attributes)?;
+    }
+    Ok(attributes)
+}
+
+/// `SequentialAttributeDecodersController::DecodeAttributes` for one group.
+fn decode_group(
+    buffer: &mut Buffer,
+    connectivity: &mut MeshConnectivity,
+    decoder: &AttributesDecoder,
+    attributes: &mut [Attribute],
+) -> Result<()> {
+    let point_ids = generate_sequence(connectivity, &decoder.sequencer)?;
+    let mapping = point_mapping(connectivity, &decoder.sequencer)?;
+    for &id in &decoder.attribute_ids {
+        attributes[id].point_to_value = mapping.clone();
+    }
+
+    for (&id, &kind) in decoder.attribute_ids.iter().zip(&decoder.decoders) {
+        if kind == SequentialDecoder::Generic {
+            let att = &mut attributes[id];
+            att.values = decode_generic(buffer, att, point_ids.len())?;
+            continue;
+        }
+        let portable = decode_integer_values(
+            buffer,
+            connectivity,
+            decoder,
+            id,
+            kind,
+            &point_ids,
+            attributes,
+        )?;
+        attributes[id].portable = Some(portable);
+    }
+
+    let mut transforms = Vec::with_capacity(decoder.attribute_ids.len());
+    for (&id, &kind) in decoder.attribute_ids.iter().zip(&decoder.decoders) {
+        transforms.push(match kind {
+            SequentialDecoder::Quantization => {
+                let comps = attributes[id].num_components;
+                let min = (0..comps)
+                    .map(|_| buffer.f32())
+                    .collect::<Result<Vec<_>>>()?;
+                let range = buffer.f32()?;
+                let bits = buffer.u8()?;
+                if !(1..=30).contains(&bits) {
+                    return err("invalid quantization bits");
+                }
+                PortableTransform::Quantization { min, range, bits }
+            }
+            SequentialDecoder::Normals => PortableTransform::Normals { bits: buffer.u8()? },
+            SequentialDecoder::Generic | SequentialDecoder::Integer => PortableTransform::None,
+        });
+    }
+
+    for ((&id, &kind), transform) in decoder
+        .attribute_ids
+        .iter()
+        .zip(&decoder.decoders)
+        .zip(transforms)
+    {
+        let att = &mut attributes[id];
+        let Some(portable) = att.portable.as_ref() else {
+            continue;
+        };
+        att.values = match transform {
+            PortableTransform::Quantization { min, range, bits } => {
+                let max_quantized_value = ((1u32 << bits) - 1) as i32;
+                let delta = range / max_quantized_value as f32;
+                portable
+                    .chunks_exact(att.num_components)
+                    .flat_map(|v| v.iter().zip(&min).map(|(&q, &m)| q as f32 * delta + m))
+                    .collect()
+            }
+            PortableTransform::Normals { bits } => {
+                let toolbox = OctahedronToolBox::new(bits as i32)?;
+                portable
+                    .chunks_exact(2)
+                    .flat_map(|st| toolbox.unit_vector(st[0], st[1]))
+                    .collect()
+            }
+            PortableTransform::None => {
+                debug_assert!(kind == SequentialDecoder::Integer);
+                if att.data_type > DT_UINT32 {
+                    return err("unsupported integer attribute type");
+                }
+                portable
+                    .iter()
+                    .map(|&v| convert_integer(v, att.data_type, att.normalized))
+                    .collect()
+            }
+        };
+    }
+    Ok(())
+}
+
+/// Casts a decoded integer to the attribute's storage type and then to
+/// `f32`, normalizing when the attribute asks for it.
+fn convert_integer(value: i32, data_type: u8, normalized: bool) -> f32 {
+    let (v, max) = match data_type {
+        DT_INT8 => (value as i8 as f32, i8::MAX as f32),
+        DT_UINT8 => (value as u8 as f32, u8::MAX as f32),
+        DT_INT16 => (value as i16 as f32, i16::MAX as f32),
+        DT_UINT16 => (value as u16 as f32, u16::MAX as f32),
+        DT_UINT32 => (value as u32 as f32, u32::MAX as f32),
+        _ => (value as f32, i32::MAX as f32),
+    };
+    if normalized {
+        v / max
+    } else {
+        v
+    }
+}
+
+fn decode_generic(buffer: &mut Buffer, att: &Attribute, num_values: usize) -> Result<Vec<f32>> {
+    let size = match att.data_type {
+        DT_INT8 | DT_UINT8 | DT_BOOL => 1,
+        DT_INT16 | DT_UINT16 => 2,
+        DT_INT32 | DT_UINT32 | DT_FLOAT32 => 4,
+        _ => 8,
+    };
+    let num_components = num_values * att.num_components;
+    let data = buffer.bytes(num_components * size)?;
+    let normalize = |v: f32, max: f32| if att.normalized { v / max } else { v };
+    Ok(data
+        .chunks_exact(size)
+        .map(|b| match att.data_type {
+            DT_INT8 => normalize(b[0] as i8 as f32, i8::MAX as f32),
+            DT_UINT8 => normalize(b[0] as f32, u8::MAX as f32),
+            DT_BOOL => (b[0] != 0) as u8 as f32,
+            DT_INT16 => normalize(i16::from_le_bytes([b[0], b[1]]) as f32, i16::MAX as f32),
+            DT_UINT16 => normalize(u16::from_le_bytes([b[0], b[1]]) as f32, u16::MAX as f32),
+            DT_INT32 => normalize(
+                i32::from_le_bytes(b.try_into().unwrap()) as f32,
+                i32::MAX as f32,
+            ),
+            DT_UINT32 => normalize(
+                u32::from_le_bytes(b.try_into().unwrap()) as f32,
+                u32::MAX as f32,
+            ),
+            DT_INT64 => normalize(
+                i64::from_le_bytes(b.try_into().unwrap()) as f32,
+                i64::MAX as f32,
+            ),
+            DT_UINT64 => normalize(
+                u64::from_le_bytes(b.try_into().unwrap()) as f32,
+                u64::MAX as f32,
+            ),
+            DT_FLOAT32 => f32::from_le_bytes(b.try_into().unwrap()),
+            // DT_FLOAT64
+            _ => f64::from_le_bytes(b.try_into().unwrap()) as f32,
+        })
+        .collect())
+}
+
+/// `SequentialIntegerAttributeDecoder::DecodeValues`, returning the portable
+/// integer values in encoding order.
+fn decode_integer_values(
+    buffer: &mut Buffer,
+    connectivity: &MeshConnectivity,
+    decoder: &AttributesDecoder,
+    id: usize,
+    kind: SequentialDecoder,
+    point_ids: &[u32],
+    attributes: &[Attribute],
+) -> Result<Vec<i32>> {
+    let method = buffer.i8()?;
+    if !(PREDICTION_NONE..=6).contains(&method) {
+        return err("unknown prediction method");
+    }
+    let mut transform = None;
+    if method != PREDICTION_NONE {
+        let transform_type = buffer.i8()?;
+        if !(-1..=3).contains(&transform_type) {
+            return err("unknown prediction transform");
+        }
+        transform = match (kind, transform_type) {
+            (SequentialDecoder::Normals, TRANSFORM_NORMAL_OCTAHEDRON_CANONICALIZED) => {
+                Some(Transform::Canonicalized(OctahedronToolBox::default()))
+            }
+            (SequentialDecoder::Normals, TRANSFORM_NORMAL_OCTAHEDRON) => {
+                return err("deprecated normal transform");
+            }
+            (SequentialDecoder::Normals, _) => None,
+            (_, TRANSFORM_WRAP) => Some(Transform::Wrap {
+                min: 0,
+                max: 0,
+                max_dif: 0,
+            }),
+            _ => None,
+        };
+    }
+
+    let mesh = transform
+        .as_ref()
+        .and_then(|_| mesh_data(connectivity, decoder));
+    let positions = if Scheme::uses_positions(method) {
+        attributes
+            .iter()
+            .position(|a| a.att_type == POSITION)
+            .filter(|&pos| attributes[pos].portable.is_some())
+            .map(|pos| Positions {
+                attribute: &attributes[pos],
+                point_ids,
+            })
+    } else {
+        None
+    };
+    let mut scheme = match &transform {
+        Some(transform) => Some(Scheme::create(method, transform, mesh, positions)?),
+        None => None,
+    };
+
+    let num_components = match kind {
+        SequentialDecoder::Normals => 2,
+        _ => attributes[id].num_components,
+    };
+    let num_values = point_ids.len() * num_components;
+    let mut values: Vec<i32> = if buffer.u8()? > 0 {
+        decode_symbols(num_values, num_components, buffer)?
+            .into_iter()
+            .map(|v| v as i32)
+            .collect()
+    } else {
+        let num_bytes = buffer.u8()? as usize;
+        if num_bytes == 0 || num_bytes > 4 || buffer.remaining() / num_bytes < num_values {
+            return err("invalid raw integer values");
+        }
+        buffer
+            .bytes(num_bytes * num_values)?
+            .chunks_exact(num_bytes)
+            .map(|b| {
+                let mut raw = [0u8; 4];
+                raw[..num_bytes].copy_from_slice(b);
+                i32::from_le_bytes(raw)
+            })
+            .collect()
+    };
+
+    let corrections_positive = transform.as_ref().is_some_and(|t| t.corrections_positive());
+    if num_values > 0 && !corrections_positive {
+        for v in &mut values {
+            let u = *v as u32;
+            *v = if u & 1 != 0 {
+                -((u >> 1) as i32) - 1
+            } else {
+                (u >> 1) as i32
+            };
+        }
+    }
+
+    if let (Some(scheme), Some(transform)) = (scheme.as_mut(), transform.as_mut()) {
+        scheme.decode_data(transform, buffer)?;
+        if num_values > 0 {
+            scheme.compute(transform, &mut values, num_components)?;
+        }
+    }
+    Ok(values)
+}
+
+/// Connectivity and encoding order the group was traversed with; `None`
+/// for sequentially coded meshes.
+fn mesh_data<'a>(
+    connectivity: &'a MeshConnectivity,
+    decoder: &AttributesDecoder,
+) -> Option<MeshData<'a>> {
+    let eb = connectivity.edgebreaker.as_ref()?;
+    let Sequencer::Traversal {
+        att_data_id,
+        vertex_attribute,
+        ..
+    } = decoder.sequencer
+    else {
+        return None;
+    };
+    let (table, encoding) = traversal_target(eb, att_data_id, vertex_attribute);
+    Some(MeshData {
+        table,
+        value_to_corner: &encoding.value_to_corner,
+        vertex_to_value: &encoding.vertex_to_value,
+    })
+}
+
+/// The table a group was traversed on and the encoding order it produced.
+/// Vertex attributes reuse the base connectivity; corner attributes walk
+/// their own seam-split table.
+fn traversal_target(
+    eb: &EdgebreakerData,
+    att_data_id: i32,
+    vertex_attribute: bool,
+) -> (TableRef<'_>, &EncodingData) {
+    if att_data_id < 0 {
+        return (TableRef::Base(&eb.table), &eb.pos_encoding);
+    }
+    let data = &eb.attribute_data[att_data_id as usize];
+    if vertex_attribute {
+        (TableRef::Base(&eb.table), &data.encoding)
+    } else {
+        (
+            TableRef::Attribute(data.table.view(&eb.table)),
+            &data.encoding,
+        )
+    }
+}
+
+/// Visits the mesh in encoder order and records which point every
+/// attribute value belongs to (`MeshTraversalSequencer`).
+fn generate_sequence(
+    connectivity: &mut MeshConnectivity,
+    sequencer: &Sequencer,
+) -> Result<Vec<u32>> {
+    let Sequencer::Traversal {
+        att_data_id,
+        vertex_attribute,
+        max_prediction_degree,
+    } = *sequencer
+    else {
+        return Ok((0..connectivity.num_points as u32).collect());
+    };
+    let Some(eb) = connectivity.edgebreaker.as_mut() else {
+        return err("traversal without connectivity");
+    };
+    let faces = &connectivity.faces;
+    if att_data_id < 0 {
+        let observer = Observer::new(&eb.table, faces, &mut eb.pos_encoding);
+        return observer.traverse(&eb.table, max_prediction_degree);
+    }
+    let data = &mut eb.attribute_data[att_data_id as usize];
+    if vertex_attribute {
+        let observer = Observer::new(&eb.table, faces, &mut data.encoding);
+        observer.traverse(&eb.table, max_prediction_degree)
+    } else {
+        let view = data.table.view(&eb.table);
+        let observer = Observer::new(&view, faces, &mut data.encoding);
+        observer.traverse(&view, false)
+    }
+}
+
+/// `UpdatePointToAttributeIndexMapping`: identity for the linear sequencer,
+/// otherwise every face corner maps its point to the traversed value.
+fn point_mapping(connectivity: &MeshConnectivity, sequencer: &Sequencer) -> Result<Vec<u32>> {
+    let Sequencer::Traversal {
+        att_data_id,
+        vertex_attribute,
+        ..
+    } = *sequencer
+    else {
+        return Ok(Vec::new());
+    };
+    let Some(eb) = connectivity.edgebreaker.as_ref() else {
+        return err("traversal without connectivity");
+    };
+    let num_points = connectivity.num_points;
+    let (table, encoding) = traversal_target(eb, att_data_id, vertex_attribute);
+    let mut mapping = vec![INVALID; num_points];
+    for (f, face) in connectivity.faces.iter().enumerate() {
+        for (p, &point) in face.iter().enumerate() {
+            let vertex = table.vertex((f * 3 + p) as u32);
+            let Some(&entry) = encoding.vertex_to_value.get(vertex as usize) else {
+                return err("corner without attribute value");
+            };
+            if point as usize >= num_points || entry as usize >= num_points {
+                return err("attribute mapping out of range");
+            }
+            mapping[point as usize] = entry as u32;
+        }
+    }
+    Ok(mapping)
+}
+
+/// Traversal state plus the `MeshAttributeIndicesEncodingObserver` that
+/// assigns value ids as vertices are reached.
+struct Observer<'a> {
+    faces: &'a [[u32; 3]],
+    encoding: &'a mut EncodingData,
+    point_ids: Vec<u32>,
+    face_visited: Vec<bool>,
+    vertex_visited: Vec<bool>,
+}
+
+impl<'a> Observer<'a> {
+    fn new<T: Connectivity + ?Sized>(
+        table: &T,
+        faces: &'a [[u32; 3]],
+        encoding: &'a mut EncodingData,
+    ) -> Self {
+        Self {
+            faces,
+            encoding,
+            point_ids: Vec::new(),
+            face_visited: vec![false; table.num_faces()],
+            vertex_visited: vec![false; table.num_vertices()],
+        }
+    }
+
+    fn traverse<T: Connectivity + ?Sized>(
+        mut self,
+        table: &T,
+        max_prediction_degree: bool,
+    ) -> Result<Vec<u32>> {
+        if max_prediction_degree {
+            let mut traverser = MaxDegreeTraverser {
+                prediction_degree: vec![0; table.num_vertices()],
+                stacks: Default::default(),
+                best_priority: 0,
+            };
+            for f in 0..table.num_faces() as u32 {
+                traverser.traverse_from_corner(&mut self, table, f * 3)?;
+            }
+        } else {
+            let mut stack = Vec::new();
+            for f in 0..table.num_faces() as u32 {
+                self.depth_first_from_corner(table, &mut stack, f * 3)?;
+            }
+        }
+        Ok(self.point_ids)
+    }
+
+    fn is_face_visited(&self, corner: u32) -> bool {
+        corner == INVALID || self.face_visited[(corner / 3) as usize]
+    }
+
+    fn is_vertex_visited(&self, vertex: u32) -> Result<bool> {
+        match self.vertex_visited.get(vertex as usize) {
+            Some(&visited) => Ok(visited),
+            None => err("traversal reached an invalid vertex"),
+        }
+    }
+
+    fn visit(&mut self, vertex: u32, corner: u32) -> Result<()> {
+        let Some(face) = self.faces.get((corner / 3) as usize) else {
+            return err("traversal corner out of range");
+        };
+        self.vertex_visited[vertex as usize] = true;
+        self.point_ids.push(face[(corner % 3) as usize]);
+        self.encoding.value_to_corner.push(corner);
+        let Some(slot) = self.encoding.vertex_to_value.get_mut(vertex as usize) else {
+            return err("traversal vertex out of range");
+        };
+        *slot = self.encoding.num_values;
+        self.encoding.num_values += 1;
+        Ok(())
+    }
+
+    fn visit_if_new(&mut self, vertex: u32, corner: u32) -> Result<()> {
+        if !self.is_vertex_visited(vertex)? {
+            self.visit(vertex, corner)?;
+        }
+        Ok(())
+    }
+
+    /// `DepthFirstTraverser::TraverseFromCorner`.
+    fn depth_first_from_corner<T: Connectivity + ?Sized>(
+        &mut self,
+        table: &T,
+        stack: &mut Vec<u32>,
+        corner: u32,
+    ) -> Result<()> {
+        if self.is_face_visited(corner) {
+            return Ok(());
+        }
+        stack.clear();
+        stack.push(corner);
+        let next_vert = table.vertex(table.next(corner));
+        let prev_vert = table.vertex(table.previous(corner));
+        if next_vert == INVALID || prev_vert == INVALID {
+            return err("traversal reached an invalid vertex");
+        }
+        self.visit_if_new(next_vert, table.next(corner))?;
+        self.visit_if_new(prev_vert, table.previous(corner))?;
+
+        while let Some(&top) = stack.last() {
+            let mut corner = top;
+            if self.is_face_visited(corner) {
+                stack.pop();
+                continue;
+            }
+            loop {
+                self.face_visited[(corner / 3) as usize] = true;
+                let vert = table.vertex(corner);
+                if vert == INVALID {
+                    return err("traversal reached an invalid vertex");
+                }
+                if !self.is_vertex_visited(vert)? {
+                    let on_boundary = table.is_on_boundary(vert);
+                    self.visit(vert, corner)?;
+                    if !on_boundary {
+                        corner = table.right_corner(corner);
+                        continue;
+                    }
+                }
+                let right = table.right_corner(corner);
+                let left = table.left_corner(corner);
+                match (self.is_face_visited(right), self.is_face_visited(left)) {
+                    (true, true) => {
+                        stack.pop();
+                        break;
+                    }
+                    (true, false) => corner = left,
+                    (false, true) => corner = right,
+                    (false, false) => {
+                        *stack.last_mut().unwrap() = left;
+                        stack.push(right);
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `MaxPredictionDegreeTraverser`: prefers corners whose tip vertex already
+/// has the most decoded neighbours.
+struct MaxDegreeTraverser {
+    prediction_degree: Vec<u32>,
+    stacks: [Vec<u32>; 3],
+    best_priority: usize,
+}
+
+impl MaxDegreeTraverser {
+    fn traverse_from_corner<T: Connectivity + ?Sized>(
+        &mut self,
+        observer: &mut Observer,
+        table: &T,
+        corner: u32,
+    ) -> Result<()> {
+        if self.prediction_degree.is_empty() {
+            return Ok(());
+        }
+        self.stacks[0].push(corner);
+        self.best_priority = 0;
+        let next_vert = table.vertex(table.next(corner));
+        let prev_vert = table.vertex(table.previous(corner));
+        let tip_vert = table.vertex(corner);
+        if next_vert == INVALID || prev_vert == INVALID || tip_vert == INVALID {
+            return err("traversal reached an invalid vertex");
+        }
+        observer.visit_if_new(next_vert, table.next(corner))?;
+        observer.visit_if_new(prev_vert, table.previous(corner))?;
+        observer.visit_if_new(tip_vert, corner)?;
+
+        while let Some(mut corner) = self.pop() {
+            if observer.is_face_visited(corner) {
+                continue;
+            }
+            loop {
+                observer.face_visited[(corner / 3) as usize] = true;
+                let vert = table.vertex(corner);
+                if vert == INVALID {
+                    return err("traversal reached an invalid vertex");
+                }
+                observer.visit_if_new(vert, corner)?;
+
+                let right = table.right_corner(corner);
+                let left = table.left_corner(corner);
+                let right_visited = observer.is_face_visited(right);
+                let left_visited = observer.is_face_visited(left);
+                if !left_visited {
+                    let priority = self.priority(observer, table, left)?;
+                    if right_visited && priority <= self.best_priority {
+                        corner = left;
+                        continue;
+                    }
+                    self.push(left, priority);
+                }
+                if !right_visited {
+                    let priority = self.priority(observer, table, right)?;
+                    if priority <= self.best_priority {
+                        corner = right;
+                        continue;
+                    }
+                    self.push(right, priority);
+                }
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Option<u32> {
+        for i in self.best_priority..self.stacks.len() {
+            if let Some(corner) = self.stacks[i].pop() {
+                self.best_priority = i;
+                return Some(corner);
+            }
+        }
+        None
+    }
+
+    fn push(&mut self, corner: u32, priority: usize) {
+        self.stacks[priority].push(corner);
+        self.best_priority = self.best_priority.min(priority);
+    }
+
+    fn priority<T: Connectivity + ?Sized>(
+        &mut self,
+        observer: &Observer,
+        table: &T,
+        corner: u32,
+    ) -> Result<usize> {
+        let tip = table.vertex(corner);
+        if observer.is_vertex_visited(tip)? {
+            return Ok(0);
+        }
+        let degree = &mut self.prediction_degree[tip as usize];
+        *degree += 1;
+        Ok(if *degree > 1 { 1 } else { 2 })
+    }
+}
@@ -0,0 +1,143 @@
+use super::{err, Result};
+
+/// Cursor over an encoded Draco stream. Mirrors `draco::DecoderBuffer`,
+/// including the side-channel bit decoder used by a few connectivity blocks.
+#[derive(Clone)]
+pub(super) struct Buffer<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bits: Option<BitReader>,
+}
+
+#[derive(Clone, Copy)]
+struct BitReader {
+    start: usize,
+    bit_offset: usize,
+}
+
+impl<'a> Buffer<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            pos: 0,
+            bits: None,
+        }
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.data.len().saturating_sub(self.pos)
+    }
+
+    /// The bytes that have not been consumed yet.
+    pub fn head(&self) -> &'a [u8] {
+        &self.data[self.pos.min(self.data.len())..]
+    }
+
+    pub fn advance(&mut self, n: usize) -> Result<()> {
+        if n > self.remaining() {
+            return err("buffer overrun");
+        }
+        self.pos += n;
+        Ok(())
+    }
+
+    pub fn bytes(&mut self, n: usize) -> Result<&'a [u8]> {
+        if n > self.remaining() {
+            return err("buffer overrun");
+        }
+        let out = &self.data[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(out)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut out = [0u8; N];
+        out.copy_from_slice(self.bytes(N)?);
+        Ok(out)
+    }
+
+    pub fn u8(&mut self) -> Result<u8> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    pub fn i8(&mut self) -> Result<i8> {
+        Ok(self.u8()? as i8)
+    }
+
+    pub fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    pub fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    pub fn i32(&mut self) -> Result<i32> {
+        Ok(i32::from_le_bytes(self.array()?))
+    }
+
+    pub fn f32(&mut self) -> Result<f32> {
+        Ok(f32::from_le_bytes(self.array()?))
+    }
+
+    /// LEB128 varint limited to five bytes, as `DecodeVarint<uint32_t>`.
+    pub fn varint_u32(&mut self) -> Result<u32> {
+        Ok(self.varint(5)? as u32)
+    }
+
+    /// LEB128 varint limited to ten bytes, as `DecodeVarint<uint64_t>`.
+    pub fn varint_u64(&mut self) -> Result<u64> {
+        self.varint(10)
+    }
+
+    fn varint(&mut self, max_bytes: usize) -> Result<u64> {
+        let mut value = 0u64;
+        for i in 0..max_bytes {
+            let byte = self.u8()?;
+            value |= ((byte & 0x7f) as u64).wrapping_shl(7 * i as u32);
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        err("varint too long")
+    }
+
+    /// Switches the buffer into bit mode. When `decode_size` is set the size
+    /// of the bit section is read first (as a varint) and returned.
+    pub fn start_bits(&mut self, decode_size: bool) -> Result<u64> {
+        let size = if decode_size { self.varint_u64()? } else { 0 };
+        self.bits = Some(BitReader {
+            start: self.pos,
+            bit_offset: 0,
+        });
+        Ok(size)
+    }
+
+    /// Reads `n` bits LSB first. Reads past the end of the data yield zeros,
+    /// matching the reference bit decoder.
+    pub fn bits(&mut self, n: u32) -> Result<u32> {
+        let Some(reader) = self.bits.as_mut() else {
+            return err("bit decoder not active");
+        };
+        let mut value = 0u32;
+        for bit in 0..n {
+            let byte_offset = reader.start + reader.bit_offset / 8;
+            let b = match self.data.get(byte_offset) {
+                Some(byte) => {
+                    let b = (byte >> (reader.bit_offset % 8)) & 1;
+                    reader.bit_offset += 1;
+                    b
+                }
+                None => 0,
+            };
+            value |= (b as u32) << bit;
+        }
+        Ok(value)
+    }
+
+    pub fn end_bits(&mut self) {
+        if let Some(reader) = self.bits.take() {
+            self.pos = reader.start + reader.bit_offset.div_ceil(8);
+        }
+    }
+}
@@ -0,0 +1,755 @@
+use std::collections::HashMap;
+
+use super::{
+    buffer::Buffer,
+    corner_table::{
+        next, previous, vertex_corners, AttributeCornerTable, Connectivity, CornerTable, INVALID,
+    },
+    err,
+    rans::{decode_symbols, RAnsBitDecoder},
+    Result,
+};
+
+const SYMBOL_C: u32 = 0;
+const SYMBOL_S: u32 = 1;
+const SYMBOL_L: u32 = 3;
+const SYMBOL_R: u32 = 5;
+const SYMBOL_E: u32 = 7;
+const SYMBOL_INVALID: u32 = u32::MAX;
+
+/// Maps the symbol ids stored by the valence coder to edgebreaker symbols.
+const VALENCE_SYMBOLS: [u32; 5] = [SYMBOL_C, SYMBOL_S, SYMBOL_L, SYMBOL_R, SYMBOL_E];
+
+/// Far more than any tile needs. Valence coded symbols can take well under a
+/// bit each, so the input's length alone doesn't bound the corner table.
+const MAX_EDGEBREAKER_FACES: usize = 1 << 22;
+
+/// Mesh faces as point ids, plus the corner tables the attribute decoders
+/// need when the mesh was edgebreaker coded.
+pub(super) struct MeshConnectivity {
+    pub faces: Vec<[u32; 3]>,
+    pub num_points: usize,
+    pub edgebreaker: Option<EdgebreakerData>,
+}
+
+pub(super) struct EdgebreakerData {
+    pub table: CornerTable,
+    pub pos_encoding: EncodingData,
+    pub attribute_data: Vec<AttributeData>,
+}
+
+/// Connectivity of one attribute group that has its own seams.
+pub(super) struct AttributeData {
+    pub table: AttributeCornerTable,
+    pub encoding: EncodingData,
+    pub decoder_id: i32,
+    pub is_connectivity_used: bool,
+}
+
+/// Order in which attribute values were encoded
+/// (`MeshAttributeIndicesEncodingData`).
+#[derive(Default)]
+pub(super) struct EncodingData {
+    pub value_to_corner: Vec<u32>,
+    pub vertex_to_value: Vec<i32>,
+    pub num_values: i32,
+}
+
+impl EncodingData {
+    fn new(num_vertices: usize) -> Self {
+        Self {
+            value_to_corner: Vec::with_capacity(num_vertices),
+            vertex_to_value: vec![0; num_vertices],
+            num_values: 0,
+        }
+    }
+}
+
+pub(super) fn decode_sequential(buffer: &mut Buffer) -> Result<MeshConnectivity> {
+    let num_faces = buffer.varint_u32()? as usize;
+    let num_points = buffer.varint_u32()?;
+    if num_faces > 0xffff_ffff / 3 || num_faces > buffer.remaining() / 3 {
+        return err("too many faces");
+    }
+
+    let method = buffer.u8()?;
+    let mut indices = Vec::with_capacity(num_faces * 3);
+    if method == 0 {
+        let symbols = decode_symbols(num_faces * 3, 1, buffer)?;
+        let mut last = 0i32;
+        for v in symbols {
+            let diff = (v >> 1) as i32;
+            if v & 1 != 0 {
+                if diff > last {
+                    return err("invalid index delta");
+                }
+                last -= diff;
+            } else {
+                if diff > i32::MAX - last {
+                    return err("invalid index delta");
+                }
+                last += diff;
+            }
+            indices.push(last as u32);
+        }
+    } else {
+        for _ in 0..num_faces * 3 {
+            let index = if num_points < 256 {
+                buffer.u8()? as u32
+            } else if num_points < 1 << 16 {
+                buffer.u16()? as u32
+            } else if num_points < 1 << 21 {
+                buffer.varint_u32()?
+            } else {
+                buffer.u32()?
+            };
+            indices.push(index);
+        }
+    }
+
+    Ok(MeshConnectivity {
+        faces: indices
+            .chunks_exact(3)
+            .map(|f| [f[0], f[1], f[2]])
+            .collect(),
+        num_points: num_points as usize,
+        edgebreaker: None,
+    })
+}
+
+struct TopologySplit {
+    source_symbol: u32,
+    split_symbol: u32,
+    source_edge: u32,
+}
+
+/// Symbol source of the edgebreaker traversal, plus the decoders shared by
+/// both traversal flavours.
+struct Traversal<'a> {
+    symbols: Option<Buffer<'a>>,
+    valence: Option<ValenceState>,
+    start_faces: RAnsBitDecoder<'a>,
+    seams: Vec<RAnsBitDecoder<'a>>,
+}
+
+/// Context state of the valence traversal coder.
+struct ValenceState {
+    valences: Vec<i32>,
+    context_symbols: Vec<Vec<u32>>,
+    context_counters: Vec<i32>,
+    active_context: i32,
+    last_symbol: u32,
+}
+
+impl<'a> Traversal<'a> {
+    fn start(
+        buffer: &Buffer<'a>,
+        valence: bool,
+        num_vertices: usize,
+        num_faces: usize,
+        num_attribute_data: usize,
+    ) -> Result<(Self, Buffer<'a>)> {
+        let mut buffer = buffer.clone();
+        let mut symbols = None;
+        if !valence {
+            let mut symbol_buffer = buffer.clone();
+            let size = symbol_buffer.start_bits(true)?;
+            let mut rest = symbol_buffer.clone();
+            if size > rest.remaining() as u64 {
+                return err("traversal symbols exceed buffer");
+            }
+            rest.advance(size as usize)?;
+            symbols = Some(symbol_buffer);
+            buffer = rest;
+        }
+        let start_faces = RAnsBitDecoder::start(&mut buffer)?;
+        let seams = (0..num_attribute_data)
+            .map(|_| RAnsBitDecoder::start(&mut buffer))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut out = buffer;
+        let valence = if valence {
+            let mut context_symbols = vec![Vec::new(); 6];
+            let mut context_counters = vec![0i32; 6];
+            for i in 0..6 {
+                let n = out.varint_u32()? as usize;
+                if n > num_faces {
+                    return err("too many valence symbols");
+                }
+                if n > 0 {
+                    // The reference decoder ignores failures here; a short
+                    // symbol list surfaces as an invalid symbol later.
+                    let mut values = decode_symbols(n, 1, &mut out).unwrap_or_default();
+                    values.resize(n, 0);
+                    context_symbols[i] = values;
+                    context_counters[i] = n as i32;
+                }
+            }
+            Some(ValenceState {
+                valences: vec![0; num_vertices],
+                context_symbols,
+                context_counters,
+                active_context: -1,
+                last_symbol: SYMBOL_INVALID,
+            })
+        } else {
+            None
+        };
+
+        Ok((
+            Self {
+                symbols,
+                valence,
+                start_faces,
+                seams,
+            },
+            out,
+        ))
+    }
+
+    fn decode_symbol(&mut self) -> Result<u32> {
+        if let Some(state) = self.valence.as_mut() {
+            state.last_symbol = if state.active_context < 0 {
+                SYMBOL_E
+            } else {
+                let ctx = state.active_context as usize;
+                state.context_counters[ctx] -= 1;
+                let counter = state.context_counters[ctx];
+                if counter < 0 {
+                    SYMBOL_INVALID
+                } else {
+                    match state.context_symbols[ctx][counter as usize] {
+                        id @ 0..=4 => VALENCE_SYMBOLS[id as usize],
+                        _ => SYMBOL_INVALID,
+                    }
+                }
+            };
+            return Ok(state.last_symbol);
+        }
+
+        let Some(bits) = self.symbols.as_mut() else {
+            return err("missing traversal symbols");
+        };
+        let symbol = bits.bits(1)?;
+        if symbol == SYMBOL_C {
+            return Ok(symbol);
+        }
+        Ok(symbol | (bits.bits(2)? << 1))
+    }
+
+    fn new_active_corner(&mut self, table: &CornerTable, corner: u32) {
+        let Some(state) = self.valence.as_mut() else {
+            return;
+        };
+        let next = next(corner);
+        let prev = previous(corner);
+        let mut bump = |c: u32, n: i32| {
+            if let Some(v) = state.valences.get_mut(table.vertex(c) as usize) {
+                *v += n;
+            }
+        };
+        match state.last_symbol {
+            SYMBOL_C | SYMBOL_S => {
+                bump(next, 1);
+                bump(prev, 1);
+            }
+            SYMBOL_R => {
+                bump(corner, 1);
+                bump(next, 1);
+                bump(prev, 2);
+            }
+            SYMBOL_L => {
+                bump(corner, 1);
+                bump(next, 2);
+                bump(prev, 1);
+            }
+            SYMBOL_E => {
+                bump(corner, 2);
+                bump(next, 2);
+                bump(prev, 2);
+            }
+            _ => {}
+        }
+        let valence = state
+            .valences
+            .get(table.vertex(next) as usize)
+            .copied()
+            .unwrap_or(0);
+        state.active_context = valence.clamp(2, 7) - 2;
+    }
+
+    fn merge_vertices(&mut self, dest: u32, src: u32) {
+        if let Some(state) = self.valence.as_mut() {
+            let add = state.valences.get(src as usize).copied().unwrap_or(0);
+            if let Some(v) = state.valences.get_mut(dest as usize) {
+                *v += add;
+            }
+        }
+    }
+}
+
+pub(super) fn decode_edgebreaker<'a>(
+    buffer: &mut Buffer<'a>,
+    valence: bool,
+) -> Result<MeshConnectivity> {
+    let num_encoded_vertices = buffer.varint_u32()? as usize;
+    let num_faces = buffer.varint_u32()? as usize;
+    if num_faces > MAX_EDGEBREAKER_FACES {
+        return err("too many faces");
+    }
+    if num_encoded_vertices > num_faces * 3 {
+        return err("more vertices than corners");
+    }
+    let v = num_encoded_vertices as u64;
+    if v * v.saturating_sub(1) / 2 < (3 * num_faces / 2) as u64 {
+        return err("too few vertices for the face count");
+    }
+    let num_attribute_data = buffer.u8()? as usize;
+    let num_symbols = buffer.varint_u32()? as usize;
+    if num_faces < num_symbols || num_faces > num_symbols + num_symbols / 3 {
+        return err("face and symbol counts disagree");
+    }
+    // the standard traversal spends at least a bit on each symbol
+    if !valence && num_symbols > buffer.remaining() * 8 {
+        return err("more symbols than the input holds");
+    }
+    let num_split_symbols = buffer.varint_u32()? as usize;
+    if num_split_symbols > num_symbols {
+        return err("too many split symbols");
+    }
+
+    let max_vertices = num_encoded_vertices + num_split_symbols;
+    let mut decoder = EdgebreakerDecoder {
+        table: CornerTable::new(num_faces),
+        is_vert_hole: vec![true; max_vertices],
+        splits: decode_topology_splits(buffer, num_faces)?,
+        remove_invalid_vertices: num_attribute_data == 0,
+    };
+
+    let (mut traversal, end) =
+        Traversal::start(buffer, valence, max_vertices, num_faces, num_attribute_data)?;
+    let num_vertices = decoder.decode(&mut traversal, num_symbols, num_faces)?;
+    *buffer = end;
+
+    let mut seam_corners = vec![Vec::new(); num_attribute_data];
+    if num_attribute_data > 0 {
+        let table = &decoder.table;
+        for first in (0..table.corner_to_vertex.len() as u32).step_by(3) {
+            let src_face = first / 3;
+            for corner in [first, next(first), previous(first)] {
+                let opp = table.opposite(corner);
+                if opp == INVALID {
+                    for seams in seam_corners.iter_mut() {
+                        seams.push(corner);
+                    }
+                    continue;
+                }
+                if opp / 3 < src_face {
+                    continue;
+                }
+                for (i, seams) in seam_corners.iter_mut().enumerate() {
+                    if traversal.seams[i].decode_bit() {
+                        seams.push(corner);
+                    }
+                }
+            }
+        }
+    }
+
+    let table = decoder.table;
+    let mut attribute_data = Vec::with_capacity(num_attribute_data);
+    for seams in seam_corners {
+        let mut att_table = AttributeCornerTable::new(&table);
+        for corner in seams {
+            att_table.add_seam_edge(&table, corner);
+        }
+        att_table.recompute_vertices(&table)?;
+        let num_att_vertices = att_table.view(&table).num_vertices();
+        attribute_data.push(AttributeData {
+            encoding: EncodingData::new(num_att_vertices.max(table.num_vertices())),
+            table: att_table,
+            decoder_id: -1,
+            is_connectivity_used: true,
+        });
+    }
+
+    let (faces, num_points) = if attribute_data.is_empty() {
+        let faces = table
+            .corner_to_vertex
+            .chunks_exact(3)
+            .map(|f| [f[0], f[1], f[2]])
+            .collect();
+        (faces, num_vertices)
+    } else {
+        assign_points_to_corners(&table, &decoder.is_vert_hole, &attribute_data)?
+    };
+
+    Ok(MeshConnectivity {
+        faces,
+        num_points,
+        edgebreaker: Some(EdgebreakerData {
+            pos_encoding: EncodingData::new(table.num_vertices()),
+            table,
+            attribute_data,
+        }),
+    })
+}
+
+fn decode_topology_splits(buffer: &mut Buffer, num_faces: usize) -> Result<Vec<TopologySplit>> {
+    let num_splits = buffer.varint_u32()? as usize;
+    if num_splits == 0 {
+        return Ok(Vec::new());
+    }
+    if num_splits > num_faces {
+        return err("too many topology splits");
+    }
+    let mut splits = Vec::with_capacity(num_splits);
+    let mut last_source = 0u32;
+    for _ in 0..num_splits {
+        let source_symbol = buffer.varint_u32()?.wrapping_add(last_source);
+        let delta = buffer.varint_u32()?;
+        if delta > source_symbol {
+            return err("invalid topology split");
+        }
+        splits.push(TopologySplit {
+            source_symbol,
+            split_symbol: source_symbol - delta,
+            source_edge: 0,
+        });
+        last_source = source_symbol;
+    }
+    buffer.start_bits(false)?;
+    for split in splits.iter_mut() {
+        split.source_edge = buffer.bits(1)? & 1;
+    }
+    buffer.end_bits();
+    Ok(splits)
+}
+
+struct EdgebreakerDecoder {
+    table: CornerTable,
+    is_vert_hole: Vec<bool>,
+    splits: Vec<TopologySplit>,
+    remove_invalid_vertices: bool,
+}
+
+impl EdgebreakerDecoder {
+    fn set_hole(&mut self, vertex: u32, hole: bool) -> Result<()> {
+        match self.is_vert_hole.get_mut(vertex as usize) {
+            Some(h) => {
+                *h = hole;
+                Ok(())
+            }
+            None => err("vertex out of range"),
+        }
+    }
+
+    fn new_vertex(&mut self, max_vertices: usize) -> Result<u32> {
+        let v = self.table.add_vertex();
+        if self.table.num_vertices() > max_vertices {
+            return err("unexpected number of vertices");
+        }
+        Ok(v)
+    }
+
+    /// Replays the edgebreaker symbols and rebuilds the corner table. Returns
+    /// the number of vertices that are actually referenced.
+    fn decode(
+        &mut self,
+        traversal: &mut Traversal,
+        num_symbols: usize,
+        expected_faces: usize,
+    ) -> Result<usize> {
+        let max_vertices = self.is_vert_hole.len();
+        let mut active: Vec<u32> = Vec::new();
+        let mut split_corners: HashMap<usize, u32> = HashMap::new();
+        let mut invalid_vertices = Vec::new();
+        let mut num_faces = 0u32;
+
+        for symbol_id in 0..num_symbols {
+            let face = num_faces;
+            num_faces += 1;
+            let corner = 3 * face;
+            let mut check_split = false;
+            let symbol = traversal.decode_symbol()?;
+            match symbol {
+                SYMBOL_C => {
+                    let Some(&corner_a) = active.last() else {
+                        return err("empty active stack");
+                    };
+                    let t = &self.table;
+                    let vertex_x = t.vertex(next(corner_a));
+                    let corner_b = next(t.left_most_corner(vertex_x));
+                    if corner_b == INVALID || corner_a == corner_b {
+                        return err("invalid C symbol");
+                    }
+                    if t.opposite(corner_a) != INVALID || t.opposite(corner_b) != INVALID {
+                        return err("invalid C symbol");
+                    }
+                    let vert_a_prev = t.vertex(previous(corner_a));
+                    let vert_b_next = t.vertex(next(corner_b));
+                    if vertex_x == vert_a_prev || vertex_x == vert_b_next {
+                        return err("invalid C symbol");
+                    }
+                    let t = &mut self.table;
+                    t.set_opposite(corner_a, corner + 1);
+                    t.set_opposite(corner_b, corner + 2);
+                    t.map(corner, vertex_x);
+                    t.map(corner + 1, vert_b_next);
+                    t.map(corner + 2, vert_a_prev);
+                    t.set_left_most_corner(vert_a_prev, corner + 2);
+                    self.set_hole(vertex_x, false)?;
+                    *active.last_mut().unwrap() = corner;
+                }
+                SYMBOL_R | SYMBOL_L => {
+                    let Some(&corner_a) = active.last() else {
+                        return err("empty active stack");
+                    };
+                    if self.table.opposite(corner_a) != INVALID {
+                        return err("invalid R/L symbol");
+                    }
+                    let (opp_corner, corner_l, corner_r) = if symbol == SYMBOL_R {
+                        (corner + 2, corner + 1, corner)
+                    } else {
+                        (corner + 1, corner, corner + 2)
+                    };
+                    self.table.set_opposite(opp_corner, corner_a);
+                    let new_vertex = self.new_vertex(max_vertices)?;
+                    let t = &mut self.table;
+                    t.map(opp_corner, new_vertex);
+                    t.set_left_most_corner(new_vertex, opp_corner);
+                    let vertex_r = t.vertex(previous(corner_a));
+                    t.map(corner_r, vertex_r);
+                    t.set_left_most_corner(vertex_r, corner_r);
+                    let vertex_l = t.vertex(next(corner_a));
+                    t.map(corner_l, vertex_l);
+                    *active.last_mut().unwrap() = corner;
+                    check_split = true;
+                }
+                SYMBOL_S => {
+                    let Some(corner_b) = active.pop() else {
+                        return err("empty active stack");
+                    };
+                    if let Some(&c) = split_corners.get(&symbol_id) {
+                        active.push(c);
+                    }
+                    let Some(&corner_a) = active.last() else {
+                        return err("empty active stack");
+                    };
+                    let t = &mut self.table;
+                    if corner_a == corner_b
+                        || t.opposite(corner_a) != INVALID
+                        || t.opposite(corner_b) != INVALID
+                    {
+                        return err("invalid S symbol");
+                    }
+                    t.set_opposite(corner_a, corner + 2);
+                    t.set_opposite(corner_b, corner + 1);
+                    let vertex_p = t.vertex(previous(corner_a));
+                    t.map(corner, vertex_p);
+                    let vertex_a_next = t.vertex(next(corner_a));
+                    t.map(corner + 1, vertex_a_next);
+                    let vert_b_prev = t.vertex(previous(corner_b));
+                    t.map(corner + 2, vert_b_prev);
+                    t.set_left_most_corner(vert_b_prev, corner + 2);
+                    let mut corner_n = next(corner_b);
+                    let vertex_n = t.vertex(corner_n);
+                    traversal.merge_vertices(vertex_p, vertex_n);
+                    let lmc = t.left_most_corner(vertex_n);
+                    t.set_left_most_corner(vertex_p, lmc);
+                    let first = corner_n;
+                    while corner_n != INVALID {
+                        t.map(corner_n, vertex_p);
+                        corner_n = t.swing_left(corner_n);
+                        if corner_n == first {
+                            return err("invalid S symbol");
+                        }
+                    }
+                    t.set_left_most_corner(vertex_n, INVALID);
+                    if self.remove_invalid_vertices {
+                        invalid_vertices.push(vertex_n);
+                    }
+                    *active.last_mut().unwrap() = corner;
+                }
+                SYMBOL_E => {
+                    let first = self.table.add_vertex();
+                    let second = self.table.add_vertex();
+                    let third = self.new_vertex(max_vertices)?;
+                    let t = &mut self.table;
+                    for (i, v) in [first, second, third].into_iter().enumerate() {
+                        t.map(corner + i as u32, v);
+                        t.set_left_most_corner(v, corner + i as u32);
+                    }
+                    active.push(corner);
+                    check_split = true;
+                }
+                _ => return err("invalid edgebreaker symbol"),
+            }
+
+            let top = *active.last().unwrap();
+            traversal.new_active_corner(&self.table, top);
+            if check_split {
+                let encoder_symbol = (num_symbols - symbol_id - 1) as u32;
+                while let Some(split) = self.splits.last() {
+                    if split.source_symbol > encoder_symbol {
+                        return err("missed topology split");
+                    }
+                    if split.source_symbol != encoder_symbol {
+                        break;
+                    }
+                    let top = *active.last().unwrap();
+                    let new_corner = if split.source_edge == 1 {
+                        next(top)
+                    } else {
+                        previous(top)
+                    };
+                    let decoder_split = num_symbols as i64 - split.split_symbol as i64 - 1;
+                    if decoder_split >= 0 {
+                        split_corners.insert(decoder_split as usize, new_corner);
+                    }
+                    self.splits.pop();
+                }
+            }
+        }
+
+        if self.table.num_vertices() > max_vertices {
+            return err("unexpected number of vertices");
+        }
+
+        while let Some(corner) = active.pop() {
+            if !traversal.start_faces.decode_bit() {
+                continue;
+            }
+            if num_faces as usize >= expected_faces {
+                return err("too many faces");
+            }
+            let t = &self.table;
+            let vert_n = t.vertex(next(corner));
+            let corner_b = next(t.left_most_corner(vert_n));
+            let vert_x = t.vertex(next(corner_b));
+            let corner_c = next(t.left_most_corner(vert_x));
+            if corner_b == INVALID || corner_c == INVALID {
+                return err("invalid start face");
+            }
+            if corner == corner_b || corner == corner_c || corner_b == corner_c {
+                return err("invalid start face");
+            }
+            if t.opposite(corner) != INVALID
+                || t.opposite(corner_b) != INVALID
+                || t.opposite(corner_c) != INVALID
+            {
+                return err("invalid start face");
+            }
+            let vert_p = t.vertex(next(corner_c));
+            let new_corner = 3 * num_faces;
+            num_faces += 1;
+            let t = &mut self.table;
+            t.set_opposite(new_corner, corner);
+            t.set_opposite(new_corner + 1, corner_b);
+            t.set_opposite(new_corner + 2, corner_c);
+            t.map(new_corner, vert_x);
+            t.map(new_corner + 1, vert_p);
+            t.map(new_corner + 2, vert_n);
+            for v in [vert_x, vert_p, vert_n] {
+                self.set_hole(v, false)?;
+            }
+        }
+        if num_faces as usize != expected_faces {
+            return err("unexpected number of faces");
+        }
+
+        // Move the last used vertices into the slots freed by S symbols so
+        // the vertex range stays dense.
+        let mut num_vertices = self.table.num_vertices();
+        for invalid in invalid_vertices {
+            while num_vertices > 0
+                && self.table.left_most_corner(num_vertices as u32 - 1) == INVALID
+            {
+                num_vertices -= 1;
+            }
+            if num_vertices == 0 {
+                return err("no vertices left to compact");
+            }
+            let src = num_vertices as u32 - 1;
+            if src < invalid {
+                continue;
+            }
+            for c in vertex_corners(&self.table, self.table.left_most_corner(src)) {
+                if self.table.vertex(c) != src {
+                    return err("inconsistent vertex corners");
+                }
+                self.table.map(c, invalid);
+            }
+            let lmc = self.table.left_most_corner(src);
+            self.table.set_left_most_corner(invalid, lmc);
+            self.table.set_left_most_corner(src, INVALID);
+            self.is_vert_hole[invalid as usize] = self.is_vert_hole[src as usize];
+            self.is_vert_hole[src as usize] = false;
+            num_vertices -= 1;
+        }
+        Ok(num_vertices)
+    }
+}
+
+/// Splits connectivity vertices into points wherever any attribute has a
+/// seam, matching `AssignPointsToCorners`.
+fn assign_points_to_corners(
+    table: &CornerTable,
+    is_vert_hole: &[bool],
+    attribute_data: &[AttributeData],
+) -> Result<(Vec<[u32; 3]>, usize)> {
+    let views: Vec<_> = attribute_data.iter().map(|d| d.table.view(table)).collect();
+    let mut corner_to_point = vec![0u32; table.corner_to_vertex.len()];
+    let mut num_points = 0u32;
+    for (v, &is_hole) in is_vert_hole.iter().enumerate().take(table.num_vertices()) {
+        let c = table.left_most_corner(v as u32);
+        if c == INVALID {
+            continue;
+        }
+        let mut first = c;
+        if !is_hole {
+            for (data, view) in attribute_data.iter().zip(&views) {
+                if !data.table.is_corner_on_seam(table, c) {
+                    continue;
+                }
+                let vert_id = view.vertex(c);
+                let mut act = table.swing_right(c);
+                let mut seam_found = false;
+                while act != c {
+                    if act == INVALID {
+                        return err("open vertex fan on an attribute seam");
+                    }
+                    if view.vertex(act) != vert_id {
+                        first = act;
+                        seam_found = true;
+                        break;
+                    }
+                    act = table.swing_right(act);
+                }
+                if seam_found {
+                    break;
+                }
+            }
+        }
+
+        corner_to_point[first as usize] = num_points;
+        num_points += 1;
+        let mut prev = first;
+        let mut c = table.swing_right(first);
+        while c != INVALID && c != first {
+            if views.iter().any(|view| view.vertex(c) != view.vertex(prev)) {
+                corner_to_point[c as usize] = num_points;
+                num_points += 1;
+            } else {
+                corner_to_point[c as usize] = corner_to_point[prev as usize];
+            }
+            prev = c;
+            c = table.swing_right(c);
+        }
+    }
+    let faces = corner_to_point
+        .chunks_exact(3)
+        .map(|f| [f[0], f[1], f[2]])
+        .collect();
+    Ok((faces, num_points as usize))
+}
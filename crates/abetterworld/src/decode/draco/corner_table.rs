@@ -0,0 +1,312 @@
+use super::{err, Result};
+
+/// Sentinel used for missing corners and vertices, as `kInvalidCornerIndex`.
+pub(super) const INVALID: u32 = u32::MAX;
+
+/// Read access shared by the base corner table and the per-attribute tables
+/// that split vertices along attribute seams.
+pub(super) trait Connectivity {
+    fn opposite(&self, corner: u32) -> u32;
+    fn vertex(&self, corner: u32) -> u32;
+    fn left_most_corner(&self, vertex: u32) -> u32;
+    fn num_vertices(&self) -> usize;
+    fn num_faces(&self) -> usize;
+
+    fn next(&self, corner: u32) -> u32 {
+        next(corner)
+    }
+
+    fn previous(&self, corner: u32) -> u32 {
+        previous(corner)
+    }
+
+    fn swing_right(&self, corner: u32) -> u32 {
+        self.previous(self.opposite(self.previous(corner)))
+    }
+
+    fn swing_left(&self, corner: u32) -> u32 {
+        self.next(self.opposite(self.next(corner)))
+    }
+
+    fn right_corner(&self, corner: u32) -> u32 {
+        self.opposite(self.next(corner))
+    }
+
+    fn left_corner(&self, corner: u32) -> u32 {
+        self.opposite(self.previous(corner))
+    }
+
+    fn is_on_boundary(&self, vertex: u32) -> bool {
+        let corner = self.left_most_corner(vertex);
+        corner == INVALID || self.swing_left(corner) == INVALID
+    }
+}
+
+pub(super) fn next(corner: u32) -> u32 {
+    if corner == INVALID {
+        corner
+    } else if corner % 3 == 2 {
+        corner - 2
+    } else {
+        corner + 1
+    }
+}
+
+pub(super) fn previous(corner: u32) -> u32 {
+    if corner == INVALID {
+        corner
+    } else if corner.is_multiple_of(3) {
+        corner + 2
+    } else {
+        corner - 1
+    }
+}
+
+/// Walks all corners around a vertex: counter-clockwise first and, when a
+/// boundary is hit, clockwise from the start (`VertexCornersIterator`).
+pub(super) fn vertex_corners<T: Connectivity + ?Sized>(table: &T, start: u32) -> Vec<u32> {
+    let mut out = Vec::new();
+    if start == INVALID {
+        return out;
+    }
+    out.push(start);
+    let mut corner = table.swing_left(start);
+    while corner != INVALID && corner != start {
+        out.push(corner);
+        corner = table.swing_left(corner);
+    }
+    if corner == INVALID {
+        corner = table.swing_right(start);
+        while corner != INVALID {
+            out.push(corner);
+            corner = table.swing_right(corner);
+        }
+    }
+    out
+}
+
+/// Corner table rebuilt from the edgebreaker connectivity.
+#[derive(Default)]
+pub(super) struct CornerTable {
+    pub corner_to_vertex: Vec<u32>,
+    pub opposite: Vec<u32>,
+    pub vertex_corners: Vec<u32>,
+}
+
+impl CornerTable {
+    pub fn new(num_faces: usize) -> Self {
+        Self {
+            corner_to_vertex: vec![INVALID; num_faces * 3],
+            opposite: vec![INVALID; num_faces * 3],
+            vertex_corners: Vec::new(),
+        }
+    }
+
+    pub fn set_opposite(&mut self, a: u32, b: u32) {
+        self.opposite[a as usize] = b;
+        self.opposite[b as usize] = a;
+    }
+
+    pub fn map(&mut self, corner: u32, vertex: u32) {
+        self.corner_to_vertex[corner as usize] = vertex;
+    }
+
+    pub fn add_vertex(&mut self) -> u32 {
+        self.vertex_corners.push(INVALID);
+        (self.vertex_corners.len() - 1) as u32
+    }
+
+    pub fn set_left_most_corner(&mut self, vertex: u32, corner: u32) {
+        if vertex != INVALID {
+            self.vertex_corners[vertex as usize] = corner;
+        }
+    }
+}
+
+impl Connectivity for CornerTable {
+    fn opposite(&self, corner: u32) -> u32 {
+        if corner == INVALID {
+            return INVALID;
+        }
+        self.opposite[corner as usize]
+    }
+
+    fn vertex(&self, corner: u32) -> u32 {
+        if corner == INVALID {
+            return INVALID;
+        }
+        self.corner_to_vertex[corner as usize]
+    }
+
+    fn left_most_corner(&self, vertex: u32) -> u32 {
+        if vertex == INVALID {
+            return INVALID;
+        }
+        self.vertex_corners[vertex as usize]
+    }
+
+    fn num_vertices(&self) -> usize {
+        self.vertex_corners.len()
+    }
+
+    fn num_faces(&self) -> usize {
+        self.corner_to_vertex.len() / 3
+    }
+}
+
+/// Attribute connectivity on top of a [`CornerTable`]: seam edges act as
+/// boundaries and vertices on seams are split
+/// (`MeshAttributeCornerTable`).
+pub(super) struct AttributeCornerTable {
+    is_edge_on_seam: Vec<bool>,
+    is_vertex_on_seam: Vec<bool>,
+    corner_to_vertex: Vec<u32>,
+    vertex_corners: Vec<u32>,
+}
+
+impl AttributeCornerTable {
+    pub fn new(base: &CornerTable) -> Self {
+        Self {
+            is_edge_on_seam: vec![false; base.corner_to_vertex.len()],
+            is_vertex_on_seam: vec![false; base.num_vertices()],
+            corner_to_vertex: vec![INVALID; base.corner_to_vertex.len()],
+            vertex_corners: Vec::new(),
+        }
+    }
+
+    pub fn add_seam_edge(&mut self, base: &CornerTable, corner: u32) {
+        self.is_edge_on_seam[corner as usize] = true;
+        self.is_vertex_on_seam[base.vertex(next(corner)) as usize] = true;
+        self.is_vertex_on_seam[base.vertex(previous(corner)) as usize] = true;
+        let opp = base.opposite(corner);
+        if opp != INVALID {
+            self.is_edge_on_seam[opp as usize] = true;
+            self.is_vertex_on_seam[base.vertex(next(opp)) as usize] = true;
+            self.is_vertex_on_seam[base.vertex(previous(opp)) as usize] = true;
+        }
+    }
+
+    pub fn is_corner_on_seam(&self, base: &CornerTable, corner: u32) -> bool {
+        self.is_vertex_on_seam[base.vertex(corner) as usize]
+    }
+
+    /// Assigns attribute vertex ids by walking around every base vertex and
+    /// starting a new id whenever a seam edge is crossed.
+    pub fn recompute_vertices(&mut self, base: &CornerTable) -> Result<()> {
+        for v in 0..base.num_vertices() as u32 {
+            let c = base.left_most_corner(v);
+            if c == INVALID {
+                continue;
+            }
+            let mut vertex_id = self.vertex_corners.len() as u32;
+            let mut first = c;
+            if self.is_vertex_on_seam[v as usize] {
+                let view = self.view(base);
+                let mut act = view.swing_left(c);
+                while act != INVALID {
+                    first = act;
+                    act = view.swing_left(act);
+                    if act == c {
+                        return err("attribute seam loop");
+                    }
+                }
+            }
+            self.corner_to_vertex[first as usize] = vertex_id;
+            self.vertex_corners.push(first);
+            let mut act = base.swing_right(first);
+            while act != INVALID && act != first {
+                if self.is_edge_on_seam[next(act) as usize] {
+                    vertex_id = self.vertex_corners.len() as u32;
+                    self.vertex_corners.push(act);
+                }
+                self.corner_to_vertex[act as usize] = vertex_id;
+                act = base.swing_right(act);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn view<'a>(&'a self, base: &'a CornerTable) -> AttributeView<'a> {
+        AttributeView { base, table: self }
+    }
+}
+
+/// An [`AttributeCornerTable`] paired with the base table it refines.
+pub(super) struct AttributeView<'a> {
+    base: &'a CornerTable,
+    table: &'a AttributeCornerTable,
+}
+
+impl Connectivity for AttributeView<'_> {
+    fn opposite(&self, corner: u32) -> u32 {
+        if corner == INVALID || self.table.is_edge_on_seam[corner as usize] {
+            return INVALID;
+        }
+        self.base.opposite(corner)
+    }
+
+    fn vertex(&self, corner: u32) -> u32 {
+        if corner == INVALID {
+            return INVALID;
+        }
+        self.table.corner_to_vertex[corner as usize]
+    }
+
+    fn left_most_corner(&self, vertex: u32) -> u32 {
+        if vertex == INVALID {
+            return INVALID;
+        }
+        self.table.vertex_corners[vertex as usize]
+    }
+
+    fn num_vertices(&self) -> usize {
+        self.table.vertex_corners.len()
+    }
+
+    fn num_faces(&self) -> usize {
+        self.base.num_faces()
+    }
+}
+
+/// Either table, chosen per attribute group at runtime.
+pub(super) enum TableRef<'a> {
+    Base(&'a CornerTable),
+    Attribute(AttributeView<'a>),
+}
+
+impl Connectivity for TableRef<'_> {
+    fn opposite(&self, corner: u32) -> u32 {
+        match self {
+            TableRef::Base(t) => t.opposite(corner),
+            TableRef::Attribute(t) => t.opposite(corner),
+        }
+    }
+
+    fn vertex(&self, corner: u32) -> u32 {
+        match self {
+            TableRef::Base(t) => t.vertex(corner),
+            TableRef::Attribute(t) => t.vertex(corner),
+        }
+    }
+
+    fn left_most_corner(&self, vertex: u32) -> u32 {
+        match self {
+            TableRef::Base(t) => t.left_most_corner(vertex),
+            TableRef::Attribute(t) => t.left_most_corner(vertex),
+        }
+    }
+
+    fn num_vertices(&self) -> usize {
+        match self {
+            TableRef::Base(t) => t.num_vertices(),
+            TableRef::Attribute(t) => t.num_vertices(),
+        }
+    }
+
+    fn num_faces(&self) -> usize {
+        match self {
+            TableRef::Base(t) => t.num_faces(),
+            TableRef::Attribute(t) => t.num_faces(),
+        }
+    }
+}
@@ -0,0 +1,153 @@
+//! Pure-Rust decoder for Draco 2.2 meshes, the bitstream produced by
+//! `KHR_draco_mesh_compression` encoders. It follows the reference decoder
+//! step by step so both paths produce identical vertices and indices.
+
+mod attributes;
+mod buffer;
+mod connectivity;
+mod corner_table;
+mod prediction;
+mod rans;
+
+use attributes::{Attribute, COLOR, NORMAL, POSITION, TEX_COORD};
+use buffer::Buffer;
+
+use super::Vertex;
+use crate::helpers::AbwError;
+
+type Result<T> = std::result::Result<T, AbwError>;
+
+fn err<T>(msg: &str) -> Result<T> {
+    Err(AbwError::TileLoading(format!("draco: {msg}")))
+}
+
+const TRIANGULAR_MESH: u8 = 1;
+const MESH_SEQUENTIAL_ENCODING: u8 = 0;
+const MESH_EDGEBREAKER_ENCODING: u8 = 1;
+const EDGEBREAKER_VALENCE_DECODING: u8 = 2;
+const METADATA_FLAG_MASK: u16 = 0x8000;
+
+/// Decodes a Draco mesh into the interleaved layout the C++ wrapper emits:
+/// one vertex per point and three indices per face.
+pub(crate) fn decode_mesh(data: &[u8]) -> Result<(Vec<Vertex>, Vec<u32>)> {
+    let mut buffer = Buffer::new(data);
+    if buffer.bytes(5)? != b"DRACO" {
+        return err("not a draco stream");
+    }
+    let (major, minor) = (buffer.u8()?, buffer.u8()?);
+    if (major, minor) != (2, 2) {
+        return err(&format!("unsupported bitstream version {major}.{minor}"));
+    }
+    if buffer.u8()? != TRIANGULAR_MESH {
+        return err("not a triangular mesh");
+    }
+    let method = buffer.u8()?;
+    let flags = buffer.u16()?;
+    if flags & METADATA_FLAG_MASK != 0 {
+        skip_metadata(&mut buffer)?;
+    }
+
+    let mut connectivity = match method {
+        MESH_SEQUENTIAL_ENCODING => connectivity::decode_sequential(&mut buffer)?,
+        MESH_EDGEBREAKER_ENCODING => {
+            let valence = match buffer.u8()? {
+                0 => false,
+                EDGEBREAKER_VALENCE_DECODING => true,
+                _ => return err("unsupported edgebreaker traversal"),
+            };
+            connectivity::decode_edgebreaker(&mut buffer, valence)?
+        }
+        _ => return err("unknown encoding method"),
+    };
+    let attributes = attributes::decode_attributes(&mut buffer, &mut connectivity)?;
+
+    let first = |att_type| attributes.iter().find(|a| a.att_type == att_type);
+    let Some(position) = first(POSITION) else {
+        return err("mesh has no positions");
+    };
+    let normal = first(NORMAL);
+    let color = first(COLOR);
+    let mut tex_coords = attributes.iter().filter(|a| a.att_type == TEX_COORD);
+    let (uv0, uv1) = (tex_coords.next(), tex_coords.next());
+
+    let mut vertices = Vec::with_capacity(connectivity.num_points);
+    for point in 0..connectivity.num_points as u32 {
+        let mut vertex = Vertex {
+            position: [0.0; 3],
+            normal: [0.0, 0.0, 1.0],
+            color: [1.0; 4],
+            texcoord0: [0.0; 2],
+            texcoord1: [0.0; 2],
+        };
+        convert(position, point, &mut vertex.position)?;
+        if let Some(normal) = normal {
+            convert(normal, point, &mut vertex.normal)?;
+        }
+        if let Some(color) = color {
+            convert(color, point, &mut vertex.color)?;
+            if color.num_components < 4 {
+                vertex.color[3] = 1.0;
+            }
+        }
+        if let Some(uv0) = uv0 {
+            convert(uv0, point, &mut vertex.texcoord0)?;
+        }
+        if let Some(uv1) = uv1 {
+            convert(uv1, point, &mut vertex.texcoord1)?;
+        }
+        vertices.push(vertex);
+    }
+
+    let indices = connectivity.faces.into_iter().flatten().collect();
+    Ok((vertices, indices))
+}
+
+/// `ConvertValue` into a fixed number of output components: missing
+/// components are zero, extra ones are dropped.
+fn convert(attribute: &Attribute, point: u32, out: &mut [f32]) -> Result<()> {
+    let Some(value) = attribute.value(point) else {
+        return err("point without attribute value");
+    };
+    out.fill(0.0);
+    for (o, v) in out.iter_mut().zip(value) {
+        *o = *v;
+    }
+    Ok(())
+}
+
+/// Geometry and attribute metadata carry nothing the renderer uses, so it is
+/// validated and skipped.
+fn skip_metadata(buffer: &mut Buffer) -> Result<()> {
+    let num_att_metadata = buffer.varint_u32()?;
+    for _ in 0..num_att_metadata {
+        let _att_id = buffer.varint_u32()?;
+        skip_metadata_block(buffer)?;
+    }
+    skip_metadata_block(buffer)
+}
+
+fn skip_metadata_block(buffer: &mut Buffer) -> Result<()> {
+    let num_entries = buffer.varint_u32()?;
+    for _ in 0..num_entries {
+        skip_name(buffer)?;
+        let size = buffer.varint_u32()? as usize;
+        if size == 0 || size > buffer.remaining() {
+            return err("invalid metadata entry");
+        }
+        buffer.advance(size)?;
+    }
+    let num_sub_metadata = buffer.varint_u32()? as usize;
+    if num_sub_metadata > buffer.remaining() {
+        return err("invalid metadata");
+    }
+    for _ in 0..num_sub_metadata {
+        skip_name(buffer)?;
+        skip_metadata_block(buffer)?;
+    }
+    Ok(())
+}
+
+fn skip_name(buffer: &mut Buffer) -> Result<()> {
+    let len = buffer.u8()? as usize;
+    buffer.advance(len)
+}
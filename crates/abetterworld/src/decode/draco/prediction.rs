@@ -0,0 +1,688 @@
+use smallvec::SmallVec;
+
+use super::{
+    attributes::Attribute,
+    buffer::Buffer,
+    corner_table::{vertex_corners, Connectivity, TableRef, INVALID},
+    err,
+    rans::RAnsBitDecoder,
+    Result,
+};
+
+type Values = SmallVec<[i32; 4]>;
+
+/// Method ids as stored in the stream (`PredictionSchemeMethod`).
+pub(super) const PREDICTION_NONE: i8 = -2;
+const MESH_PARALLELOGRAM: i8 = 1;
+const MESH_MULTI_PARALLELOGRAM: i8 = 2;
+const MESH_TEX_COORDS_DEPRECATED: i8 = 3;
+const MESH_CONSTRAINED_MULTI_PARALLELOGRAM: i8 = 4;
+const MESH_TEX_COORDS_PORTABLE: i8 = 5;
+const MESH_GEOMETRIC_NORMAL: i8 = 6;
+
+/// Transform ids (`PredictionSchemeTransformType`).
+pub(super) const TRANSFORM_WRAP: i8 = 1;
+pub(super) const TRANSFORM_NORMAL_OCTAHEDRON: i8 = 2;
+pub(super) const TRANSFORM_NORMAL_OCTAHEDRON_CANONICALIZED: i8 = 3;
+
+/// Helpers for octahedral normal coordinates (`OctahedronToolBox`).
+#[derive(Clone, Copy, Default)]
+pub(super) struct OctahedronToolBox {
+    pub quantization_bits: i32,
+    max_quantized_value: i32,
+    max_value: i32,
+    center_value: i32,
+    dequantization_scale: f32,
+}
+
+impl OctahedronToolBox {
+    pub fn new(quantization_bits: i32) -> Result<Self> {
+        if !(2..=30).contains(&quantization_bits) {
+            return err("invalid normal quantization bits");
+        }
+        let max_quantized_value = ((1u32 << quantization_bits) - 1) as i32;
+        let max_value = max_quantized_value - 1;
+        Ok(Self {
+            quantization_bits,
+            max_quantized_value,
+            max_value,
+            center_value: max_value / 2,
+            dequantization_scale: 2.0 / max_value as f32,
+        })
+    }
+
+    pub fn unit_vector(&self, s: i32, t: i32) -> [f32; 3] {
+        let mut y = s as f32 * self.dequantization_scale - 1.0;
+        let mut z = t as f32 * self.dequantization_scale - 1.0;
+        let x = 1.0 - y.abs() - z.abs();
+        let x_offset = (-x).max(0.0);
+        y += if y < 0.0 { x_offset } else { -x_offset };
+        z += if z < 0.0 { x_offset } else { -x_offset };
+        let norm_squared = x * x + y * y + z * z;
+        if (norm_squared as f64) < 1e-6 {
+            return [0.0; 3];
+        }
+        let d = 1.0 / norm_squared.sqrt();
+        [x * d, y * d, z * d]
+    }
+
+    fn is_in_diamond(&self, s: i32, t: i32) -> bool {
+        s.unsigned_abs().wrapping_add(t.unsigned_abs()) <= self.center_value as u32
+    }
+
+    fn invert_diamond(&self, s: &mut i32, t: &mut i32) {
+        let (sign_s, sign_t) = if *s >= 0 && *t >= 0 {
+            (1, 1)
+        } else if *s <= 0 && *t <= 0 {
+            (-1, -1)
+        } else {
+            (if *s > 0 { 1 } else { -1 }, if *t > 0 { 1 } else { -1 })
+        };
+        let corner_s = (sign_s * self.center_value) as u32;
+        let corner_t = (sign_t * self.center_value) as u32;
+        let mut us = *s as u32;
+        let mut ut = *t as u32;
+        us = us.wrapping_add(us).wrapping_sub(corner_s);
+        ut = ut.wrapping_add(ut).wrapping_sub(corner_t);
+        if sign_s * sign_t >= 0 {
+            let temp = us;
+            us = ut.wrapping_neg();
+            ut = temp.wrapping_neg();
+        } else {
+            std::mem::swap(&mut us, &mut ut);
+        }
+        us = us.wrapping_add(corner_s);
+        ut = ut.wrapping_add(corner_t);
+        *s = us as i32 / 2;
+        *t = ut as i32 / 2;
+    }
+
+    fn mod_max(&self, x: i32) -> i32 {
+        if x > self.center_value {
+            x.wrapping_sub(self.max_quantized_value)
+        } else if x < -self.center_value {
+            x.wrapping_add(self.max_quantized_value)
+        } else {
+            x
+        }
+    }
+
+    fn canonicalize_integer_vector(&self, vec: &mut [i32; 3]) {
+        let abs_sum = (vec[0] as i64).abs() + (vec[1] as i64).abs() + (vec[2] as i64).abs();
+        if abs_sum == 0 {
+            vec[0] = self.center_value;
+            return;
+        }
+        let center = self.center_value as i64;
+        vec[0] = ((vec[0] as i64 * center) / abs_sum) as i32;
+        vec[1] = ((vec[1] as i64 * center) / abs_sum) as i32;
+        let rest = self.center_value - vec[0].abs() - vec[1].abs();
+        vec[2] = if vec[2] >= 0 { rest } else { -rest };
+    }
+
+    fn integer_vector_to_octahedral(&self, vec: &[i32; 3]) -> (i32, i32) {
+        let (s, t) = if vec[0] >= 0 {
+            (vec[1] + self.center_value, vec[2] + self.center_value)
+        } else {
+            let s = if vec[1] < 0 {
+                vec[2].abs()
+            } else {
+                self.max_value - vec[2].abs()
+            };
+            let t = if vec[2] < 0 {
+                vec[1].abs()
+            } else {
+                self.max_value - vec[1].abs()
+            };
+            (s, t)
+        };
+        self.canonicalize_octahedral(s, t)
+    }
+
+    fn canonicalize_octahedral(&self, mut s: i32, mut t: i32) -> (i32, i32) {
+        let (c, m) = (self.center_value, self.max_value);
+        if (s == 0 && (t == 0 || t == m)) || (s == m && t == 0) {
+            s = m;
+            t = m;
+        } else if s == 0 && t > c {
+            t = c - (t - c);
+        } else if s == m && t < c {
+            t = c + (c - t);
+        } else if t == m && s < c {
+            s = c + (c - s);
+        } else if t == 0 && s > c {
+            s = c - (s - c);
+        }
+        (s, t)
+    }
+}
+
+/// Transform that turns a prediction and a correction into the original
+/// value.
+pub(super) enum Transform {
+    Wrap { min: i32, max: i32, max_dif: i32 },
+    Canonicalized(OctahedronToolBox),
+}
+
+impl Transform {
+    pub fn corrections_positive(&self) -> bool {
+        matches!(self, Transform::Canonicalized(_))
+    }
+
+    fn decode_data(&mut self, buffer: &mut Buffer) -> Result<()> {
+        match self {
+            Transform::Wrap { min, max, max_dif } => {
+                let lo = buffer.i32()?;
+                let hi = buffer.i32()?;
+                if lo > hi {
+                    return err("invalid wrap bounds");
+                }
+                let dif = hi as i64 - lo as i64;
+                if dif >= i32::MAX as i64 {
+                    return err("invalid wrap bounds");
+                }
+                (*min, *max, *max_dif) = (lo, hi, 1 + dif as i32);
+            }
+            Transform::Canonicalized(toolbox) => {
+                let max_quantized_value = buffer.i32()?;
+                let _center_value = buffer.i32()?;
+                if max_quantized_value % 2 == 0 || max_quantized_value <= 0 {
+                    return err("invalid normal transform");
+                }
+                let msb = 31 - max_quantized_value.leading_zeros() as i32;
+                *toolbox = OctahedronToolBox::new(msb + 1)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// `values` holds the correction on input and the original value on
+    /// output.
+    fn compute(&self, pred: &[i32], values: &mut [i32]) {
+        match self {
+            Transform::Wrap { min, max, max_dif } => {
+                for (out, &p) in values.iter_mut().zip(pred) {
+                    let p = p.clamp(*min, *max);
+                    let mut v = (p as u32).wrapping_add(*out as u32) as i32;
+                    if v > *max {
+                        v = v.wrapping_sub(*max_dif);
+                    } else if v < *min {
+                        v = v.wrapping_add(*max_dif);
+                    }
+                    *out = v;
+                }
+            }
+            Transform::Canonicalized(tb) => {
+                let c = tb.center_value;
+                let mut ps = pred[0].wrapping_sub(c);
+                let mut pt = pred[1].wrapping_sub(c);
+                let in_diamond = tb.is_in_diamond(ps, pt);
+                if !in_diamond {
+                    tb.invert_diamond(&mut ps, &mut pt);
+                }
+                let bottom_left = (ps == 0 && pt == 0) || (ps < 0 && pt <= 0);
+                let rotation = match (ps.signum(), pt) {
+                    (0, 0) => 0,
+                    (0, y) if y > 0 => 3,
+                    (0, _) => 1,
+                    (1, y) if y >= 0 => 2,
+                    (1, _) => 1,
+                    (_, y) if y <= 0 => 0,
+                    _ => 3,
+                };
+                if !bottom_left {
+                    (ps, pt) = rotate(ps, pt, rotation);
+                }
+                let mut os = tb.mod_max(ps.wrapping_add(values[0]));
+                let mut ot = tb.mod_max(pt.wrapping_add(values[1]));
+                if !bottom_left {
+                    (os, ot) = rotate(os, ot, (4 - rotation) % 4);
+                }
+                if !in_diamond {
+                    tb.invert_diamond(&mut os, &mut ot);
+                }
+                values[0] = os.wrapping_add(c);
+                values[1] = ot.wrapping_add(c);
+            }
+        }
+    }
+}
+
+fn rotate(x: i32, y: i32, count: i32) -> (i32, i32) {
+    match count {
+        1 => (y, x.wrapping_neg()),
+        2 => (x.wrapping_neg(), y.wrapping_neg()),
+        3 => (y.wrapping_neg(), x),
+        _ => (x, y),
+    }
+}
+
+/// Connectivity and encoding order the mesh prediction schemes work on.
+pub(super) struct MeshData<'a> {
+    pub table: TableRef<'a>,
+    pub value_to_corner: &'a [u32],
+    pub vertex_to_value: &'a [i32],
+}
+
+impl MeshData<'_> {
+    fn corner_of_value(&self, value: usize) -> Result<u32> {
+        match self.value_to_corner.get(value) {
+            Some(&corner) => Ok(corner),
+            None => err("attribute value without a corner"),
+        }
+    }
+
+    fn value_of_corner(&self, corner: u32) -> Result<i32> {
+        let vertex = self.table.vertex(corner);
+        match self.vertex_to_value.get(vertex as usize) {
+            Some(&v) if vertex != INVALID => Ok(v),
+            _ => err("corner without attribute value"),
+        }
+    }
+}
+
+/// Position lookups for schemes that predict from geometry.
+pub(super) struct Positions<'a> {
+    pub attribute: &'a Attribute,
+    pub point_ids: &'a [u32],
+}
+
+impl Positions<'_> {
+    fn for_entry(&self, entry: i32) -> Result<[i64; 3]> {
+        let Some(&point) = self.point_ids.get(entry as usize) else {
+            return err("prediction entry out of range");
+        };
+        self.attribute.portable_position(point)
+    }
+}
+
+pub(super) enum Scheme<'a> {
+    Delta,
+    Parallelogram(MeshData<'a>),
+    ConstrainedMulti(MeshData<'a>, [Vec<bool>; 4]),
+    TexCoordsPortable(MeshData<'a>, Positions<'a>, Vec<bool>),
+    GeometricNormal(MeshData<'a>, Positions<'a>, RAnsBitDecoder<'a>),
+}
+
+impl<'a> Scheme<'a> {
+    /// Picks the scheme the reference decoder would build. Mesh schemes need
+    /// edgebreaker connectivity; without it everything is delta coded.
+    pub fn create(
+        method: i8,
+        transform: &Transform,
+        mesh: Option<MeshData<'a>>,
+        positions: Option<Positions<'a>>,
+    ) -> Result<Scheme<'a>> {
+        let Some(mesh) = mesh else {
+            return Ok(Scheme::Delta);
+        };
+        let canonicalized = matches!(transform, Transform::Canonicalized(_));
+        let needs_positions = |positions: Option<Positions<'a>>| match positions {
+            Some(p) if p.attribute.num_components == 3 => Ok(p),
+            _ => err("prediction needs quantized positions"),
+        };
+        Ok(match method {
+            MESH_GEOMETRIC_NORMAL => Scheme::GeometricNormal(
+                mesh,
+                needs_positions(positions)?,
+                RAnsBitDecoder::default(),
+            ),
+            _ if canonicalized => Scheme::Delta,
+            MESH_PARALLELOGRAM => Scheme::Parallelogram(mesh),
+            MESH_CONSTRAINED_MULTI_PARALLELOGRAM => {
+                Scheme::ConstrainedMulti(mesh, Default::default())
+            }
+            MESH_TEX_COORDS_PORTABLE => {
+                Scheme::TexCoordsPortable(mesh, needs_positions(positions)?, Vec::new())
+            }
+            MESH_MULTI_PARALLELOGRAM | MESH_TEX_COORDS_DEPRECATED => {
+                return err("deprecated prediction scheme");
+            }
+            _ => Scheme::Delta,
+        })
+    }
+
+    /// Whether the scheme reads a parent attribute.
+    pub fn uses_positions(method: i8) -> bool {
+        matches!(method, MESH_TEX_COORDS_PORTABLE | MESH_GEOMETRIC_NORMAL)
+    }
+
+    pub fn decode_data<'b: 'a>(
+        &mut self,
+        transform: &mut Transform,
+        buffer: &mut Buffer<'b>,
+    ) -> Result<()> {
+        match self {
+            Scheme::ConstrainedMulti(mesh, creases) => {
+                let num_corners = mesh.table.num_faces() * 3;
+                for flags in creases.iter_mut() {
+                    let num_flags = buffer.varint_u32()? as usize;
+                    if num_flags > num_corners {
+                        return err("too many crease flags");
+                    }
+                    if num_flags > 0 {
+                        let mut decoder = RAnsBitDecoder::start(buffer)?;
+                        *flags = (0..num_flags).map(|_| decoder.decode_bit()).collect();
+                    }
+                }
+                transform.decode_data(buffer)
+            }
+            Scheme::TexCoordsPortable(_, _, orientations) => {
+                let num_orientations = buffer.i32()?;
+                if num_orientations < 0 {
+                    return err("invalid orientation count");
+                }
+                let mut decoder = RAnsBitDecoder::start(buffer)?;
+                let mut last = true;
+                *orientations = (0..num_orientations)
+                    .map(|_| {
+                        if !decoder.decode_bit() {
+                            last = !last;
+                        }
+                        last
+                    })
+                    .collect();
+                transform.decode_data(buffer)
+            }
+            Scheme::GeometricNormal(_, _, flips) => {
+                transform.decode_data(buffer)?;
+                *flips = RAnsBitDecoder::start(buffer)?;
+                Ok(())
+            }
+            Scheme::Delta | Scheme::Parallelogram(_) => transform.decode_data(buffer),
+        }
+    }
+
+    /// Reverts the prediction in place; `data` holds corrections on input.
+    pub fn compute(&mut self, transform: &Transform, data: &mut [i32], comps: usize) -> Result<()> {
+        let zero: Values = SmallVec::from_elem(0, comps);
+        match self {
+            Scheme::Delta => {
+                transform.compute(&zero, &mut data[..comps]);
+                for i in (comps..data.len()).step_by(comps) {
+                    let pred: Values = data[i - comps..i].into();
+                    transform.compute(&pred, &mut data[i..i + comps]);
+                }
+            }
+            Scheme::Parallelogram(mesh) => {
+                transform.compute(&zero, &mut data[..comps]);
+                for p in 1..data.len() / comps {
+                    let corner = mesh.corner_of_value(p)?;
+                    let pred = match parallelogram(mesh, p as i32, corner, data, comps)? {
+                        Some(pred) => pred,
+                        None => data[(p - 1) * comps..p * comps].into(),
+                    };
+                    transform.compute(&pred, &mut data[p * comps..(p + 1) * comps]);
+                }
+            }
+            Scheme::ConstrainedMulti(mesh, creases) => {
+                transform.compute(&zero, &mut data[..comps]);
+                let mut crease_pos = [0usize; 4];
+                for p in 1..data.len() / comps {
+                    let start = mesh.corner_of_value(p)?;
+                    let mut preds: SmallVec<[Values; 4]> = SmallVec::new();
+                    let mut corner = start;
+                    let mut first_pass = true;
+                    while corner != INVALID {
+                        if let Some(pred) = parallelogram(mesh, p as i32, corner, data, comps)? {
+                            preds.push(pred);
+                            if preds.len() == 4 {
+                                break;
+                            }
+                        }
+                        corner = if first_pass {
+                            mesh.table.swing_left(corner)
+                        } else {
+                            mesh.table.swing_right(corner)
+                        };
+                        if corner == start {
+                            break;
+                        }
+                        if corner == INVALID && first_pass {
+                            first_pass = false;
+                            corner = mesh.table.swing_right(start);
+                        }
+                    }
+
+                    let mut used = 0;
+                    let mut multi = zero.clone();
+                    if !preds.is_empty() {
+                        let context = preds.len() - 1;
+                        for pred in &preds {
+                            let pos = crease_pos[context];
+                            crease_pos[context] += 1;
+                            let Some(&is_crease) = creases[context].get(pos) else {
+                                return err("missing crease flag");
+                            };
+                            if !is_crease {
+                                used += 1;
+                                for (m, v) in multi.iter_mut().zip(pred) {
+                                    *m = m.wrapping_add(*v);
+                                }
+                            }
+                        }
+                    }
+                    let pred: Values = if used == 0 {
+                        data[(p - 1) * comps..p * comps].into()
+                    } else {
+                        multi.iter().map(|m| m / used).collect()
+                    };
+                    transform.compute(&pred, &mut data[p * comps..(p + 1) * comps]);
+                }
+            }
+            Scheme::TexCoordsPortable(mesh, positions, orientations) => {
+                if comps != 2 {
+                    return err("texture coordinate prediction needs two components");
+                }
+                for p in 0..data.len() / 2 {
+                    let corner = mesh.corner_of_value(p)?;
+                    let pred =
+                        predict_tex_coord(mesh, positions, orientations, corner, data, p as i32)?;
+                    transform.compute(&pred, &mut data[p * 2..p * 2 + 2]);
+                }
+            }
+            Scheme::GeometricNormal(mesh, positions, flips) => {
+                let Transform::Canonicalized(toolbox) = transform else {
+                    return err("geometric normal prediction needs the octahedron transform");
+                };
+                let toolbox = OctahedronToolBox::new(toolbox.quantization_bits)?;
+                if data.len() < mesh.value_to_corner.len() * 2 {
+                    return err("normal prediction entries out of range");
+                }
+                for id in 0..mesh.value_to_corner.len() {
+                    let corner = mesh.value_to_corner[id];
+                    let mut normal = predict_normal(mesh, positions, corner)?;
+                    toolbox.canonicalize_integer_vector(&mut normal);
+                    if flips.decode_bit() {
+                        normal = normal.map(i32::wrapping_neg);
+                    }
+                    let (s, t) = toolbox.integer_vector_to_octahedral(&normal);
+                    transform.compute(&[s, t], &mut data[id * 2..id * 2 + 2]);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parallelogram(
+    mesh: &MeshData,
+    entry: i32,
+    corner: u32,
+    data: &[i32],
+    comps: usize,
+) -> Result<Option<Values>> {
+    let oci = mesh.table.opposite(corner);
+    if oci == INVALID {
+        return Ok(None);
+    }
+    let opp = mesh.value_of_corner(oci)?;
+    let next = mesh.value_of_corner(mesh.table.next(oci))?;
+    let prev = mesh.value_of_corner(mesh.table.previous(oci))?;
+    if opp >= entry || next >= entry || prev >= entry {
+        return Ok(None);
+    }
+    let (opp, next, prev) = (
+        opp as usize * comps,
+        next as usize * comps,
+        prev as usize * comps,
+    );
+    Ok(Some(
+        (0..comps)
+            .map(|c| (data[next + c] as i64 + data[prev + c] as i64 - data[opp + c] as i64) as i32)
+            .collect(),
+    ))
+}
+
+fn predict_tex_coord(
+    mesh: &MeshData,
+    positions: &Positions,
+    orientations: &mut Vec<bool>,
+    corner: u32,
+    data: &[i32],
+    entry: i32,
+) -> Result<Values> {
+    let next_entry = mesh.value_of_corner(mesh.table.next(corner))?;
+    let prev_entry = mesh.value_of_corner(mesh.table.previous(corner))?;
+    let uv = |e: i32| [data[e as usize * 2] as i64, data[e as usize * 2 + 1] as i64];
+
+    if prev_entry < entry && next_entry < entry {
+        let n_uv = uv(next_entry);
+        let p_uv = uv(prev_entry);
+        if p_uv == n_uv {
+            return Ok(SmallVec::from_slice(&[p_uv[0] as i32, p_uv[1] as i32]));
+        }
+        let tip = positions.for_entry(entry)?;
+        let next_pos = positions.for_entry(next_entry)?;
+        let prev_pos = positions.for_entry(prev_entry)?;
+        let pn = sub3(prev_pos, next_pos);
+        let pn_norm2 = dot3(pn, pn) as u64;
+        if let Some(n_uv_limit) = (i64::MAX as u64).checked_div(pn_norm2) {
+            let cn = sub3(tip, next_pos);
+            let cn_dot_pn = dot3(pn, cn);
+            let pn_uv = [p_uv[0].wrapping_sub(n_uv[0]), p_uv[1].wrapping_sub(n_uv[1])];
+
+            let n_uv_absmax = n_uv[0].wrapping_abs().max(n_uv[1].wrapping_abs());
+            if n_uv_absmax as u64 > n_uv_limit {
+                return err("texture coordinate prediction overflow");
+            }
+            let pn_uv_absmax = pn_uv[0].wrapping_abs().max(pn_uv[1].wrapping_abs());
+            if cn_dot_pn.wrapping_abs() > i64::MAX / pn_uv_absmax {
+                return err("texture coordinate prediction overflow");
+            }
+            let norm = pn_norm2 as i64;
+            let x_uv = [0, 1].map(|i| {
+                n_uv[i]
+                    .wrapping_mul(norm)
+                    .wrapping_add(cn_dot_pn.wrapping_mul(pn_uv[i]))
+            });
+            let pn_absmax = pn[0]
+                .wrapping_abs()
+                .max(pn[1].wrapping_abs())
+                .max(pn[2].wrapping_abs());
+            if cn_dot_pn.wrapping_abs() > i64::MAX / pn_absmax {
+                return err("texture coordinate prediction overflow");
+            }
+            let x_pos =
+                [0, 1, 2].map(|i| next_pos[i].wrapping_add(cn_dot_pn.wrapping_mul(pn[i]) / norm));
+            let cx = sub3(tip, x_pos);
+            let cx_norm2 = dot3(cx, cx) as u64;
+            let norm_squared = int_sqrt(cx_norm2.wrapping_mul(pn_norm2)) as i64;
+            let cx_uv = [
+                pn_uv[1].wrapping_mul(norm_squared),
+                pn_uv[0].wrapping_neg().wrapping_mul(norm_squared),
+            ];
+
+            let Some(orientation) = orientations.pop() else {
+                return err("ran out of texture coordinate orientations");
+            };
+            let predicted = [0, 1].map(|i| {
+                let v = if orientation {
+                    (x_uv[i] as u64).wrapping_add(cx_uv[i] as u64)
+                } else {
+                    (x_uv[i] as u64).wrapping_sub(cx_uv[i] as u64)
+                };
+                ((v as i64).wrapping_div(norm)) as i32
+            });
+            return Ok(SmallVec::from_slice(&predicted));
+        }
+    }
+
+    let offset = if next_entry < entry {
+        next_entry
+    } else if entry > 0 {
+        entry - 1
+    } else {
+        return Ok(SmallVec::from_slice(&[0, 0]));
+    };
+    let [u, v] = uv(offset);
+    Ok(SmallVec::from_slice(&[u as i32, v as i32]))
+}
+
+/// Sums the area-weighted face normals around the corner's vertex.
+fn predict_normal(mesh: &MeshData, positions: &Positions, corner: u32) -> Result<[i32; 3]> {
+    let position = |c: u32| positions.for_entry(mesh.value_of_corner(c)?);
+    let center = position(corner)?;
+    let mut normal = [0i64; 3];
+    for c in vertex_corners(&mesh.table, corner) {
+        let delta_next = sub3(position(mesh.table.next(c))?, center);
+        let delta_prev = sub3(position(mesh.table.previous(c))?, center);
+        let cross = cross3(delta_next, delta_prev);
+        for i in 0..3 {
+            normal[i] = normal[i].wrapping_add(cross[i]);
+        }
+    }
+
+    const UPPER_BOUND: i64 = 1 << 29;
+    let mut abs_sum = 0i64;
+    for v in normal {
+        let abs = v.wrapping_abs();
+        if abs_sum > i64::MAX - abs {
+            abs_sum = i64::MAX;
+            break;
+        }
+        abs_sum += abs;
+    }
+    if abs_sum > UPPER_BOUND {
+        let quotient = abs_sum / UPPER_BOUND;
+        normal = normal.map(|v| v / quotient);
+    }
+    Ok(normal.map(|v| v as i32))
+}
+
+fn sub3(a: [i64; 3], b: [i64; 3]) -> [i64; 3] {
+    [0, 1, 2].map(|i| a[i].wrapping_sub(b[i]))
+}
+
+fn dot3(a: [i64; 3], b: [i64; 3]) -> i64 {
+    (0..3).fold(0i64, |acc, i| acc.wrapping_add(a[i].wrapping_mul(b[i])))
+}
+
+fn cross3(u: [i64; 3], v: [i64; 3]) -> [i64; 3] {
+    [
+        u[1].wrapping_mul(v[2])
+            .wrapping_sub(u[2].wrapping_mul(v[1])),
+        u[2].wrapping_mul(v[0])
+            .wrapping_sub(u[0].wrapping_mul(v[2])),
+        u[0].wrapping_mul(v[1])
+            .wrapping_sub(u[1].wrapping_mul(v[0])),
+    ]
+}
+
+fn int_sqrt(number: u64) -> u64 {
+    if number == 0 {
+        return 0;
+    }
+    let mut act = number;
+    let mut root = 1u64;
+    while act >= 2 {
+        root *= 2;
+        act /= 4;
+    }
+    loop {
+        root = (root + number / root) / 2;
+        if root.wrapping_mul(root) <= number {
+            return root;
+        }
+    }
+}
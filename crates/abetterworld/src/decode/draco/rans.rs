@@ -0,0 +1,247 @@
+use super::{buffer::Buffer, err, Result};
+
+const IO_BASE: u32 = 256;
+const BIT_L_BASE: u32 = 4096;
+
+/// Shared state of the reverse-order ANS readers.
+#[derive(Clone, Default)]
+struct AnsState<'a> {
+    buf: &'a [u8],
+    offset: usize,
+    state: u32,
+}
+
+impl<'a> AnsState<'a> {
+    /// `ans_read_init` / `RAnsDecoder::read_init`. The two-bit prefix of the
+    /// last byte tells how many bytes hold the initial state; the four byte
+    /// form is only valid for the symbol coder.
+    fn init(buf: &'a [u8], l_base: u32, allow_long: bool) -> Result<Self> {
+        let Some(&last) = buf.last() else {
+            return err("empty ans stream");
+        };
+        let offset = buf.len();
+        let (len, mask) = match last >> 6 {
+            0 => (1, 0x3f),
+            1 => (2, 0x3fff),
+            2 => (3, 0x3f_ffff),
+            _ if allow_long => (4, 0x3fff_ffff),
+            _ => return err("invalid ans state prefix"),
+        };
+        if offset < len {
+            return err("truncated ans state");
+        }
+        let mut raw = [0u8; 4];
+        raw[..len].copy_from_slice(&buf[offset - len..]);
+        let state = (u32::from_le_bytes(raw) & mask) + l_base;
+        if state >= l_base * IO_BASE {
+            return err("invalid ans state");
+        }
+        Ok(Self {
+            buf,
+            offset: offset - len,
+            state,
+        })
+    }
+
+    fn pull(&mut self) {
+        self.offset -= 1;
+        self.state = self.state * IO_BASE + self.buf[self.offset] as u32;
+    }
+}
+
+/// Binary rANS decoder with a fixed probability of zero (`RAnsBitDecoder`).
+#[derive(Clone, Default)]
+pub(super) struct RAnsBitDecoder<'a> {
+    prob_zero: u8,
+    ans: AnsState<'a>,
+}
+
+impl<'a> RAnsBitDecoder<'a> {
+    pub fn start(buffer: &mut Buffer<'a>) -> Result<Self> {
+        let prob_zero = buffer.u8()?;
+        let size = buffer.varint_u32()? as usize;
+        if size > buffer.remaining() {
+            return err("bit decoder size exceeds buffer");
+        }
+        let ans = AnsState::init(&buffer.head()[..size], BIT_L_BASE, false)?;
+        buffer.advance(size)?;
+        Ok(Self { prob_zero, ans })
+    }
+
+    pub fn decode_bit(&mut self) -> bool {
+        let p = 256 - self.prob_zero as u32;
+        if self.ans.state < BIT_L_BASE && self.ans.offset > 0 {
+            self.ans.pull();
+        }
+        let x = self.ans.state;
+        let quot = x / 256;
+        let rem = x % 256;
+        let xn = quot.wrapping_mul(p);
+        let val = rem < p;
+        self.ans.state = if val {
+            xn.wrapping_add(rem)
+        } else {
+            x.wrapping_sub(xn).wrapping_sub(p)
+        };
+        val
+    }
+}
+
+/// Multi-symbol rANS decoder (`RAnsSymbolDecoder`). The precision depends on
+/// the maximum bit length of the coded symbols.
+struct RAnsSymbolDecoder<'a> {
+    precision: u32,
+    l_base: u32,
+    probs: Vec<u32>,
+    cum_probs: Vec<u32>,
+    lut: Vec<u32>,
+    ans: AnsState<'a>,
+}
+
+impl<'a> RAnsSymbolDecoder<'a> {
+    fn create(buffer: &mut Buffer<'a>, symbol_bit_length: u32) -> Result<Self> {
+        let precision_bits = ((3 * symbol_bit_length) / 2).clamp(12, 20);
+        let precision = 1u32 << precision_bits;
+        let num_symbols = buffer.varint_u32()? as usize;
+        if num_symbols / 64 > buffer.remaining() {
+            return err("too many rans symbols");
+        }
+        let mut probs = vec![0u32; num_symbols];
+        let mut i = 0;
+        while i < num_symbols {
+            let prob_data = buffer.u8()?;
+            let token = prob_data & 3;
+            if token == 3 {
+                let offset = (prob_data >> 2) as usize;
+                if i + offset >= num_symbols {
+                    return err("rans zero run out of range");
+                }
+                i += offset;
+            } else {
+                let mut prob = (prob_data >> 2) as u32;
+                for b in 0..token as u32 {
+                    prob |= (buffer.u8()? as u32) << (8 * (b + 1) - 2);
+                }
+                probs[i] = prob;
+            }
+            i += 1;
+        }
+
+        let mut decoder = Self {
+            precision,
+            l_base: precision * 4,
+            cum_probs: Vec::with_capacity(num_symbols),
+            lut: Vec::new(),
+            probs,
+            ans: AnsState::default(),
+        };
+        if num_symbols > 0 {
+            decoder.build_lut()?;
+        }
+        Ok(decoder)
+    }
+
+    fn build_lut(&mut self) -> Result<()> {
+        self.lut = vec![0; self.precision as usize];
+        let mut cum = 0u32;
+        for (i, &prob) in self.probs.iter().enumerate() {
+            self.cum_probs.push(cum);
+            let next = cum.wrapping_add(prob);
+            if next > self.precision || next < cum {
+                return err("rans probabilities exceed precision");
+            }
+            self.lut[cum as usize..next as usize].fill(i as u32);
+            cum = next;
+        }
+        if cum != self.precision {
+            return err("rans probabilities do not sum to precision");
+        }
+        Ok(())
+    }
+
+    fn num_symbols(&self) -> usize {
+        self.probs.len()
+    }
+
+    fn start(&mut self, buffer: &mut Buffer<'a>) -> Result<()> {
+        let size = buffer.varint_u64()?;
+        if size > buffer.remaining() as u64 {
+            return err("rans data exceeds buffer");
+        }
+        let size = size as usize;
+        let data = &buffer.head()[..size];
+        buffer.advance(size)?;
+        self.ans = AnsState::init(data, self.l_base, true)?;
+        Ok(())
+    }
+
+    fn decode_symbol(&mut self) -> u32 {
+        while self.ans.state < self.l_base && self.ans.offset > 0 {
+            self.ans.pull();
+        }
+        let quo = self.ans.state / self.precision;
+        let rem = self.ans.state % self.precision;
+        let sym = self.lut[rem as usize] as usize;
+        self.ans.state = quo
+            .wrapping_mul(self.probs[sym])
+            .wrapping_add(rem)
+            .wrapping_sub(self.cum_probs[sym]);
+        sym as u32
+    }
+}
+
+/// Decodes `num_values` unsigned symbols written by `EncodeSymbols`, using
+/// either the tagged or the raw rANS scheme.
+pub(super) fn decode_symbols(
+    num_values: usize,
+    num_components: usize,
+    buffer: &mut Buffer,
+) -> Result<Vec<u32>> {
+    if num_values == 0 {
+        return Ok(Vec::new());
+    }
+    match buffer.u8()? {
+        0 => decode_tagged_symbols(num_values, num_components.max(1), buffer),
+        1 => decode_raw_symbols(num_values, buffer),
+        _ => err("unknown symbol coding scheme"),
+    }
+}
+
+fn decode_tagged_symbols(
+    num_values: usize,
+    num_components: usize,
+    buffer: &mut Buffer,
+) -> Result<Vec<u32>> {
+    let mut tags = RAnsSymbolDecoder::create(buffer, 5)?;
+    tags.start(buffer)?;
+    if tags.num_symbols() == 0 {
+        return err("tagged symbols without a tag table");
+    }
+    buffer.start_bits(false)?;
+    let mut out = Vec::with_capacity(num_values);
+    while out.len() < num_values {
+        let bit_length = tags.decode_symbol();
+        if bit_length > 32 {
+            return err("tagged symbol too long");
+        }
+        for _ in 0..num_components {
+            out.push(buffer.bits(bit_length)?);
+        }
+    }
+    out.truncate(num_values);
+    buffer.end_bits();
+    Ok(out)
+}
+
+fn decode_raw_symbols(num_values: usize, buffer: &mut Buffer) -> Result<Vec<u32>> {
+    let max_bit_length = buffer.u8()? as u32;
+    if !(1..=18).contains(&max_bit_length) {
+        return err("invalid raw symbol bit length");
+    }
+    let mut decoder = RAnsSymbolDecoder::create(buffer, max_bit_length)?;
+    if decoder.num_symbols() == 0 {
+        return err("raw symbols without a symbol table");
+    }
+    decoder.start(buffer)?;
+    Ok((0..num_values).map(|_| decoder.decode_symbol()).collect())
+}
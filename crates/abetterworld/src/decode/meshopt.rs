@@ -0,0 +1,594 @@
+//! Decoder for `EXT_meshopt_compression` buffer views. Compressed views are
+//! expanded into plain interleaved data up front so the regular accessor
+//! readers never see the encoded bytes.
+
+use serde_json::Value;
+
+use crate::helpers::AbwError;
+
+type Result<T> = std::result::Result<T, AbwError>;
+
+fn err<T>(msg: &str) -> Result<T> {
+    Err(AbwError::TileLoading(format!("meshopt: {msg}")))
+}
+
+const EXTENSION: &str = "EXT_meshopt_compression";
+
+const VERTEX_HEADER: u8 = 0xa0;
+const INDEX_HEADER: u8 = 0xe0;
+const SEQUENCE_HEADER: u8 = 0xd0;
+
+const VERTEX_BLOCK_SIZE_BYTES: usize = 8192;
+const VERTEX_BLOCK_MAX_SIZE: usize = 256;
+const BYTE_GROUP_SIZE: usize = 16;
+const BYTE_GROUP_DECODE_LIMIT: usize = 24;
+const TAIL_MAX_SIZE: usize = 32;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Mode {
+    Attributes,
+    Triangles,
+    Indices,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Filter {
+    None,
+    Octahedral,
+    Quaternion,
+    Exponential,
+}
+
+/// Rewrites every compressed buffer view so it points at decoded data
+/// appended to the end of `bin`. Returns `None` when nothing is compressed,
+/// letting callers keep borrowing the original JSON and buffer.
+pub(crate) fn decompress_buffer_views(
+    json: &Value,
+    bin: &[u8],
+) -> Result<Option<(Value, Vec<u8>)>> {
+    let Some(views) = json.get("bufferViews").and_then(|v| v.as_array()) else {
+        return Ok(None);
+    };
+    if !views.iter().any(|view| {
+        view.get("extensions")
+            .and_then(|e| e.get(EXTENSION))
+            .is_some()
+    }) {
+        return Ok(None);
+    }
+
+    let mut json = json.clone();
+    let mut out = bin.to_vec();
+    let views = json["bufferViews"].as_array_mut().unwrap();
+
+    for view in views.iter_mut() {
+        let Some(ext) = view.get("extensions").and_then(|e| e.get(EXTENSION)) else {
+            continue;
+        };
+
+        let field = |name: &str| ext.get(name).and_then(|v| v.as_u64()).map(|v| v as usize);
+        let (Some(length), Some(stride), Some(count)) =
+            (field("byteLength"), field("byteStride"), field("count"))
+        else {
+            return err("compressed view without byteLength, byteStride or count");
+        };
+        let offset = field("byteOffset").unwrap_or(0);
+        let Some(source) = offset
+            .checked_add(length)
+            .and_then(|end| bin.get(offset..end))
+        else {
+            return err("compressed view outside the binary chunk");
+        };
+
+        let mode = match ext.get("mode").and_then(|v| v.as_str()) {
+            Some("ATTRIBUTES") => Mode::Attributes,
+            Some("TRIANGLES") => Mode::Triangles,
+            Some("INDICES") => Mode::Indices,
+            _ => return err("unknown compression mode"),
+        };
+        let filter = match ext.get("filter").and_then(|v| v.as_str()).unwrap_or("NONE") {
+            "NONE" => Filter::None,
+            "OCTAHEDRAL" => Filter::Octahedral,
+            "QUATERNION" => Filter::Quaternion,
+            "EXPONENTIAL" => Filter::Exponential,
+            _ => return err("unknown filter"),
+        };
+        let buffer = ext.get("buffer").cloned().unwrap_or(Value::from(0));
+
+        let decoded = decompress(source, count, stride, mode, filter)?;
+
+        // Keep the decoded view 4-byte aligned like any other glTF view.
+        out.resize(out.len().next_multiple_of(4), 0);
+        let view_offset = out.len();
+        out.extend_from_slice(&decoded);
+
+        let view = view.as_object_mut().unwrap();
+        view.insert("buffer".into(), buffer);
+        view.insert("byteOffset".into(), Value::from(view_offset));
+        view.insert("byteLength".into(), Value::from(decoded.len()));
+        if mode == Mode::Attributes {
+            view.insert("byteStride".into(), Value::from(stride));
+        }
+        if let Some(extensions) = view.get_mut("extensions").and_then(|e| e.as_object_mut()) {
+            extensions.remove(EXTENSION);
+        }
+    }
+
+    Ok(Some((json, out)))
+}
+
+/// Decodes one compressed stream into `count * stride` bytes.
+pub(crate) fn decompress(
+    data: &[u8],
+    count: usize,
+    stride: usize,
+    mode: Mode,
+    filter: Filter,
+) -> Result<Vec<u8>> {
+    let Some(size) = count.checked_mul(stride) else {
+        return err("decoded view too large");
+    };
+    // The count comes from the JSON, so check the stream could hold that
+    // much before allocating for it. Attribute blocks spend at least two
+    // bits per group of 16 on every byte of the stride; index codecs at
+    // least a byte per triangle or index.
+    let encodable = match mode {
+        Mode::Attributes => {
+            let blocks = data.len().saturating_sub(1 + TAIL_MAX_SIZE.max(stride));
+            blocks / stride.max(1) * BYTE_GROUP_SIZE * 4
+        }
+        Mode::Triangles => data.len().saturating_sub(1 + 16) * 3 + 2,
+        Mode::Indices => data.len().saturating_sub(1 + 4),
+    };
+    if count > encodable {
+        return err("count exceeds what the stream can encode");
+    }
+    let mut out = vec![0u8; size];
+    match mode {
+        Mode::Attributes => decode_vertex_buffer(&mut out, count, stride, data)?,
+        Mode::Triangles => decode_index_buffer(&mut out, count, stride, data)?,
+        Mode::Indices => decode_index_sequence(&mut out, count, stride, data)?,
+    }
+    match filter {
+        Filter::None => {}
+        Filter::Octahedral => match stride {
+            4 => filter_oct::<i8>(&mut out),
+            8 => filter_oct::<i16>(&mut out),
+            _ => return err("octahedral filter needs a stride of 4 or 8"),
+        },
+        Filter::Quaternion if stride == 8 => filter_quat(&mut out),
+        Filter::Quaternion => return err("quaternion filter needs a stride of 8"),
+        Filter::Exponential if stride.is_multiple_of(4) => filter_exp(&mut out),
+        Filter::Exponential => return err("exponential filter needs a stride multiple of 4"),
+    }
+    Ok(out)
+}
+
+// --- attribute codec ---
+
+fn decode_vertex_buffer(out: &mut [u8], count: usize, stride: usize, data: &[u8]) -> Result<()> {
+    if stride == 0 || !stride.is_multiple_of(4) || stride > 256 {
+        return err("invalid vertex stride");
+    }
+    if data.len() < 1 + stride {
+        return err("truncated vertex buffer");
+    }
+    match data[0] {
+        VERTEX_HEADER => {}
+        h if h & 0xf0 == VERTEX_HEADER => return err("unsupported vertex codec version"),
+        _ => return err("not a vertex buffer"),
+    }
+
+    let mut last_vertex = [0u8; 256];
+    last_vertex[..stride].copy_from_slice(&data[data.len() - stride..]);
+
+    let block_size =
+        ((VERTEX_BLOCK_SIZE_BYTES / stride) & !(BYTE_GROUP_SIZE - 1)).min(VERTEX_BLOCK_MAX_SIZE);
+
+    let mut pos = 1;
+    let mut offset = 0;
+    while offset < count {
+        let block = block_size.min(count - offset);
+        pos = decode_vertex_block(
+            data,
+            pos,
+            &mut out[offset * stride..(offset + block) * stride],
+            block,
+            stride,
+            &mut last_vertex,
+        )?;
+        offset += block;
+    }
+
+    if data.len() - pos != TAIL_MAX_SIZE.max(stride) {
+        return err("vertex buffer has trailing data");
+    }
+    Ok(())
+}
+
+fn decode_vertex_block(
+    data: &[u8],
+    mut pos: usize,
+    out: &mut [u8],
+    count: usize,
+    stride: usize,
+    last_vertex: &mut [u8; 256],
+) -> Result<usize> {
+    let aligned = count.next_multiple_of(BYTE_GROUP_SIZE);
+    let mut buffer = [0u8; VERTEX_BLOCK_MAX_SIZE];
+
+    for k in 0..stride {
+        pos = decode_bytes(data, pos, &mut buffer[..aligned])?;
+
+        let mut p = last_vertex[k];
+        for (i, &b) in buffer[..count].iter().enumerate() {
+            let v = unzigzag8(b).wrapping_add(p);
+            out[i * stride + k] = v;
+            p = v;
+        }
+    }
+
+    last_vertex[..stride].copy_from_slice(&out[(count - 1) * stride..count * stride]);
+    Ok(pos)
+}
+
+fn decode_bytes(data: &[u8], mut pos: usize, buffer: &mut [u8]) -> Result<usize> {
+    let header_size = (buffer.len() / BYTE_GROUP_SIZE).div_ceil(4);
+    if data.len() - pos < header_size {
+        return err("truncated byte group header");
+    }
+    let header = pos;
+    pos += header_size;
+
+    for (g, group) in buffer.chunks_exact_mut(BYTE_GROUP_SIZE).enumerate() {
+        if data.len() - pos < BYTE_GROUP_DECODE_LIMIT {
+            return err("truncated byte group");
+        }
+        let bits = (data[header + g / 4] >> ((g % 4) * 2)) & 3;
+        pos = decode_bytes_group(data, pos, group, bits);
+    }
+    Ok(pos)
+}
+
+/// Each group stores 16 deltas as 0, 2, 4 or 8 bits; a value with every bit
+/// set is an escape for a full byte stored after the packed bits.
+fn decode_bytes_group(data: &[u8], pos: usize, group: &mut [u8], bits: u8) -> usize {
+    match bits {
+        0 => {
+            group.fill(0);
+            pos
+        }
+        3 => {
+            group.copy_from_slice(&data[pos..pos + BYTE_GROUP_SIZE]);
+            pos + BYTE_GROUP_SIZE
+        }
+        _ => {
+            let width = 1usize << bits;
+            let per_byte = 8 / width;
+            let mask = (1u8 << width) - 1;
+            let mut extra = pos + BYTE_GROUP_SIZE / per_byte;
+            for (i, out) in group.iter_mut().enumerate() {
+                let byte = data[pos + i / per_byte];
+                let shift = 8 - width * (i % per_byte + 1);
+                let v = (byte >> shift) & mask;
+                *out = if v == mask {
+                    extra += 1;
+                    data[extra - 1]
+                } else {
+                    v
+                };
+            }
+            extra
+        }
+    }
+}
+
+fn unzigzag8(v: u8) -> u8 {
+    (v & 1).wrapping_neg() ^ (v >> 1)
+}
+
+// --- index codecs ---
+
+fn write_index(out: &mut [u8], i: usize, stride: usize, value: u32) {
+    if stride == 2 {
+        out[i * 2..i * 2 + 2].copy_from_slice(&(value as u16).to_le_bytes());
+    } else {
+        out[i * 4..i * 4 + 4].copy_from_slice(&value.to_le_bytes());
+    }
+}
+
+fn decode_vbyte(data: &[u8], pos: &mut usize) -> u32 {
+    let mut result = 0u32;
+    let mut shift = 0;
+    for _ in 0..5 {
+        let group = data[*pos];
+        *pos += 1;
+        result |= ((group & 127) as u32).wrapping_shl(shift);
+        shift += 7;
+        if group < 128 {
+            break;
+        }
+    }
+    result
+}
+
+fn decode_index(data: &[u8], pos: &mut usize, last: u32) -> u32 {
+    let v = decode_vbyte(data, pos);
+    last.wrapping_add((v >> 1) ^ (v & 1).wrapping_neg())
+}
+
+struct Fifo {
+    edges: [(u32, u32); 16],
+    edge_offset: usize,
+    vertices: [u32; 16],
+    vertex_offset: usize,
+}
+
+impl Fifo {
+    fn edge(&self, back: usize) -> (u32, u32) {
+        self.edges[self.edge_offset.wrapping_sub(1 + back) & 15]
+    }
+
+    fn vertex(&self, back: usize) -> u32 {
+        self.vertices[self.vertex_offset.wrapping_sub(back) & 15]
+    }
+
+    fn push_edge(&mut self, a: u32, b: u32) {
+        self.edges[self.edge_offset] = (a, b);
+        self.edge_offset = (self.edge_offset + 1) & 15;
+    }
+
+    fn push_vertex(&mut self, v: u32, cond: bool) {
+        self.vertices[self.vertex_offset] = v;
+        self.vertex_offset = (self.vertex_offset + cond as usize) & 15;
+    }
+}
+
+/// Triangle lists: each triangle is a code byte that references recently
+/// seen edges and vertices, with free indices delta-encoded in a side stream.
+fn decode_index_buffer(out: &mut [u8], count: usize, stride: usize, data: &[u8]) -> Result<()> {
+    if !count.is_multiple_of(3) || !(stride == 2 || stride == 4) {
+        return err("invalid triangle index layout");
+    }
+    if data.len() < 1 + count / 3 + 16 {
+        return err("truncated index buffer");
+    }
+    let version = match data[0] {
+        h if h & 0xf0 == INDEX_HEADER && h & 0x0f <= 1 => h & 0x0f,
+        _ => return err("not an index buffer"),
+    };
+    let fec_max = if version >= 1 { 13 } else { 15 };
+
+    let mut fifo = Fifo {
+        edges: [(u32::MAX, u32::MAX); 16],
+        edge_offset: 0,
+        vertices: [u32::MAX; 16],
+        vertex_offset: 0,
+    };
+    let mut next = 0u32;
+    let mut last = 0u32;
+
+    let mut pos = 1 + count / 3;
+    let safe_end = data.len() - 16;
+    let codeaux_table = &data[safe_end..];
+
+    for i in (0..count).step_by(3) {
+        // Each triangle reads at most 16 bytes of side data.
+        if pos > safe_end {
+            return err("truncated index buffer");
+        }
+        let codetri = data[1 + i / 3];
+
+        let (a, b, c);
+        if codetri < 0xf0 {
+            let fe = (codetri >> 4) as usize;
+            (a, b) = fifo.edge(fe);
+            let fec = codetri & 15;
+            if fec < fec_max {
+                let fec0 = fec == 0;
+                c = if fec0 {
+                    next
+                } else {
+                    fifo.vertex(1 + fec as usize)
+                };
+                next += fec0 as u32;
+                fifo.push_vertex(c, fec0);
+            } else {
+                // 13 and 14 step the last free index by -1 and +1.
+                c = if fec != 15 {
+                    last.wrapping_add((fec as u32).wrapping_sub((fec ^ 3) as u32))
+                } else {
+                    decode_index(data, &mut pos, last)
+                };
+                last = c;
+                fifo.push_vertex(c, true);
+            }
+            fifo.push_edge(c, b);
+            fifo.push_edge(a, c);
+        } else {
+            let (fea, feb, fec);
+            if codetri < 0xfe {
+                let codeaux = codeaux_table[(codetri & 15) as usize];
+                (fea, feb, fec) = (0, codeaux >> 4, codeaux & 15);
+            } else {
+                let codeaux = data[pos];
+                pos += 1;
+                (fea, feb, fec) = (
+                    if codetri == 0xfe { 0 } else { 15 },
+                    codeaux >> 4,
+                    codeaux & 15,
+                );
+                // A fully "new" triangle never needs the slow path, so the
+                // encoder uses it to restart vertex numbering.
+                if codetri == 0xfe && codeaux == 0 {
+                    next = 0;
+                }
+            }
+
+            let mut take = |fe: u8, fifo: &Fifo| {
+                if fe == 0 {
+                    next += 1;
+                    next - 1
+                } else if fe == 15 {
+                    0
+                } else {
+                    fifo.vertex(fe as usize)
+                }
+            };
+            let mut va = take(fea, &fifo);
+            let mut vb = take(feb, &fifo);
+            let mut vc = take(fec, &fifo);
+            for (fe, v) in [(fea, &mut va), (feb, &mut vb), (fec, &mut vc)] {
+                if fe == 15 {
+                    last = decode_index(data, &mut pos, last);
+                    *v = last;
+                }
+            }
+            (a, b, c) = (va, vb, vc);
+
+            fifo.push_vertex(a, true);
+            fifo.push_vertex(b, feb == 0 || feb == 15);
+            fifo.push_vertex(c, fec == 0 || fec == 15);
+            fifo.push_edge(b, a);
+            fifo.push_edge(c, b);
+            fifo.push_edge(a, c);
+        }
+
+        write_index(out, i, stride, a);
+        write_index(out, i + 1, stride, b);
+        write_index(out, i + 2, stride, c);
+    }
+
+    if pos != safe_end {
+        return err("index buffer has trailing data");
+    }
+    Ok(())
+}
+
+/// Arbitrary index lists: zigzag deltas against one of two running
+/// baselines, selected by the low bit of each value.
+fn decode_index_sequence(out: &mut [u8], count: usize, stride: usize, data: &[u8]) -> Result<()> {
+    if !(stride == 2 || stride == 4) {
+        return err("invalid index stride");
+    }
+    if data.len() < 1 + count + 4 {
+        return err("truncated index sequence");
+    }
+    match data[0] {
+        h if h & 0xf0 == SEQUENCE_HEADER && h & 0x0f <= 1 => {}
+        _ => return err("not an index sequence"),
+    }
+
+    let safe_end = data.len() - 4;
+    let mut last = [0u32; 2];
+    let mut pos = 1;
+    for i in 0..count {
+        // Each index reads at most 5 bytes.
+        if pos >= safe_end {
+            return err("truncated index sequence");
+        }
+        let v = decode_vbyte(data, &mut pos);
+        let current = (v & 1) as usize;
+        let v = v >> 1;
+        let index = last[current].wrapping_add((v >> 1) ^ (v & 1).wrapping_neg());
+        last[current] = index;
+        write_index(out, i, stride, index);
+    }
+
+    if pos != safe_end {
+        return err("index sequence has trailing data");
+    }
+    Ok(())
+}
+
+// --- filters ---
+
+trait Component: Copy {
+    const MAX: f32;
+    const SIZE: usize;
+    fn read(bytes: &[u8]) -> f32;
+    fn write(bytes: &mut [u8], v: i32);
+}
+
+impl Component for i8 {
+    const MAX: f32 = 127.0;
+    const SIZE: usize = 1;
+    fn read(bytes: &[u8]) -> f32 {
+        bytes[0] as i8 as f32
+    }
+    fn write(bytes: &mut [u8], v: i32) {
+        bytes[0] = v as i8 as u8;
+    }
+}
+
+impl Component for i16 {
+    const MAX: f32 = 32767.0;
+    const SIZE: usize = 2;
+    fn read(bytes: &[u8]) -> f32 {
+        i16::from_le_bytes([bytes[0], bytes[1]]) as f32
+    }
+    fn write(bytes: &mut [u8], v: i32) {
+        bytes[..2].copy_from_slice(&(v as i16).to_le_bytes());
+    }
+}
+
+fn round(v: f32) -> i32 {
+    (v + if v >= 0.0 { 0.5 } else { -0.5 }) as i32
+}
+
+/// Octahedral unit vectors; the third component carries the encoded 1.0 so
+/// z can be rebuilt at the same precision.
+fn filter_oct<T: Component>(data: &mut [u8]) {
+    for element in data.chunks_exact_mut(4 * T::SIZE) {
+        let get = |i: usize| T::read(&element[i * T::SIZE..]);
+        let mut x = get(0);
+        let mut y = get(1);
+        let z = get(2) - x.abs() - y.abs();
+
+        let t = z.min(0.0);
+        x += if x >= 0.0 { t } else { -t };
+        y += if y >= 0.0 { t } else { -t };
+
+        let s = T::MAX / (x * x + y * y + z * z).sqrt();
+        for (i, v) in [x, y, z].into_iter().enumerate() {
+            T::write(&mut element[i * T::SIZE..], round(v * s));
+        }
+    }
+}
+
+/// Quaternions stored as three components plus the index of the dropped
+/// largest one, which is rebuilt from unit length.
+fn filter_quat(data: &mut [u8]) {
+    let scale = std::f32::consts::FRAC_1_SQRT_2;
+    for element in data.chunks_exact_mut(8) {
+        let get = |i: usize| i16::from_le_bytes([element[i * 2], element[i * 2 + 1]]);
+        let packed = get(3);
+        let ss = scale / (packed | 3) as f32;
+        let x = get(0) as f32 * ss;
+        let y = get(1) as f32 * ss;
+        let z = get(2) as f32 * ss;
+        let w = (1.0 - x * x - y * y - z * z).max(0.0).sqrt();
+
+        let qc = (packed & 3) as usize;
+        let values = [
+            (qc, (w * 32767.0 + 0.5) as i32),
+            ((qc + 1) & 3, round(x * 32767.0)),
+            ((qc + 2) & 3, round(y * 32767.0)),
+            ((qc + 3) & 3, round(z * 32767.0)),
+        ];
+        for (i, v) in values {
+            element[i * 2..i * 2 + 2].copy_from_slice(&(v as i16).to_le_bytes());
+        }
+    }
+}
+
+/// Floats stored as a 24-bit signed mantissa and an 8-bit signed exponent.
+fn filter_exp(data: &mut [u8]) {
+    for word in data.chunks_exact_mut(4) {
+        let v = u32::from_le_bytes([word[0], word[1], word[2], word[3]]);
+        let m = ((v << 8) as i32) >> 8;
+        let e = (v as i32) >> 24;
+        let f = f32::from_bits(((e + 127) as u32) << 23) * m as f32;
+        word.copy_from_slice(&f.to_le_bytes());
+    }
+}
@@ -1,13 +1,27 @@
 mod types;
-pub use types::{DecodedMesh, OwnedDecodedMesh, Vertex};
+#[cfg(not(feature = "rust-draco"))]
+pub use types::DecodedMesh;
+pub use types::{OwnedDecodedMesh, Vertex};
 
-#[cfg(not(target_arch = "wasm32"))]
+#[cfg(any(feature = "rust-draco", test))]
+pub(crate) mod draco;
+
+pub(crate) mod meshopt;
+
+pub(crate) mod basisu;
+
+#[cfg(all(not(feature = "rust-draco"), not(target_arch = "wasm32")))]
 mod native;
-#[cfg(target_arch = "wasm32")]
+#[cfg(feature = "rust-draco")]
+mod pure;
+#[cfg(all(not(feature = "rust-draco"), target_arch = "wasm32"))]
 mod wasm;
 
-#[cfg(target_arch = "wasm32")]
+#[cfg(feature = "rust-draco")]
+pub use pure::DracoClient;
+
+#[cfg(all(not(feature = "rust-draco"), target_arch = "wasm32"))]
 pub use wasm::DracoClient;
 
-#[cfg(not(target_arch = "wasm32"))]
+#[cfg(all(not(feature = "rust-draco"), not(target_arch = "wasm32")))]
 pub use native::DracoClient;
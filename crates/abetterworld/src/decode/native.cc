@@ -86,9 +86,8 @@ extern "C"
             // Color
             if (color_attr)
             {
-                float color[4] = {1.0f, 1.0f, 1.0f, 1.0f};
-                color_attr->GetValue(color_attr->mapped_index(i), color);
-                std::memcpy(v.color, color, sizeof(float) * color_attr->num_components());
+                // GetValue would copy the raw bytes of u8 colors into floats
+                color_attr->ConvertValue<float>(color_attr->mapped_index(i), 4, v.color);
                 if (color_attr->num_components() < 4)
                 {
                     v.color[3] = 1.0f; // opaque default
@@ -7,21 +7,7 @@ extern "C" {
 
 impl Drop for InnerDecodedMesh {
     fn drop(&mut self) {
-        if self.rust_owned {
-            // Reconstruct boxed slices so Rust frees the memory
-            unsafe {
-                let _ = Box::from_raw(std::slice::from_raw_parts_mut(
-                    self.data.vertices,
-                    self.data.vertex_count as usize,
-                ));
-                let _ = Box::from_raw(std::slice::from_raw_parts_mut(
-                    self.data.indices,
-                    self.data.index_count as usize,
-                ));
-            }
-        } else {
-            unsafe { free_decoded_mesh(&mut self.data) }
-        }
+        unsafe { free_decoded_mesh(&mut self.data) }
     }
 }
 
@@ -49,13 +35,7 @@ impl DracoClient {
                 ));
             }
 
-            Ok(OwnedDecodedMesh {
-                inner: std::sync::Arc::new(InnerDecodedMesh {
-                    data: mesh,
-                    rust_owned: false,
-                }),
-                material_index: None,
-            })
+            Ok(OwnedDecodedMesh::from_foreign(mesh))
         }
     }
 }
@@ -0,0 +1,19 @@
+use super::{draco, types::OwnedDecodedMesh};
+
+/// Draco decoding without the C++ library, enabled by the `rust-draco`
+/// feature. Meshes come back as owned vectors, with nothing to free by hand.
+pub struct DracoClient {}
+
+impl DracoClient {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    pub async fn decode(&self, data: &[u8]) -> Result<OwnedDecodedMesh, std::io::Error> {
+        let (vertices, indices) = draco::decode_mesh(data)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))?;
+        Ok(OwnedDecodedMesh::from_vertices_and_indices(
+            vertices, indices,
+        ))
+    }
+}
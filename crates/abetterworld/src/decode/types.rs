@@ -10,6 +10,7 @@ pub struct Vertex {
     pub texcoord1: [f32; 2],
 }
 
+#[cfg(not(feature = "rust-draco"))]
 #[repr(C)]
 #[derive(Clone, Debug, PartialEq)]
 pub struct DecodedMesh {
@@ -19,69 +20,69 @@ pub struct DecodedMesh {
     pub index_count: u32,
     pub job_id: u32,
 }
+#[cfg(not(feature = "rust-draco"))]
 unsafe impl Send for DecodedMesh {}
+#[cfg(not(feature = "rust-draco"))]
 unsafe impl Sync for DecodedMesh {}
 
-#[derive(Clone, Debug, PartialEq)]
+/// Buffers handed over by the C++ or JS Draco decoder.
+#[cfg(not(feature = "rust-draco"))]
+#[derive(Debug)]
 pub struct InnerDecodedMesh {
     pub data: DecodedMesh,
-    /// If true, vertices/indices were allocated by Rust (Vec) and must be
-    /// freed with Vec::from_raw_parts in Drop instead of the C FFI free.
-    pub rust_owned: bool,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Debug)]
+pub(crate) enum MeshBuffers {
+    /// Decoded or built in Rust.
+    Owned {
+        vertices: Vec<Vertex>,
+        indices: Vec<u32>,
+    },
+    #[cfg(not(feature = "rust-draco"))]
+    Foreign(InnerDecodedMesh),
+}
+
+#[derive(Clone, Debug)]
 pub struct OwnedDecodedMesh {
-    pub(crate) inner: Arc<InnerDecodedMesh>,
+    pub(crate) inner: Arc<MeshBuffers>,
     pub material_index: Option<usize>,
 }
 
 impl OwnedDecodedMesh {
     /// Create an OwnedDecodedMesh from Rust-owned vertex and index data.
     pub fn from_vertices_and_indices(vertices: Vec<Vertex>, indices: Vec<u32>) -> Self {
-        let vertex_count = vertices.len() as u32;
-        let index_count = indices.len() as u32;
-
-        // into_boxed_slice guarantees len == capacity, making Drop reconstruction safe
-        let mut vertices_box = vertices.into_boxed_slice();
-        let mut indices_box = indices.into_boxed_slice();
-
-        let vertices_ptr = vertices_box.as_mut_ptr();
-        let indices_ptr = indices_box.as_mut_ptr();
-
-        // Prevent deallocation — Drop will reconstruct and free
-        std::mem::forget(vertices_box);
-        std::mem::forget(indices_box);
+        OwnedDecodedMesh {
+            inner: Arc::new(MeshBuffers::Owned { vertices, indices }),
+            material_index: None,
+        }
+    }
 
+    /// Wraps buffers a foreign decoder allocated.
+    #[cfg(not(feature = "rust-draco"))]
+    pub(crate) fn from_foreign(data: DecodedMesh) -> Self {
         OwnedDecodedMesh {
-            inner: Arc::new(InnerDecodedMesh {
-                data: DecodedMesh {
-                    vertices: vertices_ptr,
-                    vertex_count,
-                    indices: indices_ptr,
-                    index_count,
-                    job_id: 0,
-                },
-                rust_owned: true,
-            }),
+            inner: Arc::new(MeshBuffers::Foreign(InnerDecodedMesh { data })),
             material_index: None,
         }
     }
 
     pub fn as_vertex_slice(&self) -> &[Vertex] {
-        unsafe {
-            std::slice::from_raw_parts(
-                self.inner.data.vertices,
-                self.inner.data.vertex_count as usize,
-            )
+        match &*self.inner {
+            MeshBuffers::Owned { vertices, .. } => vertices,
+            #[cfg(not(feature = "rust-draco"))]
+            MeshBuffers::Foreign(mesh) => unsafe {
+                std::slice::from_raw_parts(mesh.data.vertices, mesh.data.vertex_count as usize)
+            },
         }
     }
     pub fn as_index_slice(&self) -> &[u32] {
-        unsafe {
-            std::slice::from_raw_parts(
-                self.inner.data.indices,
-                self.inner.data.index_count as usize,
-            )
+        match &*self.inner {
+            MeshBuffers::Owned { indices, .. } => indices,
+            #[cfg(not(feature = "rust-draco"))]
+            MeshBuffers::Foreign(mesh) => unsafe {
+                std::slice::from_raw_parts(mesh.data.indices, mesh.data.index_count as usize)
+            },
         }
     }
 }
@@ -4,7 +4,7 @@ use tracing::{event, Level};
 use wasm_bindgen::prelude::*;
 use wasm_bindgen_futures::JsFuture;
 
-use crate::decode::types::{InnerDecodedMesh, Vertex};
+use crate::decode::types::Vertex;
 use crate::decode::{DecodedMesh, OwnedDecodedMesh};
 
 #[wasm_bindgen(module = "/js/dist/draco-client.js")]
@@ -161,13 +161,7 @@ impl DracoClient {
                     mesh.index_count
                 );
 
-                Ok(OwnedDecodedMesh {
-                    inner: std::sync::Arc::new(crate::decode::types::InnerDecodedMesh {
-                        data: mesh,
-                        rust_owned: false,
-                    }),
-                    material_index: None,
-                })
+                Ok(OwnedDecodedMesh::from_foreign(mesh))
             }
             Err(e) => Err(std::io::Error::new(
                 std::io::ErrorKind::Other,
@@ -177,24 +171,6 @@ impl DracoClient {
     }
 }
 
-impl Drop for InnerDecodedMesh {
-    fn drop(&mut self) {
-        if self.rust_owned {
-            unsafe {
-                let _ = Box::from_raw(std::slice::from_raw_parts_mut(
-                    self.data.vertices,
-                    self.data.vertex_count as usize,
-                ));
-                let _ = Box::from_raw(std::slice::from_raw_parts_mut(
-                    self.data.indices,
-                    self.data.index_count as usize,
-                ));
-            }
-        }
-        // Draco-decoded (rust_owned == false) memory was mem::forget'd — no cleanup needed
-    }
-}
-
 impl Drop for DracoClient {
     fn drop(&mut self) {
         self.inner.dispose();
@@ -154,10 +154,16 @@ impl Camera {
 
     pub fn set_viewport(&self, width: f64, height: f64) {
         if let Ok(mut state) = self.user_state.write() {
-            state.aspect = width as f64 / height as f64;
-            state.viewport_wh = (width, height);
+            if state.viewport_wh != (width, height) {
+                state.aspect = width / height;
+                state.viewport_wh = (width, height);
+                self.dirty.store(true, Ordering::Relaxed);
+            }
         }
-        self.dirty.store(true, Ordering::Relaxed);
+    }
+
+    pub fn viewport(&self) -> (f64, f64) {
+        self.user_state.read().unwrap().viewport_wh
     }
 
     pub fn set_position(&self, new_state: &PositionState) {
@@ -42,5 +42,10 @@ pub mod channel {
                 .try_recv()
                 .map_err(|_| AbwError::Paging("Failed to receive item".to_string()))
         }
+
+        /// Items waiting in the channel.
+        pub fn len(&self) -> usize {
+            self.inner.len()
+        }
     }
 }
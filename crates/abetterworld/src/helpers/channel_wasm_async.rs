@@ -53,5 +53,10 @@ pub mod channel {
         pub fn poll_next(&self) -> Option<T> {
             self.inner.try_recv().ok()
         }
+
+        /// Items waiting in the channel.
+        pub fn len(&self) -> usize {
+            self.inner.len()
+        }
     }
 }
@@ -19,6 +19,9 @@ pub use logging::*;
 pub mod frame_clock;
 pub use frame_clock::*;
 
+pub mod triangulate;
+pub use triangulate::*;
+
 #[cfg(target_arch = "wasm32")]
 mod channel_wasm_async;
 #[cfg(target_arch = "wasm32")]
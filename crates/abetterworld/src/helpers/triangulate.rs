@@ -0,0 +1,171 @@
+//! Ear clipping for polygons with holes. Each hole is bridged into the outer
+//! ring from its rightmost vertex, rightmost hole first, and ears are then cut
+//! from the single ring that leaves. Quadratic, which is plenty for overlays.
+
+type Point = [f64; 2];
+
+fn cross(o: Point, a: Point, b: Point) -> f64 {
+    (a[0] - o[0]) * (b[1] - o[1]) - (a[1] - o[1]) * (b[0] - o[0])
+}
+
+/// Twice the signed area, positive for counter-clockwise rings.
+pub fn signed_area(ring: &[Point]) -> f64 {
+    (0..ring.len())
+        .map(|i| {
+            let (a, b) = (ring[i], ring[(i + 1) % ring.len()]);
+            a[0] * b[1] - b[0] * a[1]
+        })
+        .sum()
+}
+
+/// Triangles covering `rings`, the outer boundary first and holes after it,
+/// as indices into the rings' points taken in order. Rings may wind either
+/// way and may repeat their first point at the end; the triangles always
+/// wind counter-clockwise. Degenerate rings give no triangles.
+pub fn triangulate(rings: &[Vec<Point>]) -> Vec<u32> {
+    let mut points = Vec::new();
+    let mut loops: Vec<Vec<usize>> = Vec::new();
+    for (r, ring) in rings.iter().enumerate() {
+        let start = points.len();
+        points.extend_from_slice(ring);
+        let mut len = ring.len();
+        if len > 1 && ring[0] == ring[len - 1] {
+            len -= 1;
+        }
+        if len < 3 {
+            if r == 0 {
+                return Vec::new();
+            }
+            continue;
+        }
+        let mut indices: Vec<usize> = (start..start + len).collect();
+        // outer ring counter-clockwise, holes clockwise
+        if (signed_area(&ring[..len]) > 0.0) != (r == 0) {
+            indices.reverse();
+        }
+        loops.push(indices);
+    }
+    if loops.is_empty() {
+        return Vec::new();
+    }
+
+    let mut holes = loops.split_off(1);
+    let mut ring = loops.pop().unwrap();
+    let rightmost = |hole: &[usize]| {
+        (0..hole.len())
+            .max_by(|&a, &b| points[hole[a]][0].total_cmp(&points[hole[b]][0]))
+            .unwrap()
+    };
+    holes.sort_by(|a, b| {
+        let (a, b) = (points[a[rightmost(a)]][0], points[b[rightmost(b)]][0]);
+        b.total_cmp(&a)
+    });
+    while !holes.is_empty() {
+        let hole = holes.remove(0);
+        let start = rightmost(&hole);
+        let Some(at) = find_bridge(&points, &ring, &holes, hole[start]) else {
+            continue;
+        };
+        // ring[at] → hole, all the way around, → back to ring[at]
+        let mut spliced = Vec::with_capacity(hole.len() + 2);
+        spliced.extend((0..=hole.len()).map(|k| hole[(start + k) % hole.len()]));
+        spliced.push(ring[at]);
+        ring.splice(at + 1..at + 1, spliced);
+    }
+
+    clip_ears(&points, ring)
+}
+
+/// Position in `ring` of the nearest vertex `from` can see, ignoring ring
+/// vertices whose corner `from` isn't inside.
+fn find_bridge(
+    points: &[Point],
+    ring: &[usize],
+    holes: &[Vec<usize>],
+    from: usize,
+) -> Option<usize> {
+    let m = points[from];
+    let n = ring.len();
+    let mut candidates: Vec<usize> = (0..n).collect();
+    candidates.sort_by(|&a, &b| {
+        let distance = |i: usize| {
+            let p = points[ring[i]];
+            (p[0] - m[0]).powi(2) + (p[1] - m[1]).powi(2)
+        };
+        distance(a).total_cmp(&distance(b))
+    });
+
+    let edges =
+        (0..n)
+            .map(|i| (ring[i], ring[(i + 1) % n]))
+            .chain(holes.iter().flat_map(|hole| {
+                (0..hole.len()).map(move |i| (hole[i], hole[(i + 1) % hole.len()]))
+            }))
+            .collect::<Vec<_>>();
+
+    candidates.into_iter().find(|&i| {
+        let v = points[ring[i]];
+        let (prev, next) = (points[ring[(i + n - 1) % n]], points[ring[(i + 1) % n]]);
+        locally_inside(prev, v, next, m)
+            && edges.iter().all(|&(a, b)| {
+                let (a, b) = (points[a], points[b]);
+                a == v || b == v || a == m || b == m || !segments_cross(m, v, a, b)
+            })
+    })
+}
+
+/// Whether `p` lies inside the corner at `v` of a counter-clockwise ring.
+fn locally_inside(prev: Point, v: Point, next: Point, p: Point) -> bool {
+    if cross(prev, v, next) >= 0.0 {
+        cross(v, next, p) >= 0.0 && cross(v, p, prev) >= 0.0
+    } else {
+        !(cross(v, prev, p) > 0.0 && cross(v, p, next) > 0.0)
+    }
+}
+
+fn segments_cross(p1: Point, p2: Point, q1: Point, q2: Point) -> bool {
+    let d1 = cross(p1, p2, q1);
+    let d2 = cross(p1, p2, q2);
+    let d3 = cross(q1, q2, p1);
+    let d4 = cross(q1, q2, p2);
+    (d1 > 0.0) != (d2 > 0.0) && (d3 > 0.0) != (d4 > 0.0) && d1 != 0.0 && d2 != 0.0
+}
+
+fn inside_triangle(a: Point, b: Point, c: Point, p: Point) -> bool {
+    cross(a, b, p) >= 0.0 && cross(b, c, p) >= 0.0 && cross(c, a, p) >= 0.0
+}
+
+fn clip_ears(points: &[Point], mut ring: Vec<usize>) -> Vec<u32> {
+    let mut triangles = Vec::with_capacity(ring.len().saturating_sub(2) * 3);
+    let mut i = 0;
+    // vertices looked at since the last cut; a whole lap without one means
+    // the rest is degenerate or self-intersecting
+    let mut stalled = 0;
+    while ring.len() >= 3 && stalled < ring.len() {
+        let n = ring.len();
+        let (ia, ib, ic) = (ring[(i + n - 1) % n], ring[i % n], ring[(i + 1) % n]);
+        let (a, b, c) = (points[ia], points[ib], points[ic]);
+        let turn = cross(a, b, c);
+        if turn == 0.0 {
+            // collinear or a doubled bridge point, no area to cover
+            ring.remove(i % n);
+            stalled = 0;
+            continue;
+        }
+        let is_ear = turn > 0.0
+            && ring.iter().all(|&j| {
+                let p = points[j];
+                p == a || p == b || p == c || !inside_triangle(a, b, c, p)
+            });
+        if is_ear {
+            triangles.extend([ia as u32, ib as u32, ic as u32]);
+            ring.remove(i % n);
+            stalled = 0;
+        } else {
+            i = (i % n) + 1;
+            stalled += 1;
+        }
+        i %= ring.len().max(1);
+    }
+    triangles
+}
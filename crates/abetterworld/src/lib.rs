@@ -9,10 +9,20 @@ mod world;
 #[cfg(test)]
 mod tests;
 
+pub use cache::{
+    CacheBackend, CacheEntry, CacheFuture, CacheStats, EvictionHook, MemoryCacheBackend,
+    TilesetCache,
+};
+pub use content::WorkerStats;
 pub use world::{
-    AutoTour, CameraPosition, Config, InputEvent, Key, Location, MouseButton, Orientation, Source,
-    World,
+    AtmosphereConfig, AutoTour, Billboard, BillboardId, CacheBackendConfig, CameraPosition, Config,
+    GlobeConfig, Icon, IconImage, ImageryLayer, ImagerySource, InputEvent, Key, Label, Location,
+    MipmapMode, MouseButton, NormalsConfig, Orientation, Polygon, Polyline, ScaleByDistance,
+    ShadowsConfig, Source, VectorId, VectorShape, VectorStyle, WorkersConfig, World,
 };
+pub use world::{sun_azimuth_elevation, sun_direction_ecef};
+
+pub use render::{supported_sample_count, RenderedImage};
 
 use crate::world::load_config;
 
@@ -30,5 +40,18 @@ pub fn get_debug_config() -> Config {
         debug_render_volumes: false,
         debug_auto_tour: false,
         tile_culling: false,
+        decoded_cache: false,
+        cache_backend: CacheBackendConfig::default(),
+        mipmaps: MipmapMode::default(),
+        max_anisotropy: 16,
+        normals: NormalsConfig {
+            generate: true,
+            ..Default::default()
+        },
+        workers: WorkersConfig::default(),
+        atmosphere: AtmosphereConfig::default(),
+        shadows: ShadowsConfig::default(),
+        msaa_samples: 1,
+        globe: GlobeConfig::default(),
     })
 }
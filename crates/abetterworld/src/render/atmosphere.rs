@@ -0,0 +1,340 @@
+//! Earth's atmosphere after Hillaire, "A Scalable and Production Ready Sky and
+//! Atmosphere Rendering Technique" (2020). Transmittance and multiple
+//! scattering are precomputed here once into two small LUTs; the sky pass and
+//! the aerial perspective on tiles raymarch single scattering against them in
+//! assets/atmosphere.wgsl, which mirrors these constants.
+//!
+//! The atmosphere is a spherical shell over a sphere of radius `WGS84_A`; the
+//! shaders squash WGS84 onto it by scaling z by `WGS84_A / WGS84_B`.
+
+use crate::dynamics::WGS84_A;
+use crate::render::TextureResource;
+use cgmath::{ElementWise, InnerSpace, Vector3};
+use half::f16;
+use std::f64::consts::PI;
+
+pub const BOTTOM_RADIUS: f64 = WGS84_A;
+pub const TOP_RADIUS: f64 = WGS84_A + 100_000.0;
+
+const RAYLEIGH_SCATTERING: Vector3<f64> = Vector3::new(5.802e-6, 13.558e-6, 33.1e-6);
+const RAYLEIGH_SCALE_HEIGHT: f64 = 8_000.0;
+const MIE_SCATTERING: f64 = 3.996e-6;
+const MIE_EXTINCTION: f64 = 4.40e-6;
+const MIE_SCALE_HEIGHT: f64 = 1_200.0;
+/// Ozone only absorbs, in a layer peaking at 25 km and gone 15 km either side.
+const OZONE_ABSORPTION: Vector3<f64> = Vector3::new(0.650e-6, 1.881e-6, 0.085e-6);
+const OZONE_CENTER: f64 = 25_000.0;
+const OZONE_HALF_WIDTH: f64 = 15_000.0;
+const GROUND_ALBEDO: f64 = 0.3;
+
+/// Transmittance to the top of the atmosphere by altitude (v) and view
+/// zenith angle (u), in Bruneton's parameterization.
+pub const TRANSMITTANCE_LUT_SIZE: (u32, u32) = (256, 64);
+/// Multiple scattering by altitude (v) and sun zenith cosine (u).
+pub const MULTI_SCATTERING_LUT_SIZE: u32 = 32;
+
+const TRANSMITTANCE_STEPS: usize = 40;
+const MULTI_SCATTERING_STEPS: usize = 20;
+/// Directions integrated per multiple scattering texel, as a square grid.
+const MULTI_SCATTERING_DIRECTIONS: usize = 8;
+
+/// `LightingUniform::flags` bit: fog tile fragments by their distance through
+/// the atmosphere. Matches LIGHTING_AERIAL_PERSPECTIVE in atmosphere.wgsl.
+pub const LIGHTING_AERIAL_PERSPECTIVE: u32 = 1;
+
+struct Medium {
+    scattering: Vector3<f64>,
+    extinction: Vector3<f64>,
+}
+
+fn medium(altitude: f64) -> Medium {
+    let rayleigh = RAYLEIGH_SCATTERING * (-altitude / RAYLEIGH_SCALE_HEIGHT).exp();
+    let mie_density = (-altitude / MIE_SCALE_HEIGHT).exp();
+    let ozone = (1.0 - (altitude - OZONE_CENTER).abs() / OZONE_HALF_WIDTH).max(0.0);
+    let mie = Vector3::new(1.0, 1.0, 1.0) * (MIE_SCATTERING * mie_density);
+
+    Medium {
+        scattering: rayleigh + mie,
+        extinction: rayleigh
+            + Vector3::new(1.0, 1.0, 1.0) * (MIE_EXTINCTION * mie_density)
+            + OZONE_ABSORPTION * ozone,
+    }
+}
+
+fn distance_to_top(r: f64, mu: f64) -> f64 {
+    let discriminant = r * r * (mu * mu - 1.0) + TOP_RADIUS * TOP_RADIUS;
+    (-r * mu + discriminant.max(0.0).sqrt()).max(0.0)
+}
+
+fn distance_to_bottom(r: f64, mu: f64) -> f64 {
+    let discriminant = r * r * (mu * mu - 1.0) + BOTTOM_RADIUS * BOTTOM_RADIUS;
+    (-r * mu - discriminant.max(0.0).sqrt()).max(0.0)
+}
+
+fn ray_hits_ground(r: f64, mu: f64) -> bool {
+    mu < 0.0 && r * r * (mu * mu - 1.0) + BOTTOM_RADIUS * BOTTOM_RADIUS >= 0.0
+}
+
+/// Distance from the ground to the top of the atmosphere along the horizon.
+fn horizon_length() -> f64 {
+    (TOP_RADIUS * TOP_RADIUS - BOTTOM_RADIUS * BOTTOM_RADIUS).sqrt()
+}
+
+/// Transmittance LUT coordinates for radius `r` and view zenith cosine `mu`.
+pub fn transmittance_uv(r: f64, mu: f64) -> (f64, f64) {
+    let h = horizon_length();
+    let rho = (r * r - BOTTOM_RADIUS * BOTTOM_RADIUS).max(0.0).sqrt();
+    let d = distance_to_top(r, mu);
+    let d_min = TOP_RADIUS - r;
+    let d_max = rho + h;
+    ((d - d_min) / (d_max - d_min), rho / h)
+}
+
+/// Inverse of [`transmittance_uv`].
+pub fn transmittance_r_mu(u: f64, v: f64) -> (f64, f64) {
+    let h = horizon_length();
+    let rho = h * v;
+    let r = (rho * rho + BOTTOM_RADIUS * BOTTOM_RADIUS).sqrt();
+    let d_min = TOP_RADIUS - r;
+    let d_max = rho + h;
+    let d = d_min + u * (d_max - d_min);
+    let mu = if d == 0.0 {
+        1.0
+    } else {
+        (h * h - rho * rho - d * d) / (2.0 * r * d)
+    };
+    (r, mu.clamp(-1.0, 1.0))
+}
+
+/// Transmittance from radius `r` along view zenith cosine `mu` to the top of
+/// the atmosphere, integrated directly.
+pub fn integrate_transmittance(r: f64, mu: f64) -> Vector3<f64> {
+    let length = distance_to_top(r, mu);
+    let dt = length / TRANSMITTANCE_STEPS as f64;
+    let optical_depth = (0..TRANSMITTANCE_STEPS)
+        .map(|i| {
+            let t = (i as f64 + 0.5) * dt;
+            let radius = (r * r + t * t + 2.0 * r * mu * t).sqrt();
+            medium(radius - BOTTOM_RADIUS).extinction * dt
+        })
+        .fold(Vector3::new(0.0, 0.0, 0.0), |sum, depth| sum + depth);
+    optical_depth.map(|depth| (-depth).exp())
+}
+
+/// The precomputed LUTs, as linear RGB.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AtmosphereLuts {
+    /// `TRANSMITTANCE_LUT_SIZE`, rows from the ground up.
+    pub transmittance: Vec<Vector3<f64>>,
+    /// `MULTI_SCATTERING_LUT_SIZE` squared, rows from the ground up, for a sun
+    /// of unit illuminance.
+    pub multi_scattering: Vec<Vector3<f64>>,
+}
+
+impl AtmosphereLuts {
+    pub fn compute() -> Self {
+        let (width, height) = TRANSMITTANCE_LUT_SIZE;
+        let transmittance = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let (r, mu) = transmittance_r_mu(
+                    (x as f64 + 0.5) / width as f64,
+                    (y as f64 + 0.5) / height as f64,
+                );
+                integrate_transmittance(r, mu)
+            })
+            .collect();
+
+        let mut luts = Self {
+            transmittance,
+            multi_scattering: Vec::new(),
+        };
+
+        let size = MULTI_SCATTERING_LUT_SIZE;
+        luts.multi_scattering = (0..size)
+            .flat_map(|y| (0..size).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let mu_s = (x as f64 + 0.5) / size as f64 * 2.0 - 1.0;
+                let altitude = (y as f64 + 0.5) / size as f64 * (TOP_RADIUS - BOTTOM_RADIUS);
+                luts.integrate_multi_scattering(BOTTOM_RADIUS + altitude, mu_s)
+            })
+            .collect();
+        luts
+    }
+
+    /// Bilinear lookup in the transmittance LUT, as the shaders sample it.
+    pub fn transmittance(&self, r: f64, mu: f64) -> Vector3<f64> {
+        let (width, height) = TRANSMITTANCE_LUT_SIZE;
+        let (u, v) = transmittance_uv(r.clamp(BOTTOM_RADIUS, TOP_RADIUS), mu);
+        let x = (u * width as f64 - 0.5).clamp(0.0, (width - 1) as f64);
+        let y = (v * height as f64 - 0.5).clamp(0.0, (height - 1) as f64);
+        let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+        let (x1, y1) = (
+            (x0 + 1).min(width as usize - 1),
+            (y0 + 1).min(height as usize - 1),
+        );
+        let (fx, fy) = (x - x0 as f64, y - y0 as f64);
+        let texel = |x: usize, y: usize| self.transmittance[y * width as usize + x];
+        let bottom = texel(x0, y0) * (1.0 - fx) + texel(x1, y0) * fx;
+        let top = texel(x0, y1) * (1.0 - fx) + texel(x1, y1) * fx;
+        bottom * (1.0 - fy) + top * fy
+    }
+
+    /// Hillaire's Ψ_ms: second order scattering from every direction around a
+    /// point, with an isotropic phase function, summed as the geometric series
+    /// of further orders.
+    fn integrate_multi_scattering(&self, r: f64, mu_s: f64) -> Vector3<f64> {
+        let origin = Vector3::new(0.0, 0.0, r);
+        let sun = Vector3::new((1.0 - mu_s * mu_s).max(0.0).sqrt(), 0.0, mu_s);
+        let isotropic_phase = 1.0 / (4.0 * PI);
+        let zero = Vector3::new(0.0, 0.0, 0.0);
+
+        let mut second_order = zero;
+        let mut transfer = zero;
+        let n = MULTI_SCATTERING_DIRECTIONS;
+        for i in 0..n * n {
+            let cos_theta = 1.0 - 2.0 * ((i / n) as f64 + 0.5) / n as f64;
+            let phi = 2.0 * PI * ((i % n) as f64 + 0.5) / n as f64;
+            let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+            let direction = Vector3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+
+            let hits_ground = ray_hits_ground(r, cos_theta);
+            let length = if hits_ground {
+                distance_to_bottom(r, cos_theta)
+            } else {
+                distance_to_top(r, cos_theta)
+            };
+            let dt = length / MULTI_SCATTERING_STEPS as f64;
+
+            let mut throughput = Vector3::new(1.0, 1.0, 1.0);
+            for step in 0..MULTI_SCATTERING_STEPS {
+                let p = origin + direction * ((step as f64 + 0.5) * dt);
+                let radius = p.magnitude();
+                let sun_mu = p.dot(sun) / radius;
+                let medium = medium(radius - BOTTOM_RADIUS);
+                let step_transmittance = (medium.extinction * -dt).map(f64::exp);
+                let sun_transmittance = if ray_hits_ground(radius, sun_mu) {
+                    zero
+                } else {
+                    self.transmittance(radius, sun_mu)
+                };
+
+                // Energy conserving integration over the step
+                let absorbed = (Vector3::new(1.0, 1.0, 1.0) - step_transmittance)
+                    .div_element_wise(medium.extinction.map(|e| e.max(1e-30)));
+                let inscattered =
+                    sun_transmittance.mul_element_wise(medium.scattering) * isotropic_phase;
+                second_order += throughput
+                    .mul_element_wise(inscattered)
+                    .mul_element_wise(absorbed);
+                transfer += throughput
+                    .mul_element_wise(medium.scattering)
+                    .mul_element_wise(absorbed);
+                throughput = throughput.mul_element_wise(step_transmittance);
+            }
+
+            if hits_ground {
+                let ground = origin + direction * length;
+                let ground_mu = ground.normalize().dot(sun);
+                let lit = self.transmittance(BOTTOM_RADIUS, ground_mu)
+                    * (ground_mu.max(0.0) * GROUND_ALBEDO / PI);
+                second_order += throughput.mul_element_wise(lit);
+            }
+        }
+
+        let directions = (n * n) as f64;
+        let second_order = second_order / directions;
+        let transfer = transfer / directions;
+        second_order.div_element_wise(transfer.map(|f| 1.0 - f.min(0.999)))
+    }
+}
+
+/// RGBA16F texels for a LUT.
+fn lut_texels(lut: &[Vector3<f64>]) -> Vec<u8> {
+    lut.iter()
+        .flat_map(|c| [c.x, c.y, c.z, 1.0])
+        .flat_map(|c| f16::from_f64(c).to_le_bytes())
+        .collect()
+}
+
+fn lut_texture(device: &wgpu::Device, label: &str, width: u32, height: u32) -> TextureResource {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba16Float,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some(label),
+        address_mode_u: wgpu::AddressMode::ClampToEdge,
+        address_mode_v: wgpu::AddressMode::ClampToEdge,
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        ..Default::default()
+    });
+    TextureResource {
+        texture,
+        view,
+        sampler,
+    }
+}
+
+/// The LUTs on the GPU. They are computed with the pipeline but need a queue
+/// to upload, which happens on the first update.
+pub struct AtmosphereTextures {
+    pub transmittance: TextureResource,
+    pub multi_scattering: TextureResource,
+    pending: Option<AtmosphereLuts>,
+}
+
+impl AtmosphereTextures {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let (width, height) = TRANSMITTANCE_LUT_SIZE;
+        Self {
+            transmittance: lut_texture(device, "Transmittance LUT", width, height),
+            multi_scattering: lut_texture(
+                device,
+                "Multiple Scattering LUT",
+                MULTI_SCATTERING_LUT_SIZE,
+                MULTI_SCATTERING_LUT_SIZE,
+            ),
+            pending: Some(AtmosphereLuts::compute()),
+        }
+    }
+
+    pub fn upload(&mut self, queue: &wgpu::Queue) {
+        let Some(luts) = self.pending.take() else {
+            return;
+        };
+        for (target, lut) in [
+            (&self.transmittance, &luts.transmittance),
+            (&self.multi_scattering, &luts.multi_scattering),
+        ] {
+            let size = target.texture.size();
+            queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    texture: &target.texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &lut_texels(lut),
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(size.width * 8),
+                    rows_per_image: Some(size.height),
+                },
+                size,
+            );
+        }
+    }
+}
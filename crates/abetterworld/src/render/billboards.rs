@@ -0,0 +1,771 @@
+use std::{collections::BTreeMap, collections::HashMap, mem, ops::Range};
+
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3};
+use tracing::{event, Level};
+use wgpu::util::DeviceExt;
+
+use crate::{
+    dynamics::WGS84_B,
+    helpers::Uniforms,
+    render::{Atlas, DepthBuffer, GlyphAtlas, RenderPipeline, GLYPH_SIZE, GLYPH_SPREAD},
+    world::{Billboard, BillboardId, Icon, IconImage, Label},
+};
+
+/// What a billboard vertex draws; match the constants in billboards.wgsl.
+pub const KIND_GLYPH: u32 = 0;
+pub const KIND_IMAGE: u32 = 1;
+pub const KIND_POINT: u32 = 2;
+
+/// `BillboardInstance::flags`: nothing is drawn.
+pub const HIDDEN: u32 = 1;
+/// `BillboardInstance::flags`: the label overlaps a nearer one and isn't drawn.
+pub const LABEL_HIDDEN: u32 = 2;
+
+/// A corner of an icon or glyph quad.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct BillboardVertex {
+    /// From the anchor, in pixels with y down, scaled by distance.
+    pub offset: [f32; 2],
+    /// The billboard's pixel offset, which isn't scaled.
+    pub shift: [f32; 2],
+    /// Atlas texels for glyphs and images, radii from the middle for points.
+    pub uv: [f32; 2],
+    /// sRGB with straight alpha.
+    pub color: [u8; 4],
+    pub outline_color: [u8; 4],
+    /// Pixels from the edge per unit of distance field for glyphs, the
+    /// radius for points; then the outline width in pixels.
+    pub style: [f32; 2],
+    pub kind: u32,
+    /// Index of the billboard's `BillboardInstance`.
+    pub billboard: u32,
+}
+
+impl BillboardVertex {
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        const ATTRIBUTES: [wgpu::VertexAttribute; 8] = wgpu::vertex_attr_array![
+            0 => Float32x2,
+            1 => Float32x2,
+            2 => Float32x2,
+            3 => Unorm8x4,
+            4 => Unorm8x4,
+            5 => Float32x2,
+            6 => Uint32,
+            7 => Uint32,
+        ];
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<BillboardVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &ATTRIBUTES,
+        }
+    }
+}
+
+/// Where a billboard is this frame; matches `Instance` in billboards.wgsl.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct BillboardInstance {
+    /// Relative to the eye.
+    pub position: [f32; 3],
+    pub scale: f32,
+    /// `HIDDEN` and `LABEL_HIDDEN`.
+    pub flags: u32,
+    pub _pad: [u32; 3],
+}
+
+/// What the billboard shader needs besides the camera; matches `Billboards`
+/// in billboards.wgsl.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct BillboardUniform {
+    /// In pixels.
+    pub viewport: [f32; 2],
+    pub _pad: [f32; 2],
+}
+
+/// Whether the Earth hides `point` from `eye`, taking it as a sphere no
+/// bigger than the poles' radius or the point's own. The globe's squeezed
+/// depth can't hide anything, and tiles cover the rest.
+pub fn behind_horizon(eye: &Point3<f64>, point: &Point3<f64>) -> bool {
+    let radius = WGS84_B.min(point.to_vec().magnitude() - 1.0);
+    let (origin, direction) = (eye.to_vec(), point - eye);
+    let c = origin.magnitude2() - radius * radius;
+    if c <= 0.0 {
+        // underground, nothing to go by
+        return false;
+    }
+    let (a, b) = (direction.magnitude2(), 2.0 * origin.dot(direction));
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 || a == 0.0 {
+        return false;
+    }
+    let t = (-b - discriminant.sqrt()) / (2.0 * a);
+    t > 0.0 && t < 1.0
+}
+
+struct BillboardEntry {
+    anchor: Point3<f64>,
+    billboard: Billboard,
+    vertices: Vec<BillboardVertex>,
+    /// The label's left, top, right and bottom in unscaled pixels from the
+    /// anchor, outline and pixel offset left out.
+    label: Option<[f32; 4]>,
+}
+
+/// Every billboard and the atlases their icons and labels are drawn from.
+/// All of them share one vertex buffer, rebuilt when any changes, and each
+/// is placed by an instance written every update.
+pub struct BillboardLayer {
+    next_id: u64,
+    entries: BTreeMap<BillboardId, BillboardEntry>,
+    pub glyphs: GlyphAtlas,
+    pub icons: Atlas,
+    /// Icon images by content hash, and where they went in `icons`.
+    icon_rects: HashMap<u64, Option<[u32; 4]>>,
+    buffer: Option<wgpu::Buffer>,
+    /// Vertices of depth tested billboards, then of those always on top.
+    ranges: [Range<u32>; 2],
+    dirty: bool,
+}
+
+impl BillboardLayer {
+    pub fn new() -> Self {
+        Self {
+            next_id: 1,
+            entries: BTreeMap::new(),
+            glyphs: GlyphAtlas::new(),
+            icons: Atlas::new(4),
+            icon_rects: HashMap::new(),
+            buffer: None,
+            ranges: [0..0, 0..0],
+            dirty: false,
+        }
+    }
+
+    pub fn add(&mut self, billboard: Billboard) -> BillboardId {
+        let id = BillboardId(self.next_id);
+        self.next_id += 1;
+        self.set(id, billboard);
+        id
+    }
+
+    /// Replaces the billboard `id` names, returning false if there's none.
+    pub fn replace(&mut self, id: BillboardId, billboard: Billboard) -> bool {
+        if !self.entries.contains_key(&id) {
+            return false;
+        }
+        self.set(id, billboard);
+        true
+    }
+
+    fn set(&mut self, id: BillboardId, billboard: Billboard) {
+        let mut vertices = Vec::new();
+        if let Some(icon) = &billboard.icon {
+            self.add_icon(icon, &billboard, &mut vertices);
+        }
+        let label = billboard
+            .label
+            .as_ref()
+            .map(|label| self.add_label(label, &billboard, &mut vertices));
+        let entry = BillboardEntry {
+            anchor: billboard.position.to_ecef(),
+            billboard,
+            vertices,
+            label,
+        };
+        self.entries.insert(id, entry);
+        self.dirty = true;
+    }
+
+    pub fn remove(&mut self, id: BillboardId) -> bool {
+        let removed = self.entries.remove(&id).is_some();
+        self.dirty |= removed;
+        removed
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn add_icon(&mut self, icon: &Icon, billboard: &Billboard, out: &mut Vec<BillboardVertex>) {
+        let vertex = BillboardVertex {
+            shift: billboard.pixel_offset,
+            ..bytemuck::Zeroable::zeroed()
+        };
+        match icon {
+            Icon::Point {
+                diameter,
+                color,
+                outline_color,
+                outline_width,
+            } => {
+                let radius = (diameter / 2.0).max(0.5);
+                // a pixel beyond the outline to fade out over
+                let half = radius + outline_width + 1.0;
+                let uv = half / radius;
+                push_quad(
+                    out,
+                    [-half, -half, half, half],
+                    [-uv, -uv, uv, uv],
+                    BillboardVertex {
+                        color: *color,
+                        outline_color: *outline_color,
+                        style: [radius, *outline_width],
+                        kind: KIND_POINT,
+                        ..vertex
+                    },
+                );
+            }
+            Icon::Image { image, scale } => {
+                let Some([x, y, w, h]) = self.icon_rect(image) else {
+                    return;
+                };
+                let (half_w, half_h) = (w as f32 * scale / 2.0, h as f32 * scale / 2.0);
+                push_quad(
+                    out,
+                    [-half_w, -half_h, half_w, half_h],
+                    [x as f32, y as f32, (x + w) as f32, (y + h) as f32],
+                    BillboardVertex {
+                        color: [255; 4],
+                        kind: KIND_IMAGE,
+                        ..vertex
+                    },
+                );
+            }
+        }
+    }
+
+    fn icon_rect(&mut self, image: &IconImage) -> Option<[u32; 4]> {
+        if image.rgba.len() != (image.width * image.height * 4) as usize {
+            event!(
+                Level::WARN,
+                "icon image isn't {}×{} RGBA",
+                image.width,
+                image.height
+            );
+            return None;
+        }
+        let mut key = xxhash_rust::xxh3::xxh3_64(&image.rgba);
+        key ^= ((image.width as u64) << 32) | image.height as u64;
+        let icons = &mut self.icons;
+        *self.icon_rects.entry(key).or_insert_with(|| {
+            let at = icons.insert(image.width, image.height, &image.rgba);
+            if at.is_none() {
+                event!(Level::WARN, "no room in the icon atlas");
+            }
+            at.map(|[x, y]| [x, y, image.width, image.height])
+        })
+    }
+
+    /// Returns the label's bounds, see `BillboardEntry::label`.
+    fn add_label(
+        &mut self,
+        label: &Label,
+        billboard: &Billboard,
+        out: &mut Vec<BillboardVertex>,
+    ) -> [f32; 4] {
+        let layout = self.glyphs.layout(&label.text, label.size);
+        let [x, y] = label.offset;
+        let spread = GLYPH_SPREAD * label.size / GLYPH_SIZE;
+        let vertex = BillboardVertex {
+            shift: billboard.pixel_offset,
+            color: label.color,
+            outline_color: label.outline_color,
+            // the field runs out a pixel short of the quad's edge
+            style: [spread, label.outline_width.min(spread - 1.0).max(0.0)],
+            kind: KIND_GLYPH,
+            ..bytemuck::Zeroable::zeroed()
+        };
+        for quad in &layout.quads {
+            let [l, t, r, b] = quad.bounds;
+            push_quad(out, [l + x, t + y, r + x, b + y], quad.texels, vertex);
+        }
+        let [half_w, half_h] = layout.size.map(|s| s / 2.0);
+        [x - half_w, y - half_h, x + half_w, y + half_h]
+    }
+
+    /// Every billboard's vertices with their instance index set, the depth
+    /// tested ones first, and where the always on top ones start.
+    pub fn vertices(&self) -> (Vec<BillboardVertex>, u32) {
+        let mut vertices = Vec::new();
+        for on_top in [false, true] {
+            for (index, entry) in self.entries.values().enumerate() {
+                if entry.billboard.always_on_top == on_top {
+                    vertices.extend(entry.vertices.iter().map(|v| BillboardVertex {
+                        billboard: index as u32,
+                        ..*v
+                    }));
+                }
+            }
+        }
+        let on_top = self
+            .entries
+            .values()
+            .filter(|e| e.billboard.always_on_top)
+            .map(|e| e.vertices.len())
+            .sum::<usize>();
+        let split = (vertices.len() - on_top) as u32;
+        (vertices, split)
+    }
+
+    /// Rebuilds the vertex buffer after billboards were added, replaced or
+    /// removed, returning whether anything changed.
+    pub fn upload(&mut self, device: &wgpu::Device) -> bool {
+        if !mem::take(&mut self.dirty) {
+            return false;
+        }
+        let (vertices, split) = self.vertices();
+        let end = vertices.len() as u32;
+        self.ranges = [0..split, split..end];
+        self.buffer = (!vertices.is_empty()).then(|| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Billboard Vertices"),
+                contents: bytemuck::cast_slice(&vertices),
+                usage: wgpu::BufferUsages::VERTEX,
+            })
+        });
+        true
+    }
+
+    /// One per billboard, placing it relative to the eye, scaling it by
+    /// distance and hiding what's behind the horizon. Labels overlapping
+    /// one already kept are hidden, keeping those always on top and then
+    /// the nearest.
+    pub fn instances(
+        &self,
+        eye_pos: &Point3<f64>,
+        view_proj: &Matrix4<f64>,
+        viewport: (f64, f64),
+    ) -> Vec<BillboardInstance> {
+        let mut instances = Vec::with_capacity(self.entries.len());
+        // index, on top, distance and screen rectangle of each visible label
+        let mut labels = Vec::new();
+        for (index, entry) in self.entries.values().enumerate() {
+            let relative = entry.anchor - eye_pos;
+            let distance = relative.magnitude();
+            let billboard = &entry.billboard;
+            let scale = billboard
+                .scale_by_distance
+                .map_or(1.0, |s| s.scale(distance));
+            let clip = view_proj * relative.extend(1.0);
+            let hidden = scale <= 0.0 || behind_horizon(eye_pos, &entry.anchor);
+            instances.push(BillboardInstance {
+                position: relative.cast::<f32>().unwrap().into(),
+                scale,
+                flags: if hidden { HIDDEN } else { 0 },
+                _pad: [0; 3],
+            });
+
+            let Some([l, t, r, b]) = entry.label else {
+                continue;
+            };
+            if hidden || clip.w <= 0.0 {
+                continue;
+            }
+            let x = (clip.x / clip.w + 1.0) * 0.5 * viewport.0 + billboard.pixel_offset[0] as f64;
+            let y = (1.0 - clip.y / clip.w) * 0.5 * viewport.1 + billboard.pixel_offset[1] as f64;
+            let scale = scale as f64;
+            let rect = [
+                x + l as f64 * scale,
+                y + t as f64 * scale,
+                x + r as f64 * scale,
+                y + b as f64 * scale,
+            ];
+            labels.push((index, billboard.always_on_top, distance, rect));
+        }
+
+        labels.sort_by(|a, b| b.1.cmp(&a.1).then(a.2.total_cmp(&b.2)));
+        let mut kept: Vec<[f64; 4]> = Vec::with_capacity(labels.len());
+        for (index, on_top, _, rect) in labels {
+            let overlaps = kept
+                .iter()
+                .any(|k| rect[0] < k[2] && k[0] < rect[2] && rect[1] < k[3] && k[1] < rect[3]);
+            if overlaps && !on_top {
+                instances[index].flags |= LABEL_HIDDEN;
+            } else {
+                kept.push(rect);
+            }
+        }
+        instances
+    }
+
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass, pipelines: &BillboardPipelines) {
+        let Some(buffer) = self.buffer.as_ref() else {
+            return;
+        };
+        render_pass.set_bind_group(0, &pipelines.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, buffer.slice(..));
+        for (range, pipeline) in self.ranges.iter().zip(&pipelines.pipelines) {
+            if !range.is_empty() {
+                render_pass.set_pipeline(pipeline);
+                render_pass.draw(range.clone(), 0..1);
+            }
+        }
+    }
+}
+
+impl Default for BillboardLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Two triangles from `bounds` to `uv`, both left, top, right and bottom.
+fn push_quad(
+    out: &mut Vec<BillboardVertex>,
+    bounds: [f32; 4],
+    uv: [f32; 4],
+    vertex: BillboardVertex,
+) {
+    // corners numbered right + 2 × bottom
+    for corner in [0, 1, 3, 0, 3, 2] {
+        let (right, bottom) = (corner & 1 == 1, corner & 2 == 2);
+        let pick = |rect: [f32; 4]| {
+            [
+                if right { rect[2] } else { rect[0] },
+                if bottom { rect[3] } else { rect[1] },
+            ]
+        };
+        out.push(BillboardVertex {
+            offset: pick(bounds),
+            uv: pick(uv),
+            ..vertex
+        });
+    }
+}
+
+/// A texture following an `Atlas`, recreated when the atlas grows.
+struct AtlasTexture {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    version: u64,
+}
+
+impl AtlasTexture {
+    fn new(device: &wgpu::Device, atlas: &Atlas, format: wgpu::TextureFormat) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Billboard Atlas"),
+            size: wgpu::Extent3d {
+                width: atlas.width,
+                height: atlas.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        Self {
+            texture,
+            view,
+            // uploads on the first update
+            version: u64::MAX,
+        }
+    }
+
+    /// Copies the atlas in if it changed, returning true if the texture had
+    /// to be recreated, and the bind group with it.
+    fn sync(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, atlas: &Atlas) -> bool {
+        if self.version == atlas.version {
+            return false;
+        }
+        let recreated = self.texture.height() != atlas.height;
+        if recreated {
+            *self = Self::new(device, atlas, self.texture.format());
+        }
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &atlas.pixels,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(atlas.width * atlas.channels),
+                rows_per_image: Some(atlas.height),
+            },
+            self.texture.size(),
+        );
+        self.version = atlas.version;
+        recreated
+    }
+}
+
+/// The depth tested and always on top pipelines for billboards, and the
+/// buffers and atlas textures they share.
+pub struct BillboardPipelines {
+    /// Depth tested, then always on top.
+    pub pipelines: Vec<wgpu::RenderPipeline>,
+    pub bind_group: wgpu::BindGroup,
+    bind_group_layout: wgpu::BindGroupLayout,
+    camera: wgpu::Buffer,
+    instances: wgpu::Buffer,
+    uniform: wgpu::Buffer,
+    glyphs: AtlasTexture,
+    icons: AtlasTexture,
+    sampler: wgpu::Sampler,
+}
+
+impl BillboardPipelines {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        camera: &wgpu::Buffer,
+        depth: &DepthBuffer,
+        layer: &BillboardLayer,
+    ) -> Self {
+        let uniform_entry = |binding: u32, size: usize| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: std::num::NonZeroU64::new(size as u64),
+            },
+            count: None,
+        };
+        let texture_entry = |binding: u32| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Billboard Bind Group Layout"),
+            entries: &[
+                // binding(0) camera
+                uniform_entry(0, mem::size_of::<Uniforms>()),
+                // binding(1) one instance per billboard
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                // binding(2) viewport
+                uniform_entry(2, mem::size_of::<BillboardUniform>()),
+                // binding(3) glyph distance fields, binding(4) icons
+                texture_entry(3),
+                texture_entry(4),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let uniform = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("billboard_ubo"),
+            size: mem::size_of::<BillboardUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let glyphs = AtlasTexture::new(device, &layer.glyphs.atlas, wgpu::TextureFormat::R8Unorm);
+        let icons = AtlasTexture::new(device, &layer.icons, wgpu::TextureFormat::Rgba8UnormSrgb);
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Billboard Atlas"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let instances = instance_buffer(device, 64);
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Billboard Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../../assets/billboards.wgsl").into()),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Billboard Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        // Blended over everything without writing depth, either tested
+        // against the tiles or not at all
+        let build = |on_top: bool| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Billboard Pipeline"),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_billboard"),
+                    buffers: &[BillboardVertex::desc()],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_billboard"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                cache: None,
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: depth
+                    .depth_stencil_state()
+                    .map(|d| wgpu::DepthStencilState {
+                        depth_write_enabled: false,
+                        depth_compare: if on_top {
+                            wgpu::CompareFunction::Always
+                        } else {
+                            d.depth_compare
+                        },
+                        ..d
+                    }),
+                multisample: wgpu::MultisampleState {
+                    count: depth.sample_count,
+                    ..Default::default()
+                },
+                multiview: None,
+            })
+        };
+
+        Self {
+            pipelines: vec![build(false), build(true)],
+            bind_group: create_billboard_bg(
+                device,
+                &bind_group_layout,
+                [camera, &instances, &uniform],
+                [&glyphs.view, &icons.view],
+                &sampler,
+            ),
+            bind_group_layout,
+            camera: camera.clone(),
+            instances,
+            uniform,
+            glyphs,
+            icons,
+            sampler,
+        }
+    }
+
+    /// Billboard pipelines drawing into `pipeline`'s targets with its
+    /// camera, or None if it has neither, like the debug pipeline.
+    pub fn build_for(
+        device: &wgpu::Device,
+        pipeline: &RenderPipeline,
+        format: wgpu::TextureFormat,
+        layer: &BillboardLayer,
+    ) -> Option<Self> {
+        let camera = pipeline.bindings.camera_buffer.as_ref()?;
+        Some(Self::new(
+            device,
+            format,
+            camera,
+            pipeline.depth.as_ref()?,
+            layer,
+        ))
+    }
+
+    fn rebind(&mut self, device: &wgpu::Device) {
+        self.bind_group = create_billboard_bg(
+            device,
+            &self.bind_group_layout,
+            [&self.camera, &self.instances, &self.uniform],
+            [&self.glyphs.view, &self.icons.view],
+            &self.sampler,
+        );
+    }
+
+    /// Places every billboard for the frame about to be drawn, and uploads
+    /// any glyphs and icons added since the last update.
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layer: &BillboardLayer,
+        eye_pos: &Point3<f64>,
+        camera: &Uniforms,
+        viewport: (f64, f64),
+    ) {
+        let uniform = BillboardUniform {
+            viewport: [viewport.0 as f32, viewport.1 as f32],
+            _pad: [0.0; 2],
+        };
+        queue.write_buffer(&self.uniform, 0, bytemuck::bytes_of(&uniform));
+
+        let mut rebind = self.glyphs.sync(device, queue, &layer.glyphs.atlas);
+        rebind |= self.icons.sync(device, queue, &layer.icons);
+        let view_proj = Matrix4::from(camera.mat).cast::<f64>().unwrap();
+        let instances = layer.instances(eye_pos, &view_proj, viewport);
+        let size = mem::size_of_val(instances.as_slice()) as u64;
+        if size > self.instances.size() {
+            self.instances = instance_buffer(device, instances.len().next_power_of_two());
+            rebind = true;
+        }
+        if rebind {
+            self.rebind(device);
+        }
+        if !instances.is_empty() {
+            queue.write_buffer(&self.instances, 0, bytemuck::cast_slice(&instances));
+        }
+    }
+}
+
+fn instance_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("billboard_instances"),
+        size: (capacity * mem::size_of::<BillboardInstance>()) as u64,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_billboard_bg(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    [camera, instances, uniform]: [&wgpu::Buffer; 3],
+    [glyphs, icons]: [&wgpu::TextureView; 2],
+    sampler: &wgpu::Sampler,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Billboard Bind Group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: instances.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: uniform.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::TextureView(glyphs),
+            },
+            wgpu::BindGroupEntry {
+                binding: 4,
+                resource: wgpu::BindingResource::TextureView(icons),
+            },
+            wgpu::BindGroupEntry {
+                binding: 5,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+        ],
+    })
+}
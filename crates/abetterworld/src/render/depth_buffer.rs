@@ -1,12 +1,22 @@
 pub struct DepthBuffer {
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
+    /// The depth aspect alone, for binding as a texture.
+    pub depth_view: wgpu::TextureView,
     pub format: wgpu::TextureFormat,
     pub sample_count: u32,
 }
 
-pub fn recommended_format() -> wgpu::TextureFormat {
-    wgpu::TextureFormat::Depth32Float
+/// Float depth keeps reverse-Z precise enough for the squeezed globe, so
+/// stencil for draped vectors' volumes comes only with
+/// `DEPTH32FLOAT_STENCIL8`; the 24 bit depth of the universal stencil
+/// format would round the globe to the far plane.
+pub fn recommended_format(features: wgpu::Features) -> wgpu::TextureFormat {
+    if features.contains(wgpu::Features::DEPTH32FLOAT_STENCIL8) {
+        wgpu::TextureFormat::Depth32FloatStencil8
+    } else {
+        wgpu::TextureFormat::Depth32Float
+    }
 }
 
 impl DepthBuffer {
@@ -14,7 +24,7 @@ impl DepthBuffer {
         device: &wgpu::Device,
         width: u32,
         height: u32,
-        format: wgpu::TextureFormat, // e.g., Depth32Float (preferred) or Depth24Plus
+        format: wgpu::TextureFormat, // from recommended_format
         sample_count: u32,
     ) -> Self {
         let size = wgpu::Extent3d {
@@ -23,6 +33,13 @@ impl DepthBuffer {
             depth_or_array_layers: 1,
         };
 
+        // Multisampled depth is attachment only: GL drops draws into
+        // multisampled depth textures, and WebGL2 has none to sample
+        let usage = if sample_count > 1 {
+            wgpu::TextureUsages::RENDER_ATTACHMENT
+        } else {
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING
+        };
         let texture = device.create_texture(&wgpu::TextureDescriptor {
             label: Some("Depth Texture"),
             size,
@@ -30,15 +47,20 @@ impl DepthBuffer {
             sample_count,
             dimension: wgpu::TextureDimension::D2,
             format,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            usage,
             view_formats: &[], // depth generally doesn't need view format remaps
         });
 
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let depth_view = texture.create_view(&wgpu::TextureViewDescriptor {
+            aspect: wgpu::TextureAspect::DepthOnly,
+            ..Default::default()
+        });
 
         Self {
             texture,
             view,
+            depth_view,
             format,
             sample_count,
         }
@@ -63,6 +85,17 @@ impl DepthBuffer {
         wgpu::CompareFunction::GreaterEqual
     }
 
+    /// Stencil ops for formats that have stencil; without any, a stencil
+    /// aspect would be read only.
+    fn stencil_ops(&self, load: wgpu::LoadOp<u32>) -> Option<wgpu::Operations<u32>> {
+        self.format
+            .has_stencil_aspect()
+            .then_some(wgpu::Operations {
+                load,
+                store: wgpu::StoreOp::Store,
+            })
+    }
+
     /// Convenience builder for a depth attachment with a clear.
     pub fn attachment_clear(&self) -> wgpu::RenderPassDepthStencilAttachment {
         wgpu::RenderPassDepthStencilAttachment {
@@ -71,7 +104,7 @@ impl DepthBuffer {
                 load: wgpu::LoadOp::Clear(self.clear_value()),
                 store: wgpu::StoreOp::Store,
             }),
-            stencil_ops: None,
+            stencil_ops: self.stencil_ops(wgpu::LoadOp::Clear(0)),
         }
     }
 
@@ -83,7 +116,7 @@ impl DepthBuffer {
                 load: wgpu::LoadOp::Load,
                 store: wgpu::StoreOp::Store,
             }),
-            stencil_ops: None,
+            stencil_ops: self.stencil_ops(wgpu::LoadOp::Load),
         }
     }
 
@@ -0,0 +1,261 @@
+//! Atlases for billboards: signed distance fields of the bundled font's
+//! glyphs, rendered on the CPU the first time a label uses them, and icon
+//! images. Both are shelf packed into a texture that only grows.
+
+use std::collections::HashMap;
+
+use ab_glyph::{Font, FontArc, GlyphId, ScaleFont};
+use tracing::{event, Level};
+
+/// Cantarell Regular, under the SIL Open Font License; see assets/fonts/OFL.txt.
+const FONT: &[u8] = include_bytes!("../../assets/fonts/Cantarell-Regular.ttf");
+
+/// Em size glyphs are rasterized at, in atlas pixels.
+pub const GLYPH_SIZE: f32 = 40.0;
+
+/// How far outside and inside a glyph's edge its distance field reaches, in
+/// atlas pixels. Labels can't outline wider than this scaled to their size.
+pub const GLYPH_SPREAD: f32 = 6.0;
+
+pub const ATLAS_WIDTH: u32 = 512;
+const MAX_ATLAS_HEIGHT: u32 = 4096;
+
+/// A texture's worth of images packed in rows, with a pixel between them so
+/// filtering doesn't bleed.
+#[derive(Debug, Clone)]
+pub struct Atlas {
+    pub width: u32,
+    pub height: u32,
+    /// Bytes per pixel.
+    pub channels: u32,
+    pub pixels: Vec<u8>,
+    /// Bumped whenever `pixels` changes, so the texture knows to follow.
+    pub version: u64,
+    cursor: [u32; 2],
+    row_height: u32,
+}
+
+impl Atlas {
+    pub fn new(channels: u32) -> Self {
+        Self {
+            width: ATLAS_WIDTH,
+            height: 256,
+            channels,
+            pixels: vec![0; (ATLAS_WIDTH * 256 * channels) as usize],
+            version: 0,
+            cursor: [1, 1],
+            row_height: 0,
+        }
+    }
+
+    /// Copies in a `width` × `height` image, returning its top left corner,
+    /// or None once the atlas can't grow any taller.
+    pub fn insert(&mut self, width: u32, height: u32, pixels: &[u8]) -> Option<[u32; 2]> {
+        debug_assert_eq!(pixels.len(), (width * height * self.channels) as usize);
+        if width + 2 > self.width {
+            return None;
+        }
+        if self.cursor[0] + width + 1 > self.width {
+            self.cursor = [1, self.cursor[1] + self.row_height + 1];
+            self.row_height = 0;
+        }
+        while self.cursor[1] + height + 1 > self.height {
+            if self.height >= MAX_ATLAS_HEIGHT {
+                return None;
+            }
+            // rows run the full width, so growing just appends rows
+            self.height *= 2;
+            self.pixels
+                .resize((self.width * self.height * self.channels) as usize, 0);
+        }
+
+        let at = self.cursor;
+        let row = (width * self.channels) as usize;
+        for y in 0..height as usize {
+            let start = (((at[1] as usize + y) * self.width as usize) + at[0] as usize)
+                * self.channels as usize;
+            self.pixels[start..start + row].copy_from_slice(&pixels[y * row..(y + 1) * row]);
+        }
+        self.cursor[0] += width + 1;
+        self.row_height = self.row_height.max(height);
+        self.version += 1;
+        Some(at)
+    }
+}
+
+/// Where a glyph is in the atlas and how it sits on the baseline, in atlas
+/// pixels at `GLYPH_SIZE`, spread included.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GlyphEntry {
+    /// Top left, width and height in the atlas.
+    pub rect: [u32; 4],
+    /// From the pen position on the baseline to the top left, y down.
+    pub offset: [f32; 2],
+}
+
+/// One glyph of laid out text.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GlyphQuad {
+    /// Left, top, right and bottom in pixels, relative to the middle of the
+    /// text with y down.
+    pub bounds: [f32; 4],
+    /// The matching atlas rectangle, left, top, right and bottom in texels.
+    pub texels: [f32; 4],
+}
+
+/// Text ready to draw: its glyphs and the box they fill.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextLayout {
+    pub quads: Vec<GlyphQuad>,
+    /// Width and height of the lines, centered on the origin.
+    pub size: [f32; 2],
+}
+
+pub struct GlyphAtlas {
+    font: FontArc,
+    glyphs: HashMap<GlyphId, Option<GlyphEntry>>,
+    pub atlas: Atlas,
+}
+
+impl GlyphAtlas {
+    pub fn new() -> Self {
+        Self {
+            font: FontArc::try_from_slice(FONT).expect("bundled font"),
+            glyphs: HashMap::new(),
+            atlas: Atlas::new(1),
+        }
+    }
+
+    /// Lays `text` out at `size` pixels to the em, adding any glyphs it
+    /// needs to the atlas. Glyphs the atlas has no room for are left out.
+    pub fn layout(&mut self, text: &str, size: f32) -> TextLayout {
+        let scale = size / GLYPH_SIZE;
+        let font = self.font.clone();
+        let scaled = font.as_scaled(GLYPH_SIZE);
+        let line_height = scaled.height() + scaled.line_gap();
+
+        // pen positions at GLYPH_SIZE, then each line centered
+        let mut quads = Vec::new();
+        let mut lines = Vec::new();
+        for (row, line) in text.split('\n').enumerate() {
+            let start = quads.len();
+            let baseline = row as f32 * line_height + scaled.ascent();
+            let mut pen = 0.0;
+            let mut previous = None;
+            for c in line.chars() {
+                let id = font.glyph_id(c);
+                if let Some(previous) = previous {
+                    pen += scaled.kern(previous, id);
+                }
+                previous = Some(id);
+                if let Some(entry) = self.entry(id) {
+                    let [x, y, w, h] = entry.rect.map(|v| v as f32);
+                    let left = pen + entry.offset[0];
+                    let top = baseline + entry.offset[1];
+                    quads.push(GlyphQuad {
+                        bounds: [left, top, left + w, top + h],
+                        texels: [x, y, x + w, y + h],
+                    });
+                }
+                pen += scaled.h_advance(id);
+            }
+            lines.push((start..quads.len(), pen));
+        }
+
+        let width = lines.iter().map(|(_, w)| *w).fold(0.0, f32::max);
+        let height = lines.len() as f32 * line_height - scaled.line_gap();
+        for (range, line_width) in lines {
+            let dx = -line_width / 2.0;
+            for quad in &mut quads[range] {
+                let [l, t, r, b] = quad.bounds;
+                quad.bounds = [
+                    (l + dx) * scale,
+                    (t - height / 2.0) * scale,
+                    (r + dx) * scale,
+                    (b - height / 2.0) * scale,
+                ];
+            }
+        }
+        TextLayout {
+            quads,
+            size: [width * scale, height * scale],
+        }
+    }
+
+    fn entry(&mut self, id: GlyphId) -> Option<GlyphEntry> {
+        if let Some(entry) = self.glyphs.get(&id) {
+            return *entry;
+        }
+        // blank glyphs are remembered as None too, and cost nothing to look up again
+        let entry = self.rasterize(id);
+        self.glyphs.insert(id, entry);
+        entry
+    }
+
+    fn rasterize(&mut self, id: GlyphId) -> Option<GlyphEntry> {
+        let outline = self
+            .font
+            .outline_glyph(id.with_scale(GLYPH_SIZE))
+            .filter(|g| g.px_bounds().width() > 0.0)?;
+        let bounds = outline.px_bounds();
+        let (w, h) = (bounds.width() as usize, bounds.height() as usize);
+        let mut coverage = vec![0.0; w * h];
+        outline.draw(|x, y, c| coverage[y as usize * w + x as usize] = c);
+
+        let pad = GLYPH_SPREAD.ceil() as usize;
+        let (width, height) = (w + 2 * pad, h + 2 * pad);
+        let field = signed_distance_field(&coverage, w, h, pad);
+        let Some(at) = self.atlas.insert(width as u32, height as u32, &field) else {
+            event!(Level::WARN, "no room in the glyph atlas for {id:?}");
+            return None;
+        };
+        Some(GlyphEntry {
+            rect: [at[0], at[1], width as u32, height as u32],
+            offset: [bounds.min.x - pad as f32, bounds.min.y - pad as f32],
+        })
+    }
+}
+
+impl Default for GlyphAtlas {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A coverage bitmap's distance field with `pad` pixels around it, 128 on
+/// the edge and `GLYPH_SPREAD` pixels in or out reaching 255 or 0.
+/// Brute force over the spread, which is small enough for a few glyphs.
+pub fn signed_distance_field(coverage: &[f32], w: usize, h: usize, pad: usize) -> Vec<u8> {
+    let (width, height) = (w + 2 * pad, h + 2 * pad);
+    let inside = |x: isize, y: isize| {
+        let (x, y) = (x - pad as isize, y - pad as isize);
+        x >= 0
+            && y >= 0
+            && (x as usize) < w
+            && (y as usize) < h
+            && coverage[y as usize * w + x as usize] >= 0.5
+    };
+    let reach = GLYPH_SPREAD.ceil() as isize;
+    let mut field = vec![0; width * height];
+    for y in 0..height as isize {
+        for x in 0..width as isize {
+            let here = inside(x, y);
+            let mut nearest = GLYPH_SPREAD * GLYPH_SPREAD;
+            for dy in -reach..=reach {
+                for dx in -reach..=reach {
+                    let d = (dx * dx + dy * dy) as f32;
+                    if d < nearest && inside(x + dx, y + dy) != here {
+                        nearest = d;
+                    }
+                }
+            }
+            // the edge lies half way to the nearest pixel across it
+            let distance = (nearest.sqrt() - 0.5).max(0.0);
+            let signed = if here { distance } else { -distance };
+            field[y as usize * width + x as usize] = (128.0 + signed / GLYPH_SPREAD * 127.0)
+                .round()
+                .clamp(0.0, 255.0) as u8;
+        }
+    }
+    field
+}
@@ -7,17 +7,17 @@ use web_time::{Duration, Instant};
 use crate::{
     content::{tiles, TilePipelineMessage},
     helpers::{channel::Receiver, AbwError},
-    render::SceneGraph,
+    render::{SceneGraph, TextureUploader},
 };
 
 pub fn import_renderables(
     device: &wgpu::Device,
     queue: &wgpu::Queue,
     layout: &wgpu::BindGroupLayout,
+    uploader: &TextureUploader,
     content: &mut SceneGraph,
     receiver: &mut Receiver<TilePipelineMessage>,
     budget: Duration,
-    surface_format: wgpu::TextureFormat,
 ) -> Result<bool, AbwError> {
     if budget.is_zero() {
         return Ok(false);
@@ -28,28 +28,21 @@ pub fn import_renderables(
 
     // Pull tiles until either the channel is empty or the time budget is spent.
     loop {
-        // Respect the budget before starting another expensive setup.
+        // Respect the budget before starting another expensive setup. A
+        // message taken off the channel has to be handled, the pager never
+        // sends it again.
         if start.elapsed() >= budget {
-            //break;
+            break;
         }
 
         match receiver.try_recv() {
             Ok(tile_message) => {
-                // If setup itself can be costly, double-check budget again.
-                if start.elapsed() >= budget {
-                    break;
-                }
-
                 match tile_message {
                     TilePipelineMessage::Load(message) => {
                         let new_tile = tiles::content_render_setup(
-                            device,
-                            queue,
-                            layout,
-                            message.1,
-                            surface_format,
+                            device, queue, layout, uploader, message.1,
                         )?;
-                        content.add_renderable(message.0.key, new_tile);
+                        content.add_renderable(message.0, new_tile);
                     }
                     TilePipelineMessage::Unload(message) => {
                         content.remove(message);
@@ -16,7 +16,7 @@ pub struct Instance3x4 {
 }
 
 impl Instance3x4 {
-    fn build(m: Matrix4<f64>, offset: &Point3<f64>) -> Self {
+    pub(crate) fn build(m: Matrix4<f64>, offset: &Point3<f64>) -> Self {
         let r0 = [
             m.x.x as f32,
             m.y.x as f32,
@@ -83,14 +83,15 @@ pub fn build_instances(
     use rayon::iter::IntoParallelRefIterator;
     use rayon::iter::ParallelIterator;
 
+    // content tiles then globe tiles, the order render draws them in
     frame
         .tiles
         .par_iter()
+        .chain(frame.globe.par_iter())
         .map(|tile_id| {
             with_renderable_state(renderables, *tile_id, |tile| {
-                tile.nodes
-                    .iter()
-                    .map(|n| Instance3x4::build(n.transform, eye_pos))
+                tile.draws()
+                    .map(|(n, mesh)| Instance3x4::build(n.transform * mesh.dequantize, eye_pos))
                     .collect::<Vec<_>>()
             })
             .unwrap_or_default()
@@ -109,13 +110,13 @@ pub fn build_instances(
 ) -> Vec<Instance3x4> {
     let mut out: Vec<Instance3x4> = Vec::new();
 
-    for tile_id in &frame.tiles {
+    for tile_id in frame.tiles.iter().chain(frame.globe.iter()) {
         // If tile/state missing, skip silently.
         let _ = with_renderable_state(renderables, *tile_id, |tile| {
             // Reserve to reduce reallocations.
-            out.reserve(tile.nodes.len());
-            for n in &tile.nodes {
-                out.push(Instance3x4::build(n.transform, eye_pos));
+            out.reserve(tile.meshes.len());
+            for (n, mesh) in tile.draws() {
+                out.push(Instance3x4::build(n.transform * mesh.dequantize, eye_pos));
             }
         });
     }
@@ -18,3 +18,27 @@ pub use scene_graph::*;
 
 pub mod import_renderables;
 pub use import_renderables::*;
+
+pub mod textures;
+pub use textures::*;
+
+pub mod atmosphere;
+pub use atmosphere::*;
+
+pub mod shadows;
+pub use shadows::*;
+
+pub mod msaa;
+pub use msaa::*;
+
+pub mod readback;
+pub use readback::*;
+
+pub mod vectors;
+pub use vectors::*;
+
+pub mod glyphs;
+pub use glyphs::*;
+
+pub mod billboards;
+pub use billboards::*;
@@ -0,0 +1,121 @@
+use crate::render::recommended_format;
+
+/// Sample counts every device renders and resolves with, for any renderable
+/// format, without `TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES`.
+pub const GUARANTEED_SAMPLE_COUNTS: [u32; 2] = [1, 4];
+
+const SAMPLE_COUNTS: [u32; 5] = [16, 8, 4, 2, 1];
+
+/// The largest power of two sample count, no more than `requested`, that
+/// `supported` accepts. 1 is always accepted.
+pub fn pick_sample_count(requested: u32, supported: impl Fn(u32) -> bool) -> u32 {
+    SAMPLE_COUNTS
+        .into_iter()
+        .find(|&count| count == 1 || (count <= requested && supported(count)))
+        .unwrap_or(1)
+}
+
+/// The sample count to request for `Config::msaa_samples`, so that the
+/// color and depth targets both support it on `adapter`. Counts beyond the
+/// guaranteed ones need `device` to have adapter specific format features.
+pub fn supported_sample_count(
+    adapter: &wgpu::Adapter,
+    device: &wgpu::Device,
+    color_format: wgpu::TextureFormat,
+    requested: u32,
+) -> u32 {
+    if !device
+        .features()
+        .contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES)
+    {
+        return pick_sample_count(requested, |count| GUARANTEED_SAMPLE_COUNTS.contains(&count));
+    }
+    let color = adapter.get_texture_format_features(color_format).flags;
+    let depth = adapter
+        .get_texture_format_features(recommended_format(device.features()))
+        .flags;
+    pick_sample_count(requested, |count| {
+        color.sample_count_supported(count)
+            && color.contains(wgpu::TextureFormatFeatureFlags::MULTISAMPLE_RESOLVE)
+            && depth.sample_count_supported(count)
+    })
+}
+
+/// `supported_sample_count` when the host passed its adapter. Without one,
+/// only the guaranteed counts are known to work.
+pub fn device_sample_count(
+    adapter: Option<&wgpu::Adapter>,
+    device: &wgpu::Device,
+    color_format: wgpu::TextureFormat,
+    requested: u32,
+) -> u32 {
+    match adapter {
+        Some(adapter) => supported_sample_count(adapter, device, color_format, requested),
+        None => pick_sample_count(requested, |count| GUARANTEED_SAMPLE_COUNTS.contains(&count)),
+    }
+}
+
+/// Multisampled color target the world draws into and resolves to the
+/// host's surface.
+pub struct MsaaTarget {
+    pub view: wgpu::TextureView,
+    pub format: wgpu::TextureFormat,
+    pub sample_count: u32,
+}
+
+impl MsaaTarget {
+    pub fn new(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("MSAA Color Texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        Self {
+            view,
+            format,
+            sample_count,
+        }
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        *self = Self::new(device, width, height, self.format, self.sample_count);
+    }
+
+    /// Color attachment drawing here and resolving into `surface`. The
+    /// samples themselves are dropped once resolved.
+    pub fn attachment<'a>(
+        &'a self,
+        surface: &'a wgpu::TextureView,
+        load: wgpu::LoadOp<wgpu::Color>,
+    ) -> wgpu::RenderPassColorAttachment<'a> {
+        wgpu::RenderPassColorAttachment {
+            view: &self.view,
+            resolve_target: Some(surface),
+            ops: wgpu::Operations {
+                load,
+                store: wgpu::StoreOp::Discard,
+            },
+            depth_slice: None,
+        }
+    }
+}
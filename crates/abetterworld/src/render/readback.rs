@@ -0,0 +1,350 @@
+//! Offscreen targets and CPU readback for `World::render_to_image`.
+
+use crate::{
+    helpers::AbwError,
+    render::{
+        build_debug_pipeline, build_pipeline, recommended_format, DepthBuffer, RenderPipeline,
+    },
+    ShadowsConfig,
+};
+
+/// A frame rendered by `World::render_to_image`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderedImage {
+    pub width: u32,
+    pub height: u32,
+    /// RGBA8 rows, top first, encoded like the surface format the world was
+    /// created for (sRGB for sRGB formats).
+    pub rgba: Vec<u8>,
+    /// Reverse-Z depth per pixel, top row first: 1 at the near plane,
+    /// falling toward 0 with distance, and 0 where nothing was drawn. The
+    /// globe is squeezed toward the far plane, so it reads as tiny depths.
+    pub depth: Vec<f32>,
+    /// False when the timeout ran out before the view finished loading.
+    pub complete: bool,
+}
+
+/// Depth bits; R32Float isn't renderable on downlevel devices.
+const DEPTH_COPY_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Uint;
+
+/// Row pitch for texture to buffer copies, which must be a multiple of
+/// `COPY_BYTES_PER_ROW_ALIGNMENT`.
+pub fn padded_bytes_per_row(width: u32, bytes_per_pixel: u32) -> u32 {
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    (width * bytes_per_pixel).div_ceil(align) * align
+}
+
+/// Drops the padding `padded_bytes_per_row` added to each row.
+pub fn unpad_rows(data: &[u8], row_bytes: usize, padded_row_bytes: usize) -> Vec<u8> {
+    data.chunks(padded_row_bytes)
+        .flat_map(|row| &row[..row_bytes])
+        .copied()
+        .collect()
+}
+
+/// Color bytes in `format` as RGBA8. Only 8 bit RGBA and BGRA targets can
+/// be read back.
+pub fn to_rgba8(format: wgpu::TextureFormat, mut bytes: Vec<u8>) -> Result<Vec<u8>, AbwError> {
+    use wgpu::TextureFormat::*;
+    match format {
+        Rgba8Unorm | Rgba8UnormSrgb => Ok(bytes),
+        Bgra8Unorm | Bgra8UnormSrgb => {
+            bytes.chunks_exact_mut(4).for_each(|px| px.swap(0, 2));
+            Ok(bytes)
+        }
+        other => Err(AbwError::InvalidInput(format!(
+            "can't read back {other:?} images"
+        ))),
+    }
+}
+
+/// Fullscreen pass writing the depth buffer's bits into an R32Uint target.
+pub struct DepthReadback {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl DepthReadback {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Depth Readback Shader"),
+            source: wgpu::ShaderSource::Wgsl(
+                include_str!("../../assets/depth_readback.wgsl").into(),
+            ),
+        });
+        // Spelled out because a derived layout would ask for a filterable texture
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Depth Readback Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            }],
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Depth Readback Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Depth Readback Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_fullscreen"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_depth"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: DEPTH_COPY_FORMAT,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            cache: None,
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            pipeline,
+            bind_group_layout,
+        }
+    }
+}
+
+/// What `World::render_to_image` draws with, built on its first call.
+pub struct ImagePipelines {
+    /// Single sample copies of a multisampling world's pipelines, swapped in
+    /// while it renders an image so that depth can be read back. None when
+    /// the world doesn't multisample.
+    pub pipeline: Option<RenderPipeline>,
+    pub debug_pipeline: Option<RenderPipeline>,
+    pub depth_readback: DepthReadback,
+}
+
+impl ImagePipelines {
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        shadows: ShadowsConfig,
+        sample_count: u32,
+    ) -> Self {
+        let (pipeline, debug_pipeline) = if sample_count > 1 {
+            let pipeline = build_pipeline(device, config, shadows, 1);
+            let debug_pipeline =
+                build_debug_pipeline(device, config, pipeline.depth.as_ref().unwrap());
+            (Some(pipeline), Some(debug_pipeline))
+        } else {
+            (None, None)
+        };
+
+        Self {
+            pipeline,
+            debug_pipeline,
+            depth_readback: DepthReadback::new(device),
+        }
+    }
+}
+
+/// Single sample color and depth targets sized for one image.
+pub struct OffscreenTargets {
+    pub width: u32,
+    pub height: u32,
+    pub format: wgpu::TextureFormat,
+    pub color: wgpu::Texture,
+    pub color_view: wgpu::TextureView,
+    pub depth: DepthBuffer,
+    depth_copy: wgpu::Texture,
+    depth_copy_view: wgpu::TextureView,
+}
+
+impl OffscreenTargets {
+    pub fn new(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+    ) -> Self {
+        let target = |label, format, usage| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage,
+                view_formats: &[],
+            })
+        };
+        let readable = wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC;
+        let color = target("Offscreen Color Texture", format, readable);
+        let depth_copy = target("Offscreen Depth Copy", DEPTH_COPY_FORMAT, readable);
+
+        Self {
+            width,
+            height,
+            format,
+            color_view: color.create_view(&wgpu::TextureViewDescriptor::default()),
+            color,
+            depth: DepthBuffer::new(
+                device,
+                width,
+                height,
+                recommended_format(device.features()),
+                1,
+            ),
+            depth_copy_view: depth_copy.create_view(&wgpu::TextureViewDescriptor::default()),
+            depth_copy,
+        }
+    }
+
+    /// Color attachment for the world's pass.
+    pub fn color_attachment(&self) -> wgpu::RenderPassColorAttachment<'_> {
+        wgpu::RenderPassColorAttachment {
+            view: &self.color_view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                store: wgpu::StoreOp::Store,
+            },
+            depth_slice: None,
+        }
+    }
+
+    /// Records the depth copy pass and the copies into mappable buffers.
+    /// Call after the world's pass has ended.
+    pub fn encode_readback(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        depth_readback: &DepthReadback,
+    ) -> Readback {
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Depth Readback Bind Group"),
+            layout: &depth_readback.bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&self.depth.depth_view),
+            }],
+        });
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Depth Readback Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &self.depth_copy_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                    depth_slice: None,
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            pass.set_pipeline(&depth_readback.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+
+        let padded_row_bytes = padded_bytes_per_row(self.width, 4);
+        let mut copy = |label, texture: &wgpu::Texture| {
+            let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size: padded_row_bytes as u64 * self.height as u64,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            });
+            encoder.copy_texture_to_buffer(
+                texture.as_image_copy(),
+                wgpu::TexelCopyBufferInfo {
+                    buffer: &buffer,
+                    layout: wgpu::TexelCopyBufferLayout {
+                        offset: 0,
+                        bytes_per_row: Some(padded_row_bytes),
+                        rows_per_image: Some(self.height),
+                    },
+                },
+                wgpu::Extent3d {
+                    width: self.width,
+                    height: self.height,
+                    depth_or_array_layers: 1,
+                },
+            );
+            buffer
+        };
+        let color = copy("Offscreen Color Readback", &self.color);
+        let depth = copy("Offscreen Depth Readback", &self.depth_copy);
+
+        Readback {
+            color,
+            depth,
+            width: self.width,
+            format: self.format,
+        }
+    }
+}
+
+/// Buffers holding a copied frame until it is mapped.
+pub struct Readback {
+    color: wgpu::Buffer,
+    depth: wgpu::Buffer,
+    width: u32,
+    format: wgpu::TextureFormat,
+}
+
+impl Readback {
+    /// Blocks until the GPU has finished the copies, then reads them out as
+    /// RGBA8 and depth.
+    pub fn read(self, device: &wgpu::Device) -> Result<(Vec<u8>, Vec<f32>), AbwError> {
+        let (tx, rx) = std::sync::mpsc::channel();
+        for buffer in [&self.color, &self.depth] {
+            let tx = tx.clone();
+            buffer
+                .slice(..)
+                .map_async(wgpu::MapMode::Read, move |result| {
+                    let _ = tx.send(result);
+                });
+        }
+        drop(tx);
+        device
+            .poll(wgpu::PollType::Wait)
+            .map_err(|e| AbwError::Internal(format!("waiting for readback: {e}")))?;
+        for result in rx.iter() {
+            result.map_err(|e| AbwError::Internal(format!("mapping readback: {e}")))?;
+        }
+
+        let row_bytes = self.width as usize * 4;
+        let padded_row_bytes = padded_bytes_per_row(self.width, 4) as usize;
+        let unpad = |buffer: &wgpu::Buffer| {
+            let data = unpad_rows(
+                &buffer.slice(..).get_mapped_range(),
+                row_bytes,
+                padded_row_bytes,
+            );
+            buffer.unmap();
+            data
+        };
+        let rgba = to_rgba8(self.format, unpad(&self.color))?;
+        let depth = bytemuck::pod_collect_to_vec(&unpad(&self.depth));
+        Ok((rgba, depth))
+    }
+}
@@ -1,14 +1,15 @@
 use crate::{
-    content::{TileKey, MAX_RENDERABLE_TILES_US},
+    content::{TileKey, TileLayer, MAX_RENDERABLE_TILES_US},
     dynamics::FrustumPlanes,
     helpers::{AbwError, Uniforms},
     render::{
         build_instances, get_renderable_tile, rebuild_tile_bg, upload_instances,
-        with_renderable_state, DebugVertex, SceneGraph, SIZE_OF_VOLUME,
+        with_renderable_state, BillboardPipelines, DebugVertex, LightingUniform, SceneGraph,
+        VectorPipelines, VectorUniform, LIGHTING_AERIAL_PERSPECTIVE, SIZE_OF_VOLUME,
     },
     world::WorldPrivate,
 };
-use cgmath::Point3;
+use cgmath::{Matrix4, Point3, SquareMatrix};
 use tracing::{event, Level};
 
 pub struct RenderAndUpdate {
@@ -17,6 +18,17 @@ pub struct RenderAndUpdate {
 
 pub struct RenderFrame {
     pub tiles: Vec<TileKey>,
+    /// Globe tiles, drawn beneath the content tiles.
+    pub globe: Vec<TileKey>,
+}
+
+impl RenderFrame {
+    pub fn empty() -> Self {
+        Self {
+            tiles: Vec::new(),
+            globe: Vec::new(),
+        }
+    }
 }
 
 fn build_up(
@@ -24,7 +36,7 @@ fn build_up(
     root: TileKey,
     tile_culling: bool,
     planes: FrustumPlanes,
-    frame: &mut RenderFrame,
+    out: &mut Vec<TileKey>,
 ) -> bool {
     let renderables = &scene.renderable;
 
@@ -54,14 +66,14 @@ fn build_up(
                         add_this_tile = false;
 
                         for child_key in children_keys.iter() {
-                            build_up(scene, *child_key, tile_culling, planes, frame);
+                            build_up(scene, *child_key, tile_culling, planes, out);
                         }
                     }
                 }
             }
 
             if add_this_tile {
-                frame.tiles.push(tile_guard.key);
+                out.push(tile_guard.key);
             }
 
             Some(true)
@@ -72,14 +84,18 @@ fn build_up(
 
 fn build_frame(scene: &SceneGraph, tile_culling: bool, planes: FrustumPlanes) -> RenderFrame {
     // --- Phase 2: frontier traversal from roots ---
-    let mut frame = RenderFrame { tiles: Vec::new() };
+    let mut frame = RenderFrame::empty();
 
     let renderables = &scene.renderable;
 
     for (key, _renderable_tile) in renderables.iter() {
         //frame.tiles.push(*key);
         if scene.is_root_and_ready(*key) {
-            let _ = build_up(scene, *key, tile_culling, planes, &mut frame);
+            let out = match scene.layer(*key) {
+                Some(TileLayer::Globe) => &mut frame.globe,
+                _ => &mut frame.tiles,
+            };
+            let _ = build_up(scene, *key, tile_culling, planes, out);
         }
     }
 
@@ -89,7 +105,7 @@ fn build_frame(scene: &SceneGraph, tile_culling: bool, planes: FrustumPlanes) ->
 impl RenderAndUpdate {
     pub fn new() -> Self {
         Self {
-            frame: RenderFrame { tiles: Vec::new() },
+            frame: RenderFrame::empty(),
         }
     }
 
@@ -103,36 +119,65 @@ impl RenderAndUpdate {
         render_pass.set_pipeline(&world.pipeline.pipeline);
         render_pass.set_bind_group(0, &world.pipeline.bindings.tile_bg, &[]);
 
-        let mut node_counter: u32 = 0;
+        // Opaque and masked materials first, then blended ones over them. Each
+        // pass walks every draw so instances stay in the order build_instances
+        // wrote them, one per draw.
+        let mut bound = None;
         let renderables = &world.content.renderable;
-        for render_tile_id in self.frame.tiles.iter() {
-            with_renderable_state(renderables, *render_tile_id, |render_tile| {
-                for _node in render_tile.nodes.iter() {
-                    for mesh in render_tile.meshes.iter() {
+        for blend_pass in [false, true] {
+            // The globe goes under everything opaque, its depth squeezed so
+            // tiles always win, then the sky fills whatever is still empty
+            if blend_pass {
+                self.draw_globe(render_pass, world)?;
+                bound = None;
+            }
+            if blend_pass && world.atmosphere.enabled {
+                if let Some(sky) = world.pipeline.sky.as_ref() {
+                    render_pass.set_pipeline(sky);
+                    render_pass.draw(0..3, 0..1);
+                    bound = None;
+                }
+            }
+
+            let mut instance: u32 = 0;
+            for render_tile_id in self.frame.tiles.iter() {
+                with_renderable_state(renderables, *render_tile_id, |render_tile| {
+                    for (_, mesh) in render_tile.draws() {
+                        let material = mesh
+                            .material_index
+                            .and_then(|i| render_tile.materials.get(i))
+                            .unwrap_or(&render_tile.default_material);
+                        instance += 1;
+                        if material.state.blend != blend_pass {
+                            continue;
+                        }
+
+                        let key = (mesh.layout, material.state);
+                        if bound != Some(key) {
+                            render_pass.set_pipeline(world.pipeline.pipeline_for(key.0, key.1));
+                            bound = Some(key);
+                        }
                         render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
                         render_pass.set_index_buffer(
                             mesh.index_buffer.slice(..),
                             wgpu::IndexFormat::Uint32,
                         );
-
-                        if let Some(material_index) = mesh.material_index {
-                            let material = &render_tile.materials[material_index];
-                            if let Some(texture_index) = material.base_color_texture_index {
-                                let texture_resource = &render_tile.textures[texture_index];
-                                render_pass.set_bind_group(1, &texture_resource.bind_group, &[]);
-                            }
-                        }
+                        render_pass.set_bind_group(1, &material.bind_group, &[]);
 
                         // Draw call for this mesh
-                        render_pass.draw_indexed(
-                            0..mesh.num_indices,
-                            0,
-                            node_counter..node_counter + 1,
-                        );
+                        render_pass.draw_indexed(0..mesh.num_indices, 0, instance - 1..instance);
                     }
-                    node_counter += 1;
-                }
-            })?;
+                })?;
+            }
+        }
+
+        // lines and polygons over the lot, unlit
+        if let Some(vectors) = world.pipeline.vectors.as_ref() {
+            world.vectors.draw(render_pass, vectors);
+        }
+        // icons and labels over those
+        if let Some(billboards) = world.pipeline.billboards.as_ref() {
+            world.billboards.draw(render_pass, billboards);
         }
 
         if draw_tile_volumes {
@@ -146,6 +191,45 @@ impl RenderAndUpdate {
         Ok(())
     }
 
+    fn draw_globe(
+        &self,
+        render_pass: &mut wgpu::RenderPass,
+        world: &WorldPrivate,
+    ) -> Result<(), AbwError> {
+        let Some(globe) = world.pipeline.globe.as_ref() else {
+            return Ok(());
+        };
+        if self.frame.globe.is_empty() {
+            return Ok(());
+        }
+        render_pass.set_pipeline(globe);
+
+        // instances follow on from the content tiles'
+        let renderables = &world.content.renderable;
+        let mut instance = self.frame.tiles.iter().try_fold(0u32, |count, tile_id| {
+            with_renderable_state(renderables, *tile_id, |tile| {
+                count + tile.draws().count() as u32
+            })
+        })?;
+        for tile_id in self.frame.globe.iter() {
+            with_renderable_state(renderables, *tile_id, |tile| {
+                for (_, mesh) in tile.draws() {
+                    let material = mesh
+                        .material_index
+                        .and_then(|i| tile.materials.get(i))
+                        .unwrap_or(&tile.default_material);
+                    instance += 1;
+                    render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+                    render_pass
+                        .set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                    render_pass.set_bind_group(1, &material.bind_group, &[]);
+                    render_pass.draw_indexed(0..mesh.num_indices, 0, instance - 1..instance);
+                }
+            })?;
+        }
+        Ok(())
+    }
+
     fn draw_all_tile_volumes(&self, render_pass: &mut wgpu::RenderPass, world: &WorldPrivate) {
         render_pass.set_bind_group(0, &world.debug_pipeline.bindings.tile_bg, &[]);
         render_pass.set_pipeline(&world.debug_pipeline.pipeline);
@@ -330,6 +414,76 @@ impl RenderAndUpdate {
             );
         }
 
+        if let Some(atmosphere) = world.pipeline.bindings.atmosphere.as_mut() {
+            atmosphere.upload(queue);
+        }
+
+        if world.pipeline.vectors.is_none() && world.vectors.len() > 0 {
+            world.pipeline.vectors =
+                VectorPipelines::build_for(device, &world.pipeline, world.surface_config.format);
+        }
+        if let Some(vectors) = world.pipeline.vectors.as_mut() {
+            let uniform = VectorUniform::new(
+                eye_pos,
+                world.camera.viewport(),
+                world.camera.refinement_data().fovy.into(),
+            );
+            vectors.update(device, queue, &world.vectors, eye_pos, &uniform);
+        }
+        if world.pipeline.billboards.is_none() && world.billboards.len() > 0 {
+            world.pipeline.billboards = BillboardPipelines::build_for(
+                device,
+                &world.pipeline,
+                world.surface_config.format,
+                &world.billboards,
+            );
+        }
+        if let Some(billboards) = world.pipeline.billboards.as_mut() {
+            billboards.update(
+                device,
+                queue,
+                &world.billboards,
+                eye_pos,
+                uniform_camera_mvp,
+                world.camera.viewport(),
+            );
+        }
+
+        // sun, atmosphere and shadows, relative to the main camera like the tile positions
+        let inverse_view_proj = Matrix4::from(uniform_camera_mvp.mat)
+            .cast::<f64>()
+            .and_then(|m| m.invert())
+            .unwrap_or_else(Matrix4::identity);
+        if let Some(lighting_buffer) = world.pipeline.bindings.lighting_buffer.as_ref() {
+            let aerial_perspective =
+                world.atmosphere.enabled && world.atmosphere.aerial_perspective;
+            let lighting = LightingUniform::new(
+                world.sun_direction,
+                eye_pos,
+                inverse_view_proj,
+                if aerial_perspective {
+                    LIGHTING_AERIAL_PERSPECTIVE
+                } else {
+                    0
+                },
+            );
+            queue.write_buffer(
+                lighting_buffer,
+                0,
+                bytemuck::cast_slice(std::slice::from_ref(&lighting)),
+            );
+        }
+        if let Some(shadows) = world.pipeline.bindings.shadows.as_ref() {
+            shadows.update(
+                device,
+                queue,
+                &self.frame,
+                &world.content.renderable,
+                (&inverse_view_proj, eye_pos),
+                world.sun_direction,
+            );
+        }
+
         // debug camera
         {
             if let Some(camera_buffer) = world.debug_pipeline.bindings.camera_buffer.as_ref() {
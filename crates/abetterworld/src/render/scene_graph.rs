@@ -2,7 +2,7 @@ use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
 use tracing::{event, Level};
 
-use crate::content::{TileInfo, TileKey, TileMessage};
+use crate::content::{TileInfo, TileKey, TileLayer, TileMessage};
 use crate::helpers::AbwError;
 use crate::render::{RenderTile, RenderableState};
 
@@ -53,6 +53,7 @@ impl SceneGraph {
                 key,
                 gen: 0,
                 tile_info: None,
+                renderable_gen: 0,
                 renderable_state: None,
             }))
         })
@@ -76,6 +77,12 @@ impl SceneGraph {
         false
     }
 
+    pub fn layer(&self, key: TileKey) -> Option<TileLayer> {
+        let ptr = self.renderable.get(&key)?;
+        let rt = ptr.read().expect("RenderTile RwLock poisoned");
+        rt.tile_info.as_ref().map(|info| info.layer)
+    }
+
     pub fn add_info(&mut self, (msg, info): (TileMessage, TileInfo)) {
         let ptr = self.ensure_entry(msg.key).clone();
         let mut rt = ptr.write().expect("RenderTile RwLock poisoned");
@@ -111,7 +118,7 @@ impl SceneGraph {
     pub fn remove(&mut self, msg: TileMessage) {
         if let Some(ptr) = self.renderable.get(&msg.key) {
             let rt = ptr.read().expect("RenderTile RwLock poisoned");
-            let current_max_gen = rt.gen.max(rt.gen);
+            let current_max_gen = rt.gen.max(rt.renderable_gen);
             if msg.gen >= current_max_gen {
                 drop(rt); // release read lock before removing
                 event!(
@@ -141,14 +148,29 @@ impl SceneGraph {
         }
     }
 
-    pub fn add_renderable(&mut self, tile_id: TileKey, renderable_state: RenderableState) {
-        let ptr = self.ensure_entry(tile_id).clone();
+    /// Loads are only ordered among themselves: the info for the same tile
+    /// can be of a later generation than its load by the time it arrives.
+    pub fn add_renderable(&mut self, msg: TileMessage, renderable_state: RenderableState) {
+        let ptr = self.ensure_entry(msg.key).clone();
         let mut rt = ptr.write().expect("RenderTile RwLock poisoned");
-        event!(
-            Level::TRACE,
-            key = tile_id,
-            "SceneGraph: set/update renderable"
-        );
-        rt.renderable_state = Some(renderable_state);
+
+        if msg.gen >= rt.renderable_gen {
+            event!(
+                Level::TRACE,
+                key = msg.key,
+                gen = msg.gen,
+                "SceneGraph: set/update renderable"
+            );
+            rt.renderable_state = Some(renderable_state);
+            rt.renderable_gen = msg.gen;
+        } else {
+            event!(
+                Level::WARN,
+                key = msg.key,
+                incoming_gen = msg.gen,
+                current_gen = rt.renderable_gen,
+                "SceneGraph: stale renderable ignored"
+            );
+        }
     }
 }
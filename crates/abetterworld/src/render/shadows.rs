@@ -0,0 +1,504 @@
+//! Cascaded shadow maps from the sun. The camera frustum out to
+//! `ShadowsConfig::distance` is split into slices, each covered by an
+//! orthographic map fitted around the slice's bounding sphere. Everything
+//! here is camera relative, like the instance transforms, so the light
+//! matrices stay small enough for f32.
+
+use crate::{
+    helpers::Uniforms,
+    render::{with_renderable_state, InstanceBuffer, RenderFrame, RenderableMap, VertexLayout},
+    ShadowsConfig,
+};
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, Vector3, Vector4};
+use std::collections::HashMap;
+
+pub const MAX_CASCADES: usize = 4;
+
+pub const SHADOW_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+/// Blend between logarithmic (1) and uniform (0) cascade splits.
+const SPLIT_LAMBDA: f64 = 0.75;
+/// Splits start at least this far out, so tiny near planes don't squash the
+/// logarithmic ones.
+const MIN_SPLIT_NEAR: f64 = 1.0;
+/// Extra depth toward the sun, in meters, so casters outside a slice still
+/// land in its map.
+const CASTER_MARGIN: f64 = 2_000.0;
+
+/// Cascade selection and lookup for the tile shader; matches `Shadows` in
+/// shader.wgsl.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ShadowUniform {
+    /// Camera relative positions to each cascade's clip space.
+    pub view_proj: [[[f32; 4]; 4]; MAX_CASCADES],
+    /// Camera view direction, w unused; cascades are picked by view depth.
+    pub forward: [f32; 4],
+    /// View depth where each cascade ends.
+    pub splits: [f32; MAX_CASCADES],
+    /// Meters per shadow map texel in each cascade.
+    pub texel_meters: [f32; MAX_CASCADES],
+    /// 0 turns shadows off.
+    pub cascade_count: u32,
+    /// One texel in shadow map UV.
+    pub texel_uv: f32,
+    pub _pad: [u32; 2],
+}
+
+/// One fitted cascade.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cascade {
+    pub view_proj: Matrix4<f64>,
+    /// View depth the cascade covers, from the previous split.
+    pub near: f64,
+    pub far: f64,
+    pub texel_meters: f64,
+}
+
+/// View depths where each of `count` cascades ends, from `near` to `distance`.
+pub fn cascade_splits(near: f64, distance: f64, count: usize) -> Vec<f64> {
+    let near = near.max(MIN_SPLIT_NEAR).min(distance);
+    (1..=count)
+        .map(|i| {
+            let f = i as f64 / count as f64;
+            let log = near * (distance / near).powf(f);
+            let uniform = near + (distance - near) * f;
+            SPLIT_LAMBDA * log + (1.0 - SPLIT_LAMBDA) * uniform
+        })
+        .collect()
+}
+
+/// Orthographic projection onto [-half, half] squared, with depth 0 at
+/// `near` and 1 at `far` in front of the view.
+fn ortho(half: f64, near: f64, far: f64) -> Matrix4<f64> {
+    let depth = far - near;
+    Matrix4::new(
+        1.0 / half,
+        0.0,
+        0.0,
+        0.0,
+        0.0,
+        1.0 / half,
+        0.0,
+        0.0,
+        0.0,
+        0.0,
+        -1.0 / depth,
+        0.0,
+        0.0,
+        0.0,
+        -near / depth,
+        1.0,
+    )
+}
+
+fn unproject(inverse_view_proj: &Matrix4<f64>, x: f64, y: f64) -> Vector3<f64> {
+    // Reverse Z: the near plane is at depth 1
+    let p = inverse_view_proj * Vector4::new(x, y, 1.0, 1.0);
+    p.truncate() / p.w
+}
+
+/// Fits the cascades for a camera given by its camera relative
+/// `inverse_view_proj`. `eye` only anchors the texel snapping, which keeps
+/// shadow edges from crawling as the camera moves.
+pub fn fit_cascades(
+    inverse_view_proj: &Matrix4<f64>,
+    eye: &Point3<f64>,
+    sun: Vector3<f64>,
+    config: &ShadowsConfig,
+) -> Vec<Cascade> {
+    // Near plane corners and center, as rays from the eye
+    let corners = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+        .map(|(x, y)| unproject(inverse_view_proj, x, y));
+    let center = unproject(inverse_view_proj, 0.0, 0.0);
+    let near = center.magnitude();
+
+    // Light basis: z toward the sun
+    let light_z = sun.normalize();
+    let reference = if light_z.z.abs() < 0.99 {
+        Vector3::unit_z()
+    } else {
+        Vector3::unit_x()
+    };
+    let light_x = reference.cross(light_z).normalize();
+    let light_y = light_z.cross(light_x);
+
+    let count = (config.cascades as usize).clamp(1, MAX_CASCADES);
+    let splits = cascade_splits(near, config.distance, count);
+    let resolution = config.resolution.max(1) as f64;
+
+    let mut start = near;
+    splits
+        .into_iter()
+        .map(|end| {
+            let slice: Vec<Vector3<f64>> = [start, end]
+                .into_iter()
+                .flat_map(|depth| corners.map(|c| c * (depth / near)))
+                .collect();
+            let sphere_center = slice.iter().sum::<Vector3<f64>>() / slice.len() as f64;
+            let radius = slice
+                .iter()
+                .map(|p| (p - sphere_center).magnitude())
+                .fold(0.0, f64::max)
+                .ceil();
+
+            // Snap the center to whole texels in a world fixed light frame
+            let texel = 2.0 * radius / resolution;
+            let absolute = eye.to_vec() + sphere_center;
+            let snap = |axis: Vector3<f64>| (absolute.dot(axis) / texel).round() * texel;
+            let snapped =
+                light_x * snap(light_x) + light_y * snap(light_y) + light_z * absolute.dot(light_z)
+                    - eye.to_vec();
+
+            let back = radius + CASTER_MARGIN;
+            let view = Matrix4::look_at_rh(
+                Point3::from_vec(snapped + light_z * back),
+                Point3::from_vec(snapped),
+                light_y,
+            );
+            let cascade = Cascade {
+                view_proj: ortho(radius, 0.0, back + radius) * view,
+                near: start,
+                far: end,
+                texel_meters: texel,
+            };
+            start = end;
+            cascade
+        })
+        .collect()
+}
+
+impl ShadowUniform {
+    pub fn new(cascades: &[Cascade], forward: Vector3<f64>, resolution: u32) -> Self {
+        let mut uniform = Self {
+            view_proj: [[[0.0; 4]; 4]; MAX_CASCADES],
+            forward: [forward.x as f32, forward.y as f32, forward.z as f32, 0.0],
+            splits: [0.0; MAX_CASCADES],
+            texel_meters: [0.0; MAX_CASCADES],
+            cascade_count: cascades.len().min(MAX_CASCADES) as u32,
+            texel_uv: 1.0 / resolution.max(1) as f32,
+            _pad: [0; 2],
+        };
+        for (i, cascade) in cascades.iter().take(MAX_CASCADES).enumerate() {
+            uniform.view_proj[i] = cascade.view_proj.cast::<f32>().unwrap().into();
+            uniform.splits[i] = cascade.far as f32;
+            uniform.texel_meters[i] = cascade.texel_meters as f32;
+        }
+        uniform
+    }
+
+    pub fn disabled() -> Self {
+        Self::new(&[], Vector3::unit_z(), 1)
+    }
+}
+
+/// The cascade maps, one layer each, and the depth-only tile pipelines that
+/// render them. With shadows off there is a single texel layer to bind and
+/// no pipelines.
+pub struct ShadowMaps {
+    pub config: ShadowsConfig,
+    /// All layers, for sampling.
+    pub view: wgpu::TextureView,
+    pub sampler: wgpu::Sampler,
+    pub uniform: wgpu::Buffer,
+    layer_views: Vec<wgpu::TextureView>,
+    cascade_buffers: Vec<wgpu::Buffer>,
+    cascade_bind_groups: Vec<wgpu::BindGroup>,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipelines: HashMap<VertexLayout, wgpu::RenderPipeline>,
+}
+
+impl ShadowMaps {
+    /// `shader` is the tile shader module; only its vertex entry points are used.
+    pub fn new(
+        device: &wgpu::Device,
+        shader: &wgpu::ShaderModule,
+        config: ShadowsConfig,
+        instances: &InstanceBuffer,
+    ) -> Self {
+        let (layers, size) = if config.enabled {
+            (
+                (config.cascades as usize).clamp(1, MAX_CASCADES),
+                config.resolution.max(1),
+            )
+        } else {
+            (1, 1)
+        };
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Shadow Maps"),
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: layers as u32,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: SHADOW_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("Shadow Maps View"),
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let layer_views = (0..layers as u32)
+            .map(|layer| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("Shadow Cascade View"),
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    base_array_layer: layer,
+                    array_layer_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect();
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Shadow Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
+        let uniform = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("shadow_ubo"),
+            size: std::mem::size_of::<ShadowUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        // Each cascade draws the tiles with its own matrix in place of the camera's
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Shadow Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: std::num::NonZeroU64::new(
+                            std::mem::size_of::<Uniforms>() as u64,
+                        ),
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let cascade_buffers = if config.enabled {
+            (0..layers)
+                .map(|_| {
+                    device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some("shadow_cascade_ubo"),
+                        size: std::mem::size_of::<Uniforms>() as u64,
+                        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                        mapped_at_creation: false,
+                    })
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        let pipelines = if config.enabled {
+            let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Shadow Pipeline Layout"),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            });
+            VertexLayout::ALL
+                .into_iter()
+                .map(|vertex_layout| {
+                    let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                        label: Some("Shadow Pipeline"),
+                        layout: Some(&layout),
+                        vertex: wgpu::VertexState {
+                            module: shader,
+                            entry_point: Some(vertex_layout.entry_point()),
+                            buffers: &[vertex_layout.desc()],
+                            compilation_options: Default::default(),
+                        },
+                        fragment: None,
+                        cache: None,
+                        // Photogrammetry isn't reliably closed, so both faces cast
+                        primitive: wgpu::PrimitiveState {
+                            cull_mode: None,
+                            ..Default::default()
+                        },
+                        depth_stencil: Some(wgpu::DepthStencilState {
+                            format: SHADOW_FORMAT,
+                            depth_write_enabled: true,
+                            depth_compare: wgpu::CompareFunction::LessEqual,
+                            stencil: wgpu::StencilState::default(),
+                            bias: wgpu::DepthBiasState {
+                                constant: 2,
+                                slope_scale: 2.0,
+                                clamp: 0.0,
+                            },
+                        }),
+                        multisample: wgpu::MultisampleState::default(),
+                        multiview: None,
+                    });
+                    (vertex_layout, pipeline)
+                })
+                .collect()
+        } else {
+            HashMap::new()
+        };
+
+        let mut maps = Self {
+            config,
+            view,
+            sampler,
+            uniform,
+            layer_views,
+            cascade_buffers,
+            cascade_bind_groups: Vec::new(),
+            bind_group_layout,
+            pipelines,
+        };
+        maps.rebuild_bind_groups(device, instances);
+        maps
+    }
+
+    /// The cascade bind groups hold the instance buffer, so they follow it
+    /// when it grows.
+    pub fn rebuild_bind_groups(&mut self, device: &wgpu::Device, instances: &InstanceBuffer) {
+        self.cascade_bind_groups = self
+            .cascade_buffers
+            .iter()
+            .map(|buffer| {
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("Shadow Cascade Bind Group"),
+                    layout: &self.bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: instances.buf.as_entire_binding(),
+                        },
+                    ],
+                })
+            })
+            .collect();
+    }
+
+    /// Fits the cascades to the camera, uploads them and renders every
+    /// cascade's map from the instances already uploaded for `frame`.
+    pub fn update(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        frame: &RenderFrame,
+        renderables: &RenderableMap,
+        camera: (&Matrix4<f64>, &Point3<f64>),
+        sun: Vector3<f64>,
+    ) {
+        let (inverse_view_proj, eye) = camera;
+        if !self.config.enabled {
+            queue.write_buffer(
+                &self.uniform,
+                0,
+                bytemuck::bytes_of(&ShadowUniform::disabled()),
+            );
+            return;
+        }
+
+        let cascades = fit_cascades(inverse_view_proj, eye, sun, &self.config);
+        let forward = unproject(inverse_view_proj, 0.0, 0.0).normalize();
+        queue.write_buffer(
+            &self.uniform,
+            0,
+            bytemuck::bytes_of(&ShadowUniform::new(
+                &cascades,
+                forward,
+                self.config.resolution,
+            )),
+        );
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Shadow Encoder"),
+        });
+        for ((cascade, buffer), (bind_group, view)) in cascades
+            .iter()
+            .zip(&self.cascade_buffers)
+            .zip(self.cascade_bind_groups.iter().zip(&self.layer_views))
+        {
+            let uniform = Uniforms {
+                mat: cascade.view_proj.cast::<f32>().unwrap().into(),
+            };
+            queue.write_buffer(buffer, 0, bytemuck::bytes_of(&uniform));
+
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Shadow Pass"),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            pass.set_bind_group(0, bind_group, &[]);
+            self.draw_casters(&mut pass, frame, renderables);
+        }
+        queue.submit(std::iter::once(encoder.finish()));
+    }
+
+    /// Every opaque and masked draw, with the instance numbering
+    /// `RenderAndUpdate::render` uses. Blended materials don't cast.
+    fn draw_casters(
+        &self,
+        pass: &mut wgpu::RenderPass,
+        frame: &RenderFrame,
+        renderables: &RenderableMap,
+    ) {
+        let mut instance: u32 = 0;
+        let mut bound = None;
+        for tile in frame.tiles.iter() {
+            let _ = with_renderable_state(renderables, *tile, |render_tile| {
+                for (_, mesh) in render_tile.draws() {
+                    let material = mesh
+                        .material_index
+                        .and_then(|i| render_tile.materials.get(i))
+                        .unwrap_or(&render_tile.default_material);
+                    instance += 1;
+                    if material.state.blend {
+                        continue;
+                    }
+                    let Some(pipeline) = self.pipelines.get(&mesh.layout) else {
+                        continue;
+                    };
+                    if bound != Some(mesh.layout) {
+                        pass.set_pipeline(pipeline);
+                        bound = Some(mesh.layout);
+                    }
+                    pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+                    pass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                    pass.draw_indexed(0..mesh.num_indices, 0, instance - 1..instance);
+                }
+            });
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use crate::{content::TextureEncoding, Config, MipmapMode};
+
+/// Builds mip chains on the GPU by rendering each level from the one above.
+/// Only RGBA8 textures qualify, block compressed formats can't be render
+/// targets.
+pub struct MipmapGenerator {
+    pipeline: wgpu::RenderPipeline,
+    layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    format: wgpu::TextureFormat,
+}
+
+impl MipmapGenerator {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Mipmap Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Mipmap Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Mipmap Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../../assets/mipmap.wgsl").into()),
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Mipmap Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(format.into())],
+                compilation_options: Default::default(),
+            }),
+            cache: None,
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Mipmap Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            pipeline,
+            layout,
+            sampler,
+            format,
+        }
+    }
+
+    /// Texture format this generator renders into.
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.format
+    }
+
+    /// Records passes filling levels `1..mip_level_count` of `texture` from
+    /// level 0. The texture needs `RENDER_ATTACHMENT` and `TEXTURE_BINDING`.
+    pub fn generate(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+        mip_level_count: u32,
+    ) {
+        let level_view = |level: u32| {
+            texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some("Mipmap Level View"),
+                base_mip_level: level,
+                mip_level_count: Some(1),
+                ..Default::default()
+            })
+        };
+
+        for level in 1..mip_level_count {
+            let src = level_view(level - 1);
+            let dst = level_view(level);
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Mipmap Bind Group"),
+                layout: &self.layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&src),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                ],
+            });
+
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Mipmap Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &dst,
+                    depth_slice: None,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+    }
+}
+
+/// Shared state for turning decoded tile textures into GPU textures: the
+/// format RGBA8 data is uploaded as, the one sampler every tile texture uses,
+/// and the GPU mip generators when `MipmapMode::Gpu` is selected.
+pub struct TextureUploader {
+    pub surface_format: wgpu::TextureFormat,
+    pub sampler: wgpu::Sampler,
+    /// One per format RGBA8 textures upload as: color textures take the
+    /// surface format, data textures its linear twin.
+    pub mipmaps: Vec<MipmapGenerator>,
+}
+
+impl TextureUploader {
+    pub fn new(
+        device: &wgpu::Device,
+        surface_format: wgpu::TextureFormat,
+        config: &Config,
+    ) -> Self {
+        // Trilinear, plus anisotropy where the adapter has it. wgpu falls back
+        // to a clamp of 1 on adapters without anisotropic filtering.
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("GLTF Texture Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            anisotropy_clamp: config.max_anisotropy.clamp(1, 16),
+            ..Default::default()
+        });
+
+        let mut mipmaps = Vec::new();
+        if config.mipmaps == MipmapMode::Gpu {
+            let color = TextureEncoding::Rgba8.wgpu_format(surface_format);
+            mipmaps.push(MipmapGenerator::new(device, color));
+            if color.is_srgb() {
+                mipmaps.push(MipmapGenerator::new(device, color.remove_srgb_suffix()));
+            }
+        }
+
+        Self {
+            surface_format,
+            sampler,
+            mipmaps,
+        }
+    }
+
+    /// The GPU mip generator rendering into `format`, if there is one.
+    pub fn mip_generator(&self, format: wgpu::TextureFormat) -> Option<&MipmapGenerator> {
+        self.mipmaps.iter().find(|g| g.format() == format)
+    }
+}
@@ -1,11 +1,15 @@
-use crate::content::{Gen, Material, Node, TileContent, TileInfo, TileKey};
-use std::mem;
+use crate::content::{AlphaMode, Gen, Material, Node, TileInfo, TileKey};
+use cgmath::{Matrix4, Point3, Vector3};
+use std::{mem, ops::Range};
 
 #[derive(Debug, Clone, PartialEq, Default)]
 pub struct RenderTile {
     pub key: TileKey,
     pub gen: Gen,
     pub tile_info: Option<TileInfo>,
+    /// Generation of the load `renderable_state` came from, which the
+    /// pager sends apart from the info updates.
+    pub renderable_gen: Gen,
     pub renderable_state: Option<RenderableState>,
 }
 
@@ -13,8 +17,24 @@ pub struct RenderTile {
 pub struct RenderableState {
     pub nodes: Vec<Node>,
     pub meshes: Vec<Mesh>, // Mesh contains wgpu::Buffer
+    /// Range of `meshes` holding each glTF mesh's primitives.
+    pub mesh_primitives: Vec<Range<usize>>,
     pub textures: Vec<TextureResource>,
-    pub materials: Vec<Material>,
+    /// One per glTF material, in order.
+    pub materials: Vec<MaterialResource>,
+    /// Used by meshes without a material.
+    pub default_material: MaterialResource,
+}
+
+impl RenderableState {
+    /// Every draw in this tile, in order: one per primitive of each node.
+    /// Instances are built from the same sequence, one per draw.
+    pub fn draws(&self) -> impl Iterator<Item = (&Node, &Mesh)> {
+        self.nodes.iter().flat_map(move |node| {
+            node.primitives(&self.mesh_primitives)
+                .filter_map(move |primitive| Some((node, self.meshes.get(primitive)?)))
+        })
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -22,7 +42,171 @@ pub struct TextureResource {
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
     pub sampler: wgpu::Sampler,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MaterialResource {
+    pub uniform: wgpu::Buffer,
     pub bind_group: wgpu::BindGroup,
+    pub state: RenderState,
+}
+
+/// Fixed-function state a material needs. There is one tile pipeline per
+/// combination and vertex layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct RenderState {
+    /// Alpha blended and drawn after everything opaque, without writing depth.
+    pub blend: bool,
+    /// Back faces are drawn too.
+    pub double_sided: bool,
+}
+
+impl RenderState {
+    pub const ALL: [RenderState; 4] = [
+        RenderState {
+            blend: false,
+            double_sided: false,
+        },
+        RenderState {
+            blend: true,
+            double_sided: false,
+        },
+        RenderState {
+            blend: false,
+            double_sided: true,
+        },
+        RenderState {
+            blend: true,
+            double_sided: true,
+        },
+    ];
+
+    pub fn new(material: &Material) -> Self {
+        Self {
+            blend: material.alpha_mode == AlphaMode::Blend,
+            double_sided: material.double_sided,
+        }
+    }
+}
+
+/// `MaterialUniform::flags` bits; match the MATERIAL_ constants in shader.wgsl.
+pub const MATERIAL_MASK: u32 = 1;
+pub const MATERIAL_BLEND: u32 = 2;
+pub const MATERIAL_DOUBLE_SIDED: u32 = 4;
+pub const MATERIAL_UNLIT: u32 = 8;
+pub const MATERIAL_NORMAL_MAP: u32 = 16;
+
+const IDENTITY_UV: [[f32; 4]; 2] = [[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0]];
+
+/// Per-material shader parameters; matches `Material` in shader.wgsl.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MaterialUniform {
+    pub base_color_factor: [f32; 4],
+    /// RGB, w unused.
+    pub emissive_factor: [f32; 4],
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    pub normal_scale: f32,
+    pub occlusion_strength: f32,
+    pub alpha_cutoff: f32,
+    /// `MATERIAL_*` bits.
+    pub flags: u32,
+    /// One bit per texture slot, set when it samples TEXCOORD_1.
+    pub tex_coords: u32,
+    pub _pad: u32,
+    /// Rows of each slot's UV transform, xyz used, in `Material::textures` order.
+    pub uv_transforms: [[[f32; 4]; 2]; 5],
+}
+
+impl MaterialUniform {
+    pub fn new(material: &Material) -> Self {
+        let slots = material.textures();
+        let [r, g, b] = material.emissive_factor;
+        let flags = [
+            (material.alpha_mode == AlphaMode::Mask, MATERIAL_MASK),
+            (material.alpha_mode == AlphaMode::Blend, MATERIAL_BLEND),
+            (material.double_sided, MATERIAL_DOUBLE_SIDED),
+            (material.unlit, MATERIAL_UNLIT),
+            (material.normal_texture.is_some(), MATERIAL_NORMAL_MAP),
+        ]
+        .into_iter()
+        .filter(|(set, _)| *set)
+        .fold(0, |flags, (_, bit)| flags | bit);
+
+        Self {
+            base_color_factor: material.base_color_factor,
+            emissive_factor: [r, g, b, 0.0],
+            metallic_factor: material.metallic_factor,
+            roughness_factor: material.roughness_factor,
+            normal_scale: material.normal_scale,
+            occlusion_strength: material.occlusion_strength,
+            alpha_cutoff: material.alpha_cutoff,
+            flags,
+            tex_coords: slots
+                .iter()
+                .enumerate()
+                .filter(|(_, t)| t.is_some_and(|t| t.tex_coord == 1))
+                .fold(0, |bits, (slot, _)| bits | 1 << slot),
+            _pad: 0,
+            uv_transforms: slots.map(|t| {
+                t.map_or(IDENTITY_UV, |t| {
+                    t.transform.matrix().map(|[a, b, c]| [a, b, c, 0.0])
+                })
+            }),
+        }
+    }
+}
+
+/// Sun radiance on a surface facing it, roughly linear sRGB. A facing white
+/// dielectric comes out close to its base color.
+const SUN_RADIANCE: [f32; 3] = [2.67, 2.6, 2.45];
+/// Sky light reaching everything in daylight; night keeps a fraction of it.
+const AMBIENT: [f32; 3] = [0.13, 0.15, 0.19];
+
+/// Scene lighting for the tile shader; matches `Lighting` in shader.wgsl.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LightingUniform {
+    /// ECEF unit vector toward the sun, w unused.
+    pub sun_direction: [f32; 4],
+    /// RGB, w unused.
+    pub sun_radiance: [f32; 4],
+    /// RGB, w unused.
+    pub ambient: [f32; 4],
+    /// ECEF camera position, which shader positions are relative to, w unused.
+    pub eye: [f32; 4],
+    /// Clip space back to eye relative positions, for the sky's view rays.
+    pub inverse_view_proj: [[f32; 4]; 4],
+    /// `LIGHTING_*` bits.
+    pub flags: u32,
+    pub _pad: [u32; 3],
+}
+
+impl LightingUniform {
+    pub fn new(
+        sun_direction: Vector3<f64>,
+        eye: &Point3<f64>,
+        inverse_view_proj: Matrix4<f64>,
+        flags: u32,
+    ) -> Self {
+        let [r, g, b] = SUN_RADIANCE;
+        let [ar, ag, ab] = AMBIENT;
+        Self {
+            sun_direction: [
+                sun_direction.x as f32,
+                sun_direction.y as f32,
+                sun_direction.z as f32,
+                0.0,
+            ],
+            sun_radiance: [r, g, b, 0.0],
+            ambient: [ar, ag, ab, 0.0],
+            eye: [eye.x as f32, eye.y as f32, eye.z as f32, 0.0],
+            inverse_view_proj: inverse_view_proj.cast::<f32>().unwrap().into(),
+            flags,
+            _pad: [0; 3],
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -31,6 +215,9 @@ pub struct Mesh {
     pub index_buffer: wgpu::Buffer,
     pub num_indices: u32,
     pub material_index: Option<usize>,
+    pub layout: VertexLayout,
+    /// Maps the quantized positions back to mesh space, applied per instance.
+    pub dequantize: Matrix4<f64>,
 }
 
 #[repr(C)]
@@ -62,57 +249,86 @@ impl DebugVertex {
     }
 }
 
-#[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct Vertex {
-    pub position: [f32; 3],
-    pub normal: [f32; 3],
-    pub color: [f32; 4],
-    pub texcoord0: [f32; 2],
-    pub texcoord1: [f32; 2],
+/// Which optional attributes a tile mesh's vertices carry, chosen per mesh
+/// at decode time. Every layout starts with a Snorm16 position relative to
+/// the mesh center, an octahedral Snorm16 normal and a half float
+/// TEXCOORD_0; colors (Unorm8) and TEXCOORD_1 (half float) follow only when
+/// the mesh has them. The position's w is 1 when the normal is real and 0
+/// when the mesh had none, so the shader falls back to flat normals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct VertexLayout {
+    pub color: bool,
+    pub texcoord1: bool,
 }
 
-impl Vertex {
-    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+const BASE_ATTRIBUTES: [wgpu::VertexAttribute; 3] =
+    wgpu::vertex_attr_array![0 => Snorm16x4, 1 => Snorm16x2, 3 => Float16x2];
+const COLOR_ATTRIBUTES: [wgpu::VertexAttribute; 4] =
+    wgpu::vertex_attr_array![0 => Snorm16x4, 1 => Snorm16x2, 2 => Unorm8x4, 3 => Float16x2];
+const TEXCOORD1_ATTRIBUTES: [wgpu::VertexAttribute; 4] =
+    wgpu::vertex_attr_array![0 => Snorm16x4, 1 => Snorm16x2, 3 => Float16x2, 4 => Float16x2];
+const COLOR_TEXCOORD1_ATTRIBUTES: [wgpu::VertexAttribute; 5] = wgpu::vertex_attr_array![
+    0 => Snorm16x4, 1 => Snorm16x2, 2 => Unorm8x4, 3 => Float16x2, 4 => Float16x2
+];
+
+impl VertexLayout {
+    pub const ALL: [VertexLayout; 4] = [
+        VertexLayout {
+            color: false,
+            texcoord1: false,
+        },
+        VertexLayout {
+            color: true,
+            texcoord1: false,
+        },
+        VertexLayout {
+            color: false,
+            texcoord1: true,
+        },
+        VertexLayout {
+            color: true,
+            texcoord1: true,
+        },
+    ];
+
+    /// Bytes per vertex.
+    pub fn stride(self) -> usize {
+        16 + if self.color { 4 } else { 0 } + if self.texcoord1 { 4 } else { 0 }
+    }
+
+    /// Packs the layout into the bits stored in the decoded tile cache.
+    pub fn bits(self) -> u32 {
+        self.color as u32 | (self.texcoord1 as u32) << 1
+    }
+
+    pub fn from_bits(bits: u32) -> Option<Self> {
+        (bits < 4).then_some(VertexLayout {
+            color: bits & 1 != 0,
+            texcoord1: bits & 2 != 0,
+        })
+    }
+
+    /// Vertex shader entry point in shader.wgsl reading this layout.
+    pub fn entry_point(self) -> &'static str {
+        match (self.color, self.texcoord1) {
+            (false, false) => "vs_main",
+            (true, false) => "vs_main_color",
+            (false, true) => "vs_main_uv1",
+            (true, true) => "vs_main_color_uv1",
+        }
+    }
+
+    pub fn desc(self) -> wgpu::VertexBufferLayout<'static> {
+        let attributes: &'static [wgpu::VertexAttribute] = match (self.color, self.texcoord1) {
+            (false, false) => &BASE_ATTRIBUTES,
+            (true, false) => &COLOR_ATTRIBUTES,
+            (false, true) => &TEXCOORD1_ATTRIBUTES,
+            (true, true) => &COLOR_TEXCOORD1_ATTRIBUTES,
+        };
         wgpu::VertexBufferLayout {
-            array_stride: mem::size_of::<Vertex>() as wgpu::BufferAddress,
+            array_stride: self.stride() as wgpu::BufferAddress,
             step_mode: wgpu::VertexStepMode::Vertex,
-            attributes: &[
-                // Positions
-                wgpu::VertexAttribute {
-                    offset: 0,
-                    shader_location: 0,
-                    format: wgpu::VertexFormat::Float32x3,
-                },
-                // Normals
-                wgpu::VertexAttribute {
-                    offset: mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
-                    shader_location: 1,
-                    format: wgpu::VertexFormat::Float32x3,
-                },
-                // Colors
-                wgpu::VertexAttribute {
-                    offset: (mem::size_of::<[f32; 3]>() * 2) as wgpu::BufferAddress,
-                    shader_location: 2,
-                    format: wgpu::VertexFormat::Float32x4,
-                },
-                // Texcoord0
-                wgpu::VertexAttribute {
-                    offset: (mem::size_of::<[f32; 3]>() * 2 + mem::size_of::<[f32; 4]>())
-                        as wgpu::BufferAddress,
-                    shader_location: 3,
-                    format: wgpu::VertexFormat::Float32x2,
-                },
-                // Texcoord1
-                wgpu::VertexAttribute {
-                    offset: (mem::size_of::<[f32; 3]>() * 2
-                        + mem::size_of::<[f32; 4]>()
-                        + mem::size_of::<[f32; 2]>())
-                        as wgpu::BufferAddress,
-                    shader_location: 4,
-                    format: wgpu::VertexFormat::Float32x2,
-                },
-            ],
+            attributes,
         }
     }
 }
@@ -0,0 +1,757 @@
+use std::{collections::BTreeMap, mem, ops::Range};
+
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, Vector3};
+use wgpu::util::DeviceExt;
+
+use crate::{
+    helpers::{signed_area, triangulate, Uniforms},
+    render::{DepthBuffer, Instance3x4, InstanceBuffer, RenderPipeline},
+    world::{Location, Polygon, Polyline, VectorId, VectorShape},
+};
+
+/// Heights the drape volumes of clamped shapes reach between, enough for
+/// any ground on Earth; match the constants in vectors.wgsl.
+pub const DRAPE_MIN_HEIGHT: f64 = -500.0;
+pub const DRAPE_MAX_HEIGHT: f64 = 9000.0;
+
+/// Longest polyline segment, in degrees, before it's split along the great circle.
+const MAX_SEGMENT_DEGREES: f64 = 0.5;
+
+/// Corners of a segment's quad, numbered end + 2 × left.
+pub const QUAD_CORNERS: [usize; 6] = [0, 1, 3, 0, 3, 2];
+
+/// Corners of a segment's drape box, numbered end + 2 × left + 4 × top, in
+/// counter-clockwise triangles seen from outside.
+pub const BOX_CORNERS: [usize; 36] = [
+    0, 2, 3, 0, 3, 1, // bottom
+    4, 5, 7, 4, 7, 6, // top
+    0, 1, 5, 0, 5, 4, // right
+    2, 6, 7, 2, 7, 3, // left
+    0, 4, 6, 0, 6, 2, // start
+    1, 3, 7, 1, 7, 5, // end
+];
+
+/// A vertex of a vector overlay, relative to its shape's center.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct VectorVertex {
+    pub position: [f32; 3],
+    /// The segment's other end for line corners, `position` otherwise.
+    pub other: [f32; 3],
+    /// Which corner of a line this is: x 1 on the left of the segment and −1
+    /// on the right, y 0 at its start and 1 at its end, z 0 at the bottom of
+    /// a drape volume and 1 at its top. w is the line's width in pixels.
+    /// All zero for fills.
+    pub extrude: [f32; 4],
+    /// sRGB with straight alpha.
+    pub color: [u8; 4],
+}
+
+impl VectorVertex {
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        const ATTRIBUTES: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![
+            0 => Float32x3,
+            1 => Float32x3,
+            2 => Float32x4,
+            3 => Unorm8x4,
+        ];
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<VectorVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &ATTRIBUTES,
+        }
+    }
+}
+
+/// What the vector shader needs besides the camera; matches `Vectors` in
+/// vectors.wgsl.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct VectorUniform {
+    /// In pixels.
+    pub viewport: [f32; 2],
+    /// Meters a pixel covers one meter in front of the camera.
+    pub pixel_scale: f32,
+    pub _pad: f32,
+    /// ECEF, w unused.
+    pub eye: [f32; 4],
+}
+
+impl VectorUniform {
+    pub fn new(eye: &Point3<f64>, viewport: (f64, f64), fovy: cgmath::Rad<f64>) -> Self {
+        Self {
+            viewport: [viewport.0 as f32, viewport.1 as f32],
+            pixel_scale: (2.0 * (fovy.0 * 0.5).tan() / viewport.1.max(1.0)) as f32,
+            _pad: 0.0,
+            eye: [eye.x as f32, eye.y as f32, eye.z as f32, 0.0],
+        }
+    }
+}
+
+/// The groups of a shape's triangles, each drawn with its own pipeline, in
+/// the order they're drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VectorPass {
+    /// Clamped polygons' volumes, painted wherever a tile is inside them.
+    /// Without a stencil buffer, tiles in front of the volume are painted
+    /// too.
+    DrapedFills,
+    /// Clamped polygons on the ellipsoid, for where there are no tiles.
+    GroundFills,
+    Fills,
+    DrapedLines,
+    GroundLines,
+    Lines,
+}
+
+impl VectorPass {
+    pub const ALL: [VectorPass; 6] = [
+        VectorPass::DrapedFills,
+        VectorPass::GroundFills,
+        VectorPass::Fills,
+        VectorPass::DrapedLines,
+        VectorPass::GroundLines,
+        VectorPass::Lines,
+    ];
+}
+
+/// A shape's triangles, grouped by `VectorPass`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VectorMesh {
+    /// ECEF; the vertices are relative to it.
+    pub center: Point3<f64>,
+    pub vertices: Vec<VectorVertex>,
+    /// Range of `vertices` for each pass, in `VectorPass::ALL` order.
+    pub passes: [Range<u32>; 6],
+}
+
+// corner of a line or drape box, before it's made relative to the center
+#[derive(Clone, Copy)]
+struct Corner {
+    position: Point3<f64>,
+    other: Point3<f64>,
+    extrude: [f32; 4],
+}
+
+impl VectorMesh {
+    pub fn new(shape: &VectorShape) -> Self {
+        let mut groups: [Vec<Corner>; 6] = Default::default();
+        let (color, built) = match shape {
+            VectorShape::Polyline(line) => (line.color, polyline_corners(line, &mut groups)),
+            VectorShape::Polygon(polygon) => (polygon.color, polygon_corners(polygon, &mut groups)),
+        };
+
+        let count = groups.iter().map(Vec::len).sum::<usize>().max(1);
+        let center = if built {
+            let sum = groups
+                .iter()
+                .flatten()
+                .fold(Vector3::new(0.0, 0.0, 0.0), |sum, c| {
+                    sum + c.position.to_vec()
+                });
+            Point3::from_vec(sum / count as f64)
+        } else {
+            Point3::origin()
+        };
+
+        let relative = |p: Point3<f64>| {
+            let v = p - center;
+            [v.x as f32, v.y as f32, v.z as f32]
+        };
+        let mut vertices = Vec::with_capacity(count);
+        let passes = groups.map(|group| {
+            let start = vertices.len() as u32;
+            vertices.extend(group.into_iter().map(|c| VectorVertex {
+                position: relative(c.position),
+                other: relative(c.other),
+                extrude: c.extrude,
+                color,
+            }));
+            start..vertices.len() as u32
+        });
+
+        Self {
+            center,
+            vertices,
+            passes,
+        }
+    }
+
+    pub fn pass(&self, pass: VectorPass) -> Range<u32> {
+        self.passes[pass as usize].clone()
+    }
+}
+
+fn geodetic(location: Location, clamp_to_ground: bool) -> (f64, f64, f64) {
+    let (lat, lon, height) = location.to_geodetic();
+    (lat, lon, if clamp_to_ground { 0.0 } else { height })
+}
+
+/// The line's points with long segments split, ECEF.
+fn densify(line: &Polyline) -> Vec<Point3<f64>> {
+    let points: Vec<_> = line
+        .positions
+        .iter()
+        .map(|p| geodetic(*p, line.clamp_to_ground))
+        .collect();
+    let mut out = Vec::with_capacity(points.len());
+    for (i, &(lat, lon, height)) in points.iter().enumerate() {
+        let Some(&(next_lat, next_lon, next_height)) = points.get(i + 1) else {
+            out.push(Location::Geodetic(lat, lon, height).to_ecef());
+            break;
+        };
+        let a = Location::Geodetic(lat, lon, 0.0).to_ecef();
+        let b = Location::Geodetic(next_lat, next_lon, 0.0).to_ecef();
+        let angle = a.to_vec().angle(b.to_vec()).0.to_degrees();
+        let steps = (angle / MAX_SEGMENT_DEGREES).ceil().max(1.0) as usize;
+        for step in 0..steps {
+            let t = step as f64 / steps as f64;
+            let (lat, lon, _) = Location::Geocentric(
+                a.x + (b.x - a.x) * t,
+                a.y + (b.y - a.y) * t,
+                a.z + (b.z - a.z) * t,
+            )
+            .to_geodetic();
+            let height = height + (next_height - height) * t;
+            out.push(Location::Geodetic(lat, lon, height).to_ecef());
+        }
+    }
+    out.dedup();
+    out
+}
+
+fn polyline_corners(line: &Polyline, groups: &mut [Vec<Corner>; 6]) -> bool {
+    let points = densify(line);
+    if points.len() < 2 {
+        return false;
+    }
+    let corner = |a: Point3<f64>, b: Point3<f64>, index: usize| {
+        let (end, left, top) = (index & 1, (index >> 1) & 1, index >> 2);
+        let (position, other) = if end == 0 { (a, b) } else { (b, a) };
+        Corner {
+            position,
+            other,
+            extrude: [
+                if left == 1 { 1.0 } else { -1.0 },
+                end as f32,
+                top as f32,
+                line.width,
+            ],
+        }
+    };
+    for segment in points.windows(2) {
+        let (a, b) = (segment[0], segment[1]);
+        if line.clamp_to_ground {
+            groups[VectorPass::GroundLines as usize]
+                .extend(QUAD_CORNERS.iter().map(|&i| corner(a, b, i)));
+            groups[VectorPass::DrapedLines as usize]
+                .extend(BOX_CORNERS.iter().map(|&i| corner(a, b, i)));
+        } else {
+            groups[VectorPass::Lines as usize]
+                .extend(QUAD_CORNERS.iter().map(|&i| corner(a, b, i)));
+        }
+    }
+    true
+}
+
+/// A ring's points without a repeated first point.
+fn open_ring(ring: &[Location]) -> &[Location] {
+    match ring {
+        [first, .., last] if first == last => &ring[..ring.len() - 1],
+        _ => ring,
+    }
+}
+
+fn polygon_corners(polygon: &Polygon, groups: &mut [Vec<Corner>; 6]) -> bool {
+    let exterior = open_ring(&polygon.exterior);
+    if exterior.len() < 3 {
+        return false;
+    }
+    let rings: Vec<Vec<(f64, f64, f64)>> = std::iter::once(exterior)
+        .chain(polygon.holes.iter().map(|hole| open_ring(hole)))
+        .filter(|ring| ring.len() >= 3)
+        .map(|ring| {
+            ring.iter()
+                .map(|p| geodetic(*p, polygon.clamp_to_ground))
+                .collect()
+        })
+        .collect();
+
+    // flattened onto the plane touching the ellipsoid under the middle
+    let ground = |&(lat, lon, _): &(f64, f64, f64)| Location::Geodetic(lat, lon, 0.0).to_ecef();
+    let origin = Point3::from_vec(
+        rings[0].iter().fold(Vector3::new(0.0, 0.0, 0.0), |sum, p| {
+            sum + ground(p).to_vec()
+        }) / rings[0].len() as f64,
+    );
+    let up = origin.to_vec().normalize();
+    let east = Vector3::unit_z().cross(up);
+    let east = if east.magnitude2() > 1e-12 {
+        east.normalize()
+    } else {
+        Vector3::unit_y()
+    };
+    let north = up.cross(east);
+    let flat: Vec<Vec<[f64; 2]>> = rings
+        .iter()
+        .map(|ring| {
+            ring.iter()
+                .map(|p| {
+                    let v = ground(p) - origin;
+                    [v.dot(east), v.dot(north)]
+                })
+                .collect()
+        })
+        .collect();
+    let triangles = triangulate(&flat);
+    if triangles.is_empty() {
+        return false;
+    }
+
+    let points: Vec<(f64, f64, f64)> = rings.iter().flatten().copied().collect();
+    let at = |&(lat, lon, height): &(f64, f64, f64), offset: f64| {
+        let position = Location::Geodetic(lat, lon, height + offset).to_ecef();
+        Corner {
+            position,
+            other: position,
+            extrude: [0.0; 4],
+        }
+    };
+    if !polygon.clamp_to_ground {
+        groups[VectorPass::Fills as usize]
+            .extend(triangles.iter().map(|&i| at(&points[i as usize], 0.0)));
+        return true;
+    }
+
+    groups[VectorPass::GroundFills as usize]
+        .extend(triangles.iter().map(|&i| at(&points[i as usize], 0.0)));
+
+    // a closed prism, every face wound counter-clockwise from outside
+    let volume = &mut groups[VectorPass::DrapedFills as usize];
+    volume.extend(
+        triangles
+            .iter()
+            .map(|&i| at(&points[i as usize], DRAPE_MAX_HEIGHT)),
+    );
+    volume.extend(
+        triangles
+            .chunks(3)
+            .flat_map(|t| [t[0], t[2], t[1]])
+            .map(|i| at(&points[i as usize], DRAPE_MIN_HEIGHT)),
+    );
+    for (r, (ring, flat)) in rings.iter().zip(&flat).enumerate() {
+        // outer ring counter-clockwise and holes clockwise, so the solid is
+        // always on the left
+        let reversed = (signed_area(flat) > 0.0) != (r == 0);
+        for i in 0..ring.len() {
+            let (mut a, mut b) = (&ring[i], &ring[(i + 1) % ring.len()]);
+            if reversed {
+                mem::swap(&mut a, &mut b);
+            }
+            let (a0, b0) = (at(a, DRAPE_MIN_HEIGHT), at(b, DRAPE_MIN_HEIGHT));
+            let (a1, b1) = (at(a, DRAPE_MAX_HEIGHT), at(b, DRAPE_MAX_HEIGHT));
+            volume.extend([a0, b0, b1, a0, b1, a1]);
+        }
+    }
+    true
+}
+
+struct VectorEntry {
+    mesh: VectorMesh,
+    /// None until uploaded, and for shapes with nothing to draw.
+    buffer: Option<wgpu::Buffer>,
+}
+
+/// The shapes added to the world, drawn in the order they were added.
+pub struct VectorLayer {
+    next_id: u64,
+    shapes: BTreeMap<VectorId, VectorEntry>,
+    /// Shapes changed since the last upload.
+    dirty: bool,
+}
+
+impl VectorLayer {
+    pub fn new() -> Self {
+        Self {
+            next_id: 0,
+            shapes: BTreeMap::new(),
+            dirty: false,
+        }
+    }
+
+    pub fn add(&mut self, shape: &VectorShape) -> VectorId {
+        let id = VectorId(self.next_id);
+        self.next_id += 1;
+        self.set(id, shape);
+        id
+    }
+
+    /// Replaces the shape `id` names, returning false if there's none.
+    pub fn replace(&mut self, id: VectorId, shape: &VectorShape) -> bool {
+        if !self.shapes.contains_key(&id) {
+            return false;
+        }
+        self.set(id, shape);
+        true
+    }
+
+    fn set(&mut self, id: VectorId, shape: &VectorShape) {
+        let entry = VectorEntry {
+            mesh: VectorMesh::new(shape),
+            buffer: None,
+        };
+        self.shapes.insert(id, entry);
+        self.dirty = true;
+    }
+
+    pub fn remove(&mut self, id: VectorId) -> bool {
+        let removed = self.shapes.remove(&id).is_some();
+        self.dirty |= removed;
+        removed
+    }
+
+    pub fn len(&self) -> usize {
+        self.shapes.len()
+    }
+
+    /// Creates vertex buffers for the shapes added or replaced since the
+    /// last call, returning whether anything changed.
+    pub fn upload(&mut self, device: &wgpu::Device) -> bool {
+        if !mem::take(&mut self.dirty) {
+            return false;
+        }
+        for entry in self.shapes.values_mut() {
+            if entry.buffer.is_some() || entry.mesh.vertices.is_empty() {
+                continue;
+            }
+            entry.buffer = Some(
+                device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Vector Vertices"),
+                    contents: bytemuck::cast_slice(&entry.mesh.vertices),
+                    usage: wgpu::BufferUsages::VERTEX,
+                }),
+            );
+        }
+        true
+    }
+
+    /// One per shape, in draw order, placing its center relative to the eye.
+    pub fn instances(&self, eye_pos: &Point3<f64>) -> Vec<Instance3x4> {
+        self.shapes
+            .values()
+            .map(|entry| {
+                Instance3x4::build(
+                    Matrix4::from_translation(entry.mesh.center.to_vec()),
+                    eye_pos,
+                )
+            })
+            .collect()
+    }
+
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass, pipelines: &VectorPipelines) {
+        if self.shapes.is_empty() {
+            return;
+        }
+        render_pass.set_bind_group(0, &pipelines.bind_group, &[]);
+        let passes = VectorPass::ALL
+            .iter()
+            .zip(&pipelines.pipelines)
+            .zip(&pipelines.volumes);
+        for ((pass, pipeline), volume) in passes {
+            let mut bound = false;
+            for (instance, entry) in self.shapes.values().enumerate() {
+                let (Some(buffer), range) = (entry.buffer.as_ref(), entry.mesh.pass(*pass)) else {
+                    continue;
+                };
+                if range.is_empty() {
+                    continue;
+                }
+                let instance = instance as u32;
+                render_pass.set_vertex_buffer(0, buffer.slice(..));
+                // draped shapes go one at a time: count, then paint and clear
+                if let Some(volume) = volume {
+                    render_pass.set_pipeline(volume);
+                    render_pass.draw(range.clone(), instance..instance + 1);
+                    render_pass.set_pipeline(pipeline);
+                } else if !mem::replace(&mut bound, true) {
+                    render_pass.set_pipeline(pipeline);
+                }
+                render_pass.draw(range, instance..instance + 1);
+            }
+        }
+    }
+}
+
+impl Default for VectorLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Pipelines for each `VectorPass` and the buffers they share. Vectors are
+/// placed with instances of their own, like tiles, but only read the camera
+/// from the tile bindings.
+pub struct VectorPipelines {
+    /// In `VectorPass::ALL` order.
+    pub pipelines: Vec<wgpu::RenderPipeline>,
+    /// For the draped passes, the pipeline counting each shape's volume into
+    /// the stencil before the one in `pipelines` paints it; None otherwise,
+    /// and when the depth buffer has no stencil.
+    pub volumes: Vec<Option<wgpu::RenderPipeline>>,
+    pub bind_group: wgpu::BindGroup,
+    bind_group_layout: wgpu::BindGroupLayout,
+    camera: wgpu::Buffer,
+    instances: InstanceBuffer,
+    uniform: wgpu::Buffer,
+}
+
+impl VectorPipelines {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        camera: &wgpu::Buffer,
+        depth: &DepthBuffer,
+    ) -> Self {
+        let uniform_entry = |binding: u32, size: usize| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: std::num::NonZeroU64::new(size as u64),
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Vector Bind Group Layout"),
+            entries: &[
+                // binding(0) camera
+                uniform_entry(0, mem::size_of::<Uniforms>()),
+                // binding(1) one instance per shape
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                // binding(2) viewport and eye
+                uniform_entry(2, mem::size_of::<VectorUniform>()),
+            ],
+        });
+        let uniform = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("vector_ubo"),
+            size: mem::size_of::<VectorUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let instances = InstanceBuffer::new(device, 64);
+        let bind_group = create_vector_bg(device, &bind_group_layout, camera, &instances, &uniform);
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Vector Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../../assets/vectors.wgsl").into()),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Vector Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        // Blended over everything, testing depth without writing it.
+        //
+        // With stencil, drape volumes are stencil shadow volumes. Counting,
+        // faces behind the depth already drawn add one on the back and take
+        // one away on the front, so the count is left nonzero where a tile
+        // is inside the volume, even with the camera inside it too. Painting
+        // then ignores depth, draws where the count isn't zero and clears
+        // it, so each pixel is painted once and the next shape starts from
+        // zero. Without stencil, volumes paint their back faces wherever
+        // they're behind a tile, which with reverse-Z is a smaller depth, so
+        // tiles between the camera and the volume are painted too.
+        let volumes = depth.format.has_stencil_aspect();
+        let build = |pass: VectorPass, counting: bool| {
+            let (vertex, fragment) = match pass {
+                VectorPass::DrapedFills => ("vs_fill", "fs_vector"),
+                VectorPass::GroundFills => ("vs_fill", "fs_ground"),
+                VectorPass::Fills => ("vs_fill", "fs_vector"),
+                VectorPass::DrapedLines => ("vs_drape_line", "fs_vector"),
+                VectorPass::GroundLines => ("vs_line", "fs_ground"),
+                VectorPass::Lines => ("vs_line", "fs_vector"),
+            };
+            let draped = matches!(pass, VectorPass::DrapedFills | VectorPass::DrapedLines);
+            let (cull_mode, depth_compare) = if !draped || counting {
+                (None, depth.compare_fn())
+            } else if volumes {
+                (None, wgpu::CompareFunction::Always)
+            } else {
+                (Some(wgpu::Face::Front), wgpu::CompareFunction::Less)
+            };
+            let face = |depth_fail_op, compare, pass_op| wgpu::StencilFaceState {
+                compare,
+                fail_op: wgpu::StencilOperation::Keep,
+                depth_fail_op,
+                pass_op,
+            };
+            let stencil = if counting {
+                wgpu::StencilState {
+                    front: face(
+                        wgpu::StencilOperation::DecrementWrap,
+                        wgpu::CompareFunction::Always,
+                        wgpu::StencilOperation::Keep,
+                    ),
+                    back: face(
+                        wgpu::StencilOperation::IncrementWrap,
+                        wgpu::CompareFunction::Always,
+                        wgpu::StencilOperation::Keep,
+                    ),
+                    read_mask: 0xff,
+                    write_mask: 0xff,
+                }
+            } else if draped && volumes {
+                let paint = face(
+                    wgpu::StencilOperation::Keep,
+                    wgpu::CompareFunction::NotEqual,
+                    wgpu::StencilOperation::Zero,
+                );
+                wgpu::StencilState {
+                    front: paint,
+                    back: paint,
+                    read_mask: 0xff,
+                    write_mask: 0xff,
+                }
+            } else {
+                wgpu::StencilState::default()
+            };
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Vector Pipeline"),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some(vertex),
+                    buffers: &[VectorVertex::desc()],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some(fragment),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: if counting {
+                            wgpu::ColorWrites::empty()
+                        } else {
+                            wgpu::ColorWrites::ALL
+                        },
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                cache: None,
+                primitive: wgpu::PrimitiveState {
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode,
+                    ..Default::default()
+                },
+                depth_stencil: depth
+                    .depth_stencil_state()
+                    .map(|d| wgpu::DepthStencilState {
+                        depth_write_enabled: false,
+                        depth_compare,
+                        stencil,
+                        ..d
+                    }),
+                multisample: wgpu::MultisampleState {
+                    count: depth.sample_count,
+                    ..Default::default()
+                },
+                multiview: None,
+            })
+        };
+
+        Self {
+            pipelines: VectorPass::ALL
+                .into_iter()
+                .map(|pass| build(pass, false))
+                .collect(),
+            volumes: VectorPass::ALL
+                .into_iter()
+                .map(|pass| {
+                    let draped = matches!(pass, VectorPass::DrapedFills | VectorPass::DrapedLines);
+                    (volumes && draped).then(|| build(pass, true))
+                })
+                .collect(),
+            bind_group,
+            bind_group_layout,
+            camera: camera.clone(),
+            instances,
+            uniform,
+        }
+    }
+
+    /// Vector pipelines drawing into `pipeline`'s targets with its camera,
+    /// or None if it has neither, like the debug pipeline.
+    pub fn build_for(
+        device: &wgpu::Device,
+        pipeline: &RenderPipeline,
+        format: wgpu::TextureFormat,
+    ) -> Option<Self> {
+        let camera = pipeline.bindings.camera_buffer.as_ref()?;
+        Some(Self::new(device, format, camera, pipeline.depth.as_ref()?))
+    }
+
+    /// Places every shape relative to the eye for the frame about to be drawn.
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layer: &VectorLayer,
+        eye_pos: &Point3<f64>,
+        uniform: &VectorUniform,
+    ) {
+        queue.write_buffer(&self.uniform, 0, bytemuck::bytes_of(uniform));
+        if layer.len() == 0 {
+            return;
+        }
+        let instances = layer.instances(eye_pos);
+        if self.instances.ensure_capacity(device, instances.len()) {
+            self.bind_group = create_vector_bg(
+                device,
+                &self.bind_group_layout,
+                &self.camera,
+                &self.instances,
+                &self.uniform,
+            );
+        }
+        queue.write_buffer(&self.instances.buf, 0, bytemuck::cast_slice(&instances));
+    }
+}
+
+fn create_vector_bg(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    camera: &wgpu::Buffer,
+    instances: &InstanceBuffer,
+    uniform: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Vector Bind Group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: instances.buf.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: uniform.as_entire_binding(),
+            },
+        ],
+    })
+}
@@ -3,47 +3,103 @@ use wgpu::{util::DeviceExt, TextureFormat};
 use crate::{
     content::{MAX_RENDERABLE_NODES_US, MAX_RENDERABLE_TILES},
     helpers::Uniforms,
-    render::{recommended_format, DebugVertex, DepthBuffer, InstanceBuffer, Vertex},
+    render::{
+        recommended_format, AtmosphereTextures, BillboardPipelines, DebugVertex, DepthBuffer,
+        InstanceBuffer, LightingUniform, MaterialUniform, MsaaTarget, RenderState, ShadowMaps,
+        ShadowUniform, VectorPipelines, VertexLayout,
+    },
+    ShadowsConfig,
 };
+use std::collections::HashMap;
 
 pub struct BindingData {
     pub tile_bg: wgpu::BindGroup,
     pub tile_bg_layout: wgpu::BindGroupLayout,
     pub instance_buffer: Option<InstanceBuffer>,
     pub camera_buffer: Option<wgpu::Buffer>,
+    pub lighting_buffer: Option<wgpu::Buffer>,
+    pub atmosphere: Option<AtmosphereTextures>,
+    pub shadows: Option<ShadowMaps>,
 }
 
 pub struct RenderPipeline {
     pub pipeline: wgpu::RenderPipeline,
+    /// Tile pipelines keyed by the vertex layout they read and the material
+    /// state they draw with; `pipeline` is the defaults'. Empty for the debug
+    /// pipeline.
+    pub variants: HashMap<(VertexLayout, RenderState), wgpu::RenderPipeline>,
     pub bindings: BindingData,
-    pub texture_bind_group_layout: Option<wgpu::BindGroupLayout>,
+    pub material_bind_group_layout: Option<wgpu::BindGroupLayout>,
     pub depth: Option<DepthBuffer>,
+    /// Color target drawn into and resolved to the surface when multisampling.
+    pub msaa: Option<MsaaTarget>,
+    /// Draws the atmosphere wherever no tile wrote depth.
+    pub sky: Option<wgpu::RenderPipeline>,
+    /// Draws the globe beneath the tiles, see fs_globe.
+    pub globe: Option<wgpu::RenderPipeline>,
+    /// Draws the lines and polygons added to the world over everything else.
+    /// Built with the first shape, see `VectorPipelines::build_for`.
+    pub vectors: Option<VectorPipelines>,
+    /// Draws icons and labels over the vectors. Built with the first
+    /// billboard, see `BillboardPipelines::build_for`.
+    pub billboards: Option<BillboardPipelines>,
+}
+
+impl RenderPipeline {
+    pub fn pipeline_for(&self, layout: VertexLayout, state: RenderState) -> &wgpu::RenderPipeline {
+        self.variants
+            .get(&(layout, state))
+            .unwrap_or(&self.pipeline)
+    }
 }
 
 pub fn build_pipeline(
     device: &wgpu::Device,
     config: &wgpu::SurfaceConfiguration,
+    shadows: ShadowsConfig,
+    sample_count: u32,
 ) -> RenderPipeline {
-    let texture_bind_group_layout =
+    let material_texture = |binding: u32| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        },
+        count: None,
+    };
+    let material_bind_group_layout =
         device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Texture Bind Group Layout"),
+            label: Some("Material Bind Group Layout"),
             entries: &[
+                // binding(0) base color
+                material_texture(0),
                 wgpu::BindGroupLayoutEntry {
-                    binding: 0,
+                    binding: 1,
                     visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Texture {
-                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                        view_dimension: wgpu::TextureViewDimension::D2,
-                        multisampled: false,
-                    },
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
                     count: None,
                 },
+                // binding(2) material parameters
                 wgpu::BindGroupLayoutEntry {
-                    binding: 1,
+                    binding: 2,
                     visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: std::num::NonZeroU64::new(std::mem::size_of::<
+                            MaterialUniform,
+                        >()
+                            as u64),
+                    },
                     count: None,
                 },
+                // binding(3..=6) metallic-roughness, normal, occlusion, emissive
+                material_texture(3),
+                material_texture(4),
+                material_texture(5),
+                material_texture(6),
             ],
         });
 
@@ -76,6 +132,58 @@ pub fn build_pipeline(
                     },
                     count: None,
                 },
+                // binding(2) sun and ambient light
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: std::num::NonZeroU64::new(std::mem::size_of::<
+                            LightingUniform,
+                        >()
+                            as u64),
+                    },
+                    count: None,
+                },
+                // binding(3..=5) atmosphere transmittance and multiple scattering LUTs
+                material_texture(3),
+                material_texture(4),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                // binding(6..=8) shadow cascades, their comparison sampler and matrices
+                wgpu::BindGroupLayoutEntry {
+                    binding: 6,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Depth,
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 7,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 8,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: std::num::NonZeroU64::new(
+                            std::mem::size_of::<ShadowUniform>() as u64,
+                        ),
+                    },
+                    count: None,
+                },
             ],
         });
 
@@ -86,117 +194,270 @@ pub fn build_pipeline(
         mapped_at_creation: false,
     });
 
-    let instance_buffer = InstanceBuffer::new(device, MAX_RENDERABLE_NODES_US);
-
-    let tile_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-        label: Some("Tile Bind Group"),
-        layout: &tile_bind_group_layout,
-        entries: &[
-            wgpu::BindGroupEntry {
-                binding: 0,
-                resource: camera_buf.as_entire_binding(),
-            },
-            wgpu::BindGroupEntry {
-                binding: 1,
-                resource: instance_buffer.buf.as_entire_binding(),
-            },
-        ],
+    let lighting_buf = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("lighting_ubo"),
+        size: std::mem::size_of::<LightingUniform>() as u64,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
     });
 
+    let instance_buffer = InstanceBuffer::new(device, MAX_RENDERABLE_NODES_US);
+    let atmosphere = AtmosphereTextures::new(device);
+
     // Create pipeline layout.
     let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
         label: Some("Tile Render Pipeline Layout"),
-        bind_group_layouts: &[&tile_bind_group_layout, &texture_bind_group_layout],
+        bind_group_layouts: &[&tile_bind_group_layout, &material_bind_group_layout],
         push_constant_ranges: &[],
     });
 
     // Load WGSL shader from file.
     let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
         label: Some("Tile Shader"),
-        source: wgpu::ShaderSource::Wgsl(include_str!("../../assets/shader.wgsl").into()),
+        source: wgpu::ShaderSource::Wgsl(
+            concat!(
+                include_str!("../../assets/atmosphere.wgsl"),
+                include_str!("../../assets/shader.wgsl")
+            )
+            .into(),
+        ),
     });
 
-    let depth = DepthBuffer::new(device, config.width, config.height, recommended_format(), 1);
+    let shadows = ShadowMaps::new(device, &shader, shadows, &instance_buffer);
+
+    let tile_bind_group = create_tile_bg(
+        device,
+        &tile_bind_group_layout,
+        &camera_buf,
+        &instance_buffer,
+        &lighting_buf,
+        &atmosphere,
+        &shadows,
+    );
+
+    let depth = DepthBuffer::new(
+        device,
+        config.width,
+        config.height,
+        recommended_format(device.features()),
+        sample_count,
+    );
+    let msaa = (sample_count > 1).then(|| {
+        MsaaTarget::new(
+            device,
+            config.width,
+            config.height,
+            config.format,
+            sample_count,
+        )
+    });
 
-    // Create the render pipeline.
-    let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-        label: Some("Render Pipeline"),
-        layout: Some(&pipeline_layout),
+    // One render pipeline per vertex layout and material state. Blended
+    // materials test depth but don't write it.
+    let build_variant = |layout: VertexLayout, state: RenderState, fragment: &str| {
+        let depth_stencil = depth
+            .depth_stencil_state()
+            .map(|d| wgpu::DepthStencilState {
+                depth_write_enabled: !state.blend,
+                ..d
+            });
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Render Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some(layout.entry_point()),
+                buffers: &[layout.desc()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some(fragment),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: state.blend.then_some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            cache: None,
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: (!state.double_sided).then_some(wgpu::Face::Back),
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil,
+            multisample: wgpu::MultisampleState {
+                count: depth.sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        })
+    };
+    let variants: HashMap<_, _> = VertexLayout::ALL
+        .into_iter()
+        .flat_map(|layout| RenderState::ALL.map(|state| (layout, state)))
+        .map(|key| (key, build_variant(key.0, key.1, "fs_main")))
+        .collect();
+    let globe = build_variant(VertexLayout::default(), RenderState::default(), "fs_globe");
+    let pipeline = variants[&Default::default()].clone();
+    let sky = build_sky_pipeline(device, config, &tile_bind_group_layout, &depth);
+
+    RenderPipeline {
+        pipeline,
+        variants,
+        material_bind_group_layout: Some(material_bind_group_layout),
+        depth: Some(depth),
+        msaa,
+        sky: Some(sky),
+        globe: Some(globe),
+        vectors: None,
+        billboards: None,
+        bindings: BindingData {
+            tile_bg: tile_bind_group,
+            tile_bg_layout: tile_bind_group_layout,
+            instance_buffer: Some(instance_buffer),
+            camera_buffer: Some(camera_buf),
+            lighting_buffer: Some(lighting_buf),
+            atmosphere: Some(atmosphere),
+            shadows: Some(shadows),
+        },
+    }
+}
+
+/// A fullscreen triangle at the far plane, so it only lands where the depth
+/// buffer still holds its clear value. Shares the tile bind group for the
+/// lighting uniform and LUTs.
+fn build_sky_pipeline(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+    tile_bind_group_layout: &wgpu::BindGroupLayout,
+    depth: &DepthBuffer,
+) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Sky Shader"),
+        source: wgpu::ShaderSource::Wgsl(
+            concat!(
+                include_str!("../../assets/atmosphere.wgsl"),
+                include_str!("../../assets/sky.wgsl")
+            )
+            .into(),
+        ),
+    });
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Sky Pipeline Layout"),
+        bind_group_layouts: &[tile_bind_group_layout],
+        push_constant_ranges: &[],
+    });
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Sky Pipeline"),
+        layout: Some(&layout),
         vertex: wgpu::VertexState {
             module: &shader,
-            entry_point: Some("vs_main"),
-            buffers: &[Vertex::desc()],
+            entry_point: Some("vs_sky"),
+            buffers: &[],
             compilation_options: Default::default(),
         },
         fragment: Some(wgpu::FragmentState {
             module: &shader,
-            entry_point: Some("fs_main"),
+            entry_point: Some("fs_sky"),
             targets: &[Some(wgpu::ColorTargetState {
                 format: config.format,
-                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                blend: None,
                 write_mask: wgpu::ColorWrites::ALL,
             })],
             compilation_options: Default::default(),
         }),
         cache: None,
-        primitive: wgpu::PrimitiveState {
-            topology: wgpu::PrimitiveTopology::TriangleList,
-            strip_index_format: None,
-            front_face: wgpu::FrontFace::Ccw,
-            cull_mode: Some(wgpu::Face::Back),
-            polygon_mode: wgpu::PolygonMode::Fill,
-            unclipped_depth: false,
-            conservative: false,
-        },
-        depth_stencil: depth.depth_stencil_state(),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: depth
+            .depth_stencil_state()
+            .map(|d| wgpu::DepthStencilState {
+                depth_write_enabled: false,
+                ..d
+            }),
         multisample: wgpu::MultisampleState {
-            count: 1,
-            mask: !0,
-            alpha_to_coverage_enabled: false,
+            count: depth.sample_count,
+            ..Default::default()
         },
         multiview: None,
-    });
-
-    RenderPipeline {
-        pipeline,
-        texture_bind_group_layout: Some(texture_bind_group_layout),
-        depth: Some(depth),
-        bindings: BindingData {
-            tile_bg: tile_bind_group,
-            tile_bg_layout: tile_bind_group_layout,
-            instance_buffer: Some(instance_buffer),
-            camera_buffer: Some(camera_buf),
-        },
-    }
+    })
 }
 
-pub fn rebuild_tile_bg(device: &wgpu::Device, pipeline: &mut RenderPipeline) {
-    let new_tile_bg = device.create_bind_group(&wgpu::BindGroupDescriptor {
+fn create_tile_bg(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    camera: &wgpu::Buffer,
+    instances: &InstanceBuffer,
+    lighting: &wgpu::Buffer,
+    atmosphere: &AtmosphereTextures,
+    shadows: &ShadowMaps,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
         label: Some("Tile Bind Group"),
-        layout: &pipeline.bindings.tile_bg_layout,
+        layout,
         entries: &[
             wgpu::BindGroupEntry {
                 binding: 0,
-                resource: pipeline
-                    .bindings
-                    .camera_buffer
-                    .as_ref()
-                    .unwrap()
-                    .as_entire_binding(),
+                resource: camera.as_entire_binding(),
             },
             wgpu::BindGroupEntry {
                 binding: 1,
-                resource: pipeline
-                    .bindings
-                    .instance_buffer
-                    .as_ref()
-                    .unwrap()
-                    .buf
-                    .as_entire_binding(),
+                resource: instances.buf.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: lighting.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::TextureView(&atmosphere.transmittance.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 4,
+                resource: wgpu::BindingResource::TextureView(&atmosphere.multi_scattering.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 5,
+                resource: wgpu::BindingResource::Sampler(&atmosphere.transmittance.sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 6,
+                resource: wgpu::BindingResource::TextureView(&shadows.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 7,
+                resource: wgpu::BindingResource::Sampler(&shadows.sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 8,
+                resource: shadows.uniform.as_entire_binding(),
             },
         ],
-    });
-    pipeline.bindings.tile_bg = new_tile_bg;
+    })
+}
+
+pub fn rebuild_tile_bg(device: &wgpu::Device, pipeline: &mut RenderPipeline) {
+    let bindings = &mut pipeline.bindings;
+    let instances = bindings.instance_buffer.as_ref().unwrap();
+    let shadows = bindings.shadows.as_mut().unwrap();
+    shadows.rebuild_bind_groups(device, instances);
+
+    let new_tile_bg = create_tile_bg(
+        device,
+        &bindings.tile_bg_layout,
+        bindings.camera_buffer.as_ref().unwrap(),
+        instances,
+        bindings.lighting_buffer.as_ref().unwrap(),
+        bindings.atmosphere.as_ref().unwrap(),
+        shadows,
+    );
+    bindings.tile_bg = new_tile_bg;
 }
 
 pub fn build_debug_pipeline(
@@ -282,7 +543,7 @@ pub fn build_debug_pipeline(
         },
         depth_stencil: shared_depth.depth_stencil_state(),
         multisample: wgpu::MultisampleState {
-            count: 1,
+            count: shared_depth.sample_count,
             mask: !0,
             alpha_to_coverage_enabled: false,
         },
@@ -291,13 +552,22 @@ pub fn build_debug_pipeline(
 
     RenderPipeline {
         pipeline: debug_pipeline,
-        texture_bind_group_layout: None,
+        variants: HashMap::new(),
+        material_bind_group_layout: None,
         depth: None,
+        msaa: None,
+        sky: None,
+        globe: None,
+        vectors: None,
+        billboards: None,
         bindings: BindingData {
             tile_bg_layout: camera_bind_group_layout,
             tile_bg: camera_bind_group,
             instance_buffer: None,
             camera_buffer: Some(camera_uniform_buffer),
+            lighting_buffer: None,
+            atmosphere: None,
+            shadows: None,
         },
     }
 }
@@ -0,0 +1,369 @@
+#[cfg(test)]
+mod tests {
+    use crate::content::accessor::{read_accessor_f32, read_accessor_indices};
+    use crate::content::{build_meshes, parse_glb};
+    use crate::decode::DracoClient;
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+
+    /// Packs a JSON chunk and a binary chunk into a GLB, then parses it back
+    /// the way the tile loader does.
+    fn glb(json: Value, bin: &[u8]) -> (Value, Vec<u8>) {
+        let mut json_bytes = serde_json::to_vec(&json).unwrap();
+        json_bytes.resize(json_bytes.len().next_multiple_of(4), b' ');
+        let mut bin = bin.to_vec();
+        bin.resize(bin.len().next_multiple_of(4), 0);
+
+        let total = 12 + 8 + json_bytes.len() + 8 + bin.len();
+        let mut out = Vec::with_capacity(total);
+        out.extend(b"glTF");
+        out.extend(2u32.to_le_bytes());
+        out.extend((total as u32).to_le_bytes());
+        out.extend((json_bytes.len() as u32).to_le_bytes());
+        out.extend(b"JSON");
+        out.extend(&json_bytes);
+        out.extend((bin.len() as u32).to_le_bytes());
+        out.extend(b"BIN\0");
+        out.extend(&bin);
+        parse_glb(&out).unwrap()
+    }
+
+    fn view(offset: usize, length: usize, stride: Option<usize>) -> Value {
+        let mut view = json!({ "buffer": 0, "byteOffset": offset, "byteLength": length });
+        if let Some(stride) = stride {
+            view["byteStride"] = json!(stride);
+        }
+        view
+    }
+
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len(), "{actual:?} vs {expected:?}");
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-6, "{actual:?} vs {expected:?}");
+        }
+    }
+
+    #[test]
+    fn reads_every_component_type() {
+        let mut bin = Vec::new();
+        bin.extend([127u8, 0x80, 0, 0x81]); // i8
+        bin.extend([0u8, 255, 51, 0]); // u8
+        for v in [-32768i16, 32767, -1, 0] {
+            bin.extend(v.to_le_bytes());
+        }
+        for v in [0u16, 65535, 13107, 1] {
+            bin.extend(v.to_le_bytes());
+        }
+        for v in [7u32, 70000] {
+            bin.extend(v.to_le_bytes());
+        }
+        bin.extend(1.5f32.to_le_bytes());
+
+        let accessor = |offset: usize, component: u32, count: usize, normalized: bool| {
+            json!({
+                "bufferView": 0, "byteOffset": offset, "componentType": component,
+                "count": count, "type": "SCALAR", "normalized": normalized,
+            })
+        };
+        let (json, bin) = glb(
+            json!({
+                "bufferViews": [view(0, bin.len(), None)],
+                "accessors": [
+                    accessor(0, 5120, 4, true),
+                    accessor(0, 5120, 4, false),
+                    accessor(4, 5121, 4, true),
+                    accessor(4, 5121, 4, false),
+                    accessor(8, 5122, 4, true),
+                    accessor(8, 5122, 4, false),
+                    accessor(16, 5123, 4, true),
+                    accessor(16, 5123, 4, false),
+                    accessor(24, 5125, 2, false),
+                    accessor(32, 5126, 1, false),
+                ],
+            }),
+            &bin,
+        );
+
+        let read = |idx| read_accessor_f32(&json, &bin, idx).unwrap();
+        assert_close(&read(0).0, &[1.0, -1.0, 0.0, -1.0]);
+        assert_close(&read(1).0, &[127.0, -128.0, 0.0, -127.0]);
+        assert_close(&read(2).0, &[0.0, 1.0, 0.2, 0.0]);
+        assert_close(&read(3).0, &[0.0, 255.0, 51.0, 0.0]);
+        assert_close(&read(4).0, &[-1.0, 1.0, -1.0 / 32767.0, 0.0]);
+        assert_close(&read(5).0, &[-32768.0, 32767.0, -1.0, 0.0]);
+        assert_close(&read(6).0, &[0.0, 1.0, 0.2, 1.0 / 65535.0]);
+        assert_close(&read(7).0, &[0.0, 65535.0, 13107.0, 1.0]);
+        assert_close(&read(8).0, &[7.0, 70000.0]);
+        assert_eq!(read(9), (vec![1.5], 1));
+
+        assert_eq!(
+            read_accessor_indices(&json, &bin, 3).unwrap(),
+            vec![0, 255, 51, 0]
+        );
+        assert_eq!(
+            read_accessor_indices(&json, &bin, 7).unwrap(),
+            vec![0, 65535, 13107, 1]
+        );
+        assert_eq!(
+            read_accessor_indices(&json, &bin, 8).unwrap(),
+            vec![7, 70000]
+        );
+        // Signed and float types aren't valid indices
+        assert!(read_accessor_indices(&json, &bin, 1).is_err());
+        assert!(read_accessor_indices(&json, &bin, 9).is_err());
+    }
+
+    #[test]
+    fn honors_strides_and_matrix_padding() {
+        // Two i16 VEC3 positions interleaved with u8 normalized VEC2 uvs in
+        // 12-byte vertices.
+        let mut bin = Vec::new();
+        for (p, uv) in [([1i16, -2, 3], [0u8, 255]), ([-4, 5, -6], [255, 0])] {
+            for v in p {
+                bin.extend(v.to_le_bytes());
+            }
+            bin.extend([0, 0]);
+            bin.extend(uv);
+            bin.extend([0, 0]);
+        }
+        // A u8 MAT3 pads each 3-byte column to 4 bytes
+        let mat_offset = bin.len();
+        bin.extend([1, 2, 3, 0, 4, 5, 6, 0, 7, 8, 9, 0]);
+
+        let (json, bin) = glb(
+            json!({
+                "bufferViews": [
+                    view(0, mat_offset, Some(12)),
+                    view(mat_offset, 12, None),
+                ],
+                "accessors": [
+                    { "bufferView": 0, "componentType": 5122, "count": 2, "type": "VEC3" },
+                    {
+                        "bufferView": 0, "byteOffset": 8, "componentType": 5121,
+                        "normalized": true, "count": 2, "type": "VEC2",
+                    },
+                    { "bufferView": 1, "componentType": 5121, "count": 1, "type": "MAT3" },
+                ],
+            }),
+            &bin,
+        );
+
+        let (positions, n) = read_accessor_f32(&json, &bin, 0).unwrap();
+        assert_eq!(n, 3);
+        assert_close(&positions, &[1.0, -2.0, 3.0, -4.0, 5.0, -6.0]);
+        let (uvs, n) = read_accessor_f32(&json, &bin, 1).unwrap();
+        assert_eq!(n, 2);
+        assert_close(&uvs, &[0.0, 1.0, 1.0, 0.0]);
+        let (matrix, n) = read_accessor_f32(&json, &bin, 2).unwrap();
+        assert_eq!(n, 9);
+        assert_close(&matrix, &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0]);
+    }
+
+    #[test]
+    fn applies_sparse_substitution() {
+        let mut bin = Vec::new();
+        for v in [1.0f32, 2.0, 3.0, 4.0] {
+            bin.extend(v.to_le_bytes());
+        }
+        bin.extend([3u8, 1, 0, 0]); // sparse indices
+        for v in [-1.0f32, -2.0] {
+            bin.extend(v.to_le_bytes());
+        }
+
+        let sparse = json!({
+            "count": 2,
+            "indices": { "bufferView": 1, "componentType": 5121 },
+            "values": { "bufferView": 2 },
+        });
+        let (json, bin) = glb(
+            json!({
+                "bufferViews": [view(0, 16, None), view(16, 2, None), view(20, 8, None)],
+                "accessors": [
+                    {
+                        "bufferView": 0, "componentType": 5126, "count": 4,
+                        "type": "SCALAR", "sparse": sparse,
+                    },
+                    // No bufferView: the base is all zeros
+                    { "componentType": 5126, "count": 5, "type": "SCALAR", "sparse": sparse },
+                    // Sparse index past the end
+                    { "componentType": 5126, "count": 2, "type": "SCALAR", "sparse": sparse },
+                ],
+            }),
+            &bin,
+        );
+
+        assert_close(
+            &read_accessor_f32(&json, &bin, 0).unwrap().0,
+            &[1.0, -2.0, 3.0, -1.0],
+        );
+        assert_close(
+            &read_accessor_f32(&json, &bin, 1).unwrap().0,
+            &[0.0, -2.0, 0.0, -1.0, 0.0],
+        );
+        assert!(read_accessor_f32(&json, &bin, 2).is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_accessors() {
+        let (json, bin) = glb(
+            json!({
+                "bufferViews": [view(0, 8, None), view(4, 64, None), view(0, 8, Some(2))],
+                "accessors": [
+                    // Runs past the end of its view
+                    { "bufferView": 0, "componentType": 5126, "count": 3, "type": "SCALAR" },
+                    // View runs past the binary chunk
+                    { "bufferView": 1, "componentType": 5126, "count": 1, "type": "SCALAR" },
+                    // Stride smaller than the element
+                    { "bufferView": 2, "componentType": 5126, "count": 2, "type": "SCALAR" },
+                    // Floats can't be normalized
+                    {
+                        "bufferView": 0, "componentType": 5126, "normalized": true,
+                        "count": 1, "type": "SCALAR",
+                    },
+                    { "bufferView": 0, "componentType": 5124, "count": 1, "type": "SCALAR" },
+                    { "bufferView": 0, "componentType": 5126, "count": 1, "type": "VEC5" },
+                    { "bufferView": 9, "componentType": 5126, "count": 1, "type": "SCALAR" },
+                    // Counts that would allocate far more than the file holds
+                    {
+                        "bufferView": 0, "componentType": 5126,
+                        "count": 1u64 << 61, "type": "VEC4",
+                    },
+                    { "componentType": 5126, "count": 1u64 << 40, "type": "SCALAR" },
+                    // Offsets that overflow before they can be checked
+                    {
+                        "bufferView": 0, "byteOffset": u64::MAX, "componentType": 5126,
+                        "count": 1, "type": "SCALAR",
+                    },
+                    {
+                        "componentType": 5126, "count": 1, "type": "SCALAR",
+                        "sparse": {
+                            "count": 1,
+                            "indices": {
+                                "bufferView": 0, "byteOffset": u64::MAX, "componentType": 5125,
+                            },
+                            "values": { "bufferView": 0 },
+                        },
+                    },
+                ],
+            }),
+            &[0; 8],
+        );
+
+        for idx in 0..12 {
+            assert!(
+                read_accessor_f32(&json, &bin, idx).is_err(),
+                "accessor {idx} was accepted"
+            );
+        }
+    }
+
+    #[test]
+    fn skips_primitives_with_mismatched_attributes() {
+        let (json, bin) = glb(
+            json!({
+                "bufferViews": [view(0, 36, None)],
+                "accessors": [
+                    { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3" },
+                    { "bufferView": 0, "componentType": 5126, "count": 2, "type": "VEC3" },
+                    { "bufferView": 0, "componentType": 5126, "count": 4, "type": "VEC2" },
+                ],
+                "meshes": [{
+                    "primitives": [
+                        { "attributes": { "POSITION": 0, "NORMAL": 0, "TEXCOORD_0": 0 } },
+                        { "attributes": { "POSITION": 0, "NORMAL": 1 } },
+                        { "attributes": { "POSITION": 0, "TEXCOORD_0": 2 } },
+                        { "attributes": { "POSITION": 0, "COLOR_0": 1 } },
+                    ],
+                }],
+            }),
+            &[0; 36],
+        );
+
+        let (meshes, mesh_primitives) =
+            futures::executor::block_on(build_meshes(Arc::new(DracoClient::new()), &json, &bin))
+                .unwrap();
+        // TEXCOORD_0 on the first is ignored for its width, not its count
+        assert_eq!(meshes.len(), 1);
+        assert_eq!(mesh_primitives, vec![0..1]);
+        assert_eq!(meshes[0].as_vertex_slice().len(), 3);
+    }
+
+    #[test]
+    fn builds_quantized_primitive() {
+        // KHR_mesh_quantization: i16 positions, i8 normalized normals,
+        // u16 normalized texcoords, u8 normalized colors and u8 indices.
+        let mut bin = Vec::new();
+        for p in [[0i16, 0, 0], [100, 0, 0], [0, 100, 0]] {
+            for v in p {
+                bin.extend(v.to_le_bytes());
+            }
+            bin.extend([0, 0]);
+        }
+        for _ in 0..3 {
+            bin.extend([0u8, 0, 127, 0]);
+        }
+        for uv in [[0u16, 0], [65535, 0], [0, 65535]] {
+            for v in uv {
+                bin.extend(v.to_le_bytes());
+            }
+        }
+        for _ in 0..3 {
+            bin.extend([255u8, 0, 51, 255]);
+        }
+        bin.extend([0u8, 1, 2]);
+
+        let (json, bin) = glb(
+            json!({
+                "extensionsUsed": ["KHR_mesh_quantization"],
+                "extensionsRequired": ["KHR_mesh_quantization"],
+                "bufferViews": [
+                    view(0, 24, Some(8)),
+                    view(24, 12, Some(4)),
+                    view(36, 12, None),
+                    view(48, 12, None),
+                    view(60, 3, None),
+                ],
+                "accessors": [
+                    { "bufferView": 0, "componentType": 5122, "count": 3, "type": "VEC3" },
+                    {
+                        "bufferView": 1, "componentType": 5120, "normalized": true,
+                        "count": 3, "type": "VEC3",
+                    },
+                    {
+                        "bufferView": 2, "componentType": 5123, "normalized": true,
+                        "count": 3, "type": "VEC2",
+                    },
+                    {
+                        "bufferView": 3, "componentType": 5121, "normalized": true,
+                        "count": 3, "type": "VEC4",
+                    },
+                    { "bufferView": 4, "componentType": 5121, "count": 3, "type": "SCALAR" },
+                ],
+                "meshes": [{
+                    "primitives": [{
+                        "attributes": { "POSITION": 0, "NORMAL": 1, "TEXCOORD_0": 2, "COLOR_0": 3 },
+                        "indices": 4,
+                    }],
+                }],
+            }),
+            &bin,
+        );
+
+        let (meshes, mesh_primitives) =
+            futures::executor::block_on(build_meshes(Arc::new(DracoClient::new()), &json, &bin))
+                .unwrap();
+        assert_eq!(meshes.len(), 1);
+        assert_eq!(mesh_primitives, vec![0..1]);
+        let mesh = &meshes[0];
+        assert_eq!(mesh.as_index_slice(), [0, 1, 2]);
+        let vertices = mesh.as_vertex_slice();
+        assert_eq!(vertices[1].position, [100.0, 0.0, 0.0]);
+        assert_eq!(vertices[2].position, [0.0, 100.0, 0.0]);
+        for vertex in vertices {
+            assert_eq!(vertex.normal, [0.0, 0.0, 1.0]);
+            assert_close(&vertex.color, &[1.0, 0.0, 0.2, 1.0]);
+        }
+        assert_eq!(vertices[1].texcoord0, [1.0, 0.0]);
+        assert_eq!(vertices[2].texcoord0, [0.0, 1.0]);
+    }
+}
@@ -0,0 +1,87 @@
+#[cfg(test)]
+mod tests {
+    use crate::render::{
+        integrate_transmittance, transmittance_r_mu, transmittance_uv, AtmosphereLuts,
+        LightingUniform, BOTTOM_RADIUS, MULTI_SCATTERING_LUT_SIZE, TOP_RADIUS,
+        TRANSMITTANCE_LUT_SIZE,
+    };
+
+    #[test]
+    fn transmittance_parameterization_round_trips() {
+        for (r, mu) in [
+            (BOTTOM_RADIUS, 1.0),
+            (BOTTOM_RADIUS, 0.0),
+            // Just above the horizon, which dips to -0.0396 at 5 km
+            (BOTTOM_RADIUS + 5_000.0, -0.035),
+            (BOTTOM_RADIUS + 40_000.0, 0.3),
+            (TOP_RADIUS - 1.0, -0.1),
+        ] {
+            let (u, v) = transmittance_uv(r, mu);
+            let unit = -1e-9..=1.0 + 1e-9;
+            assert!(unit.contains(&u) && unit.contains(&v), "{u}, {v}");
+            let (r2, mu2) = transmittance_r_mu(u, v);
+            assert!((r2 - r).abs() < 1e-3, "{r} vs {r2}");
+            assert!((mu2 - mu).abs() < 1e-6, "{mu} vs {mu2}");
+        }
+    }
+
+    #[test]
+    fn zenith_transmittance_matches_optical_depth() {
+        // Exponential layers integrate to scale height times coefficient; the
+        // ozone tent to its half width.
+        let rayleigh: [f64; 3] = [5.802e-6, 13.558e-6, 33.1e-6].map(|b| b * 8_000.0);
+        let ozone = [0.650e-6, 1.881e-6, 0.085e-6].map(|b| b * 15_000.0);
+        let mie: f64 = 4.40e-6 * 1_200.0;
+
+        let t = integrate_transmittance(BOTTOM_RADIUS, 1.0);
+        for (i, actual) in [t.x, t.y, t.z].into_iter().enumerate() {
+            let expected = (-(rayleigh[i] + mie + ozone[i])).exp();
+            assert!(
+                (actual - expected).abs() < 0.005,
+                "{i}: {actual} vs {expected}"
+            );
+        }
+        // Blue is scattered away the most
+        assert!(t.x > t.y && t.y > t.z);
+    }
+
+    #[test]
+    fn luts_have_the_expected_shape() {
+        let luts = AtmosphereLuts::compute();
+        let (width, height) = TRANSMITTANCE_LUT_SIZE;
+        assert_eq!(luts.transmittance.len(), (width * height) as usize);
+        assert_eq!(
+            luts.multi_scattering.len(),
+            (MULTI_SCATTERING_LUT_SIZE * MULTI_SCATTERING_LUT_SIZE) as usize
+        );
+
+        // The LUT reproduces the direct integral between texel centers
+        for (r, mu) in [(BOTTOM_RADIUS, 0.5), (BOTTOM_RADIUS + 10_000.0, 0.02)] {
+            let direct = integrate_transmittance(r, mu);
+            let lut = luts.transmittance(r, mu);
+            assert!((direct - lut).x.abs() < 0.01, "{direct:?} vs {lut:?}");
+            assert!((direct - lut).z.abs() < 0.01, "{direct:?} vs {lut:?}");
+        }
+        // Thinner air along the horizon than straight up
+        assert!(
+            luts.transmittance(BOTTOM_RADIUS, 0.0).z < luts.transmittance(BOTTOM_RADIUS, 1.0).z
+        );
+
+        // Multiple scattering is bounded, brightest by day and bluish
+        let size = MULTI_SCATTERING_LUT_SIZE as usize;
+        assert!(luts.multi_scattering.iter().all(|c| [c.x, c.y, c.z]
+            .iter()
+            .all(|v| v.is_finite() && *v >= 0.0 && *v < 1.0)));
+        let night = luts.multi_scattering[0];
+        let noon = luts.multi_scattering[size - 1];
+        assert!(noon.z > night.z * 10.0, "{noon:?} vs {night:?}");
+        assert!(noon.z > noon.x);
+    }
+
+    #[test]
+    fn lighting_uniform_matches_shader_layout() {
+        // Lighting in atmosphere.wgsl: four vec4s, a mat4x4 and flags, padded
+        // to a multiple of 16 bytes
+        assert_eq!(std::mem::size_of::<LightingUniform>(), 4 * 16 + 64 + 16);
+    }
+}
@@ -0,0 +1,583 @@
+#[cfg(test)]
+mod tests {
+    use crate::content::{SupportedTextureEncodings, TextureEncoding};
+    use crate::decode::basisu::transcode_ktx2;
+
+    const KTX2_IDENTIFIER: [u8; 12] = [
+        0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+    ];
+
+    /// LSB-first bit writer matching the BasisLZ bitstream.
+    #[derive(Default)]
+    struct Bits {
+        bytes: Vec<u8>,
+        len: usize,
+    }
+
+    impl Bits {
+        fn put(&mut self, value: u32, count: u32) {
+            for i in 0..count {
+                if self.len.is_multiple_of(8) {
+                    self.bytes.push(0);
+                }
+                let bit = ((value >> i) & 1) as u8;
+                *self.bytes.last_mut().unwrap() |= bit << (self.len % 8);
+                self.len += 1;
+            }
+        }
+
+        fn empty_table(&mut self) {
+            self.put(0, 14);
+        }
+
+        /// A table whose only symbol is `symbol`, coded as a single 0 bit.
+        fn single_symbol_table(&mut self, symbol: u32) {
+            self.put(symbol + 1, 14);
+            // Code length codes 0 and 1, each one bit long; code length 1 is
+            // the 19th entry in the transmitted order.
+            self.put(19, 5);
+            for i in 0..19 {
+                self.put(if i == 4 || i == 18 { 1 } else { 0 }, 3);
+            }
+            // Canonical codes: "0" is code length 0, "1" is code length 1.
+            for _ in 0..symbol {
+                self.put(0, 1);
+            }
+            self.put(1, 1);
+        }
+    }
+
+    struct Ktx2 {
+        vk_format: u32,
+        width: u32,
+        height: u32,
+        supercompression: u32,
+        color_model: u8,
+        samples: u32,
+        global_data: Vec<u8>,
+        levels: Vec<(Vec<u8>, usize)>,
+    }
+
+    impl Ktx2 {
+        fn build(&self) -> Vec<u8> {
+            let level_count = self.levels.len();
+            let dfd_offset = 80 + level_count * 24;
+            let block_size = 24 + 16 * self.samples;
+            let mut dfd = Vec::new();
+            dfd.extend((4 + block_size).to_le_bytes());
+            dfd.extend(0u32.to_le_bytes());
+            dfd.extend((2 | block_size << 16).to_le_bytes());
+            dfd.extend([self.color_model, 1, 2, 0]);
+            dfd.resize(4 + block_size as usize, 0);
+
+            let sgd_offset = dfd_offset + dfd.len();
+            let mut data_offset = sgd_offset + self.global_data.len();
+
+            let mut out = KTX2_IDENTIFIER.to_vec();
+            for v in [
+                self.vk_format,
+                1,
+                self.width,
+                self.height,
+                0,
+                0,
+                1,
+                level_count as u32,
+                self.supercompression,
+            ] {
+                out.extend(v.to_le_bytes());
+            }
+            out.extend((dfd_offset as u32).to_le_bytes());
+            out.extend((dfd.len() as u32).to_le_bytes());
+            out.extend([0u8; 8]);
+            out.extend((sgd_offset as u64).to_le_bytes());
+            out.extend((self.global_data.len() as u64).to_le_bytes());
+            for (bytes, uncompressed) in &self.levels {
+                out.extend((data_offset as u64).to_le_bytes());
+                out.extend((bytes.len() as u64).to_le_bytes());
+                out.extend((*uncompressed as u64).to_le_bytes());
+                data_offset += bytes.len();
+            }
+            out.extend(&dfd);
+            out.extend(&self.global_data);
+            for (bytes, _) in &self.levels {
+                out.extend(bytes);
+            }
+            out
+        }
+    }
+
+    fn gradient(width: u32, height: u32) -> Vec<u8> {
+        (0..width * height)
+            .flat_map(|i| [(i * 7) as u8, (i * 13) as u8, (i * 29) as u8, 255 - i as u8])
+            .collect()
+    }
+
+    #[test]
+    fn reads_rgba8_levels() {
+        let base = gradient(4, 2);
+        let mip = gradient(2, 1);
+        let mut file = Ktx2 {
+            vk_format: 43,
+            width: 4,
+            height: 2,
+            supercompression: 0,
+            color_model: 1,
+            samples: 4,
+            global_data: Vec::new(),
+            levels: vec![(base.clone(), base.len()), (mip.clone(), mip.len())],
+        };
+
+        let texture = transcode_ktx2(&file.build(), SupportedTextureEncodings::default()).unwrap();
+        assert_eq!(texture.encoding, TextureEncoding::Rgba8);
+        assert_eq!((texture.width, texture.height), (4, 2));
+        assert_eq!(texture.levels, vec![base.clone(), mip.clone()]);
+
+        file.supercompression = 2;
+        file.levels = [&base, &mip]
+            .iter()
+            .map(|level| {
+                let compressed = ruzstd::encoding::compress_to_vec(
+                    level.as_slice(),
+                    ruzstd::encoding::CompressionLevel::Fastest,
+                );
+                (compressed, level.len())
+            })
+            .collect();
+        let texture = transcode_ktx2(&file.build(), SupportedTextureEncodings::default()).unwrap();
+        assert_eq!(texture.levels, vec![base, mip]);
+    }
+
+    /// An 8x8 ETC1S image with one endpoint and one selector, so every block
+    /// is identical: color5 (20, 20, 20), intensity table 3, and selectors
+    /// running 0..3 across each row.
+    fn etc1s_file() -> Ktx2 {
+        let mut endpoints = Bits::default();
+        endpoints.empty_table();
+        endpoints.single_symbol_table(4); // 16 + 4 = 20 for every channel
+        endpoints.empty_table();
+        endpoints.single_symbol_table(3);
+        endpoints.put(0, 1); // not grayscale
+        endpoints.put(0, 4); // intensity, then r, g, b deltas
+
+        let mut selectors = Bits::default();
+        selectors.put(0b100, 3); // no global or hybrid codebook, raw
+        for _ in 0..4 {
+            selectors.put(0b11_10_01_00, 8);
+        }
+
+        // Prediction symbol for a 2x2 group: delta, left, up, left.
+        let pred = 0b00_01_00_11;
+        let mut tables = Bits::default();
+        tables.single_symbol_table(pred);
+        tables.single_symbol_table(0);
+        tables.single_symbol_table(0);
+        tables.empty_table();
+        tables.put(0, 13);
+
+        // Level 0 has 2x2 blocks, level 1 one block; each symbol is one bit.
+        let slices = [vec![0u8], vec![0u8]];
+
+        let mut global = Vec::new();
+        global.extend(1u16.to_le_bytes());
+        global.extend(1u16.to_le_bytes());
+        global.extend((endpoints.bytes.len() as u32).to_le_bytes());
+        global.extend((selectors.bytes.len() as u32).to_le_bytes());
+        global.extend((tables.bytes.len() as u32).to_le_bytes());
+        global.extend(0u32.to_le_bytes());
+        for slice in &slices {
+            for v in [0, 0, slice.len() as u32, 0, 0] {
+                global.extend(v.to_le_bytes());
+            }
+        }
+        global.extend(&endpoints.bytes);
+        global.extend(&selectors.bytes);
+        global.extend(&tables.bytes);
+
+        Ktx2 {
+            vk_format: 0,
+            width: 8,
+            height: 8,
+            supercompression: 1,
+            color_model: 163,
+            samples: 1,
+            global_data: global,
+            levels: slices.iter().map(|s| (s.clone(), 0)).collect(),
+        }
+    }
+
+    #[test]
+    fn transcodes_etc1s_to_rgba8() {
+        let texture =
+            transcode_ktx2(&etc1s_file().build(), SupportedTextureEncodings::default()).unwrap();
+        assert_eq!(texture.encoding, TextureEncoding::Rgba8);
+        assert_eq!(texture.levels.len(), 2);
+        assert_eq!(texture.levels[0].len(), 8 * 8 * 4);
+        assert_eq!(texture.levels[1].len(), 4 * 4 * 4);
+
+        // 20 expands to 165; table 3 is (-42, -13, 13, 42).
+        let expected = [123u8, 152, 178, 207];
+        for (i, texel) in texture.levels[0].chunks_exact(4).enumerate() {
+            let v = expected[i % 8 % 4];
+            assert_eq!(texel, [v, v, v, 255], "texel {i}");
+        }
+    }
+
+    #[test]
+    fn transcodes_etc1s_to_etc2_without_reencoding() {
+        let supported = SupportedTextureEncodings {
+            bc7: true,
+            etc2: true,
+            astc: true,
+        };
+        let texture = transcode_ktx2(&etc1s_file().build(), supported).unwrap();
+        assert_eq!(texture.encoding, TextureEncoding::Etc2Rgb8);
+
+        let block = [160, 160, 160, 0b0110_1110, 0x00, 0xFF, 0xF0, 0x0F];
+        assert_eq!(texture.levels[0], block.repeat(4));
+        assert_eq!(texture.levels[1], block.to_vec());
+    }
+
+    #[test]
+    fn transcodes_etc1s_to_bc7_and_astc() {
+        let bc7 = SupportedTextureEncodings {
+            bc7: true,
+            ..Default::default()
+        };
+        let texture = transcode_ktx2(&etc1s_file().build(), bc7).unwrap();
+        assert_eq!(texture.encoding, TextureEncoding::Bc7);
+        assert_eq!(texture.levels[0].len(), 4 * 16);
+
+        let expected = [123i32, 152, 178, 207];
+        for block in texture.levels[0].chunks_exact(16) {
+            let texels = decode_bc7_mode6(block.try_into().unwrap());
+            for (i, texel) in texels.iter().enumerate() {
+                let v = expected[i % 4];
+                for c in 0..3 {
+                    assert!((texel[c] - v).abs() <= 3, "texel {i}: {texel:?} vs {v}");
+                }
+                assert!(texel[3] >= 253);
+            }
+        }
+
+        let astc = SupportedTextureEncodings {
+            astc: true,
+            ..Default::default()
+        };
+        let texture = transcode_ktx2(&etc1s_file().build(), astc).unwrap();
+        assert_eq!(texture.encoding, TextureEncoding::Astc4x4);
+        for block in texture.levels[0].chunks_exact(16) {
+            let bits = u128::from_le_bytes(block.try_into().unwrap());
+            // 4x4 grid with 3-bit weights, one partition, LDR RGB direct
+            assert_eq!(bits & 0x7ff, 0x53);
+            assert_eq!((bits >> 11) & 3, 0);
+            assert_eq!((bits >> 13) & 15, 8);
+        }
+    }
+
+    /// Reference mode 6 decoder used to check the encoder's bit layout.
+    fn decode_bc7_mode6(block: [u8; 16]) -> [[i32; 4]; 16] {
+        const WEIGHTS: [i32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+        let bits = u128::from_le_bytes(block);
+        assert_eq!(bits & 0x7f, 0x40, "not a mode 6 block");
+        let field = |pos: u32, len: u32| ((bits >> pos) & ((1 << len) - 1)) as i32;
+
+        let p = [field(63, 1), field(64, 1)];
+        let mut endpoints = [[0i32; 4]; 2];
+        for c in 0..4 {
+            for (e, endpoint) in endpoints.iter_mut().enumerate() {
+                endpoint[c] = field(7 + c as u32 * 14 + e as u32 * 7, 7) << 1 | p[e];
+            }
+        }
+        let mut pos = 65;
+        std::array::from_fn(|i| {
+            let len = if i == 0 { 3 } else { 4 };
+            let w = WEIGHTS[field(pos, len) as usize];
+            pos += len;
+            std::array::from_fn(|c| ((64 - w) * endpoints[0][c] + w * endpoints[1][c] + 32) >> 6)
+        })
+    }
+
+    #[test]
+    fn rejects_truncated_and_unsupported_files() {
+        let file = etc1s_file().build();
+        for len in [0, 12, 79, 120, file.len() - 1] {
+            assert!(
+                transcode_ktx2(&file[..len], SupportedTextureEncodings::default()).is_err(),
+                "accepted {len} bytes"
+            );
+        }
+
+        // UASTC levels that aren't whole blocks
+        let mut uastc = etc1s_file();
+        uastc.supercompression = 0;
+        uastc.color_model = 166;
+        assert!(transcode_ktx2(&uastc.build(), SupportedTextureEncodings::default()).is_err());
+    }
+
+    fn block(bits: &Bits) -> Vec<u8> {
+        let mut block = bits.bytes.clone();
+        assert!(block.len() <= 16);
+        block.resize(16, 0);
+        block
+    }
+
+    /// Mode 1: RGB endpoints black and white in 8 bits, then 2-bit weights
+    /// running 0..3 across each row.
+    fn uastc_gradient() -> Vec<u8> {
+        let mut bits = Bits::default();
+        bits.put(0x35, 6);
+        bits.put(0, 15);
+        for _ in 0..3 {
+            bits.put(0, 8);
+            bits.put(255, 8);
+        }
+        bits.put(0, 1);
+        for i in 1..16 {
+            bits.put(i % 4, 2);
+        }
+        block(&bits)
+    }
+
+    /// Mode 2 with the first pattern, whose right two columns are the second
+    /// subset: red on the left, black on the right but for a blue last texel.
+    fn uastc_partitioned() -> Vec<u8> {
+        let mut bits = Bits::default();
+        bits.put(0x1d, 5);
+        bits.put(0, 15);
+        bits.put(0, 5);
+        for v in [15, 15, 0, 0, 0, 0, 0, 0, 0, 0, 0, 15] {
+            bits.put(v, 4);
+        }
+        for i in 0..16 {
+            let anchor = i == 0 || i == 2;
+            bits.put(if i == 15 { 7 } else { 0 }, if anchor { 2 } else { 3 });
+        }
+        block(&bits)
+    }
+
+    /// Mode 0, whose endpoints carry a trit each: r from 0 to 255, g from 1
+    /// to 254 and b from 2 to 253, weighted by texel index.
+    fn uastc_trits() -> Vec<u8> {
+        let mut bits = Bits::default();
+        bits.put(0x01, 4);
+        bits.put(0, 15);
+        // trits 0 0 1 1 2 | 2, then the low six bits of each value
+        bits.put(9 + 27 + 2 * 81, 8);
+        bits.put(2, 2);
+        for low in [0, 1, 0, 1, 0, 1] {
+            bits.put(low, 6);
+        }
+        bits.put(0, 3);
+        for i in 1..16 {
+            bits.put(i, 4);
+        }
+        block(&bits)
+    }
+
+    fn uastc_solid(rgba: [u8; 4]) -> Vec<u8> {
+        let mut bits = Bits::default();
+        bits.put(0x17, 5);
+        for v in rgba {
+            bits.put(v as u32, 8);
+        }
+        block(&bits)
+    }
+
+    /// A 12x4 UASTC image of three blocks, over a 6x2 level of two solid ones.
+    fn uastc_file() -> Ktx2 {
+        let base = [uastc_gradient(), uastc_partitioned(), uastc_trits()].concat();
+        let mip = [uastc_solid([10, 20, 30, 40]), uastc_solid([50, 60, 70, 80])].concat();
+        Ktx2 {
+            vk_format: 0,
+            width: 12,
+            height: 4,
+            supercompression: 0,
+            color_model: 166,
+            samples: 1,
+            global_data: Vec::new(),
+            levels: vec![(base, 0), (mip, 0)],
+        }
+    }
+
+    #[test]
+    fn transcodes_uastc_to_rgba8() {
+        let texture =
+            transcode_ktx2(&uastc_file().build(), SupportedTextureEncodings::default()).unwrap();
+        assert_eq!(texture.encoding, TextureEncoding::Rgba8);
+        let texel = |level: usize, width: usize, x: usize, y: usize| -> [u8; 4] {
+            let at = (y * width + x) * 4;
+            texture.levels[level][at..at + 4].try_into().unwrap()
+        };
+
+        // 2-bit weights are 0, 21, 43 and 64 out of 64
+        let expected = [0, 84, 171, 255];
+        for (x, y) in (0..4).flat_map(|y| (0..4).map(move |x| (x, y))) {
+            let v = expected[x];
+            assert_eq!(texel(0, 12, x, y), [v, v, v, 255], "texel {x}, {y}");
+        }
+
+        for (x, y) in (0..4).flat_map(|y| (0..4).map(move |x| (x, y))) {
+            let expected = match (x, y) {
+                (0 | 1, _) => [255, 0, 0, 255],
+                (3, 3) => [0, 0, 255, 255],
+                _ => [0, 0, 0, 255],
+            };
+            assert_eq!(texel(0, 12, 4 + x, y), expected, "texel {x}, {y}");
+        }
+
+        assert_eq!(texel(0, 12, 8, 0), [0, 1, 2, 255]);
+        assert_eq!(texel(0, 12, 11, 3), [255, 254, 253, 255]);
+        let reds: Vec<u8> = (0..16).map(|i| texel(0, 12, 8 + i % 4, i / 4)[0]).collect();
+        assert!(reds.windows(2).all(|w| w[0] < w[1]), "{reds:?}");
+
+        // the mip is cropped to its 6x2 texels
+        assert_eq!(texture.levels[1].len(), 6 * 2 * 4);
+        assert_eq!(texel(1, 6, 3, 1), [10, 20, 30, 40]);
+        assert_eq!(texel(1, 6, 5, 1), [50, 60, 70, 80]);
+    }
+
+    #[test]
+    fn transcodes_uastc_to_astc_and_bc7() {
+        let supported = SupportedTextureEncodings {
+            bc7: true,
+            etc2: true,
+            astc: true,
+        };
+        let texture = transcode_ktx2(&uastc_file().build(), supported).unwrap();
+        assert_eq!(texture.encoding, TextureEncoding::Astc4x4);
+        assert_eq!(texture.levels[0].len(), 3 * 16);
+        assert_eq!(texture.levels[1].len(), 2 * 16);
+
+        let bc7 = SupportedTextureEncodings {
+            bc7: true,
+            etc2: true,
+            ..Default::default()
+        };
+        let texture = transcode_ktx2(&uastc_file().build(), bc7).unwrap();
+        assert_eq!(texture.encoding, TextureEncoding::Bc7);
+        let texels = decode_bc7_mode6(texture.levels[0][..16].try_into().unwrap());
+        for (i, texel) in texels.iter().enumerate() {
+            let v = [0, 84, 171, 255][i % 4];
+            for c in 0..3 {
+                assert!((texel[c] - v).abs() <= 3, "texel {i}: {texel:?} vs {v}");
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_reserved_uastc_blocks() {
+        let mut file = uastc_file();
+        let mut bits = Bits::default();
+        bits.put(0x45, 7);
+        file.levels[1].0[16..].copy_from_slice(&block(&bits));
+        assert!(transcode_ktx2(&file.build(), SupportedTextureEncodings::default()).is_err());
+    }
+
+    #[test]
+    fn rejects_forged_sizes_without_allocating() {
+        let none = SupportedTextureEncodings::default();
+        let pixels = gradient(4, 2);
+        let rgba8 = |width, height, supercompression, uncompressed| Ktx2 {
+            vk_format: 43,
+            width,
+            height,
+            supercompression,
+            color_model: 1,
+            samples: 4,
+            global_data: Vec::new(),
+            levels: vec![(pixels.clone(), uncompressed)],
+        };
+
+        // dimensions past any adapter's limit, and ones whose byte count
+        // overflows u32
+        assert!(transcode_ktx2(&rgba8(32768, 32768, 0, 0).build(), none).is_err());
+        assert!(transcode_ktx2(&rgba8(u32::MAX, 1, 0, 0).build(), none).is_err());
+        assert!(transcode_ktx2(&rgba8(16384, 16384, 0, 0).build(), none).is_err());
+
+        // a Zstd level claiming far more than its dimensions or input allow
+        for uncompressed in [usize::MAX / 2, 1 << 40, 33] {
+            let file = rgba8(4, 2, 2, uncompressed).build();
+            assert!(transcode_ktx2(&file, none).is_err(), "{uncompressed}");
+        }
+        let file = rgba8(16384, 16384, 2, 16384 * 16384 * 4).build();
+        assert!(transcode_ktx2(&file, none).is_err());
+
+        // a Zstd stream that inflates past the level's size
+        let big = vec![7u8; 4096];
+        let compressed = ruzstd::encoding::compress_to_vec(
+            &big[..],
+            ruzstd::encoding::CompressionLevel::Fastest,
+        );
+        let mut file = rgba8(4, 2, 2, pixels.len());
+        file.levels = vec![(compressed, pixels.len())];
+        assert!(transcode_ktx2(&file.build(), none).is_err());
+
+        // ETC1S slices can't cover a forged size
+        let mut etc1s = etc1s_file();
+        etc1s.width = 16384;
+        etc1s.height = 16384;
+        assert!(transcode_ktx2(&etc1s.build(), none).is_err());
+        etc1s.width = u32::MAX;
+        assert!(transcode_ktx2(&etc1s.build(), none).is_err());
+
+        // offsets and lengths at u32::MAX, which wrap a 32 bit usize
+        let mut file = rgba8(4, 2, 0, pixels.len()).build();
+        file[48..52].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(transcode_ktx2(&file, none).is_err());
+        let mut etc1s = etc1s_file();
+        for word in etc1s.global_data[4..16].chunks_mut(4) {
+            word.copy_from_slice(&u32::MAX.to_le_bytes());
+        }
+        assert!(transcode_ktx2(&etc1s.build(), none).is_err());
+    }
+
+    #[test]
+    fn survives_corrupted_headers() {
+        let all = SupportedTextureEncodings {
+            bc7: true,
+            etc2: true,
+            astc: true,
+        };
+        let files = [etc1s_file().build(), uastc_file().build(), {
+            let pixels = gradient(4, 2);
+            Ktx2 {
+                vk_format: 43,
+                width: 4,
+                height: 2,
+                supercompression: 0,
+                color_model: 1,
+                samples: 4,
+                global_data: Vec::new(),
+                levels: vec![(pixels.clone(), pixels.len())],
+            }
+            .build()
+        }];
+
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        for file in &files {
+            // header, level index and the start of the descriptor
+            let span = file.len().min(160);
+            for _ in 0..2000 {
+                let mut forged = file.clone();
+                for _ in 0..1 + next() % 4 {
+                    let at = (next() as usize) % span;
+                    forged[at] = match next() % 3 {
+                        0 => 0xFF,
+                        1 => 0,
+                        _ => next() as u8,
+                    };
+                }
+                for encodings in [SupportedTextureEncodings::default(), all] {
+                    let _ = transcode_ktx2(&forged, encodings);
+                }
+            }
+        }
+    }
+}
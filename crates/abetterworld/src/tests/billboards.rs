@@ -0,0 +1,268 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::{
+        render::{
+            behind_horizon, signed_distance_field, BillboardLayer, GlyphAtlas, GLYPH_SPREAD,
+            HIDDEN, KIND_GLYPH, KIND_IMAGE, KIND_POINT, LABEL_HIDDEN,
+        },
+        world::{Billboard, Icon, IconImage, Label, Location, ScaleByDistance},
+    };
+    use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, Vector3};
+
+    #[test]
+    fn measures_distance_fields_in_and_out() {
+        // a 4 × 4 square, solid
+        let pad = GLYPH_SPREAD as usize;
+        let field = signed_distance_field(&[1.0; 16], 4, 4, pad);
+        let width = 4 + 2 * pad;
+        let at = |x: usize, y: usize| field[y * width + x];
+
+        // half a pixel either side of the edge
+        let half = (0.5 / GLYPH_SPREAD * 127.0).round() as u8;
+        assert_eq!(at(pad, pad + 1), 128 + half);
+        assert_eq!(at(pad - 1, pad + 1), 128 - half);
+        assert!(at(pad + 1, pad + 1) > at(pad, pad + 1));
+        // as far out as the field reaches
+        assert!(at(0, 0) < 16);
+    }
+
+    #[test]
+    fn lays_out_centered_lines() {
+        let mut glyphs = GlyphAtlas::new();
+        let layout = glyphs.layout("Hi\nthere", 20.0);
+        assert_eq!(layout.quads.len(), 7);
+        // h, e and r are shared, one insert each for the rest
+        assert_eq!(glyphs.atlas.version, 6);
+        assert!(
+            layout.size[0] > 30.0 && layout.size[0] < 60.0,
+            "{:?}",
+            layout.size
+        );
+        assert!(
+            layout.size[1] >= 36.0 && layout.size[1] < 60.0,
+            "{:?}",
+            layout.size
+        );
+
+        // quads carry the spread around each glyph
+        let left = layout
+            .quads
+            .iter()
+            .map(|q| q.bounds[0])
+            .fold(f32::MAX, f32::min);
+        let right = layout
+            .quads
+            .iter()
+            .map(|q| q.bounds[2])
+            .fold(f32::MIN, f32::max);
+        let spread = GLYPH_SPREAD * 20.0 / 40.0;
+        assert!((left + right).abs() < 2.0, "{left} {right} not centered");
+        assert!(right - left < layout.size[0] + 2.0 * spread + 2.0);
+        // the first line above the second
+        assert!(layout.quads[0].bounds[3] < layout.quads[6].bounds[3]);
+
+        // nothing new to rasterize, nothing new to upload
+        let version = glyphs.atlas.version;
+        glyphs.layout("there Hi", 32.0);
+        assert!(glyphs.layout(" ", 12.0).quads.is_empty());
+        assert_eq!(glyphs.atlas.version, version);
+
+        // the middle of the I's stem is well inside
+        let i = glyphs.layout("I", 40.0).quads[0];
+        let [l, t, r, b] = i.texels.map(|v| v as usize);
+        let atlas = &glyphs.atlas;
+        let texel = |x: usize, y: usize| atlas.pixels[y * atlas.width as usize + x];
+        assert!(texel((l + r) / 2, (t + b) / 2) > 150);
+        assert!(texel(l, t) < 16);
+    }
+
+    #[test]
+    fn scales_by_distance() {
+        let scale = ScaleByDistance {
+            near: 1_000.0,
+            near_scale: 1.5,
+            far: 11_000.0,
+            far_scale: 0.5,
+        };
+        assert_eq!(scale.scale(10.0), 1.5);
+        assert_eq!(scale.scale(6_000.0), 1.0);
+        assert_eq!(scale.scale(1e9), 0.5);
+        let backwards = ScaleByDistance { far: 0.0, ..scale };
+        assert_eq!(backwards.scale(6_000.0), 1.5);
+    }
+
+    fn ecef(lat: f64, lon: f64, height: f64) -> Point3<f64> {
+        Location::Geodetic(lat, lon, height).to_ecef()
+    }
+
+    #[test]
+    fn hides_what_is_over_the_horizon() {
+        let eye = ecef(0.0, 0.0, 1_000_000.0);
+        assert!(!behind_horizon(&eye, &ecef(0.0, 0.0, 0.0)));
+        assert!(!behind_horizon(&eye, &ecef(10.0, 5.0, 0.0)));
+        assert!(behind_horizon(&eye, &ecef(0.0, 60.0, 0.0)));
+        assert!(behind_horizon(&eye, &ecef(-10.0, 180.0, 0.0)));
+        // high enough to peek over
+        assert!(!behind_horizon(&eye, &ecef(0.0, 60.0, 20_000_000.0)));
+        // below the sphere, there's nothing to tell
+        assert!(!behind_horizon(
+            &ecef(0.0, 0.0, -30_000.0),
+            &ecef(0.0, 90.0, 0.0)
+        ));
+    }
+
+    /// Looking straight down from 10 km over the equator, with a reverse Z
+    /// infinite projection like the renderer's.
+    fn look_down() -> (Point3<f64>, Matrix4<f64>, (f64, f64)) {
+        let eye = ecef(0.0, 0.0, 10_000.0);
+        let f = 1.0 / 30f64.to_radians().tan();
+        #[rustfmt::skip]
+        let proj = Matrix4::new(
+            f, 0.0, 0.0, 0.0,
+            0.0, f, 0.0, 0.0,
+            0.0, 0.0, 0.0, -1.0,
+            0.0, 0.0, 0.5, 0.0,
+        );
+        let down = -eye.to_vec().normalize();
+        let view = Matrix4::look_at_rh(Point3::origin(), Point3::from_vec(down), Vector3::unit_z());
+        (eye, proj * view, (800.0, 800.0))
+    }
+
+    fn labelled(lat: f64, lon: f64, height: f64, text: &str) -> Billboard {
+        Billboard {
+            label: Some(Label::new(text)),
+            ..Billboard::new(Location::Geodetic(lat, lon, height))
+        }
+    }
+
+    #[test]
+    fn declutters_overlapping_labels() {
+        let (eye, view_proj, viewport) = look_down();
+        let mut layer = BillboardLayer::new();
+        let far = layer.add(labelled(0.0, 0.0, 0.0, "Sensor 12"));
+        let near = layer.add(labelled(0.0, 0.0001, 100.0, "Sensor 13"));
+        let apart = layer.add(labelled(0.03, 0.0, 0.0, "Sensor 14"));
+        let point = Icon::Point {
+            diameter: 8.0,
+            color: [255, 0, 0, 255],
+            outline_color: [255; 4],
+            outline_width: 1.0,
+        };
+        let unlabelled = Billboard {
+            icon: Some(point),
+            ..Billboard::new(Location::Geodetic(0.0, 0.0, 0.0))
+        };
+        layer.add(unlabelled);
+
+        // the nearer of the overlapping labels wins
+        let flags = |layer: &BillboardLayer| -> Vec<u32> {
+            layer
+                .instances(&eye, &view_proj, viewport)
+                .iter()
+                .map(|i| i.flags)
+                .collect()
+        };
+        assert_eq!(flags(&layer), [LABEL_HIDDEN, 0, 0, 0]);
+
+        // unless the other is on top
+        let on_top = Billboard {
+            always_on_top: true,
+            ..labelled(0.0, 0.0, 0.0, "Sensor 12")
+        };
+        assert!(layer.replace(far, on_top));
+        assert_eq!(flags(&layer), [0, LABEL_HIDDEN, 0, 0]);
+
+        // or moved out of the way
+        let moved = Billboard {
+            pixel_offset: [0.0, 40.0],
+            ..labelled(0.0, 0.0001, 100.0, "Sensor 13")
+        };
+        assert!(layer.replace(near, moved));
+        assert_eq!(flags(&layer), [0; 4]);
+
+        // shrunk to nothing is hidden, and out of the way
+        let gone = Billboard {
+            scale_by_distance: Some(ScaleByDistance {
+                near: 0.0,
+                near_scale: 0.0,
+                far: 1.0,
+                far_scale: 0.0,
+            }),
+            ..labelled(0.03, 0.0, 0.0, "Sensor 14")
+        };
+        assert!(layer.replace(apart, gone));
+        let instances = layer.instances(&eye, &view_proj, viewport);
+        assert_eq!(instances[2].flags, HIDDEN);
+        assert_eq!(instances[2].scale, 0.0);
+
+        let relative: [f32; 3] = (ecef(0.0, 0.0001, 100.0) - eye).cast().unwrap().into();
+        assert_eq!(instances[1].position, relative);
+    }
+
+    #[test]
+    fn groups_billboards_on_top_last() {
+        let image = Arc::new(IconImage {
+            width: 2,
+            height: 3,
+            rgba: vec![200; 2 * 3 * 4],
+        });
+        let icon = Icon::Image {
+            image: image.clone(),
+            scale: 2.0,
+        };
+        let mut layer = BillboardLayer::new();
+        let point = Icon::Point {
+            diameter: 10.0,
+            color: [255; 4],
+            outline_color: [0; 4],
+            outline_width: 0.0,
+        };
+        layer.add(Billboard {
+            icon: Some(point),
+            pixel_offset: [3.0, -4.0],
+            ..Billboard::new(Location::Geodetic(1.0, 2.0, 3.0))
+        });
+        layer.add(Billboard {
+            icon: Some(icon.clone()),
+            always_on_top: true,
+            ..labelled(1.0, 2.0, 3.0, "AB")
+        });
+        layer.add(Billboard {
+            icon: Some(icon),
+            ..Billboard::new(Location::Geodetic(1.0, 2.0, 3.0))
+        });
+        // shared images go into the atlas once
+        assert_eq!(layer.icons.version, 1);
+
+        let (vertices, split) = layer.vertices();
+        let split = split as usize;
+        assert_eq!(vertices.len(), 6 * 5);
+        assert_eq!(split, 6 * 2);
+        let kinds = |range: &[crate::render::BillboardVertex]| {
+            range
+                .iter()
+                .map(|v| (v.kind, v.billboard))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(kinds(&vertices[..6]), [(KIND_POINT, 0); 6]);
+        assert_eq!(kinds(&vertices[6..split]), [(KIND_IMAGE, 2); 6]);
+        // the label after the icon, so it's drawn over it
+        assert_eq!(kinds(&vertices[split..split + 6]), [(KIND_IMAGE, 1); 6]);
+        assert_eq!(kinds(&vertices[split + 6..]), [(KIND_GLYPH, 1); 12]);
+
+        assert!(vertices[..6].iter().all(|v| v.shift == [3.0, -4.0]));
+        let extent = |i: usize| {
+            let xs = vertices[6..split].iter().map(|v| v.offset[i]);
+            xs.clone().fold(f32::MIN, f32::max) - xs.fold(f32::MAX, f32::min)
+        };
+        assert_eq!([extent(0), extent(1)], [4.0, 6.0]);
+        let radius = vertices[..6]
+            .iter()
+            .map(|v| Vector3::new(v.uv[0], v.uv[1], 0.0).magnitude())
+            .fold(0.0, f32::max);
+        // points reach a pixel past their edge
+        assert!((radius - 6.0 / 5.0 * 2f32.sqrt()).abs() < 1e-5);
+    }
+}
@@ -1,17 +1,22 @@
 #[cfg(test)]
 mod tests {
-    use crate::cache::{get_tileset_cache, init_tileset_cache};
+    use crate::cache::{
+        CacheBackend, CacheEntry, FsCacheBackend, MemoryCacheBackend, TilesetCache,
+    };
     use crate::helpers::{hash_uri, PlatformAwait};
+    use crate::tests::{gpu::headless_device, offscreen_world};
+    use crate::{get_debug_config, Source, World};
 
     use bytes::Bytes;
     use std::fs;
     use std::path::Path;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
 
     #[test]
     fn test_insert_get_lru_disk_roundtrip() {
-        init_tileset_cache("../tilesets");
-
-        let cache = get_tileset_cache();
+        let backend = Arc::new(FsCacheBackend::new("../tilesets"));
+        let cache = TilesetCache::new(backend.clone());
         let base_key = "test-key";
         let content_type = "application/octet-stream";
         let value = Bytes::from_static(b"hello-payload");
@@ -73,7 +78,7 @@ mod tests {
         assert_eq!(val_disk, value);
 
         // Clean up disk file
-        let disk_path = cache.disk_path_for(base_key);
+        let disk_path = backend.disk_path_for(base_key);
         if Path::new(&disk_path).exists() {
             fs::remove_file(disk_path).unwrap();
         }
@@ -81,10 +86,101 @@ mod tests {
         // Clean up spurious disk writes
         for i in 0..1024 {
             let key = format!("key-{}", i);
-            let path = cache.disk_path_for(&key);
+            let path = backend.disk_path_for(&key);
             if Path::new(&path).exists() {
                 let _ = fs::remove_file(path);
             }
         }
     }
+
+    #[test]
+    fn test_corrupt_disk_entry_is_evicted() {
+        let dir = std::env::temp_dir().join(format!("abw-cache-corrupt-{}", std::process::id()));
+        let backend = Arc::new(FsCacheBackend::new(dir.to_str().unwrap()));
+
+        let evicted = Arc::new(Mutex::new(Vec::new()));
+        let sink = evicted.clone();
+        backend.set_eviction_hook(Some(Arc::new(move |key: &str| {
+            sink.lock().unwrap().push(key.to_string());
+        })));
+
+        let cache = TilesetCache::new(backend.clone());
+        let key = "corrupt-key";
+        cache
+            .insert(
+                key.to_string(),
+                "model/gltf-binary".to_string(),
+                Bytes::from_static(b"glTF-not-really"),
+            )
+            .platform_await()
+            .unwrap();
+
+        // Flip the last payload byte on disk
+        let path = backend.disk_path_for(key);
+        let mut raw = fs::read(&path).unwrap();
+        *raw.last_mut().unwrap() ^= 0xff;
+        fs::write(&path, raw).unwrap();
+
+        // Skip the memory tier so the read goes to disk
+        cache.map.invalidate_all();
+        assert!(cache.get(key).platform_await().unwrap().is_none());
+        assert!(!path.exists(), "Expected the corrupt file to be removed");
+        assert_eq!(*evicted.lock().unwrap(), vec![key.to_string()]);
+        assert_eq!(backend.stats().evictions, 1);
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    fn world_with_backend(device: &wgpu::Device, backend: Arc<MemoryCacheBackend>) -> World {
+        let mut config = get_debug_config();
+        config.source = Source::Google {
+            key: "test".to_string(),
+            url: ROOT_URL.to_string(),
+        };
+        offscreen_world(device, &config, backend, 16, 16)
+    }
+
+    /// Nothing listens here, so the tileset only ever comes out of a cache.
+    const ROOT_URL: &str = "http://127.0.0.1:9/root.json";
+    /// What the Google source asks for: the root with its key appended, once
+    /// as the root and once more as it loads.
+    const ROOT_KEY: &str = "http://127.0.0.1:9/root.json?key=test&key=test";
+
+    /// Two worlds side by side each page through their own backend: the one
+    /// holding the root tileset finds it, the other misses and never sees it.
+    #[test]
+    fn worlds_keep_separate_caches() {
+        let Some((device, queue)) = headless_device() else {
+            return;
+        };
+
+        let tileset = br#"{"asset": {"version": "1.0"}, "root": null}"#;
+        let seeded = Arc::new(MemoryCacheBackend::new(1 << 20));
+        seeded
+            .insert(
+                ROOT_KEY,
+                CacheEntry::new("application/json".into(), Bytes::from_static(tileset)),
+            )
+            .platform_await()
+            .unwrap();
+        let empty = Arc::new(MemoryCacheBackend::new(1 << 20));
+
+        let mut a = world_with_backend(&device, seeded.clone());
+        let mut b = world_with_backend(&device, empty.clone());
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while (a.cache_stats().hits == 0 || b.cache_stats().misses == 0)
+            && Instant::now() < deadline
+        {
+            a.update(&device, &queue).unwrap();
+            b.update(&device, &queue).unwrap();
+            std::thread::sleep(Duration::from_millis(20));
+        }
+
+        assert!(a.cache_stats().hits > 0);
+        assert!(b.cache_stats().misses > 0);
+        assert_eq!(b.cache_stats().hits, 0);
+        assert!(empty.get(ROOT_KEY).platform_await().unwrap().is_none());
+        assert_eq!(empty.stats().entries, Some(0));
+    }
 }
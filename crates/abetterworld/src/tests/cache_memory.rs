@@ -0,0 +1,118 @@
+#[cfg(test)]
+mod tests {
+    use crate::cache::{CacheBackend, CacheEntry, MemoryCacheBackend, TilesetCache};
+    use crate::helpers::PlatformAwait;
+
+    use bytes::Bytes;
+    use std::sync::{Arc, Mutex};
+
+    fn entry(len: usize) -> CacheEntry {
+        CacheEntry::new("application/test".to_string(), Bytes::from(vec![7u8; len]))
+    }
+
+    #[test]
+    fn test_memory_backend_evicts_lru_and_reports() {
+        // Each entry is "k-N" (3) + content type (16) + 100 payload bytes = 119 bytes
+        let backend = MemoryCacheBackend::new(119 * 3);
+
+        let evicted = Arc::new(Mutex::new(Vec::new()));
+        let sink = evicted.clone();
+        backend.set_eviction_hook(Some(Arc::new(move |key: &str| {
+            sink.lock().unwrap().push(key.to_string());
+        })));
+
+        for i in 0..3 {
+            backend
+                .insert(&format!("k-{i}"), entry(100))
+                .platform_await()
+                .unwrap();
+        }
+
+        // Touch k-0 so k-1 becomes the least recently used
+        assert!(backend.get("k-0").platform_await().unwrap().is_some());
+
+        backend.insert("k-3", entry(100)).platform_await().unwrap();
+
+        assert_eq!(*evicted.lock().unwrap(), vec!["k-1".to_string()]);
+        assert!(backend.get("k-1").platform_await().unwrap().is_none());
+        assert!(backend.get("k-0").platform_await().unwrap().is_some());
+
+        let stats = backend.stats();
+        assert_eq!(stats.entries, Some(3));
+        assert_eq!(stats.bytes, Some(119 * 3));
+        assert_eq!(stats.inserts, 4);
+        assert_eq!(stats.evictions, 1);
+        assert_eq!(stats.hits, 2);
+        assert_eq!(stats.misses, 1);
+
+        // Larger than the whole budget: reported as evicted, never stored
+        backend
+            .insert("huge", entry(1000))
+            .platform_await()
+            .unwrap();
+        assert!(backend.get("huge").platform_await().unwrap().is_none());
+        assert_eq!(evicted.lock().unwrap().last().unwrap(), "huge");
+
+        backend.clear().platform_await().unwrap();
+        assert_eq!(backend.stats().entries, Some(0));
+        assert_eq!(backend.stats().bytes, Some(0));
+    }
+
+    #[test]
+    fn test_tileset_cache_over_memory_backend() {
+        let cache = TilesetCache::new(Arc::new(MemoryCacheBackend::new(1 << 20)));
+        let value = Bytes::from_static(b"memory-only-payload");
+
+        cache
+            .insert(
+                "memory-key".to_string(),
+                "application/octet-stream".to_string(),
+                value.clone(),
+            )
+            .platform_await()
+            .unwrap();
+
+        // Drop the hot tier, the backend still has it
+        cache.map.invalidate_all();
+        let (ct, data) = cache.get("memory-key").platform_await().unwrap().unwrap();
+        assert_eq!(ct, "application/octet-stream");
+        assert_eq!(data, value);
+
+        // Decoded payloads use their own key space
+        cache
+            .insert_decoded("memory-key", Bytes::from_static(b"decoded"))
+            .platform_await()
+            .unwrap();
+        let decoded = cache.get_decoded("memory-key").platform_await().unwrap();
+        assert_eq!(decoded, Some(Bytes::from_static(b"decoded")));
+        assert_eq!(cache.stats().entries, Some(2));
+
+        cache.clear().platform_await().unwrap();
+        assert!(cache.get("memory-key").platform_await().unwrap().is_none());
+    }
+
+    /// The memory backend hands entries back unchecked, so the damage is
+    /// caught by `TilesetCache` and reported like any other eviction.
+    #[test]
+    fn test_tileset_cache_reports_corrupt_entries() {
+        let backend = Arc::new(MemoryCacheBackend::new(1 << 20));
+        let evicted = Arc::new(Mutex::new(Vec::new()));
+        let sink = evicted.clone();
+        backend.set_eviction_hook(Some(Arc::new(move |key: &str| {
+            sink.lock().unwrap().push(key.to_string());
+        })));
+
+        let mut damaged = entry(100);
+        damaged.checksum ^= 1;
+        backend.insert("corrupt", damaged).platform_await().unwrap();
+
+        let cache = TilesetCache::new(backend.clone());
+        assert!(cache.get("corrupt").platform_await().unwrap().is_none());
+        assert!(cache.get_decoded("x").platform_await().unwrap().is_none());
+        assert_eq!(*evicted.lock().unwrap(), vec!["corrupt".to_string()]);
+
+        let stats = cache.stats();
+        assert_eq!(stats.evictions, 1);
+        assert_eq!(stats.entries, Some(0));
+    }
+}
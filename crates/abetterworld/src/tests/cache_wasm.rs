@@ -5,7 +5,8 @@ mod wasm_tests {
     use tracing::{event, Level};
     use wasm_bindgen_test::*;
 
-    use crate::cache::{get_tileset_cache, init_tileset_cache, init_wasm_indexdb_on_every_thread};
+    use crate::cache::{init_wasm_indexdb_on_every_thread, IndexedDbCacheBackend, TilesetCache};
+    use std::sync::Arc;
 
     fn random_id(len: usize) -> String {
         const CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
@@ -28,8 +29,6 @@ mod wasm_tests {
         console_log::init_with_level(log::Level::Info).ok();
         event!(Level::INFO, "Starting IndexedDB stress test...");
 
-        init_tileset_cache("");
-
         let _ = init_wasm_indexdb_on_every_thread()
             .await
             .expect("Should init IndexedDB");
@@ -38,7 +37,7 @@ mod wasm_tests {
 
         event!(Level::INFO, "Initialized tileset cache...");
 
-        let cache = get_tileset_cache();
+        let cache = TilesetCache::new(Arc::new(IndexedDbCacheBackend::new()));
 
         let content_type = "application/octet-stream";
         let base_value = Bytes::from_static(b"stress-test-payload");
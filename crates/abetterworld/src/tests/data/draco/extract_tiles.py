@@ -0,0 +1,69 @@
+"""Copies the Draco buffers out of glTF tiles into this corpus.
+
+Run from this directory with any Python 3, passing `.glb` tiles or the
+`.bin` entries of a filesystem tile cache (`cache_dir` of a world paging
+through Google's tiles):
+
+    python3 extract_tiles.py path/to/cache_dir/*.bin
+
+Every `KHR_draco_mesh_compression` primitive becomes
+`google_<file>_<mesh>_<primitive>.drc`. A test build without `rust-draco`
+then prints the `cpp.sha256` line each new file needs.
+"""
+
+import json
+import struct
+import sys
+from pathlib import Path
+
+GLB_MAGIC = b"glTF"
+CHUNK_JSON = 0x4E4F534A
+CHUNK_BIN = 0x004E4942
+# the header FsCacheBackend writes before each payload
+CACHE_MAGIC = b"ABWC"
+CACHE_HEADER_LEN = 20
+
+
+def glb_payload(data):
+    if data[:4] == CACHE_MAGIC:
+        (ct_len,) = struct.unpack_from("<I", data, 16)
+        data = data[CACHE_HEADER_LEN + ct_len :]
+    return data if data[:4] == GLB_MAGIC else None
+
+
+def chunks(glb):
+    at = 12
+    while at + 8 <= len(glb):
+        length, kind = struct.unpack_from("<II", glb, at)
+        yield kind, glb[at + 8 : at + 8 + length]
+        at += 8 + length
+
+
+def draco_buffers(glb):
+    found = dict(chunks(glb))
+    gltf = json.loads(found[CHUNK_JSON])
+    binary = found.get(CHUNK_BIN, b"")
+    for m, mesh in enumerate(gltf.get("meshes", [])):
+        for p, primitive in enumerate(mesh["primitives"]):
+            draco = primitive.get("extensions", {}).get("KHR_draco_mesh_compression")
+            if draco is None:
+                continue
+            view = gltf["bufferViews"][draco["bufferView"]]
+            start = view.get("byteOffset", 0)
+            yield m, p, binary[start : start + view["byteLength"]]
+
+
+def main(paths):
+    written = 0
+    for path in map(Path, paths):
+        glb = glb_payload(path.read_bytes())
+        if glb is None:
+            continue
+        for m, p, buffer in draco_buffers(glb):
+            Path(f"google_{path.stem}_{m}_{p}.drc").write_bytes(buffer)
+            written += 1
+    print(f"wrote {written} Draco buffers")
+
+
+if __name__ == "__main__":
+    main(sys.argv[1:])
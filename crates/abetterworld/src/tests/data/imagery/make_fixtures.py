@@ -0,0 +1,84 @@
+"""Writes the MBTiles fixtures used by src/tests/imagery.rs.
+
+Run from this directory with any Python 3. Tiles hold placeholder bytes
+rather than images, so the tests can check exactly what comes back.
+"""
+
+import os
+import sqlite3
+
+MAX_ZOOM = 4
+
+
+def tile_data(z, x, y):
+    # every seventh tile is long enough to spill onto overflow pages
+    repeat = 200 if (x + y) % 7 == 0 else 3
+    return (f"{z}/{x}/{y};" * repeat).encode()
+
+
+def tiles():
+    for z in range(MAX_ZOOM + 1):
+        for x in range(1 << z):
+            for y in range(1 << z):
+                yield z, x, y, (1 << z) - 1 - y
+
+
+def create(path, statements):
+    if os.path.exists(path):
+        os.remove(path)
+    db = sqlite3.connect(path)
+    # small pages so the trees get interior pages and overflow chains
+    db.execute("PRAGMA page_size = 512")
+    db.execute("CREATE TABLE metadata (name text, value text)")
+    db.execute("INSERT INTO metadata VALUES ('name', 'fixture'), ('maxzoom', ?)", (str(MAX_ZOOM),))
+    statements(db)
+    db.commit()
+    db.execute("VACUUM")
+    db.close()
+
+
+def plain(db):
+    db.execute(
+        "CREATE TABLE tiles (zoom_level integer, tile_column integer, tile_row integer, "
+        "tile_data blob)"
+    )
+    db.execute("CREATE UNIQUE INDEX tile_index ON tiles (zoom_level, tile_column, tile_row)")
+    for z, x, y, row in tiles():
+        db.execute("INSERT INTO tiles VALUES (?, ?, ?, ?)", (z, x, row, tile_data(z, x, y)))
+
+
+def deduplicated(db):
+    db.execute(
+        "CREATE TABLE map (zoom_level INTEGER, tile_column INTEGER, tile_row INTEGER, "
+        "tile_id TEXT)"
+    )
+    db.execute("CREATE UNIQUE INDEX map_index ON map (zoom_level, tile_column, tile_row)")
+    # no index on images, so it is read by scanning
+    db.execute("CREATE TABLE images (tile_data blob, tile_id text)")
+    db.execute(
+        "CREATE VIEW tiles AS SELECT map.zoom_level AS zoom_level, "
+        "map.tile_column AS tile_column, map.tile_row AS tile_row, "
+        "images.tile_data AS tile_data FROM map JOIN images ON images.tile_id = map.tile_id"
+    )
+    for z, x, y, row in tiles():
+        tile_id = f"{z}-{x}-{y}"
+        db.execute("INSERT INTO map VALUES (?, ?, ?, ?)", (z, x, row, tile_id))
+        db.execute("INSERT INTO images VALUES (?, ?)", (tile_data(z, x, y), tile_id))
+
+
+def altered(db):
+    # tile_row comes from ALTER TABLE, so the row stored before it is one
+    # column short; no index, so the short row is read by scanning
+    db.execute("CREATE TABLE tiles (zoom_level integer, tile_column integer, tile_data blob)")
+    db.execute("INSERT INTO tiles VALUES (0, 0, x'00')")
+    db.execute("ALTER TABLE tiles ADD COLUMN tile_row integer")
+    for z, x, y, row in tiles():
+        db.execute(
+            "INSERT INTO tiles (zoom_level, tile_column, tile_row, tile_data) VALUES (?, ?, ?, ?)",
+            (z, x, row, tile_data(z, x, y)),
+        )
+
+
+create("tiles.mbtiles", plain)
+create("altered.mbtiles", altered)
+create("deduplicated.mbtiles", deduplicated)
@@ -0,0 +1,157 @@
+"""Writes the quantized-mesh-1.0 fixtures used by src/tests/terrain.rs.
+
+Run from this directory with any Python 3. Every tile is a regular grid
+over TILE with heights rising from MIN_HEIGHT in the south west corner to
+MAX_HEIGHT in the north east, so the tests can work out where any vertex
+should land.
+"""
+
+import gzip
+import json
+import math
+import struct
+
+# z, x, y on the geographic grid, rows from the south
+TILE = (9, 171, 353)
+MIN_HEIGHT = 100.0
+MAX_HEIGHT = 500.0
+
+A = 6378137.0
+E2 = 6.69437999014e-3
+
+
+def rectangle(z, x, y):
+    size = 180.0 / (1 << z)
+    west = x * size - 180.0
+    south = y * size - 90.0
+    return west, south, west + size, south + size
+
+
+def ecef(lat, lon, h):
+    lat, lon = math.radians(lat), math.radians(lon)
+    n = A / math.sqrt(1.0 - E2 * math.sin(lat) ** 2)
+    return (
+        (n + h) * math.cos(lat) * math.cos(lon),
+        (n + h) * math.cos(lat) * math.sin(lon),
+        (n * (1.0 - E2) + h) * math.sin(lat),
+    )
+
+
+def up(lat, lon):
+    lat, lon = math.radians(lat), math.radians(lon)
+    return (math.cos(lat) * math.cos(lon), math.cos(lat) * math.sin(lon), math.sin(lat))
+
+
+def oct_encode(n):
+    x, y, z = n
+    length = abs(x) + abs(y) + abs(z)
+    x, y = x / length, y / length
+    if z < 0:
+        sign = lambda v: 1.0 if v >= 0 else -1.0
+        x, y = (1.0 - abs(y)) * sign(x), (1.0 - abs(x)) * sign(y)
+    to_byte = lambda v: round((max(-1.0, min(1.0, v)) * 0.5 + 0.5) * 255)
+    return bytes([to_byte(x), to_byte(y)])
+
+
+def zigzag_deltas(values):
+    out, last = [], 0
+    for v in values:
+        delta = v - last
+        out.append(((delta << 1) ^ (delta >> 31)) & 0xFFFF)
+        last = v
+    return out
+
+
+def high_water_mark(indices):
+    out, highest = [], 0
+    for i in indices:
+        out.append(highest - i)
+        if i == highest:
+            highest += 1
+    return out
+
+
+def grid(n):
+    """Vertices (u, v) and counter-clockwise triangles of an n by n grid,
+    with vertices numbered in the order the triangles first use them."""
+    triangles = []
+    for j in range(n - 1):
+        for i in range(n - 1):
+            a, b, c, d = j * n + i, j * n + i + 1, (j + 1) * n + i + 1, (j + 1) * n + i
+            triangles += [a, b, c, a, c, d]
+    order = {}
+    for i in triangles:
+        order.setdefault(i, len(order))
+    cells = [None] * len(order)
+    for i, k in order.items():
+        cells[k] = (i % n, i // n)
+    step = 32767 / (n - 1)
+    uv = [(round(i * step), round(j * step)) for i, j in cells]
+    return uv, [order[i] for i in triangles]
+
+
+def tile(n, normals=False, water=None, metadata=False):
+    west, south, east, north = rectangle(*TILE)
+    uv, indices = grid(n)
+    heights = [round(u * v / 32767) for u, v in uv]
+
+    def geodetic(u, v):
+        return south + (north - south) * v / 32767, west + (east - west) * u / 32767
+
+    center = ecef(*geodetic(16384, 16384), (MIN_HEIGHT + MAX_HEIGHT) / 2)
+    radius = max(
+        math.dist(center, ecef(*geodetic(u, v), h))
+        for u in (0, 32767)
+        for v in (0, 32767)
+        for h in (MIN_HEIGHT, MAX_HEIGHT)
+    )
+    out = struct.pack("<3d2f4d3d", *center, MIN_HEIGHT, MAX_HEIGHT, *center, radius, *center)
+
+    wide = len(uv) > 65536
+    index = "I" if wide else "H"
+    out += struct.pack("<I", len(uv))
+    for column in range(2):
+        out += struct.pack(f"<{len(uv)}H", *zigzag_deltas([p[column] for p in uv]))
+    out += struct.pack(f"<{len(uv)}H", *zigzag_deltas(heights))
+    if wide and len(out) % 4:
+        out += bytes(4 - len(out) % 4)
+    out += struct.pack("<I", len(indices) // 3)
+    out += struct.pack(f"<{len(indices)}{index}", *high_water_mark(indices))
+
+    # west, south, east, north, each in scrambled order
+    for on_edge in (
+        lambda u, v: u == 0,
+        lambda u, v: v == 0,
+        lambda u, v: u == 32767,
+        lambda u, v: v == 32767,
+    ):
+        edge = [k for k, (u, v) in enumerate(uv) if on_edge(u, v)]
+        edge = edge[1::2] + edge[::2]
+        out += struct.pack(f"<I{len(edge)}{index}", len(edge), *edge)
+
+    if normals:
+        data = b"".join(oct_encode(up(*geodetic(u, v))) for u, v in uv)
+        out += struct.pack("<BI", 1, len(data)) + data
+    if water is not None:
+        out += struct.pack("<BI", 2, len(water)) + water
+    if metadata:
+        data = json.dumps({"available": [[{"startX": 0, "startY": 0, "endX": 1, "endY": 1}]]})
+        data = struct.pack("<I", len(data)) + data.encode()
+        out += struct.pack("<BI", 4, len(data)) + data
+    return out
+
+
+def write(name, data):
+    with open(name, "wb") as f:
+        f.write(data)
+
+
+# the western half is sea
+mask = bytes(255 if col < 128 else 0 for row in range(256) for col in range(256))
+
+write("plain.terrain", tile(5))
+write("extensions.terrain", tile(9, normals=True, water=mask, metadata=True))
+# mtime 0 keeps the output stable between runs
+write("gzipped.terrain", gzip.compress(tile(5, water=b"\xff"), mtime=0))
+# enough vertices for 32 bit indices
+write("wide.terrain", gzip.compress(tile(257), mtime=0))
@@ -0,0 +1,136 @@
+#[cfg(test)]
+mod tests {
+    use cgmath::{Matrix4, Vector3};
+
+    use crate::content::{
+        decode_decoded_tile, encode_decoded_tile, pack_mesh, AlphaMode, Material, Node,
+        Quantization, Texture, TextureEncoding, TextureRef, TextureTransform, TileState,
+    };
+    use crate::decode::{OwnedDecodedMesh, Vertex};
+
+    fn sample_tile() -> TileState {
+        let vertices = (0..3)
+            .map(|i| Vertex {
+                position: [i as f32, 1.0, 2.0],
+                normal: [0.0, 0.0, 1.0],
+                color: [1.0, 0.5, 0.25, 1.0],
+                texcoord0: [0.1 * i as f32, 0.2],
+                texcoord1: [0.0, 0.0],
+            })
+            .collect();
+        let mut mesh = OwnedDecodedMesh::from_vertices_and_indices(vertices, vec![0, 1, 2]);
+        mesh.material_index = Some(0);
+
+        TileState::Decoded {
+            nodes: vec![Node {
+                transform: Matrix4::from_translation(Vector3::new(1.0, -2.0, 6_378_137.5)),
+                mesh_indices: vec![0],
+            }],
+            meshes: vec![pack_mesh(
+                &mesh,
+                Quantization::for_tile(std::slice::from_ref(&mesh), true),
+            )],
+            mesh_primitives: vec![0..1, 1..1],
+            textures: vec![
+                Texture {
+                    width: 2,
+                    height: 1,
+                    encoding: TextureEncoding::Rgba8,
+                    levels: vec![vec![255, 0, 0, 255, 0, 255, 0, 128], vec![128, 128, 0, 192]],
+                },
+                Texture {
+                    width: 4,
+                    height: 4,
+                    encoding: TextureEncoding::Bc7,
+                    levels: vec![vec![7; 16], vec![9; 16], vec![11; 16]],
+                },
+            ],
+            materials: vec![
+                Material {
+                    base_color_texture: Some(TextureRef {
+                        index: 1,
+                        tex_coord: 1,
+                        transform: TextureTransform {
+                            offset: [0.5, -0.25],
+                            rotation: 1.5,
+                            scale: [2.0, 0.5],
+                        },
+                    }),
+                    ..Default::default()
+                },
+                Material {
+                    base_color_factor: [0.5, 0.25, 1.0, 0.75],
+                    metallic_factor: 0.0,
+                    roughness_factor: 0.5,
+                    metallic_roughness_texture: Some(TextureRef {
+                        index: 0,
+                        tex_coord: 0,
+                        transform: TextureTransform::default(),
+                    }),
+                    normal_texture: Some(TextureRef {
+                        index: 0,
+                        tex_coord: 1,
+                        transform: TextureTransform::default(),
+                    }),
+                    normal_scale: 0.5,
+                    occlusion_strength: 0.25,
+                    emissive_factor: [1.0, 0.5, 0.0],
+                    alpha_mode: AlphaMode::Blend,
+                    double_sided: true,
+                    unlit: true,
+                    ..Default::default()
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_decoded_tile_roundtrip() {
+        let tile = sample_tile();
+        let bytes = encode_decoded_tile(&tile).expect("encode");
+        let decoded = decode_decoded_tile(&bytes).expect("decode");
+
+        let (
+            TileState::Decoded {
+                nodes: n0,
+                meshes: m0,
+                mesh_primitives: p0,
+                textures: t0,
+                materials: mat0,
+            },
+            TileState::Decoded {
+                nodes: n1,
+                meshes: m1,
+                mesh_primitives: p1,
+                textures: t1,
+                materials: mat1,
+            },
+        ) = (&tile, &decoded)
+        else {
+            panic!("Expected decoded tiles");
+        };
+
+        assert_eq!(n0, n1);
+        assert_eq!(p0, p1);
+        assert_eq!(t0, t1);
+        assert_eq!(mat0, mat1);
+        assert_eq!(m0, m1);
+    }
+
+    #[test]
+    fn test_decoded_tile_rejects_bad_input() {
+        let bytes = encode_decoded_tile(&sample_tile()).expect("encode");
+
+        // Truncated payloads must not decode (or allocate wildly)
+        for len in [0, 4, 8, bytes.len() / 2, bytes.len() - 1] {
+            assert!(decode_decoded_tile(&bytes[..len]).is_err());
+        }
+
+        // A different layout version is a miss, not a misread
+        let mut wrong_version = bytes.clone();
+        wrong_version[4] = wrong_version[4].wrapping_add(1);
+        assert!(decode_decoded_tile(&wrong_version).is_err());
+
+        assert!(encode_decoded_tile(&TileState::ToLoad).is_err());
+    }
+}
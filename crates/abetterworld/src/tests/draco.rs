@@ -0,0 +1,147 @@
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, path::PathBuf};
+
+    use sha2::{Digest, Sha256};
+
+    use crate::decode::{draco::decode_mesh, Vertex};
+    use crate::tests::varint;
+
+    fn corpus_dir() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/tests/data/draco")
+    }
+
+    fn corpus() -> Vec<(String, Vec<u8>)> {
+        let dir = corpus_dir();
+        let mut files: Vec<_> = std::fs::read_dir(&dir)
+            .expect("draco corpus directory")
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "drc"))
+            .collect();
+        files.sort();
+        assert!(!files.is_empty(), "no .drc files in {}", dir.display());
+        files
+            .into_iter()
+            .map(|path| {
+                let name = path.file_name().unwrap().to_string_lossy().into_owned();
+                (name, std::fs::read(&path).unwrap())
+            })
+            .collect()
+    }
+
+    /// Digests of what the C++ decoder made of each file, from `cpp.sha256`.
+    fn cpp_digests() -> HashMap<String, String> {
+        std::fs::read_to_string(corpus_dir().join("cpp.sha256"))
+            .expect("draco reference digests")
+            .lines()
+            .filter_map(|line| line.split_once("  "))
+            .map(|(digest, name)| (name.to_string(), digest.to_string()))
+            .collect()
+    }
+
+    fn digest(vertices: &[Vertex], indices: &[u32]) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(bytemuck::cast_slice(vertices));
+        hasher.update(bytemuck::cast_slice(indices));
+        hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect()
+    }
+
+    #[test]
+    fn rust_decoder_reads_corpus() {
+        for (name, data) in corpus() {
+            let (vertices, indices) = decode_mesh(&data).unwrap_or_else(|e| panic!("{name}: {e}"));
+            assert!(!vertices.is_empty(), "{name}: no vertices");
+            assert_eq!(indices.len() % 3, 0, "{name}: partial face");
+            assert!(
+                indices.iter().all(|&i| (i as usize) < vertices.len()),
+                "{name}: index out of range"
+            );
+        }
+    }
+
+    #[test]
+    fn rust_decoder_rejects_truncated_input() {
+        for (name, data) in corpus() {
+            for len in [0, 5, 11, data.len() / 2, data.len() - 1] {
+                assert!(
+                    decode_mesh(&data[..len]).is_err(),
+                    "{name}: accepted {len} bytes"
+                );
+            }
+        }
+    }
+
+    /// An edgebreaker header claiming `faces` faces over a few stray bytes.
+    fn forged_edgebreaker(traversal: u8, faces: u32) -> Vec<u8> {
+        let mut data = b"DRACO\x02\x02\x01\x01\x00\x00".to_vec();
+        data.push(traversal);
+        varint(4_000, &mut data);
+        varint(faces.into(), &mut data);
+        data.push(0);
+        varint(faces.into(), &mut data);
+        varint(0, &mut data);
+        data.extend([0; 16]);
+        data
+    }
+
+    #[test]
+    fn rust_decoder_rejects_inflated_face_counts() {
+        let err = |data: &[u8]| decode_mesh(data).unwrap_err().to_string();
+        assert!(err(&forged_edgebreaker(0, 3_000_000)).contains("more symbols than the input"));
+        assert!(err(&forged_edgebreaker(2, 5_000_000)).contains("too many faces"));
+    }
+
+    /// Checked in builds without the C++ decoder too, against its stored
+    /// output.
+    #[test]
+    fn rust_decoder_matches_stored_cpp_output() {
+        let digests = cpp_digests();
+        for (name, data) in corpus() {
+            let (vertices, indices) = decode_mesh(&data).unwrap();
+            let expected = digests
+                .get(&name)
+                .unwrap_or_else(|| panic!("{name}: no entry in cpp.sha256"));
+            assert_eq!(&digest(&vertices, &indices), expected, "{name}");
+        }
+    }
+
+    /// Both decoders must agree bit for bit, including the float conversion
+    /// of quantized and integer attributes.
+    #[cfg(not(feature = "rust-draco"))]
+    #[test]
+    fn rust_decoder_matches_cpp() {
+        let client = crate::decode::DracoClient::new();
+        let digests = cpp_digests();
+        for (name, data) in corpus() {
+            let (vertices, indices) = decode_mesh(&data).unwrap();
+            let reference = futures::executor::block_on(client.decode(&data))
+                .unwrap_or_else(|e| panic!("{name}: {e}"));
+            let reference_digest = digest(reference.as_vertex_slice(), reference.as_index_slice());
+            assert_eq!(
+                digests.get(&name),
+                Some(&reference_digest),
+                "{name}: stored output is stale, cpp.sha256 needs `{reference_digest}  {name}`"
+            );
+            assert_eq!(
+                indices,
+                reference.as_index_slice(),
+                "{name}: indices differ"
+            );
+            assert_eq!(
+                vertices.len(),
+                reference.as_vertex_slice().len(),
+                "{name}: vertex count differs"
+            );
+            for (i, (a, b)) in vertices.iter().zip(reference.as_vertex_slice()).enumerate() {
+                assert!(
+                    bytemuck::bytes_of(a) == bytemuck::bytes_of(b),
+                    "{name}: vertex {i} differs: {a:?} vs {b:?}"
+                );
+            }
+        }
+    }
+}
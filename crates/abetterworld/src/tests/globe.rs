@@ -0,0 +1,332 @@
+#[cfg(test)]
+mod tests {
+    use crate::{
+        content::{
+            build_globe_mesh,
+            pmtiles::{find_entry, hilbert_index, parse_directory, read_varint, tile_id, PmTiles},
+            Globe, GlobeTileId, GLOBE_GRID,
+        },
+        dynamics::init_camera,
+        helpers::geodetic_to_ecef_z_up,
+        tests::varint,
+        GlobeConfig,
+    };
+    use cgmath::{EuclideanSpace, InnerSpace, Point3, Vector3};
+    use std::{collections::HashSet, io::Cursor, io::Write};
+
+    #[test]
+    fn walks_the_quadtree() {
+        let tile = GlobeTileId::new(3, 5, 2);
+        let children = tile.children();
+        assert_eq!(children[0], GlobeTileId::new(4, 10, 4));
+        assert_eq!(children[3], GlobeTileId::new(4, 11, 5));
+        assert!(children.iter().all(|c| c.parent() == Some(tile)));
+        assert_eq!(GlobeTileId::ROOT.parent(), None);
+
+        let deep = GlobeTileId::new(6, 43, 17);
+        assert_eq!(deep.ancestor(3), tile);
+        assert_eq!(deep.ancestor(9), deep);
+        assert_eq!(deep.within(tile), [3.0 / 8.0, 1.0 / 8.0, 1.0 / 8.0]);
+        assert_eq!(deep.within(deep), [0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn covers_the_mercator_range() {
+        let root = GlobeTileId::ROOT;
+        assert_eq!(root.lon(0.0), -180.0);
+        assert_eq!(root.lon(1.0), 180.0);
+        assert!((root.lat(0.0) - 85.0511).abs() < 1e-4);
+        assert!((root.lat(1.0) + 85.0511).abs() < 1e-4);
+        assert!(root.lat(0.5).abs() < 1e-9);
+
+        // children split the parent's edges
+        let [nw, _, _, se] = root.children();
+        assert_eq!(nw.lat(1.0), root.lat(0.5));
+        assert_eq!(se.lon(0.0), root.lon(0.5));
+        assert!(nw.texel_meters() < root.texel_meters());
+    }
+
+    fn face_normal(p: [Point3<f64>; 3]) -> Vector3<f64> {
+        (p[1] - p[0]).cross(p[2] - p[0])
+    }
+
+    fn check_mesh(tile: GlobeTileId) {
+        let (center, mesh) = build_globe_mesh(tile);
+        let position = |i: u32| {
+            let [x, y, z] = mesh.as_vertex_slice()[i as usize].position;
+            center + Vector3::new(x as f64, y as f64, z as f64)
+        };
+        let volume = tile.bounding_volume().to_aabb();
+        for v in 0..mesh.as_vertex_slice().len() as u32 {
+            let p = position(v).to_vec();
+            for axis in 0..3 {
+                assert!(volume.min[axis] <= p[axis] && p[axis] <= volume.max[axis]);
+            }
+        }
+
+        let middle = geodetic_to_ecef_z_up(tile.lat(0.5), tile.lon(0.5), 0.0);
+        let mut skirts = 0;
+        for (t, triangle) in mesh.as_index_slice().chunks(3).enumerate() {
+            let p = [
+                position(triangle[0]),
+                position(triangle[1]),
+                position(triangle[2]),
+            ];
+            let n = face_normal(p);
+            // rows out to the pole collapse into slivers
+            if n.magnitude() < 1e-3 {
+                continue;
+            }
+            let centroid = (p[0].to_vec() + p[1].to_vec() + p[2].to_vec()) / 3.0;
+            let up = centroid.normalize();
+            // the surface faces up or, wound the wrong way, down; skirts
+            // hang sideways
+            let facing = n.normalize().dot(up);
+            if facing.abs() > 0.5 {
+                assert!(facing > 0.9, "{tile:?} face {t} points inward");
+            } else {
+                let away = centroid - middle.to_vec();
+                let away = away - up * away.dot(up);
+                assert!(n.dot(away) > 0.0, "{tile:?} skirt {t} points inward");
+                skirts += 1;
+            }
+        }
+        assert!(skirts >= GLOBE_GRID * 4);
+    }
+
+    #[test]
+    fn winds_faces_and_skirts_outward() {
+        check_mesh(GlobeTileId::new(3, 2, 3));
+        check_mesh(GlobeTileId::new(6, 40, 50));
+        // with the row out to the north pole
+        check_mesh(GlobeTileId::new(2, 1, 0));
+    }
+
+    fn select_from(lat: f64, lon: f64, alt: f64, max_level: u8) -> Vec<GlobeTileId> {
+        let camera = init_camera(Point3::new(lat, lon, alt));
+        let globe = Globe::new(&GlobeConfig {
+            max_level,
+            ..Default::default()
+        })
+        .unwrap();
+        let selection = globe.select(&camera.refinement_data());
+
+        let keys: HashSet<_> = selection.iter().map(|s| s.tile.key()).collect();
+        for selected in &selection {
+            assert_eq!(
+                selected.info.parent,
+                selected.tile.parent().map(|p| p.key())
+            );
+            if let Some(children) = &selected.info.children {
+                assert!(children.iter().all(|c| keys.contains(c)));
+            }
+        }
+        selection.into_iter().map(|s| s.tile).collect()
+    }
+
+    #[test]
+    fn refines_towards_the_camera() {
+        let far = select_from(34.4, -119.7, 20_000_000.0, 18);
+        assert!(far.contains(&GlobeTileId::ROOT));
+        assert!(far.iter().all(|t| t.z <= 4));
+
+        let near = select_from(34.4, -119.7, 1_000.0, 18);
+        let deepest = near.iter().max_by_key(|t| t.z).unwrap();
+        assert!(deepest.z >= 12);
+        assert!((deepest.lat(0.5) - 34.4).abs() < 0.1);
+        assert!((deepest.lon(0.5) + 119.7).abs() < 0.1);
+
+        let capped = select_from(34.4, -119.7, 1_000.0, 5);
+        assert!(capped.iter().all(|t| t.z <= 5));
+    }
+
+    #[test]
+    fn disabled_globe_selects_nothing() {
+        let config = GlobeConfig {
+            enabled: false,
+            ..Default::default()
+        };
+        assert!(Globe::new(&config).is_none());
+    }
+
+    #[test]
+    fn numbers_tiles_along_the_hilbert_curve() {
+        assert_eq!(tile_id(GlobeTileId::ROOT), 0);
+        assert_eq!(tile_id(GlobeTileId::new(1, 0, 0)), 1);
+        assert_eq!(tile_id(GlobeTileId::new(1, 0, 1)), 2);
+        assert_eq!(tile_id(GlobeTileId::new(1, 1, 1)), 3);
+        assert_eq!(tile_id(GlobeTileId::new(1, 1, 0)), 4);
+        assert_eq!(tile_id(GlobeTileId::new(2, 0, 0)), 5);
+
+        let level: HashSet<_> = (0..8)
+            .flat_map(|x| (0..8).map(move |y| hilbert_index(3, x, y)))
+            .collect();
+        assert_eq!(level, (0..64).collect());
+    }
+
+    /// (tile id, run length, offset, length), written the way the spec's
+    /// encoder does, continuing offsets as 0.
+    fn directory(entries: &[(u64, u32, u64, u32)]) -> Vec<u8> {
+        let mut out = Vec::new();
+        varint(entries.len() as u64, &mut out);
+        let mut last = 0;
+        for &(id, ..) in entries {
+            varint(id - last, &mut out);
+            last = id;
+        }
+        for &(_, run, ..) in entries {
+            varint(run as u64, &mut out);
+        }
+        for &(.., length) in entries {
+            varint(length as u64, &mut out);
+        }
+        for (i, &(_, _, offset, _)) in entries.iter().enumerate() {
+            let continues = i > 0 && {
+                let (_, _, prev_offset, prev_length) = entries[i - 1];
+                prev_offset + prev_length as u64 == offset
+            };
+            varint(if continues { 0 } else { offset + 1 }, &mut out);
+        }
+        out
+    }
+
+    #[test]
+    fn decodes_directories() {
+        assert_eq!(read_varint(&[0x96, 0x01]), Some((150, 2)));
+        assert_eq!(read_varint(&[0x80]), None);
+
+        let entries =
+            parse_directory(&directory(&[(0, 1, 0, 10), (1, 4, 10, 5), (9, 0, 100, 20)])).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!((entries[1].tile_id, entries[1].offset), (1, 10));
+        assert_eq!((entries[2].tile_id, entries[2].offset), (9, 100));
+
+        assert_eq!(find_entry(&entries, 4).unwrap().tile_id, 1);
+        assert_eq!(find_entry(&entries, 5), None);
+        // past the last entry, a leaf directory may still cover it
+        assert_eq!(find_entry(&entries, 50).unwrap().tile_id, 9);
+
+        assert!(parse_directory(&[5, 1]).is_err());
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    /// Level 0 on its own, level 1 as one run, level 2 behind a leaf
+    /// directory with two of its tiles.
+    fn archive() -> Vec<u8> {
+        let tiles: [&[u8]; 4] = [b"root", b"level one", b"first", b"third"];
+        let offsets: Vec<u64> = tiles
+            .iter()
+            .scan(0, |at, t| {
+                let offset = *at;
+                *at += t.len() as u64;
+                Some(offset)
+            })
+            .collect();
+        let len = |i: usize| tiles[i].len() as u32;
+
+        let leaf = gzip(&directory(&[
+            (5, 1, offsets[2], len(2)),
+            (7, 1, offsets[3], len(3)),
+        ]));
+        let root = gzip(&directory(&[
+            (0, 1, offsets[0], len(0)),
+            (1, 4, offsets[1], len(1)),
+            (5, 0, 0, leaf.len() as u32),
+        ]));
+
+        let root_offset = 127u64;
+        let leaf_offset = root_offset + root.len() as u64;
+        let data_offset = leaf_offset + leaf.len() as u64;
+        let mut header = vec![0u8; 127];
+        header[..7].copy_from_slice(b"PMTiles");
+        header[7] = 3;
+        let mut put = |at: usize, value: u64| {
+            header[at..at + 8].copy_from_slice(&value.to_le_bytes());
+        };
+        put(8, root_offset);
+        put(16, root.len() as u64);
+        put(40, leaf_offset);
+        put(48, leaf.len() as u64);
+        put(56, data_offset);
+        header[97] = 2; // gzip directories
+        header[98] = 1; // uncompressed tiles
+        header[100] = 0;
+        header[101] = 2;
+
+        [header, root, leaf, tiles.concat()].concat()
+    }
+
+    #[test]
+    fn reads_tiles_through_leaf_directories() {
+        let archive = PmTiles::new(Box::new(Cursor::new(archive()))).unwrap();
+        assert_eq!(archive.header.max_zoom, 2);
+
+        let tile = |z, x, y| archive.tile(GlobeTileId::new(z, x, y)).unwrap();
+        assert_eq!(tile(0, 0, 0).as_deref(), Some(&b"root"[..]));
+        for [x, y] in [[0, 0], [0, 1], [1, 1], [1, 0]] {
+            assert_eq!(tile(1, x, y).as_deref(), Some(&b"level one"[..]));
+        }
+        for x in 0..4 {
+            for y in 0..4 {
+                let expected: Option<&[u8]> = match tile_id(GlobeTileId::new(2, x, y)) {
+                    5 => Some(b"first"),
+                    7 => Some(b"third"),
+                    _ => None,
+                };
+                assert_eq!(tile(2, x, y).as_deref(), expected);
+            }
+        }
+        assert_eq!(tile(3, 0, 0), None);
+
+        assert!(PmTiles::new(Box::new(Cursor::new(vec![0u8; 127]))).is_err());
+    }
+
+    #[test]
+    fn rejects_directories_that_overflow() {
+        // tile id deltas summing past u64
+        let mut ids = Vec::new();
+        varint(2, &mut ids);
+        varint(u64::MAX, &mut ids);
+        varint(1, &mut ids);
+        ids.extend([0, 0, 0, 0, 1, 1]);
+        assert!(parse_directory(&ids).is_err());
+
+        // an offset continuing past u64
+        let mut offsets = Vec::new();
+        varint(2, &mut offsets);
+        offsets.extend([0, 1, 1, 1, 5, 1]);
+        varint(u64::MAX, &mut offsets);
+        offsets.push(0);
+        assert!(parse_directory(&offsets).is_err());
+    }
+
+    #[test]
+    fn bounds_reads_by_the_archive_size() {
+        let open = |patch: &dyn Fn(&mut Vec<u8>)| {
+            let mut bytes = archive();
+            patch(&mut bytes);
+            PmTiles::new(Box::new(Cursor::new(bytes)))
+        };
+        let put = |bytes: &mut Vec<u8>, at: usize, value: u64| {
+            bytes[at..at + 8].copy_from_slice(&value.to_le_bytes());
+        };
+
+        for root_length in [u64::MAX, 1 << 40] {
+            assert!(open(&|b| put(b, 16, root_length)).is_err());
+        }
+        assert!(open(&|b| put(b, 8, u64::MAX)).is_err());
+
+        // tile data and leaf directories placed past the end, or past u64
+        for data_offset in [u64::MAX, 1 << 40] {
+            let archive = open(&|b| put(b, 56, data_offset)).unwrap();
+            assert!(archive.tile(GlobeTileId::new(0, 0, 0)).is_err());
+        }
+        let archive = open(&|b| put(b, 40, u64::MAX)).unwrap();
+        assert!(archive.tile(GlobeTileId::new(2, 0, 0)).is_err());
+    }
+}
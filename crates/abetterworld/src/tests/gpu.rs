@@ -0,0 +1,29 @@
+//! A device for the tests that render, on whatever adapter is around; a
+//! software one will do.
+
+/// Set to skip the tests that need a device, where there is no adapter.
+const SKIP_GPU_TESTS: &str = "ABW_SKIP_GPU_TESTS";
+
+/// None only when `ABW_SKIP_GPU_TESTS` is set. Panics without an adapter
+/// otherwise, so a missing one can't pass for a passing test.
+pub fn headless_device() -> Option<(wgpu::Device, wgpu::Queue)> {
+    if std::env::var_os(SKIP_GPU_TESTS).is_some() {
+        return None;
+    }
+    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+    let adapter =
+        futures::executor::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::LowPower,
+            compatible_surface: None,
+            force_fallback_adapter: false,
+        }))
+        .unwrap_or_else(|e| panic!("no GPU adapter ({e}); set {SKIP_GPU_TESTS}=1 to skip"));
+    // like the bindings, so draped vectors get their stencil where they can
+    let descriptor = wgpu::DeviceDescriptor {
+        required_features: adapter.features() & wgpu::Features::DEPTH32FLOAT_STENCIL8,
+        ..Default::default()
+    };
+    let device = futures::executor::block_on(adapter.request_device(&descriptor))
+        .unwrap_or_else(|e| panic!("no GPU device ({e}); set {SKIP_GPU_TESTS}=1 to skip"));
+    Some(device)
+}
@@ -0,0 +1,275 @@
+#[cfg(test)]
+mod tests {
+    use crate::{
+        content::{
+            blend_over, composite_imagery, crop_to_tile, mbtiles::MbTiles, GlobeTileId, Imagery,
+            ImageryLocation, ImageryRequest, TextureEncoding, GLOBE_BASE_COLOR, IMAGERY_TILE_SIZE,
+        },
+        helpers::AbwError,
+        ImageryLayer, ImagerySource,
+    };
+    use bytes::Bytes;
+    use image::{Rgba, RgbaImage};
+    use std::{
+        path::PathBuf,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    fn fixture(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("src/tests/data/imagery")
+            .join(name)
+    }
+
+    /// Matches tile_data in make_fixtures.py.
+    fn tile_data(tile: GlobeTileId) -> Vec<u8> {
+        let repeat = if (tile.x + tile.y).is_multiple_of(7) {
+            200
+        } else {
+            3
+        };
+        format!("{}/{}/{};", tile.z, tile.x, tile.y)
+            .repeat(repeat)
+            .into_bytes()
+    }
+
+    fn check_archive(name: &str) {
+        let archive = MbTiles::open(fixture(name)).unwrap();
+        assert_eq!(archive.max_zoom, Some(4));
+
+        for z in 0..=4 {
+            for x in 0..1 << z {
+                for y in 0..1 << z {
+                    let tile = GlobeTileId::new(z, x, y);
+                    assert_eq!(
+                        archive.tile(tile).unwrap(),
+                        Some(tile_data(tile)),
+                        "{tile:?}"
+                    );
+                }
+            }
+        }
+        assert_eq!(archive.tile(GlobeTileId::new(5, 0, 0)).unwrap(), None);
+    }
+
+    #[test]
+    fn reads_mbtiles_through_an_index() {
+        check_archive("tiles.mbtiles");
+    }
+
+    #[test]
+    fn reads_deduplicated_mbtiles() {
+        check_archive("deduplicated.mbtiles");
+    }
+
+    #[test]
+    fn reads_rows_stored_before_a_column_was_added() {
+        check_archive("altered.mbtiles");
+    }
+
+    /// Reads every tile of an archive written out from `data`, stopping at
+    /// the first error.
+    fn read_all(data: &[u8]) -> Result<(), AbwError> {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "abw-mbtiles-{}-{}.mbtiles",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::write(&path, data).unwrap();
+        let read = || {
+            let archive = MbTiles::open(&path)?;
+            for z in 0..=4 {
+                for x in 0..1 << z {
+                    for y in 0..1 << z {
+                        archive.tile(GlobeTileId::new(z, x, y))?;
+                    }
+                }
+            }
+            Ok(())
+        };
+        let result = read();
+        let _ = std::fs::remove_file(&path);
+        result
+    }
+
+    /// The fixture and its page size.
+    fn pages() -> (Vec<u8>, usize) {
+        let data = std::fs::read(fixture("tiles.mbtiles")).unwrap();
+        let page_size = u16::from_be_bytes([data[16], data[17]]) as usize;
+        (data, page_size)
+    }
+
+    #[test]
+    fn rejects_truncated_mbtiles() {
+        let (data, page_size) = pages();
+        read_all(&data).unwrap();
+        for len in (0..data.len()).step_by(page_size) {
+            assert!(read_all(&data[..len]).is_err(), "{len} bytes");
+        }
+        assert!(read_all(b"not a database").is_err());
+    }
+
+    #[test]
+    fn rejects_cell_pointers_past_the_page() {
+        let (data, page_size) = pages();
+        for number in 0..data.len() / page_size {
+            let start = number * page_size + if number == 0 { 100 } else { 0 };
+            // table and index pages, leaving overflow pages alone
+            if ![2, 5, 10, 13].contains(&data[start]) {
+                continue;
+            }
+            let pointers = start + if data[start] < 8 { 12 } else { 8 };
+            let mut damaged = data.clone();
+            damaged[pointers..pointers + 2].copy_from_slice(&(page_size as u16 - 1).to_be_bytes());
+            assert!(read_all(&damaged).is_err(), "page {}", number + 1);
+        }
+    }
+
+    /// Overwrites random bytes anywhere in the fixture, headers included.
+    /// Damage SQLite doesn't notice just reads back wrong tiles, but none may
+    /// panic or hang.
+    #[test]
+    fn survives_random_corruption() {
+        let (data, _) = pages();
+        // xorshift64, so every run damages the same bytes
+        let mut state = 0x9e37_79b9_7f4a_7c15u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        let mut rejected = 0;
+        for _ in 0..200 {
+            let mut damaged = data.clone();
+            for _ in 0..1 + next() % 8 {
+                let at = (next() % data.len() as u64) as usize;
+                damaged[at] = next() as u8;
+            }
+            rejected += read_all(&damaged).is_err() as usize;
+        }
+        assert!(rejected > 0);
+    }
+
+    #[test]
+    fn builds_urls_per_layer() {
+        let imagery = Imagery::open(&[
+            ImageryLayer {
+                source: ImagerySource::Xyz {
+                    url: "https://tiles.example/{z}/{x}/{y}.png".to_string(),
+                    max_zoom: 3,
+                },
+                opacity: 1.0,
+            },
+            ImageryLayer {
+                source: ImagerySource::Wmts {
+                    url: "https://wmts.example/service?key=k".to_string(),
+                    layer: "roads & rail".to_string(),
+                    style: "default".to_string(),
+                    tile_matrix_set: "GoogleMapsCompatible".to_string(),
+                    format: "image/png".to_string(),
+                    max_zoom: 19,
+                },
+                opacity: 2.0,
+            },
+            // missing archives are left out
+            ImageryLayer {
+                source: ImagerySource::MbTiles {
+                    path: "no/such/file.mbtiles".to_string(),
+                },
+                opacity: 1.0,
+            },
+        ]);
+
+        let requests = imagery.requests(GlobeTileId::new(5, 20, 9));
+        assert_eq!(requests.len(), 2);
+
+        assert_eq!(requests[0].source_tile, GlobeTileId::new(3, 5, 2));
+        assert_eq!(
+            requests[0].location,
+            ImageryLocation::Url("https://tiles.example/3/5/2.png".to_string())
+        );
+
+        assert_eq!(requests[1].source_tile, GlobeTileId::new(5, 20, 9));
+        assert_eq!(requests[1].opacity, 1.0);
+        assert_eq!(
+            requests[1].location,
+            ImageryLocation::Url(
+                "https://wmts.example/service?key=k&SERVICE=WMTS&REQUEST=GetTile&VERSION=1.0.0\
+                 &LAYER=roads%20%26%20rail&STYLE=default&TILEMATRIXSET=GoogleMapsCompatible\
+                 &FORMAT=image%2Fpng&TILEMATRIX=5&TILEROW=9&TILECOL=20"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn blends_with_layer_opacity() {
+        let mut dst = RgbaImage::from_pixel(2, 1, Rgba([0, 0, 0, 255]));
+        let mut src = RgbaImage::from_pixel(2, 1, Rgba([200, 100, 50, 255]));
+        src.put_pixel(1, 0, Rgba([200, 100, 50, 0]));
+        blend_over(&mut dst, &src, 0.5);
+        assert_eq!(dst.get_pixel(0, 0).0, [100, 50, 25, 255]);
+        assert_eq!(dst.get_pixel(1, 0).0, [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn crops_ancestor_imagery() {
+        // each quadrant its own color
+        let image = RgbaImage::from_fn(64, 64, |x, y| {
+            Rgba([(x / 32 * 255) as u8, (y / 32 * 255) as u8, 0, 255])
+        });
+        let parent = GlobeTileId::new(2, 1, 1);
+        let [_, ne, sw, _] = parent.children();
+
+        let cropped = crop_to_tile(&image, ne, parent);
+        assert_eq!(cropped.dimensions(), (IMAGERY_TILE_SIZE, IMAGERY_TILE_SIZE));
+        assert_eq!(cropped.get_pixel(128, 128).0, [255, 0, 0, 255]);
+        assert_eq!(
+            crop_to_tile(&image, sw, parent).get_pixel(10, 10).0,
+            [0, 255, 0, 255]
+        );
+    }
+
+    fn png(color: [u8; 4]) -> Bytes {
+        let mut out = std::io::Cursor::new(Vec::new());
+        RgbaImage::from_pixel(8, 8, Rgba(color))
+            .write_to(&mut out, image::ImageFormat::Png)
+            .unwrap();
+        Bytes::from(out.into_inner())
+    }
+
+    fn request(bytes: Option<Bytes>, opacity: f32) -> ImageryRequest {
+        ImageryRequest {
+            location: ImageryLocation::Url(String::new()),
+            source_tile: GlobeTileId::new(1, 0, 0),
+            opacity,
+            bytes,
+        }
+    }
+
+    #[test]
+    fn composites_layers_over_the_base_color() {
+        let tile = GlobeTileId::new(1, 0, 0);
+
+        let empty = composite_imagery(tile, &[request(None, 1.0)]);
+        assert_eq!((empty.width, empty.height), (1, 1));
+        assert_eq!(empty.levels[0], GLOBE_BASE_COLOR.to_vec());
+
+        let texture = composite_imagery(
+            tile,
+            &[
+                request(Some(png([255, 0, 0, 255])), 1.0),
+                request(Some(Bytes::from_static(b"not an image")), 1.0),
+                request(Some(png([0, 0, 255, 255])), 0.25),
+            ],
+        );
+        assert_eq!(texture.encoding, TextureEncoding::Rgba8);
+        assert_eq!(
+            (texture.width, texture.height),
+            (IMAGERY_TILE_SIZE, IMAGERY_TILE_SIZE)
+        );
+        assert_eq!(&texture.levels[0][..4], &[191, 0, 64, 255]);
+    }
+}
@@ -0,0 +1,243 @@
+#[cfg(test)]
+mod tests {
+    use crate::content::{
+        build_materials, linear_textures, AlphaMode, Material, TextureRef, TextureTransform,
+    };
+    use crate::render::{
+        MaterialUniform, RenderState, MATERIAL_BLEND, MATERIAL_DOUBLE_SIDED, MATERIAL_MASK,
+        MATERIAL_NORMAL_MAP, MATERIAL_UNLIT,
+    };
+    use serde_json::json;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn apply(m: [[f32; 3]; 2], uv: [f32; 2]) -> [f32; 2] {
+        m.map(|row| row[0] * uv[0] + row[1] * uv[1] + row[2])
+    }
+
+    fn assert_close(actual: [f32; 2], expected: [f32; 2]) {
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-6, "{actual:?} vs {expected:?}");
+        }
+    }
+
+    #[test]
+    fn texture_transform_matches_spec_order() {
+        assert_eq!(
+            TextureTransform::default().matrix(),
+            [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]
+        );
+
+        // Scale first, then rotate, then offset
+        let transform = TextureTransform {
+            offset: [0.5, 0.25],
+            rotation: FRAC_PI_2,
+            scale: [2.0, 3.0],
+        };
+        let m = transform.matrix();
+        // (1, 0) -> (2, 0) -> rotated to (0, -2) -> (0.5, -1.75)
+        assert_close(apply(m, [1.0, 0.0]), [0.5, -1.75]);
+        // (0, 1) -> (0, 3) -> rotated to (3, 0) -> (3.5, 0.25)
+        assert_close(apply(m, [0.0, 1.0]), [3.5, 0.25]);
+    }
+
+    #[test]
+    fn parses_texcoord_and_texture_transform() {
+        let json = json!({
+            "materials": [
+                { "pbrMetallicRoughness": { "baseColorTexture": { "index": 2, "texCoord": 1 } } },
+                {
+                    "pbrMetallicRoughness": {
+                        "baseColorTexture": {
+                            "index": 0,
+                            "texCoord": 0,
+                            "extensions": {
+                                "KHR_texture_transform": {
+                                    "offset": [0.5, 0.0],
+                                    "rotation": 0.25,
+                                    "scale": [0.5, 0.5],
+                                    "texCoord": 1,
+                                },
+                            },
+                        },
+                    },
+                },
+                // Only two UV sets are loaded
+                { "pbrMetallicRoughness": { "baseColorTexture": { "index": 1, "texCoord": 3 } } },
+                { "pbrMetallicRoughness": { "baseColorFactor": [1.0, 0.0, 0.0, 1.0] } },
+            ],
+        });
+
+        let materials = build_materials(&json).unwrap();
+        let texture = |index, tex_coord, transform| Material {
+            base_color_texture: Some(TextureRef {
+                index,
+                tex_coord,
+                transform,
+            }),
+            ..Default::default()
+        };
+        assert_eq!(
+            materials,
+            vec![
+                texture(2, 1, TextureTransform::default()),
+                texture(
+                    0,
+                    1,
+                    TextureTransform {
+                        offset: [0.5, 0.0],
+                        rotation: 0.25,
+                        scale: [0.5, 0.5],
+                    }
+                ),
+                texture(1, 0, TextureTransform::default()),
+                Material {
+                    base_color_factor: [1.0, 0.0, 0.0, 1.0],
+                    ..Default::default()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn material_uniform_packs_transform_rows() {
+        let uniform = MaterialUniform::new(&Material::default());
+        assert_eq!(
+            uniform.uv_transforms,
+            [[[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0]]; 5]
+        );
+        assert_eq!(uniform.tex_coords, 0);
+        assert_eq!(uniform.flags, 0);
+
+        let transform = TextureTransform {
+            offset: [0.25, 0.75],
+            rotation: 0.0,
+            scale: [2.0, 4.0],
+        };
+        let uniform = MaterialUniform::new(&Material {
+            base_color_texture: Some(TextureRef {
+                index: 0,
+                tex_coord: 1,
+                transform,
+            }),
+            occlusion_texture: Some(TextureRef {
+                index: 1,
+                tex_coord: 1,
+                transform: TextureTransform::default(),
+            }),
+            ..Default::default()
+        });
+        assert_eq!(
+            uniform.uv_transforms[0],
+            [[2.0, 0.0, 0.25, 0.0], [0.0, 4.0, 0.75, 0.0]]
+        );
+        // Base color is slot 0, occlusion slot 3
+        assert_eq!(uniform.tex_coords, 0b1001);
+        assert_eq!(std::mem::size_of::<MaterialUniform>(), 224);
+    }
+
+    #[test]
+    fn parses_metallic_roughness_materials() {
+        let json = json!({
+            "materials": [
+                {
+                    "pbrMetallicRoughness": {
+                        "baseColorFactor": [0.5, 0.5, 0.5, 0.25],
+                        "metallicFactor": 0.0,
+                        "roughnessFactor": 0.75,
+                        "metallicRoughnessTexture": { "index": 1 },
+                    },
+                    "normalTexture": { "index": 2, "scale": 0.5 },
+                    "occlusionTexture": { "index": 1, "strength": 0.8 },
+                    "emissiveTexture": { "index": 3 },
+                    "emissiveFactor": [1.0, 0.5, 0.25],
+                    "alphaMode": "BLEND",
+                    "doubleSided": true,
+                },
+                { "alphaMode": "MASK", "alphaCutoff": 0.3 },
+                { "extensions": { "KHR_materials_unlit": {} } },
+            ],
+        });
+
+        let materials = build_materials(&json).unwrap();
+        let texture = |index| {
+            Some(TextureRef {
+                index,
+                tex_coord: 0,
+                transform: TextureTransform::default(),
+            })
+        };
+        assert_eq!(
+            materials[0],
+            Material {
+                base_color_factor: [0.5, 0.5, 0.5, 0.25],
+                base_color_texture: None,
+                metallic_factor: 0.0,
+                roughness_factor: 0.75,
+                metallic_roughness_texture: texture(1),
+                normal_texture: texture(2),
+                normal_scale: 0.5,
+                occlusion_texture: texture(1),
+                occlusion_strength: 0.8,
+                emissive_factor: [1.0, 0.5, 0.25],
+                emissive_texture: texture(3),
+                alpha_mode: AlphaMode::Blend,
+                alpha_cutoff: 0.5,
+                double_sided: true,
+                unlit: false,
+            }
+        );
+        assert_eq!(
+            materials[1],
+            Material {
+                alpha_mode: AlphaMode::Mask,
+                alpha_cutoff: 0.3,
+                ..Default::default()
+            }
+        );
+        assert!(materials[2].unlit);
+
+        let uniform = MaterialUniform::new(&materials[0]);
+        assert_eq!(
+            uniform.flags,
+            MATERIAL_BLEND | MATERIAL_DOUBLE_SIDED | MATERIAL_NORMAL_MAP
+        );
+        assert_eq!(MaterialUniform::new(&materials[1]).flags, MATERIAL_MASK);
+        assert_eq!(MaterialUniform::new(&materials[2]).flags, MATERIAL_UNLIT);
+        assert_eq!(
+            RenderState::new(&materials[0]),
+            RenderState {
+                blend: true,
+                double_sided: true,
+            }
+        );
+    }
+
+    #[test]
+    fn data_textures_upload_linear() {
+        let texture = |index| {
+            Some(TextureRef {
+                index,
+                tex_coord: 0,
+                transform: TextureTransform::default(),
+            })
+        };
+        let materials = [
+            Material {
+                base_color_texture: texture(0),
+                normal_texture: texture(1),
+                metallic_roughness_texture: texture(2),
+                ..Default::default()
+            },
+            // Sampled as color somewhere, so it stays sRGB
+            Material {
+                emissive_texture: texture(2),
+                occlusion_texture: texture(7),
+                ..Default::default()
+            },
+        ];
+        assert_eq!(
+            linear_textures(&materials, 4),
+            vec![false, true, false, false]
+        );
+    }
+}
@@ -0,0 +1,171 @@
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::decode::meshopt::{decompress, decompress_buffer_views, Filter, Mode};
+
+    // Streams produced by the reference meshoptimizer encoder for a 4x4 grid
+    // of vertices (u16 position + pad, u16 uv) and its 18 triangles.
+    const GRID_VERTICES: [u8; 132] = [
+        160, 3, 0, 200, 200, 200, 87, 200, 200, 200, 87, 200, 200, 200, 87, 200, 200, 200, 1, 2,
+        66, 66, 66, 1, 0, 192, 192, 192, 200, 200, 200, 1, 0, 0, 0, 128, 1, 0, 63, 255, 255, 14,
+        14, 14, 41, 28, 28, 28, 83, 42, 42, 42, 0, 0, 0, 3, 0, 47, 47, 47, 144, 47, 47, 47, 144,
+        47, 47, 47, 144, 47, 47, 47, 2, 6, 136, 246, 136, 246, 136, 246, 136, 21, 21, 21, 1, 0,
+        192, 192, 192, 47, 47, 47, 1, 0, 192, 192, 192, 6, 8, 8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    ];
+    const GRID_TRIANGLES: [u8; 50] = [
+        224, 254, 15, 254, 19, 254, 18, 191, 15, 175, 2, 159, 1, 159, 15, 159, 2, 159, 1, 15, 10,
+        1, 243, 4, 242, 2, 4, 1, 4, 2, 4, 1, 4, 2, 0, 118, 135, 86, 103, 120, 169, 134, 101, 137,
+        104, 152, 1, 105, 0, 0,
+    ];
+
+    fn grid_vertices() -> Vec<u16> {
+        let mut out = Vec::new();
+        for y in 0..4u16 {
+            for x in 0..4u16 {
+                out.extend([x * 100, y * 100, x * y * 7, 0, x * 1000, y * 1000]);
+            }
+        }
+        out
+    }
+
+    /// The grid's triangles as the reference decoder returns them; the
+    /// encoder is free to rotate a triangle's corners.
+    const GRID_INDICES: [u32; 54] = [
+        0, 1, 5, 0, 5, 4, 2, 6, 1, 1, 6, 5, 3, 7, 2, 2, 7, 6, 4, 5, 9, 4, 9, 8, 5, 6, 10, 5, 10, 9,
+        6, 7, 11, 6, 11, 10, 8, 9, 13, 8, 13, 12, 9, 10, 14, 9, 14, 13, 10, 11, 15, 10, 15, 14,
+    ];
+
+    fn u16s(bytes: &[u8]) -> Vec<u16> {
+        bytes
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect()
+    }
+
+    fn u32s(bytes: &[u8]) -> Vec<u32> {
+        bytes
+            .chunks_exact(4)
+            .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+            .collect()
+    }
+
+    #[test]
+    fn decodes_reference_streams() {
+        let vertices = decompress(&GRID_VERTICES, 16, 12, Mode::Attributes, Filter::None).unwrap();
+        assert_eq!(u16s(&vertices), grid_vertices());
+
+        let indices = decompress(&GRID_TRIANGLES, 54, 4, Mode::Triangles, Filter::None).unwrap();
+        assert_eq!(u32s(&indices), GRID_INDICES);
+
+        let short = decompress(&GRID_TRIANGLES, 54, 2, Mode::Triangles, Filter::None).unwrap();
+        let expected: Vec<u16> = GRID_INDICES.iter().map(|&i| i as u16).collect();
+        assert_eq!(u16s(&short), expected);
+    }
+
+    #[test]
+    fn decodes_index_sequence() {
+        let data = [0xd1, 0, 4, 4, 136, 3, 0, 0, 0, 0];
+        let indices = decompress(&data, 4, 4, Mode::Indices, Filter::None).unwrap();
+        assert_eq!(u32s(&indices), [0, 1, 2, 100]);
+    }
+
+    #[test]
+    fn rejects_truncated_and_corrupt_streams() {
+        for len in [0, 1, 20, GRID_VERTICES.len() - 1] {
+            assert!(decompress(
+                &GRID_VERTICES[..len],
+                16,
+                12,
+                Mode::Attributes,
+                Filter::None
+            )
+            .is_err());
+        }
+        for len in [0, 1, 20, GRID_TRIANGLES.len() - 1] {
+            assert!(
+                decompress(&GRID_TRIANGLES[..len], 54, 4, Mode::Triangles, Filter::None).is_err()
+            );
+        }
+        // counts the stream is far too short for, caught before allocating
+        for mode in [Mode::Attributes, Mode::Triangles, Mode::Indices] {
+            assert!(decompress(&GRID_VERTICES, 1 << 40, 12, mode, Filter::None).is_err());
+        }
+        let mut wrong_header = GRID_VERTICES;
+        wrong_header[0] = 0xe0;
+        assert!(decompress(&wrong_header, 16, 12, Mode::Attributes, Filter::None).is_err());
+    }
+
+    #[test]
+    fn applies_filters() {
+        let octahedral = filter_only(&[0, 0, 127, 0, 64, 0, 127, 0], 4, Filter::Octahedral);
+        assert_eq!(octahedral[..4], [0, 0, 127, 0]);
+        let n: Vec<f32> = octahedral[4..7].iter().map(|&b| b as i8 as f32).collect();
+        let length = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+        assert!((length - 127.0).abs() < 1.0, "{n:?}");
+
+        let mut identity = Vec::new();
+        for v in [0i16, 0, 0, 0x7fff] {
+            identity.extend(v.to_le_bytes());
+        }
+        let quaternion = filter_only(&identity, 8, Filter::Quaternion);
+        assert_eq!(u16s(&quaternion), [0, 0, 0, 32767]);
+
+        let packed = (0xffu32 << 24) | 3;
+        let exponential = filter_only(&packed.to_le_bytes(), 4, Filter::Exponential);
+        assert_eq!(f32::from_le_bytes(exponential.try_into().unwrap()), 1.5);
+    }
+
+    /// Decodes each element of `raw` from a one-vertex attribute stream whose
+    /// deltas are all zero, so the tail's seed vertex comes through unchanged
+    /// and only the filter acts on it.
+    fn filter_only(raw: &[u8], stride: usize, filter: Filter) -> Vec<u8> {
+        let mut out = Vec::new();
+        for element in raw.chunks_exact(stride) {
+            let mut stream = vec![0xa0];
+            stream.extend(std::iter::repeat_n(
+                0,
+                stride + 32usize.max(stride) - stride,
+            ));
+            stream.extend_from_slice(element);
+            out.extend(decompress(&stream, 1, stride, Mode::Attributes, filter).unwrap());
+        }
+        out
+    }
+
+    #[test]
+    fn rewrites_compressed_buffer_views() {
+        let mut bin = vec![1u8, 2, 3, 4];
+        bin.extend_from_slice(&GRID_VERTICES);
+        let json = json!({
+            "buffers": [{ "byteLength": bin.len() }, { "byteLength": 192, "extensions": { "EXT_meshopt_compression": { "fallback": true } } }],
+            "bufferViews": [
+                { "buffer": 0, "byteOffset": 0, "byteLength": 4 },
+                {
+                    "buffer": 1, "byteOffset": 0, "byteLength": 192, "byteStride": 12,
+                    "extensions": { "EXT_meshopt_compression": {
+                        "buffer": 0, "byteOffset": 4, "byteLength": GRID_VERTICES.len(),
+                        "byteStride": 12, "count": 16, "mode": "ATTRIBUTES"
+                    } }
+                }
+            ]
+        });
+
+        let (json, out) = decompress_buffer_views(&json, &bin).unwrap().unwrap();
+        let view = &json["bufferViews"][1];
+        assert_eq!(view["buffer"], 0);
+        assert_eq!(view["byteLength"], 192);
+        assert!(view["extensions"].get("EXT_meshopt_compression").is_none());
+        let offset = view["byteOffset"].as_u64().unwrap() as usize;
+        assert_eq!(offset % 4, 0);
+        assert_eq!(u16s(&out[offset..offset + 192]), grid_vertices());
+        assert_eq!(
+            json["bufferViews"][0],
+            json!({ "buffer": 0, "byteOffset": 0, "byteLength": 4 })
+        );
+
+        let plain = json!({ "bufferViews": [{ "buffer": 0, "byteLength": 4 }] });
+        assert!(decompress_buffer_views(&plain, &bin).unwrap().is_none());
+    }
+}
@@ -0,0 +1,112 @@
+#[cfg(test)]
+mod tests {
+    use crate::content::{
+        downsample_rgba8, generate_mips, mip_level_count, upload_textures_to_gpu, Texture,
+        TextureEncoding,
+    };
+    use crate::render::TextureUploader;
+    use crate::tests::gpu::headless_device;
+    use crate::{get_debug_config, MipmapMode};
+
+    #[test]
+    fn counts_levels_down_to_one_texel() {
+        assert_eq!(mip_level_count(1, 1), 1);
+        assert_eq!(mip_level_count(2, 1), 2);
+        assert_eq!(mip_level_count(256, 256), 9);
+        assert_eq!(mip_level_count(300, 17), 9);
+        assert_eq!(mip_level_count(0, 0), 1);
+    }
+
+    #[test]
+    fn averages_color_in_linear_light() {
+        // Black and white average to linear 0.5, which is sRGB 188, not 128
+        let src = [[0, 0, 0, 0], [255, 255, 255, 255]].repeat(2).concat();
+        assert_eq!(downsample_rgba8(&src, 2, 2), vec![188, 188, 188, 128]);
+
+        // Uniform colors survive unchanged
+        let src = [10, 100, 200, 77].repeat(4);
+        assert_eq!(downsample_rgba8(&src, 2, 2), vec![10, 100, 200, 77]);
+    }
+
+    #[test]
+    fn folds_odd_edges_and_thin_axes() {
+        // 3x1: the last column is dropped into the single output texel's clamp
+        let src = [[255, 0, 0, 255], [255, 0, 0, 255], [0, 0, 255, 255]].concat();
+        assert_eq!(downsample_rgba8(&src, 3, 1), vec![255, 0, 0, 255]);
+
+        // 1x4 keeps its width at one texel
+        let src = [
+            [0, 0, 0, 255],
+            [0, 0, 0, 255],
+            [255, 255, 255, 255],
+            [255, 255, 255, 255],
+        ]
+        .concat();
+        let out = downsample_rgba8(&src, 1, 4);
+        assert_eq!(out, vec![0, 0, 0, 255, 255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn fills_single_level_rgba8_textures_only() {
+        let mut texture = Texture {
+            width: 5,
+            height: 3,
+            encoding: TextureEncoding::Rgba8,
+            levels: vec![vec![128; 5 * 3 * 4]],
+        };
+        generate_mips(&mut texture);
+        let sizes: Vec<usize> = texture.levels.iter().map(|l| l.len()).collect();
+        assert_eq!(sizes, vec![5 * 3 * 4, 2 * 4, 4]);
+        assert!(texture.levels.iter().flatten().all(|&v| v == 128));
+
+        // Already mipped and block compressed textures are left as they are
+        let mut mipped = texture.clone();
+        mipped.levels.truncate(2);
+        generate_mips(&mut mipped);
+        assert_eq!(mipped.levels.len(), 2);
+
+        let mut bc7 = Texture {
+            width: 8,
+            height: 8,
+            encoding: TextureEncoding::Bc7,
+            levels: vec![vec![0; 4 * 16]],
+        };
+        generate_mips(&mut bc7);
+        assert_eq!(bc7.levels.len(), 1);
+    }
+
+    #[test]
+    fn gpu_mips_linear_textures_too() {
+        let Some((device, queue)) = headless_device() else {
+            return;
+        };
+        let mut config = get_debug_config();
+        config.mipmaps = MipmapMode::Gpu;
+        let uploader = TextureUploader::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb, &config);
+
+        let texture = Texture {
+            width: 4,
+            height: 4,
+            encoding: TextureEncoding::Rgba8,
+            levels: vec![vec![128; 4 * 4 * 4]],
+        };
+        let uploaded = upload_textures_to_gpu(
+            &device,
+            &queue,
+            &[texture.clone(), texture],
+            &[false, true],
+            &uploader,
+        );
+        let uploaded: Vec<_> = uploaded
+            .iter()
+            .map(|t| (t.texture.format(), t.texture.mip_level_count()))
+            .collect();
+        assert_eq!(
+            uploaded,
+            [
+                (wgpu::TextureFormat::Rgba8UnormSrgb, 3),
+                (wgpu::TextureFormat::Rgba8Unorm, 3),
+            ]
+        );
+    }
+}
@@ -14,3 +14,77 @@ pub use cache_wasm::*;
 mod paging;
 
 mod dynamics;
+
+mod decoded_tile;
+
+mod cache_memory;
+
+mod validation;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod draco;
+
+mod meshopt;
+
+mod basisu;
+
+mod accessor;
+
+mod nodes;
+
+mod materials;
+mod mipmaps;
+mod packing;
+mod normals;
+mod sun;
+mod atmosphere;
+mod shadows;
+mod msaa;
+mod gpu;
+mod readback;
+mod globe;
+mod imagery;
+mod terrain;
+mod vectors;
+mod billboards;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod workers;
+
+/// Appends `value` as a LEB128 varint, as Draco and PMTiles write them.
+pub fn varint(mut value: u64, out: &mut Vec<u8>) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// A world drawing into a `width` by `height` offscreen target, paging
+/// through `backend`.
+pub fn offscreen_world(
+    device: &wgpu::Device,
+    config: &crate::Config,
+    backend: std::sync::Arc<crate::MemoryCacheBackend>,
+    width: u32,
+    height: u32,
+) -> crate::World {
+    let surface = wgpu::SurfaceConfiguration {
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        format: wgpu::TextureFormat::Rgba8UnormSrgb,
+        width,
+        height,
+        present_mode: wgpu::PresentMode::Fifo,
+        alpha_mode: wgpu::CompositeAlphaMode::Auto,
+        view_formats: vec![],
+        desired_maximum_frame_latency: 2,
+    };
+    crate::World::new_with_cache_backend(
+        None,
+        device,
+        &surface,
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+        config,
+        backend,
+    )
+}
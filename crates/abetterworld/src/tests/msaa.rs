@@ -0,0 +1,34 @@
+#[cfg(test)]
+mod tests {
+    use crate::render::{device_sample_count, pick_sample_count, GUARANTEED_SAMPLE_COUNTS};
+    use crate::tests::gpu::headless_device;
+
+    #[test]
+    fn picks_the_largest_supported_count_up_to_the_request() {
+        let guaranteed = |count| GUARANTEED_SAMPLE_COUNTS.contains(&count);
+        assert_eq!(pick_sample_count(4, guaranteed), 4);
+        assert_eq!(pick_sample_count(8, guaranteed), 4);
+        assert_eq!(pick_sample_count(2, guaranteed), 1);
+        assert_eq!(pick_sample_count(1, guaranteed), 1);
+        assert_eq!(pick_sample_count(0, guaranteed), 1);
+
+        let any = |_| true;
+        assert_eq!(pick_sample_count(8, any), 8);
+        assert_eq!(pick_sample_count(6, any), 4);
+        assert_eq!(pick_sample_count(64, any), 16);
+
+        // 1 even when nothing else is
+        assert_eq!(pick_sample_count(4, |_| false), 1);
+    }
+
+    #[test]
+    fn keeps_to_the_guaranteed_counts_without_an_adapter() {
+        let Some((device, _queue)) = headless_device() else {
+            return;
+        };
+        let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+        assert_eq!(device_sample_count(None, &device, format, 16), 4);
+        assert_eq!(device_sample_count(None, &device, format, 4), 4);
+        assert_eq!(device_sample_count(None, &device, format, 2), 1);
+    }
+}
@@ -0,0 +1,191 @@
+#[cfg(test)]
+mod tests {
+    use crate::content::{build_meshes, build_nodes, unwrap_b3dm};
+    use crate::decode::DracoClient;
+    use cgmath::{Deg, Matrix4, Point3, Transform, Vector3};
+    use serde_json::json;
+    use std::sync::Arc;
+
+    fn y_up_to_z_up() -> Matrix4<f64> {
+        Matrix4::from_angle_x(Deg(90.0))
+    }
+
+    fn assert_maps(transform: Matrix4<f64>, from: [f64; 3], to: [f64; 3]) {
+        let p = transform.transform_point(Point3::from(from));
+        for (a, e) in [p.x, p.y, p.z].iter().zip(to) {
+            assert!((a - e).abs() < 1e-9, "{from:?} -> {p:?}, expected {to:?}");
+        }
+    }
+
+    #[test]
+    fn walks_default_scene_hierarchy() {
+        let json = json!({
+            "scene": 1,
+            "scenes": [{ "nodes": [3] }, { "nodes": [0] }],
+            "nodes": [
+                { "translation": [10.0, 0.0, 0.0], "children": [1] },
+                { "scale": [2.0, 2.0, 2.0], "children": [2] },
+                { "translation": [0.0, 1.0, 0.0], "mesh": 0 },
+                // Only in the non-default scene
+                { "mesh": 1 },
+            ],
+        });
+
+        let nodes = build_nodes(&json, None).unwrap();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].mesh_indices, vec![0]);
+        // (0, 1, 0) * 2 + (10, 0, 0) = (10, 2, 0), then y-up to z-up
+        assert_maps(nodes[0].transform, [0.0, 0.0, 0.0], [10.0, 0.0, 2.0]);
+        assert_eq!(
+            nodes[0].transform,
+            y_up_to_z_up()
+                * Matrix4::from_translation(Vector3::new(10.0, 0.0, 0.0))
+                * Matrix4::from_scale(2.0)
+                * Matrix4::from_translation(Vector3::new(0.0, 1.0, 0.0))
+        );
+    }
+
+    #[test]
+    fn uses_unparented_nodes_without_scenes() {
+        let json = json!({
+            "nodes": [
+                { "children": [2], "matrix": [
+                    1.0, 0.0, 0.0, 0.0,
+                    0.0, 1.0, 0.0, 0.0,
+                    0.0, 0.0, 1.0, 0.0,
+                    5.0, 0.0, 0.0, 1.0,
+                ] },
+                { "mesh": 1 },
+                { "mesh": 0 },
+                // Cycles and dangling references are skipped, not followed
+                { "children": [3, 42], "mesh": 2 },
+            ],
+        });
+
+        let nodes = build_nodes(&json, None).unwrap();
+        let meshes: Vec<_> = nodes.iter().map(|n| n.mesh_indices[0]).collect();
+        assert_eq!(meshes, vec![0, 1]);
+        assert_maps(nodes[0].transform, [0.0, 0.0, 0.0], [5.0, 0.0, 0.0]);
+        assert_eq!(nodes[1].transform, y_up_to_z_up());
+    }
+
+    #[test]
+    fn applies_rtc_center_after_axis_conversion() {
+        let json = json!({
+            "extensions": { "CESIUM_RTC": { "center": [1000.0, 2000.0, 3000.0] } },
+            "scenes": [{ "nodes": [0] }],
+            "nodes": [{ "translation": [0.0, 1.0, 0.0], "mesh": 0 }],
+        });
+
+        let nodes = build_nodes(&json, None).unwrap();
+        assert_maps(
+            nodes[0].transform,
+            [0.0, 0.0, 0.0],
+            [1000.0, 2000.0, 3001.0],
+        );
+
+        // A b3dm RTC_CENTER adds to the glTF one
+        let nodes = build_nodes(&json, Some(Vector3::new(1.0, 1.0, 1.0))).unwrap();
+        assert_maps(
+            nodes[0].transform,
+            [0.0, 0.0, 0.0],
+            [1001.0, 2001.0, 3002.0],
+        );
+    }
+
+    fn b3dm(feature_json: &[u8], feature_bin: &[u8], glb: &[u8]) -> Vec<u8> {
+        let len = 28 + feature_json.len() + feature_bin.len() + glb.len();
+        let mut out = b"b3dm".to_vec();
+        for v in [1, len, feature_json.len(), feature_bin.len(), 0, 0] {
+            out.extend((v as u32).to_le_bytes());
+        }
+        out.extend(feature_json);
+        out.extend(feature_bin);
+        out.extend(glb);
+        out
+    }
+
+    #[test]
+    fn unwraps_b3dm_rtc_center() {
+        let glb = b"glTF-payload";
+        assert_eq!(unwrap_b3dm(glb).unwrap(), (None, &glb[..]));
+
+        let inline = b3dm(br#"{"BATCH_LENGTH":0,"RTC_CENTER":[1,2,3]}  "#, &[], glb);
+        let (center, payload) = unwrap_b3dm(&inline).unwrap();
+        assert_eq!(center, Some(Vector3::new(1.0, 2.0, 3.0)));
+        assert_eq!(payload, glb);
+
+        let mut body = vec![0u8; 4];
+        for v in [4.0f32, 5.0, 6.0] {
+            body.extend(v.to_le_bytes());
+        }
+        let binary = b3dm(br#"{"RTC_CENTER":{"byteOffset":4}}"#, &body, glb);
+        let (center, payload) = unwrap_b3dm(&binary).unwrap();
+        assert_eq!(center, Some(Vector3::new(4.0, 5.0, 6.0)));
+        assert_eq!(payload, glb);
+
+        let plain = b3dm(b"", &[], glb);
+        assert_eq!(unwrap_b3dm(&plain).unwrap(), (None, &glb[..]));
+
+        assert!(unwrap_b3dm(&inline[..20]).is_err());
+        assert!(unwrap_b3dm(&b3dm(br#"{"RTC_CENTER":"#, &[], glb)).is_err());
+        let mut overrun = plain.clone();
+        overrun[16..20].copy_from_slice(&1000u32.to_le_bytes());
+        assert!(unwrap_b3dm(&overrun).is_err());
+        // all four lengths at u32::MAX, which wrap a 32 bit usize
+        let mut wrapping = plain.clone();
+        for word in wrapping[12..28].chunks_mut(4) {
+            word.copy_from_slice(&u32::MAX.to_le_bytes());
+        }
+        assert!(unwrap_b3dm(&wrapping).is_err());
+    }
+
+    #[test]
+    fn binds_nodes_to_their_own_primitives() {
+        // One triangle's positions, shared by every primitive
+        let bin: Vec<u8> = [[0.0f32, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]
+            .iter()
+            .flatten()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        let primitive =
+            |material: usize| json!({ "attributes": { "POSITION": 0 }, "material": material });
+        let json = json!({
+            "bufferViews": [{ "buffer": 0, "byteLength": 36 }],
+            "accessors": [{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3" }],
+            "meshes": [
+                { "primitives": [primitive(0), primitive(1)] },
+                // The broken primitive is dropped from the range
+                { "primitives": [{ "attributes": { "POSITION": 9 } }, primitive(2)] },
+                { "primitives": [] },
+                { "primitives": [primitive(3)] },
+            ],
+            "scenes": [{ "nodes": [0, 1, 2] }],
+            "nodes": [{ "mesh": 3 }, { "mesh": 0 }, { "mesh": 2, "children": [3] }, { "mesh": 1 }],
+        });
+
+        let (meshes, mesh_primitives) =
+            futures::executor::block_on(build_meshes(Arc::new(DracoClient::new()), &json, &bin))
+                .unwrap();
+        assert_eq!(mesh_primitives, vec![0..2, 2..3, 3..3, 3..4]);
+
+        let nodes = build_nodes(&json, None).unwrap();
+        let drawn: Vec<Vec<Option<usize>>> = nodes
+            .iter()
+            .map(|node| {
+                node.primitives(&mesh_primitives)
+                    .map(|p| meshes[p].material_index)
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            drawn,
+            vec![vec![Some(3)], vec![Some(0), Some(1)], vec![], vec![Some(2)]]
+        );
+
+        // Mesh indices without a range (e.g. a bad glTF reference) draw nothing
+        let mut stray = nodes[0].clone();
+        stray.mesh_indices = vec![7];
+        assert_eq!(stray.primitives(&mesh_primitives).count(), 0);
+    }
+}
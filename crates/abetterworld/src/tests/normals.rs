@@ -0,0 +1,123 @@
+#[cfg(test)]
+mod tests {
+    use crate::content::{generate_normals, has_normals};
+    use crate::decode::{OwnedDecodedMesh, Vertex};
+
+    fn mesh(positions: &[[f32; 3]], indices: Vec<u32>) -> OwnedDecodedMesh {
+        let vertices = positions
+            .iter()
+            .map(|&position| Vertex {
+                position,
+                normal: [0.0, 0.0, 1.0],
+                color: [1.0; 4],
+                texcoord0: [0.0; 2],
+                texcoord1: [0.0; 2],
+            })
+            .collect();
+        let mut mesh = OwnedDecodedMesh::from_vertices_and_indices(vertices, indices);
+        mesh.material_index = Some(3);
+        mesh
+    }
+
+    fn assert_normal(actual: [f32; 3], expected: [f32; 3]) {
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-5, "{actual:?} vs {expected:?}");
+        }
+    }
+
+    // Two faces folded 90 degrees along the y axis: one in z = 0 facing +z,
+    // one in x = 0 facing +x
+    fn fold() -> OwnedDecodedMesh {
+        mesh(
+            &[
+                [0.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0],
+                [1.0, 0.0, 0.0],
+                [0.0, 0.0, 1.0],
+            ],
+            vec![0, 2, 1, 0, 1, 3],
+        )
+    }
+
+    #[test]
+    fn detects_placeholder_normals() {
+        assert!(!has_normals(&fold()));
+        assert!(has_normals(&generate_normals(&fold(), 60.0)));
+    }
+
+    #[test]
+    fn smooths_within_the_crease_angle() {
+        let out = generate_normals(&fold(), 100.0);
+        let v = out.as_vertex_slice();
+        assert_eq!(v.len(), 4);
+        assert_eq!(out.material_index, Some(3));
+        let s = std::f32::consts::FRAC_1_SQRT_2;
+        // Shared edge vertices average both faces (equal area, equal angle)
+        assert_normal(v[0].normal, [s, 0.0, s]);
+        assert_normal(v[1].normal, [s, 0.0, s]);
+        assert_normal(v[2].normal, [0.0, 0.0, 1.0]);
+        assert_normal(v[3].normal, [1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn splits_vertices_across_creases() {
+        let out = generate_normals(&fold(), 60.0);
+        let v = out.as_vertex_slice();
+        let i = out.as_index_slice();
+        // The two edge vertices are duplicated for the second face
+        assert_eq!(v.len(), 6);
+        assert_eq!(i, &[0, 2, 1, 4, 5, 3]);
+        for &index in &i[..3] {
+            assert_normal(v[index as usize].normal, [0.0, 0.0, 1.0]);
+        }
+        let source = fold();
+        for (copy, original) in [(4, 0), (5, 1), (3, 3)] {
+            assert_normal(v[copy].normal, [1.0, 0.0, 0.0]);
+            assert_eq!(
+                v[copy].position,
+                source.as_vertex_slice()[original].position
+            );
+        }
+    }
+
+    #[test]
+    fn weights_by_area_and_angle_across_seams() {
+        // A shallow roof: a large face tilted toward +x and a small one
+        // toward -x, meeting along the y axis. The faces don't share vertex
+        // indices (as at a UV seam) but still smooth together.
+        let out = generate_normals(
+            &mesh(
+                &[
+                    [0.0, 0.0, 0.0],
+                    [0.0, 1.0, 0.0],
+                    [4.0, 0.0, -0.4],
+                    [0.0, 0.0, 0.0],
+                    [-1.0, 0.0, -0.1],
+                    [0.0, 1.0, 0.0],
+                ],
+                vec![0, 2, 1, 3, 5, 4],
+            ),
+            60.0,
+        );
+        let v = out.as_vertex_slice();
+        assert_eq!(v.len(), 6);
+        assert_normal(v[0].normal, v[3].normal);
+        assert_normal(v[1].normal, v[5].normal);
+        // Leans toward the bigger face's normal, (0.0995, 0, 0.995)
+        assert!(v[0].normal[0] > 0.05, "{:?}", v[0].normal);
+
+        // Degenerate triangles don't poison their neighbours
+        let out = generate_normals(
+            &mesh(
+                &[[0.0; 3], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [2.0, 0.0, 0.0]],
+                vec![0, 1, 2, 0, 1, 3],
+            ),
+            60.0,
+        );
+        assert_normal(out.as_vertex_slice()[2].normal, [0.0, 0.0, 1.0]);
+        assert!(out
+            .as_vertex_slice()
+            .iter()
+            .all(|v| v.normal.iter().all(|c| c.is_finite())));
+    }
+}
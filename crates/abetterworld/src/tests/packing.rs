@@ -0,0 +1,187 @@
+#[cfg(test)]
+mod tests {
+    use crate::content::{octahedral_encode, pack_mesh, PackedMesh, Quantization};
+    use crate::decode::{OwnedDecodedMesh, Vertex};
+    use crate::render::VertexLayout;
+    use cgmath::{Point3, Transform};
+
+    // Same steps as oct_decode in shader.wgsl
+    fn octahedral_decode(e: [i16; 2]) -> [f32; 3] {
+        let (x, y) = (
+            (e[0] as f32 / 32767.0).max(-1.0),
+            (e[1] as f32 / 32767.0).max(-1.0),
+        );
+        let z = 1.0 - x.abs() - y.abs();
+        let t = (-z).max(0.0);
+        let x = if x >= 0.0 { x - t } else { x + t };
+        let y = if y >= 0.0 { y - t } else { y + t };
+        let len = (x * x + y * y + z * z).sqrt();
+        [x / len, y / len, z / len]
+    }
+
+    fn vertex(position: [f32; 3]) -> Vertex {
+        Vertex {
+            position,
+            normal: [0.0, 0.0, 1.0],
+            color: [1.0; 4],
+            texcoord0: [0.25, 0.75],
+            texcoord1: [0.0; 2],
+        }
+    }
+
+    fn mesh(vertices: Vec<Vertex>) -> OwnedDecodedMesh {
+        OwnedDecodedMesh::from_vertices_and_indices(vertices, vec![0, 1, 2])
+    }
+
+    /// Packs a tile of one mesh with absolute positions.
+    fn pack(mesh: &OwnedDecodedMesh) -> PackedMesh {
+        pack_mesh(
+            mesh,
+            Quantization::for_tile(std::slice::from_ref(mesh), false),
+        )
+    }
+
+    fn i16_at(bytes: &[u8], offset: usize) -> i16 {
+        i16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+    }
+
+    #[test]
+    fn octahedral_normals_round_trip() {
+        let s = std::f32::consts::FRAC_1_SQRT_2;
+        for n in [
+            [0.0, 0.0, 1.0],
+            [0.0, 0.0, -1.0],
+            [1.0, 0.0, 0.0],
+            [0.0, -1.0, 0.0],
+            [s, 0.0, -s],
+            [0.48, -0.6, -0.64],
+            [-0.36, 0.48, 0.8],
+        ] {
+            let d = octahedral_decode(octahedral_encode(n));
+            let dot: f32 = n.iter().zip(d).map(|(a, b)| a * b).sum();
+            assert!(dot > 0.99999, "{n:?} came back as {d:?}");
+        }
+        assert_eq!(
+            octahedral_decode(octahedral_encode([0.0; 3])),
+            [0.0, 0.0, 1.0]
+        );
+    }
+
+    #[test]
+    fn layouts_describe_their_stride() {
+        for layout in VertexLayout::ALL {
+            let desc = layout.desc();
+            let last = desc.attributes.last().unwrap();
+            assert_eq!(last.offset + last.format.size(), desc.array_stride);
+            assert_eq!(desc.array_stride as usize, layout.stride());
+            assert_eq!(VertexLayout::from_bits(layout.bits()), Some(layout));
+        }
+        assert_eq!(VertexLayout::default().stride(), 16);
+        assert_eq!(VertexLayout::from_bits(4), None);
+    }
+
+    #[test]
+    fn drops_absent_attributes() {
+        let packed = pack(&mesh(vec![
+            vertex([0.0, 0.0, 0.0]),
+            vertex([1.0, 0.0, 0.0]),
+            vertex([0.0, 1.0, 0.0]),
+        ]));
+        assert_eq!(packed.layout, VertexLayout::default());
+        assert_eq!(packed.vertices.len(), 3 * 16);
+        assert_eq!(packed.indices, vec![0, 1, 2]);
+
+        let mut colored = vertex([0.0; 3]);
+        colored.color = [1.0, 0.0, 0.0, 1.0];
+        let mut second_uv = vertex([1.0; 3]);
+        second_uv.texcoord1 = [0.5, 0.5];
+        let packed = pack(&mesh(vec![colored, second_uv, vertex([2.0; 3])]));
+        assert_eq!(
+            packed.layout,
+            VertexLayout {
+                color: true,
+                texcoord1: true,
+            }
+        );
+        assert_eq!(packed.vertex_count(), 3);
+        // Color follows the normal as Unorm8
+        assert_eq!(&packed.vertices[12..16], &[255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn position_w_flags_real_normals() {
+        // The decoders' +Z placeholder isn't a real normal
+        let packed = pack(&mesh(vec![vertex([0.0; 3]); 3]));
+        assert_eq!(i16_at(&packed.vertices, 6), 0);
+
+        let mut tilted = vertex([0.0; 3]);
+        tilted.normal = [0.0, 1.0, 0.0];
+        let packed = pack(&mesh(vec![tilted, vertex([1.0; 3]), vertex([2.0; 3])]));
+        let stride = packed.layout.stride();
+        for i in 0..3 {
+            assert_eq!(i16_at(&packed.vertices, i * stride + 6), i16::MAX);
+        }
+    }
+
+    /// Packs the meshes of one tile and checks every position comes back to
+    /// within half a Snorm16 step.
+    fn check_round_trip(meshes: &[[[f32; 3]; 3]], quantization: Quantization) {
+        for positions in meshes {
+            let packed = pack_mesh(
+                &mesh(positions.iter().map(|&p| vertex(p)).collect()),
+                quantization,
+            );
+            assert_eq!(packed.center, quantization.center);
+            assert_eq!(packed.extent, quantization.extent);
+
+            let dequantize = packed.dequantize();
+            for (i, expected) in positions.iter().enumerate() {
+                let v = &packed.vertices[i * packed.layout.stride()..];
+                let q = [0, 2, 4].map(|o| (i16_at(v, o) as f64 / 32767.0).max(-1.0));
+                let p = dequantize.transform_point(Point3::new(q[0], q[1], q[2]));
+                for (a, e) in [p.x, p.y, p.z].iter().zip(expected) {
+                    assert!(
+                        (a - *e as f64).abs() <= quantization.extent as f64 / 32767.0,
+                        "{p:?} vs {expected:?}"
+                    );
+                }
+                // Texcoords are half floats
+                let uv = [12, 14].map(|o| half::f16::from_bits(i16_at(v, o) as u16).to_f32());
+                assert_eq!(uv, [0.25, 0.75]);
+            }
+        }
+    }
+
+    const TILE: [[[f32; 3]; 3]; 2] = [
+        [
+            [100.0, -50.0, 3.0],
+            [140.0, -40.0, 3.5],
+            [120.0, -45.0, 2.0],
+        ],
+        [[60.0, -45.0, 2.5], [70.0, -30.0, 4.0], [65.0, -35.0, 3.0]],
+    ];
+
+    fn tile_meshes() -> Vec<OwnedDecodedMesh> {
+        TILE.iter()
+            .map(|positions| mesh(positions.iter().map(|&p| vertex(p)).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn quantizes_rtc_positions_around_the_tile_center() {
+        // the RTC center is the origin of the tile's positions
+        let quantization = Quantization::for_tile(&tile_meshes(), true);
+        assert_eq!(quantization.center, [0.0; 3]);
+        assert_eq!(quantization.extent, 140.0);
+        check_round_trip(&TILE, quantization);
+    }
+
+    #[test]
+    fn quantizes_absolute_positions_around_the_tile_bounds() {
+        // one frame for both meshes, around the middle of the two together
+        let quantization = Quantization::for_tile(&tile_meshes(), false);
+        assert_eq!(quantization.center, [100.0, -40.0, 3.0]);
+        assert_eq!(quantization.extent, 40.0);
+        check_round_trip(&TILE, quantization);
+    }
+}
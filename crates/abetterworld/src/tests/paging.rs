@@ -1,24 +1,12 @@
 #[cfg(test)]
 mod tests {
-    use std::sync::Arc;
-
     pub const GOOGLE_API_KEY: &str = "Google Key";
     pub const GOOGLE_API_URL: &str = "https://tile.googleapis.com/v1/3dtiles/root.json";
 
-    use cgmath::Point3;
-
-    use crate::{
-        cache::{get_tileset_cache, init_tileset_cache},
-        content::{pager::build_client, TileManager},
-        dynamics::init_camera,
-        helpers::{channel::channel, PlatformAwait},
-    };
-
     #[test]
     fn test_paging() {
-        /*         init_tileset_cache("../tilesets");
-        let cache = get_tileset_cache();
-        cache.clear().expect("Failed to clear cache");
+        /*         let cache = Arc::new(TilesetCache::new(Arc::new(FsCacheBackend::new("../tilesets"))));
+        cache.clear().platform_await().expect("Failed to clear cache");
 
         let camera = init_camera(Point3::new(34.4208, -119.6982, 6_378_137.0 * 2.0)); // Santa Barbara
 
@@ -0,0 +1,232 @@
+#[cfg(test)]
+mod tests {
+    use crate::{
+        cache::MemoryCacheBackend,
+        get_debug_config,
+        helpers::geodetic_to_ecef_z_up,
+        render::{padded_bytes_per_row, to_rgba8, unpad_rows},
+        tests::{gpu::headless_device, offscreen_world},
+        Billboard, CameraPosition, Icon, Label, Location, Orientation, Polygon, Polyline,
+        RenderedImage, Source, World,
+    };
+    use cgmath::{EuclideanSpace, InnerSpace};
+    use std::{
+        sync::Arc,
+        time::{Duration, Instant},
+    };
+
+    #[test]
+    fn pads_rows_to_the_copy_alignment() {
+        assert_eq!(padded_bytes_per_row(1, 4), 256);
+        assert_eq!(padded_bytes_per_row(64, 4), 256);
+        assert_eq!(padded_bytes_per_row(65, 4), 512);
+
+        let padded: Vec<u8> = (0..2)
+            .flat_map(|row| (0..8).map(move |i| if i < 3 { row * 10 + i } else { 0xff }))
+            .collect();
+        assert_eq!(unpad_rows(&padded, 3, 8), vec![0, 1, 2, 10, 11, 12]);
+    }
+
+    #[test]
+    fn reads_back_rgba_and_bgra_only() {
+        let pixel = vec![1, 2, 3, 4];
+        assert_eq!(
+            to_rgba8(wgpu::TextureFormat::Rgba8UnormSrgb, pixel.clone()).unwrap(),
+            vec![1, 2, 3, 4]
+        );
+        assert_eq!(
+            to_rgba8(wgpu::TextureFormat::Bgra8Unorm, pixel.clone()).unwrap(),
+            vec![3, 2, 1, 4]
+        );
+        assert!(to_rgba8(wgpu::TextureFormat::Rgba16Float, pixel).is_err());
+    }
+
+    fn unreachable_source_world(device: &wgpu::Device, globe: bool) -> World {
+        let mut config = get_debug_config();
+        config.source = Source::Google {
+            key: String::new(),
+            url: "http://127.0.0.1:9/root.json".to_string(),
+        };
+        config.globe.enabled = globe;
+        // multisampled, so render_to_image has pipelines to swap and put back
+        config.msaa_samples = 4;
+        let backend = Arc::new(MemoryCacheBackend::new(1 << 20));
+        // the size of the images rendered from it, so putting the viewport
+        // back after each one leaves the camera as it was
+        offscreen_world(device, &config, backend, 40, 30)
+    }
+
+    /// Looking at the horizon from 10 km up
+    fn horizon_camera() -> CameraPosition {
+        CameraPosition {
+            location: Location::Geodetic(34.4208, -119.6982, 10_000.0),
+            orientation: Orientation::HeadingPitchRoll(0.0, 0.0, 0.0),
+        }
+    }
+
+    /// Needs an adapter, a software one will do. With nothing to load from,
+    /// it times out on an empty world and gets the clear color.
+    #[test]
+    fn renders_to_an_image_without_a_surface() {
+        let Some((device, queue)) = headless_device() else {
+            return;
+        };
+
+        let mut world = unreachable_source_world(&device, false);
+        let camera = horizon_camera();
+        let sample_count = world.sample_count();
+        let image = world
+            .render_to_image(&device, &queue, camera, 40, 30, Duration::from_millis(300))
+            .unwrap();
+        // Drawn single sampled, with the world's own pipelines put back after
+        assert_eq!(world.sample_count(), sample_count);
+
+        assert!(!image.complete);
+        assert_eq!((image.width, image.height), (40, 30));
+        assert_eq!(image.rgba.len(), 40 * 30 * 4);
+        assert_eq!(image.depth.len(), 40 * 30);
+        // No tiles, so nothing wrote depth
+        assert!(image.depth.iter().all(|&d| d == 0.0));
+        assert!(image.rgba.chunks(4).all(|px| px[3] == 255));
+    }
+
+    /// Level at 10 km, looking north along the ground
+    fn level_camera() -> CameraPosition {
+        let eye = geodetic_to_ecef_z_up(34.4208, -119.6982, 10_000.0);
+        let ahead = geodetic_to_ecef_z_up(35.4208, -119.6982, 10_000.0);
+        let up = eye.to_vec().normalize();
+        CameraPosition {
+            location: Location::Geocentric(eye.x, eye.y, eye.z),
+            orientation: Orientation::TargetUp((ahead.x, ahead.y, ahead.z), (up.x, up.y, up.z)),
+        }
+    }
+
+    /// The unreachable source never lets the view settle, so renders until
+    /// the globe covers the bottom row, however slow the adapter is.
+    fn render_globe(
+        world: &mut World,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        camera: CameraPosition,
+    ) -> RenderedImage {
+        let deadline = Instant::now() + Duration::from_secs(30);
+        loop {
+            let image = world
+                .render_to_image(device, queue, camera, 40, 30, Duration::from_secs(1))
+                .unwrap();
+            if image.depth[29 * 40..].iter().all(|&d| d > 0.0) || Instant::now() > deadline {
+                break image;
+            }
+        }
+    }
+
+    /// The globe needs no network without imagery layers, so it covers the
+    /// ground under the horizon while the sky stays at the far plane.
+    #[test]
+    fn draws_the_globe_beneath_missing_tiles() {
+        let Some((device, queue)) = headless_device() else {
+            return;
+        };
+
+        let mut world = unreachable_source_world(&device, true);
+        let image = render_globe(&mut world, &device, &queue, level_camera());
+
+        let row = |y: usize| &image.depth[y * 40..(y + 1) * 40];
+        assert!(row(0).iter().all(|&d| d == 0.0));
+        assert!(row(29).iter().all(|&d| d > 0.0 && d < 1e-6));
+    }
+
+    /// Clamped shapes go through the stencil counting and painting passes as
+    /// well as onto the globe. Without tiles inside their volumes the drape
+    /// paints nothing, so the ground below gets the fill and the sky stays.
+    #[test]
+    fn drapes_clamped_shapes_over_the_globe() {
+        let Some((device, queue)) = headless_device() else {
+            return;
+        };
+
+        let mut world = unreachable_source_world(&device, true);
+        let camera = level_camera();
+        let globe = render_globe(&mut world, &device, &queue, camera);
+        let corner = |lat, lon| Location::Geodetic(lat, lon, 0.0);
+        world.add_vector(Polygon {
+            exterior: vec![
+                corner(34.0, -121.0),
+                corner(34.0, -118.5),
+                corner(34.8, -118.5),
+                corner(34.8, -121.0),
+            ],
+            holes: Vec::new(),
+            color: [255, 0, 255, 255],
+            clamp_to_ground: true,
+        });
+        world.add_vector(Polyline {
+            positions: vec![corner(34.6, -119.6982), corner(36.0, -119.6982)],
+            width: 4.0,
+            color: [0, 255, 0, 255],
+            clamp_to_ground: true,
+        });
+        let image = render_globe(&mut world, &device, &queue, camera);
+
+        let pixel = |image: &RenderedImage, x: usize, y: usize| {
+            let i = (y * 40 + x) * 4;
+            image.rgba[i..i + 4].to_vec()
+        };
+        assert_eq!(pixel(&image, 3, 29), [255, 0, 255, 255]);
+        assert_eq!(pixel(&image, 3, 0), pixel(&globe, 3, 0));
+        assert_eq!(pixel(&image, 20, 0), pixel(&globe, 20, 0));
+    }
+
+    /// A point a kilometer ahead lands in the middle of the image, the same
+    /// size whatever the distance, with its label drawn from the glyph atlas.
+    #[test]
+    fn draws_billboards_facing_the_screen() {
+        let Some((device, queue)) = headless_device() else {
+            return;
+        };
+
+        let eye = geodetic_to_ecef_z_up(34.4208, -119.6982, 10_000.0);
+        let ahead = geodetic_to_ecef_z_up(34.4298, -119.6982, 10_000.0);
+        let up = eye.to_vec().normalize();
+        let camera = CameraPosition {
+            location: Location::Geocentric(eye.x, eye.y, eye.z),
+            orientation: Orientation::TargetUp((ahead.x, ahead.y, ahead.z), (up.x, up.y, up.z)),
+        };
+
+        let mut world = unreachable_source_world(&device, false);
+        let sky = world
+            .render_to_image(&device, &queue, camera, 40, 30, Duration::from_millis(300))
+            .unwrap();
+        world.add_billboard(Billboard {
+            icon: Some(Icon::Point {
+                diameter: 10.0,
+                color: [255, 0, 0, 255],
+                outline_color: [0; 4],
+                outline_width: 0.0,
+            }),
+            label: Some(Label {
+                offset: [0.0, -14.0],
+                ..Label::new("A")
+            }),
+            ..Billboard::new(Location::Geocentric(ahead.x, ahead.y, ahead.z))
+        });
+        let image = world
+            .render_to_image(&device, &queue, camera, 40, 30, Duration::from_millis(300))
+            .unwrap();
+
+        let pixel = |image: &RenderedImage, x: usize, y: usize| {
+            let i = (y * 40 + x) * 4;
+            image.rgba[i..i + 4].to_vec()
+        };
+        assert_eq!(pixel(&image, 20, 15), [255, 0, 0, 255]);
+        // away from the point and the label, the sky as it was
+        assert_eq!(pixel(&image, 20, 25), pixel(&sky, 20, 25));
+        assert_eq!(pixel(&image, 3, 15), pixel(&sky, 3, 15));
+        // the label above the point, white with a dark outline
+        let changed = (0..10)
+            .flat_map(|y| (10..30).map(move |x| (x, y)))
+            .filter(|&(x, y)| pixel(&image, x, y) != pixel(&sky, x, y))
+            .count();
+        assert!(changed > 10, "{changed}");
+    }
+}
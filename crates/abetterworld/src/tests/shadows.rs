@@ -0,0 +1,123 @@
+#[cfg(test)]
+mod tests {
+    use crate::render::{cascade_splits, fit_cascades, ShadowUniform};
+    use crate::ShadowsConfig;
+    use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, SquareMatrix, Vector3, Vector4};
+
+    const NEAR: f64 = 0.5;
+
+    /// Camera relative view-projection like the renderer's: rotation only,
+    /// with a reverse Z infinite perspective.
+    fn inverse_view_proj(forward: Vector3<f64>, up: Vector3<f64>) -> Matrix4<f64> {
+        let f = 1.0 / (30f64.to_radians()).tan();
+        let aspect = 16.0 / 9.0;
+        #[rustfmt::skip]
+        let proj = Matrix4::new(
+            f / aspect, 0.0, 0.0, 0.0,
+            0.0, f, 0.0, 0.0,
+            0.0, 0.0, 0.0, -1.0,
+            0.0, 0.0, NEAR, 0.0,
+        );
+        let view = Matrix4::look_at_rh(Point3::origin(), Point3::from_vec(forward), up);
+        (proj * view).invert().unwrap()
+    }
+
+    fn camera() -> (Matrix4<f64>, Point3<f64>, Vector3<f64>) {
+        // A few hundred meters over the equator, looking north and a little down
+        let eye = Point3::new(6_378_437.0, 0.0, 0.0);
+        let forward = Vector3::new(-0.3, 0.0, 1.0).normalize();
+        (inverse_view_proj(forward, Vector3::unit_x()), eye, forward)
+    }
+
+    #[test]
+    fn splits_increase_to_the_distance() {
+        for count in 1..=4 {
+            let splits = cascade_splits(NEAR, 5_000.0, count);
+            assert_eq!(splits.len(), count);
+            assert!((splits[count - 1] - 5_000.0).abs() < 1e-6);
+            assert!(splits[0] > 1.0);
+            for pair in splits.windows(2) {
+                assert!(pair[0] < pair[1], "{splits:?}");
+            }
+        }
+        // Logarithmic leaning: the first cascade is much shorter than uniform
+        assert!(cascade_splits(NEAR, 5_000.0, 4)[0] < 5_000.0 / 8.0);
+    }
+
+    #[test]
+    fn cascades_contain_their_frustum_slices() {
+        let (inverse, eye, forward) = camera();
+        let config = ShadowsConfig {
+            cascades: 4,
+            ..Default::default()
+        };
+        let sun = Vector3::new(1.0, 0.4, 0.3).normalize();
+        let cascades = fit_cascades(&inverse, &eye, sun, &config);
+        assert_eq!(cascades.len(), config.cascades as usize);
+
+        for cascade in &cascades {
+            for depth in [cascade.near, cascade.far] {
+                for (x, y) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+                    let p = inverse * Vector4::new(x, y, 1.0, 1.0);
+                    let ray = p.truncate() / p.w;
+                    let corner = ray * (depth / ray.dot(forward));
+                    let clip = cascade.view_proj * corner.extend(1.0);
+                    let clip = clip.truncate() / clip.w;
+                    assert!(clip.x.abs() <= 1.0 && clip.y.abs() <= 1.0, "{clip:?}");
+                    assert!((0.0..=1.0).contains(&clip.z), "{clip:?}");
+                }
+            }
+            // Casters between the slice and the sun land in front of it
+            let toward_sun = cascade.view_proj * (sun * 1_000.0).extend(1.0);
+            let at_eye = cascade.view_proj * Vector4::new(0.0, 0.0, 0.0, 1.0);
+            assert!(toward_sun.z < at_eye.z);
+        }
+    }
+
+    #[test]
+    fn snapping_keeps_texels_fixed_as_the_eye_moves() {
+        let (inverse, eye, _) = camera();
+        let config = ShadowsConfig {
+            cascades: 4,
+            ..Default::default()
+        };
+        let sun = Vector3::new(1.0, 0.4, 0.3).normalize();
+        let before = fit_cascades(&inverse, &eye, sun, &config);
+
+        let step = Vector3::new(0.0, 0.37, 0.11);
+        let after = fit_cascades(&inverse, &(eye + step), sun, &config);
+
+        // A world fixed point should stay put in the map, to within a small
+        // fraction of a texel, or move by whole texels
+        let resolution = config.resolution as f64;
+        let world = Vector3::new(-40.0, 25.0, 120.0);
+        for (a, b) in before.iter().zip(&after) {
+            let ta = a.view_proj * world.extend(1.0);
+            let tb = b.view_proj * (world - step).extend(1.0);
+            for axis in 0..2 {
+                let texels = (ta[axis] - tb[axis]) * resolution / 2.0;
+                assert!((texels - texels.round()).abs() < 1e-3, "{texels}");
+            }
+        }
+    }
+
+    #[test]
+    fn uniform_matches_shader_layout() {
+        assert_eq!(std::mem::size_of::<ShadowUniform>(), 320);
+
+        let disabled = ShadowUniform::disabled();
+        assert_eq!(disabled.cascade_count, 0);
+
+        let (inverse, eye, forward) = camera();
+        let config = ShadowsConfig {
+            cascades: 3,
+            ..Default::default()
+        };
+        let cascades = fit_cascades(&inverse, &eye, Vector3::unit_x(), &config);
+        let uniform = ShadowUniform::new(&cascades, forward, config.resolution);
+        assert_eq!(uniform.cascade_count, 3);
+        assert_eq!(uniform.splits[2], config.distance as f32);
+        assert_eq!(uniform.splits[3], 0.0);
+        assert!(uniform.texel_meters[0] < uniform.texel_meters[2]);
+    }
+}
@@ -0,0 +1,215 @@
+#[cfg(test)]
+mod tests {
+    use crate::world::{sun_azimuth_elevation, sun_direction_ecef, SimulationClock};
+    use cgmath::InnerSpace;
+    use web_time::{Duration, SystemTime, UNIX_EPOCH};
+
+    /// Unix seconds for a UTC civil date and time.
+    fn utc(year: i64, month: i64, day: i64, hour: i64, minute: i64) -> f64 {
+        // Days from 1970-01-01 in the proleptic Gregorian calendar
+        let y = if month <= 2 { year - 1 } else { year };
+        let era = y.div_euclid(400);
+        let yoe = y - era * 400;
+        let mp = (month + 9) % 12;
+        let doy = (153 * mp + 2) / 5 + day - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        let days = era * 146_097 + doe - 719_468;
+        (days * 86_400 + hour * 3_600 + minute * 60) as f64
+    }
+
+    fn declination(unix_seconds: f64) -> f64 {
+        sun_direction_ecef(unix_seconds).z.asin().to_degrees()
+    }
+
+    #[test]
+    fn declination_at_equinoxes_and_solstices() {
+        // 2024 seasons, UTC, from the USNO
+        let seasons = [
+            (utc(2024, 3, 20, 3, 6), 0.0),
+            (utc(2024, 6, 20, 20, 51), 23.44),
+            (utc(2024, 9, 22, 12, 44), 0.0),
+            (utc(2024, 12, 21, 9, 21), -23.44),
+        ];
+        for (time, expected) in seasons {
+            let actual = declination(time);
+            assert!((actual - expected).abs() < 0.02, "{actual} vs {expected}");
+        }
+    }
+
+    #[test]
+    fn equation_of_time_extremes() {
+        // Minutes the sundial runs ahead of the clock. At 12:00 UTC the sun
+        // is overhead at longitude -eot / 4 degrees.
+        let extremes = [
+            (utc(2024, 2, 11, 12, 0), -14.2),
+            (utc(2024, 5, 14, 12, 0), 3.7),
+            (utc(2024, 7, 26, 12, 0), -6.5),
+            (utc(2024, 11, 3, 12, 0), 16.4),
+        ];
+        for (time, eot) in extremes {
+            let sun = sun_direction_ecef(time);
+            let longitude = sun.y.atan2(sun.x).to_degrees();
+            let minutes = -longitude * 4.0;
+            assert!((minutes - eot).abs() < 0.3, "{minutes} vs {eot}");
+        }
+    }
+
+    struct Reference {
+        time: f64,
+        lat: f64,
+        lon: f64,
+        /// None where it is undefined, at the poles.
+        azimuth: Option<f64>,
+        elevation: f64,
+    }
+
+    #[test]
+    fn azimuth_and_elevation_match_reference() {
+        // Expected values are NOAA's solar calculator, from the formulas of
+        // its spreadsheet (gml.noaa.gov/grad/solcalc/calcdetails.html), taking
+        // the elevation before the refraction correction as this model has
+        // none. Times are UTC.
+        let references = [
+            // Greenwich, December solstice, mid morning (NOAA)
+            Reference {
+                time: utc(2024, 12, 21, 9, 30),
+                lat: 51.48,
+                lon: 0.0,
+                azimuth: Some(146.02),
+                elevation: 8.32,
+            },
+            // Santa Barbara, June solstice, early morning (NOAA)
+            Reference {
+                time: utc(2024, 6, 20, 14, 0),
+                lat: 34.42,
+                lon: -119.70,
+                azimuth: Some(70.26),
+                elevation: 12.89,
+            },
+            // Santa Barbara, June solstice, evening (NOAA)
+            Reference {
+                time: utc(2024, 6, 21, 2, 0),
+                lat: 34.42,
+                lon: -119.70,
+                azimuth: Some(289.60),
+                elevation: 13.12,
+            },
+            // Sydney, December solstice, morning (NOAA)
+            Reference {
+                time: utc(2024, 12, 20, 22, 0),
+                lat: -33.87,
+                lon: 151.21,
+                azimuth: Some(94.51),
+                elevation: 38.46,
+            },
+            // Tokyo, March equinox, afternoon (NOAA)
+            Reference {
+                time: utc(2024, 3, 20, 6, 0),
+                lat: 35.68,
+                lon: 139.69,
+                azimuth: Some(242.21),
+                elevation: 33.07,
+            },
+            // Cape Town, July, noon with the sun to the north (NOAA)
+            Reference {
+                time: utc(2024, 7, 1, 10, 45),
+                lat: -33.92,
+                lon: 18.42,
+                azimuth: Some(1.45),
+                elevation: 33.01,
+            },
+            // Nairobi, October, just after sunrise (NOAA)
+            Reference {
+                time: utc(2024, 10, 15, 3, 30),
+                lat: -1.29,
+                lon: 36.82,
+                azimuth: Some(98.62),
+                elevation: 3.05,
+            },
+            // New York, January, just before sunset (NOAA)
+            Reference {
+                time: utc(2024, 1, 15, 21, 40),
+                lat: 40.71,
+                lon: -74.01,
+                azimuth: Some(240.37),
+                elevation: 1.31,
+            },
+            // Reykjavik, June solstice, midnight sun just under the horizon (NOAA)
+            Reference {
+                time: utc(2024, 6, 21, 0, 0),
+                lat: 64.15,
+                lon: -21.94,
+                azimuth: Some(339.54),
+                elevation: -0.68,
+            },
+            // North pole, June solstice (NOAA)
+            Reference {
+                time: utc(2024, 6, 20, 21, 0),
+                lat: 90.0,
+                lon: 0.0,
+                azimuth: None,
+                elevation: 23.44,
+            },
+            // South pole, June solstice (NOAA)
+            Reference {
+                time: utc(2024, 6, 20, 9, 0),
+                lat: -90.0,
+                lon: 0.0,
+                azimuth: None,
+                elevation: -23.44,
+            },
+        ];
+
+        for r in references {
+            let (azimuth, elevation) = sun_azimuth_elevation(r.time, r.lat, r.lon);
+            assert!(
+                (elevation - r.elevation).abs() < 0.05,
+                "({}, {}) elevation {elevation} vs {}",
+                r.lat,
+                r.lon,
+                r.elevation
+            );
+            if let Some(expected) = r.azimuth {
+                let error = (azimuth - expected + 180.0).rem_euclid(360.0) - 180.0;
+                assert!(
+                    error.abs() < 0.05,
+                    "({}, {}) azimuth {azimuth} vs {expected}",
+                    r.lat,
+                    r.lon
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn sun_is_a_unit_vector() {
+        for day in 0..365 {
+            let time = utc(2024, 1, 1, 0, 0) + day as f64 * 86_400.0 + day as f64 * 137.0;
+            let length = sun_direction_ecef(time).magnitude();
+            assert!((length - 1.0).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn simulation_clock_runs_from_set_time() {
+        let mut clock = SimulationClock::default();
+        let set = UNIX_EPOCH + Duration::from_secs(1_718_917_200); // 2024-06-20 21:00 UTC
+        clock.set(set);
+
+        assert!((clock.unix_seconds() - utc(2024, 6, 20, 21, 0)).abs() < 1.0);
+        let elapsed = match clock.now().duration_since(set) {
+            Ok(after) => after,
+            Err(before) => before.duration(),
+        };
+        assert!(elapsed < Duration::from_secs(1));
+
+        // Back on the wall clock
+        let wall = SystemTime::now();
+        clock.set(wall);
+        let drift = match clock.now().duration_since(wall) {
+            Ok(after) => after,
+            Err(before) => before.duration(),
+        };
+        assert!(drift < Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,342 @@
+#[cfg(test)]
+mod tests {
+    use crate::{
+        content::{
+            build_terrain_mesh, decode_high_water_mark, decode_terrain_tile, decode_zigzag_deltas,
+            oct_decode, ContentOptions, QuantizedMesh, SupportedTextureEncodings, TerrainLayer,
+            TerrainNode, TerrainTileId, TileState, WaterMask, TERRAIN_LAND_COLOR,
+            TERRAIN_WATER_COLOR, WATER_MASK_SIZE,
+        },
+        helpers::geodetic_to_ecef_z_up,
+        MipmapMode, NormalsConfig,
+    };
+    use cgmath::{EuclideanSpace, InnerSpace, MetricSpace, Point3, Vector3};
+    use std::{path::PathBuf, sync::Arc};
+
+    /// TILE, MIN_HEIGHT and MAX_HEIGHT in make_fixtures.py.
+    const TILE: TerrainTileId = TerrainTileId {
+        z: 9,
+        x: 171,
+        y: 353,
+    };
+    const MIN_HEIGHT: f64 = 100.0;
+    const MAX_HEIGHT: f64 = 500.0;
+
+    fn fixture(name: &str) -> Vec<u8> {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("src/tests/data/terrain")
+            .join(name);
+        std::fs::read(path).unwrap()
+    }
+
+    fn parse(name: &str) -> QuantizedMesh {
+        QuantizedMesh::parse(&fixture(name)).unwrap()
+    }
+
+    fn options() -> ContentOptions {
+        ContentOptions {
+            decoded_cache: false,
+            texture_encodings: SupportedTextureEncodings {
+                bc7: false,
+                etc2: false,
+                astc: false,
+            },
+            mipmaps: MipmapMode::Off,
+            normals: NormalsConfig::default(),
+        }
+    }
+
+    /// Where the fixtures put a vertex with quantized `u` and `v`.
+    fn expected_position(u: u16, v: u16) -> Point3<f64> {
+        let [west, south, east, north] = TILE.rectangle();
+        let (u, v) = (u as f64 / 32767.0, v as f64 / 32767.0);
+        let height = (u * v * 32767.0).round() / 32767.0;
+        geodetic_to_ecef_z_up(
+            south + (north - south) * v,
+            west + (east - west) * u,
+            MIN_HEIGHT + (MAX_HEIGHT - MIN_HEIGHT) * height,
+        )
+    }
+
+    #[test]
+    fn undoes_the_integer_codings() {
+        let mut values = [0, 10, 1, 3, 0];
+        decode_zigzag_deltas(&mut values);
+        assert_eq!(values, [0, 5, 4, 2, 2]);
+
+        let mut indices = [0, 0, 0, 2, 0, 1];
+        decode_high_water_mark(&mut indices);
+        assert_eq!(indices, [0, 1, 2, 1, 3, 3]);
+
+        let up = oct_decode(128, 128);
+        assert!(up[2] > 0.999);
+        let down = oct_decode(255, 255);
+        assert!(down[2] < -0.999);
+    }
+
+    #[test]
+    fn decodes_a_plain_tile() {
+        let mesh = parse("plain.terrain");
+        assert_eq!(mesh.u.len(), 25);
+        assert_eq!(mesh.indices.len(), 32 * 3);
+        for edge in [
+            &mesh.west_indices,
+            &mesh.south_indices,
+            &mesh.east_indices,
+            &mesh.north_indices,
+        ] {
+            assert_eq!(edge.len(), 5);
+        }
+        assert!(mesh.west_indices.iter().all(|&i| mesh.u[i as usize] == 0));
+        assert!(mesh
+            .north_indices
+            .iter()
+            .all(|&i| mesh.v[i as usize] == 32767));
+        assert_eq!((mesh.min_height, mesh.max_height), (100.0, 500.0));
+        assert_eq!(mesh.normals, None);
+        assert_eq!(mesh.water_mask, None);
+
+        let built = build_terrain_mesh(TILE, &mesh);
+        let vertices = built.as_vertex_slice();
+        // the edge loop, closed, hangs a skirt
+        assert_eq!(vertices.len(), 25 + 17);
+        for (i, vertex) in vertices[..25].iter().enumerate() {
+            let [x, y, z] = vertex.position;
+            let p = mesh.center + Vector3::new(x as f64, y as f64, z as f64);
+            assert!(p.distance(expected_position(mesh.u[i], mesh.v[i])) < 0.05);
+            assert_eq!(
+                vertex.texcoord0,
+                [mesh.u[i] as f32 / 32767.0, mesh.v[i] as f32 / 32767.0]
+            );
+        }
+        for skirt in &vertices[25..] {
+            let [x, y, z] = skirt.position;
+            let p = mesh.center + Vector3::new(x as f64, y as f64, z as f64);
+            let ground = (p.to_vec().magnitude() - mesh.center.to_vec().magnitude()).abs();
+            assert!(ground > TILE.skirt_height() - MAX_HEIGHT);
+        }
+    }
+
+    #[test]
+    fn decodes_normals_and_the_water_mask() {
+        let mesh = parse("extensions.terrain");
+        assert_eq!(mesh.u.len(), 81);
+
+        let normals = mesh.normals.as_ref().unwrap();
+        assert_eq!(normals.len(), 81);
+        for (i, n) in normals.iter().enumerate() {
+            let expected = (expected_position(mesh.u[i], mesh.v[i]).to_vec()).normalize();
+            let n = Vector3::new(n[0] as f64, n[1] as f64, n[2] as f64);
+            assert!((n.magnitude() - 1.0).abs() < 1e-5);
+            // the geocentric direction is within a fraction of a degree
+            // of the geodetic normal the fixtures encode
+            assert!(n.dot(expected) > 0.999, "vertex {i}");
+        }
+
+        let Some(WaterMask::Grid(mask)) = &mesh.water_mask else {
+            panic!("expected a water mask grid");
+        };
+        assert_eq!(mask.len(), (WATER_MASK_SIZE * WATER_MASK_SIZE) as usize);
+        assert_eq!((mask[0], mask[127], mask[128], mask[255]), (255, 255, 0, 0));
+
+        let TileState::Decoded {
+            meshes,
+            textures,
+            materials,
+            ..
+        } = decode_terrain_tile(TILE, &fixture("extensions.terrain"), options()).unwrap()
+        else {
+            panic!("expected a decoded tile");
+        };
+        assert_eq!(meshes.len(), 1);
+        assert_eq!(meshes[0].material_index, Some(0));
+        assert_eq!(materials[0].base_color_texture.unwrap().index, 0);
+        let texture = &textures[0];
+        assert_eq!((texture.width, texture.height), (256, 256));
+        assert_eq!(texture.levels[0][..4], TERRAIN_WATER_COLOR);
+        assert_eq!(texture.levels[0][255 * 4..256 * 4], TERRAIN_LAND_COLOR);
+    }
+
+    #[test]
+    fn gunzips_tiles() {
+        let plain = parse("plain.terrain");
+        let mesh = parse("gzipped.terrain");
+        assert_eq!(
+            (&mesh.u, &mesh.v, &mesh.height),
+            (&plain.u, &plain.v, &plain.height)
+        );
+        assert_eq!(mesh.indices, plain.indices);
+        assert_eq!(mesh.water_mask, Some(WaterMask::Uniform(255)));
+
+        let TileState::Decoded { textures, .. } =
+            decode_terrain_tile(TILE, &fixture("gzipped.terrain"), options()).unwrap()
+        else {
+            panic!("expected a decoded tile");
+        };
+        assert_eq!((textures[0].width, textures[0].height), (1, 1));
+        assert_eq!(textures[0].levels[0], TERRAIN_WATER_COLOR);
+    }
+
+    #[test]
+    fn reads_32_bit_indices() {
+        let mesh = parse("wide.terrain");
+        assert_eq!(mesh.u.len(), 257 * 257);
+        assert_eq!(mesh.indices.len(), 256 * 256 * 6);
+        assert_eq!(mesh.east_indices.len(), 257);
+        let max = mesh.u.len() as u32;
+        assert!(mesh.indices.iter().all(|&i| i < max));
+        // the last vertex the grid introduces is the north east corner
+        let last = *mesh.indices.iter().max().unwrap() as usize;
+        assert_eq!((mesh.u[last], mesh.v[last]), (32767, 32767));
+        assert_eq!(mesh.height[last], 32767);
+    }
+
+    #[test]
+    fn rejects_damaged_tiles() {
+        let bytes = fixture("plain.terrain");
+        assert!(QuantizedMesh::parse(&bytes[..60]).is_err());
+        assert!(QuantizedMesh::parse(&bytes[..200]).is_err());
+
+        // a triangle pointing past the vertices
+        let mut bytes = bytes;
+        let first_index = 88 + 4 + 25 * 6 + 4;
+        bytes[first_index..first_index + 2].copy_from_slice(&u16::MAX.to_le_bytes());
+        assert!(QuantizedMesh::parse(&bytes).is_err());
+    }
+
+    #[test]
+    fn winds_faces_and_skirts_outward() {
+        let mesh = parse("extensions.terrain");
+        let built = build_terrain_mesh(TILE, &mesh);
+        let position = |i: u32| {
+            let [x, y, z] = built.as_vertex_slice()[i as usize].position;
+            mesh.center + Vector3::new(x as f64, y as f64, z as f64)
+        };
+        let middle = mesh.center.to_vec();
+
+        let mut skirts = 0;
+        for (t, triangle) in built.as_index_slice().chunks(3).enumerate() {
+            let p = [
+                position(triangle[0]),
+                position(triangle[1]),
+                position(triangle[2]),
+            ];
+            let n = (p[1] - p[0]).cross(p[2] - p[0]).normalize();
+            let centroid = (p[0].to_vec() + p[1].to_vec() + p[2].to_vec()) / 3.0;
+            let up = centroid.normalize();
+            let facing = n.dot(up);
+            if facing.abs() > 0.5 {
+                assert!(facing > 0.9, "face {t} points inward");
+            } else {
+                let away = centroid - middle;
+                let away = away - up * away.dot(up);
+                assert!(n.dot(away) > 0.0, "skirt {t} points inward");
+                skirts += 1;
+            }
+        }
+        // two triangles under each of the 8 segments along the 4 edges
+        assert_eq!(skirts, 8 * 4 * 2);
+    }
+
+    #[test]
+    fn lays_out_the_geographic_grid() {
+        let [west, east] = TerrainTileId::level_zero();
+        assert_eq!(west.rectangle(), [-180.0, -90.0, 0.0, 90.0]);
+        assert_eq!(east.rectangle(), [0.0, -90.0, 180.0, 90.0]);
+
+        let tile = TerrainTileId::new(2, 5, 1);
+        assert_eq!(tile.rectangle(), [45.0, -45.0, 90.0, 0.0]);
+        let [sw, se, nw, ne] = tile.children();
+        assert_eq!(sw, TerrainTileId::new(3, 10, 2));
+        assert_eq!(ne, TerrainTileId::new(3, 11, 3));
+        assert_eq!(se.rectangle()[0], sw.rectangle()[2]);
+        assert_eq!(nw.rectangle()[1], sw.rectangle()[3]);
+        assert_eq!(sw.geometric_error() * 2.0, tile.geometric_error());
+
+        // the box holds the tile's corners between the default heights
+        let volume = tile.bounding_volume(-500.0, 9000.0).to_aabb();
+        let [w, s, e, n] = tile.rectangle();
+        for (lat, lon) in [
+            (s, w),
+            (s, e),
+            (n, w),
+            (n, e),
+            ((s + n) / 2.0, (w + e) / 2.0),
+        ] {
+            let p = geodetic_to_ecef_z_up(lat, lon, 9000.0);
+            for axis in 0..3 {
+                assert!(volume.min[axis] <= p[axis] && p[axis] <= volume.max[axis]);
+            }
+        }
+    }
+
+    const LAYER: &str = r#"{
+        "tilejson": "2.1.0",
+        "format": "quantized-mesh-1.0",
+        "version": "1.2.0",
+        "scheme": "tms",
+        "tiles": ["{z}/{x}/{y}.terrain?v={version}"],
+        "projection": "EPSG:4326",
+        "extensions": ["watermask", "metadata", "octvertexnormals"],
+        "available": [
+            [{"startX": 0, "startY": 0, "endX": 1, "endY": 0}],
+            [{"startX": 0, "startY": 0, "endX": 3, "endY": 1}],
+            [{"startX": 0, "startY": 0, "endX": 7, "endY": 2}]
+        ]
+    }"#;
+
+    #[test]
+    fn reads_layer_json() {
+        let layer = TerrainLayer::parse(LAYER.as_bytes()).unwrap();
+        assert_eq!(
+            layer.tile_uri(TerrainTileId::new(2, 3, 1)),
+            "2/3/1.terrain?v=1.2.0&extensions=watermask-octvertexnormals"
+        );
+        assert!(layer.is_available(TerrainTileId::new(2, 7, 2)));
+        assert!(!layer.is_available(TerrainTileId::new(2, 7, 3)));
+        assert!(!layer.is_available(TerrainTileId::new(3, 0, 0)));
+
+        let slippy = LAYER
+            .replace("\"tms\"", "\"slippyMap\"")
+            .replace(", \"metadata\", \"octvertexnormals\"", "");
+        let slippy = TerrainLayer::parse(slippy.as_bytes()).unwrap();
+        // rows count from the north, so the southern row is the last
+        assert_eq!(
+            slippy.tile_uri(TerrainTileId::new(2, 3, 0)),
+            "2/3/3.terrain?v=1.2.0&extensions=watermask"
+        );
+        assert!(slippy.is_available(TerrainTileId::new(2, 0, 3)));
+        assert!(!slippy.is_available(TerrainTileId::new(2, 0, 0)));
+
+        let heightmap = LAYER.replace("quantized-mesh-1.0", "heightmap-1.0");
+        assert!(TerrainLayer::parse(heightmap.as_bytes()).is_err());
+        let mercator = LAYER.replace("EPSG:4326", "EPSG:3857");
+        assert!(TerrainLayer::parse(mercator.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn refines_only_into_available_tiles() {
+        let layer = Arc::new(TerrainLayer::parse(LAYER.as_bytes()).unwrap());
+        let root = layer.as_ref().clone().root();
+        assert!(root.content.is_none());
+        let level_zero = root.children.unwrap();
+        assert_eq!(level_zero.len(), 2);
+        assert_eq!(
+            level_zero[1].content.as_ref().unwrap().uri,
+            layer.tile_uri(TerrainTileId::new(0, 1, 0))
+        );
+
+        let node = |z, x, y| TerrainNode {
+            id: TerrainTileId::new(z, x, y),
+            layer: layer.clone(),
+        };
+        let children = node(1, 3, 0).children();
+        assert_eq!(children.len(), 4);
+        assert!(children
+            .iter()
+            .all(|c| c.terrain.as_ref().unwrap().id.z == 2 && c.geometric_error > 0.0));
+        // level 2 only has its southern three rows
+        assert!(node(1, 0, 1).children().is_empty());
+        assert!(node(2, 0, 0).children().is_empty());
+    }
+}
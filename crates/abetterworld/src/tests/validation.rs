@@ -0,0 +1,70 @@
+#[cfg(test)]
+mod tests {
+    use crate::content::{validate_download, verify_digest};
+
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use sha2::{Digest, Sha256};
+
+    fn glb(total_len: u32, body: usize) -> Vec<u8> {
+        let mut out = b"glTF".to_vec();
+        out.extend_from_slice(&2u32.to_le_bytes());
+        out.extend_from_slice(&total_len.to_le_bytes());
+        out.resize(out.len() + body, 0);
+        out
+    }
+
+    #[test]
+    fn test_validate_download_length_and_glb() {
+        let url = "https://example.com/tiles/a.glb?key=abc";
+        let good = glb(20, 8);
+
+        assert!(validate_download(url, "model/gltf-binary", Some(20), &[], &good).is_ok());
+
+        // Truncated transfer
+        assert!(validate_download(url, "model/gltf-binary", Some(40), &[], &good).is_err());
+
+        // Header claims more than was delivered
+        let short = glb(64, 8);
+        assert!(validate_download(url, "model/gltf-binary", None, &[], &short).is_err());
+
+        // Not a GLB at all, even though the server says it is
+        let html = b"<html>quota exceeded</html>".to_vec();
+        assert!(validate_download(url, "model/gltf-binary", None, &[], &html).is_err());
+
+        // Content type is wrong but the extension still marks it as a GLB
+        assert!(validate_download(url, "application/octet-stream", None, &[], &html).is_err());
+    }
+
+    #[test]
+    fn test_validate_download_json() {
+        let url = "https://example.com/root.json";
+        let good = br#"{"asset":{"version":"1.0"},"root":{}}"#;
+        let cut = br#"{"asset":{"version":"1.0"},"ro"#;
+
+        assert!(validate_download(url, "application/json", None, &[], good).is_ok());
+        assert!(validate_download(url, "application/json", None, &[], cut).is_err());
+        assert!(validate_download(url, "text/plain", None, &[], cut).is_err());
+    }
+
+    #[test]
+    fn test_verify_digest_headers() {
+        let body = b"payload";
+        let sha = STANDARD.encode(Sha256::digest(body));
+
+        // RFC 9530 and RFC 3230 syntax
+        assert!(verify_digest(&format!("sha-256=:{sha}:"), body).unwrap());
+        assert!(verify_digest(&format!("SHA-256={sha}"), body).unwrap());
+        assert!(verify_digest(&format!("md5=abc, sha-256=:{sha}:"), body).unwrap());
+
+        // Unsupported algorithms verify nothing but aren't errors
+        assert!(!verify_digest("md5=:rL0Y20zC+Fzt72VPzMSk2A==:", body).unwrap());
+
+        assert!(verify_digest(&format!("sha-256=:{sha}:"), b"tampered").is_err());
+        assert!(verify_digest("sha-256=:not base64!:", body).is_err());
+
+        let header = format!("sha-256=:{sha}:");
+        let url = "https://example.com/blob";
+        assert!(validate_download(url, "", None, &[header.as_str()], body).is_ok());
+        assert!(validate_download(url, "", None, &[header.as_str()], b"tampered").is_err());
+    }
+}
@@ -0,0 +1,292 @@
+#[cfg(test)]
+mod tests {
+    use crate::{
+        helpers::{signed_area, triangulate},
+        render::{VectorLayer, VectorMesh, VectorPass, BOX_CORNERS, DRAPE_MAX_HEIGHT},
+        world::{parse_geojson, Location, Polygon, Polyline, VectorShape, VectorStyle},
+    };
+    use cgmath::{EuclideanSpace, InnerSpace, Vector3};
+
+    fn triangles_area(points: &[[f64; 2]], triangles: &[u32]) -> f64 {
+        triangles
+            .chunks(3)
+            .map(|t| {
+                let area = signed_area(&t.iter().map(|&i| points[i as usize]).collect::<Vec<_>>());
+                assert!(area > 0.0, "triangle {t:?} isn't counter-clockwise");
+                area / 2.0
+            })
+            .sum()
+    }
+
+    fn square(x: f64, y: f64, size: f64) -> Vec<[f64; 2]> {
+        vec![[x, y], [x + size, y], [x + size, y + size], [x, y + size]]
+    }
+
+    #[test]
+    fn triangulates_polygons_with_holes() {
+        // clockwise and closed outside, counter-clockwise holes
+        let mut outer = square(0.0, 0.0, 10.0);
+        outer.reverse();
+        outer.push(outer[0]);
+        let rings = vec![outer, square(2.0, 2.0, 2.0), square(6.0, 5.0, 3.0)];
+        let triangles = triangulate(&rings);
+        let points: Vec<_> = rings.concat();
+
+        // n + 2h − 2 triangles for n corners and h holes
+        assert_eq!(triangles.len() / 3, 12 + 2 * 2 - 2);
+        assert!((triangles_area(&points, &triangles) - (100.0 - 4.0 - 9.0)).abs() < 1e-9);
+        // the repeated closing point is never used
+        assert!(!triangles.contains(&4));
+    }
+
+    #[test]
+    fn triangulates_concave_rings() {
+        // a comb with three teeth
+        let comb = vec![
+            [0.0, 0.0],
+            [5.0, 0.0],
+            [5.0, 3.0],
+            [4.0, 3.0],
+            [4.0, 1.0],
+            [3.0, 1.0],
+            [3.0, 3.0],
+            [2.0, 3.0],
+            [2.0, 1.0],
+            [1.0, 1.0],
+            [1.0, 3.0],
+            [0.0, 3.0],
+        ];
+        // fewer than n − 2 when cuts leave collinear corners behind
+        let triangles = triangulate(std::slice::from_ref(&comb));
+        assert!(triangles.len() / 3 <= comb.len() - 2);
+        assert!((triangles_area(&comb, &triangles) - 11.0).abs() < 1e-9);
+
+        assert!(triangulate(&[vec![[0.0, 0.0], [1.0, 1.0]]]).is_empty());
+        assert!(triangulate(&[vec![[0.0, 0.0], [1.0, 1.0], [2.0, 2.0]]]).is_empty());
+    }
+
+    fn signed_volume(triangles: impl Iterator<Item = [Vector3<f64>; 3]>) -> f64 {
+        triangles.map(|[a, b, c]| a.dot(b.cross(c)) / 6.0).sum()
+    }
+
+    #[test]
+    fn winds_drape_boxes_outward() {
+        let corner =
+            |i: usize| Vector3::new((i & 1) as f64, ((i >> 1) & 1) as f64, (i >> 2) as f64);
+        let volume = signed_volume(
+            BOX_CORNERS
+                .chunks(3)
+                .map(|t| [corner(t[0]), corner(t[1]), corner(t[2])]),
+        );
+        assert!((volume - 1.0).abs() < 1e-12);
+    }
+
+    fn ring(lat: f64, lon: f64, size: f64) -> Vec<Location> {
+        [(0.0, 0.0), (0.0, 1.0), (1.0, 1.0), (1.0, 0.0)]
+            .iter()
+            .map(|(y, x)| Location::Geodetic(lat + y * size, lon + x * size, 0.0))
+            .collect()
+    }
+
+    fn positions(mesh: &VectorMesh, pass: VectorPass) -> Vec<Vector3<f64>> {
+        let range = mesh.pass(pass);
+        mesh.vertices[range.start as usize..range.end as usize]
+            .iter()
+            .map(|v| {
+                Vector3::new(v.position[0], v.position[1], v.position[2])
+                    .cast()
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn drapes_clamped_polygons_in_closed_prisms() {
+        let polygon = Polygon {
+            exterior: ring(34.40, -119.70, 0.02),
+            holes: vec![ring(34.405, -119.695, 0.005)],
+            color: [0, 128, 255, 128],
+            clamp_to_ground: true,
+        };
+        let mesh = VectorMesh::new(&polygon.into());
+        assert!(mesh.pass(VectorPass::Fills).is_empty());
+        assert!(mesh.pass(VectorPass::Lines).is_empty());
+
+        // the prism's volume comes out positive only if every face winds
+        // outward, whatever the walls' order
+        let prism = positions(&mesh, VectorPass::DrapedFills);
+        let volume = signed_volume(prism.chunks(3).map(|t| [t[0], t[1], t[2]]));
+        let ground = positions(&mesh, VectorPass::GroundFills);
+        let area: f64 = ground
+            .chunks(3)
+            .map(|t| (t[1] - t[0]).cross(t[2] - t[0]).magnitude() / 2.0)
+            .sum();
+        let height = DRAPE_MAX_HEIGHT + 500.0;
+        assert!(volume > 0.0);
+        assert!(
+            (volume / (area * height) - 1.0).abs() < 0.01,
+            "{volume} vs {area} × {height}"
+        );
+
+        // n + 2h − 2 ground triangles, twice over for the caps, and 2 per wall
+        assert_eq!(ground.len(), 3 * (4 + 4 + 2 - 2));
+        assert_eq!(prism.len(), ground.len() * 2 + 8 * 6);
+        assert!(mesh.vertices.iter().all(|v| v.color == [0, 128, 255, 128]));
+    }
+
+    #[test]
+    fn splits_long_segments() {
+        let line = |clamp_to_ground| Polyline {
+            positions: vec![
+                Location::Geodetic(0.0, 0.0, 1000.0),
+                Location::Geodetic(0.0, 2.9, 1000.0),
+                Location::Geodetic(0.0, 3.0, 1000.0),
+            ],
+            width: 3.0,
+            color: [255; 4],
+            clamp_to_ground,
+        };
+
+        let mesh = VectorMesh::new(&line(false).into());
+        let lines = mesh.pass(VectorPass::Lines);
+        assert_eq!(lines.len(), 7 * 6);
+        assert!(mesh.pass(VectorPass::GroundLines).is_empty());
+        for v in &mesh.vertices {
+            let p = mesh.center
+                + Vector3::new(v.position[0], v.position[1], v.position[2])
+                    .cast()
+                    .unwrap();
+            let (lat, _, height) = Location::Geocentric(p.x, p.y, p.z).to_geodetic();
+            assert!(
+                lat.abs() < 1e-6 && (height - 1000.0).abs() < 0.5,
+                "{lat} {height}"
+            );
+            assert_eq!(v.extrude[3], 3.0);
+        }
+
+        let mesh = VectorMesh::new(&line(true).into());
+        assert!(mesh.pass(VectorPass::Lines).is_empty());
+        assert_eq!(mesh.pass(VectorPass::GroundLines).len(), 7 * 6);
+        assert_eq!(mesh.pass(VectorPass::DrapedLines).len(), 7 * 36);
+        for v in positions(&mesh, VectorPass::GroundLines) {
+            let p = mesh.center + v;
+            assert!(Location::Geocentric(p.x, p.y, p.z).to_geodetic().2.abs() < 0.5);
+        }
+    }
+
+    #[test]
+    fn places_shapes_relative_to_the_eye() {
+        let mut layer = VectorLayer::new();
+        let shape: VectorShape = Polyline {
+            positions: vec![
+                Location::Geodetic(34.42, -119.70, 10.0),
+                Location::Geodetic(34.43, -119.69, 10.0),
+            ],
+            width: 2.0,
+            color: [255; 4],
+            clamp_to_ground: false,
+        }
+        .into();
+        let first = layer.add(&shape);
+        let second = layer.add(&shape);
+        assert_ne!(first, second);
+
+        let mesh = VectorMesh::new(&shape);
+        let eye = mesh.center + Vector3::new(12.25, -3.5, 100.0);
+        let instances = layer.instances(&eye);
+        assert_eq!(instances.len(), 2);
+        assert_eq!(
+            [instances[0].r0[3], instances[0].r1[3], instances[0].r2[3]],
+            [-12.25, 3.5, -100.0]
+        );
+        assert_eq!(
+            [instances[0].r0[0], instances[0].r1[1], instances[0].r2[2]],
+            [1.0; 3]
+        );
+
+        assert!(layer.replace(first, &shape));
+        assert!(layer.remove(first));
+        assert!(!layer.remove(first));
+        assert!(!layer.replace(first, &shape));
+        assert_eq!(layer.len(), 1);
+        assert!(mesh.center.to_vec().magnitude() > 6.3e6);
+    }
+
+    #[test]
+    fn reads_geojson_with_simplestyle() {
+        let document = br##"{
+            "type": "FeatureCollection",
+            "features": [
+                {
+                    "type": "Feature",
+                    "properties": {"stroke": "#ff0000", "stroke-width": 4, "fill": "#0f0", "fill-opacity": 0.5},
+                    "geometry": {
+                        "type": "Polygon",
+                        "coordinates": [
+                            [[-119.70, 34.40], [-119.68, 34.40], [-119.68, 34.42], [-119.70, 34.42], [-119.70, 34.40]],
+                            [[-119.695, 34.405], [-119.69, 34.405], [-119.69, 34.41], [-119.695, 34.405]]
+                        ]
+                    }
+                },
+                {
+                    "type": "Feature",
+                    "properties": null,
+                    "geometry": {
+                        "type": "GeometryCollection",
+                        "geometries": [
+                            {"type": "MultiPoint", "coordinates": [[0, 0]]},
+                            {"type": "MultiLineString", "coordinates": [[[0, 0, 50], [1, 1, 50]], [[2, 2], [3, 3]]]}
+                        ]
+                    }
+                },
+                {"type": "Feature", "properties": {}, "geometry": null}
+            ]
+        }"##;
+        let style = VectorStyle {
+            clamp_to_ground: false,
+            ..Default::default()
+        };
+        let shapes = parse_geojson(document, &style).unwrap();
+        assert_eq!(shapes.len(), 5);
+
+        let VectorShape::Polygon(polygon) = &shapes[0] else {
+            panic!("{:?}", shapes[0]);
+        };
+        assert_eq!(polygon.color, [0, 255, 0, 128]);
+        assert_eq!(polygon.exterior.len(), 5);
+        assert_eq!(polygon.holes.len(), 1);
+        assert_eq!(polygon.exterior[1], Location::Geodetic(34.40, -119.68, 0.0));
+        for outline in &shapes[1..3] {
+            let VectorShape::Polyline(line) = outline else {
+                panic!("{outline:?}");
+            };
+            assert_eq!(line.color, [255, 0, 0, 255]);
+            assert_eq!(line.width, 4.0);
+        }
+
+        let VectorShape::Polyline(line) = &shapes[3] else {
+            panic!("{:?}", shapes[3]);
+        };
+        assert_eq!(line.color, style.stroke);
+        assert_eq!(line.positions[1], Location::Geodetic(1.0, 1.0, 50.0));
+        assert!(!line.clamp_to_ground);
+
+        // no outlines without a stroke
+        let unstroked = VectorStyle {
+            stroke_width: 0.0,
+            ..style
+        };
+        let polygon = br#"{"type": "Polygon", "coordinates": [[[0, 0], [1, 0], [1, 1]]]}"#;
+        assert_eq!(parse_geojson(polygon, &unstroked).unwrap().len(), 1);
+        assert_eq!(parse_geojson(polygon, &style).unwrap().len(), 2);
+
+        for bad in [
+            &b"{\"type\": \"Polygon\"}"[..],
+            b"{\"type\": \"Circle\", \"coordinates\": []}",
+            b"{\"type\": \"LineString\", \"coordinates\": [[0]]}",
+            b"not json",
+        ] {
+            assert!(parse_geojson(bad, &style).is_err());
+        }
+    }
+}
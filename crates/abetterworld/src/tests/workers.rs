@@ -0,0 +1,341 @@
+#[cfg(test)]
+mod tests {
+    use crate::{
+        cache::{MemoryCacheBackend, TilesetCache},
+        content::{
+            pager::{build_client, PagerStatus},
+            tiles::{
+                decode_content_worker, download_content_stage, ContentOptions, DecodeJob,
+                DownloadContext,
+            },
+            types::TileState,
+            SupportedTextureEncodings, TileContent, TileMessage, TilePipelineMessage,
+        },
+        helpers::{channel::channel, PlatformAwait},
+        MipmapMode, NormalsConfig,
+    };
+    use cgmath::{Point3, Transform};
+    use std::sync::Arc;
+
+    fn options() -> ContentOptions {
+        ContentOptions {
+            decoded_cache: false,
+            texture_encodings: SupportedTextureEncodings {
+                bc7: false,
+                etc2: false,
+                astc: false,
+            },
+            mipmaps: MipmapMode::Off,
+            normals: NormalsConfig::default(),
+        }
+    }
+
+    fn cache() -> TilesetCache {
+        TilesetCache::new(Arc::new(MemoryCacheBackend::new(1 << 20)))
+    }
+
+    fn header(key: u64) -> TileMessage {
+        TileMessage {
+            key,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn download_stage_passes_unloads_to_renderer() {
+        let (tx, mut rx) = channel::<TilePipelineMessage>(4);
+        let (decode_tx, decode_rx) = channel::<DecodeJob>(4);
+        let (render_tx, render_rx) = channel::<TilePipelineMessage>(4);
+
+        tx.send(TilePipelineMessage::Unload(header(7)))
+            .platform_await()
+            .unwrap();
+        drop(tx);
+
+        let context = DownloadContext {
+            client: build_client(1).unwrap(),
+            cache: Arc::new(cache()),
+            decode_tx,
+            render_time: render_tx,
+            options: options(),
+            status: Arc::new(PagerStatus::default()),
+        };
+        download_content_stage(context, &mut rx, 2)
+            .platform_await()
+            .unwrap();
+
+        assert_eq!(decode_rx.len(), 0);
+        assert!(matches!(
+            render_rx.try_recv(),
+            Ok(TilePipelineMessage::Unload(TileMessage { key: 7, .. }))
+        ));
+    }
+
+    /// An empty GLB, as little as passes download validation.
+    const GLB_HEADER: &[u8] = b"glTF\x02\0\0\0\x0c\0\0\0";
+
+    /// Serves `requests` copies of `body` over HTTP, holding each response
+    /// back a little, and returns the most it saw in flight at once.
+    fn slow_tile_server(
+        requests: usize,
+        content_type: &'static str,
+        body: Vec<u8>,
+    ) -> (String, std::thread::JoinHandle<usize>) {
+        use std::io::{Read, Write};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let active = Arc::new(AtomicUsize::new(0));
+            let most = Arc::new(AtomicUsize::new(0));
+            let connections: Vec<_> = listener
+                .incoming()
+                .take(requests)
+                .map(|stream| {
+                    let mut stream = stream.unwrap();
+                    let (active, most) = (active.clone(), most.clone());
+                    let body = body.clone();
+                    std::thread::spawn(move || {
+                        let mut request = [0u8; 1024];
+                        let _ = stream.read(&mut request).unwrap();
+                        most.fetch_max(active.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+                        std::thread::sleep(std::time::Duration::from_millis(100));
+                        active.fetch_sub(1, Ordering::SeqCst);
+                        let head = format!(
+                            "HTTP/1.1 200 OK\r\ncontent-type: {content_type}\r\n\
+                             content-length: {}\r\nconnection: close\r\n\r\n",
+                            body.len()
+                        );
+                        stream.write_all(head.as_bytes()).unwrap();
+                        stream.write_all(&body).unwrap();
+                    })
+                })
+                .collect();
+            for connection in connections {
+                connection.join().unwrap();
+            }
+            most.load(Ordering::SeqCst)
+        });
+        (url, server)
+    }
+
+    #[test]
+    fn download_stage_bounds_concurrent_downloads() {
+        let (url, server) = slow_tile_server(6, "model/gltf-binary", GLB_HEADER.to_vec());
+        let (tx, mut rx) = channel::<TilePipelineMessage>(8);
+        let (decode_tx, decode_rx) = channel::<DecodeJob>(8);
+        let (render_tx, _render_rx) = channel::<TilePipelineMessage>(8);
+        for key in 0..6 {
+            let tile = TileContent {
+                uri: format!("{url}/tile{key}.glb"),
+                state: TileState::ToLoad,
+            };
+            tx.send(TilePipelineMessage::Load((header(key), tile)))
+                .platform_await()
+                .unwrap();
+        }
+        drop(tx);
+
+        let context = DownloadContext {
+            client: build_client(1).unwrap(),
+            cache: Arc::new(cache()),
+            decode_tx,
+            render_time: render_tx,
+            options: options(),
+            status: Arc::new(PagerStatus::default()),
+        };
+        download_content_stage(context, &mut rx, 2)
+            .platform_await()
+            .unwrap();
+
+        // the queue closes once the last download has handed off
+        let mut keys = Vec::new();
+        while let Ok(job) = decode_rx.recv().platform_await() {
+            assert_eq!(job.bytes, GLB_HEADER);
+            keys.push(job.header.key);
+        }
+        keys.sort();
+        assert_eq!(keys, [0, 1, 2, 3, 4, 5]);
+        assert_eq!(server.join().unwrap(), 2);
+    }
+
+    #[test]
+    fn decode_worker_skips_bad_tiles_and_drains_queue() {
+        let cache = cache();
+        let (decode_tx, mut decode_rx) = channel::<DecodeJob>(4);
+        let (mut render_tx, render_rx) = channel::<TilePipelineMessage>(4);
+        let status = PagerStatus::default();
+        status.requested(3);
+
+        for key in 0..3 {
+            decode_tx
+                .send(DecodeJob {
+                    header: header(key),
+                    tile: TileContent {
+                        uri: format!("tile{key}.glb"),
+                        state: TileState::ToLoad,
+                    },
+                    bytes: b"not a tile".to_vec(),
+                })
+                .platform_await()
+                .unwrap();
+        }
+        assert_eq!(decode_rx.len(), 3);
+        drop(decode_tx);
+
+        decode_content_worker(&cache, &mut decode_rx, &mut render_tx, options(), &status)
+            .platform_await()
+            .unwrap();
+
+        assert_eq!(decode_rx.len(), 0);
+        assert!(render_rx.try_recv().is_err());
+        // dropped tiles still count as done
+        assert_eq!(status.in_flight(), 0);
+    }
+
+    /// A b3dm around a one triangle GLB without normals, with an inline
+    /// RTC_CENTER. The triangle is upright, so its face normal isn't the
+    /// importer's stand-in for a missing one.
+    fn b3dm_tile(rtc_center: [f64; 3]) -> Vec<u8> {
+        let mut bin: Vec<u8> = [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        bin.extend([0u16, 1, 2, 0].iter().flat_map(|i| i.to_le_bytes()));
+        let json = serde_json::json!({
+            "asset": { "version": "2.0" },
+            "buffers": [{ "byteLength": bin.len() }],
+            "bufferViews": [
+                { "buffer": 0, "byteLength": 36 },
+                { "buffer": 0, "byteOffset": 36, "byteLength": 6 },
+            ],
+            "accessors": [
+                {
+                    "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                    "min": [0.0, 0.0, 0.0], "max": [1.0, 0.0, 1.0],
+                },
+                { "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" },
+            ],
+            "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 }, "indices": 1 }] }],
+            "nodes": [{ "mesh": 0 }],
+            "scenes": [{ "nodes": [0] }],
+        });
+        let mut json = serde_json::to_vec(&json).unwrap();
+        json.resize(json.len().next_multiple_of(4), b' ');
+        let glb_len = 12 + 8 + json.len() + 8 + bin.len();
+        let mut glb = b"glTF".to_vec();
+        for v in [2, glb_len, json.len()] {
+            glb.extend((v as u32).to_le_bytes());
+        }
+        glb.extend(b"JSON");
+        glb.extend(&json);
+        glb.extend((bin.len() as u32).to_le_bytes());
+        glb.extend(b"BIN\0");
+        glb.extend(&bin);
+
+        let mut feature_table = serde_json::to_vec(&serde_json::json!({
+            "BATCH_LENGTH": 0,
+            "RTC_CENTER": rtc_center,
+        }))
+        .unwrap();
+        feature_table.resize(feature_table.len().next_multiple_of(8), b' ');
+        let len = 28 + feature_table.len() + glb.len();
+        let mut out = b"b3dm".to_vec();
+        for v in [1, len, feature_table.len(), 0, 0, 0] {
+            out.extend((v as u32).to_le_bytes());
+        }
+        out.extend(&feature_table);
+        out.extend(&glb);
+        out
+    }
+
+    /// Runs `uri` through the download stage and a decode worker, returning
+    /// what reaches the renderer.
+    fn load_tile(uri: String, cache: &Arc<TilesetCache>, options: ContentOptions) -> TileState {
+        let (tx, mut rx) = channel::<TilePipelineMessage>(2);
+        let (decode_tx, mut decode_rx) = channel::<DecodeJob>(2);
+        let (mut render_tx, render_rx) = channel::<TilePipelineMessage>(2);
+        let tile = TileContent {
+            uri,
+            state: TileState::ToLoad,
+        };
+        tx.send(TilePipelineMessage::Load((header(1), tile)))
+            .platform_await()
+            .unwrap();
+        drop(tx);
+
+        let status = Arc::new(PagerStatus::default());
+        status.requested(1);
+        let context = DownloadContext {
+            client: build_client(1).unwrap(),
+            cache: cache.clone(),
+            decode_tx,
+            render_time: render_tx.clone(),
+            options,
+            status: status.clone(),
+        };
+        download_content_stage(context, &mut rx, 1)
+            .platform_await()
+            .unwrap();
+
+        // runs until the download task drops its end of the queue
+        decode_content_worker(cache, &mut decode_rx, &mut render_tx, options, &status)
+            .platform_await()
+            .unwrap();
+        assert_eq!(status.in_flight(), 0);
+
+        let Ok(TilePipelineMessage::Load((_, tile))) = render_rx.try_recv() else {
+            panic!("the tile was not decoded");
+        };
+        tile.state
+    }
+
+    #[test]
+    fn loads_b3dm_served_as_octet_stream() {
+        let body = b3dm_tile([1000.0, 2000.0, 3000.0]);
+        let (url, server) = slow_tile_server(1, "application/octet-stream; charset=binary", body);
+
+        let state = load_tile(format!("{url}/tile.b3dm"), &Arc::new(cache()), options());
+        server.join().unwrap();
+        let TileState::Decoded { nodes, .. } = state else {
+            panic!("the b3dm tile was not decoded");
+        };
+        // the RTC_CENTER lands in the node's transform
+        let origin = nodes[0]
+            .transform
+            .transform_point(Point3::new(0.0, 0.0, 0.0));
+        assert_eq!(origin, Point3::new(1000.0, 2000.0, 3000.0));
+    }
+
+    #[test]
+    fn generates_normals_for_tiles_cached_without_them() {
+        let body = b3dm_tile([0.0; 3]);
+        let (url, server) = slow_tile_server(1, "application/octet-stream", body);
+        let uri = format!("{url}/tile.b3dm");
+        let cache = Arc::new(cache());
+
+        // the position's w is 0 without a normal, 32767 with one
+        let has_normals = |state: TileState| {
+            let TileState::Decoded { meshes, .. } = state else {
+                panic!("the tile was not decoded");
+            };
+            i16::from_le_bytes([meshes[0].vertices[6], meshes[0].vertices[7]]) != 0
+        };
+
+        let mut options = ContentOptions {
+            decoded_cache: true,
+            ..options()
+        };
+        assert!(!has_normals(load_tile(uri.clone(), &cache, options)));
+        server.join().unwrap();
+
+        // the raw download is cached, so nothing more is served
+        options.normals.generate = true;
+        assert!(has_normals(load_tile(uri.clone(), &cache, options)));
+        options.normals.crease_angle = 30.0;
+        assert!(has_normals(load_tile(uri.clone(), &cache, options)));
+        options.normals.generate = false;
+        assert!(!has_normals(load_tile(uri, &cache, options)));
+    }
+}
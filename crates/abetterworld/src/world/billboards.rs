@@ -0,0 +1,115 @@
+use std::sync::Arc;
+
+use crate::world::Location;
+
+/// Names a billboard added with `World::add_billboard`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BillboardId(pub(crate) u64);
+
+/// An icon and a text label marking a position, kept facing the screen and
+/// the same size in pixels whatever the distance, unless `scale_by_distance`
+/// says otherwise.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Billboard {
+    pub position: Location,
+    /// Centered on the position.
+    pub icon: Option<Icon>,
+    pub label: Option<Label>,
+    /// Moves the icon and label on screen, in pixels with y down. Not scaled
+    /// by distance.
+    pub pixel_offset: [f32; 2],
+    pub scale_by_distance: Option<ScaleByDistance>,
+    /// Draws over the tiles instead of hiding behind them, and keeps the
+    /// label when it overlaps others'.
+    pub always_on_top: bool,
+}
+
+impl Billboard {
+    /// Nothing to draw yet, at `position`.
+    pub fn new(position: Location) -> Self {
+        Self {
+            position,
+            icon: None,
+            label: None,
+            pixel_offset: [0.0; 2],
+            scale_by_distance: None,
+            always_on_top: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Icon {
+    /// A filled circle with an optional ring around it.
+    Point {
+        /// In pixels, not counting the outline.
+        diameter: f32,
+        /// sRGB with straight alpha.
+        color: [u8; 4],
+        outline_color: [u8; 4],
+        /// In pixels, outside the diameter.
+        outline_width: f32,
+    },
+    /// An image drawn `scale` screen pixels per image pixel. Billboards
+    /// sharing an image share its place in the icon atlas.
+    Image { image: Arc<IconImage>, scale: f32 },
+}
+
+/// Pixels for `Icon::Image`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct IconImage {
+    pub width: u32,
+    pub height: u32,
+    /// sRGB with straight alpha, rows top to bottom.
+    pub rgba: Vec<u8>,
+}
+
+/// Text drawn from a signed distance field atlas of the bundled font. Lines
+/// break at '\n' and are centered on each other.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub text: String,
+    /// Em size in pixels.
+    pub size: f32,
+    /// sRGB with straight alpha.
+    pub color: [u8; 4],
+    pub outline_color: [u8; 4],
+    /// In pixels, up to a sixth of `size` or so before it's cut off.
+    pub outline_width: f32,
+    /// From the position to the middle of the text, in pixels with y down.
+    pub offset: [f32; 2],
+}
+
+impl Label {
+    /// White 16 pixel text with a thin dark outline, centered on the position.
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            size: 16.0,
+            color: [255; 4],
+            outline_color: [0, 0, 0, 255],
+            outline_width: 1.5,
+            offset: [0.0; 2],
+        }
+    }
+}
+
+/// Scales a billboard by `near_scale` up to `near` meters from the camera and
+/// by `far_scale` from `far` on, linearly in between.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScaleByDistance {
+    pub near: f64,
+    pub near_scale: f32,
+    pub far: f64,
+    pub far_scale: f32,
+}
+
+impl ScaleByDistance {
+    pub fn scale(&self, distance: f64) -> f32 {
+        if distance <= self.near || self.far <= self.near {
+            return self.near_scale;
+        }
+        let t = ((distance - self.near) / (self.far - self.near)).min(1.0) as f32;
+        self.near_scale + (self.far_scale - self.near_scale) * t
+    }
+}
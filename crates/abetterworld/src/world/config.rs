@@ -34,6 +34,227 @@ pub enum Source {
         headers: Vec<(String, String)>,
         url: String,
     },
+    /// Cesium quantized-mesh terrain, from the directory holding its
+    /// `layer.json` (or the `layer.json` itself).
+    QuantizedMesh {
+        url: String,
+    },
+}
+
+/// Where downloaded (and decoded) tiles are kept between requests.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum CacheBackendConfig {
+    /// One file per entry under `cache_dir`. Native only.
+    Filesystem,
+    /// Browser IndexedDB. Wasm only.
+    IndexedDb,
+    /// Nothing is persisted; entries are dropped least recently used first past `max_bytes`.
+    Memory { max_bytes: u64 },
+}
+
+impl Default for CacheBackendConfig {
+    fn default() -> Self {
+        #[cfg(target_arch = "wasm32")]
+        return CacheBackendConfig::IndexedDb;
+
+        #[cfg(not(target_arch = "wasm32"))]
+        return CacheBackendConfig::Filesystem;
+    }
+}
+
+/// Where mip chains for tile textures are built.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MipmapMode {
+    /// Textures keep whatever levels they were decoded with.
+    Off,
+    /// Box filtered on the decode workers; the levels go into the decoded cache too.
+    #[default]
+    Cpu,
+    /// Rendered level by level on the GPU as each texture is uploaded.
+    Gpu,
+}
+
+/// Normal generation for meshes that arrive without normals, as Google's
+/// photorealistic tiles do. Runs on the decode workers.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NormalsConfig {
+    pub generate: bool,
+    /// Faces meeting at a sharper angle than this, in degrees, keep separate normals.
+    pub crease_angle: f32,
+}
+
+impl Default for NormalsConfig {
+    fn default() -> Self {
+        Self {
+            generate: false,
+            crease_angle: 60.0,
+        }
+    }
+}
+
+/// Sizes of the two tile loading pools. Downloads wait on the network and
+/// decodes on the CPU, so each gets its own limit, joined by a bounded queue.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorkersConfig {
+    /// Tile downloads in flight at once.
+    pub download: usize,
+    /// Decode threads, one per core by default.
+    pub decode: usize,
+    /// Downloaded tiles waiting for a decode thread. Downloads stall while it is full.
+    pub decode_queue: usize,
+}
+
+impl Default for WorkersConfig {
+    fn default() -> Self {
+        let decode = num_cpus::get().max(1);
+        Self {
+            download: 12,
+            decode,
+            decode_queue: decode * 2,
+        }
+    }
+}
+
+/// Sky and aerial perspective, lit by the sun at the simulation time. Off by
+/// default, leaving the clear color behind the globe and tiles unhazed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AtmosphereConfig {
+    /// Draws the sky behind the globe. Off leaves the clear color.
+    pub enabled: bool,
+    /// Also hazes tiles by their distance through the air.
+    pub aerial_perspective: bool,
+}
+
+impl Default for AtmosphereConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            aerial_perspective: false,
+        }
+    }
+}
+
+/// Cascaded shadow maps cast by tiles in sunlight. Off by default: every
+/// cascade draws the tiles again, which costs most on mobile GPUs.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShadowsConfig {
+    pub enabled: bool,
+    /// Shadow maps splitting the view from the camera out to `distance`, 1 to 4.
+    pub cascades: u32,
+    /// Texels along each side of every cascade's map.
+    pub resolution: u32,
+    /// Meters from the camera past which nothing is shadowed.
+    pub distance: f64,
+}
+
+impl Default for ShadowsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cascades: 2,
+            resolution: 1024,
+            distance: 5_000.0,
+        }
+    }
+}
+
+/// Where an imagery layer's tiles come from. Every source is in the web
+/// mercator tiling, with rows counted from the north.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum ImagerySource {
+    /// A URL template with `{z}`, `{x}` and `{y}` placeholders.
+    Xyz {
+        url: String,
+        /// Deepest level the server has; closer views stretch its tiles.
+        #[serde(default = "default_imagery_max_zoom")]
+        max_zoom: u8,
+    },
+    /// KVP GetTile requests against a `GoogleMapsCompatible` style tile
+    /// matrix set whose matrices are named by their zoom level.
+    Wmts {
+        url: String,
+        layer: String,
+        #[serde(default = "default_wmts_style")]
+        style: String,
+        #[serde(default = "default_wmts_tile_matrix_set")]
+        tile_matrix_set: String,
+        #[serde(default = "default_wmts_format")]
+        format: String,
+        #[serde(default = "default_imagery_max_zoom")]
+        max_zoom: u8,
+    },
+    /// A local MBTiles file of PNG, JPEG or WebP tiles. Native only.
+    #[serde(rename = "mbtiles")]
+    MbTiles { path: String },
+    /// A local PMTiles v3 archive of PNG, JPEG or WebP tiles. Native only.
+    #[serde(rename = "pmtiles")]
+    PmTiles { path: String },
+}
+
+fn default_imagery_max_zoom() -> u8 {
+    19
+}
+
+fn default_wmts_style() -> String {
+    "default".to_string()
+}
+
+fn default_wmts_tile_matrix_set() -> String {
+    "GoogleMapsCompatible".to_string()
+}
+
+fn default_wmts_format() -> String {
+    "image/jpeg".to_string()
+}
+
+/// One raster layer draped over the globe.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImageryLayer {
+    pub source: ImagerySource,
+    /// 0 to 1, blended over the layers before it.
+    #[serde(default = "default_imagery_opacity")]
+    pub opacity: f32,
+}
+
+fn default_imagery_opacity() -> f32 {
+    1.0
+}
+
+/// The WGS84 ellipsoid drawn beneath the tiles, wherever they have nothing
+/// to show.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GlobeConfig {
+    pub enabled: bool,
+    /// Imagery layers, bottom first. Without any the globe is a plain color.
+    pub imagery: Vec<ImageryLayer>,
+    /// Deepest globe tile level, in web mercator zoom levels.
+    pub max_level: u8,
+}
+
+impl Default for GlobeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            imagery: Vec::new(),
+            max_level: 18,
+        }
+    }
+}
+
+fn default_max_anisotropy() -> u16 {
+    16
+}
+
+fn default_msaa_samples() -> u32 {
+    1
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -47,4 +268,32 @@ pub struct Config {
     pub debug_render_volumes: bool,
     pub debug_auto_tour: bool,
     pub tile_culling: bool,
+    /// Also cache tiles after GLB/Draco/image decoding, so revisits skip straight to GPU upload.
+    #[serde(default)]
+    pub decoded_cache: bool,
+    #[serde(default)]
+    pub cache_backend: CacheBackendConfig,
+    /// Mip generation for RGBA8 tile textures. KTX2 textures bring their own levels.
+    #[serde(default)]
+    pub mipmaps: MipmapMode,
+    /// Anisotropic filtering limit for tile textures, 1 to 16 (1 turns it off).
+    /// Ignored on adapters without anisotropic filtering.
+    #[serde(default = "default_max_anisotropy")]
+    pub max_anisotropy: u16,
+    /// Part of the decoded cache key, so changing it decodes tiles again.
+    #[serde(default)]
+    pub normals: NormalsConfig,
+    /// Tile download and decode pool sizes.
+    #[serde(default)]
+    pub workers: WorkersConfig,
+    #[serde(default)]
+    pub atmosphere: AtmosphereConfig,
+    #[serde(default)]
+    pub shadows: ShadowsConfig,
+    /// MSAA samples per pixel, off (1) by default. Counts the adapter passed
+    /// to `World::new` can't do drop to the next lower one.
+    #[serde(default = "default_msaa_samples")]
+    pub msaa_samples: u32,
+    #[serde(default)]
+    pub globe: GlobeConfig,
 }
@@ -0,0 +1,234 @@
+use serde_json::{Map, Value};
+
+use crate::{
+    helpers::AbwError,
+    world::{Location, Polygon, Polyline, VectorShape},
+};
+
+/// How `World::load_geojson` draws features, unless their simplestyle
+/// properties (`stroke`, `stroke-width`, `stroke-opacity`, `fill` and
+/// `fill-opacity`) say otherwise.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VectorStyle {
+    /// Lines and polygon outlines, sRGB with straight alpha.
+    pub stroke: [u8; 4],
+    /// In pixels; polygons get no outline at 0.
+    pub stroke_width: f32,
+    /// Polygon interiors, sRGB with straight alpha.
+    pub fill: [u8; 4],
+    pub clamp_to_ground: bool,
+}
+
+impl Default for VectorStyle {
+    fn default() -> Self {
+        Self {
+            stroke: [255, 214, 0, 255],
+            stroke_width: 2.0,
+            fill: [255, 214, 0, 96],
+            clamp_to_ground: true,
+        }
+    }
+}
+
+/// Lines and polygons for every LineString and Polygon in a GeoJSON
+/// document, including those inside Multi* geometries, collections and
+/// features, in document order. A polygon's outline follows its fill. Points
+/// have nothing to draw and are skipped.
+pub fn parse_geojson(bytes: &[u8], style: &VectorStyle) -> Result<Vec<VectorShape>, AbwError> {
+    let document: Value = serde_json::from_slice(bytes)
+        .map_err(|e| AbwError::InvalidInput(format!("GeoJSON: {e}")))?;
+    let mut shapes = Vec::new();
+    add_object(&document, style, &mut shapes)?;
+    Ok(shapes)
+}
+
+fn invalid(what: impl std::fmt::Display) -> AbwError {
+    AbwError::InvalidInput(format!("GeoJSON: {what}"))
+}
+
+fn add_object(
+    object: &Value,
+    style: &VectorStyle,
+    shapes: &mut Vec<VectorShape>,
+) -> Result<(), AbwError> {
+    let kind = object
+        .get("type")
+        .and_then(Value::as_str)
+        .ok_or_else(|| invalid("object without a type"))?;
+    let member = |name: &str| {
+        object
+            .get(name)
+            .ok_or_else(|| invalid(format!("{kind} without {name}")))
+    };
+    let array = |name: &str| {
+        member(name)?
+            .as_array()
+            .ok_or_else(|| invalid(format!("{kind} {name} isn't an array")))
+    };
+
+    match kind {
+        "FeatureCollection" => {
+            for feature in array("features")? {
+                add_object(feature, style, shapes)?;
+            }
+        }
+        "Feature" => {
+            let style = match object.get("properties").and_then(Value::as_object) {
+                Some(properties) => feature_style(properties, style),
+                None => *style,
+            };
+            match member("geometry")? {
+                Value::Null => {}
+                geometry => add_object(geometry, &style, shapes)?,
+            }
+        }
+        "GeometryCollection" => {
+            for geometry in array("geometries")? {
+                add_object(geometry, style, shapes)?;
+            }
+        }
+        "Point" | "MultiPoint" => {}
+        "LineString" => add_line(member("coordinates")?, style, shapes)?,
+        "MultiLineString" => {
+            for line in array("coordinates")? {
+                add_line(line, style, shapes)?;
+            }
+        }
+        "Polygon" => add_polygon(member("coordinates")?, style, shapes)?,
+        "MultiPolygon" => {
+            for polygon in array("coordinates")? {
+                add_polygon(polygon, style, shapes)?;
+            }
+        }
+        other => return Err(invalid(format!("unknown type {other}"))),
+    }
+    Ok(())
+}
+
+/// A position's [longitude, latitude, altitude?], altitude 0 when missing.
+fn position(value: &Value) -> Result<Location, AbwError> {
+    let numbers = value
+        .as_array()
+        .filter(|p| p.len() >= 2)
+        .and_then(|p| p.iter().map(Value::as_f64).collect::<Option<Vec<_>>>())
+        .ok_or_else(|| invalid(format!("bad position {value}")))?;
+    let altitude = numbers.get(2).copied().unwrap_or(0.0);
+    Ok(Location::Geodetic(numbers[1], numbers[0], altitude))
+}
+
+fn positions(value: &Value) -> Result<Vec<Location>, AbwError> {
+    value
+        .as_array()
+        .ok_or_else(|| invalid(format!("bad coordinates {value}")))?
+        .iter()
+        .map(position)
+        .collect()
+}
+
+fn add_line(
+    coordinates: &Value,
+    style: &VectorStyle,
+    shapes: &mut Vec<VectorShape>,
+) -> Result<(), AbwError> {
+    shapes.push(
+        Polyline {
+            positions: positions(coordinates)?,
+            width: style.stroke_width,
+            color: style.stroke,
+            clamp_to_ground: style.clamp_to_ground,
+        }
+        .into(),
+    );
+    Ok(())
+}
+
+fn add_polygon(
+    coordinates: &Value,
+    style: &VectorStyle,
+    shapes: &mut Vec<VectorShape>,
+) -> Result<(), AbwError> {
+    let mut rings = coordinates
+        .as_array()
+        .ok_or_else(|| invalid(format!("bad polygon {coordinates}")))?
+        .iter()
+        .map(positions)
+        .collect::<Result<Vec<_>, _>>()?;
+    if rings.is_empty() {
+        return Ok(());
+    }
+    let holes = rings.split_off(1);
+    let exterior = rings.pop().unwrap();
+
+    let outlines: Vec<VectorShape> = if style.stroke_width > 0.0 {
+        std::iter::once(&exterior)
+            .chain(&holes)
+            .map(|ring| {
+                Polyline {
+                    positions: ring.clone(),
+                    width: style.stroke_width,
+                    color: style.stroke,
+                    clamp_to_ground: style.clamp_to_ground,
+                }
+                .into()
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+    shapes.push(
+        Polygon {
+            exterior,
+            holes,
+            color: style.fill,
+            clamp_to_ground: style.clamp_to_ground,
+        }
+        .into(),
+    );
+    shapes.extend(outlines);
+    Ok(())
+}
+
+/// `style` with a feature's simplestyle properties applied.
+fn feature_style(properties: &Map<String, Value>, style: &VectorStyle) -> VectorStyle {
+    let mut style = *style;
+    let number = |name: &str| properties.get(name).and_then(Value::as_f64);
+    let color = |name: &str| {
+        properties
+            .get(name)
+            .and_then(Value::as_str)
+            .and_then(parse_color)
+    };
+    let opacity = |o: f64| (o.clamp(0.0, 1.0) * 255.0).round() as u8;
+
+    if let Some([r, g, b]) = color("stroke") {
+        style.stroke = [r, g, b, style.stroke[3]];
+    }
+    if let Some(o) = number("stroke-opacity") {
+        style.stroke[3] = opacity(o);
+    }
+    if let Some(width) = number("stroke-width") {
+        style.stroke_width = width.max(0.0) as f32;
+    }
+    if let Some([r, g, b]) = color("fill") {
+        style.fill = [r, g, b, style.fill[3]];
+    }
+    if let Some(o) = number("fill-opacity") {
+        style.fill[3] = opacity(o);
+    }
+    style
+}
+
+/// `#rrggbb` or `#rgb`, the # optional.
+fn parse_color(text: &str) -> Option<[u8; 3]> {
+    let hex = text.strip_prefix('#').unwrap_or(text);
+    let channel = |range: std::ops::Range<usize>| u8::from_str_radix(hex.get(range)?, 16).ok();
+    match hex.len() {
+        6 => Some([channel(0..2)?, channel(2..4)?, channel(4..6)?]),
+        3 => Some([
+            channel(0..1)? * 17,
+            channel(1..2)? * 17,
+            channel(2..3)? * 17,
+        ]),
+        _ => None,
+    }
+}
@@ -2,9 +2,24 @@ mod world;
 pub use world::*;
 
 mod config;
-pub use config::{Config, Source};
+pub use config::{
+    AtmosphereConfig, CacheBackendConfig, Config, GlobeConfig, ImageryLayer, ImagerySource,
+    MipmapMode, NormalsConfig, ShadowsConfig, Source, WorkersConfig,
+};
 mod config_loader;
 pub use config_loader::load_config;
 
+mod sun;
+pub use sun::{sun_azimuth_elevation, sun_direction_ecef, SimulationClock};
+
 mod auto_tour;
 pub use auto_tour::AutoTour;
+
+mod vectors;
+pub use vectors::{Polygon, Polyline, VectorId, VectorShape};
+
+mod geojson;
+pub use geojson::{parse_geojson, VectorStyle};
+
+mod billboards;
+pub use billboards::{Billboard, BillboardId, Icon, IconImage, Label, ScaleByDistance};
@@ -0,0 +1,90 @@
+//! Solar position from UTC, using the Astronomical Almanac's low precision
+//! formulas (good to about 0.01° between 1950 and 2050), plus the simulation
+//! clock that drives it.
+
+use cgmath::{InnerSpace, Vector3};
+use web_time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// 2000-01-01 12:00 UTC (J2000.0) as Unix seconds.
+const J2000_UNIX: f64 = 946_728_000.0;
+
+/// Seconds since the Unix epoch, negative before it.
+pub fn unix_seconds(time: SystemTime) -> f64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(after) => after.as_secs_f64(),
+        Err(before) => -before.duration().as_secs_f64(),
+    }
+}
+
+/// Inverse of [`unix_seconds`].
+pub fn from_unix_seconds(seconds: f64) -> SystemTime {
+    if seconds >= 0.0 {
+        UNIX_EPOCH + Duration::from_secs_f64(seconds)
+    } else {
+        UNIX_EPOCH - Duration::from_secs_f64(-seconds)
+    }
+}
+
+/// Unit vector from the Earth's center toward the sun, in ECEF (Z up,
+/// X through the prime meridian), at `unix_seconds` UTC.
+pub fn sun_direction_ecef(unix_seconds: f64) -> Vector3<f64> {
+    // Days since J2000.0
+    let n = (unix_seconds - J2000_UNIX) / 86_400.0;
+
+    // Ecliptic longitude from the mean longitude and mean anomaly
+    let mean_longitude = (280.460 + 0.985_647_4 * n).to_radians();
+    let mean_anomaly = (357.528 + 0.985_600_3 * n).to_radians();
+    let longitude = mean_longitude
+        + 1.915f64.to_radians() * mean_anomaly.sin()
+        + 0.020f64.to_radians() * (2.0 * mean_anomaly).sin();
+    let obliquity = (23.439 - 0.000_000_4 * n).to_radians();
+
+    // Equatorial (inertial) direction, then into the Earth's frame by the
+    // Greenwich mean sidereal angle
+    let x = longitude.cos();
+    let y = obliquity.cos() * longitude.sin();
+    let z = obliquity.sin() * longitude.sin();
+    let gmst = (280.460_618_37 + 360.985_647_366_29 * n).to_radians();
+    let (sin_g, cos_g) = gmst.sin_cos();
+
+    Vector3::new(cos_g * x + sin_g * y, -sin_g * x + cos_g * y, z).normalize()
+}
+
+/// Sun azimuth (degrees clockwise from north, 0 to 360) and elevation
+/// (degrees above the horizon) seen from a geodetic latitude and longitude.
+/// Refraction is ignored.
+pub fn sun_azimuth_elevation(unix_seconds: f64, lat_deg: f64, lon_deg: f64) -> (f64, f64) {
+    let sun = sun_direction_ecef(unix_seconds);
+    let (sin_lat, cos_lat) = lat_deg.to_radians().sin_cos();
+    let (sin_lon, cos_lon) = lon_deg.to_radians().sin_cos();
+
+    let east = Vector3::new(-sin_lon, cos_lon, 0.0);
+    let north = Vector3::new(-sin_lat * cos_lon, -sin_lat * sin_lon, cos_lat);
+    let up = Vector3::new(cos_lat * cos_lon, cos_lat * sin_lon, sin_lat);
+
+    let azimuth = sun.dot(east).atan2(sun.dot(north)).to_degrees();
+    let elevation = sun.dot(up).clamp(-1.0, 1.0).asin().to_degrees();
+    (azimuth.rem_euclid(360.0), elevation)
+}
+
+/// Simulation UTC time. Runs at wall clock speed from wherever it was last
+/// set.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SimulationClock {
+    /// Simulation minus wall clock, in seconds.
+    offset: f64,
+}
+
+impl SimulationClock {
+    pub fn now(&self) -> SystemTime {
+        from_unix_seconds(self.unix_seconds())
+    }
+
+    pub fn unix_seconds(&self) -> f64 {
+        unix_seconds(SystemTime::now()) + self.offset
+    }
+
+    pub fn set(&mut self, time: SystemTime) {
+        self.offset = unix_seconds(time) - unix_seconds(SystemTime::now());
+    }
+}
@@ -0,0 +1,72 @@
+use cgmath::Point3;
+
+use crate::{
+    helpers::{ecef_to_lla_wgs84, geodetic_to_ecef_z_up},
+    world::Location,
+};
+
+impl Location {
+    /// Earth centered, Z up.
+    pub fn to_ecef(self) -> Point3<f64> {
+        match self {
+            Location::Geodetic(lat, lon, alt) => geodetic_to_ecef_z_up(lat, lon, alt),
+            Location::Geocentric(x, y, z) => Point3::new(x, y, z),
+        }
+    }
+
+    /// Latitude and longitude in degrees and height above the ellipsoid.
+    pub fn to_geodetic(self) -> (f64, f64, f64) {
+        match self {
+            Location::Geodetic(lat, lon, alt) => (lat, lon, alt),
+            Location::Geocentric(x, y, z) => ecef_to_lla_wgs84(Point3::new(x, y, z)),
+        }
+    }
+}
+
+/// Names a shape added with `World::add_vector`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct VectorId(pub(crate) u64);
+
+/// A line through `positions`, drawn `width` pixels wide whatever the
+/// distance. Segments longer than half a degree follow the great circle.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polyline {
+    pub positions: Vec<Location>,
+    /// In pixels.
+    pub width: f32,
+    /// sRGB with straight alpha.
+    pub color: [u8; 4],
+    /// Drapes the line over the tiles and globe, ignoring the altitudes.
+    pub clamp_to_ground: bool,
+}
+
+/// A filled area. Rings may wind either way and needn't repeat their first
+/// point. Unclamped polygons are flat between their corners, so large ones
+/// cut into the globe.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polygon {
+    pub exterior: Vec<Location>,
+    pub holes: Vec<Vec<Location>>,
+    /// sRGB with straight alpha.
+    pub color: [u8; 4],
+    /// Drapes the fill over the tiles and globe, ignoring the altitudes.
+    pub clamp_to_ground: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VectorShape {
+    Polyline(Polyline),
+    Polygon(Polygon),
+}
+
+impl From<Polyline> for VectorShape {
+    fn from(line: Polyline) -> Self {
+        VectorShape::Polyline(line)
+    }
+}
+
+impl From<Polygon> for VectorShape {
+    fn from(polygon: Polygon) -> Self {
+        VectorShape::Polygon(polygon)
+    }
+}
@@ -1,29 +1,39 @@
-use cgmath::{Point3, Vector3};
-use tracing::instrument;
+use cgmath::{InnerSpace, Point3, Vector3};
+use tracing::{event, instrument, Level};
 //use tracing::instrument;
 
 use crate::{
-    cache::init_tileset_cache,
-    content::{start_pager, TileManager, TilePipelineMessage},
+    cache::{build_cache_backend, CacheBackend, CacheStats, TilesetCache},
+    content::{
+        start_pager, ContentOptions, PagerHandle, SupportedTextureEncodings, TilePipelineMessage,
+        WorkerStats,
+    },
     dynamics::{camera_config, Camera, Dynamics, InputState, PositionState},
     helpers::{
         channel::{channel, Receiver},
-        geodetic_to_ecef_z_up, hpr_to_forward_up, init_profiling, target_from_distance, AbwError,
-        FrameClock,
+        hpr_to_forward_up, init_profiling, target_from_distance, AbwError, FrameClock,
     },
     render::{
-        build_debug_pipeline, build_frustum_render, build_pipeline, import_renderables,
-        FrustumRender, RenderAndUpdate, RenderPipeline, SceneGraph,
+        build_debug_pipeline, build_frustum_render, build_pipeline, device_sample_count,
+        import_renderables, BillboardLayer, FrustumRender, ImagePipelines, OffscreenTargets,
+        RenderAndUpdate, RenderPipeline, RenderedImage, SceneGraph, TextureUploader, VectorLayer,
+    },
+    world::{
+        parse_geojson, sun_direction_ecef, Billboard, BillboardId, SimulationClock, VectorId,
+        VectorShape, VectorStyle,
     },
-    world::auto_tour,
-    AutoTour, Config,
+    AtmosphereConfig, AutoTour, Config,
 };
 use std::{sync::Arc, time::Duration};
+use web_time::SystemTime;
 
 pub struct WorldPrivate {
     pub camera: Arc<Camera>,
     pub pipeline: RenderPipeline,
-    pub surface_format: wgpu::TextureFormat,
+    pub textures: TextureUploader,
+    /// What the pipelines were built for.
+    pub surface_config: wgpu::SurfaceConfiguration,
+    pub image_pipelines: Option<ImagePipelines>,
 
     pub debug_camera: Option<Arc<Camera>>,
     pub debug_pipeline: RenderPipeline,
@@ -36,8 +46,23 @@ pub struct WorldPrivate {
     pub dynamics: Dynamics,
 
     pub clock: FrameClock,
+    /// UTC time the sun is placed for.
+    pub sim_clock: SimulationClock,
+    /// ECEF unit vector toward the sun, as last uploaded.
+    pub sun_direction: Vector3<f64>,
+    /// Re-uploads the lighting on the next update.
+    pub lighting_dirty: bool,
+    pub atmosphere: AtmosphereConfig,
+    /// Lines and polygons drawn over the tiles.
+    pub vectors: VectorLayer,
+    /// Icons and labels drawn over the vectors.
+    pub billboards: BillboardLayer,
 
     pub debug_auto_tour: Option<AutoTour>,
+
+    pub cache: Arc<TilesetCache>,
+    // stops the pager and its workers with the world
+    pager: Option<PagerHandle>,
 }
 
 pub struct World {
@@ -156,21 +181,62 @@ pub struct CameraPosition {
 // backpressure limit on new tiles per frame
 pub const MAX_NEW_TILES_PER_FRAME: usize = 4;
 
+// how often render_to_image checks whether the view has loaded
+const LOAD_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+// cosine of how far the sun may move before the lighting is re-uploaded,
+// about 0.05° or 12 s of the Earth's rotation
+const SUN_UPDATE_COS: f64 = 0.999_999_6;
+
 impl World {
-    /// Creates a new ABetterWorld.
+    /// Creates a new ABetterWorld. `adapter` is the one `device` came from;
+    /// without it `Config::msaa_samples` is limited to the counts every
+    /// device supports.
     pub fn new(
+        adapter: Option<&wgpu::Adapter>,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        texture_surface_format: wgpu::TextureFormat,
+        abw_config: &Config,
+    ) -> Self {
+        let backend = build_cache_backend(abw_config);
+        Self::new_with_cache_backend(
+            adapter,
+            device,
+            config,
+            texture_surface_format,
+            abw_config,
+            backend,
+        )
+    }
+
+    /// Creates a new ABetterWorld that stores tiles in `cache_backend` instead of the
+    /// one selected by `Config::cache_backend`.
+    pub fn new_with_cache_backend(
+        adapter: Option<&wgpu::Adapter>,
         device: &wgpu::Device,
         config: &wgpu::SurfaceConfiguration,
         texture_surface_format: wgpu::TextureFormat,
         abw_config: &Config,
+        cache_backend: Arc<dyn CacheBackend>,
     ) -> Self {
         init_profiling();
 
-        init_tileset_cache(&abw_config.cache_dir.to_string());
+        let cache = Arc::new(TilesetCache::new(cache_backend));
 
         let (camera, debug_camera_option) = camera_config(abw_config);
 
-        let pipeline = build_pipeline(device, config);
+        let sample_count =
+            device_sample_count(adapter, device, config.format, abw_config.msaa_samples);
+        if sample_count != abw_config.msaa_samples {
+            event!(
+                Level::WARN,
+                "{} MSAA samples not supported, using {}",
+                abw_config.msaa_samples,
+                sample_count
+            );
+        }
+        let pipeline = build_pipeline(device, config, abw_config.shadows, sample_count);
 
         let debug_pipeline =
             build_debug_pipeline(device, config, &pipeline.depth.as_ref().unwrap());
@@ -182,11 +248,22 @@ impl World {
 
         let (loader_tx, render_rx) = channel::<TilePipelineMessage>(MAX_NEW_TILES_PER_FRAME * 2);
 
-        let _ = start_pager(
+        let pager = start_pager(
             abw_config.source.clone(),
+            &abw_config.globe,
             Arc::clone(debug_camera_option.as_ref().unwrap_or(&camera)),
             loader_tx,
-        );
+            cache.clone(),
+            ContentOptions {
+                decoded_cache: abw_config.decoded_cache,
+                texture_encodings: SupportedTextureEncodings::from_features(device.features()),
+                mipmaps: abw_config.mipmaps,
+                normals: abw_config.normals,
+            },
+            abw_config.workers,
+        )
+        .map_err(|e| event!(Level::ERROR, "Failed to start pager: {}", e))
+        .ok();
 
         let auto_tour = if abw_config.debug_auto_tour {
             Some(AutoTour::new())
@@ -206,8 +283,18 @@ impl World {
                 frustum_render,
                 receiver: render_rx,
                 clock: FrameClock::new(std::time::Duration::from_millis(16), 0.2),
-                surface_format: texture_surface_format,
+                sim_clock: SimulationClock::default(),
+                sun_direction: sun_direction_ecef(SimulationClock::default().unix_seconds()),
+                lighting_dirty: true,
+                atmosphere: abw_config.atmosphere,
+                vectors: VectorLayer::new(),
+                billboards: BillboardLayer::new(),
+                textures: TextureUploader::new(device, texture_surface_format, abw_config),
+                surface_config: config.clone(),
+                image_pipelines: None,
                 debug_auto_tour: auto_tour,
+                cache,
+                pager,
             },
             render: RenderAndUpdate::new(),
             config: abw_config.clone(),
@@ -218,6 +305,8 @@ impl World {
         &self.private.pipeline.depth.as_ref().unwrap().view
     }
 
+    /// Depth attachment for the pass `render` records into, multisampled like
+    /// the color attachment from `get_color_attachment`.
     pub fn get_depth_attachment(&self) -> wgpu::RenderPassDepthStencilAttachment {
         self.private
             .pipeline
@@ -227,6 +316,138 @@ impl World {
             .attachment_clear()
     }
 
+    /// Color attachment for the pass `render` records into. With MSAA it
+    /// draws into the world's multisampled target and resolves to `surface`;
+    /// otherwise it draws to `surface` directly.
+    pub fn get_color_attachment<'a>(
+        &'a self,
+        surface: &'a wgpu::TextureView,
+        load: wgpu::LoadOp<wgpu::Color>,
+    ) -> wgpu::RenderPassColorAttachment<'a> {
+        match self.private.pipeline.msaa.as_ref() {
+            Some(msaa) => msaa.attachment(surface, load),
+            None => wgpu::RenderPassColorAttachment {
+                view: surface,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load,
+                    store: wgpu::StoreOp::Store,
+                },
+                depth_slice: None,
+            },
+        }
+    }
+
+    /// MSAA samples per pixel the world renders with.
+    pub fn sample_count(&self) -> u32 {
+        self.private
+            .pipeline
+            .depth
+            .as_ref()
+            .map_or(1, |depth| depth.sample_count)
+    }
+
+    pub fn cache_stats(&self) -> CacheStats {
+        self.private.cache.stats()
+    }
+
+    /// Pool sizes and queue depths of the tile download and decode workers.
+    pub fn worker_stats(&self) -> WorkerStats {
+        self.private
+            .pager
+            .as_ref()
+            .map(PagerHandle::stats)
+            .unwrap_or_default()
+    }
+
+    /// Sets the simulation UTC time the sun is placed for. It keeps running
+    /// at wall clock speed from there.
+    pub fn set_time(&mut self, time: SystemTime) {
+        self.private.sim_clock.set(time);
+        self.private.lighting_dirty = true;
+    }
+
+    /// Current simulation UTC time; the wall clock unless `set_time` was called.
+    pub fn time(&self) -> SystemTime {
+        self.private.sim_clock.now()
+    }
+
+    /// ECEF unit vector toward the sun at the simulation time.
+    pub fn sun_direction(&self) -> Vector3<f64> {
+        sun_direction_ecef(self.private.sim_clock.unix_seconds())
+    }
+
+    /// Turns the sky and aerial perspective on or off.
+    pub fn set_atmosphere(&mut self, atmosphere: AtmosphereConfig) {
+        self.private.atmosphere = atmosphere;
+        self.private.lighting_dirty = true;
+    }
+
+    pub fn atmosphere(&self) -> AtmosphereConfig {
+        self.private.atmosphere
+    }
+
+    /// Draws a line or polygon over the world from the next update on.
+    pub fn add_vector(&mut self, shape: impl Into<VectorShape>) -> VectorId {
+        self.private.vectors.add(&shape.into())
+    }
+
+    /// Replaces the shape `id` names with `shape`.
+    pub fn update_vector(
+        &mut self,
+        id: VectorId,
+        shape: impl Into<VectorShape>,
+    ) -> Result<(), AbwError> {
+        if self.private.vectors.replace(id, &shape.into()) {
+            Ok(())
+        } else {
+            Err(AbwError::InvalidInput(format!("no vector {id:?}")))
+        }
+    }
+
+    /// Stops drawing the shape `id` names, returning false if it's already gone.
+    pub fn remove_vector(&mut self, id: VectorId) -> bool {
+        self.private.vectors.remove(id)
+    }
+
+    /// Adds every line and polygon in a GeoJSON document, styled by `style`
+    /// and the features' simplestyle properties. Nothing is added if the
+    /// document can't be read.
+    pub fn load_geojson(
+        &mut self,
+        bytes: &[u8],
+        style: &VectorStyle,
+    ) -> Result<Vec<VectorId>, AbwError> {
+        let shapes = parse_geojson(bytes, style)?;
+        Ok(shapes
+            .iter()
+            .map(|shape| self.private.vectors.add(shape))
+            .collect())
+    }
+
+    /// Draws an icon and label at a position from the next update on.
+    pub fn add_billboard(&mut self, billboard: Billboard) -> BillboardId {
+        self.private.billboards.add(billboard)
+    }
+
+    /// Replaces the billboard `id` names with `billboard`.
+    pub fn update_billboard(
+        &mut self,
+        id: BillboardId,
+        billboard: Billboard,
+    ) -> Result<(), AbwError> {
+        if self.private.billboards.replace(id, billboard) {
+            Ok(())
+        } else {
+            Err(AbwError::InvalidInput(format!("no billboard {id:?}")))
+        }
+    }
+
+    /// Stops drawing the billboard `id` names, returning false if it's already gone.
+    pub fn remove_billboard(&mut self, id: BillboardId) -> bool {
+        self.private.billboards.remove(id)
+    }
+
     pub fn resize(&mut self, device: &wgpu::Device, new_width: u32, new_height: u32) {
         if new_width == 0 || new_height == 0 {
             return;
@@ -244,7 +465,10 @@ impl World {
             .camera
             .set_viewport(new_width as f64, new_height as f64);
 
-        // 3) (If using MSAA) also recreate the MSAA color target here
+        // 3) And the MSAA color target
+        if let Some(msaa) = self.private.pipeline.msaa.as_mut() {
+            msaa.resize(device, new_width, new_height);
+        }
     }
 
     #[instrument(skip(self, render_pass))]
@@ -257,6 +481,141 @@ impl World {
         )
     }
 
+    /// Whether every tile the current view needs has been loaded and
+    /// imported. Stays false while the tileset can't be reached.
+    pub fn is_view_loaded(&self) -> bool {
+        let pager_camera = self
+            .private
+            .debug_camera
+            .as_ref()
+            .unwrap_or(&self.private.camera);
+        self.private
+            .pager
+            .as_ref()
+            .is_some_and(|pager| pager.status().is_idle(pager_camera.generation()))
+            && self.private.receiver.len() == 0
+    }
+
+    /// Renders the view from `camera` into a `width` by `height` image with
+    /// targets of its own, updating first until the view has finished
+    /// loading or `timeout` runs out. Images aren't multisampled, whatever
+    /// `Config::msaa_samples` is, so depth is exact per pixel. Blocks the
+    /// calling thread, so it is native only. The camera stays at `camera`
+    /// afterwards.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn render_to_image(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        camera: CameraPosition,
+        width: u32,
+        height: u32,
+        timeout: Duration,
+    ) -> Result<RenderedImage, AbwError> {
+        if width == 0 || height == 0 {
+            return Err(AbwError::InvalidInput(format!(
+                "image size {width}x{height} is empty"
+            )));
+        }
+        let view_camera = if self.config.use_debug_camera {
+            self.private.debug_camera.clone()
+        } else {
+            None
+        }
+        .unwrap_or_else(|| self.private.camera.clone());
+
+        if self.private.image_pipelines.is_none() {
+            self.private.image_pipelines = Some(ImagePipelines::new(
+                device,
+                &self.private.surface_config,
+                self.config.shadows,
+                self.sample_count(),
+            ));
+        }
+
+        let viewport = view_camera.viewport();
+        view_camera.set_viewport(width as f64, height as f64);
+        self.set_camera_position(camera, self.config.use_debug_camera);
+        self.swap_image_pipelines();
+        let image = self.load_and_render(device, queue, width, height, timeout);
+        self.swap_image_pipelines();
+        view_camera.set_viewport(viewport.0, viewport.1);
+        image
+    }
+
+    /// Trades the world's pipelines for the single sample ones, if it has
+    /// any, and forces the next update to fill in the buffers of those
+    /// swapped in.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn swap_image_pipelines(&mut self) {
+        let private = &mut self.private;
+        let Some(images) = private.image_pipelines.as_mut() else {
+            return;
+        };
+        if let (Some(pipeline), Some(debug_pipeline)) =
+            (images.pipeline.as_mut(), images.debug_pipeline.as_mut())
+        {
+            std::mem::swap(&mut private.pipeline, pipeline);
+            std::mem::swap(&mut private.debug_pipeline, debug_pipeline);
+            private.lighting_dirty = true;
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn load_and_render(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        width: u32,
+        height: u32,
+        timeout: Duration,
+    ) -> Result<RenderedImage, AbwError> {
+        let deadline = std::time::Instant::now() + timeout;
+        let complete = loop {
+            self.update(device, queue)?;
+            if self.is_view_loaded() {
+                break true;
+            }
+            if std::time::Instant::now() >= deadline {
+                break false;
+            }
+            std::thread::sleep(LOAD_POLL_INTERVAL);
+        };
+
+        let targets =
+            OffscreenTargets::new(device, width, height, self.private.surface_config.format);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Render To Image Encoder"),
+        });
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Render To Image Pass"),
+                color_attachments: &[Some(targets.color_attachment())],
+                depth_stencil_attachment: Some(targets.depth.attachment_clear()),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            self.render(&mut render_pass)?;
+        }
+        let depth_readback = &self
+            .private
+            .image_pipelines
+            .as_ref()
+            .unwrap()
+            .depth_readback;
+        let readback = targets.encode_readback(device, &mut encoder, depth_readback);
+        queue.submit(std::iter::once(encoder.finish()));
+        let (rgba, depth) = readback.read(device)?;
+
+        Ok(RenderedImage {
+            width,
+            height,
+            rgba,
+            depth,
+            complete,
+        })
+    }
+
     #[instrument(skip(self, device, queue), fields(need_update = false))]
     pub fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) -> Result<bool, AbwError> {
         if let Some(script) = self.private.debug_auto_tour.as_mut() {
@@ -277,15 +636,15 @@ impl World {
         const BUDGET: Duration = Duration::from_millis(16);
 
         let mut needs_update = false;
-        if let Some(layout) = self.private.pipeline.texture_bind_group_layout.as_ref() {
+        if let Some(layout) = self.private.pipeline.material_bind_group_layout.as_ref() {
             needs_update = import_renderables(
                 device,
                 queue,
                 layout,
+                &self.private.textures,
                 &mut self.private.content,
                 &mut self.private.receiver,
                 BUDGET,
-                self.private.surface_format,
             )?;
         }
 
@@ -306,6 +665,21 @@ impl World {
             needs_update = true;
         }
 
+        if self.private.vectors.upload(device) {
+            needs_update = true;
+        }
+        if self.private.billboards.upload(device) {
+            needs_update = true;
+        }
+
+        let sun = self.sun_direction();
+        if std::mem::take(&mut self.private.lighting_dirty)
+            || sun.dot(self.private.sun_direction) < SUN_UPDATE_COS
+        {
+            self.private.sun_direction = sun;
+            needs_update = true;
+        }
+
         // we do not need to update anything
         if needs_update {
             self.render.update(
@@ -340,10 +714,7 @@ impl World {
         };
 
         if let Some(cam) = camera {
-            let loc = match position.location {
-                Location::Geodetic(lon, lat, alt) => geodetic_to_ecef_z_up(lon, lat, alt),
-                Location::Geocentric(x, y, z) => Point3::new(x, y, z),
-            };
+            let loc = position.location.to_ecef();
             let (target, up) = match position.orientation {
                 Orientation::HeadingPitchRoll(h, p, r) => {
                     let (f, u) = hpr_to_forward_up(h, p, r);
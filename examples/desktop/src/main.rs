@@ -55,7 +55,11 @@ impl<'window> State<'window> {
             .await
             .unwrap();
 
-        let needed_features = GpuProfiler::ALL_WGPU_TIMER_FEATURES;
+        // Compressed formats let KTX2 textures stay compressed on the GPU
+        let needed_features = GpuProfiler::ALL_WGPU_TIMER_FEATURES
+            | wgpu::Features::TEXTURE_COMPRESSION_BC
+            | wgpu::Features::TEXTURE_COMPRESSION_ETC2
+            | wgpu::Features::TEXTURE_COMPRESSION_ASTC;
 
         let adapter_features = adapter.features();
 
@@ -107,6 +111,7 @@ impl<'window> State<'window> {
         abw_config.debug_render_volumes = DEBUG_VOLUMES;
         abw_config.debug_auto_tour = DEBUG_PATH;
         let world = World::new(
+            Some(&adapter),
             &device,
             &config,
             wgpu::TextureFormat::Rgba8UnormSrgb,
@@ -161,20 +166,15 @@ impl<'window> State<'window> {
         {
             let mut rp = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.1,
-                            g: 0.2,
-                            b: 0.3,
-                            a: 1.0,
-                        }),
-                        store: wgpu::StoreOp::Store,
-                    },
-                    depth_slice: None,
-                })],
+                color_attachments: &[Some(self.world.get_color_attachment(
+                    &view,
+                    wgpu::LoadOp::Clear(wgpu::Color {
+                        r: 0.1,
+                        g: 0.2,
+                        b: 0.3,
+                        a: 1.0,
+                    }),
+                ))],
                 depth_stencil_attachment: Some(self.world.get_depth_attachment()),
                 timestamp_writes: None,
                 occlusion_query_set: None,